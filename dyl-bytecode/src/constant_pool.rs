@@ -0,0 +1,212 @@
+//! The constants of a program that are not encoded in the instructions
+//! using them, but referred to by their index in the pool.
+//!
+//! The pool is a section of the bytecode file, which comes before the
+//! instructions. Each constant is stored once, however many instructions use
+//! it.
+
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::operations::{dump_eight, dump_four, pump_eight, pump_four, pump_one, DecodingError};
+
+/// A value that can be stored in the constant pool.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Integer(i32),
+    Float(f64),
+    String(String),
+}
+
+impl Constant {
+    /// Tells whether two constants can share an entry of the pool. Floats
+    /// are compared bit by bit, so that `NaN` has an entry, and `0.0` and
+    /// `-0.0` have one each.
+    fn is_same_as(&self, other: &Constant) -> bool {
+        match (self, other) {
+            (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+            (a, b) => a == b,
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Constant::Integer(_) => 0,
+            Constant::Float(_) => 1,
+            Constant::String(_) => 2,
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        match self {
+            Constant::Integer(i) => write!(f, "{}", i),
+            Constant::Float(x) => write!(f, "{:?}", x),
+            Constant::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConstantPool(Vec<Constant>);
+
+impl ConstantPool {
+    /// Adds a constant to the pool, unless it is already there, and returns
+    /// its index. Fails if the pool is full.
+    pub fn add(&mut self, constant: Constant) -> Result<u16> {
+        if let Some(idx) = self.0.iter().position(|known| known.is_same_as(&constant)) {
+            return Ok(idx as u16);
+        }
+
+        let idx = u16::try_from(self.0.len())
+            .ok()
+            .context("The constant pool can't hold more than 65536 constants")?;
+        self.0.push(constant);
+
+        Ok(idx)
+    }
+
+    pub fn get(&self, idx: u16) -> Option<&Constant> {
+        self.0.get(idx as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Encodes the pool: the number of constants comes first, then each
+    /// constant as a tag byte followed by its value. Integers are stored on
+    /// four bytes, floats as the eight bytes of their IEEE 754 binary64
+    /// representation, and strings as their length in bytes followed by
+    /// their UTF-8 encoding. Numbers are stored most significant byte
+    /// first.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&dump_four(self.0.len() as u32));
+
+        for constant in &self.0 {
+            encoded.push(constant.tag());
+
+            match constant {
+                Constant::Integer(i) => encoded.extend_from_slice(&dump_four(*i as u32)),
+                Constant::Float(x) => encoded.extend_from_slice(&dump_eight(x.to_bits())),
+                Constant::String(s) => {
+                    encoded.extend_from_slice(&dump_four(s.len() as u32));
+                    encoded.extend_from_slice(s.as_bytes());
+                }
+            }
+        }
+
+        encoded
+    }
+
+    pub fn decode(input: &[u8]) -> Result<ConstantPool> {
+        let (pool, input) = ConstantPool::decode_section(input)?;
+        ensure!(input.is_empty(), "Some bytes have not been consumed");
+
+        Ok(pool)
+    }
+
+    /// Decodes the pool at the start of `input`, and returns the bytes that
+    /// follow it.
+    pub fn decode_section(input: &[u8]) -> Result<(ConstantPool, &[u8])> {
+        let (count, mut input) = pump_four(input).context("Failed to get constant count")?;
+        let mut constants = Vec::new();
+
+        for _ in 0..count {
+            let (tag, tail) = pump_one(input).context("Failed to get constant kind")?;
+
+            let (constant, tail) = match tag {
+                0 => {
+                    let (i, tail) = pump_four(tail).context("Failed to get integer constant")?;
+                    (Constant::Integer(i as i32), tail)
+                }
+                1 => {
+                    let (bits, tail) = pump_eight(tail).context("Failed to get float constant")?;
+                    (Constant::Float(f64::from_bits(bits)), tail)
+                }
+                2 => {
+                    let (len, tail) = pump_four(tail).context("Failed to get string length")?;
+                    let len = len as usize;
+
+                    ensure!(tail.len() >= len, DecodingError::UnexpectedEof);
+                    let (bytes, tail) = tail.split_at(len);
+                    let s = String::from_utf8(bytes.to_vec())
+                        .context("Failed to decode string constant")?;
+
+                    (Constant::String(s), tail)
+                }
+                tag => bail!("Unknown constant kind: `{}`", tag),
+            };
+
+            constants.push(constant);
+            input = tail;
+        }
+
+        Ok((ConstantPool(constants), input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_are_stored_once() {
+        let mut pool = ConstantPool::default();
+
+        assert_eq!(pool.add(Constant::String("a".to_owned())).unwrap(), 0);
+        assert_eq!(pool.add(Constant::Float(f64::NAN)).unwrap(), 1);
+        assert_eq!(pool.add(Constant::String("a".to_owned())).unwrap(), 0);
+        assert_eq!(pool.add(Constant::Float(f64::NAN)).unwrap(), 1);
+        assert_eq!(pool.add(Constant::Float(-0.0)).unwrap(), 2);
+        assert_eq!(pool.add(Constant::Float(0.0)).unwrap(), 3);
+        assert_eq!(pool.add(Constant::Integer(2)).unwrap(), 4);
+
+        assert_eq!(pool.len(), 5);
+        assert_eq!(pool.get(4), Some(&Constant::Integer(2)));
+        assert_eq!(pool.get(5), None);
+    }
+
+    #[test]
+    fn encoding() {
+        let mut pool = ConstantPool::default();
+        pool.add(Constant::Integer(-1)).unwrap();
+        pool.add(Constant::Float(1.5)).unwrap();
+        pool.add(Constant::String("hé".to_owned())).unwrap();
+
+        assert_eq!(
+            pool.encode(),
+            [
+                0, 0, 0, 3, // 3 constants
+                0, 255, 255, 255, 255, // -1
+                1, 63, 248, 0, 0, 0, 0, 0, 0, // 1.5
+                2, 0, 0, 0, 3, b'h', 0xc3, 0xa9, // "hé"
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut pool = ConstantPool::default();
+        pool.add(Constant::String("hello".to_owned())).unwrap();
+        pool.add(Constant::Integer(i32::MIN)).unwrap();
+        pool.add(Constant::Float(-2.25)).unwrap();
+
+        assert_eq!(ConstantPool::decode(&pool.encode()).unwrap(), pool);
+    }
+
+    #[test]
+    fn invalid_encodings() {
+        assert!(ConstantPool::decode(&[0, 0, 0, 1, 3]).is_err());
+        assert!(ConstantPool::decode(&[0, 0, 0, 1, 2, 0, 0, 0, 2, b'a']).is_err());
+        assert!(ConstantPool::decode(&[0, 0, 0, 0, 1]).is_err());
+    }
+}
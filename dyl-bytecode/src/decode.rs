@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 
+use crate::constant_pool::ConstantPool;
 use crate::operations::{self, AVAILABLE_DECODERS};
 use crate::{operations::DecodingError, Instruction};
 
@@ -20,6 +21,16 @@ impl Instruction {
         Ok(instrs)
     }
 
+    /// Decodes the content of a bytecode file, written by
+    /// [`Instruction::encode_with_constants`].
+    pub fn from_bytes_with_constants(input: &[u8]) -> Result<(Vec<Instruction>, ConstantPool)> {
+        let (constants, input) =
+            ConstantPool::decode_section(input).context("Failed to read constant pool")?;
+        let instructions = Instruction::from_bytes(input)?;
+
+        Ok((instructions, constants))
+    }
+
     pub fn decode(input: &[u8]) -> Result<(Instruction, usize, &[u8])> {
         let (op, input) = operations::pump_one(input)?;
 
@@ -28,3 +39,31 @@ impl Instruction {
             .ok_or(DecodingError::UnknownOpcode(op))?(input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::constant_pool::Constant;
+
+    use super::*;
+
+    #[test]
+    fn constants_come_before_the_instructions() {
+        let instructions = [Instruction::push_const(0), Instruction::f_stop()];
+        let mut constants = ConstantPool::default();
+        constants.add(Constant::String("a".to_owned())).unwrap();
+
+        let encoded = Instruction::encode_with_constants(&instructions, &constants);
+
+        assert_eq!(
+            Instruction::from_bytes_with_constants(&encoded).unwrap(),
+            (instructions.to_vec(), constants)
+        );
+    }
+
+    #[test]
+    fn truncated_constant_pool() {
+        let encoded = Instruction::encode_multiple(&[Instruction::f_stop()]);
+
+        assert!(Instruction::from_bytes_with_constants(&encoded).is_err());
+    }
+}
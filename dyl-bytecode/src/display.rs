@@ -2,7 +2,11 @@ use std::fmt::{Display, Formatter, Result as FResult};
 
 use anyhow::Result;
 
-use crate::Instruction;
+use crate::{
+    line_table::LineTable,
+    operations::{Call, CondJmp, Goto, JmpIfNotZero, JmpIfZero, MakeClosure},
+    Instruction,
+};
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter) -> FResult {
@@ -20,11 +24,74 @@ impl Display for Instruction {
             Instruction::Neg(op) => op.fmt(f),
             Instruction::Mul(op) => op.fmt(f),
             Instruction::Pop(op) => op.fmt(f),
+            Instruction::PushS(op) => op.fmt(f),
+            Instruction::Pack(op) => op.fmt(f),
+            Instruction::Field(op) => op.fmt(f),
+            Instruction::NewArray(op) => op.fmt(f),
+            Instruction::Index(op) => op.fmt(f),
+            Instruction::StoreIndex(op) => op.fmt(f),
+            Instruction::MakeClosure(op) => op.fmt(f),
+            Instruction::CallClosure(op) => op.fmt(f),
+            Instruction::Pow(op) => op.fmt(f),
+            Instruction::Out(op) => op.fmt(f),
+            Instruction::InI(op) => op.fmt(f),
+            Instruction::Trap(op) => op.fmt(f),
+            Instruction::Len(op) => op.fmt(f),
+            Instruction::Substr(op) => op.fmt(f),
+            Instruction::CharAt(op) => op.fmt(f),
+            Instruction::PushBack(op) => op.fmt(f),
+            Instruction::PopBack(op) => op.fmt(f),
+            Instruction::CheckFrame(op) => op.fmt(f),
+            Instruction::CheckOverflow(op) => op.fmt(f),
+            Instruction::SubI(op) => op.fmt(f),
+            Instruction::DivI(op) => op.fmt(f),
+            Instruction::ModI(op) => op.fmt(f),
+            Instruction::EqI(op) => op.fmt(f),
+            Instruction::NeqI(op) => op.fmt(f),
+            Instruction::LtI(op) => op.fmt(f),
+            Instruction::LeI(op) => op.fmt(f),
+            Instruction::GtI(op) => op.fmt(f),
+            Instruction::GeI(op) => op.fmt(f),
+            Instruction::JmpIfZero(op) => op.fmt(f),
+            Instruction::JmpIfNotZero(op) => op.fmt(f),
+            Instruction::Swap(op) => op.fmt(f),
+            Instruction::Nop(op) => op.fmt(f),
+            Instruction::AndI(op) => op.fmt(f),
+            Instruction::OrI(op) => op.fmt(f),
+            Instruction::XorI(op) => op.fmt(f),
+            Instruction::ShlI(op) => op.fmt(f),
+            Instruction::ShrI(op) => op.fmt(f),
+            Instruction::NotI(op) => op.fmt(f),
+            Instruction::PushF(op) => op.fmt(f),
+            Instruction::AddF(op) => op.fmt(f),
+            Instruction::SubF(op) => op.fmt(f),
+            Instruction::MulF(op) => op.fmt(f),
+            Instruction::DivF(op) => op.fmt(f),
+            Instruction::IToF(op) => op.fmt(f),
+            Instruction::FToI(op) => op.fmt(f),
+            Instruction::PushConst(op) => op.fmt(f),
+            Instruction::LoadLocal(op) => op.fmt(f),
+            Instruction::StoreLocal(op) => op.fmt(f),
         }
     }
 }
 
-pub fn disassemble(mut bytecode: &[u8]) -> Result<()> {
+pub fn disassemble(bytecode: &[u8]) -> Result<()> {
+    disassemble_with_line_table(bytecode, &LineTable::default())
+}
+
+/// Prints the instructions of a program, along with the source location of
+/// every instruction that does not come from the same code as the previous
+/// one.
+pub fn disassemble_with_line_table(bytecode: &[u8], line_table: &LineTable) -> Result<()> {
+    print!("{}", disassembly(bytecode, line_table)?);
+
+    Ok(())
+}
+
+/// Writes the instructions of a program the way `disassemble_with_line_table`
+/// prints them.
+pub fn disassembly(mut bytecode: &[u8], line_table: &LineTable) -> Result<String> {
     let mut instrs = Vec::new();
     let mut idx = 0;
     while !bytecode.is_empty() {
@@ -34,9 +101,136 @@ pub fn disassemble(mut bytecode: &[u8]) -> Result<()> {
         idx += len;
     }
 
-    for (pos, instr) in instrs {
-        println!("{:#06x}: {}", pos, instr);
+    let mut previous = None;
+    let mut disassembly = String::new();
+
+    for (n, (pos, instr)) in instrs.into_iter().enumerate() {
+        let location = line_table.location_of(n as u32);
+
+        let line = match location {
+            Some(shown) if location != previous => {
+                format!("{:#06x}: {:<24} ; {}\n", pos, instr.to_string(), shown)
+            }
+            _ => format!("{:#06x}: {}\n", pos, instr),
+        };
+        disassembly.push_str(&line);
+
+        previous = location;
     }
 
-    Ok(())
+    Ok(disassembly)
+}
+
+/// Writes a program as assembly: one instruction per line, in the same
+/// mnemonic format as the disassembler, each instruction that is the target
+/// of a jump, a call or a closure being preceded by its label. Addresses are
+/// replaced by the label they point to.
+pub fn assembly(instructions: &[Instruction]) -> String {
+    let mut targets = instructions.iter().flat_map(targets_of).collect::<Vec<_>>();
+    targets.sort_unstable();
+    targets.dedup();
+
+    let mut assembly = String::new();
+
+    for (idx, instruction) in instructions.iter().enumerate() {
+        if targets.binary_search(&(idx as u32)).is_ok() {
+            assembly.push_str(&format!("{}:\n", label(idx as u32)));
+        }
+
+        let line = match instruction {
+            Instruction::Goto(Goto(addr)) => format!("goto {}", label(*addr)),
+            Instruction::JmpIfZero(JmpIfZero(addr)) => format!("jmp_if_zero {}", label(*addr)),
+            Instruction::JmpIfNotZero(JmpIfNotZero(addr)) => {
+                format!("jmp_if_not_zero {}", label(*addr))
+            }
+            Instruction::CondJmp(CondJmp {
+                negative_addr,
+                null_addr,
+                positive_addr,
+            }) => format!(
+                "cond_jmp {} {} {}",
+                label(*negative_addr),
+                label(*null_addr),
+                label(*positive_addr)
+            ),
+            Instruction::Call(Call(addr)) => format!("call {}", label(*addr)),
+            Instruction::MakeClosure(MakeClosure {
+                addr,
+                captures,
+                arity,
+            }) => format!("make_closure {} {} {}", label(*addr), captures, arity),
+            instruction => instruction.to_string(),
+        };
+
+        assembly.push_str(&format!("    {}\n", line));
+    }
+
+    assembly
+}
+
+/// Returns the addresses an instruction may transfer control to.
+fn targets_of(instruction: &Instruction) -> Vec<u32> {
+    match instruction {
+        Instruction::Goto(Goto(addr))
+        | Instruction::JmpIfZero(JmpIfZero(addr))
+        | Instruction::JmpIfNotZero(JmpIfNotZero(addr))
+        | Instruction::Call(Call(addr)) => vec![*addr],
+        Instruction::CondJmp(jmp) => vec![jmp.negative_addr, jmp.null_addr, jmp.positive_addr],
+        Instruction::MakeClosure(closure) => vec![closure.addr],
+        _ => Vec::new(),
+    }
+}
+
+fn label(addr: u32) -> String {
+    format!("L{}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_table::SourceLocation;
+
+    use super::*;
+
+    #[test]
+    fn disassembly_shows_locations() {
+        let bytecode = Instruction::encode_multiple(&[
+            Instruction::push_i(1),
+            Instruction::push_i(2),
+            Instruction::f_stop(),
+        ]);
+        let at = |line| {
+            Some(SourceLocation {
+                file: None,
+                line,
+                column: 1,
+            })
+        };
+        let line_table = LineTable::from_locations([at(1), at(1), at(2)]);
+
+        assert_eq!(
+            disassembly(&bytecode, &line_table).unwrap(),
+            "0x0000: push_i 1                 ; 1:1\n\
+             0x0005: push_i 2\n\
+             0x000a: f_stop                   ; 2:1\n"
+        );
+    }
+
+    #[test]
+    fn assembly_labels_targets() {
+        let instructions = [
+            Instruction::push_i(1),
+            Instruction::cond_jmp(2, 3, 3),
+            Instruction::call(4),
+            Instruction::f_stop(),
+            Instruction::make_closure(0, 0, 1),
+        ];
+
+        assert_eq!(
+            assembly(&instructions),
+            "L0:\n    push_i 1\n    cond_jmp L2 L3 L3\n\
+             L2:\n    call L4\n\
+             L3:\n    f_stop\n\
+             L4:\n    make_closure L0 0 1\n"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::constant_pool::ConstantPool;
 use crate::operations::Operation;
 use crate::Instruction;
 
@@ -17,6 +18,54 @@ impl Instruction {
             Instruction::Neg(op) => op.encode(encoder),
             Instruction::Mul(op) => op.encode(encoder),
             Instruction::Pop(op) => op.encode(encoder),
+            Instruction::PushS(op) => op.encode(encoder),
+            Instruction::Pack(op) => op.encode(encoder),
+            Instruction::Field(op) => op.encode(encoder),
+            Instruction::NewArray(op) => op.encode(encoder),
+            Instruction::Index(op) => op.encode(encoder),
+            Instruction::StoreIndex(op) => op.encode(encoder),
+            Instruction::MakeClosure(op) => op.encode(encoder),
+            Instruction::CallClosure(op) => op.encode(encoder),
+            Instruction::Pow(op) => op.encode(encoder),
+            Instruction::Out(op) => op.encode(encoder),
+            Instruction::InI(op) => op.encode(encoder),
+            Instruction::Trap(op) => op.encode(encoder),
+            Instruction::Len(op) => op.encode(encoder),
+            Instruction::Substr(op) => op.encode(encoder),
+            Instruction::CharAt(op) => op.encode(encoder),
+            Instruction::PushBack(op) => op.encode(encoder),
+            Instruction::PopBack(op) => op.encode(encoder),
+            Instruction::CheckFrame(op) => op.encode(encoder),
+            Instruction::CheckOverflow(op) => op.encode(encoder),
+            Instruction::SubI(op) => op.encode(encoder),
+            Instruction::DivI(op) => op.encode(encoder),
+            Instruction::ModI(op) => op.encode(encoder),
+            Instruction::EqI(op) => op.encode(encoder),
+            Instruction::NeqI(op) => op.encode(encoder),
+            Instruction::LtI(op) => op.encode(encoder),
+            Instruction::LeI(op) => op.encode(encoder),
+            Instruction::GtI(op) => op.encode(encoder),
+            Instruction::GeI(op) => op.encode(encoder),
+            Instruction::JmpIfZero(op) => op.encode(encoder),
+            Instruction::JmpIfNotZero(op) => op.encode(encoder),
+            Instruction::Swap(op) => op.encode(encoder),
+            Instruction::Nop(op) => op.encode(encoder),
+            Instruction::AndI(op) => op.encode(encoder),
+            Instruction::OrI(op) => op.encode(encoder),
+            Instruction::XorI(op) => op.encode(encoder),
+            Instruction::ShlI(op) => op.encode(encoder),
+            Instruction::ShrI(op) => op.encode(encoder),
+            Instruction::NotI(op) => op.encode(encoder),
+            Instruction::PushF(op) => op.encode(encoder),
+            Instruction::AddF(op) => op.encode(encoder),
+            Instruction::SubF(op) => op.encode(encoder),
+            Instruction::MulF(op) => op.encode(encoder),
+            Instruction::DivF(op) => op.encode(encoder),
+            Instruction::IToF(op) => op.encode(encoder),
+            Instruction::FToI(op) => op.encode(encoder),
+            Instruction::PushConst(op) => op.encode(encoder),
+            Instruction::LoadLocal(op) => op.encode(encoder),
+            Instruction::StoreLocal(op) => op.encode(encoder),
         }
     }
 
@@ -29,6 +78,18 @@ impl Instruction {
 
         buff
     }
+
+    /// Encodes the content of a bytecode file: the constant pool the
+    /// instructions refer to, followed by the instructions.
+    pub fn encode_with_constants<'a, I>(instructions: I, constants: &ConstantPool) -> Vec<u8>
+    where
+        I: IntoIterator<Item = &'a Instruction>,
+    {
+        let mut buff = constants.encode();
+        instructions.into_iter().for_each(|i| i.encode(&mut buff));
+
+        buff
+    }
 }
 
 #[cfg(test)]
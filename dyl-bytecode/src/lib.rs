@@ -1,10 +1,16 @@
 use operations::{
-    AddI, Call, CondJmp, FStop, Goto, Mul, Neg, Pop, PopCopy, PushCopy, PushI, ResV, Ret,
+    AddF, AddI, AndI, ArithmeticOp, Call, CallClosure, CharAt, CheckFrame, CheckOverflow, CondJmp,
+    DivF, DivI, EqI, FStop, FToI, Field, GeI, Goto, GtI, IToF, InI, Index, JmpIfNotZero, JmpIfZero,
+    LeI, Len, LoadLocal, LtI, MakeClosure, ModI, Mul, MulF, Neg, NeqI, NewArray, Nop, NotI, OrI,
+    Out, Pack, Pop, PopBack, PopCopy, Pow, PushBack, PushConst, PushCopy, PushF, PushI, PushS,
+    ResV, Ret, ShlI, ShrI, StoreIndex, StoreLocal, SubF, SubI, Substr, Swap, Trap, XorI,
 };
 
+pub mod constant_pool;
 pub mod decode;
 pub mod display;
 pub mod encode;
+pub mod line_table;
 pub mod operations;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -16,7 +22,8 @@ pub enum Instruction {
     /// ```
     PushI(PushI),
 
-    /// Pops two integers from the stack, add them together, pushes the result
+    /// Pops two integers from the stack, add them together, pushes the result.
+    /// The addition wraps around on overflow.
     ///
     /// ```none
     /// a = s.pop()
@@ -86,7 +93,8 @@ pub enum Instruction {
     ///   - otherwise, jumps to the third address.
     CondJmp(CondJmp),
 
-    /// Pops an integer from the stack, negates it, and pushes it.
+    /// Pops an integer from the stack, negates it, and pushes it. Negating
+    /// `i32::MIN` wraps around to `i32::MIN`.
     ///
     /// ```none
     /// tmp = pop()
@@ -95,7 +103,7 @@ pub enum Instruction {
     Neg(Neg),
 
     /// Pops two integers from the stack, multiplies them, and pushes the
-    /// result on the stack.
+    /// result on the stack. Wraps around on overflow.
     ///
     /// ```none
     /// a = pop()
@@ -111,6 +119,486 @@ pub enum Instruction {
     ///     pop()
     /// ```
     Pop(Pop),
+
+    /// Pushes a constant string on the stack. The string is encoded in the
+    /// instruction as its length in bytes, on four bytes, followed by its
+    /// UTF-8 encoding. Strings of the constant pool are stored the same way,
+    /// and pushed with `PushConst`.
+    ///
+    /// ```none
+    /// push(s)
+    /// ```
+    PushS(PushS),
+
+    /// Pops a given amount of values from the stack, pushes a tuple
+    /// containing them, in the order they were pushed.
+    ///
+    /// ```none
+    /// values = [pop() for _ in range(n)]
+    /// push(tuple(reversed(values)))
+    /// ```
+    Pack(Pack),
+
+    /// Pops a tuple from the stack, pushes one of its elements.
+    ///
+    /// ```none
+    /// t = pop()
+    /// push(t[idx])
+    /// ```
+    Field(Field),
+
+    /// Pops a given amount of values from the stack, allocates an array
+    /// containing them on the heap, and pushes a reference to it.
+    ///
+    /// ```none
+    /// values = [pop() for _ in range(n)]
+    /// push(alloc(reversed(values)))
+    /// ```
+    NewArray(NewArray),
+
+    /// Pops an index and an array from the stack, and pushes the element
+    /// stored at this index. Fails if the index is out of bounds.
+    ///
+    /// ```none
+    /// idx = pop()
+    /// array = pop()
+    /// push(array[idx])
+    /// ```
+    Index(Index),
+
+    /// Pops a value, an index and an array from the stack, stores the value
+    /// at this index, and pushes the value back. Fails if the index is out of
+    /// bounds.
+    ///
+    /// ```none
+    /// value = pop()
+    /// idx = pop()
+    /// array = pop()
+    /// array[idx] = value
+    /// push(value)
+    /// ```
+    StoreIndex(StoreIndex),
+
+    /// Pops a given amount of values from the stack, and pushes a closure
+    /// that captures them. The closure code starts at a given address and
+    /// takes a given amount of arguments.
+    ///
+    /// ```none
+    /// env = [pop() for _ in range(captures)]
+    /// push(closure(addr, reversed(env), arity))
+    /// ```
+    MakeClosure(MakeClosure),
+
+    /// Pops a given amount of arguments and a closure from the stack, pushes
+    /// the captured values, the arguments and the current instruction
+    /// pointer, then jumps to the closure code. Fails if the closure does
+    /// not take this amount of arguments.
+    ///
+    /// ```none
+    /// args = [pop() for _ in range(n)]
+    /// c = pop()
+    /// push(*c.env)
+    /// push(*reversed(args))
+    /// push(ip)
+    /// ip = c.addr
+    /// ```
+    CallClosure(CallClosure),
+
+    /// Pops an exponent and a base from the stack, raises the base to the
+    /// power of the exponent, and pushes the result on the stack. Fails if
+    /// the exponent is negative.
+    ///
+    /// ```none
+    /// exponent = pop()
+    /// base = pop()
+    /// push(base ** exponent)
+    /// ```
+    Pow(Pow),
+
+    /// Pops a value from the stack and writes it to the output stream of
+    /// the VM. Any value can be written: integers are written in decimal,
+    /// and strings as they are, without quotes, so no variant per type is
+    /// needed.
+    ///
+    /// ```none
+    /// write(pop())
+    /// ```
+    Out(Out),
+
+    /// Reads a line containing an integer from the input stream of the VM,
+    /// and pushes the integer on the stack. The whitespace around the
+    /// integer is ignored. Reaching the end of the input, or reading a line
+    /// that is not an integer, is a runtime error.
+    ///
+    /// ```none
+    /// push(int(read_line()))
+    /// ```
+    InI(InI),
+
+    /// Pops a message from the stack, and stops the execution with an error
+    /// carrying this message.
+    ///
+    /// ```none
+    /// fail(pop())
+    /// ```
+    Trap(Trap),
+
+    /// Pops a string or an array from the stack, and pushes its length, in
+    /// characters or in elements.
+    ///
+    /// ```none
+    /// push(len(pop()))
+    /// ```
+    Len(Len),
+
+    /// Pops a length, a start index and a string from the stack, and pushes
+    /// the part of the string made of this amount of characters, starting at
+    /// the start index. Fails if the part does not fit in the string.
+    ///
+    /// ```none
+    /// length = pop()
+    /// start = pop()
+    /// s = pop()
+    /// push(s[start..start + length])
+    /// ```
+    Substr(Substr),
+
+    /// Pops an index and a string from the stack, and pushes a string made of
+    /// the character at this index. Fails if the index is out of bounds.
+    ///
+    /// ```none
+    /// idx = pop()
+    /// s = pop()
+    /// push(s[idx])
+    /// ```
+    CharAt(CharAt),
+
+    /// Pops a value and an array from the stack, and appends the value at the
+    /// end of the array.
+    ///
+    /// ```none
+    /// value = pop()
+    /// array = pop()
+    /// array.append(value)
+    /// ```
+    PushBack(PushBack),
+
+    /// Pops an array from the stack, removes its last element and pushes it.
+    /// Fails if the array is empty.
+    ///
+    /// ```none
+    /// array = pop()
+    /// push(array.remove_last())
+    /// ```
+    PopBack(PopBack),
+
+    /// Fails unless the value at a given index is a return address. The
+    /// compiler emits it to check that the stack of the current function is
+    /// laid out as it expects.
+    ///
+    /// ```none
+    /// if not is_return_address(get(idx)):
+    ///     fail()
+    /// ```
+    CheckFrame(CheckFrame),
+
+    /// Fails if an arithmetic operation on the two integers on top of the
+    /// stack would overflow. The stack is left untouched.
+    ///
+    /// ```none
+    /// b = get(0)
+    /// a = get(1)
+    /// if overflows(a op b):
+    ///     fail()
+    /// ```
+    CheckOverflow(CheckOverflow),
+
+    /// Pops two integers from the stack, subtracts the second popped from
+    /// the first popped, and pushes the result on the stack. Wraps around on
+    /// overflow.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a - b)
+    /// ```
+    SubI(SubI),
+
+    /// Pops two integers from the stack, divides the second popped by the
+    /// first popped, and pushes the quotient, rounded towards zero, on the
+    /// stack. Fails if the divisor is zero. Dividing `i32::MIN` by `-1` wraps
+    /// around to `i32::MIN`.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a / b)
+    /// ```
+    DivI(DivI),
+
+    /// Pops two integers from the stack, and pushes the remainder of the
+    /// division of the second popped by the first popped on the stack. The
+    /// remainder has the sign of the dividend. Fails if the divisor is zero.
+    /// The remainder of `i32::MIN` by `-1` is zero.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a % b)
+    /// ```
+    ModI(ModI),
+
+    /// Pops two integers from the stack, and pushes 1 if they are equal, 0
+    /// otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a == b else 0)
+    /// ```
+    EqI(EqI),
+
+    /// Pops two integers from the stack, and pushes 1 if they are different, 0
+    /// otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a != b else 0)
+    /// ```
+    NeqI(NeqI),
+
+    /// Pops two integers from the stack, and pushes 1 if the second popped
+    /// is less than the first popped, 0 otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a < b else 0)
+    /// ```
+    LtI(LtI),
+
+    /// Pops two integers from the stack, and pushes 1 if the second popped
+    /// is less than or equal to the first popped, 0 otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a <= b else 0)
+    /// ```
+    LeI(LeI),
+
+    /// Pops two integers from the stack, and pushes 1 if the second popped
+    /// is greater than the first popped, 0 otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a > b else 0)
+    /// ```
+    GtI(GtI),
+
+    /// Pops two integers from the stack, and pushes 1 if the second popped
+    /// is greater than or equal to the first popped, 0 otherwise.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(1 if a >= b else 0)
+    /// ```
+    GeI(GeI),
+
+    /// Pops an integer from the stack, and jumps to an address if it is
+    /// zero. Otherwise, continues to the next instruction.
+    ///
+    /// ```none
+    /// if pop() == 0:
+    ///     ip = ptr
+    /// ```
+    JmpIfZero(JmpIfZero),
+
+    /// Pops an integer from the stack, and jumps to an address if it is not
+    /// zero. Otherwise, continues to the next instruction.
+    ///
+    /// ```none
+    /// if pop() != 0:
+    ///     ip = ptr
+    /// ```
+    JmpIfNotZero(JmpIfNotZero),
+
+    /// Swaps the two values on top of the stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(b)
+    /// push(a)
+    /// ```
+    Swap(Swap),
+
+    /// Does nothing.
+    ///
+    /// ```none
+    /// pass
+    /// ```
+    Nop(Nop),
+
+    /// Pops two integers from the stack, and pushes their bitwise and on the
+    /// stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a & b)
+    /// ```
+    AndI(AndI),
+
+    /// Pops two integers from the stack, and pushes their bitwise or on the
+    /// stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a | b)
+    /// ```
+    OrI(OrI),
+
+    /// Pops two integers from the stack, and pushes their bitwise exclusive or on the
+    /// stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a ^ b)
+    /// ```
+    XorI(XorI),
+
+    /// Pops a shift amount and an integer from the stack, shifts the integer
+    /// to the left by the amount, and pushes the result on the stack. Only
+    /// the five lowest bits of the amount are used, so it is taken modulo 32.
+    ///
+    /// ```none
+    /// amount = pop()
+    /// a = pop()
+    /// push(a << (amount % 32))
+    /// ```
+    ShlI(ShlI),
+
+    /// Pops a shift amount and an integer from the stack, shifts the integer
+    /// to the right by the amount, and pushes the result on the stack. The
+    /// shift is arithmetic: the sign bit is copied to the left. Only the five
+    /// lowest bits of the amount are used, so it is taken modulo 32.
+    ///
+    /// ```none
+    /// amount = pop()
+    /// a = pop()
+    /// push(a >> (amount % 32))
+    /// ```
+    ShrI(ShrI),
+
+    /// Pops an integer from the stack, flips all its bits, and pushes the
+    /// result on the stack.
+    ///
+    /// ```none
+    /// push(~pop())
+    /// ```
+    NotI(NotI),
+
+    /// Pushes a floating-point number on the stack. The number is encoded as
+    /// the eight bytes of its IEEE 754 binary64 representation, most
+    /// significant byte first.
+    ///
+    /// ```none
+    /// push(f)
+    /// ```
+    PushF(PushF),
+
+    /// Pops two floating-point numbers from the stack, adds them,
+    /// and pushes the result on the stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a + b)
+    /// ```
+    AddF(AddF),
+
+    /// Pops two floating-point numbers from the stack, subtracts the first
+    /// popped from the second popped, and pushes the result on the stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a - b)
+    /// ```
+    SubF(SubF),
+
+    /// Pops two floating-point numbers from the stack, multiplies them,
+    /// and pushes the result on the stack.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a * b)
+    /// ```
+    MulF(MulF),
+
+    /// Pops two floating-point numbers from the stack, divides the second
+    /// popped by the first popped, and pushes the result on the stack.
+    /// Dividing by zero gives an infinity, or NaN for zero by zero, as in
+    /// IEEE 754.
+    ///
+    /// ```none
+    /// b = pop()
+    /// a = pop()
+    /// push(a / b)
+    /// ```
+    DivF(DivF),
+
+    /// Pops an integer from the stack, and pushes it as a floating-point
+    /// number. The conversion is exact.
+    ///
+    /// ```none
+    /// push(float(pop()))
+    /// ```
+    IToF(IToF),
+
+    /// Pops a floating-point number from the stack, and pushes it as an
+    /// integer, rounded towards zero. Numbers out of the range of integers are
+    /// clamped to the nearest bound, and NaN becomes 0.
+    ///
+    /// ```none
+    /// push(int(pop()))
+    /// ```
+    FToI(FToI),
+
+    /// Pushes a constant of the constant pool on the stack, given its index in
+    /// the pool.
+    ///
+    /// ```none
+    /// push(constants[idx])
+    /// ```
+    PushConst(PushConst),
+
+    /// Pushes a copy of a slot of the current call frame on the stack. Slots
+    /// are indexed from the base of the frame: the first value pushed after
+    /// the return address, or the bottom of the stack outside of any
+    /// function. The arguments and the return value slot lie below the
+    /// return address, at negative indices: `-2` is the last argument.
+    ///
+    /// ```none
+    /// push(stack[base + idx])
+    /// ```
+    LoadLocal(LoadLocal),
+
+    /// Pops a value from the stack, and stores it in a slot of the current
+    /// call frame, indexed like in `LoadLocal`.
+    ///
+    /// ```none
+    /// v = pop()
+    /// stack[base + idx] = v
+    /// ```
+    StoreLocal(StoreLocal),
 }
 
 impl Instruction {
@@ -174,6 +662,203 @@ impl Instruction {
     pub fn pop(idx: u16) -> Instruction {
         Pop(idx).into()
     }
+
+    pub fn push_s(s: impl Into<String>) -> Instruction {
+        PushS(s.into()).into()
+    }
+
+    pub fn pack(len: u16) -> Instruction {
+        Pack(len).into()
+    }
+
+    pub fn field(idx: u16) -> Instruction {
+        Field(idx).into()
+    }
+
+    pub fn new_array(len: u16) -> Instruction {
+        NewArray(len).into()
+    }
+
+    pub fn index() -> Instruction {
+        Index.into()
+    }
+
+    pub fn store_index() -> Instruction {
+        StoreIndex.into()
+    }
+
+    pub fn make_closure(addr: u32, captures: u16, arity: u16) -> Instruction {
+        MakeClosure {
+            addr,
+            captures,
+            arity,
+        }
+        .into()
+    }
+
+    pub fn call_closure(arity: u16) -> Instruction {
+        CallClosure(arity).into()
+    }
+
+    pub fn pow() -> Instruction {
+        Pow.into()
+    }
+
+    pub fn out() -> Instruction {
+        Out.into()
+    }
+
+    pub fn in_i() -> Instruction {
+        InI.into()
+    }
+
+    pub fn trap() -> Instruction {
+        Trap.into()
+    }
+
+    pub fn len() -> Instruction {
+        Len.into()
+    }
+
+    pub fn substr() -> Instruction {
+        Substr.into()
+    }
+
+    pub fn char_at() -> Instruction {
+        CharAt.into()
+    }
+
+    pub fn push_back() -> Instruction {
+        PushBack.into()
+    }
+
+    pub fn pop_back() -> Instruction {
+        PopBack.into()
+    }
+
+    pub fn check_frame(idx: u16) -> Instruction {
+        CheckFrame(idx).into()
+    }
+
+    pub fn check_overflow(op: ArithmeticOp) -> Instruction {
+        CheckOverflow(op).into()
+    }
+
+    pub fn sub_i() -> Instruction {
+        SubI.into()
+    }
+
+    pub fn div_i() -> Instruction {
+        DivI.into()
+    }
+
+    pub fn mod_i() -> Instruction {
+        ModI.into()
+    }
+
+    pub fn eq_i() -> Instruction {
+        EqI.into()
+    }
+
+    pub fn neq_i() -> Instruction {
+        NeqI.into()
+    }
+
+    pub fn lt_i() -> Instruction {
+        LtI.into()
+    }
+
+    pub fn le_i() -> Instruction {
+        LeI.into()
+    }
+
+    pub fn gt_i() -> Instruction {
+        GtI.into()
+    }
+
+    pub fn ge_i() -> Instruction {
+        GeI.into()
+    }
+
+    pub fn jmp_if_zero(addr: u32) -> Instruction {
+        JmpIfZero(addr).into()
+    }
+
+    pub fn jmp_if_not_zero(addr: u32) -> Instruction {
+        JmpIfNotZero(addr).into()
+    }
+
+    pub fn swap() -> Instruction {
+        Swap.into()
+    }
+
+    pub fn nop() -> Instruction {
+        Nop.into()
+    }
+
+    pub fn and_i() -> Instruction {
+        AndI.into()
+    }
+
+    pub fn or_i() -> Instruction {
+        OrI.into()
+    }
+
+    pub fn xor_i() -> Instruction {
+        XorI.into()
+    }
+
+    pub fn shl_i() -> Instruction {
+        ShlI.into()
+    }
+
+    pub fn shr_i() -> Instruction {
+        ShrI.into()
+    }
+
+    pub fn not_i() -> Instruction {
+        NotI.into()
+    }
+
+    pub fn push_f(f: f64) -> Instruction {
+        PushF(f).into()
+    }
+
+    pub fn add_f() -> Instruction {
+        AddF.into()
+    }
+
+    pub fn sub_f() -> Instruction {
+        SubF.into()
+    }
+
+    pub fn mul_f() -> Instruction {
+        MulF.into()
+    }
+
+    pub fn div_f() -> Instruction {
+        DivF.into()
+    }
+
+    pub fn i_to_f() -> Instruction {
+        IToF.into()
+    }
+
+    pub fn f_to_i() -> Instruction {
+        FToI.into()
+    }
+
+    pub fn push_const(idx: u16) -> Instruction {
+        PushConst(idx).into()
+    }
+
+    pub fn load_local(idx: i16) -> Instruction {
+        LoadLocal(idx).into()
+    }
+
+    pub fn store_local(idx: i16) -> Instruction {
+        StoreLocal(idx).into()
+    }
 }
 
 macro_rules! impl_from_operation {
@@ -188,4 +873,4 @@ macro_rules! impl_from_operation {
     };
 }
 
-impl_from_operation! { PushI, AddI, FStop, PushCopy, Call, Ret, ResV, PopCopy, Goto, CondJmp, Neg, Mul, Pop }
+impl_from_operation! { PushI, AddI, FStop, PushCopy, Call, Ret, ResV, PopCopy, Goto, CondJmp, Neg, Mul, Pop, PushS, Pack, Field, NewArray, Index, StoreIndex, MakeClosure, CallClosure, Pow, Out, InI, Trap, Len, Substr, CharAt, PushBack, PopBack, CheckFrame, CheckOverflow, SubI, DivI, ModI, EqI, NeqI, LtI, LeI, GtI, GeI, JmpIfZero, JmpIfNotZero, Swap, Nop, AndI, OrI, XorI, ShlI, ShrI, NotI, PushF, AddF, SubF, MulF, DivF, IToF, FToI, PushConst, LoadLocal, StoreLocal }
@@ -0,0 +1,213 @@
+//! Maps the instructions of a program to the source code they come from, so
+//! that runtime errors and the disassembler can tell where an instruction
+//! was written.
+
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use anyhow::{ensure, Context, Result};
+
+use crate::operations::{dump_four, pump_four, DecodingError};
+
+/// A place in the source code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    /// The file the code comes from, if any.
+    pub file: Option<String>,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The source location of each instruction of a program, which some
+/// instructions do not have.
+///
+/// Consecutive instructions usually come from the same code, so only the
+/// instructions whose location differs from the previous one are stored,
+/// along with their index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineTable(Vec<(u32, Option<SourceLocation>)>);
+
+impl LineTable {
+    /// Creates the table of a program, given the location of each of its
+    /// instructions, in order.
+    pub fn from_locations<I>(locations: I) -> LineTable
+    where
+        I: IntoIterator<Item = Option<SourceLocation>>,
+    {
+        let mut entries: Vec<(u32, Option<SourceLocation>)> = Vec::new();
+
+        for (idx, location) in locations.into_iter().enumerate() {
+            let is_same = match entries.last() {
+                Some((_, previous)) => *previous == location,
+                None => location.is_none(),
+            };
+
+            if !is_same {
+                entries.push((idx as u32, location));
+            }
+        }
+
+        LineTable(entries)
+    }
+
+    /// Returns where the instruction at a given index comes from.
+    pub fn location_of(&self, idx: u32) -> Option<&SourceLocation> {
+        let following = self.0.partition_point(|(start, _)| *start <= idx);
+
+        self.0
+            .get(following.checked_sub(1)?)
+            .and_then(|(_, location)| location.as_ref())
+    }
+
+    /// Encodes the table: the file names come first, each as its length and
+    /// its bytes, then the entries, each as the index of an instruction, the
+    /// index of its file plus one (zero meaning no file), its line and its
+    /// column. A line of zero means that there is no location.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut files = Vec::new();
+        let mut entries = Vec::new();
+
+        for (idx, location) in &self.0 {
+            let (file, line, column) = match location {
+                Some(location) => {
+                    let file = location.file.as_ref().map_or(0, |file| {
+                        match files.iter().position(|known| known == file) {
+                            Some(known) => known + 1,
+                            None => {
+                                files.push(file.clone());
+                                files.len()
+                            }
+                        }
+                    });
+
+                    (file as u32, location.line, location.column)
+                }
+                None => (0, 0, 0),
+            };
+
+            [*idx, file, line, column]
+                .iter()
+                .for_each(|value| entries.extend_from_slice(&dump_four(*value)));
+        }
+
+        let mut encoded = Vec::new();
+
+        encoded.extend_from_slice(&dump_four(files.len() as u32));
+        for file in &files {
+            encoded.extend_from_slice(&dump_four(file.len() as u32));
+            encoded.extend_from_slice(file.as_bytes());
+        }
+
+        encoded.extend_from_slice(&dump_four(self.0.len() as u32));
+        encoded.extend(entries);
+
+        encoded
+    }
+
+    pub fn decode(input: &[u8]) -> Result<LineTable> {
+        let (file_count, mut input) = pump_four(input).context("Failed to get file count")?;
+        let mut files = Vec::new();
+
+        for _ in 0..file_count {
+            let (len, tail) = pump_four(input).context("Failed to get file name length")?;
+            let len = len as usize;
+
+            ensure!(tail.len() >= len, DecodingError::UnexpectedEof);
+            let (name, tail) = tail.split_at(len);
+
+            files.push(String::from_utf8(name.to_vec()).context("Failed to decode file name")?);
+            input = tail;
+        }
+
+        let (entry_count, mut input) = pump_four(input).context("Failed to get entry count")?;
+        let mut entries = Vec::new();
+
+        for _ in 0..entry_count {
+            let mut values = [0; 4];
+            for value in &mut values {
+                (*value, input) = pump_four(input).context("Failed to get line table entry")?;
+            }
+
+            let [idx, file, line, column] = values;
+
+            let file = match file {
+                0 => None,
+                file => Some(
+                    files
+                        .get(file as usize - 1)
+                        .context("Unknown file in line table")?
+                        .clone(),
+                ),
+            };
+
+            let location = (line > 0).then_some(SourceLocation { file, line, column });
+            entries.push((idx, location));
+        }
+
+        ensure!(input.is_empty(), "Some bytes have not been consumed");
+
+        Ok(LineTable(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(file: &str, line: u32, column: u32) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            file: Some(file.to_owned()),
+            line,
+            column,
+        })
+    }
+
+    #[test]
+    fn locations() {
+        let table = LineTable::from_locations([
+            None,
+            at("a.dyl", 1, 5),
+            at("a.dyl", 1, 5),
+            at("b.dyl", 2, 1),
+            None,
+        ]);
+
+        assert_eq!(table.location_of(0), None);
+        assert_eq!(table.location_of(1), at("a.dyl", 1, 5).as_ref());
+        assert_eq!(table.location_of(2), at("a.dyl", 1, 5).as_ref());
+        assert_eq!(table.location_of(3), at("b.dyl", 2, 1).as_ref());
+        assert_eq!(table.location_of(4), None);
+        assert_eq!(table.location_of(5), None);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let table = LineTable::from_locations([
+            at("a.dyl", 1, 5),
+            at("b.dyl", 2, 1),
+            None,
+            at("a.dyl", 3, 2),
+            Some(SourceLocation {
+                file: None,
+                line: 4,
+                column: 1,
+            }),
+        ]);
+
+        assert_eq!(LineTable::decode(&table.encode()).unwrap(), table);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(at("a.dyl", 1, 5).unwrap().to_string(), "a.dyl:1:5");
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    convert::TryFrom,
     error::Error,
     fmt::{Display, Formatter, Result as FResult},
 };
@@ -7,7 +8,7 @@ use anyhow::{anyhow, ensure, Context, Result};
 
 use crate::Instruction;
 
-pub(crate) const AVAILABLE_DECODERS: [Decoder; 13] = [
+pub(crate) const AVAILABLE_DECODERS: [Decoder; 61] = [
     PushI::decode_and_wrap,
     AddI::decode_and_wrap,
     FStop::decode_and_wrap,
@@ -21,6 +22,54 @@ pub(crate) const AVAILABLE_DECODERS: [Decoder; 13] = [
     Neg::decode_and_wrap,
     Mul::decode_and_wrap,
     Pop::decode_and_wrap,
+    PushS::decode_and_wrap,
+    Pack::decode_and_wrap,
+    Field::decode_and_wrap,
+    NewArray::decode_and_wrap,
+    Index::decode_and_wrap,
+    StoreIndex::decode_and_wrap,
+    MakeClosure::decode_and_wrap,
+    CallClosure::decode_and_wrap,
+    Pow::decode_and_wrap,
+    Out::decode_and_wrap,
+    InI::decode_and_wrap,
+    Trap::decode_and_wrap,
+    Len::decode_and_wrap,
+    Substr::decode_and_wrap,
+    CharAt::decode_and_wrap,
+    PushBack::decode_and_wrap,
+    PopBack::decode_and_wrap,
+    CheckFrame::decode_and_wrap,
+    CheckOverflow::decode_and_wrap,
+    SubI::decode_and_wrap,
+    DivI::decode_and_wrap,
+    ModI::decode_and_wrap,
+    EqI::decode_and_wrap,
+    NeqI::decode_and_wrap,
+    LtI::decode_and_wrap,
+    LeI::decode_and_wrap,
+    GtI::decode_and_wrap,
+    GeI::decode_and_wrap,
+    JmpIfZero::decode_and_wrap,
+    JmpIfNotZero::decode_and_wrap,
+    Swap::decode_and_wrap,
+    Nop::decode_and_wrap,
+    AndI::decode_and_wrap,
+    OrI::decode_and_wrap,
+    XorI::decode_and_wrap,
+    ShlI::decode_and_wrap,
+    ShrI::decode_and_wrap,
+    NotI::decode_and_wrap,
+    PushF::decode_and_wrap,
+    AddF::decode_and_wrap,
+    SubF::decode_and_wrap,
+    MulF::decode_and_wrap,
+    DivF::decode_and_wrap,
+    IToF::decode_and_wrap,
+    FToI::decode_and_wrap,
+    PushConst::decode_and_wrap,
+    LoadLocal::decode_and_wrap,
+    StoreLocal::decode_and_wrap,
 ];
 
 pub(crate) type Decoder = fn(&[u8]) -> Result<(Instruction, usize, &[u8])>;
@@ -38,6 +87,7 @@ pub(crate) trait Operation: Sized + Into<Instruction> {
             .map(|(op, tail)| (op.into(), Self::SIZE, tail))
     }
 
+    #[allow(dead_code)]
     fn decode_single_with_opcode(input: &[u8]) -> Result<Self> {
         let (opcode, tail) = pump_one(input).context("Failed to get opcode value")?;
         ensure!(opcode == Self::ID as u8, "Incorrect opcode");
@@ -432,6 +482,1320 @@ impl Display for Pop {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct PushS(pub String);
+
+impl Operation for PushS {
+    const ID: usize = next_id![Pop];
+    // The string bytes follow the opcode and the length, so this is only the
+    // size of an empty string.
+    const SIZE: usize = 5;
+    const DISPLAY_NAME: &'static str = "push_s";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (len, tail) = pump_four(input).context("Failed to get string length")?;
+        let len = len as usize;
+
+        ensure!(tail.len() >= len, DecodingError::UnexpectedEof);
+        let (bytes, tail) = tail.split_at(len);
+
+        let content = String::from_utf8(bytes.to_vec()).context("Failed to decode string")?;
+        let instr = PushS(content);
+
+        Ok((instr, tail))
+    }
+
+    fn decode_and_wrap(input: &[u8]) -> Result<(Instruction, usize, &[u8])> {
+        Self::decode(input)
+            .with_context(|| format!("Failed to decode `{}`", Self::DISPLAY_NAME))
+            .map(|(op, tail)| {
+                let size = Self::SIZE + op.0.len();
+                (op.into(), size, tail)
+            })
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_four(self.0.len() as u32));
+        encoder.extend_from_slice(self.0.as_bytes());
+    }
+}
+
+impl Display for PushS {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "push_s {:?}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pack(pub u16);
+
+impl Operation for Pack {
+    const ID: usize = next_id![PushS];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "pack";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (len, tail) = pump_two(input).context("Failed to get amount of values to pack")?;
+        let instr = Pack(len);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for Pack {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "pack {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Field(pub u16);
+
+impl Operation for Field {
+    const ID: usize = next_id![Pack];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "field";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (idx, tail) = pump_two(input).context("Failed to get field index")?;
+        let instr = Field(idx);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "field {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NewArray(pub u16);
+
+impl Operation for NewArray {
+    const ID: usize = next_id![Field];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "new_array";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (len, tail) = pump_two(input).context("Failed to get array length")?;
+        let instr = NewArray(len);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for NewArray {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "new_array {}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Index;
+
+impl Operation for Index {
+    const ID: usize = next_id![NewArray];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "index";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Index;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Index {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "index")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoreIndex;
+
+impl Operation for StoreIndex {
+    const ID: usize = next_id![Index];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "store_index";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = StoreIndex;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for StoreIndex {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "store_index")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MakeClosure {
+    pub addr: u32,
+    pub captures: u16,
+    pub arity: u16,
+}
+
+impl Operation for MakeClosure {
+    const ID: usize = next_id![StoreIndex];
+    const SIZE: usize = 9;
+    const DISPLAY_NAME: &'static str = "make_closure";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (addr, input) = pump_four(input).context("Failed to get closure address")?;
+        let (captures, input) =
+            pump_two(input).context("Failed to get amount of captured values")?;
+        let (arity, input) = pump_two(input).context("Failed to get closure arity")?;
+        let instr = MakeClosure {
+            addr,
+            captures,
+            arity,
+        };
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_four(self.addr));
+        encoder.extend_from_slice(&dump_two(self.captures));
+        encoder.extend_from_slice(&dump_two(self.arity));
+    }
+}
+
+impl Display for MakeClosure {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(
+            f,
+            "make_closure {} {} {}",
+            self.addr, self.captures, self.arity
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CallClosure(pub u16);
+
+impl Operation for CallClosure {
+    const ID: usize = next_id![MakeClosure];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "call_closure";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (arity, input) = pump_two(input).context("Failed to get amount of arguments")?;
+        let instr = CallClosure(arity);
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for CallClosure {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        write!(f, "call_closure {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pow;
+
+impl Operation for Pow {
+    const ID: usize = next_id![CallClosure];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "pow";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Pow;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Pow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "pow")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Out;
+
+impl Operation for Out {
+    const ID: usize = next_id![Pow];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "out";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Out;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Out {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "out")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InI;
+
+impl Operation for InI {
+    const ID: usize = next_id![Out];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "in_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = InI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for InI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "in_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Trap;
+
+impl Operation for Trap {
+    const ID: usize = next_id![InI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "trap";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Trap;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "trap")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Len;
+
+impl Operation for Len {
+    const ID: usize = next_id![Trap];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "len";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Len;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Len {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "len")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Substr;
+
+impl Operation for Substr {
+    const ID: usize = next_id![Len];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "substr";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Substr;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Substr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "substr")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CharAt;
+
+impl Operation for CharAt {
+    const ID: usize = next_id![Substr];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "char_at";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = CharAt;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for CharAt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "char_at")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PushBack;
+
+impl Operation for PushBack {
+    const ID: usize = next_id![CharAt];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "push_back";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = PushBack;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for PushBack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "push_back")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PopBack;
+
+impl Operation for PopBack {
+    const ID: usize = next_id![PushBack];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "pop_back";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = PopBack;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for PopBack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "pop_back")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckFrame(pub u16);
+
+impl Operation for CheckFrame {
+    const ID: usize = next_id![PopBack];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "check_frame";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (offset, input) =
+            pump_two(input).context("Failed to get stack offset of the return address")?;
+        let instr = CheckFrame(offset);
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for CheckFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "check_frame {}", self.0)
+    }
+}
+
+/// An arithmetic operation on two integers, whose result may not fit in an
+/// integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Pow,
+}
+
+impl ArithmeticOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Sub => "-",
+            ArithmeticOp::Mul => "*",
+            ArithmeticOp::Pow => "**",
+        }
+    }
+}
+
+impl TryFrom<u8> for ArithmeticOp {
+    type Error = anyhow::Error;
+
+    fn try_from(id: u8) -> Result<ArithmeticOp> {
+        match id {
+            0 => Ok(ArithmeticOp::Add),
+            1 => Ok(ArithmeticOp::Sub),
+            2 => Ok(ArithmeticOp::Mul),
+            3 => Ok(ArithmeticOp::Pow),
+            _ => Err(anyhow!("Unknown arithmetic operation: `{}`", id)),
+        }
+    }
+}
+
+impl Display for ArithmeticOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        let name = match self {
+            ArithmeticOp::Add => "add",
+            ArithmeticOp::Sub => "sub",
+            ArithmeticOp::Mul => "mul",
+            ArithmeticOp::Pow => "pow",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckOverflow(pub ArithmeticOp);
+
+impl Operation for CheckOverflow {
+    const ID: usize = next_id![CheckFrame];
+    const SIZE: usize = 2;
+    const DISPLAY_NAME: &'static str = "check_overflow";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (id, input) = pump_one(input).context("Failed to get arithmetic operation")?;
+        let instr = CheckOverflow(ArithmeticOp::try_from(id)?);
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_one(self.0 as u8));
+    }
+}
+
+impl Display for CheckOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "check_overflow {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SubI;
+
+impl Operation for SubI {
+    const ID: usize = next_id![CheckOverflow];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "sub_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = SubI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for SubI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "sub_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DivI;
+
+impl Operation for DivI {
+    const ID: usize = next_id![SubI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "div_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = DivI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for DivI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "div_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ModI;
+
+impl Operation for ModI {
+    const ID: usize = next_id![DivI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "mod_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = ModI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for ModI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "mod_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EqI;
+
+impl Operation for EqI {
+    const ID: usize = next_id![ModI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "eq_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = EqI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for EqI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "eq_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NeqI;
+
+impl Operation for NeqI {
+    const ID: usize = next_id![EqI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "neq_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = NeqI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for NeqI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "neq_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LtI;
+
+impl Operation for LtI {
+    const ID: usize = next_id![NeqI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "lt_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = LtI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for LtI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "lt_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LeI;
+
+impl Operation for LeI {
+    const ID: usize = next_id![LtI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "le_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = LeI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for LeI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "le_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GtI;
+
+impl Operation for GtI {
+    const ID: usize = next_id![LeI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "gt_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = GtI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for GtI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "gt_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GeI;
+
+impl Operation for GeI {
+    const ID: usize = next_id![GtI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "ge_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = GeI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for GeI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "ge_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JmpIfZero(pub u32);
+
+impl Operation for JmpIfZero {
+    const ID: usize = next_id![GeI];
+    const SIZE: usize = 5;
+    const DISPLAY_NAME: &'static str = "jmp_if_zero";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (addr, rest) =
+            pump_four(input).context("Failed to get conditional jump destination")?;
+        let instr = JmpIfZero(addr);
+
+        Ok((instr, rest))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_four(self.0));
+    }
+}
+
+impl Display for JmpIfZero {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "jmp_if_zero {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JmpIfNotZero(pub u32);
+
+impl Operation for JmpIfNotZero {
+    const ID: usize = next_id![JmpIfZero];
+    const SIZE: usize = 5;
+    const DISPLAY_NAME: &'static str = "jmp_if_not_zero";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (addr, rest) =
+            pump_four(input).context("Failed to get conditional jump destination")?;
+        let instr = JmpIfNotZero(addr);
+
+        Ok((instr, rest))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_four(self.0));
+    }
+}
+
+impl Display for JmpIfNotZero {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "jmp_if_not_zero {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Swap;
+
+impl Operation for Swap {
+    const ID: usize = next_id![JmpIfNotZero];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "swap";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Swap;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Swap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "swap")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Nop;
+
+impl Operation for Nop {
+    const ID: usize = next_id![Swap];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "nop";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = Nop;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for Nop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "nop")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AndI;
+
+impl Operation for AndI {
+    const ID: usize = next_id![Nop];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "and_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = AndI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for AndI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "and_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrI;
+
+impl Operation for OrI {
+    const ID: usize = next_id![AndI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "or_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = OrI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for OrI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "or_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct XorI;
+
+impl Operation for XorI {
+    const ID: usize = next_id![OrI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "xor_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = XorI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for XorI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "xor_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShlI;
+
+impl Operation for ShlI {
+    const ID: usize = next_id![XorI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "shl_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = ShlI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for ShlI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "shl_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShrI;
+
+impl Operation for ShrI {
+    const ID: usize = next_id![ShlI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "shr_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = ShrI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for ShrI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "shr_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NotI;
+
+impl Operation for NotI {
+    const ID: usize = next_id![ShrI];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "not_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = NotI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for NotI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "not_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PushF(pub f64);
+
+impl Operation for PushF {
+    const ID: usize = next_id![NotI];
+    const SIZE: usize = 9;
+    const DISPLAY_NAME: &'static str = "push_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (bits, input) = pump_eight(input).context("Failed to get float to push")?;
+        let instr = PushF(f64::from_bits(bits));
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_eight(self.0.to_bits()));
+    }
+}
+
+impl Display for PushF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "push_f {:?}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AddF;
+
+impl Operation for AddF {
+    const ID: usize = next_id![PushF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "add_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = AddF;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for AddF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "add_f")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SubF;
+
+impl Operation for SubF {
+    const ID: usize = next_id![AddF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "sub_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = SubF;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for SubF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "sub_f")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MulF;
+
+impl Operation for MulF {
+    const ID: usize = next_id![SubF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "mul_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = MulF;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for MulF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "mul_f")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DivF;
+
+impl Operation for DivF {
+    const ID: usize = next_id![MulF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "div_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = DivF;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for DivF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "div_f")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IToF;
+
+impl Operation for IToF {
+    const ID: usize = next_id![DivF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "i_to_f";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = IToF;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for IToF {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "i_to_f")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FToI;
+
+impl Operation for FToI {
+    const ID: usize = next_id![IToF];
+    const SIZE: usize = 1;
+    const DISPLAY_NAME: &'static str = "f_to_i";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let instr = FToI;
+
+        Ok((instr, input))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+    }
+}
+
+impl Display for FToI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "f_to_i")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PushConst(pub u16);
+
+impl Operation for PushConst {
+    const ID: usize = next_id![FToI];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "push_const";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (idx, tail) = pump_two(input).context("Failed to get constant index")?;
+        let instr = PushConst(idx);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0));
+    }
+}
+
+impl Display for PushConst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "push_const {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoadLocal(pub i16);
+
+impl Operation for LoadLocal {
+    const ID: usize = next_id![PushConst];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "load_local";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (idx, tail) = pump_two(input).context("Failed to get local index")?;
+        let instr = LoadLocal(idx as i16);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0 as u16));
+    }
+}
+
+impl Display for LoadLocal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "load_local {}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StoreLocal(pub i16);
+
+impl Operation for StoreLocal {
+    const ID: usize = next_id![LoadLocal];
+    const SIZE: usize = 3;
+    const DISPLAY_NAME: &'static str = "store_local";
+
+    fn decode(input: &[u8]) -> Result<(Self, &[u8])> {
+        let (idx, tail) = pump_two(input).context("Failed to get local index")?;
+        let instr = StoreLocal(idx as i16);
+
+        Ok((instr, tail))
+    }
+
+    fn encode(&self, encoder: &mut Vec<u8>) {
+        encoder.push(Self::ID as u8);
+        encoder.extend_from_slice(&dump_two(self.0 as u16));
+    }
+}
+
+impl Display for StoreLocal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "store_local {}", self.0)
+    }
+}
+
 pub(crate) fn pump_one(input: &[u8]) -> Result<(u8, &[u8])> {
     match input {
         [fst, rest @ ..] => Ok((*fst, rest)),
@@ -441,372 +1805,1275 @@ pub(crate) fn pump_one(input: &[u8]) -> Result<(u8, &[u8])> {
     }
 }
 
-pub fn dump_one(input: u8) -> [u8; 1] {
-    [input]
-}
+pub fn dump_one(input: u8) -> [u8; 1] {
+    [input]
+}
+
+fn pump_two(input: &[u8]) -> Result<(u16, &[u8])> {
+    match input {
+        [fst, snd, rest @ ..] => {
+            let val = u16::from_be_bytes([*fst, *snd]);
+            Ok((val, rest))
+        }
+        _ => {
+            Err(anyhow!(DecodingError::UnexpectedEof)).context("Failed to get two bytes from input")
+        }
+    }
+}
+
+fn dump_two(input: u16) -> [u8; 2] {
+    input.to_be_bytes()
+}
+
+pub(crate) fn pump_four(input: &[u8]) -> Result<(u32, &[u8])> {
+    match input {
+        [fst, snd, trd, fth, rest @ ..] => {
+            let val = u32::from_be_bytes([*fst, *snd, *trd, *fth]);
+            Ok((val, rest))
+        }
+        _ => Err(anyhow!(DecodingError::UnexpectedEof))
+            .context("Failed to get four bytes from input"),
+    }
+}
+
+pub(crate) fn dump_four(input: u32) -> [u8; 4] {
+    input.to_be_bytes()
+}
+
+pub(crate) fn pump_eight(input: &[u8]) -> Result<(u64, &[u8])> {
+    match input {
+        [b0, b1, b2, b3, b4, b5, b6, b7, rest @ ..] => {
+            let val = u64::from_be_bytes([*b0, *b1, *b2, *b3, *b4, *b5, *b6, *b7]);
+            Ok((val, rest))
+        }
+        _ => Err(anyhow!(DecodingError::UnexpectedEof))
+            .context("Failed to get eight bytes from input"),
+    }
+}
+
+pub(crate) fn dump_eight(input: u64) -> [u8; 8] {
+    input.to_be_bytes()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodingError {
+    UnknownOpcode(u8),
+    UnexpectedEof,
+}
+
+impl Display for DecodingError {
+    fn fmt(&self, f: &mut Formatter) -> FResult {
+        match self {
+            DecodingError::UnexpectedEof => write!(f, "Unexpected EOF"),
+            DecodingError::UnknownOpcode(id) => write!(f, "Unknown opcode: `{}`", id),
+        }
+    }
+}
+
+impl Error for DecodingError {}
+
+#[cfg(test)]
+mod id_tests {
+    use super::*;
+
+    macro_rules! assert_correct_id {
+        ($ty:ident) => {
+            assert_eq!(
+                AVAILABLE_DECODERS[$ty::ID] as usize,
+                $ty::decode_and_wrap as Decoder as usize
+            );
+        };
+    }
+
+    #[test]
+    fn decoder_id_matches_operation_id() {
+        assert_correct_id!(PushI);
+        assert_correct_id!(AddI);
+        assert_correct_id!(FStop);
+        assert_correct_id!(PushCopy);
+        assert_correct_id!(Call);
+        assert_correct_id!(Ret);
+        assert_correct_id!(ResV);
+        assert_correct_id!(PopCopy);
+        assert_correct_id!(Goto);
+        assert_correct_id!(CondJmp);
+        assert_correct_id!(Neg);
+        assert_correct_id!(Mul);
+        assert_correct_id!(Pop);
+        assert_correct_id!(PushS);
+        assert_correct_id!(Pack);
+        assert_correct_id!(Field);
+        assert_correct_id!(NewArray);
+        assert_correct_id!(Index);
+        assert_correct_id!(StoreIndex);
+        assert_correct_id!(MakeClosure);
+        assert_correct_id!(CallClosure);
+        assert_correct_id!(Pow);
+        assert_correct_id!(Out);
+        assert_correct_id!(InI);
+        assert_correct_id!(Trap);
+        assert_correct_id!(Len);
+        assert_correct_id!(Substr);
+        assert_correct_id!(CharAt);
+        assert_correct_id!(PushBack);
+        assert_correct_id!(PopBack);
+        assert_correct_id!(CheckFrame);
+        assert_correct_id!(CheckOverflow);
+        assert_correct_id!(SubI);
+        assert_correct_id!(DivI);
+        assert_correct_id!(ModI);
+        assert_correct_id!(EqI);
+        assert_correct_id!(NeqI);
+        assert_correct_id!(LtI);
+        assert_correct_id!(LeI);
+        assert_correct_id!(GtI);
+        assert_correct_id!(GeI);
+        assert_correct_id!(JmpIfZero);
+        assert_correct_id!(JmpIfNotZero);
+        assert_correct_id!(Swap);
+        assert_correct_id!(Nop);
+        assert_correct_id!(AndI);
+        assert_correct_id!(OrI);
+        assert_correct_id!(XorI);
+        assert_correct_id!(ShlI);
+        assert_correct_id!(ShrI);
+        assert_correct_id!(NotI);
+        assert_correct_id!(PushF);
+        assert_correct_id!(AddF);
+        assert_correct_id!(SubF);
+        assert_correct_id!(MulF);
+        assert_correct_id!(DivF);
+        assert_correct_id!(IToF);
+        assert_correct_id!(FToI);
+        assert_correct_id!(PushConst);
+        assert_correct_id!(LoadLocal);
+        assert_correct_id!(StoreLocal);
+    }
+}
+
+#[cfg(test)]
+macro_rules! test_encoding {
+    ( $( $instr:expr => $bytecode:expr )* $(,)? ) => {
+        #[test]
+        fn encoding() {
+            $(
+                assert_eq!(encode($instr), $bytecode);
+
+                let mut generated_bytecode = Vec::new();
+                Instruction::from($instr).encode(&mut generated_bytecode);
+
+                assert_eq!(generated_bytecode, $bytecode);
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+macro_rules! test_symmetry {
+    ($operation:ident, $instr:expr , $bytecode:expr $(,)? ) => {
+        #[test]
+        fn symmetry() {
+            assert_eq!(
+                $operation::decode_single_with_opcode(&encode($instr)).unwrap(),
+                $instr
+            );
+            assert_eq!(
+                encode($operation::decode_single_with_opcode(&$bytecode).unwrap()),
+                $bytecode
+            );
+        }
+    };
+}
+
+#[cfg(test)]
+macro_rules! test_display {
+    ( $( $instr:expr => $display:literal ),* $(,)? ) => {
+        #[test]
+        fn display() {
+            $(
+                assert_eq!($instr.to_string(), $display);
+                assert_eq!(Instruction::from($instr).to_string(), $display);
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+fn encode(instr: impl Operation) -> Vec<u8> {
+    let mut tmp = Vec::new();
+    instr.encode(&mut tmp);
+    tmp
+}
+
+#[cfg(test)]
+mod push_i {
+    use super::*;
+
+    test_encoding! {
+        PushI(42) => [0, 0, 0, 0, 42],
+    }
+
+    test_symmetry! {
+        PushI, PushI(42), [0, 0, 0, 0, 42],
+    }
+
+    test_display! {
+        PushI(42) => "push_i 42",
+        PushI(101) => "push_i 101",
+    }
+}
+
+#[cfg(test)]
+mod add_i {
+    use super::*;
+
+    test_encoding! {
+        AddI => [1],
+    }
+
+    test_symmetry! {
+        AddI, AddI, [1],
+    }
+
+    test_display! {
+        AddI => "add_i",
+    }
+}
+
+#[cfg(test)]
+mod f_stop {
+    use super::*;
+
+    test_encoding! {
+        FStop => [2],
+    }
+
+    test_symmetry! {
+        FStop, FStop, [2],
+    }
+
+    test_display! {
+        FStop => "f_stop",
+    }
+}
+
+#[cfg(test)]
+mod push_copy {
+    use super::*;
+
+    test_encoding! {
+        PushCopy(300) => [3, 1, 44]
+    }
+
+    test_symmetry! {
+        PushCopy, PushCopy(300), [3, 1, 44],
+    }
+
+    test_display! {
+        PushCopy(3) => "push_copy 3",
+        PushCopy(11) => "push_copy 11",
+    }
+}
+
+#[cfg(test)]
+mod call {
+    use super::*;
+
+    test_encoding! {
+        Call(247) => [4, 0, 0, 0, 247],
+    }
+
+    test_symmetry! {
+        Call, Call(247), [4, 0, 0, 0, 247],
+    }
+
+    test_display! {
+        Call(1010) => "call 1010",
+        Call(12) => "call 12",
+    }
+}
+
+#[cfg(test)]
+mod ret {
+    use super::*;
+
+    test_encoding! {
+        Ret { shrink_offset: 2, ip_offset: 4 } => [5, 0, 2, 0, 4],
+    }
+
+    test_symmetry! {
+        Ret,
+        Ret { shrink_offset: 2, ip_offset: 4 },
+        [5, 0, 2, 0, 4],
+    }
+
+    test_display! {
+        Ret { shrink_offset: 100, ip_offset: 34 } => "ret 100 34",
+        Ret { shrink_offset: 10, ip_offset: 4 } => "ret 10 4",
+    }
+}
+
+#[cfg(test)]
+mod res_v {
+    use super::*;
+
+    test_encoding! {
+        ResV(22) => [6, 0, 22],
+    }
+
+    test_symmetry! {
+        ResV, ResV(101), [6, 0, 101],
+    }
+
+    test_display! {
+        ResV(10) => "res_v 10"
+    }
+}
+
+#[cfg(test)]
+mod pop_cpy {
+    use super::*;
+
+    test_encoding! {
+        PopCopy(32) => [7, 0, 32],
+    }
+
+    test_symmetry! {
+        PopCopy, PopCopy(13), [7, 0, 32],
+    }
+
+    test_display! {
+        PopCopy(12) => "pop_copy 12",
+        PopCopy(2) => "pop_copy 2",
+    }
+}
+
+#[cfg(test)]
+mod goto {
+    use super::*;
+
+    test_encoding! {
+        Goto(444) => [8, 0, 0, 1, 188],
+    }
+
+    test_symmetry! {
+        Goto, Goto(10), [8, 0, 0, 0, 10],
+    }
+
+    test_display! {
+        Goto(1337) => "goto 1337",
+        Goto(2503) => "goto 2503",
+    }
+}
+
+#[cfg(test)]
+mod cond_jmp {
+    use super::*;
+
+    test_encoding! {
+        CondJmp { negative_addr: 101, null_addr: 69, positive_addr: 13 }
+        =>
+        [9, 0, 0, 0, 101, 0, 0, 0, 69, 0, 0, 0, 13],
+    }
+
+    test_symmetry! {
+        CondJmp,
+        CondJmp { negative_addr: 101, null_addr: 69, positive_addr: 42 },
+        [9, 0, 0, 0, 101, 0, 0, 0, 69, 0, 0, 0, 42],
+    }
+
+    test_display! {
+        CondJmp { negative_addr: 1221, null_addr: 92, positive_addr: 218 } => "cond_jmp 1221 92 218",
+    }
+}
+
+#[cfg(test)]
+mod neg {
+    use super::*;
+
+    test_encoding! {
+        Neg => [10],
+    }
+
+    test_symmetry! {
+        Neg, Neg, [10],
+    }
+
+    test_display! {
+        Neg => "neg",
+    }
+}
+
+#[cfg(test)]
+mod mul {
+    use super::*;
+
+    test_encoding! {
+        Mul => [11],
+    }
+
+    test_symmetry! {
+        Mul, Mul, [11],
+    }
+
+    test_display! {
+        Mul => "mul",
+    }
+}
+
+#[cfg(test)]
+mod pop {
+    use super::*;
+
+    test_encoding! {
+        Pop(10) => [12, 0, 10],
+    }
+
+    test_symmetry! {
+        Pop, Pop(32), [12, 0, 32],
+    }
+
+    test_display! {
+        Pop(111) => "pop 111",
+    }
+}
+
+#[cfg(test)]
+mod push_s {
+    use super::*;
+
+    test_encoding! {
+        PushS("hi".to_owned()) => [13, 0, 0, 0, 2, b'h', b'i'],
+    }
+
+    test_symmetry! {
+        PushS, PushS("dyl".to_owned()), [13, 0, 0, 0, 3, b'd', b'y', b'l'],
+    }
+
+    test_display! {
+        PushS("hello\n".to_owned()) => "push_s \"hello\\n\"",
+    }
+
+    #[test]
+    fn decoded_size_includes_string() {
+        let (_, size, tail) = PushS::decode_and_wrap(&[0, 0, 0, 2, b'h', b'i', 1]).unwrap();
+
+        assert_eq!(size, 7);
+        assert_eq!(tail, [1]);
+    }
+
+    #[test]
+    fn truncated_string_fails() {
+        assert!(PushS::decode(&[0, 0, 0, 3, b'h', b'i']).is_err());
+    }
+}
+
+#[cfg(test)]
+mod pack {
+    use super::*;
+
+    test_encoding! {
+        Pack(3) => [14, 0, 3],
+    }
+
+    test_symmetry! {
+        Pack, Pack(258), [14, 1, 2],
+    }
+
+    test_display! {
+        Pack(2) => "pack 2",
+    }
+}
+
+#[cfg(test)]
+mod field {
+    use super::*;
+
+    test_encoding! {
+        Field(1) => [15, 0, 1],
+    }
+
+    test_symmetry! {
+        Field, Field(4), [15, 0, 4],
+    }
+
+    test_display! {
+        Field(0) => "field 0",
+    }
+}
+
+#[cfg(test)]
+mod new_array {
+    use super::*;
+
+    test_encoding! {
+        NewArray(3) => [16, 0, 3],
+    }
+
+    test_symmetry! {
+        NewArray, NewArray(0), [16, 0, 0],
+    }
+
+    test_display! {
+        NewArray(5) => "new_array 5",
+    }
+}
+
+#[cfg(test)]
+mod index {
+    use super::*;
+
+    test_encoding! {
+        Index => [17],
+    }
+
+    test_symmetry! {
+        Index, Index, [17],
+    }
+
+    test_display! {
+        Index => "index",
+    }
+}
+
+#[cfg(test)]
+mod store_index {
+    use super::*;
+
+    test_encoding! {
+        StoreIndex => [18],
+    }
+
+    test_symmetry! {
+        StoreIndex, StoreIndex, [18],
+    }
+
+    test_display! {
+        StoreIndex => "store_index",
+    }
+}
+
+#[cfg(test)]
+mod make_closure {
+    use super::*;
+
+    test_encoding! {
+        MakeClosure { addr: 300, captures: 2, arity: 1 } => [19, 0, 0, 1, 44, 0, 2, 0, 1],
+    }
+
+    test_symmetry! {
+        MakeClosure,
+        MakeClosure { addr: 12, captures: 0, arity: 3 },
+        [19, 0, 0, 0, 12, 0, 0, 0, 3],
+    }
+
+    test_display! {
+        MakeClosure { addr: 42, captures: 1, arity: 2 } => "make_closure 42 1 2",
+    }
+}
+
+#[cfg(test)]
+mod call_closure {
+    use super::*;
+
+    test_encoding! {
+        CallClosure(2) => [20, 0, 2],
+    }
+
+    test_symmetry! {
+        CallClosure, CallClosure(1), [20, 0, 1],
+    }
+
+    test_display! {
+        CallClosure(3) => "call_closure 3",
+    }
+}
+
+#[cfg(test)]
+mod pow {
+    use super::*;
+
+    test_encoding! {
+        Pow => [21],
+    }
+
+    test_symmetry! {
+        Pow, Pow, [21],
+    }
+
+    test_display! {
+        Pow => "pow",
+    }
+}
+
+#[cfg(test)]
+mod out {
+    use super::*;
+
+    test_encoding! {
+        Out => [22],
+    }
+
+    test_symmetry! {
+        Out, Out, [22],
+    }
+
+    test_display! {
+        Out => "out",
+    }
+}
+
+#[cfg(test)]
+mod in_i {
+    use super::*;
+
+    test_encoding! {
+        InI => [23],
+    }
+
+    test_symmetry! {
+        InI, InI, [23],
+    }
+
+    test_display! {
+        InI => "in_i",
+    }
+}
+
+#[cfg(test)]
+mod trap {
+    use super::*;
+
+    test_encoding! {
+        Trap => [24],
+    }
+
+    test_symmetry! {
+        Trap, Trap, [24],
+    }
+
+    test_display! {
+        Trap => "trap",
+    }
+}
+
+#[cfg(test)]
+mod len {
+    use super::*;
+
+    test_encoding! {
+        Len => [25],
+    }
+
+    test_symmetry! {
+        Len, Len, [25],
+    }
+
+    test_display! {
+        Len => "len",
+    }
+}
+
+#[cfg(test)]
+mod substr {
+    use super::*;
+
+    test_encoding! {
+        Substr => [26],
+    }
+
+    test_symmetry! {
+        Substr, Substr, [26],
+    }
+
+    test_display! {
+        Substr => "substr",
+    }
+}
+
+#[cfg(test)]
+mod char_at {
+    use super::*;
+
+    test_encoding! {
+        CharAt => [27],
+    }
+
+    test_symmetry! {
+        CharAt, CharAt, [27],
+    }
+
+    test_display! {
+        CharAt => "char_at",
+    }
+}
+
+#[cfg(test)]
+mod push_back {
+    use super::*;
+
+    test_encoding! {
+        PushBack => [28],
+    }
+
+    test_symmetry! {
+        PushBack, PushBack, [28],
+    }
+
+    test_display! {
+        PushBack => "push_back",
+    }
+}
+
+#[cfg(test)]
+mod pop_back {
+    use super::*;
+
+    test_encoding! {
+        PopBack => [29],
+    }
+
+    test_symmetry! {
+        PopBack, PopBack, [29],
+    }
+
+    test_display! {
+        PopBack => "pop_back",
+    }
+}
+
+#[cfg(test)]
+mod check_frame {
+    use super::*;
+
+    test_encoding! {
+        CheckFrame(3) => [30, 0, 3],
+    }
+
+    test_symmetry! {
+        CheckFrame, CheckFrame(3), [30, 0, 3],
+    }
+
+    test_display! {
+        CheckFrame(3) => "check_frame 3",
+    }
+}
+
+#[cfg(test)]
+mod check_overflow {
+    use super::*;
+
+    test_encoding! {
+        CheckOverflow(ArithmeticOp::Pow) => [31, 3],
+    }
+
+    test_symmetry! {
+        CheckOverflow, CheckOverflow(ArithmeticOp::Mul), [31, 2],
+    }
+
+    test_display! {
+        CheckOverflow(ArithmeticOp::Sub) => "check_overflow sub",
+    }
+
+    #[test]
+    fn unknown_operation() {
+        assert!(CheckOverflow::decode_single_with_opcode(&[31, 4]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod sub_i {
+    use super::*;
+
+    test_encoding! {
+        SubI => [32],
+    }
+
+    test_symmetry! {
+        SubI, SubI, [32],
+    }
+
+    test_display! {
+        SubI => "sub_i",
+    }
+}
+
+#[cfg(test)]
+mod div_i {
+    use super::*;
+
+    test_encoding! {
+        DivI => [33],
+    }
+
+    test_symmetry! {
+        DivI, DivI, [33],
+    }
+
+    test_display! {
+        DivI => "div_i",
+    }
+}
+
+#[cfg(test)]
+mod mod_i {
+    use super::*;
+
+    test_encoding! {
+        ModI => [34],
+    }
+
+    test_symmetry! {
+        ModI, ModI, [34],
+    }
+
+    test_display! {
+        ModI => "mod_i",
+    }
+}
+
+#[cfg(test)]
+mod eq_i {
+    use super::*;
+
+    test_encoding! {
+        EqI => [35],
+    }
+
+    test_symmetry! {
+        EqI, EqI, [35],
+    }
+
+    test_display! {
+        EqI => "eq_i",
+    }
+}
+
+#[cfg(test)]
+mod neq_i {
+    use super::*;
+
+    test_encoding! {
+        NeqI => [36],
+    }
+
+    test_symmetry! {
+        NeqI, NeqI, [36],
+    }
+
+    test_display! {
+        NeqI => "neq_i",
+    }
+}
+
+#[cfg(test)]
+mod lt_i {
+    use super::*;
+
+    test_encoding! {
+        LtI => [37],
+    }
+
+    test_symmetry! {
+        LtI, LtI, [37],
+    }
+
+    test_display! {
+        LtI => "lt_i",
+    }
+}
+
+#[cfg(test)]
+mod le_i {
+    use super::*;
 
-fn pump_two(input: &[u8]) -> Result<(u16, &[u8])> {
-    match input {
-        [fst, snd, rest @ ..] => {
-            let val = u16::from_be_bytes([*fst, *snd]);
-            Ok((val, rest))
-        }
-        _ => {
-            Err(anyhow!(DecodingError::UnexpectedEof)).context("Failed to get two bytes from input")
-        }
+    test_encoding! {
+        LeI => [38],
     }
-}
 
-fn dump_two(input: u16) -> [u8; 2] {
-    input.to_be_bytes()
-}
+    test_symmetry! {
+        LeI, LeI, [38],
+    }
 
-fn pump_four(input: &[u8]) -> Result<(u32, &[u8])> {
-    match input {
-        [fst, snd, trd, fth, rest @ ..] => {
-            let val = u32::from_be_bytes([*fst, *snd, *trd, *fth]);
-            Ok((val, rest))
-        }
-        _ => Err(anyhow!(DecodingError::UnexpectedEof))
-            .context("Failed to get four bytes from input"),
+    test_display! {
+        LeI => "le_i",
     }
 }
 
-fn dump_four(input: u32) -> [u8; 4] {
-    input.to_be_bytes()
+#[cfg(test)]
+mod gt_i {
+    use super::*;
+
+    test_encoding! {
+        GtI => [39],
+    }
+
+    test_symmetry! {
+        GtI, GtI, [39],
+    }
+
+    test_display! {
+        GtI => "gt_i",
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum DecodingError {
-    UnknownOpcode(u8),
-    UnexpectedEof,
+#[cfg(test)]
+mod ge_i {
+    use super::*;
+
+    test_encoding! {
+        GeI => [40],
+    }
+
+    test_symmetry! {
+        GeI, GeI, [40],
+    }
+
+    test_display! {
+        GeI => "ge_i",
+    }
 }
 
-impl Display for DecodingError {
-    fn fmt(&self, f: &mut Formatter) -> FResult {
-        match self {
-            DecodingError::UnexpectedEof => write!(f, "Unexpected EOF"),
-            DecodingError::UnknownOpcode(id) => write!(f, "Unknown opcode: `{}`", id),
-        }
+#[cfg(test)]
+mod jmp_if_zero {
+    use super::*;
+
+    test_encoding! {
+        JmpIfZero(444) => [41, 0, 0, 1, 188],
+    }
+
+    test_symmetry! {
+        JmpIfZero, JmpIfZero(10), [41, 0, 0, 0, 10],
+    }
+
+    test_display! {
+        JmpIfZero(1337) => "jmp_if_zero 1337",
     }
 }
 
-impl Error for DecodingError {}
+#[cfg(test)]
+mod jmp_if_not_zero {
+    use super::*;
+
+    test_encoding! {
+        JmpIfNotZero(444) => [42, 0, 0, 1, 188],
+    }
+
+    test_symmetry! {
+        JmpIfNotZero, JmpIfNotZero(10), [42, 0, 0, 0, 10],
+    }
+
+    test_display! {
+        JmpIfNotZero(1337) => "jmp_if_not_zero 1337",
+    }
+}
 
 #[cfg(test)]
-mod id_tests {
+mod swap {
     use super::*;
 
-    macro_rules! assert_correct_id {
-        ($ty:ident) => {
-            assert_eq!(
-                AVAILABLE_DECODERS[$ty::ID] as usize,
-                $ty::decode_and_wrap as usize
-            );
-        };
+    test_encoding! {
+        Swap => [43],
     }
 
-    #[test]
-    fn decoder_id_matches_operation_id() {
-        assert_correct_id!(PushI);
-        assert_correct_id!(AddI);
-        assert_correct_id!(FStop);
-        assert_correct_id!(PushCopy);
-        assert_correct_id!(Call);
-        assert_correct_id!(Ret);
-        assert_correct_id!(ResV);
-        assert_correct_id!(PopCopy);
-        assert_correct_id!(Goto);
-        assert_correct_id!(CondJmp);
-        assert_correct_id!(Neg);
-        assert_correct_id!(Mul);
-        assert_correct_id!(Pop);
+    test_symmetry! {
+        Swap, Swap, [43],
+    }
+
+    test_display! {
+        Swap => "swap",
     }
 }
 
 #[cfg(test)]
-macro_rules! test_encoding {
-    ( $( $instr:expr => $bytecode:expr )* $(,)? ) => {
-        #[test]
-        fn encoding() {
-            $(
-                assert_eq!(encode($instr), $bytecode);
+mod nop {
+    use super::*;
 
-                let mut generated_bytecode = Vec::new();
-                Instruction::from($instr).encode(&mut generated_bytecode);
+    test_encoding! {
+        Nop => [44],
+    }
 
-                assert_eq!(generated_bytecode, $bytecode);
-            )*
-        }
-    };
+    test_symmetry! {
+        Nop, Nop, [44],
+    }
+
+    test_display! {
+        Nop => "nop",
+    }
 }
 
 #[cfg(test)]
-macro_rules! test_symmetry {
-    ($operation:ident, $instr:expr , $bytecode:expr $(,)? ) => {
-        #[test]
-        fn symmetry() {
-            assert_eq!(
-                $operation::decode_single_with_opcode(&encode($instr)).unwrap(),
-                $instr
-            );
-            assert_eq!(
-                encode($operation::decode_single_with_opcode(&$bytecode).unwrap()),
-                $bytecode
-            );
-        }
-    };
+mod and_i {
+    use super::*;
+
+    test_encoding! {
+        AndI => [45],
+    }
+
+    test_symmetry! {
+        AndI, AndI, [45],
+    }
+
+    test_display! {
+        AndI => "and_i",
+    }
 }
 
 #[cfg(test)]
-macro_rules! test_display {
-    ( $( $instr:expr => $display:literal ),* $(,)? ) => {
-        #[test]
-        fn display() {
-            $(
-                assert_eq!($instr.to_string(), $display);
-                assert_eq!(Instruction::from($instr).to_string(), $display);
-            )*
-        }
-    };
+mod or_i {
+    use super::*;
+
+    test_encoding! {
+        OrI => [46],
+    }
+
+    test_symmetry! {
+        OrI, OrI, [46],
+    }
+
+    test_display! {
+        OrI => "or_i",
+    }
 }
 
 #[cfg(test)]
-fn encode(instr: impl Operation) -> Vec<u8> {
-    let mut tmp = Vec::new();
-    instr.encode(&mut tmp);
-    tmp
+mod xor_i {
+    use super::*;
+
+    test_encoding! {
+        XorI => [47],
+    }
+
+    test_symmetry! {
+        XorI, XorI, [47],
+    }
+
+    test_display! {
+        XorI => "xor_i",
+    }
 }
 
 #[cfg(test)]
-mod push_i {
+mod shl_i {
     use super::*;
 
     test_encoding! {
-        PushI(42) => [0, 0, 0, 0, 42],
+        ShlI => [48],
     }
 
     test_symmetry! {
-        PushI, PushI(42), [0, 0, 0, 0, 42],
+        ShlI, ShlI, [48],
     }
 
     test_display! {
-        PushI(42) => "push_i 42",
-        PushI(101) => "push_i 101",
+        ShlI => "shl_i",
     }
 }
 
 #[cfg(test)]
-mod add_i {
+mod shr_i {
     use super::*;
 
     test_encoding! {
-        AddI => [1],
+        ShrI => [49],
     }
 
     test_symmetry! {
-        AddI, AddI, [1],
+        ShrI, ShrI, [49],
     }
 
     test_display! {
-        AddI => "add_i",
+        ShrI => "shr_i",
     }
 }
 
 #[cfg(test)]
-mod f_stop {
+mod not_i {
     use super::*;
 
     test_encoding! {
-        FStop => [2],
+        NotI => [50],
     }
 
     test_symmetry! {
-        FStop, FStop, [2],
+        NotI, NotI, [50],
     }
 
     test_display! {
-        FStop => "f_stop",
+        NotI => "not_i",
     }
 }
 
 #[cfg(test)]
-mod push_copy {
+mod push_f {
     use super::*;
 
     test_encoding! {
-        PushCopy(300) => [3, 1, 44]
+        PushF(1.5) => [51, 63, 248, 0, 0, 0, 0, 0, 0],
     }
 
     test_symmetry! {
-        PushCopy, PushCopy(300), [3, 1, 44],
+        PushF, PushF(-2.0), [51, 192, 0, 0, 0, 0, 0, 0, 0],
     }
 
     test_display! {
-        PushCopy(3) => "push_copy 3",
-        PushCopy(11) => "push_copy 11",
+        PushF(2.0) => "push_f 2.0",
+        PushF(0.25) => "push_f 0.25",
     }
 }
 
 #[cfg(test)]
-mod call {
+mod add_f {
     use super::*;
 
     test_encoding! {
-        Call(247) => [4, 0, 0, 0, 247],
+        AddF => [52],
     }
 
     test_symmetry! {
-        Call, Call(247), [4, 0, 0, 0, 247],
+        AddF, AddF, [52],
     }
 
     test_display! {
-        Call(1010) => "call 1010",
-        Call(12) => "call 12",
+        AddF => "add_f",
     }
 }
 
 #[cfg(test)]
-mod ret {
+mod sub_f {
     use super::*;
 
     test_encoding! {
-        Ret { shrink_offset: 2, ip_offset: 4 } => [5, 0, 2, 0, 4],
+        SubF => [53],
     }
 
     test_symmetry! {
-        Ret,
-        Ret { shrink_offset: 2, ip_offset: 4 },
-        [5, 0, 2, 0, 4],
+        SubF, SubF, [53],
     }
 
     test_display! {
-        Ret { shrink_offset: 100, ip_offset: 34 } => "ret 100 34",
-        Ret { shrink_offset: 10, ip_offset: 4 } => "ret 10 4",
+        SubF => "sub_f",
     }
 }
 
 #[cfg(test)]
-mod res_v {
+mod mul_f {
     use super::*;
 
     test_encoding! {
-        ResV(22) => [6, 0, 22],
+        MulF => [54],
     }
 
     test_symmetry! {
-        ResV, ResV(101), [6, 0, 101],
+        MulF, MulF, [54],
     }
 
     test_display! {
-        ResV(10) => "res_v 10"
+        MulF => "mul_f",
     }
 }
 
 #[cfg(test)]
-mod pop_cpy {
+mod div_f {
     use super::*;
 
     test_encoding! {
-        PopCopy(32) => [7, 0, 32],
+        DivF => [55],
     }
 
     test_symmetry! {
-        PopCopy, PopCopy(13), [7, 0, 32],
+        DivF, DivF, [55],
     }
 
     test_display! {
-        PopCopy(12) => "pop_copy 12",
-        PopCopy(2) => "pop_copy 2",
+        DivF => "div_f",
     }
 }
 
 #[cfg(test)]
-mod goto {
+mod i_to_f {
     use super::*;
 
     test_encoding! {
-        Goto(444) => [8, 0, 0, 1, 188],
+        IToF => [56],
     }
 
     test_symmetry! {
-        Goto, Goto(10), [8, 0, 0, 0, 10],
+        IToF, IToF, [56],
     }
 
     test_display! {
-        Goto(1337) => "goto 1337",
-        Goto(2503) => "goto 2503",
+        IToF => "i_to_f",
     }
 }
 
 #[cfg(test)]
-mod cond_jmp {
+mod f_to_i {
     use super::*;
 
     test_encoding! {
-        CondJmp { negative_addr: 101, null_addr: 69, positive_addr: 13 }
-        =>
-        [9, 0, 0, 0, 101, 0, 0, 0, 69, 0, 0, 0, 13],
+        FToI => [57],
     }
 
     test_symmetry! {
-        CondJmp,
-        CondJmp { negative_addr: 101, null_addr: 69, positive_addr: 42 },
-        [9, 0, 0, 0, 101, 0, 0, 0, 69, 0, 0, 0, 42],
+        FToI, FToI, [57],
     }
 
     test_display! {
-        CondJmp { negative_addr: 1221, null_addr: 92, positive_addr: 218 } => "cond_jmp 1221 92 218",
+        FToI => "f_to_i",
     }
 }
 
 #[cfg(test)]
-mod neg {
+mod push_const {
     use super::*;
 
     test_encoding! {
-        Neg => [10],
+        PushConst(258) => [58, 1, 2],
     }
 
     test_symmetry! {
-        Neg, Neg, [10],
+        PushConst, PushConst(7), [58, 0, 7],
     }
 
     test_display! {
-        Neg => "neg",
+        PushConst(42) => "push_const 42",
     }
 }
 
 #[cfg(test)]
-mod mul {
+mod load_local {
     use super::*;
 
     test_encoding! {
-        Mul => [11],
+        LoadLocal(260) => [59, 1, 4]
+        LoadLocal(-2) => [59, 255, 254],
     }
 
     test_symmetry! {
-        Mul, Mul, [11],
+        LoadLocal, LoadLocal(3), [59, 0, 3],
     }
 
     test_display! {
-        Mul => "mul",
+        LoadLocal(12) => "load_local 12",
+        LoadLocal(-2) => "load_local -2",
     }
 }
 
 #[cfg(test)]
-mod pop {
+mod store_local {
     use super::*;
 
     test_encoding! {
-        Pop(10) => [12, 0, 10],
+        StoreLocal(513) => [60, 2, 1]
+        StoreLocal(9) => [60, 0, 9]
     }
 
     test_symmetry! {
-        Pop, Pop(32), [12, 0, 32],
+        StoreLocal, StoreLocal(-3), [60, 255, 253],
     }
 
     test_display! {
-        Pop(111) => "pop 111",
+        StoreLocal(4) => "store_local 4",
     }
 }
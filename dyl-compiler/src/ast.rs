@@ -1,264 +1,2192 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use crate::{io::SourceFile, symbol::Symbol};
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Program {
     functions: Vec<Function>,
+    structs: Vec<Struct>,
+    enums: Vec<Enum>,
+    consts: Vec<Const>,
+    modules: Vec<Module>,
+    imports: Vec<Import>,
+    macros: Vec<Macro>,
+}
+
+impl Program {
+    pub(crate) fn new(items: Vec<Item>) -> Program {
+        let mut functions = Vec::new();
+        let mut structs = Vec::new();
+        let mut enums = Vec::new();
+        let mut consts = Vec::new();
+        let mut modules = Vec::new();
+        let mut imports = Vec::new();
+        let mut macros = Vec::new();
+
+        for item in items {
+            match item {
+                Item::Function(f) => functions.push(f),
+                Item::Struct(s) => structs.push(s),
+                Item::Enum(e) => enums.push(e),
+                Item::Const(c) => consts.push(c),
+                Item::Module(m) => modules.push(m),
+                Item::Import(i) => imports.push(i),
+                Item::Macro(m) => macros.push(m),
+            }
+        }
+
+        Program {
+            functions,
+            structs,
+            enums,
+            consts,
+            modules,
+            imports,
+            macros,
+        }
+    }
+
+    /// Merges programs coming from different files into a single one. All
+    /// their items end up in the same namespace.
+    pub(crate) fn merge(programs: Vec<Program>) -> Program {
+        programs
+            .into_iter()
+            .fold(Program::new(Vec::new()), |mut merged, program| {
+                merged.functions.extend(program.functions);
+                merged.structs.extend(program.structs);
+                merged.enums.extend(program.enums);
+                merged.consts.extend(program.consts);
+                merged.modules.extend(program.modules);
+                merged.imports.extend(program.imports);
+                merged.macros.extend(program.macros);
+
+                merged
+            })
+    }
+
+    pub(crate) fn functions(&self) -> &[Function] {
+        self.functions.as_slice()
+    }
+
+    pub(crate) fn structs(&self) -> &[Struct] {
+        self.structs.as_slice()
+    }
+
+    pub(crate) fn enums(&self) -> &[Enum] {
+        self.enums.as_slice()
+    }
+
+    pub(crate) fn consts(&self) -> &[Const] {
+        self.consts.as_slice()
+    }
+
+    pub(crate) fn modules(&self) -> &[Module] {
+        self.modules.as_slice()
+    }
+
+    pub(crate) fn imports(&self) -> &[Import] {
+        self.imports.as_slice()
+    }
+
+    pub(crate) fn macros(&self) -> &[Macro] {
+        self.macros.as_slice()
+    }
+}
+
+#[cfg(test)]
+impl Program {
+    pub(crate) fn main_body(&self) -> &ExprKind {
+        self.functions
+            .iter()
+            .find(|f| f.name() == "main")
+            .unwrap()
+            .body()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Item {
+    Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Const(Const),
+    Module(Module),
+    Import(Import),
+    Macro(Macro),
+}
+
+/// A namespace, declared with `mod name { ... }`.
+///
+/// Its items are laid out the same way as the ones of a program.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Module {
+    name: String,
+    content: Program,
+    span: Span,
+}
+
+impl Module {
+    pub(crate) fn new(name: String, items: Vec<Item>) -> Module {
+        let content = Program::new(items);
+        let span = Span::default();
+        Module {
+            name,
+            content,
+            span,
+        }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Module {
+        Module { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn content(&self) -> &Program {
+        &self.content
+    }
+}
+
+/// Brings an item in scope, with `import path::to::item;`.
+///
+/// The path is absolute: it starts from the top-level items of the program.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Import(Vec<String>, Span);
+
+impl Import {
+    pub(crate) fn new(path: Vec<String>) -> Import {
+        Import(path, Span::default())
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Import {
+        Import(self.0, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn path(&self) -> &[String] {
+        self.0.as_slice()
+    }
+
+    /// Returns the name under which the item is available.
+    pub(crate) fn alias(&self) -> &str {
+        self.0.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// A macro, declared with `macro name(params) => expansion;`.
+///
+/// Macros are expanded right after parsing: each invocation is replaced by
+/// the expansion, where the parameters are replaced by the arguments of the
+/// invocation.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Macro {
+    name: String,
+    params: Vec<String>,
+    body: ExprKind,
+    span: Span,
+}
+
+impl Macro {
+    pub(crate) fn new(name: String, params: Vec<String>, body: ExprKind) -> Macro {
+        let span = Span::default();
+        Macro {
+            name,
+            params,
+            body,
+            span,
+        }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Macro {
+        Macro { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn params(&self) -> &[String] {
+        self.params.as_slice()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
+    }
+}
+
+/// A constant, whose value is computed at compile time and inlined where it
+/// is used.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Const {
+    name: String,
+    value: ExprKind,
+    span: Span,
+}
+
+impl Const {
+    pub(crate) fn new(name: String, value: ExprKind) -> Const {
+        let span = Span::default();
+        Const { name, value, span }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Const {
+        Const { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.value
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Struct {
+    name: String,
+    fields: Vec<String>,
+    span: Span,
+}
+
+impl Struct {
+    pub(crate) fn new(name: String, fields: Vec<String>) -> Struct {
+        let span = Span::default();
+        Struct { name, fields, span }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Struct {
+        Struct { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn fields(&self) -> &[String] {
+        self.fields.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Enum {
+    name: String,
+    variants: Vec<EnumVariant>,
+    span: Span,
+}
+
+impl Enum {
+    pub(crate) fn new(name: String, variants: Vec<EnumVariant>) -> Enum {
+        let span = Span::default();
+        Enum {
+            name,
+            variants,
+            span,
+        }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> Enum {
+        Enum { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn variants(&self) -> &[EnumVariant] {
+        self.variants.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct EnumVariant {
+    name: String,
+    fields: Vec<String>,
+    span: Span,
+}
+
+impl EnumVariant {
+    pub(crate) fn new(name: String, fields: Vec<String>) -> EnumVariant {
+        let span = Span::default();
+        EnumVariant { name, fields, span }
+    }
+
+    pub(crate) fn with_span(self, span: Span) -> EnumVariant {
+        EnumVariant { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn fields(&self) -> &[String] {
+        self.fields.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Function {
+    name: String,
+    generics: Vec<String>,
+    args: Vec<String>,
+
+    /// The type annotation of each argument, if any.
+    arg_types: Vec<Option<Type>>,
+    ret_type: Option<Type>,
+
+    body: ExprKind,
+
+    /// The location of the name of the function.
+    span: Span,
+}
+
+impl Function {
+    pub(crate) fn new(name: String, args: Vec<String>, body: ExprKind) -> Function {
+        let arg_types = vec![None; args.len()];
+
+        Function {
+            name,
+            generics: Vec::new(),
+            args,
+            arg_types,
+            ret_type: None,
+            body,
+            span: Span::default(),
+        }
+    }
+
+    /// Sets the location of the function.
+    pub(crate) fn with_span(self, span: Span) -> Function {
+        Function { span, ..self }
+    }
+
+    /// Sets the type parameters and the type annotations of the function.
+    pub(crate) fn with_signature(
+        self,
+        generics: Vec<String>,
+        arg_types: Vec<Option<Type>>,
+        ret_type: Option<Type>,
+    ) -> Function {
+        assert_eq!(self.args.len(), arg_types.len());
+
+        Function {
+            generics,
+            arg_types,
+            ret_type,
+            ..self
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn generics(&self) -> &[String] {
+        self.generics.as_slice()
+    }
+
+    pub(crate) fn is_generic(&self) -> bool {
+        !self.generics.is_empty()
+    }
+
+    pub(crate) fn args(&self) -> &[String] {
+        self.args.as_slice()
+    }
+
+    pub(crate) fn arg_types(&self) -> &[Option<Type>] {
+        self.arg_types.as_slice()
+    }
+
+    pub(crate) fn ret_type(&self) -> Option<&Type> {
+        self.ret_type.as_ref()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+/// A type, as written in a function signature or in a binding.
+///
+/// Types are checked once generic functions are instantiated, which relies
+/// on them as well.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Type {
+    /// A builtin type, a struct, an enum or a type parameter.
+    Named(String),
+    Tuple(Vec<Type>),
+    Array(Box<Type>),
+}
+
+impl Type {
+    pub(crate) fn named(name: &str) -> Type {
+        Type::Named(name.to_owned())
+    }
+
+    pub(crate) fn array(element: Type) -> Type {
+        Type::Array(Box::new(element))
+    }
+
+    /// Replaces the type parameters by the types they are bound to.
+    pub(crate) fn substitute(&self, bindings: &[(String, Type)]) -> Type {
+        match self {
+            Type::Named(name) => bindings
+                .iter()
+                .find(|(param, _)| param == name)
+                .map(|(_, ty)| ty.clone())
+                .unwrap_or_else(|| self.clone()),
+
+            Type::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| element.substitute(bindings))
+                    .collect(),
+            ),
+
+            Type::Array(element) => Type::array(element.substitute(bindings)),
+        }
+    }
+
+    pub(crate) fn is_numeric(&self) -> bool {
+        matches!(self, Type::Named(name) if name == "int")
+    }
+
+    /// Tells whether a value of this type can be converted to `target` with
+    /// an `as` cast.
+    pub(crate) fn can_cast_to(&self, target: &Type) -> bool {
+        let is_bool = matches!(self, Type::Named(name) if name == "bool");
+
+        target.is_numeric() && (self.is_numeric() || is_bool)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Type::Named(name) => write!(f, "{}", name),
+
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+
+            Type::Array(element) => write!(f, "[{}]", element),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ExprKind {
+    Addition(Addition),
+    Subtraction(Subtraction),
+    Multiplication(Multiplication),
+    Exponentiation(Exponentiation),
+    Comparison(Comparison),
+    Logical(Logical),
+    Cast(Cast),
+    Integer(Integer),
+    If(If),
+    Bindings(Bindings),
+    Statements(Statements),
+    Ident(Ident),
+    Bool(Bool),
+    String(Str),
+    FnCall(FnCall),
+    Return(Return),
+    Assignment(Assignment),
+    CompoundAssignment(CompoundAssignment),
+    Tuple(Tuple),
+    Field(Field),
+    Array(Array),
+    Index(Index),
+    IndexAssignment(IndexAssignment),
+    StructLiteral(StructLiteral),
+    VariantLiteral(VariantLiteral),
+    Match(Match),
+    Closure(Closure),
+    LocalFunctions(LocalFunctions),
+    MacroCall(MacroCall),
+}
+
+impl ExprKind {
+    pub(crate) fn addition(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Addition(Addition::new(lhs, rhs))
+    }
+
+    pub(crate) fn subtraction(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Subtraction(Subtraction::new(lhs, rhs))
+    }
+
+    pub(crate) fn multiplication(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Multiplication(Multiplication::new(lhs, rhs))
+    }
+
+    pub(crate) fn exponentiation(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Exponentiation(Exponentiation::new(lhs, rhs))
+    }
+
+    pub(crate) fn comparison(
+        operator: ComparisonOperator,
+        lhs: ExprKind,
+        rhs: ExprKind,
+    ) -> ExprKind {
+        ExprKind::Comparison(Comparison::new(operator, lhs, rhs))
+    }
+
+    pub(crate) fn and(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Logical(Logical::new(LogicalOperator::And, lhs, rhs))
+    }
+
+    pub(crate) fn or(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        ExprKind::Logical(Logical::new(LogicalOperator::Or, lhs, rhs))
+    }
+
+    pub(crate) fn cast(value: ExprKind, target: Type) -> ExprKind {
+        ExprKind::Cast(Cast::new(value, target))
+    }
+
+    pub(crate) fn integer(value: i32) -> ExprKind {
+        ExprKind::Integer(Integer::new(value))
+    }
+
+    pub(crate) fn if_(
+        condition: ExprKind,
+        consequent: ExprKind,
+        alternative: ExprKind,
+    ) -> ExprKind {
+        ExprKind::If(If::new(condition, consequent, Some(alternative)))
+    }
+
+    pub(crate) fn if_without_else(condition: ExprKind, consequent: ExprKind) -> ExprKind {
+        ExprKind::If(If::new(condition, consequent, None))
+    }
+
+    pub(crate) fn bindings(bs: Vec<Binding>, next: ExprKind) -> ExprKind {
+        ExprKind::Bindings(Bindings::from_vec(bs, next))
+    }
+
+    pub(crate) fn statements(statements: Vec<ExprKind>, next: ExprKind) -> ExprKind {
+        ExprKind::Statements(Statements::new(statements, next))
+    }
+
+    /// Creates the expression corresponding to the content of a block.
+    ///
+    /// Consecutive bindings are grouped in a single [`Bindings`] node, and
+    /// consecutive expression statements in a single [`Statements`] node.
+    pub(crate) fn block(
+        statements: Vec<Statement>,
+        ending: ExprKind,
+        ending_span: Span,
+    ) -> ExprKind {
+        let mut statements = statements.into_iter().rev().peekable();
+        let mut block = ending;
+        let mut block_span = ending_span;
+
+        while let Some(statement) = statements.next() {
+            (block, block_span) = match statement {
+                Statement::Let(binding) => {
+                    let mut bindings = vec![binding];
+                    while let Some(Statement::Let(binding)) =
+                        statements.next_if(|s| matches!(s, Statement::Let(_)))
+                    {
+                        bindings.push(binding);
+                    }
+
+                    bindings.reverse();
+                    let span = bindings[0].span().clone();
+                    let bindings = Bindings::from_vec(bindings, block).with_ending_span(block_span);
+
+                    (ExprKind::Bindings(bindings), span)
+                }
+
+                Statement::Expr(expr, span) => {
+                    let mut exprs = vec![expr];
+                    let mut spans = vec![block_span, span];
+                    while let Some(Statement::Expr(expr, span)) =
+                        statements.next_if(|s| matches!(s, Statement::Expr(..)))
+                    {
+                        exprs.push(expr);
+                        spans.push(span);
+                    }
+
+                    exprs.reverse();
+                    spans.reverse();
+                    let span = spans[0].clone();
+                    let statements = Statements::new(exprs, block).with_spans(spans);
+
+                    (ExprKind::Statements(statements), span)
+                }
+            };
+        }
+
+        block
+    }
+
+    pub(crate) fn ident(name: impl Into<Symbol>) -> ExprKind {
+        ExprKind::Ident(Ident::new(name))
+    }
+
+    pub(crate) fn bool_(bool_: bool) -> ExprKind {
+        ExprKind::Bool(Bool::new(bool_))
+    }
+
+    pub(crate) fn string(value: String) -> ExprKind {
+        ExprKind::String(Str::new(value))
+    }
+
+    pub(crate) fn fn_call(name: String, args: Vec<ExprKind>) -> ExprKind {
+        ExprKind::FnCall(FnCall::new(name, args))
+    }
+
+    pub(crate) fn macro_call(name: String, args: Vec<ExprKind>) -> ExprKind {
+        ExprKind::MacroCall(MacroCall::new(name, args))
+    }
+
+    pub(crate) fn return_(value: ExprKind) -> ExprKind {
+        ExprKind::Return(Return::new(value))
+    }
+
+    pub(crate) fn assignment(name: String, value: ExprKind) -> ExprKind {
+        ExprKind::Assignment(Assignment::new(name, value))
+    }
+
+    pub(crate) fn compound_assignment(
+        operator: ArithmeticOperator,
+        name: String,
+        value: ExprKind,
+    ) -> ExprKind {
+        ExprKind::CompoundAssignment(CompoundAssignment::new(operator, name, value))
+    }
+
+    pub(crate) fn tuple(elements: Vec<ExprKind>) -> ExprKind {
+        ExprKind::Tuple(Tuple::new(elements))
+    }
+
+    /// Creates the unit value `()`, which is the empty tuple.
+    pub(crate) fn unit() -> ExprKind {
+        ExprKind::tuple(Vec::new())
+    }
+
+    pub(crate) fn field(tuple: ExprKind, idx: u16) -> ExprKind {
+        ExprKind::Field(Field::new(tuple, idx))
+    }
+
+    pub(crate) fn array(elements: Vec<ExprKind>) -> ExprKind {
+        ExprKind::Array(Array::new(elements))
+    }
+
+    pub(crate) fn index(array: ExprKind, index: ExprKind) -> ExprKind {
+        ExprKind::Index(Index::new(array, index))
+    }
+
+    pub(crate) fn struct_literal(name: String, fields: Vec<(String, ExprKind)>) -> ExprKind {
+        ExprKind::StructLiteral(StructLiteral::new(name, fields))
+    }
+
+    pub(crate) fn variant_literal(
+        enum_name: String,
+        variant: String,
+        args: Vec<ExprKind>,
+    ) -> ExprKind {
+        ExprKind::VariantLiteral(VariantLiteral::new(enum_name, variant, Some(args)))
+    }
+
+    /// Creates a path of the form `a::b`, written without parentheses.
+    pub(crate) fn unit_variant(enum_name: String, variant: String) -> ExprKind {
+        ExprKind::VariantLiteral(VariantLiteral::new(enum_name, variant, None))
+    }
+
+    pub(crate) fn match_(scrutinee: ExprKind, arms: Vec<MatchArm>) -> ExprKind {
+        ExprKind::Match(Match::new(scrutinee, arms))
+    }
+
+    pub(crate) fn closure(params: Vec<String>, body: ExprKind) -> ExprKind {
+        ExprKind::Closure(Closure::new(params, body))
+    }
+
+    /// Makes functions visible in `body`. Returns `body` as is when there is
+    /// no function.
+    pub(crate) fn local_functions(functions: Vec<Function>, body: ExprKind) -> ExprKind {
+        if functions.is_empty() {
+            body
+        } else {
+            ExprKind::LocalFunctions(LocalFunctions(functions, Box::new(body), Span::default()))
+        }
+    }
+
+    /// Sets the location of an expression.
+    ///
+    /// The location of a sequence of statements or bindings is the one of
+    /// its first statement, so these are returned as is.
+    pub(crate) fn with_span(self, span: Span) -> ExprKind {
+        match self {
+            ExprKind::Addition(a) => ExprKind::Addition(Addition(a.0, span)),
+            ExprKind::Subtraction(s) => ExprKind::Subtraction(Subtraction(s.0, span)),
+            ExprKind::Multiplication(m) => ExprKind::Multiplication(Multiplication(m.0, span)),
+            ExprKind::Exponentiation(e) => ExprKind::Exponentiation(Exponentiation(e.0, span)),
+            ExprKind::Comparison(c) => ExprKind::Comparison(Comparison(c.0, c.1, span)),
+            ExprKind::Logical(l) => ExprKind::Logical(Logical(l.0, l.1, span)),
+            ExprKind::Cast(c) => ExprKind::Cast(Cast(c.0, c.1, span)),
+            ExprKind::Integer(i) => ExprKind::Integer(Integer(i.0, span)),
+            ExprKind::If(i) => ExprKind::If(If(i.0, span)),
+            ExprKind::Ident(i) => ExprKind::Ident(i.with_span(span)),
+            ExprKind::Bool(b) => ExprKind::Bool(Bool(b.0, span)),
+            ExprKind::String(s) => ExprKind::String(Str(s.0, span)),
+            ExprKind::FnCall(c) => ExprKind::FnCall(c.with_span(span)),
+            ExprKind::Return(r) => ExprKind::Return(Return(r.0, span)),
+            ExprKind::Assignment(a) => ExprKind::Assignment(Assignment(a.0, a.1, span)),
+            ExprKind::CompoundAssignment(a) => {
+                ExprKind::CompoundAssignment(CompoundAssignment(a.0, a.1, a.2, span))
+            }
+            ExprKind::Tuple(t) => ExprKind::Tuple(Tuple(t.0, span)),
+            ExprKind::Field(f) => ExprKind::Field(Field(f.0, f.1, span)),
+            ExprKind::Array(a) => ExprKind::Array(Array(a.0, span)),
+            ExprKind::Index(i) => ExprKind::Index(Index(i.0, i.1, span)),
+            ExprKind::IndexAssignment(a) => {
+                ExprKind::IndexAssignment(IndexAssignment(a.0, a.1, span))
+            }
+            ExprKind::StructLiteral(s) => ExprKind::StructLiteral(StructLiteral { span, ..s }),
+            ExprKind::VariantLiteral(v) => ExprKind::VariantLiteral(VariantLiteral { span, ..v }),
+            ExprKind::Match(m) => ExprKind::Match(Match { span, ..m }),
+            ExprKind::Closure(c) => ExprKind::Closure(Closure(c.0, c.1, span)),
+            ExprKind::LocalFunctions(l) => ExprKind::LocalFunctions(LocalFunctions(l.0, l.1, span)),
+            ExprKind::MacroCall(m) => ExprKind::MacroCall(MacroCall(m.0, m.1, span)),
+            ExprKind::Bindings(_) | ExprKind::Statements(_) => self,
+        }
+    }
+
+    /// Returns the location of an expression.
+    ///
+    /// Most expressions are located by the code they are written with, from
+    /// start to end. Operations are located by their operator instead.
+    pub(crate) fn span(&self) -> &Span {
+        match self {
+            ExprKind::Addition(a) => a.span(),
+            ExprKind::Subtraction(s) => s.span(),
+            ExprKind::Multiplication(m) => m.span(),
+            ExprKind::Exponentiation(e) => e.span(),
+            ExprKind::Comparison(c) => c.span(),
+            ExprKind::Logical(l) => l.span(),
+            ExprKind::Cast(c) => c.span(),
+            ExprKind::Integer(i) => i.span(),
+            ExprKind::If(i) => i.span(),
+            ExprKind::Bindings(b) => b
+                .defines()
+                .first()
+                .map_or_else(|| b.ending_expression().span(), Binding::span),
+            ExprKind::Statements(s) => s.span(0),
+            ExprKind::Ident(i) => i.span(),
+            ExprKind::Bool(b) => b.span(),
+            ExprKind::String(s) => s.span(),
+            ExprKind::FnCall(c) => c.span(),
+            ExprKind::Return(r) => r.span(),
+            ExprKind::Assignment(a) => a.span(),
+            ExprKind::CompoundAssignment(a) => a.span(),
+            ExprKind::Tuple(t) => t.span(),
+            ExprKind::Field(f) => f.span(),
+            ExprKind::Array(a) => a.span(),
+            ExprKind::Index(i) => i.span(),
+            ExprKind::IndexAssignment(a) => a.span(),
+            ExprKind::StructLiteral(s) => s.span(),
+            ExprKind::VariantLiteral(v) => v.span(),
+            ExprKind::Match(m) => m.span(),
+            ExprKind::Closure(c) => c.span(),
+            ExprKind::LocalFunctions(l) => l.span(),
+            ExprKind::MacroCall(m) => m.span(),
+        }
+    }
+
+    /// Returns the expressions an expression is directly made of, in the
+    /// order they are evaluated.
+    ///
+    /// Passes only match the expressions they treat in their own way, and
+    /// walk the others through this.
+    pub(crate) fn children(&self) -> Vec<&ExprKind> {
+        match self {
+            ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_) | ExprKind::Ident(_) => {
+                Vec::new()
+            }
+
+            ExprKind::Addition(a) => vec![a.left(), a.right()],
+            ExprKind::Subtraction(s) => vec![s.left(), s.right()],
+            ExprKind::Multiplication(m) => vec![m.left(), m.right()],
+            ExprKind::Exponentiation(e) => vec![e.left(), e.right()],
+            ExprKind::Comparison(c) => vec![c.left(), c.right()],
+            ExprKind::Logical(l) => vec![l.left(), l.right()],
+            ExprKind::Cast(c) => vec![c.value()],
+
+            ExprKind::If(i) => [i.condition(), i.consequent()]
+                .into_iter()
+                .chain(i.alternative())
+                .collect(),
+
+            ExprKind::Bindings(b) => b
+                .defines()
+                .iter()
+                .map(Binding::value)
+                .chain([b.ending_expression()])
+                .collect(),
+
+            ExprKind::Statements(s) => s
+                .statements()
+                .iter()
+                .chain([s.ending_expression()])
+                .collect(),
+
+            ExprKind::FnCall(c) => c.args().iter().collect(),
+            ExprKind::MacroCall(m) => m.args().iter().collect(),
+            ExprKind::Return(r) => vec![r.value()],
+            ExprKind::Assignment(a) => vec![a.value()],
+            ExprKind::CompoundAssignment(a) => vec![a.value()],
+            ExprKind::Tuple(t) => t.elements().iter().collect(),
+            ExprKind::Field(f) => vec![f.tuple()],
+            ExprKind::Array(a) => a.elements().iter().collect(),
+            ExprKind::Index(i) => vec![i.array(), i.index()],
+
+            ExprKind::IndexAssignment(a) => vec![a.target().array(), a.target().index(), a.value()],
+
+            ExprKind::StructLiteral(s) => s.fields().iter().map(|(_, value)| value).collect(),
+            ExprKind::VariantLiteral(v) => v.args().iter().collect(),
+
+            ExprKind::Match(m) => [m.scrutinee()]
+                .into_iter()
+                .chain(
+                    m.arms()
+                        .iter()
+                        .flat_map(|arm| arm.guard().into_iter().chain([arm.body()])),
+                )
+                .collect(),
+
+            ExprKind::Closure(c) => vec![c.body()],
+            ExprKind::LocalFunctions(l) => vec![l.body()],
+        }
+    }
+
+    /// Rebuilds an expression at the same location, with each of its
+    /// [children](ExprKind::children) replaced by what `f` makes of it.
+    ///
+    /// Children are passed to `f` in the order they are evaluated. Bindings
+    /// and match arms keep their patterns, and local functions are kept as
+    /// they are.
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&ExprKind) -> ExprKind) -> ExprKind {
+        let mapped = match self {
+            ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_) | ExprKind::Ident(_) => {
+                return self.clone()
+            }
+
+            ExprKind::Addition(a) => ExprKind::addition(f(a.left()), f(a.right())),
+            ExprKind::Subtraction(s) => ExprKind::subtraction(f(s.left()), f(s.right())),
+            ExprKind::Multiplication(m) => ExprKind::multiplication(f(m.left()), f(m.right())),
+            ExprKind::Exponentiation(e) => ExprKind::exponentiation(f(e.left()), f(e.right())),
+            ExprKind::Comparison(c) => {
+                ExprKind::comparison(c.operator(), f(c.left()), f(c.right()))
+            }
+            ExprKind::Logical(l) => {
+                ExprKind::Logical(Logical::new(l.operator(), f(l.left()), f(l.right())))
+            }
+            ExprKind::Cast(c) => ExprKind::cast(f(c.value()), c.target().clone()),
+
+            ExprKind::If(i) => {
+                let condition = f(i.condition());
+                let consequent = f(i.consequent());
+
+                ExprKind::If(If::new(condition, consequent, i.alternative().map(f)))
+            }
+
+            ExprKind::Bindings(b) => {
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .map(|binding| {
+                        Binding::from_pattern(
+                            binding.pattern().clone(),
+                            f(binding.value()),
+                            binding.is_mutable(),
+                        )
+                        .with_annotation(binding.annotation().cloned())
+                        .with_span(binding.span().clone())
+                    })
+                    .collect();
+
+                return b.rebuild(bindings, f(b.ending_expression()));
+            }
+
+            ExprKind::Statements(s) => {
+                let statements = s.statements().iter().map(&mut f).collect();
+
+                return s.rebuild(statements, f(s.ending_expression()));
+            }
+
+            ExprKind::FnCall(c) => {
+                return c.rebuild(c.name().to_owned(), c.args().iter().map(f).collect())
+            }
+
+            ExprKind::MacroCall(m) => {
+                ExprKind::macro_call(m.name().to_owned(), m.args().iter().map(f).collect())
+            }
+
+            ExprKind::Return(r) => ExprKind::return_(f(r.value())),
+            ExprKind::Assignment(a) => ExprKind::assignment(a.name().to_owned(), f(a.value())),
+
+            ExprKind::CompoundAssignment(a) => {
+                ExprKind::compound_assignment(a.operator(), a.name().to_owned(), f(a.value()))
+            }
+
+            ExprKind::Tuple(t) => ExprKind::tuple(t.elements().iter().map(f).collect()),
+            ExprKind::Field(field) => ExprKind::field(f(field.tuple()), field.idx()),
+            ExprKind::Array(a) => ExprKind::array(a.elements().iter().map(f).collect()),
+            ExprKind::Index(i) => ExprKind::index(f(i.array()), f(i.index())),
+
+            ExprKind::IndexAssignment(a) => {
+                let target = a.target();
+                let target = Index(
+                    Box::new(f(target.array())),
+                    Box::new(f(target.index())),
+                    target.span().clone(),
+                );
+
+                ExprKind::IndexAssignment(IndexAssignment::new(target, f(a.value())))
+            }
+
+            ExprKind::StructLiteral(s) => {
+                let fields = s
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), f(value)))
+                    .collect();
+
+                ExprKind::struct_literal(s.name().to_owned(), fields)
+            }
+
+            ExprKind::VariantLiteral(v) => {
+                let enum_name = v.enum_name().to_owned();
+                let variant = v.variant().to_owned();
+
+                if v.is_parenthesized() {
+                    ExprKind::variant_literal(enum_name, variant, v.args().iter().map(f).collect())
+                } else {
+                    ExprKind::unit_variant(enum_name, variant)
+                }
+            }
+
+            ExprKind::Match(m) => {
+                let scrutinee = f(m.scrutinee());
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let guard = arm.guard().map(&mut f);
+
+                        MatchArm::new(arm.pattern().clone(), f(arm.body()))
+                            .with_guard(guard)
+                            .with_span(arm.span().clone())
+                    })
+                    .collect();
+
+                ExprKind::match_(scrutinee, arms)
+            }
+
+            ExprKind::Closure(c) => ExprKind::closure(c.params().to_vec(), f(c.body())),
+
+            ExprKind::LocalFunctions(l) => ExprKind::LocalFunctions(LocalFunctions(
+                l.0.clone(),
+                Box::new(f(l.body())),
+                Span::default(),
+            )),
+        };
+
+        mapped.with_span(self.span().clone())
+    }
+}
+
+#[cfg(test)]
+impl ExprKind {
+    pub(crate) fn single_binding(
+        name: String,
+        value: ExprKind,
+        inner_expression: ExprKind,
+    ) -> ExprKind {
+        ExprKind::Bindings(Bindings::single(name, value, inner_expression))
+    }
+
+    pub(crate) fn index_assignment(array: ExprKind, index: ExprKind, value: ExprKind) -> ExprKind {
+        ExprKind::IndexAssignment(IndexAssignment::new(Index::new(array, index), value))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Addition(Box<(ExprKind, ExprKind)>, Span);
+
+impl Addition {
+    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Addition {
+        Addition(Box::new((lhs, rhs)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.inner().0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.inner().1
+    }
+
+    fn inner(&self) -> &(ExprKind, ExprKind) {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Subtraction(Box<(ExprKind, ExprKind)>, Span);
+
+impl Subtraction {
+    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Subtraction {
+        Subtraction(Box::new((lhs, rhs)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.inner().0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.inner().1
+    }
+
+    fn inner(&self) -> &(ExprKind, ExprKind) {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Multiplication(Box<(ExprKind, ExprKind)>, Span);
+
+impl Multiplication {
+    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Multiplication {
+        Multiplication(Box::new((lhs, rhs)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.inner().0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.inner().1
+    }
+
+    fn inner(&self) -> &(ExprKind, ExprKind) {
+        &self.0
+    }
+}
+
+/// `base ** exponent`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Exponentiation(Box<(ExprKind, ExprKind)>, Span);
+
+impl Exponentiation {
+    pub(crate) fn new(base: ExprKind, exponent: ExprKind) -> Exponentiation {
+        Exponentiation(Box::new((base, exponent)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.inner().0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.inner().1
+    }
+
+    fn inner(&self) -> &(ExprKind, ExprKind) {
+        &self.0
+    }
+}
+
+/// `left == right`, `left < right`, and so on.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Comparison(ComparisonOperator, Box<(ExprKind, ExprKind)>, Span);
+
+impl Comparison {
+    pub(crate) fn new(operator: ComparisonOperator, lhs: ExprKind, rhs: ExprKind) -> Comparison {
+        Comparison(operator, Box::new((lhs, rhs)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn operator(&self) -> ComparisonOperator {
+        self.0
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.1 .0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.1 .1
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ComparisonOperator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl ComparisonOperator {
+    /// Tells whether the comparison holds, given how its left operand
+    /// compares to its right one.
+    pub(crate) fn holds_for(self, ordering: Ordering) -> bool {
+        match self {
+            ComparisonOperator::Equal => ordering == Ordering::Equal,
+            ComparisonOperator::NotEqual => ordering != Ordering::Equal,
+            ComparisonOperator::Less => ordering == Ordering::Less,
+            ComparisonOperator::LessOrEqual => ordering != Ordering::Greater,
+            ComparisonOperator::Greater => ordering == Ordering::Greater,
+            ComparisonOperator::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// `left && right` or `left || right`. The right operand is evaluated only
+/// when the left one does not determine the result.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Logical(LogicalOperator, Box<(ExprKind, ExprKind)>, Span);
+
+impl Logical {
+    pub(crate) fn new(operator: LogicalOperator, lhs: ExprKind, rhs: ExprKind) -> Logical {
+        Logical(operator, Box::new((lhs, rhs)), Span::default())
+    }
+
+    /// Returns the location of the operator.
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn operator(&self) -> LogicalOperator {
+        self.0
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.1 .0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.1 .1
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum LogicalOperator {
+    And,
+    Or,
+}
+
+/// `value as target`.
+///
+/// Integers are kept as is, and booleans become 1 when they are true and 0
+/// otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Cast(Box<ExprKind>, Type, Span);
+
+impl Cast {
+    pub(crate) fn new(value: ExprKind, target: Type) -> Cast {
+        Cast(Box::new(value), target, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.0
+    }
+
+    pub(crate) fn target(&self) -> &Type {
+        &self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Integer(i32, Span);
+
+impl Integer {
+    pub(crate) fn new(value: i32) -> Integer {
+        Integer(value, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct If(Box<(ExprKind, ExprKind, Option<ExprKind>)>, Span);
+
+impl If {
+    pub(crate) fn new(
+        condition: ExprKind,
+        consequent: ExprKind,
+        alternative: Option<ExprKind>,
+    ) -> If {
+        If(
+            Box::new((condition, consequent, alternative)),
+            Span::default(),
+        )
+    }
+
+    /// Returns the location of the `if` keyword.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn condition(&self) -> &ExprKind {
+        &self.inner().0
+    }
+
+    pub(crate) fn consequent(&self) -> &ExprKind {
+        &self.inner().1
+    }
+
+    pub(crate) fn alternative(&self) -> Option<&ExprKind> {
+        self.inner().2.as_ref()
+    }
+
+    fn inner(&self) -> &(ExprKind, ExprKind, Option<ExprKind>) {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Bindings(Vec<Binding>, Box<ExprKind>, Span);
+
+impl Bindings {
+    pub(crate) fn from_vec(bs: Vec<Binding>, next: ExprKind) -> Bindings {
+        Bindings(bs, Box::new(next), Span::default())
+    }
+
+    /// Sets the location of the ending expression.
+    pub(crate) fn with_ending_span(self, span: Span) -> Bindings {
+        Bindings(self.0, self.1, span)
+    }
+
+    pub(crate) fn defines(&self) -> &[Binding] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn ending_expression(&self) -> &ExprKind {
+        &self.1
+    }
+
+    pub(crate) fn ending_span(&self) -> &Span {
+        &self.2
+    }
+
+    /// Builds bindings at the same location, with other bindings or another
+    /// ending expression.
+    pub(crate) fn rebuild(&self, bs: Vec<Binding>, next: ExprKind) -> ExprKind {
+        ExprKind::Bindings(Bindings(bs, Box::new(next), self.2.clone()))
+    }
+}
+
+#[cfg(test)]
+impl Bindings {
+    pub(crate) fn single(name: String, value: ExprKind, next: ExprKind) -> Bindings {
+        let binding = Binding::new(name, value);
+        Bindings::from_vec(vec![binding], next)
+    }
+}
+
+/// A sequence of expressions whose values are discarded, followed by the
+/// expression that gives its value to the whole sequence.
+///
+/// The location of each statement is kept, followed by the location of the
+/// ending expression.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Statements(Vec<ExprKind>, Box<ExprKind>, Vec<Span>);
+
+impl Statements {
+    pub(crate) fn new(statements: Vec<ExprKind>, next: ExprKind) -> Statements {
+        let spans = vec![Span::default(); statements.len() + 1];
+        Statements(statements, Box::new(next), spans)
+    }
+
+    /// Sets the location of each statement, then of the ending expression.
+    pub(crate) fn with_spans(self, spans: Vec<Span>) -> Statements {
+        assert_eq!(spans.len(), self.0.len() + 1);
+        Statements(self.0, self.1, spans)
+    }
+
+    /// Builds statements at the same location, with other statements or
+    /// another ending expression. The statements are the same in number.
+    pub(crate) fn rebuild(&self, statements: Vec<ExprKind>, next: ExprKind) -> ExprKind {
+        let statements = Statements::new(statements, next);
+
+        if statements.0.len() == self.0.len() {
+            ExprKind::Statements(statements.with_spans(self.2.clone()))
+        } else {
+            ExprKind::Statements(statements)
+        }
+    }
+
+    pub(crate) fn statements(&self) -> &[ExprKind] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn ending_expression(&self) -> &ExprKind {
+        &self.1
+    }
+
+    /// Returns the location of the statement at `idx`, or of the ending
+    /// expression if `idx` is the number of statements.
+    pub(crate) fn span(&self, idx: usize) -> &Span {
+        &self.2[idx]
+    }
+}
+
+/// A statement, as written in a block.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Statement {
+    Let(Binding),
+
+    /// An expression, along with its location.
+    Expr(ExprKind, Span),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Binding(BindingPattern, ExprKind, bool, Span, Option<Type>);
+
+impl Binding {
+    /// Creates a binding that may destructure its value. Every name of a
+    /// mutable binding is mutable.
+    pub(crate) fn from_pattern(pattern: BindingPattern, value: ExprKind, mutable: bool) -> Binding {
+        Binding(pattern, value, mutable, Span::default(), None)
+    }
+
+    /// Sets the location of the bound pattern.
+    pub(crate) fn with_span(self, span: Span) -> Binding {
+        Binding(self.0, self.1, self.2, span, self.4)
+    }
+
+    /// Sets the type the value is expected to have, as written in
+    /// `let x: int = 1;`.
+    pub(crate) fn with_annotation(self, annotation: Option<Type>) -> Binding {
+        Binding(self.0, self.1, self.2, self.3, annotation)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
+    }
+
+    pub(crate) fn annotation(&self) -> Option<&Type> {
+        self.4.as_ref()
+    }
+
+    pub(crate) fn is_mutable(&self) -> bool {
+        self.2
+    }
+
+    pub(crate) fn pattern(&self) -> &BindingPattern {
+        &self.0
+    }
+
+    /// Returns the names the binding defines, in the order they appear.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        self.0.names()
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.1
+    }
+}
+
+#[cfg(test)]
+impl Binding {
+    pub(crate) fn new(name: String, value: ExprKind) -> Binding {
+        Binding::from_pattern(BindingPattern::Name(name), value, false)
+    }
+
+    pub(crate) fn new_mut(name: String, value: ExprKind) -> Binding {
+        Binding::from_pattern(BindingPattern::Name(name), value, true)
+    }
+}
+
+/// What the value of a `let` is bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum BindingPattern {
+    /// `x`: binds the whole value.
+    Name(String),
+
+    /// `_`: ignores the value.
+    Wildcard,
+
+    /// `(a, b)`: binds the elements of a tuple.
+    Tuple(Vec<BindingPattern>),
+
+    /// `P { x, y: b }`: binds the fields of a struct.
+    Struct {
+        name: String,
+        fields: Vec<(String, BindingPattern)>,
+    },
+}
+
+impl BindingPattern {
+    /// Returns the names the pattern binds, in the order they appear.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_names(&mut names);
+
+        names
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            BindingPattern::Name(name) => names.push(name),
+            BindingPattern::Wildcard => {}
+            BindingPattern::Tuple(elements) => elements
+                .iter()
+                .for_each(|element| element.collect_names(names)),
+            BindingPattern::Struct { fields, .. } => fields
+                .iter()
+                .for_each(|(_, field)| field.collect_names(names)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Ident(Symbol, Span);
+
+impl Ident {
+    pub(crate) fn new(name: impl Into<Symbol>) -> Ident {
+        Ident(name.into(), Span::default())
+    }
+
+    /// Sets the location of the identifier.
+    pub(crate) fn with_span(self, span: Span) -> Ident {
+        Ident(self.0, span)
+    }
+
+    /// Builds an identifier at the same location, with another name.
+    pub(crate) fn rebuild(&self, name: impl Into<Symbol>) -> ExprKind {
+        ExprKind::Ident(Ident(name.into(), self.1.clone()))
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn symbol(&self) -> &Symbol {
+        &self.0
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Bool(bool, Span);
+
+impl Bool {
+    pub(crate) fn new(bool_: bool) -> Bool {
+        Bool(bool_, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn value(&self) -> bool {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Str(String, Span);
+
+impl Str {
+    pub(crate) fn new(value: String) -> Str {
+        Str(value, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FnCall(String, Vec<ExprKind>, Span);
+
+impl FnCall {
+    pub(crate) fn new(name: String, args: Vec<ExprKind>) -> FnCall {
+        FnCall(name, args, Span::default())
+    }
+
+    /// Sets the location of the call.
+    pub(crate) fn with_span(self, span: Span) -> FnCall {
+        FnCall(self.0, self.1, span)
+    }
+
+    /// Builds a call at the same location, to another function or with
+    /// other arguments.
+    pub(crate) fn rebuild(&self, name: String, args: Vec<ExprKind>) -> ExprKind {
+        ExprKind::FnCall(FnCall(name, args, self.2.clone()))
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn args(&self) -> &[ExprKind] {
+        self.1.as_slice()
+    }
 }
 
-impl Program {
-    pub(crate) fn new(functions: Vec<Function>) -> Program {
-        Program { functions }
+/// An invocation of a macro, written `name!(args)`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MacroCall(String, Vec<ExprKind>, Span);
+
+impl MacroCall {
+    pub(crate) fn new(name: String, args: Vec<ExprKind>) -> MacroCall {
+        MacroCall(name, args, Span::default())
     }
 
-    pub(crate) fn functions(&self) -> &[Function] {
-        self.functions.as_slice()
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn args(&self) -> &[ExprKind] {
+        self.1.as_slice()
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Function {
-    name: String,
-    body: ExprKind,
+pub(crate) struct Return(Box<ExprKind>, Span);
+
+impl Return {
+    pub(crate) fn new(value: ExprKind) -> Return {
+        Return(Box::new(value), Span::default())
+    }
+
+    /// Returns the location of the `return` keyword.
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.0
+    }
 }
 
-impl Function {
-    pub(crate) fn new(name: String, body: ExprKind) -> Function {
-        Function { name, body }
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Assignment(String, Box<ExprKind>, Span);
+
+impl Assignment {
+    pub(crate) fn new(name: String, value: ExprKind) -> Assignment {
+        Assignment(name, Box::new(value), Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
     }
 
     pub(crate) fn name(&self) -> &str {
-        self.name.as_str()
+        self.0.as_str()
     }
 
-    pub(crate) fn body(&self) -> &ExprKind {
-        &self.body
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.1
     }
 }
 
+/// `name += value`, and likewise for the other arithmetic operators, which
+/// the desugaring turns into `name = name + value`.
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum ExprKind {
-    Addition(Addition),
-    Subtraction(Subtraction),
-    Multiplication(Multiplication),
-    Integer(Integer),
-    If(If),
-    Bindings(Bindings),
-    Ident(Ident),
-    Bool(Bool),
-}
+pub(crate) struct CompoundAssignment(ArithmeticOperator, String, Box<ExprKind>, Span);
 
-impl ExprKind {
-    pub(crate) fn addition(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
-        ExprKind::Addition(Addition::new(lhs, rhs))
+impl CompoundAssignment {
+    pub(crate) fn new(
+        operator: ArithmeticOperator,
+        name: String,
+        value: ExprKind,
+    ) -> CompoundAssignment {
+        CompoundAssignment(operator, name, Box::new(value), Span::default())
     }
 
-    pub(crate) fn subtraction(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
-        ExprKind::Subtraction(Subtraction::new(lhs, rhs))
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
     }
 
-    pub(crate) fn multiplication(lhs: ExprKind, rhs: ExprKind) -> ExprKind {
-        ExprKind::Multiplication(Multiplication::new(lhs, rhs))
+    pub(crate) fn operator(&self) -> ArithmeticOperator {
+        self.0
     }
 
-    pub(crate) fn integer(value: i32) -> ExprKind {
-        ExprKind::Integer(Integer::new(value))
+    pub(crate) fn name(&self) -> &str {
+        self.1.as_str()
     }
 
-    pub(crate) fn if_(
-        condition: ExprKind,
-        consequent: ExprKind,
-        alternative: ExprKind,
-    ) -> ExprKind {
-        ExprKind::If(If::new(condition, consequent, alternative))
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.2
     }
+}
 
-    pub(crate) fn bindings(bs: Vec<Binding>, next: ExprKind) -> ExprKind {
-        ExprKind::Bindings(Bindings::from_vec(bs, next))
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Exponentiate,
+}
+
+impl ArithmeticOperator {
+    /// Applies the operator to two operands.
+    pub(crate) fn apply(self, lhs: ExprKind, rhs: ExprKind) -> ExprKind {
+        match self {
+            ArithmeticOperator::Add => ExprKind::addition(lhs, rhs),
+            ArithmeticOperator::Subtract => ExprKind::subtraction(lhs, rhs),
+            ArithmeticOperator::Multiply => ExprKind::multiplication(lhs, rhs),
+            ArithmeticOperator::Exponentiate => ExprKind::exponentiation(lhs, rhs),
+        }
     }
 
-    pub(crate) fn ident(name: String) -> ExprKind {
-        ExprKind::Ident(Ident::new(name))
+    /// The token of the compound assignment that uses the operator.
+    pub(crate) fn assignment_token(self) -> &'static str {
+        match self {
+            ArithmeticOperator::Add => "+=",
+            ArithmeticOperator::Subtract => "-=",
+            ArithmeticOperator::Multiply => "*=",
+            ArithmeticOperator::Exponentiate => "**=",
+        }
     }
+}
 
-    pub(crate) fn bool_(bool_: bool) -> ExprKind {
-        ExprKind::Bool(Bool::new(bool_))
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Tuple(Vec<ExprKind>, Span);
+
+impl Tuple {
+    pub(crate) fn new(elements: Vec<ExprKind>) -> Tuple {
+        Tuple(elements, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn elements(&self) -> &[ExprKind] {
+        self.0.as_slice()
     }
 }
 
-#[cfg(test)]
-impl ExprKind {
-    pub(crate) fn single_binding(
-        name: String,
-        value: ExprKind,
-        inner_expression: ExprKind,
-    ) -> ExprKind {
-        ExprKind::Bindings(Bindings::single(name, value, inner_expression))
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Array(Vec<ExprKind>, Span);
+
+impl Array {
+    pub(crate) fn new(elements: Vec<ExprKind>) -> Array {
+        Array(elements, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub(crate) fn elements(&self) -> &[ExprKind] {
+        self.0.as_slice()
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Addition(Box<(ExprKind, ExprKind)>);
+pub(crate) struct StructLiteral {
+    name: String,
+    fields: Vec<(String, ExprKind)>,
+    span: Span,
+}
 
-impl Addition {
-    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Addition {
-        Addition(Box::new((lhs, rhs)))
+impl StructLiteral {
+    pub(crate) fn new(name: String, fields: Vec<(String, ExprKind)>) -> StructLiteral {
+        let span = Span::default();
+        StructLiteral { name, fields, span }
     }
 
-    pub(crate) fn left(&self) -> &ExprKind {
-        &self.inner().0
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
     }
 
-    pub(crate) fn right(&self) -> &ExprKind {
-        &self.inner().1
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
     }
 
-    fn inner(&self) -> &(ExprKind, ExprKind) {
-        &self.0
+    pub(crate) fn fields(&self) -> &[(String, ExprKind)] {
+        self.fields.as_slice()
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Subtraction(Box<(ExprKind, ExprKind)>);
+pub(crate) struct VariantLiteral {
+    enum_name: String,
+    variant: String,
 
-impl Subtraction {
-    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Subtraction {
-        Subtraction(Box::new((lhs, rhs)))
+    /// The arguments, or `None` when the literal is written without
+    /// parentheses.
+    args: Option<Vec<ExprKind>>,
+    span: Span,
+}
+
+impl VariantLiteral {
+    pub(crate) fn new(
+        enum_name: String,
+        variant: String,
+        args: Option<Vec<ExprKind>>,
+    ) -> VariantLiteral {
+        VariantLiteral {
+            enum_name,
+            variant,
+            args,
+            span: Span::default(),
+        }
     }
 
-    pub(crate) fn left(&self) -> &ExprKind {
-        &self.inner().0
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
     }
 
-    pub(crate) fn right(&self) -> &ExprKind {
-        &self.inner().1
+    pub(crate) fn enum_name(&self) -> &str {
+        self.enum_name.as_str()
     }
 
-    fn inner(&self) -> &(ExprKind, ExprKind) {
-        &self.0
+    pub(crate) fn variant(&self) -> &str {
+        self.variant.as_str()
+    }
+
+    pub(crate) fn args(&self) -> &[ExprKind] {
+        self.args.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn is_parenthesized(&self) -> bool {
+        self.args.is_some()
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Multiplication(Box<(ExprKind, ExprKind)>);
+pub(crate) struct Match {
+    scrutinee: Box<ExprKind>,
+    arms: Vec<MatchArm>,
+    span: Span,
+}
 
-impl Multiplication {
-    pub(crate) fn new(lhs: ExprKind, rhs: ExprKind) -> Multiplication {
-        Multiplication(Box::new((lhs, rhs)))
+impl Match {
+    pub(crate) fn new(scrutinee: ExprKind, arms: Vec<MatchArm>) -> Match {
+        let scrutinee = Box::new(scrutinee);
+        let span = Span::default();
+        Match {
+            scrutinee,
+            arms,
+            span,
+        }
     }
 
-    pub(crate) fn left(&self) -> &ExprKind {
-        &self.inner().0
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
     }
 
-    pub(crate) fn right(&self) -> &ExprKind {
-        &self.inner().1
+    pub(crate) fn scrutinee(&self) -> &ExprKind {
+        &self.scrutinee
     }
 
-    fn inner(&self) -> &(ExprKind, ExprKind) {
-        &self.0
+    pub(crate) fn arms(&self) -> &[MatchArm] {
+        self.arms.as_slice()
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub(crate) struct Integer(i32);
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MatchArm {
+    pattern: Pattern,
+    guard: Option<ExprKind>,
+    body: ExprKind,
+    span: Span,
+}
 
-impl Integer {
-    pub(crate) fn new(value: i32) -> Integer {
-        Integer(value)
+impl MatchArm {
+    pub(crate) fn new(pattern: Pattern, body: ExprKind) -> MatchArm {
+        MatchArm {
+            pattern,
+            guard: None,
+            body,
+            span: Span::default(),
+        }
     }
 
-    pub(crate) fn value(&self) -> i32 {
-        self.0
+    pub(crate) fn with_span(self, span: Span) -> MatchArm {
+        MatchArm { span, ..self }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Sets the condition that must hold, in addition to the pattern, for
+    /// the arm to be taken.
+    pub(crate) fn with_guard(self, guard: Option<ExprKind>) -> MatchArm {
+        MatchArm { guard, ..self }
+    }
+
+    pub(crate) fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub(crate) fn guard(&self) -> Option<&ExprKind> {
+        self.guard.as_ref()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct If(Box<(ExprKind, ExprKind, ExprKind)>);
+pub(crate) enum Pattern {
+    Wildcard,
 
-impl If {
-    pub(crate) fn new(condition: ExprKind, consequent: ExprKind, alternative: ExprKind) -> If {
-        If(Box::new((condition, consequent, alternative)))
+    /// Matches anything, and binds the matched value to a name in the arm.
+    Binding(String),
+
+    Integer(i32),
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
+}
+
+impl Pattern {
+    pub(crate) fn variant(enum_name: String, variant: String, fields: Vec<Pattern>) -> Pattern {
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        }
     }
 
-    pub(crate) fn condition(&self) -> &ExprKind {
-        &self.inner().0
+    /// Tells whether the pattern matches any value, whatever it binds.
+    pub(crate) fn matches_anything(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Binding(_))
     }
 
-    pub(crate) fn consequent(&self) -> &ExprKind {
-        &self.inner().1
+    /// Returns the names the pattern binds, in the order they appear.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        match self {
+            Pattern::Binding(name) => vec![name],
+            Pattern::Wildcard | Pattern::Integer(_) => Vec::new(),
+            Pattern::Variant { fields, .. } => fields.iter().flat_map(Pattern::names).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Index(Box<ExprKind>, Box<ExprKind>, Span);
+
+impl Index {
+    pub(crate) fn new(array: ExprKind, index: ExprKind) -> Index {
+        Index(Box::new(array), Box::new(index), Span::default())
     }
 
-    pub(crate) fn alternative(&self) -> &ExprKind {
-        &self.inner().2
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
     }
 
-    fn inner(&self) -> &(ExprKind, ExprKind, ExprKind) {
+    pub(crate) fn array(&self) -> &ExprKind {
         &self.0
     }
+
+    pub(crate) fn index(&self) -> &ExprKind {
+        &self.1
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Bindings(Vec<Binding>, Box<ExprKind>);
+pub(crate) struct IndexAssignment(Index, Box<ExprKind>, Span);
 
-impl Bindings {
-    pub(crate) fn from_vec(bs: Vec<Binding>, next: ExprKind) -> Bindings {
-        Bindings(bs, Box::new(next))
+impl IndexAssignment {
+    pub(crate) fn new(target: Index, value: ExprKind) -> IndexAssignment {
+        IndexAssignment(target, Box::new(value), Span::default())
     }
 
-    pub(crate) fn defines(&self) -> &[Binding] {
-        self.0.as_slice()
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
     }
 
-    pub(crate) fn ending_expression(&self) -> &ExprKind {
+    pub(crate) fn target(&self) -> &Index {
+        &self.0
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
         &self.1
     }
 }
 
-#[cfg(test)]
-impl Bindings {
-    pub(crate) fn single(name: String, value: ExprKind, next: ExprKind) -> Bindings {
-        let binding = Binding::new(name, value);
-        Bindings(vec![binding], Box::new(next))
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Field(Box<ExprKind>, u16, Span);
+
+impl Field {
+    pub(crate) fn new(tuple: ExprKind, idx: u16) -> Field {
+        Field(Box::new(tuple), idx, Span::default())
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn tuple(&self) -> &ExprKind {
+        &self.0
+    }
+
+    pub(crate) fn idx(&self) -> u16 {
+        self.1
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Binding(String, ExprKind);
+pub(crate) struct Closure(Vec<String>, Box<ExprKind>, Span);
 
-impl Binding {
-    pub(crate) fn new(name: String, value: ExprKind) -> Binding {
-        Binding(name, value)
+impl Closure {
+    pub(crate) fn new(params: Vec<String>, body: ExprKind) -> Closure {
+        Closure(params, Box::new(body), Span::default())
     }
 
-    pub(crate) fn name(&self) -> &str {
-        self.0.as_str()
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
     }
 
-    pub(crate) fn value(&self) -> &ExprKind {
+    pub(crate) fn params(&self) -> &[String] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
         &self.1
     }
 }
 
+/// Functions defined in a block, and the rest of the block.
+///
+/// The functions can be called from anywhere in the block, including from
+/// each other, but do not see the variables of the block.
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Ident(String);
+pub(crate) struct LocalFunctions(Vec<Function>, Box<ExprKind>, Span);
 
-impl Ident {
-    pub(crate) fn new(name: String) -> Ident {
-        Ident(name)
+impl LocalFunctions {
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
     }
 
-    pub(crate) fn name(&self) -> &str {
-        self.0.as_str()
+    pub(crate) fn functions(&self) -> &[Function] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.1
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Bool(bool);
+/// A location in the source code, as a line and a column, both starting at 1.
+/// The file is known when the program was read from the disk.
+///
+/// The code a span covers starts `offset` bytes after the start of the file,
+/// and is `length` bytes long. Spans are copied into every node, so they are
+/// kept small: files are at most 4 GiB long.
+///
+/// Spans do not take part in comparisons: two nodes are equal if they
+/// represent the same code, no matter where it is written.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Span {
+    file: Option<Arc<SourceFile>>,
+    line: u32,
+    column: u32,
+    offset: u32,
+    length: u32,
+}
 
-impl Bool {
-    pub(crate) fn new(bool_: bool) -> Bool {
-        Bool(bool_)
+impl Span {
+    pub(crate) fn new(line: u32, column: usize) -> Span {
+        Span {
+            file: None,
+            line,
+            column: column as u32,
+            offset: 0,
+            length: 0,
+        }
     }
 
-    pub(crate) fn value(&self) -> bool {
-        self.0
+    /// Sets where the code starts, in bytes from the start of the file.
+    pub(crate) fn with_offset(self, offset: usize) -> Span {
+        Span {
+            offset: offset as u32,
+            ..self
+        }
+    }
+
+    /// Sets how long the code is, in bytes.
+    pub(crate) fn with_length(self, length: usize) -> Span {
+        Span {
+            length: length as u32,
+            ..self
+        }
+    }
+
+    pub(crate) fn in_file(self, file: Arc<SourceFile>) -> Span {
+        Span {
+            file: Some(file),
+            ..self
+        }
+    }
+
+    pub(crate) fn file(&self) -> Option<&SourceFile> {
+        self.file.as_deref()
+    }
+
+    pub(crate) fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub(crate) fn column(&self) -> usize {
+        self.column as usize
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.offset as usize
+    }
+
+    pub(crate) fn length(&self) -> usize {
+        self.length as usize
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.name())?;
+        }
+
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn children_are_in_evaluation_order() {
+        let expr: ExprKind = inline_expr! { if a { b } else { c } };
+
+        assert_eq!(
+            expr.children(),
+            [
+                &ExprKind::ident("a"),
+                &ExprKind::ident("b"),
+                &ExprKind::ident("c")
+            ]
+        );
+    }
+
+    #[test]
+    fn mapped_children_keep_their_location() {
+        let expr = ExprKind::return_(inline_expr! { a + b }).with_span(Span::new(1, 7));
+
+        let mapped = expr.map_children(|child| child.map_children(|_| ExprKind::integer(1)));
+
+        assert_eq!(mapped, ExprKind::return_(inline_expr! { 1 + 1 }));
+        assert_eq!(mapped.span().to_string(), "1:7");
     }
 }
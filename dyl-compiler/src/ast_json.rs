@@ -0,0 +1,611 @@
+//! Serializes the parsed AST to JSON, so that other tools can inspect it.
+//!
+//! Every node is an object. Items are grouped by category, the way a
+//! [`Program`] stores them, while expressions, types and patterns tell what
+//! they are with a `kind` field. The nodes that know where they are written
+//! have a `span` field, made of the file (`null` for code that does not come
+//! from a file), the line, the column, and the offset and length in bytes.
+//!
+//! The same JSON values describe the diagnostics, for the tools that read
+//! them.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::ast::{
+    Binding, BindingPattern, ComparisonOperator, Const, Enum, ExprKind, Function, Import,
+    LogicalOperator, Macro, MatchArm, Module, Pattern, Program, Span, Struct, Type,
+};
+
+pub(crate) fn program_to_json(program: &Program) -> String {
+    program.to_json().to_string()
+}
+
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(i) => write!(f, "{}", i),
+            Json::Str(s) => write_str(s, f),
+
+            Json::Array(elements) => {
+                write!(f, "[")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (idx, (name, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "\"{}\":{}", name, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(s: &str, f: &mut Formatter) -> FmtResult {
+    write!(f, "\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(i: i32) -> Json {
+        Json::Int(i.into())
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::Str(s.to_owned())
+    }
+}
+
+/// Creates the object of an expression, a type or a pattern.
+fn node(kind: &str, fields: Vec<(&'static str, Json)>) -> Json {
+    let mut object = vec![("kind", kind.into())];
+    object.extend(fields);
+
+    Json::Object(object)
+}
+
+pub(crate) fn array<'a, T: ToJson + 'a>(elements: impl IntoIterator<Item = &'a T>) -> Json {
+    Json::Array(elements.into_iter().map(ToJson::to_json).collect())
+}
+
+fn names(names: &[String]) -> Json {
+    Json::Array(names.iter().map(|name| name.as_str().into()).collect())
+}
+
+fn optional<T: ToJson>(value: Option<&T>) -> Json {
+    value.map_or(Json::Null, ToJson::to_json)
+}
+
+pub(crate) trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+impl ToJson for Program {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("functions", array(self.functions())),
+            ("structs", array(self.structs())),
+            ("enums", array(self.enums())),
+            ("consts", array(self.consts())),
+            ("modules", array(self.modules())),
+            ("imports", array(self.imports())),
+            ("macros", array(self.macros())),
+        ])
+    }
+}
+
+impl ToJson for Function {
+    fn to_json(&self) -> Json {
+        let args = self
+            .args()
+            .iter()
+            .zip(self.arg_types())
+            .map(|(name, ty)| {
+                Json::Object(vec![
+                    ("name", name.as_str().into()),
+                    ("type", optional(ty.as_ref())),
+                ])
+            })
+            .collect();
+
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("generics", names(self.generics())),
+            ("args", Json::Array(args)),
+            ("ret_type", optional(self.ret_type())),
+            ("body", self.body().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Struct {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("fields", names(self.fields())),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Enum {
+    fn to_json(&self) -> Json {
+        let variants = self
+            .variants()
+            .iter()
+            .map(|variant| {
+                Json::Object(vec![
+                    ("name", variant.name().into()),
+                    ("fields", names(variant.fields())),
+                    ("span", variant.span().to_json()),
+                ])
+            })
+            .collect();
+
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("variants", Json::Array(variants)),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Const {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("value", self.value().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Module {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("content", self.content().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Macro {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("name", self.name().into()),
+            ("params", names(self.params())),
+            ("body", self.body().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Import {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("path", names(self.path())),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Type {
+    fn to_json(&self) -> Json {
+        match self {
+            Type::Named(name) => node("Named", vec![("name", name.as_str().into())]),
+            Type::Tuple(elements) => node("Tuple", vec![("elements", array(elements))]),
+            Type::Array(element) => node("Array", vec![("element", element.to_json())]),
+        }
+    }
+}
+
+impl ToJson for Span {
+    fn to_json(&self) -> Json {
+        let file = self.file().map_or(Json::Null, |file| file.name().into());
+
+        Json::Object(vec![
+            ("file", file),
+            ("line", Json::Int(self.line().into())),
+            ("column", Json::Int(self.column() as i64)),
+            ("offset", Json::Int(self.offset() as i64)),
+            ("length", Json::Int(self.length() as i64)),
+        ])
+    }
+}
+
+impl ToJson for ExprKind {
+    fn to_json(&self) -> Json {
+        let json = match self {
+            ExprKind::Addition(a) => binary("Addition", a.left(), a.right()),
+            ExprKind::Subtraction(s) => binary("Subtraction", s.left(), s.right()),
+            ExprKind::Multiplication(m) => binary("Multiplication", m.left(), m.right()),
+            ExprKind::Exponentiation(e) => binary("Exponentiation", e.left(), e.right()),
+
+            ExprKind::Comparison(c) => {
+                let operator = match c.operator() {
+                    ComparisonOperator::Equal => "==",
+                    ComparisonOperator::NotEqual => "!=",
+                    ComparisonOperator::Less => "<",
+                    ComparisonOperator::LessOrEqual => "<=",
+                    ComparisonOperator::Greater => ">",
+                    ComparisonOperator::GreaterOrEqual => ">=",
+                };
+
+                with_operator(binary("Comparison", c.left(), c.right()), operator)
+            }
+
+            ExprKind::Logical(l) => {
+                let operator = match l.operator() {
+                    LogicalOperator::And => "&&",
+                    LogicalOperator::Or => "||",
+                };
+
+                with_operator(binary("Logical", l.left(), l.right()), operator)
+            }
+
+            ExprKind::Cast(c) => node(
+                "Cast",
+                vec![
+                    ("value", c.value().to_json()),
+                    ("target", c.target().to_json()),
+                ],
+            ),
+
+            ExprKind::Integer(i) => node("Integer", vec![("value", i.value().into())]),
+
+            ExprKind::If(i) => node(
+                "If",
+                vec![
+                    ("condition", i.condition().to_json()),
+                    ("consequent", i.consequent().to_json()),
+                    ("alternative", optional(i.alternative())),
+                ],
+            ),
+
+            ExprKind::Bindings(b) => node(
+                "Bindings",
+                vec![
+                    ("bindings", array(b.defines())),
+                    ("ending", b.ending_expression().to_json()),
+                    ("ending_span", b.ending_span().to_json()),
+                ],
+            ),
+
+            ExprKind::Statements(s) => {
+                let spans = (0..=s.statements().len()).map(|idx| s.span(idx));
+
+                node(
+                    "Statements",
+                    vec![
+                        ("statements", array(s.statements())),
+                        ("ending", s.ending_expression().to_json()),
+                        ("spans", array(spans)),
+                    ],
+                )
+            }
+
+            ExprKind::Ident(i) => node("Ident", vec![("name", i.name().into())]),
+
+            ExprKind::Bool(b) => node("Bool", vec![("value", b.value().into())]),
+
+            ExprKind::String(s) => node("String", vec![("value", s.value().into())]),
+
+            ExprKind::FnCall(c) => node(
+                "FnCall",
+                vec![("name", c.name().into()), ("args", array(c.args()))],
+            ),
+
+            ExprKind::MacroCall(m) => node(
+                "MacroCall",
+                vec![("name", m.name().into()), ("args", array(m.args()))],
+            ),
+
+            ExprKind::Return(r) => node("Return", vec![("value", r.value().to_json())]),
+
+            ExprKind::Assignment(a) => node(
+                "Assignment",
+                vec![("name", a.name().into()), ("value", a.value().to_json())],
+            ),
+
+            ExprKind::CompoundAssignment(a) => with_operator(
+                node(
+                    "CompoundAssignment",
+                    vec![("name", a.name().into()), ("value", a.value().to_json())],
+                ),
+                a.operator().assignment_token(),
+            ),
+
+            ExprKind::Tuple(t) => node("Tuple", vec![("elements", array(t.elements()))]),
+
+            ExprKind::Field(f) => node(
+                "Field",
+                vec![
+                    ("tuple", f.tuple().to_json()),
+                    ("idx", Json::Int(f.idx().into())),
+                ],
+            ),
+
+            ExprKind::Array(a) => node("Array", vec![("elements", array(a.elements()))]),
+
+            ExprKind::Index(i) => node(
+                "Index",
+                vec![
+                    ("array", i.array().to_json()),
+                    ("index", i.index().to_json()),
+                ],
+            ),
+
+            ExprKind::IndexAssignment(a) => node(
+                "IndexAssignment",
+                vec![
+                    ("array", a.target().array().to_json()),
+                    ("index", a.target().index().to_json()),
+                    ("value", a.value().to_json()),
+                ],
+            ),
+
+            ExprKind::StructLiteral(s) => {
+                let fields = s
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| {
+                        Json::Object(vec![
+                            ("name", name.as_str().into()),
+                            ("value", value.to_json()),
+                        ])
+                    })
+                    .collect();
+
+                node(
+                    "StructLiteral",
+                    vec![("name", s.name().into()), ("fields", Json::Array(fields))],
+                )
+            }
+
+            ExprKind::VariantLiteral(v) => {
+                let args = if v.is_parenthesized() {
+                    array(v.args())
+                } else {
+                    Json::Null
+                };
+
+                node(
+                    "VariantLiteral",
+                    vec![
+                        ("enum", v.enum_name().into()),
+                        ("variant", v.variant().into()),
+                        ("args", args),
+                    ],
+                )
+            }
+
+            ExprKind::Match(m) => node(
+                "Match",
+                vec![
+                    ("scrutinee", m.scrutinee().to_json()),
+                    ("arms", array(m.arms())),
+                ],
+            ),
+
+            ExprKind::Closure(c) => node(
+                "Closure",
+                vec![("params", names(c.params())), ("body", c.body().to_json())],
+            ),
+
+            ExprKind::LocalFunctions(l) => node(
+                "LocalFunctions",
+                vec![
+                    ("functions", array(l.functions())),
+                    ("body", l.body().to_json()),
+                ],
+            ),
+        };
+
+        match self {
+            // Their statements and bindings are located instead.
+            ExprKind::Bindings(_) | ExprKind::Statements(_) => json,
+            _ => located(json, self.span()),
+        }
+    }
+}
+
+fn binary(kind: &str, left: &ExprKind, right: &ExprKind) -> Json {
+    node(
+        kind,
+        vec![("left", left.to_json()), ("right", right.to_json())],
+    )
+}
+
+/// Adds the location of a node, after its other fields.
+fn located(json: Json, span: &Span) -> Json {
+    match json {
+        Json::Object(mut fields) => {
+            fields.push(("span", span.to_json()));
+            Json::Object(fields)
+        }
+        json => json,
+    }
+}
+
+/// Adds the operator of a comparison or of a logical operation, right after
+/// its kind.
+fn with_operator(json: Json, operator: &str) -> Json {
+    match json {
+        Json::Object(mut fields) => {
+            fields.insert(1, ("operator", operator.into()));
+            Json::Object(fields)
+        }
+        json => json,
+    }
+}
+
+impl ToJson for Binding {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("pattern", self.pattern().to_json()),
+            ("mutable", self.is_mutable().into()),
+            ("type", optional(self.annotation())),
+            ("value", self.value().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for BindingPattern {
+    fn to_json(&self) -> Json {
+        match self {
+            BindingPattern::Name(name) => node("Name", vec![("name", name.as_str().into())]),
+
+            BindingPattern::Wildcard => node("Wildcard", Vec::new()),
+
+            BindingPattern::Tuple(elements) => node("Tuple", vec![("elements", array(elements))]),
+
+            BindingPattern::Struct { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, pattern)| {
+                        Json::Object(vec![
+                            ("name", name.as_str().into()),
+                            ("pattern", pattern.to_json()),
+                        ])
+                    })
+                    .collect();
+
+                node(
+                    "Struct",
+                    vec![
+                        ("name", name.as_str().into()),
+                        ("fields", Json::Array(fields)),
+                    ],
+                )
+            }
+        }
+    }
+}
+
+impl ToJson for MatchArm {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("pattern", self.pattern().to_json()),
+            ("guard", optional(self.guard())),
+            ("body", self.body().to_json()),
+            ("span", self.span().to_json()),
+        ])
+    }
+}
+
+impl ToJson for Pattern {
+    fn to_json(&self) -> Json {
+        match self {
+            Pattern::Wildcard => node("Wildcard", Vec::new()),
+
+            Pattern::Binding(name) => node("Binding", vec![("name", name.as_str().into())]),
+
+            Pattern::Integer(value) => node("Integer", vec![("value", (*value).into())]),
+
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => node(
+                "Variant",
+                vec![
+                    ("enum", enum_name.as_str().into()),
+                    ("variant", variant.as_str().into()),
+                    ("fields", array(fields)),
+                ],
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
+
+    use super::*;
+
+    fn json_of(code: &str) -> String {
+        let (_, program) = parser::parse_input(code).unwrap();
+        program_to_json(&program)
+    }
+
+    #[test]
+    fn function() {
+        assert_eq!(
+            json_of("fn f(a: int) { a + 1 }"),
+            "{\"functions\":[{\"name\":\"f\",\"generics\":[],\
+             \"args\":[{\"name\":\"a\",\"type\":{\"kind\":\"Named\",\"name\":\"int\"}}],\
+             \"ret_type\":null,\
+             \"body\":{\"kind\":\"Addition\",\
+             \"left\":{\"kind\":\"Ident\",\"name\":\"a\",\
+             \"span\":{\"file\":null,\"line\":1,\"column\":16,\"offset\":15,\"length\":1}},\
+             \"right\":{\"kind\":\"Integer\",\"value\":1,\
+             \"span\":{\"file\":null,\"line\":1,\"column\":20,\"offset\":19,\"length\":1}},\
+             \"span\":{\"file\":null,\"line\":1,\"column\":18,\"offset\":17,\"length\":1}},\
+             \"span\":{\"file\":null,\"line\":1,\"column\":4,\"offset\":3,\"length\":1}}],\
+             \"structs\":[],\"enums\":[],\"consts\":[],\"modules\":[],\"imports\":[],\"macros\":[]}"
+        );
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let json = json_of("const S = \"a\\\"b\\\\c\";");
+
+        assert!(json.contains("{\"kind\":\"String\",\"value\":\"a\\\"b\\\\c\","));
+    }
+
+    #[test]
+    fn statements_keep_their_spans() {
+        let json = json_of("fn main() {\n    print(1);\n    2\n}");
+
+        assert!(json.contains(
+            "\"spans\":[{\"file\":null,\"line\":2,\"column\":5,\"offset\":16,\"length\":8},\
+             {\"file\":null,\"line\":3,\"column\":5,\"offset\":30,\"length\":1}]"
+        ));
+    }
+}
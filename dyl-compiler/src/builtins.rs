@@ -0,0 +1,112 @@
+use crate::ast::Type;
+
+/// A function provided by the language.
+///
+/// Builtins are lowered to dedicated instructions instead of a call. A
+/// function declared in the program takes precedence over a builtin with
+/// the same name.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Builtin {
+    /// Writes a value to the output stream.
+    Print,
+
+    /// Writes a value to the output stream, followed by a newline.
+    Println,
+
+    /// Reads an integer from the input stream.
+    ReadInt,
+
+    /// Stops the program with an error pointing at the call if a condition
+    /// does not hold.
+    Assert,
+
+    /// Stops the program with an error carrying a message.
+    Panic,
+
+    /// Returns the number of characters of a string, or the number of
+    /// elements of an array.
+    Len,
+
+    /// Returns the part of a string made of a given amount of characters,
+    /// starting at a given index.
+    Substr,
+
+    /// Returns the character of a string at a given index, as a string.
+    CharAt,
+
+    /// Appends an element at the end of an array.
+    Push,
+
+    /// Removes the last element of an array and returns it.
+    Pop,
+}
+
+impl Builtin {
+    const ALL: [Builtin; 10] = [
+        Builtin::Print,
+        Builtin::Println,
+        Builtin::ReadInt,
+        Builtin::Assert,
+        Builtin::Panic,
+        Builtin::Len,
+        Builtin::Substr,
+        Builtin::CharAt,
+        Builtin::Push,
+        Builtin::Pop,
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL
+            .iter()
+            .copied()
+            .find(|builtin| builtin.name() == name)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Println => "println",
+            Builtin::ReadInt => "read_int",
+            Builtin::Assert => "assert",
+            Builtin::Panic => "panic",
+            Builtin::Len => "len",
+            Builtin::Substr => "substr",
+            Builtin::CharAt => "char_at",
+            Builtin::Push => "push",
+            Builtin::Pop => "pop",
+        }
+    }
+
+    pub(crate) fn arity(self) -> usize {
+        match self {
+            Builtin::Print
+            | Builtin::Println
+            | Builtin::Assert
+            | Builtin::Panic
+            | Builtin::Len
+            | Builtin::Pop => 1,
+            Builtin::ReadInt => 0,
+            Builtin::CharAt | Builtin::Push => 2,
+            Builtin::Substr => 3,
+        }
+    }
+
+    /// Returns the type of the value the builtin evaluates to when called
+    /// with arguments of the given types, or `None` if it never returns or if
+    /// this type can not be known.
+    pub(crate) fn ret_type(self, arg_types: &[Option<Type>]) -> Option<Type> {
+        match self {
+            Builtin::Print | Builtin::Println | Builtin::Assert | Builtin::Push => {
+                Some(Type::Tuple(Vec::new()))
+            }
+            Builtin::ReadInt | Builtin::Len => Some(Type::named("int")),
+            Builtin::Substr | Builtin::CharAt => Some(Type::named("string")),
+            Builtin::Panic => None,
+
+            Builtin::Pop => match arg_types.first() {
+                Some(Some(Type::Array(element))) => Some(element.as_ref().clone()),
+                _ => None,
+            },
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::ast::{Closure, ExprKind};
+
+/// Returns the variables a closure refers to without defining them, in the
+/// order they first appear in its body.
+///
+/// Some of them may not be variables of the enclosing function, but
+/// constants or undefined names. Telling them apart is left to the caller.
+pub(crate) fn free_variables(closure: &Closure) -> Vec<String> {
+    free_variables_with(closure.params().to_vec(), closure.body())
+}
+
+/// Returns the variables an expression refers to without defining them, in
+/// the order they first appear in it.
+pub(crate) fn free_variables_of(expr: &ExprKind) -> Vec<String> {
+    free_variables_with(Vec::new(), expr)
+}
+
+fn free_variables_with(bound: Vec<String>, expr: &ExprKind) -> Vec<String> {
+    let mut collector = FreeVariables {
+        bound,
+        free: Vec::new(),
+    };
+
+    collector.visit(expr);
+
+    collector.free
+}
+
+struct FreeVariables {
+    /// The variables defined in the closure so far, in scope at the current
+    /// position.
+    bound: Vec<String>,
+
+    free: Vec<String>,
+}
+
+impl FreeVariables {
+    fn visit(&mut self, expr: &ExprKind) {
+        match expr {
+            ExprKind::Ident(ident) => self.use_(ident.name()),
+
+            ExprKind::Assignment(assignment) => {
+                self.visit(assignment.value());
+                self.use_(assignment.name());
+            }
+
+            ExprKind::FnCall(call) => {
+                // The callee may be a closure stored in a variable.
+                self.use_(call.name());
+                self.visit_all(call.args());
+            }
+
+            ExprKind::Bindings(bindings) => {
+                let scope_start = self.bound.len();
+
+                for binding in bindings.defines() {
+                    self.visit(binding.value());
+                    self.bound
+                        .extend(binding.names().into_iter().map(str::to_owned));
+                }
+
+                self.visit(bindings.ending_expression());
+                self.bound.truncate(scope_start);
+            }
+
+            ExprKind::Closure(closure) => {
+                let scope_start = self.bound.len();
+
+                self.bound.extend(closure.params().iter().cloned());
+                self.visit(closure.body());
+                self.bound.truncate(scope_start);
+            }
+
+            ExprKind::Match(match_) => {
+                self.visit(match_.scrutinee());
+
+                for arm in match_.arms() {
+                    let scope_start = self.bound.len();
+
+                    self.bound
+                        .extend(arm.pattern().names().into_iter().map(str::to_owned));
+                    self.visit_all(arm.guard());
+                    self.visit(arm.body());
+                    self.bound.truncate(scope_start);
+                }
+            }
+
+            // Local functions can not refer to the variables of the block, so
+            // only the body that follows them is walked.
+            _ => self.visit_all(expr.children()),
+        }
+    }
+
+    fn visit_all<'a>(&mut self, exprs: impl IntoIterator<Item = &'a ExprKind>) {
+        exprs.into_iter().for_each(|expr| self.visit(expr));
+    }
+
+    fn use_(&mut self, name: &str) {
+        let is_bound = self.bound.iter().any(|bound| bound == name);
+        let is_known = self.free.iter().any(|free| free == name);
+
+        if !is_bound && !is_known {
+            self.free.push(name.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{MatchArm, Pattern},
+        inline_expr,
+    };
+
+    use super::*;
+
+    fn free(params: &[&str], body: ExprKind) -> Vec<String> {
+        let params = params.iter().map(|p| p.to_string()).collect();
+
+        free_variables(&Closure::new(params, body))
+    }
+
+    #[test]
+    fn params_are_not_free() {
+        assert_eq!(free(&["x"], inline_expr! { x + y + x + z + y }), ["y", "z"]);
+    }
+
+    #[test]
+    fn bindings_are_not_free() {
+        let body = inline_expr! {
+            {
+                let a = b;
+                a + c
+            }
+        };
+
+        assert_eq!(free(&[], body), ["b", "c"]);
+    }
+
+    #[test]
+    fn binding_is_not_in_scope_of_its_value() {
+        let body = inline_expr! {
+            {
+                let a = a;
+                a
+            }
+        };
+
+        assert_eq!(free(&[], body), ["a"]);
+    }
+
+    #[test]
+    fn nested_closure() {
+        let body = ExprKind::closure(vec!["y".to_owned()], inline_expr! { x + y + z });
+
+        assert_eq!(free(&["x"], body), ["z"]);
+    }
+
+    #[test]
+    fn pattern_bindings_are_not_free() {
+        let body = ExprKind::match_(
+            ExprKind::ident("o".to_owned()),
+            vec![MatchArm::new(
+                Pattern::Binding("x".to_owned()),
+                inline_expr! { x + y },
+            )],
+        );
+
+        assert_eq!(free(&[], body), ["o", "y"]);
+    }
+
+    #[test]
+    fn called_variable() {
+        assert_eq!(free(&["x"], inline_expr! { f(x, y) }), ["f", "y"]);
+    }
+}
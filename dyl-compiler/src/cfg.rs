@@ -0,0 +1,223 @@
+//! The control-flow graph of the basic blocks.
+//!
+//! Its nodes are the blocks of the IR, identified by their index, and there
+//! is an edge from a block to every block control may go to when it ends.
+//! The graph can be exported to the Graphviz dot format.
+
+use std::fmt::Write;
+
+use crate::{
+    instruction::{CondJmp, Goto, Instruction, JmpIfNotZero, JmpIfZero},
+    ir,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Cfg {
+    successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    pub(crate) fn new(program: &ir::Program) -> Cfg {
+        let successors = (0..program.blocks().len())
+            .map(|block| successors_of(program, block))
+            .collect::<Vec<_>>();
+
+        let mut predecessors = vec![Vec::new(); successors.len()];
+        for (block, successors) in successors.iter().enumerate() {
+            successors
+                .iter()
+                .for_each(|successor| predecessors[*successor].push(block));
+        }
+
+        Cfg {
+            successors,
+            predecessors,
+        }
+    }
+
+    /// Returns the blocks control may go to when `block` ends.
+    pub(crate) fn successors(&self, block: usize) -> &[usize] {
+        &self.successors[block]
+    }
+
+    /// Returns the blocks that may be executed right before `block`.
+    pub(crate) fn predecessors(&self, block: usize) -> &[usize] {
+        &self.predecessors[block]
+    }
+
+    /// Writes the graph in the Graphviz dot format, each node showing the
+    /// instructions of its block.
+    pub(crate) fn to_dot(&self, program: &ir::Program) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+        for (idx, block) in program.blocks().iter().enumerate() {
+            let mut label = format!("{}:\\l", escape(&block.header(idx)));
+            for instruction in block.instructions() {
+                write!(label, "    {}\\l", escape(&instruction.to_string())).unwrap();
+            }
+
+            writeln!(dot, "    b{} [label=\"{}\"];", idx, label).unwrap();
+        }
+
+        for (block, successors) in self.successors.iter().enumerate() {
+            for successor in successors {
+                writeln!(dot, "    b{} -> b{};", block, successor).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn successors_of(program: &ir::Program, block: usize) -> Vec<usize> {
+    let (targets, falls_through) = match program.blocks()[block].instructions().last() {
+        Some(Instruction::Goto(Goto(label))) => (vec![*label], false),
+        Some(Instruction::CondJmp(CondJmp(negative, null, positive))) => {
+            (vec![*negative, *null, *positive], false)
+        }
+        Some(Instruction::JmpIfZero(JmpIfZero(label)))
+        | Some(Instruction::JmpIfNotZero(JmpIfNotZero(label))) => (vec![*label], true),
+        Some(instruction) if ir::ends_block(instruction) => (Vec::new(), false),
+        _ => (Vec::new(), true),
+    };
+
+    let mut successors = Vec::new();
+    for label in targets {
+        let successor = program
+            .block_of(label)
+            .expect("Jump targets are placed in the code");
+
+        if !successors.contains(&successor) {
+            successors.push(successor);
+        }
+    }
+
+    if falls_through && block + 1 < program.blocks().len() && !successors.contains(&(block + 1)) {
+        successors.push(block + 1);
+    }
+
+    successors
+}
+
+/// Escapes the characters that end or break a dot string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        context::LabelContext, lowering, monomorphization, parser, resolution, type_checker,
+    };
+
+    use super::*;
+
+    fn program_of(code: &str) -> ir::Program {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (ctxt, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+        let ctxt = ctxt.into_typing_context().into_lowering_context();
+        let program = type_checker::type_program(&program);
+
+        let (_, program) = lowering::lower_program(&program, ctxt).unwrap();
+        program
+    }
+
+    #[test]
+    fn function_calls_are_not_edges() {
+        let cfg = Cfg::new(&program_of("fn main() { 42 }"));
+
+        assert_eq!(cfg.successors(0), [] as [usize; 0]);
+        assert_eq!(cfg.predecessors(1), [] as [usize; 0]);
+    }
+
+    #[test]
+    fn jumps_and_fallthrough() {
+        let mut labels = LabelContext::default();
+        let (target, end) = (labels.new_anonymous(), labels.new_anonymous());
+        labels.set_position(target, 2).unwrap();
+        labels.set_position(end, 3).unwrap();
+
+        let program = ir::Program::from_instructions(
+            vec![
+                Instruction::push_i(1),
+                Instruction::cond_jmp(target, end, target),
+                Instruction::push_i(2),
+                Instruction::push_i(3),
+                Instruction::goto(target),
+            ],
+            &labels,
+        );
+        let cfg = Cfg::new(&program);
+
+        assert_eq!(cfg.successors(0), [1, 2]);
+        assert_eq!(cfg.successors(1), [2]);
+        assert_eq!(cfg.successors(2), [1]);
+        assert_eq!(cfg.predecessors(1), [0, 2]);
+        assert_eq!(cfg.predecessors(2), [0, 1]);
+    }
+
+    #[test]
+    fn conditional_jumps_fall_through() {
+        let mut labels = LabelContext::default();
+        let target = labels.new_anonymous();
+        labels.set_position(target, 3).unwrap();
+
+        let program = ir::Program::from_instructions(
+            vec![
+                Instruction::push_i(1),
+                Instruction::jmp_if_zero(target),
+                Instruction::push_i(2),
+                Instruction::push_i(3),
+            ],
+            &labels,
+        );
+        let cfg = Cfg::new(&program);
+
+        assert_eq!(program.blocks().len(), 3);
+        assert_eq!(cfg.successors(0), [2, 1]);
+        assert_eq!(cfg.successors(1), [2]);
+    }
+
+    #[test]
+    fn edges_are_symmetric() {
+        let program = program_of(
+            "fn f(a) { if a == 0 { 1 } else { match a { 1 => 2, _ => 3 } } }
+             fn main() { let g = |x| f(x) + 1; g(2) }",
+        );
+        let cfg = Cfg::new(&program);
+
+        for block in 0..program.blocks().len() {
+            for successor in cfg.successors(block) {
+                assert!(cfg.predecessors(*successor).contains(&block));
+            }
+
+            for predecessor in cfg.predecessors(block) {
+                assert!(cfg.successors(*predecessor).contains(&block));
+            }
+        }
+    }
+
+    #[test]
+    fn dot_export() {
+        let program = program_of(r#"fn main() { print("hi"); 0 }"#);
+        let dot = Cfg::new(&program).to_dot(&program);
+
+        assert_eq!(
+            dot,
+            "digraph cfg {\n    \
+                 node [shape=box, fontname=monospace];\n    \
+                 b0 [label=\"block 0:\\l    res_v 1\\l    call L0\\l    f_stop\\l\"];\n    \
+                 b1 [label=\"block 1 (labels L0):\\l    push_const 0\\l    \
+                 out\\l    pack 0\\l    pop 1\\l    push_i 0\\l    pop_copy 2\\l    ret 1 0\\l\"];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(escape(r#"PushS("\"hi\"")"#), r#"PushS(\"\\\"hi\\\"\")"#);
+    }
+}
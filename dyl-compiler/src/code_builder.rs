@@ -0,0 +1,81 @@
+//! Collects the instructions emitted by the lowering pass.
+//!
+//! Jumps, calls and closures refer to labels instead of addresses. A label is
+//! placed where the next instruction will be emitted, which may come after
+//! the jumps that go to it, and every label is replaced by its address once
+//! the whole program has been emitted (see [`context::resolve_labels`]).
+//!
+//! [`context::resolve_labels`]: crate::context::resolve_labels
+
+use crate::{context::LabelContext, instruction::Instruction};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CodeBuilder {
+    instructions: Vec<Instruction>,
+}
+
+impl CodeBuilder {
+    pub(crate) fn push(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    pub(crate) fn extend<I>(&mut self, instructions: I)
+    where
+        I: IntoIterator<Item = Instruction>,
+    {
+        self.instructions.extend(instructions);
+    }
+
+    /// Returns the address of the next emitted instruction.
+    pub(crate) fn position(&self) -> u32 {
+        self.instructions.len() as u32
+    }
+
+    /// Places a label on the next emitted instruction.
+    ///
+    /// Panics if the label has already been placed.
+    pub(crate) fn place(&self, label: u32, labels: &mut LabelContext) {
+        labels
+            .set_position(label, self.position())
+            .expect("Label placed twice");
+    }
+
+    #[cfg(test)]
+    pub(crate) fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub(crate) fn into_instructions(self) -> Vec<Instruction> {
+        self.instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_placed_on_the_next_instruction() {
+        let mut labels = LabelContext::default();
+        let end = labels.new_anonymous();
+
+        let mut code = CodeBuilder::default();
+        code.push(Instruction::goto(end));
+        code.push(Instruction::push_i(1));
+        code.place(end, &mut labels);
+        code.push(Instruction::f_stop());
+
+        assert_eq!(labels.resolve_anonymous(end).unwrap(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label placed twice")]
+    fn labels_are_placed_once() {
+        let mut labels = LabelContext::default();
+        let label = labels.new_anonymous();
+
+        let code = CodeBuilder::default();
+        code.place(label, &mut labels);
+        code.place(label, &mut labels);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{Const, ExprKind, Span},
+    context::ErrorContext,
+    diagnostic::{Code, Diagnostic},
+};
+
+/// A value computed at compile time.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ConstValue {
+    Int(i32),
+    Bool(bool),
+    Str(String),
+}
+
+impl ConstValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            ConstValue::Int(_) => "int",
+            ConstValue::Bool(_) => "bool",
+            ConstValue::Str(_) => "string",
+        }
+    }
+}
+
+/// Evaluates every constant of a program.
+///
+/// Constants may refer to each other, in any order. Each constant that can
+/// not be evaluated is reported once.
+pub(crate) fn evaluate_consts(
+    consts: &[Const],
+    errors: &ErrorContext,
+) -> Vec<(String, Result<ConstValue, ()>)> {
+    let mut evaluator = Evaluator {
+        definitions: consts.iter().map(|c| (c.name(), c)).collect(),
+        values: HashMap::new(),
+        in_progress: Vec::new(),
+        errors,
+    };
+
+    consts
+        .iter()
+        .map(|c| (c.name().to_owned(), evaluator.evaluate_const(c.name())))
+        .collect()
+}
+
+struct Evaluator<'a> {
+    definitions: HashMap<&'a str, &'a Const>,
+
+    /// The result of the constants evaluated so far. Failed evaluations are
+    /// stored as well, so that their errors are reported only once.
+    values: HashMap<&'a str, Option<ConstValue>>,
+
+    /// The constants being evaluated, outermost first. A constant that
+    /// appears twice in this list depends on itself.
+    in_progress: Vec<&'a str>,
+
+    errors: &'a ErrorContext,
+}
+
+impl<'a> Evaluator<'a> {
+    fn error(&self, span: &Span, message: impl Into<String>) {
+        let error = Diagnostic::error(message)
+            .with_code(Code::ConstantEvaluation)
+            .with_span(span.clone());
+        self.errors.add(error);
+    }
+
+    fn evaluate_const(&mut self, name: &'a str) -> Result<ConstValue, ()> {
+        if let Some(value) = self.values.get(name) {
+            return value.clone().ok_or(());
+        }
+
+        if let Some(start) = self.in_progress.iter().position(|n| *n == name) {
+            let cycle = self.in_progress[start..]
+                .iter()
+                .chain(Some(&name))
+                .map(|n| format!("`{}`", n))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            self.error(
+                self.definitions[name].span(),
+                format!(
+                    "Cycle detected when evaluating constant `{}`: {}",
+                    name, cycle
+                ),
+            );
+
+            return Err(());
+        }
+
+        let definition = self.definitions[name];
+
+        self.in_progress.push(name);
+        let value = self.evaluate(definition.value());
+        self.in_progress.pop();
+
+        self.values.insert(name, value.clone().ok());
+        value
+    }
+
+    fn evaluate(&mut self, expr: &'a ExprKind) -> Result<ConstValue, ()> {
+        match expr {
+            ExprKind::Integer(i) => Ok(ConstValue::Int(i.value())),
+            ExprKind::Bool(b) => Ok(ConstValue::Bool(b.value())),
+            ExprKind::String(s) => Ok(ConstValue::Str(s.value().to_owned())),
+
+            ExprKind::Addition(a) => {
+                self.evaluate_arithmetic(expr, a.left(), a.right(), "add", |l, r| {
+                    Some(l.wrapping_add(r))
+                })
+            }
+            ExprKind::Subtraction(s) => {
+                self.evaluate_arithmetic(expr, s.left(), s.right(), "subtract", |l, r| {
+                    Some(l.wrapping_sub(r))
+                })
+            }
+            ExprKind::Multiplication(m) => {
+                self.evaluate_arithmetic(expr, m.left(), m.right(), "multiply", i32::checked_mul)
+            }
+            ExprKind::Exponentiation(e) => {
+                self.evaluate_arithmetic(expr, e.left(), e.right(), "exponentiate", |l, r| {
+                    u32::try_from(r).ok().and_then(|r| l.checked_pow(r))
+                })
+            }
+
+            ExprKind::Cast(c) => match self.evaluate(c.value())? {
+                ConstValue::Int(i) if c.target().is_numeric() => Ok(ConstValue::Int(i)),
+                ConstValue::Bool(b) if c.target().is_numeric() => Ok(ConstValue::Int(b as i32)),
+
+                value => {
+                    self.error(
+                        expr.span(),
+                        format!(
+                            "Cannot cast `{}` as `{}` in constant",
+                            value.type_name(),
+                            c.target()
+                        ),
+                    );
+                    Err(())
+                }
+            },
+
+            ExprKind::Ident(ident) => match self.definitions.get_key_value(ident.name()) {
+                Some((name, _)) => self.evaluate_const(name),
+                None => {
+                    self.error(
+                        expr.span(),
+                        format!("Cannot find constant `{}` in this scope", ident.name()),
+                    );
+                    Err(())
+                }
+            },
+
+            _ => {
+                self.error(
+                    expr.span(),
+                    "Expression cannot be evaluated at compile time",
+                );
+                Err(())
+            }
+        }
+    }
+
+    fn evaluate_arithmetic(
+        &mut self,
+        expr: &'a ExprKind,
+        left: &'a ExprKind,
+        right: &'a ExprKind,
+        operation: &str,
+        f: impl Fn(i32, i32) -> Option<i32>,
+    ) -> Result<ConstValue, ()> {
+        let left = self.evaluate(left);
+        let right = self.evaluate(right);
+
+        match (left?, right?) {
+            (ConstValue::Int(l), ConstValue::Int(r)) => {
+                f(l, r).map(ConstValue::Int).ok_or_else(|| {
+                    self.error(
+                        expr.span(),
+                        format!("Attempt to {} with overflow in constant", operation),
+                    )
+                })
+            }
+
+            (l, r) => {
+                self.error(
+                    expr.span(),
+                    format!(
+                        "Cannot {} `{}` and `{}` in constant",
+                        operation,
+                        l.type_name(),
+                        r.type_name()
+                    ),
+                );
+                Err(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast::Type, inline_expr};
+
+    use super::*;
+
+    type Values = Vec<(String, Result<ConstValue, ()>)>;
+
+    fn eval(consts: Vec<(&str, ExprKind)>) -> (Values, String) {
+        let consts = consts
+            .into_iter()
+            .map(|(name, value)| Const::new(name.to_owned(), value))
+            .collect::<Vec<_>>();
+        let errors = ErrorContext::default();
+
+        let values = evaluate_consts(&consts, &errors);
+
+        (values, errors.to_string())
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (values, errors) = eval(vec![("N", inline_expr! { 2 * (3 + 4) - 1 })]);
+
+        assert_eq!(values, [("N".to_owned(), Ok(ConstValue::Int(13)))]);
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn cast() {
+        let int = || Type::named("int");
+        let (values, errors) = eval(vec![
+            ("A", ExprKind::cast(ExprKind::bool_(true), int())),
+            ("B", ExprKind::cast(ExprKind::integer(2), int())),
+            ("C", ExprKind::cast(ExprKind::string("2".to_owned()), int())),
+        ]);
+
+        assert_eq!(
+            values,
+            [
+                ("A".to_owned(), Ok(ConstValue::Int(1))),
+                ("B".to_owned(), Ok(ConstValue::Int(2))),
+                ("C".to_owned(), Err(())),
+            ]
+        );
+        assert_eq!(errors, "0:0: Cannot cast `string` as `int` in constant\n");
+    }
+
+    #[test]
+    fn refers_to_later_constant() {
+        let (values, _) = eval(vec![
+            ("A", inline_expr! { B + 1 }),
+            ("B", inline_expr! { 41 }),
+        ]);
+
+        assert_eq!(
+            values,
+            [
+                ("A".to_owned(), Ok(ConstValue::Int(42))),
+                ("B".to_owned(), Ok(ConstValue::Int(41)))
+            ]
+        );
+    }
+
+    #[test]
+    fn cycle_is_reported_once() {
+        let (values, errors) = eval(vec![
+            ("A", inline_expr! { B }),
+            ("B", inline_expr! { C + 1 }),
+            ("C", inline_expr! { A }),
+        ]);
+
+        assert!(values.iter().all(|(_, value)| value.is_err()));
+        assert_eq!(
+            errors,
+            "0:0: Cycle detected when evaluating constant `A`: `A` -> `B` -> `C` -> `A`\n"
+        );
+    }
+
+    #[test]
+    fn self_reference() {
+        let (_, errors) = eval(vec![("A", inline_expr! { A })]);
+
+        assert_eq!(
+            errors,
+            "0:0: Cycle detected when evaluating constant `A`: `A` -> `A`\n"
+        );
+    }
+
+    #[test]
+    fn not_constant() {
+        let (_, errors) = eval(vec![("A", inline_expr! { f() }), ("B", inline_expr! { x })]);
+
+        assert_eq!(
+            errors,
+            "0:0: Expression cannot be evaluated at compile time\n0:0: Cannot find constant `x` in this scope\n"
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let (_, errors) = eval(vec![("A", inline_expr! { 1 + true })]);
+
+        assert_eq!(errors, "0:0: Cannot add `int` and `bool` in constant\n");
+    }
+
+    #[test]
+    fn overflow() {
+        let (_, errors) = eval(vec![("A", inline_expr! { 65536 * 65536 })]);
+
+        assert_eq!(
+            errors,
+            "0:0: Attempt to multiply with overflow in constant\n"
+        );
+    }
+}
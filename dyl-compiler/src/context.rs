@@ -1,15 +1,23 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
+    mem,
 };
 
-use anyhow::Error as AnyError;
-
-use dyl_bytecode::Instruction as ResolvedInstruction;
-
-use crate::{instruction::Instruction, ty::Ty};
+use dyl_bytecode::{constant_pool::ConstantPool, Instruction as ResolvedInstruction};
+
+use crate::{
+    ast::{Span, Type},
+    const_eval::ConstValue,
+    diagnostic::{Diagnostic, Diagnostics, Severity},
+    hir::{Closure, DefId},
+    instruction::Instruction,
+    trivia::Trivia,
+    ty::Ty,
+    DEFAULT_NESTING_LIMIT,
+};
 
 pub(crate) fn resolve_labels(
     instructions: &[Instruction],
@@ -21,35 +29,159 @@ pub(crate) fn resolve_labels(
 #[derive(Clone, Debug, Default, PartialEq)]
 pub(crate) struct ParsingContext {
     errs: ErrorContext,
+    warnings: WarningContext,
+    trivia: Trivia,
+
+    /// How many expressions contain the code being parsed.
+    depth: Cell<usize>,
+
+    /// How deep the code parsed so far in each expression that contains the
+    /// code being parsed is. Operations count as a level, as their left
+    /// operand is nested in them.
+    heights: RefCell<Vec<usize>>,
+
+    /// How deep the deepest code parsed so far is.
+    deepest: Cell<usize>,
+
+    /// How many expressions may contain some code before the parser gives
+    /// up, rather than overflow its stack.
+    nesting_limit: usize,
 }
 
 impl ParsingContext {
     pub(crate) fn new() -> ParsingContext {
-        ParsingContext::default()
+        ParsingContext {
+            nesting_limit: DEFAULT_NESTING_LIMIT,
+            ..ParsingContext::default()
+        }
+    }
+
+    pub(crate) fn with_nesting_limit(self, nesting_limit: usize) -> ParsingContext {
+        ParsingContext {
+            nesting_limit,
+            ..self
+        }
+    }
+
+    pub(crate) fn nesting_limit(&self) -> usize {
+        self.nesting_limit
+    }
+
+    /// Enters a nesting level, unless the code is nested too deep. Each level
+    /// that is entered must be left.
+    pub(crate) fn enter_nested(&self) -> bool {
+        if self.depth.get() >= self.nesting_limit {
+            return false;
+        }
+
+        self.depth.set(self.depth.get() + 1);
+        self.heights.borrow_mut().push(0);
+        true
+    }
+
+    pub(crate) fn leave_nested(&self) {
+        self.depth.set(self.depth.get() - 1);
+
+        let mut heights = self.heights.borrow_mut();
+        let height = heights.pop().expect("A nesting level was entered") + 1;
+        match heights.last_mut() {
+            Some(enclosing) => *enclosing = (*enclosing).max(height),
+            None => self.deepest.set(self.deepest.get().max(height)),
+        }
+    }
+
+    /// Records that the code parsed last is the operand of an operation,
+    /// such as an addition or a field access.
+    ///
+    /// Operations that follow each other are parsed in a loop, without
+    /// entering a nesting level, so that code is never rejected for being
+    /// long. The later phases walk down the operands recursively though, so
+    /// the operations still count in how deep the code is.
+    pub(crate) fn nest_in_operation(&self) {
+        match self.heights.borrow_mut().last_mut() {
+            Some(height) => *height += 1,
+            None => self.deepest.set(self.deepest.get() + 1),
+        }
+    }
+
+    /// Returns how deep the deepest code parsed so far is, counting the
+    /// operations.
+    pub(crate) fn deepest(&self) -> usize {
+        self.deepest.get()
     }
 
     pub(crate) fn errors(&self) -> &ErrorContext {
         &self.errs
     }
 
+    pub(crate) fn warnings(&self) -> &WarningContext {
+        &self.warnings
+    }
+
+    /// The comments found while parsing.
+    pub(crate) fn trivia(&self) -> &Trivia {
+        &self.trivia
+    }
+
+    /// Adds what another context found, after what this one found.
+    pub(crate) fn merge(&self, other: ParsingContext) {
+        let ParsingContext {
+            errs,
+            warnings,
+            trivia,
+            deepest,
+            ..
+        } = other;
+
+        self.deepest.set(self.deepest.get().max(deepest.get()));
+
+        errs.0.take().into_iter().for_each(|e| self.errs.add(e));
+        warnings
+            .take()
+            .into_iter()
+            .for_each(|w| self.warnings.add(w));
+        trivia
+            .comments()
+            .into_iter()
+            .for_each(|c| self.trivia.add(c));
+    }
+
     pub(crate) fn into_typing_context(self) -> TypingContext {
-        let errs = self.errs;
+        let ParsingContext { errs, warnings, .. } = self;
 
         TypingContext {
-            1: errs,
+            errs,
+            warnings,
             ..Default::default()
         }
     }
 
     pub(crate) fn wrap_result<T>(self, rslt: Result<T, ()>) -> PassResult<ParsingContext, T> {
         self.errs
-            .emit_possible_errors(rslt)
+            .fail_on_errors(rslt, &self.warnings)
             .map(|pass_value| (self, pass_value))
     }
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
-pub(crate) struct TypingContext(Vec<(String, Ty)>, ErrorContext);
+pub(crate) struct TypingContext {
+    bindings: Vec<(String, Ty, DefId)>,
+
+    /// The parameter types and the return type of each function.
+    functions: HashMap<String, (Vec<Ty>, Ty)>,
+    function_defs: HashMap<String, DefId>,
+
+    /// The number of definitions made so far, which is the id of the next
+    /// one.
+    defs: u32,
+    enums: Vec<String>,
+
+    /// The return type of the function being checked.
+    ret_ty: Option<Ty>,
+
+    warnings: WarningContext,
+    errs: ErrorContext,
+}
 
 impl TypingContext {
     #[cfg(test)]
@@ -58,41 +190,127 @@ impl TypingContext {
     }
 
     pub(crate) fn add_binding(&mut self, name: String, ty: Ty) {
-        self.0.push((name, ty));
+        let def = self.new_def();
+        self.bindings.push((name, ty, def));
     }
 
     pub(crate) fn resolve_binding(&self, name: &str) -> Option<&Ty> {
-        self.0
+        self.bindings
             .iter()
             .rev()
-            .find_map(|(binding, ty)| binding.eq(name).then(|| ty))
+            .find_map(|(binding, ty, _)| binding.eq(name).then_some(ty))
+    }
+
+    /// Returns the definition a name refers to: a binding if one is in
+    /// scope, a function otherwise.
+    pub(crate) fn resolve_def(&self, name: &str) -> Option<DefId> {
+        self.bindings
+            .iter()
+            .rev()
+            .find_map(|(binding, _, def)| binding.eq(name).then_some(*def))
+            .or_else(|| self.function_def(name))
+    }
+
+    pub(crate) fn function_def(&self, name: &str) -> Option<DefId> {
+        self.function_defs.get(name).copied()
+    }
+
+    /// Returns the definitions of the bindings added since a subcontext was
+    /// created, in order.
+    pub(crate) fn defs_since(&self, subctxt_id: usize) -> Vec<DefId> {
+        self.bindings[subctxt_id..]
+            .iter()
+            .map(|(_, _, def)| *def)
+            .collect()
+    }
+
+    fn new_def(&mut self) -> DefId {
+        self.defs += 1;
+        DefId::new(self.defs - 1)
     }
 
     pub(crate) fn new_subcontext(&self) -> usize {
-        self.0.len()
+        self.bindings.len()
     }
 
     pub(crate) fn drop_subcontext(&mut self, subctxt_id: usize) {
-        self.0.truncate(subctxt_id)
+        self.bindings.truncate(subctxt_id)
+    }
+
+    pub(crate) fn add_enum(&mut self, name: String) {
+        self.enums.push(name);
+    }
+
+    pub(crate) fn add_function(&mut self, name: String, params: Vec<Ty>, ret: Ty) {
+        let def = self.new_def();
+        self.function_defs.insert(name.clone(), def);
+        self.functions.insert(name, (params, ret));
+    }
+
+    /// Sets the return type of a function, telling whether it changed.
+    pub(crate) fn set_function_ret(&mut self, name: &str, ret: Ty) -> bool {
+        match self.functions.get_mut(name) {
+            Some((_, prev)) if *prev != ret => {
+                *prev = ret;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the parameter types and the return type of a function.
+    pub(crate) fn function(&self, name: &str) -> Option<&(Vec<Ty>, Ty)> {
+        self.functions.get(name)
+    }
+
+    /// Sets the return type of the function being checked, returning the
+    /// previous one.
+    pub(crate) fn set_ret_ty(&mut self, ty: Option<Ty>) -> Option<Ty> {
+        mem::replace(&mut self.ret_ty, ty)
+    }
+
+    pub(crate) fn ret_ty(&self) -> Option<&Ty> {
+        self.ret_ty.as_ref()
+    }
+
+    /// Converts a type written in the source code. Names that are neither
+    /// builtin types nor enums refer to structs.
+    pub(crate) fn ty_of(&self, ty: &Type) -> Ty {
+        match ty {
+            Type::Named(name) => match name.as_str() {
+                "int" => Ty::Int,
+                "bool" => Ty::Bool,
+                "string" => Ty::String,
+                name if self.enums.iter().any(|enum_| enum_ == name) => Ty::Enum(name.to_owned()),
+                name => Ty::Struct(name.to_owned()),
+            },
+
+            Type::Tuple(elements) => {
+                Ty::Tuple(elements.iter().map(|element| self.ty_of(element)).collect())
+            }
+
+            Type::Array(element) => Ty::Array(Box::new(self.ty_of(element))),
+        }
     }
 
     pub(crate) fn into_lowering_context(self) -> LoweringContext {
-        let errs = self.1;
+        let TypingContext { errs, warnings, .. } = self;
 
         LoweringContext {
             errs,
+            warnings,
             ..Default::default()
         }
     }
 
     pub(crate) fn wrap_result<T>(self, rslt: Result<T, ()>) -> PassResult<TypingContext, T> {
-        self.1
-            .emit_possible_errors(rslt)
+        self.errs
+            .fail_on_errors(rslt, &self.warnings)
             .map(|pass_value| (self, pass_value))
     }
 
     pub(crate) fn errs(&self) -> &ErrorContext {
-        &self.1
+        &self.errs
     }
 }
 
@@ -100,7 +318,28 @@ impl TypingContext {
 pub(crate) struct LoweringContext {
     labels: LabelContext,
     stack: StackContext,
+    functions: FunctionContext,
+    structs: StructContext,
+    enums: EnumContext,
+    consts: ConstContext,
+    frame: Option<Frame>,
+    closures: Vec<PendingClosure>,
+    warnings: WarningContext,
     errs: ErrorContext,
+
+    /// The strings of the program, which the instructions refer to by index.
+    constants: ConstantPool,
+
+    /// The code each lowered instruction comes from, by position.
+    locations: Vec<Option<Span>>,
+
+    /// Whether the bindings take the slots of the variables that are no
+    /// longer used.
+    reuse_slots: bool,
+
+    /// Whether the code checks its own stack accesses and arithmetic at
+    /// runtime.
+    debug_checks: bool,
 }
 
 impl LoweringContext {
@@ -120,16 +359,165 @@ impl LoweringContext {
         &mut self.stack
     }
 
+    pub(crate) fn functions(&self) -> &FunctionContext {
+        &self.functions
+    }
+
+    pub(crate) fn structs(&self) -> &StructContext {
+        &self.structs
+    }
+
+    #[cfg(test)]
+    pub(crate) fn constants(&self) -> &ConstantPool {
+        &self.constants
+    }
+
+    pub(crate) fn constants_mut(&mut self) -> &mut ConstantPool {
+        &mut self.constants
+    }
+
+    /// Declares a struct, so that it can be constructed from any function
+    /// body.
+    pub(crate) fn declare_struct(
+        &mut self,
+        name: String,
+        fields: Vec<String>,
+    ) -> Result<(), StructDeclarationError> {
+        self.structs.declare(name, fields)
+    }
+
+    pub(crate) fn enums(&self) -> &EnumContext {
+        &self.enums
+    }
+
+    /// Declares an enum, so that its variants can be constructed from any
+    /// function body.
+    pub(crate) fn declare_enum(
+        &mut self,
+        name: String,
+        variants: Vec<(String, usize)>,
+    ) -> Result<(), EnumDeclarationError> {
+        self.enums.declare(name, variants)
+    }
+
+    pub(crate) fn consts(&self) -> &ConstContext {
+        &self.consts
+    }
+
+    /// Declares a constant, so that its value can be used from any function
+    /// body.
+    pub(crate) fn declare_const(
+        &mut self,
+        name: String,
+        value: Result<ConstValue, ()>,
+    ) -> Result<(), ConstDeclarationError> {
+        self.consts.declare(name, value)
+    }
+
+    pub(crate) fn frame(&self) -> Option<Frame> {
+        self.frame
+    }
+
+    pub(crate) fn set_frame(&mut self, frame: Frame) {
+        self.frame = Some(frame);
+    }
+
+    pub(crate) fn reuses_slots(&self) -> bool {
+        self.reuse_slots
+    }
+
+    /// Makes the bindings take the slots of the variables that are no longer
+    /// used, rather than new ones.
+    pub(crate) fn reuse_dead_slots(&mut self) {
+        self.reuse_slots = true;
+    }
+
+    pub(crate) fn has_debug_checks(&self) -> bool {
+        self.debug_checks
+    }
+
+    /// Makes the lowered code check, at runtime, that the stack is laid out
+    /// as expected when a variable is accessed, and that arithmetic does not
+    /// overflow.
+    pub(crate) fn add_debug_checks(&mut self) {
+        self.debug_checks = true;
+    }
+
+    /// Declares a function, so that it can be called from any function
+    /// body, no matter in which order they are lowered.
+    pub(crate) fn declare_function(
+        &mut self,
+        name: String,
+        arity: usize,
+    ) -> Result<(), FunctionDeclarationError> {
+        let label = self.labels.new_anonymous();
+        self.functions
+            .declare(name, FunctionSignature { label, arity })
+    }
+
+    /// Records a closure whose body is to be lowered later, and returns the
+    /// label of its code.
+    pub(crate) fn defer_closure(&mut self, captures: Vec<String>, closure: Closure) -> u32 {
+        let label = self.labels.new_anonymous();
+        self.closures.push(PendingClosure {
+            label,
+            captures,
+            closure,
+        });
+
+        label
+    }
+
+    pub(crate) fn next_pending_closure(&mut self) -> Option<PendingClosure> {
+        self.closures.pop()
+    }
+
+    pub(crate) fn warnings(&self) -> &WarningContext {
+        &self.warnings
+    }
+
     pub(crate) fn errors(&self) -> &ErrorContext {
         &self.errs
     }
 
     pub(crate) fn wrap_result<T>(self, res: Result<T, ()>) -> PassResult<LoweringContext, T> {
         self.errs
-            .emit_possible_errors(res)
+            .fail_on_errors(res, &self.warnings)
             .map(|pass_value| (self, pass_value))
     }
 
+    /// Records that the instructions lowered between `start` and `end` that
+    /// do not come from some more precise code yet come from the code at
+    /// `span`.
+    ///
+    /// Expressions are located once their operands are, so that each
+    /// instruction is located at the innermost expression it is lowered for.
+    pub(crate) fn locate(&mut self, start: u32, end: u32, span: &Span) {
+        // Nodes created by the compiler are not written anywhere.
+        if span.line() == 0 {
+            return;
+        }
+
+        let (start, end) = (start as usize, end as usize);
+
+        if self.locations.len() < end {
+            self.locations.resize(end, None);
+        }
+
+        self.locations[start..end]
+            .iter_mut()
+            .filter(|location| location.is_none())
+            .for_each(|location| *location = Some(span.clone()));
+    }
+
+    /// Returns the location of each of the `len` instructions lowered so far.
+    pub(crate) fn take_locations(&mut self, len: usize) -> Vec<Option<Span>> {
+        let mut locations = mem::take(&mut self.locations);
+        locations.resize(len, None);
+
+        locations
+    }
+
     pub(crate) fn into_label_resolution_context(self) -> LabelResolutionContext {
         let LoweringContext { errs, labels, .. } = self;
         LabelResolutionContext { labels, errs }
@@ -151,14 +539,6 @@ impl LabelResolutionContext {
     pub(crate) fn labels(&self) -> &LabelContext {
         &self.labels
     }
-
-    pub(crate) fn labels_mut(&mut self) -> &mut LabelContext {
-        &mut self.labels
-    }
-
-    pub(crate) fn errs(&self) -> &ErrorContext {
-        &self.errs
-    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -190,6 +570,25 @@ impl LabelContext {
         }
     }
 
+    /// Moves a label that has already been placed.
+    pub(crate) fn move_to(&mut self, label_id: u32, pos: u32) -> Result<(), LabelDefinitionError> {
+        match self.0.get_mut(label_id as usize) {
+            Some(slot) => {
+                *slot = Some(pos);
+                Ok(())
+            }
+            None => Err(LabelDefinitionError::UnknownLabel),
+        }
+    }
+
+    /// Returns the labels that have been placed, along with their position.
+    pub(crate) fn positions(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(label_id, pos)| pos.map(|pos| (label_id as u32, pos)))
+    }
+
     pub(crate) fn resolve_anonymous(&self, label_id: u32) -> Result<u32, LabelResolutionError> {
         self.0
             .get(label_id as usize)
@@ -197,11 +596,12 @@ impl LabelContext {
             .ok_or(LabelResolutionError::UnknownLabelPosition)
     }
 
+    #[cfg(test)]
     pub(crate) fn resolve_named(&self, label: &str) -> Result<u32, LabelResolutionError> {
         self.1
             .get(label)
             .ok_or(LabelResolutionError::UnknownLabel)
-            .map(Clone::clone)
+            .copied()
     }
 
     #[cfg(test)]
@@ -223,33 +623,359 @@ pub(crate) enum LabelResolutionError {
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
-pub(crate) struct StackContext(Vec<String>);
+pub(crate) struct FunctionContext(HashMap<String, FunctionSignature>);
+
+impl FunctionContext {
+    fn declare(
+        &mut self,
+        name: String,
+        signature: FunctionSignature,
+    ) -> Result<(), FunctionDeclarationError> {
+        if self.0.contains_key(&name) {
+            return Err(FunctionDeclarationError::AlreadyDefined);
+        }
+
+        self.0.insert(name, signature);
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<FunctionSignature> {
+        self.0.get(name).copied()
+    }
+
+    /// Returns the signatures of every declared function.
+    pub(crate) fn signatures(&self) -> impl Iterator<Item = FunctionSignature> + '_ {
+        self.0.values().copied()
+    }
+
+    /// Returns the signature of the function whose code starts at `label`.
+    pub(crate) fn resolve_label(&self, label: u32) -> Option<FunctionSignature> {
+        self.0
+            .values()
+            .find(|signature| signature.label == label)
+            .copied()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct FunctionSignature {
+    label: u32,
+    arity: usize,
+}
+
+impl FunctionSignature {
+    pub(crate) fn label(&self) -> u32 {
+        self.label
+    }
+
+    pub(crate) fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum FunctionDeclarationError {
+    AlreadyDefined,
+}
+
+/// The declarations table of the structs defined in the program.
+///
+/// Each struct is associated with its field names, in declaration order. A
+/// struct value is laid out as a single heap value, whose fields are stored
+/// in that order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct StructContext(HashMap<String, Vec<String>>);
+
+impl StructContext {
+    fn declare(&mut self, name: String, fields: Vec<String>) -> Result<(), StructDeclarationError> {
+        if self.0.contains_key(&name) {
+            return Err(StructDeclarationError::AlreadyDefined);
+        }
+
+        if let Some(field) = first_duplicate(&fields) {
+            return Err(StructDeclarationError::DuplicateField(field.to_owned()));
+        }
+
+        self.0.insert(name, fields);
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<&[String]> {
+        self.0.get(name).map(Vec::as_slice)
+    }
+}
+
+fn first_duplicate<'a, I>(names: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut seen = Vec::new();
+
+    names.into_iter().find_map(|name| {
+        if seen.contains(&name) {
+            Some(name.as_str())
+        } else {
+            seen.push(name);
+            None
+        }
+    })
+}
+
+/// The declarations table of the enums defined in the program.
+///
+/// Each enum is associated with its variants, in declaration order, along
+/// with the number of fields they hold. A variant value is laid out as a
+/// single heap value, whose first element is the index of the variant (its
+/// tag), followed by its fields.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct EnumContext(HashMap<String, Vec<(String, usize)>>);
+
+impl EnumContext {
+    fn declare(
+        &mut self,
+        name: String,
+        variants: Vec<(String, usize)>,
+    ) -> Result<(), EnumDeclarationError> {
+        if self.0.contains_key(&name) {
+            return Err(EnumDeclarationError::AlreadyDefined);
+        }
+
+        if let Some(variant) = first_duplicate(variants.iter().map(|(name, _)| name)) {
+            return Err(EnumDeclarationError::DuplicateVariant(variant.to_owned()));
+        }
+
+        self.0.insert(name, variants);
+        Ok(())
+    }
+
+    /// Returns the variants of an enum, along with their number of fields.
+    pub(crate) fn variants(&self, enum_name: &str) -> Option<&[(String, usize)]> {
+        self.0.get(enum_name).map(Vec::as_slice)
+    }
+
+    /// Returns the tag and the number of fields of a variant.
+    pub(crate) fn resolve_variant(
+        &self,
+        enum_name: &str,
+        variant: &str,
+    ) -> Result<(u16, usize), VariantResolutionError> {
+        let variants = self
+            .0
+            .get(enum_name)
+            .ok_or(VariantResolutionError::UnknownEnum)?;
+
+        variants
+            .iter()
+            .enumerate()
+            .find(|(_, (name, _))| name == variant)
+            .map(|(tag, (_, arity))| (tag as u16, *arity))
+            .ok_or(VariantResolutionError::UnknownVariant)
+    }
+}
+
+/// The values of the constants defined in the program.
+///
+/// Constants whose evaluation failed are kept, so that their uses are not
+/// reported as undefined variables.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ConstContext(HashMap<String, Result<ConstValue, ()>>);
+
+impl ConstContext {
+    fn declare(
+        &mut self,
+        name: String,
+        value: Result<ConstValue, ()>,
+    ) -> Result<(), ConstDeclarationError> {
+        if self.0.contains_key(&name) {
+            return Err(ConstDeclarationError::AlreadyDefined);
+        }
+
+        self.0.insert(name, value);
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<Result<&ConstValue, ()>> {
+        self.0
+            .get(name)
+            .map(Result::as_ref)
+            .map(|value| value.map_err(drop))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ConstDeclarationError {
+    AlreadyDefined,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum EnumDeclarationError {
+    AlreadyDefined,
+    DuplicateVariant(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum VariantResolutionError {
+    UnknownEnum,
+    UnknownVariant,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum StructDeclarationError {
+    AlreadyDefined,
+    DuplicateField(String),
+}
+
+/// The stack layout of the function being lowered.
+///
+/// A frame starts with the return value slot, followed by the arguments of
+/// the function and the return address.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Frame {
+    base: usize,
+    arity: u16,
+}
+
+impl Frame {
+    pub(crate) fn new(base: usize, arity: u16) -> Frame {
+        Frame { base, arity }
+    }
+
+    /// Returns the depth of the stack context at which the frame starts.
+    pub(crate) fn base(&self) -> usize {
+        self.base
+    }
+
+    pub(crate) fn arity(&self) -> u16 {
+        self.arity
+    }
+}
+
+/// A closure whose body has not been lowered yet.
+///
+/// The body of a closure can not be lowered where the closure is created,
+/// as it would end up in the middle of the enclosing function code.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PendingClosure {
+    label: u32,
+    captures: Vec<String>,
+    closure: Closure,
+}
+
+impl PendingClosure {
+    pub(crate) fn label(&self) -> u32 {
+        self.label
+    }
+
+    /// The variables captured by the closure, in the order they are pushed
+    /// when it is called.
+    pub(crate) fn captures(&self) -> &[String] {
+        self.captures.as_slice()
+    }
+
+    pub(crate) fn closure(&self) -> &Closure {
+        &self.closure
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct StackContext(Vec<StackSlot>);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct StackSlot {
+    name: String,
+    mutable: bool,
+
+    /// Where the variable is bound, if it comes from a `let` binding.
+    span: Option<Span>,
+}
+
+impl StackSlot {
+    fn named(name: String) -> StackSlot {
+        StackSlot {
+            name,
+            ..StackSlot::default()
+        }
+    }
+}
 
 impl StackContext {
     pub(crate) fn push_anonymous(&mut self) {
-        self.0.push(String::new());
+        self.0.push(StackSlot::default());
     }
 
     pub(crate) fn name_top_anonymous(&mut self, name: String) -> Result<(), AnonymousNamingError> {
+        self.name_top_anonymous_with(name, false)
+    }
+
+    pub(crate) fn name_top_anonymous_mut(
+        &mut self,
+        name: String,
+    ) -> Result<(), AnonymousNamingError> {
+        self.name_top_anonymous_with(name, true)
+    }
+
+    fn name_top_anonymous_with(
+        &mut self,
+        name: String,
+        mutable: bool,
+    ) -> Result<(), AnonymousNamingError> {
         let top = self
             .0
             .last_mut()
             .ok_or(AnonymousNamingError::NoTopVariable)?;
 
-        if top.is_empty() {
-            *top = name;
+        if top.name.is_empty() {
+            *top = StackSlot {
+                name,
+                mutable,
+                span: None,
+            };
             Ok(())
         } else {
             Err(AnonymousNamingError::NotAnonymous)
         }
     }
 
+    /// Records where the variable on top of the stack is bound.
+    pub(crate) fn set_top_span(&mut self, span: Span) {
+        if let Some(top) = self.0.last_mut() {
+            top.span = Some(span);
+        }
+    }
+
     pub(crate) fn resolve(&self, name: &str) -> Option<u16> {
+        self.resolve_slot(name).map(|(depth, _)| depth)
+    }
+
+    /// Moves the variable on top of the stack to the slot `depth` below it,
+    /// as `PopCopy` does. The variable of that slot is forgotten.
+    pub(crate) fn move_top(&mut self, depth: u16) {
+        if let Some(top) = self.0.pop() {
+            let idx = self.0.len() - depth as usize;
+            self.0[idx] = top;
+        }
+    }
+
+    /// Returns where the variable `name` is bound, or `None` if it does not
+    /// exist or is a function argument.
+    pub(crate) fn resolve_span(&self, name: &str) -> Option<Span> {
+        self.resolve_slot(name)
+            .and_then(|(_, slot)| slot.span.clone())
+    }
+
+    /// Returns whether the variable `name` can be assigned to, or `None` if
+    /// no such variable exists.
+    pub(crate) fn is_mutable(&self, name: &str) -> Option<bool> {
+        self.resolve_slot(name).map(|(_, slot)| slot.mutable)
+    }
+
+    fn resolve_slot(&self, name: &str) -> Option<(u16, &StackSlot)> {
         self.0
             .iter()
             .rev()
             .enumerate()
-            .find_map(|(depth, var_name)| var_name.eq(name).then(|| depth as u16))
+            .find(|(_, slot)| slot.name == name)
+            .map(|(depth, slot)| (depth as u16, slot))
     }
 
     pub(crate) fn new_subcontext(&self) -> usize {
@@ -264,8 +990,9 @@ impl StackContext {
         self.0
             .pop()
             .ok_or(AnonymousPoppingError::EmptyStack)?
+            .name
             .is_empty()
-            .then(|| ())
+            .then_some(())
             .ok_or(AnonymousPoppingError::NotAnonymous)
     }
 
@@ -276,22 +1003,20 @@ impl StackContext {
 
     #[cfg(test)]
     fn push_variable(&mut self, name: String) {
-        self.0.push(name)
+        self.0.push(StackSlot::named(name))
     }
 
-    #[cfg(test)]
     pub(crate) fn depth(&self) -> usize {
         self.0.len()
     }
 
     #[cfg(test)]
     pub(crate) fn top(&self) -> Option<&str> {
-        self.0.last().map(AsRef::as_ref)
+        self.0.last().map(|slot| slot.name.as_str())
     }
 
-    #[cfg(test)]
     pub(crate) fn push_named(&mut self, name: String) {
-        self.0.push(name)
+        self.0.push(StackSlot::named(name))
     }
 }
 
@@ -308,29 +1033,49 @@ pub(crate) enum AnonymousPoppingError {
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
-pub(crate) struct ErrorContext(RefCell<Vec<CompilationError>>);
+pub(crate) struct ErrorContext(RefCell<Vec<Diagnostic>>);
 
 impl ErrorContext {
-    pub(crate) fn add(&self, e: impl Into<CompilationError>) {
+    pub(crate) fn add(&self, e: impl Into<Diagnostic>) {
         self.0.borrow_mut().push(e.into());
     }
 
-    fn emit_possible_errors<T>(&self, rslt: Result<T, ()>) -> Result<T, CompilerPassError> {
+    /// Adds an error, unless the same error has already been reported at the
+    /// same place.
+    pub(crate) fn add_once(&self, e: impl Into<Diagnostic>) {
+        let e = e.into();
+        let mut errs = self.0.borrow_mut();
+
+        if !errs.iter().any(|previous| previous.is_same_as(&e)) {
+            errs.push(e);
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.0.borrow().clone()
+    }
+
+    /// Fails if the pass failed or reported errors. The error then holds
+    /// the warnings that have not been taken yet, followed by the errors.
+    fn fail_on_errors<T>(
+        &self,
+        rslt: Result<T, ()>,
+        warnings: &WarningContext,
+    ) -> Result<T, CompilerPassError> {
         let errs = self.0.borrow();
 
         match (rslt, errs.as_slice()) {
             (Ok(v), []) => Ok(v),
             _ => {
-                self.emit();
-                Err(CompilerPassError(errs.len()))
+                let mut diagnostics = warnings.take();
+                diagnostics.extend(errs.iter().cloned());
+
+                Err(CompilerPassError(diagnostics.into_iter().collect()))
             }
         }
     }
 
-    fn emit(&self) {
-        eprintln!("{}", self);
-    }
-
     #[cfg(test)]
     fn new() -> ErrorContext {
         ErrorContext::default()
@@ -346,46 +1091,58 @@ impl Display for ErrorContext {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialOrd, PartialEq)]
-pub(crate) struct CompilerPassError(usize);
+/// The warnings reported during the compilation.
+///
+/// Unlike errors, warnings do not stop the compilation. They are carried from
+/// pass to pass, until they are taken to be shown.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct WarningContext(RefCell<Vec<Diagnostic>>);
 
-impl Display for CompilerPassError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let error_word = if self.0 == 1 { "error" } else { "errors" };
+impl WarningContext {
+    pub(crate) fn add(&self, w: Diagnostic) {
+        self.0.borrow_mut().push(w);
+    }
 
-        write!(f, "Compilation failed with {} {}", self.0, error_word)
+    /// Returns the warnings reported so far, which are forgotten.
+    pub(crate) fn take(&self) -> Vec<Diagnostic> {
+        self.0.take()
     }
 }
 
-impl Error for CompilerPassError {}
+impl Display for WarningContext {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        self.0
+            .borrow()
+            .iter()
+            .try_for_each(|w| writeln!(f, "{}", w))
+    }
+}
 
+/// The failure of a compiler pass, along with the diagnostics that explain
+/// it.
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct CompilationError(String);
+pub(crate) struct CompilerPassError(Diagnostics);
 
-impl From<String> for CompilationError {
-    fn from(input: String) -> Self {
-        CompilationError(input)
+impl CompilerPassError {
+    pub(crate) fn into_diagnostics(self) -> Diagnostics {
+        self.0
     }
 }
 
-impl From<&str> for CompilationError {
-    fn from(input: &str) -> CompilationError {
-        let input = input.to_owned();
-        CompilationError::from(input)
-    }
-}
+impl Display for CompilerPassError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let count = self
+            .0
+            .iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .count();
+        let error_word = if count == 1 { "error" } else { "errors" };
 
-impl From<AnyError> for CompilationError {
-    fn from(err: AnyError) -> CompilationError {
-        CompilationError(err.to_string())
+        write!(f, "Compilation failed with {} {}", count, error_word)
     }
 }
 
-impl Display for CompilationError {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        self.0.fmt(f)
-    }
-}
+impl Error for CompilerPassError {}
 
 pub(crate) trait Resolvable {
     type Output;
@@ -572,6 +1329,35 @@ mod variables {
             Err(AnonymousNamingError::NotAnonymous)
         );
     }
+    #[test]
+    fn variables_are_immutable_by_default() {
+        let mut ctxt = StackContext::new();
+        ctxt.push_anonymous();
+        ctxt.name_top_anonymous("foo".to_owned()).unwrap();
+
+        assert_eq!(ctxt.is_mutable("foo"), Some(false));
+    }
+
+    #[test]
+    fn name_top_anonymous_mut_working() {
+        let mut ctxt = StackContext::new();
+        ctxt.push_anonymous();
+        ctxt.name_top_anonymous_mut("foo".to_owned()).unwrap();
+
+        assert_eq!(ctxt.resolve("foo"), Some(0));
+        assert_eq!(ctxt.is_mutable("foo"), Some(true));
+    }
+
+    #[test]
+    fn mutability_of_shadowing_variable_is_used() {
+        let mut ctxt = StackContext::new();
+        ctxt.push_anonymous();
+        ctxt.name_top_anonymous_mut("foo".to_owned()).unwrap();
+        ctxt.push_variable("foo".to_owned());
+
+        assert_eq!(ctxt.is_mutable("foo"), Some(false));
+        assert_eq!(ctxt.is_mutable("bar"), None);
+    }
 }
 
 #[cfg(test)]
@@ -631,16 +1417,30 @@ mod errors {
 
         assert_eq!(left, right);
     }
+
+    #[test]
+    fn add_once_skips_duplicates() {
+        let errs = ErrorContext::new();
+        errs.add_once("Hello");
+        errs.add_once("Hello");
+        errs.add_once("World");
+
+        assert_eq!(errs.to_string(), "Hello\nWorld\n");
+    }
 }
 
 #[cfg(test)]
 mod compiler_pass_error {
     use super::*;
 
+    fn failure(diagnostics: Vec<Diagnostic>) -> CompilerPassError {
+        CompilerPassError(diagnostics.into_iter().collect())
+    }
+
     #[test]
     fn singular() {
         assert_eq!(
-            CompilerPassError(1).to_string(),
+            failure(vec![Diagnostic::error("a")]).to_string(),
             "Compilation failed with 1 error"
         );
     }
@@ -648,8 +1448,156 @@ mod compiler_pass_error {
     #[test]
     fn plural() {
         assert_eq!(
-            CompilerPassError(2).to_string(),
+            failure(vec![Diagnostic::error("a"), Diagnostic::error("b")]).to_string(),
             "Compilation failed with 2 errors"
         );
     }
+
+    #[test]
+    fn warnings_are_not_counted() {
+        assert_eq!(
+            failure(vec![Diagnostic::warning("a"), Diagnostic::error("b")]).to_string(),
+            "Compilation failed with 1 error"
+        );
+    }
+
+    #[test]
+    fn warnings_come_first() {
+        let ctxt = ParsingContext::new();
+        ctxt.errors().add(Diagnostic::error("a"));
+        ctxt.warnings().add(Diagnostic::warning("b"));
+
+        let diagnostics = ctxt.wrap_result(Ok(())).unwrap_err().into_diagnostics();
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["b", "a"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod functions {
+    use super::*;
+
+    #[test]
+    fn declare_and_resolve() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("foo".to_owned(), 2).unwrap();
+
+        let signature = ctxt.functions().resolve("foo").unwrap();
+
+        assert_eq!(signature.arity(), 2);
+        assert_eq!(signature.label(), 0);
+    }
+
+    #[test]
+    fn declare_twice_fails() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("foo".to_owned(), 0).unwrap();
+
+        assert_eq!(
+            ctxt.declare_function("foo".to_owned(), 1),
+            Err(FunctionDeclarationError::AlreadyDefined)
+        );
+    }
+
+    #[test]
+    fn resolve_undeclared() {
+        let ctxt = LoweringContext::new();
+
+        assert!(ctxt.functions().resolve("foo").is_none());
+    }
+}
+
+#[cfg(test)]
+mod structs {
+    use super::*;
+
+    #[test]
+    fn declare_and_resolve() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_struct("Point".to_owned(), vec!["x".to_owned(), "y".to_owned()])
+            .unwrap();
+
+        assert_eq!(
+            ctxt.structs().resolve("Point"),
+            Some(["x".to_owned(), "y".to_owned()].as_slice())
+        );
+        assert!(ctxt.structs().resolve("Line").is_none());
+    }
+
+    #[test]
+    fn declare_twice() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_struct("Unit".to_owned(), Vec::new()).unwrap();
+
+        assert_eq!(
+            ctxt.declare_struct("Unit".to_owned(), Vec::new()),
+            Err(StructDeclarationError::AlreadyDefined)
+        );
+    }
+
+    #[test]
+    fn duplicate_field() {
+        let mut ctxt = LoweringContext::new();
+
+        assert_eq!(
+            ctxt.declare_struct("P".to_owned(), vec!["x".to_owned(), "x".to_owned()]),
+            Err(StructDeclarationError::DuplicateField("x".to_owned()))
+        );
+    }
+}
+
+#[cfg(test)]
+mod enums {
+    use super::*;
+
+    fn color_ctxt() -> LoweringContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_enum(
+            "Color".to_owned(),
+            vec![("Red".to_owned(), 0), ("Rgb".to_owned(), 3)],
+        )
+        .unwrap();
+        ctxt
+    }
+
+    #[test]
+    fn resolve_variant() {
+        let ctxt = color_ctxt();
+
+        assert_eq!(ctxt.enums().resolve_variant("Color", "Red"), Ok((0, 0)));
+        assert_eq!(ctxt.enums().resolve_variant("Color", "Rgb"), Ok((1, 3)));
+    }
+
+    #[test]
+    fn resolve_unknown() {
+        let ctxt = color_ctxt();
+
+        assert_eq!(
+            ctxt.enums().resolve_variant("Colour", "Red"),
+            Err(VariantResolutionError::UnknownEnum)
+        );
+        assert_eq!(
+            ctxt.enums().resolve_variant("Color", "Blue"),
+            Err(VariantResolutionError::UnknownVariant)
+        );
+    }
+
+    #[test]
+    fn duplicate_variant() {
+        let mut ctxt = LoweringContext::new();
+
+        assert_eq!(
+            ctxt.declare_enum(
+                "E".to_owned(),
+                vec![("A".to_owned(), 0), ("A".to_owned(), 1)]
+            ),
+            Err(EnumDeclarationError::DuplicateVariant("A".to_owned()))
+        );
+    }
 }
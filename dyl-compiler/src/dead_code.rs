@@ -0,0 +1,248 @@
+//! Removes the code that has no effect on the result of a program.
+//!
+//! A binding is removed when none of the names it defines is used and
+//! evaluating its value can not have any effect. Statements that can not
+//! have any effect are removed as well, and so are the statements following
+//! a `return` and the match arms following an arm that matches any value.
+//!
+//! Evaluating an operation that may fail at runtime, such as a
+//! multiplication that overflows, is considered to have an effect.
+
+use std::collections::HashSet;
+
+use crate::{
+    ast::{Binding, ExprKind, Function, Item, MatchArm, Pattern, Program},
+    captures,
+};
+
+pub(crate) fn eliminate_dead_code(program: &Program) -> Program {
+    let items = program
+        .structs()
+        .iter()
+        .cloned()
+        .map(Item::Struct)
+        .chain(program.enums().iter().cloned().map(Item::Enum))
+        .chain(program.consts().iter().cloned().map(Item::Const))
+        .chain(program.functions().iter().map(function).map(Item::Function))
+        .collect();
+
+    Program::new(items)
+}
+
+fn function(f: &Function) -> Function {
+    Function::new(f.name().to_owned(), f.args().to_vec(), expr(f.body()))
+        .with_signature(
+            f.generics().to_vec(),
+            f.arg_types().to_vec(),
+            f.ret_type().cloned(),
+        )
+        .with_span(f.span().clone())
+}
+
+fn expr(e: &ExprKind) -> ExprKind {
+    match e {
+        ExprKind::Bindings(b) => bindings(b.defines(), b.ending_expression()),
+
+        ExprKind::Statements(s) => statements(s.statements(), s.ending_expression()),
+
+        ExprKind::Match(m) => {
+            // The arms following an arm that matches any value are never
+            // taken.
+            let reachable = m
+                .arms()
+                .iter()
+                .position(|arm| {
+                    arm.guard().is_none()
+                        && matches!(arm.pattern(), Pattern::Wildcard | Pattern::Binding(_))
+                })
+                .map_or(m.arms().len(), |idx| idx + 1);
+
+            let arms = m.arms()[..reachable]
+                .iter()
+                .map(|arm| {
+                    MatchArm::new(arm.pattern().clone(), expr(arm.body()))
+                        .with_guard(arm.guard().map(expr))
+                })
+                .collect();
+
+            ExprKind::match_(expr(m.scrutinee()), arms)
+        }
+
+        _ => e.map_children(expr),
+    }
+}
+
+/// Removes the bindings that are not used by the following bindings nor by
+/// the ending expression.
+fn bindings(defines: &[Binding], ending: &ExprKind) -> ExprKind {
+    let ending = expr(ending);
+    let mut used = captures::free_variables_of(&ending)
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    // Going backwards, the names used after a binding are known when it is
+    // reached.
+    let mut kept = Vec::new();
+    for binding in defines.iter().rev() {
+        let value = expr(binding.value());
+        let names = binding.names();
+
+        if is_pure(&value) && names.iter().all(|name| !used.contains(*name)) {
+            continue;
+        }
+
+        names.iter().for_each(|name| {
+            used.remove(*name);
+        });
+        used.extend(captures::free_variables_of(&value));
+
+        kept.push(
+            Binding::from_pattern(binding.pattern().clone(), value, binding.is_mutable())
+                .with_annotation(binding.annotation().cloned())
+                .with_span(binding.span().clone()),
+        );
+    }
+
+    if kept.is_empty() {
+        return ending;
+    }
+
+    kept.reverse();
+    ExprKind::bindings(kept, ending)
+}
+
+/// Removes the statements that have no effect, and the ones that follow a
+/// `return`.
+fn statements(statements: &[ExprKind], ending: &ExprKind) -> ExprKind {
+    let mut kept = Vec::new();
+
+    for statement in statements {
+        let statement = expr(statement);
+
+        if is_pure(&statement) {
+            continue;
+        }
+
+        let returns = matches!(statement, ExprKind::Return(_));
+        kept.push(statement);
+
+        // The ending expression is not reached either, but the block still
+        // needs one.
+        if returns {
+            return ExprKind::statements(kept, ExprKind::unit());
+        }
+    }
+
+    let ending = expr(ending);
+
+    if kept.is_empty() {
+        return ending;
+    }
+
+    ExprKind::statements(kept, ending)
+}
+
+/// Tells whether evaluating an expression can not have any effect other
+/// than producing its value.
+fn is_pure(e: &ExprKind) -> bool {
+    match e {
+        ExprKind::Integer(_)
+        | ExprKind::Bool(_)
+        | ExprKind::String(_)
+        | ExprKind::Ident(_)
+        | ExprKind::Closure(_) => true,
+
+        // Additions wrap around instead of failing.
+        ExprKind::Addition(a) => is_pure(a.left()) && is_pure(a.right()),
+        ExprKind::Logical(l) => is_pure(l.left()) && is_pure(l.right()),
+        ExprKind::Cast(c) => is_pure(c.value()),
+        ExprKind::Field(f) => is_pure(f.tuple()),
+
+        ExprKind::Tuple(t) => t.elements().iter().all(is_pure),
+        ExprKind::Array(a) => a.elements().iter().all(is_pure),
+        ExprKind::StructLiteral(s) => s.fields().iter().all(|(_, value)| is_pure(value)),
+        ExprKind::VariantLiteral(v) => v.args().iter().all(is_pure),
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monomorphization;
+
+    use super::*;
+
+    fn assert_eliminates_to(code: &str, expected: &str) {
+        monomorphization::assert_optimizes_to(eliminate_dead_code, code, expected)
+    }
+
+    #[test]
+    fn unused_bindings() {
+        assert_eliminates_to(
+            "fn main() { let a = 1; let b = (2, a); let c = 3; c }",
+            "fn main() { let c = 3; c }",
+        );
+    }
+
+    #[test]
+    fn all_bindings_unused() {
+        assert_eliminates_to("fn main() { let a = 1; 2 }", "fn main() { 2 }");
+    }
+
+    #[test]
+    fn bindings_with_effects_are_kept() {
+        assert_eliminates_to(
+            "fn main() { let a = read_int(); let b = 2 * 3; let c = 4; 5 }",
+            "fn main() { let a = read_int(); let b = 2 * 3; 5 }",
+        );
+    }
+
+    #[test]
+    fn shadowed_binding() {
+        assert_eliminates_to(
+            "fn main() { let a = 1; let a = 2; let b = a; b }",
+            "fn main() { let a = 2; let b = a; b }",
+        );
+    }
+
+    #[test]
+    fn assigned_binding_is_kept() {
+        assert_eliminates_to(
+            "fn main() { let mut a = 1; a = 2; 3 }",
+            "fn main() { let mut a = 1; a = 2; 3 }",
+        );
+    }
+
+    #[test]
+    fn binding_used_in_closure() {
+        assert_eliminates_to(
+            "fn main() { let a = 1; let f = |x| x + a; f(2) }",
+            "fn main() { let a = 1; let f = |x| x + a; f(2) }",
+        );
+    }
+
+    #[test]
+    fn statements_without_effects() {
+        assert_eliminates_to(
+            "fn main() { 1; (2, 3); println(4); 5 }",
+            "fn main() { println(4); 5 }",
+        );
+    }
+
+    #[test]
+    fn statements_after_return() {
+        assert_eliminates_to(
+            "fn main() { println(1); return 2; println(3); 4 }",
+            "fn main() { println(1); return 2; }",
+        );
+    }
+
+    #[test]
+    fn arms_after_catch_all() {
+        assert_eliminates_to(
+            "fn main() { match 1 { 0 => 1, x if x > 2 => 2, _ => 3, 4 => 4 } }",
+            "fn main() { match 1 { 0 => 1, x if x > 2 => 2, _ => 3 } }",
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Rewrites the constructs that are shorthands for other ones.
+//!
+//! The desugaring runs once the macros are expanded, so that the phases
+//! that follow, code generation included, only know about the core of the
+//! language. A compound assignment `a += b` becomes `a = a + b`, and
+//! likewise for the other arithmetic operators.
+//!
+//! The code that replaces a construct is located where the construct is, so
+//! that its errors point to the code that was written.
+
+use crate::ast::{
+    Binding, Const, ExprKind, Function, Index, IndexAssignment, Item, Logical, MatchArm, Module,
+    Program,
+};
+
+pub(crate) fn desugar_program(program: &Program) -> Program {
+    Program::new(items(program))
+}
+
+fn items(program: &Program) -> Vec<Item> {
+    let consts = program.consts().iter().map(|c| {
+        let value = expr(c.value());
+        Item::Const(Const::new(c.name().to_owned(), value).with_span(c.span().clone()))
+    });
+
+    let modules = program.modules().iter().map(|m| {
+        let items = items(m.content());
+        Item::Module(Module::new(m.name().to_owned(), items).with_span(m.span().clone()))
+    });
+
+    program
+        .structs()
+        .iter()
+        .cloned()
+        .map(Item::Struct)
+        .chain(program.enums().iter().cloned().map(Item::Enum))
+        .chain(consts)
+        .chain(program.functions().iter().map(function).map(Item::Function))
+        .chain(modules)
+        .chain(program.imports().iter().cloned().map(Item::Import))
+        .chain(program.macros().iter().cloned().map(Item::Macro))
+        .collect()
+}
+
+fn function(f: &Function) -> Function {
+    Function::new(f.name().to_owned(), f.args().to_vec(), expr(f.body()))
+        .with_signature(
+            f.generics().to_vec(),
+            f.arg_types().to_vec(),
+            f.ret_type().cloned(),
+        )
+        .with_span(f.span().clone())
+}
+
+fn expr(e: &ExprKind) -> ExprKind {
+    let desugared = match e {
+        ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_) | ExprKind::Ident(_) => {
+            return e.clone()
+        }
+
+        ExprKind::Addition(a) => ExprKind::addition(expr(a.left()), expr(a.right())),
+        ExprKind::Subtraction(s) => ExprKind::subtraction(expr(s.left()), expr(s.right())),
+        ExprKind::Multiplication(m) => ExprKind::multiplication(expr(m.left()), expr(m.right())),
+        ExprKind::Exponentiation(e) => ExprKind::exponentiation(expr(e.left()), expr(e.right())),
+        ExprKind::Comparison(c) => {
+            ExprKind::comparison(c.operator(), expr(c.left()), expr(c.right()))
+        }
+        ExprKind::Logical(l) => {
+            ExprKind::Logical(Logical::new(l.operator(), expr(l.left()), expr(l.right())))
+        }
+
+        ExprKind::Cast(c) => ExprKind::cast(expr(c.value()), c.target().clone()),
+
+        ExprKind::If(i) => {
+            let condition = expr(i.condition());
+            let consequent = expr(i.consequent());
+
+            match i.alternative() {
+                Some(alternative) => ExprKind::if_(condition, consequent, expr(alternative)),
+                None => ExprKind::if_without_else(condition, consequent),
+            }
+        }
+
+        ExprKind::Bindings(b) => {
+            let bindings = b
+                .defines()
+                .iter()
+                .map(|binding| {
+                    Binding::from_pattern(
+                        binding.pattern().clone(),
+                        expr(binding.value()),
+                        binding.is_mutable(),
+                    )
+                    .with_annotation(binding.annotation().cloned())
+                    .with_span(binding.span().clone())
+                })
+                .collect();
+
+            b.rebuild(bindings, expr(b.ending_expression()))
+        }
+
+        ExprKind::Statements(s) => s.rebuild(exprs(s.statements()), expr(s.ending_expression())),
+
+        ExprKind::FnCall(c) => c.rebuild(c.name().to_owned(), exprs(c.args())),
+        ExprKind::Return(r) => ExprKind::return_(expr(r.value())),
+        ExprKind::Assignment(a) => ExprKind::assignment(a.name().to_owned(), expr(a.value())),
+
+        ExprKind::CompoundAssignment(a) => {
+            let span = a.span().clone();
+            let current = ExprKind::ident(a.name().to_owned()).with_span(span.clone());
+            let value = a.operator().apply(current, expr(a.value()));
+
+            ExprKind::assignment(a.name().to_owned(), value.with_span(span))
+        }
+
+        ExprKind::Tuple(t) => ExprKind::tuple(exprs(t.elements())),
+        ExprKind::Field(f) => ExprKind::field(expr(f.tuple()), f.idx()),
+        ExprKind::Array(a) => ExprKind::array(exprs(a.elements())),
+        ExprKind::Index(i) => ExprKind::index(expr(i.array()), expr(i.index())),
+
+        ExprKind::IndexAssignment(a) => {
+            let target = Index::new(expr(a.target().array()), expr(a.target().index()));
+            ExprKind::IndexAssignment(IndexAssignment::new(target, expr(a.value())))
+        }
+
+        ExprKind::StructLiteral(s) => {
+            let fields = s
+                .fields()
+                .iter()
+                .map(|(name, value)| (name.clone(), expr(value)))
+                .collect();
+
+            ExprKind::struct_literal(s.name().to_owned(), fields)
+        }
+
+        ExprKind::VariantLiteral(v) => {
+            let enum_name = v.enum_name().to_owned();
+            let variant = v.variant().to_owned();
+
+            if v.is_parenthesized() {
+                ExprKind::variant_literal(enum_name, variant, exprs(v.args()))
+            } else {
+                ExprKind::unit_variant(enum_name, variant)
+            }
+        }
+
+        ExprKind::Match(m) => {
+            let arms = m
+                .arms()
+                .iter()
+                .map(|arm| {
+                    MatchArm::new(arm.pattern().clone(), expr(arm.body()))
+                        .with_guard(arm.guard().map(expr))
+                        .with_span(arm.span().clone())
+                })
+                .collect();
+
+            ExprKind::match_(expr(m.scrutinee()), arms)
+        }
+
+        ExprKind::Closure(c) => ExprKind::closure(c.params().to_vec(), expr(c.body())),
+
+        ExprKind::LocalFunctions(l) => {
+            let functions = l.functions().iter().map(function).collect();
+            ExprKind::local_functions(functions, expr(l.body()))
+        }
+
+        ExprKind::MacroCall(_) => unreachable!("Macros are expanded before the desugaring"),
+    };
+
+    desugared.with_span(e.span().clone())
+}
+
+fn exprs(es: &[ExprKind]) -> Vec<ExprKind> {
+    es.iter().map(expr).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast_json, inline_expr, parser};
+
+    use super::*;
+
+    fn desugar(code: &str) -> Program {
+        let (_, program) = parser::parse_input(code).unwrap();
+        desugar_program(&program)
+    }
+
+    #[test]
+    fn compound_assignments() {
+        let program = desugar("fn f(a) { a += 1; a -= 2; a *= 3 }");
+
+        assert_eq!(
+            program.functions()[0].body(),
+            &inline_expr! {
+                {
+                    a = a + 1;
+                    a = a - 2;
+                    a = a * 3
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn nested_code_is_desugared() {
+        let program = desugar(
+            "mod m { const C = { let mut a = 1; a += 1 }; }
+             fn f(a) { let g = |x| { a += x }; match a { _ => a *= 2 } }",
+        );
+
+        let json = ast_json::program_to_json(&program);
+        assert!(!json.contains("CompoundAssignment"));
+    }
+
+    #[test]
+    fn desugared_code_is_located_at_the_assignment() {
+        let program = desugar("fn f(a) {\n    a += 1\n}");
+
+        let (span, value) = match program.functions()[0].body() {
+            ExprKind::Assignment(a) => (a.span(), a.value()),
+            body => panic!("Expected an assignment, found {:?}", body),
+        };
+        let read = match value {
+            ExprKind::Addition(a) => a.left().span(),
+            value => panic!("Expected an addition, found {:?}", value),
+        };
+
+        assert_eq!((span.line(), span.column()), (2, 5));
+        assert_eq!((read.line(), read.column()), (2, 5));
+    }
+}
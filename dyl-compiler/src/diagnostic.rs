@@ -0,0 +1,874 @@
+//! The errors and warnings reported to the user.
+//!
+//! A diagnostic has a message, and may tell where the problem is (its
+//! primary span), point at other places that explain it (its secondary
+//! spans, each with a label), and end with notes and with suggestions of
+//! changes to the code that fix it. Diagnostics are collected by the context
+//! of each compiler pass, so that a pass reports every problem it finds
+//! before the compilation stops.
+//!
+//! When shown to the user, every span is followed by the line of code it is
+//! on, with the code it covers underlined.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult, Write},
+    vec,
+};
+
+use crate::{
+    ast::Span,
+    ast_json::{self, Json, ToJson},
+    lint::Lint,
+    passes::Location,
+    ty::{UnexpectedTypeError, UnificationError},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Stops the compilation once the pass is over.
+    Error,
+
+    /// Is only reported.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Identifies the kind of problem a diagnostic reports.
+///
+/// Errors are numbered `E0001` onwards and warnings `W0001` onwards, in the
+/// order the codes were introduced. A code is never reused for another kind
+/// of problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Code {
+    /// `E0001`: the code can't be parsed.
+    SyntaxError,
+
+    /// `E0002`: an item, a field, a variant or a pattern binding is defined
+    /// twice.
+    DuplicateName,
+
+    /// `E0003`: a name does not refer to anything in scope.
+    UndefinedName,
+
+    /// `E0004`: a value does not have the type its use requires.
+    TypeMismatch,
+
+    /// `E0005`: a function, a builtin or a variant is given the wrong number
+    /// of arguments.
+    WrongArgumentCount,
+
+    /// `E0006`: a constant or an immutable variable is assigned to, or its
+    /// array is modified.
+    ImmutableAssignment,
+
+    /// `E0007`: a constant can't be evaluated at compile time.
+    ConstantEvaluation,
+
+    /// `E0008`: a macro can't be expanded.
+    MacroExpansion,
+
+    /// `E0009`: there is no `main` function, or it takes arguments.
+    InvalidMain,
+
+    /// `E0010`: the arms of a `match` don't cover every possible value.
+    NonExhaustiveMatch,
+
+    /// `E0011`: a struct initializer or pattern leaves fields out.
+    MissingFields,
+
+    /// `E0012`: `return` is used outside of any function.
+    ReturnOutsideFunction,
+
+    /// `W0001`: a variable is never read.
+    UnusedVariable,
+
+    /// `W0002`: some code can never be executed.
+    Unreachable,
+
+    /// `W0003`: a binding hides a variable that is still in scope.
+    Shadowing,
+
+    /// `W0004`: a condition is always true, or always false.
+    ConstantCondition,
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let code = match self {
+            Code::SyntaxError => "E0001",
+            Code::DuplicateName => "E0002",
+            Code::UndefinedName => "E0003",
+            Code::TypeMismatch => "E0004",
+            Code::WrongArgumentCount => "E0005",
+            Code::ImmutableAssignment => "E0006",
+            Code::ConstantEvaluation => "E0007",
+            Code::MacroExpansion => "E0008",
+            Code::InvalidMain => "E0009",
+            Code::NonExhaustiveMatch => "E0010",
+            Code::MissingFields => "E0011",
+            Code::ReturnOutsideFunction => "E0012",
+
+            Code::UnusedVariable => "W0001",
+            Code::Unreachable => "W0002",
+            Code::Shadowing => "W0003",
+            Code::ConstantCondition => "W0004",
+        };
+
+        write!(f, "{}", code)
+    }
+}
+
+/// An error or a warning, found while compiling a program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: Option<Code>,
+
+    /// The message never changes, so it is boxed to keep diagnostics small.
+    message: Box<str>,
+
+    /// The name of the lint that reports the problem.
+    lint: Option<&'static str>,
+
+    /// Where the problem is.
+    span: Option<Span>,
+
+    /// Other places that explain the problem, with what they show.
+    secondary: Vec<(Span, String)>,
+
+    /// The notes and the suggestions, in the order they were added. They are
+    /// kept together so that diagnostics stay small.
+    help: Vec<Help>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Help {
+    Note(String),
+    Suggestion(Suggestion),
+}
+
+/// A change to the code that fixes the problem a diagnostic reports: the
+/// code the span covers is replaced, which inserts code if the span is
+/// empty. Suggestions are precise enough to be applied by a tool.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Suggestion {
+    span: Span,
+    message: String,
+    replacement: String,
+}
+
+impl Suggestion {
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    pub(crate) fn replacement(&self) -> &str {
+        self.replacement.as_str()
+    }
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message.into())
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message.into())
+    }
+
+    fn new(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into_boxed_str(),
+            lint: None,
+            span: None,
+            secondary: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_code(self, code: Code) -> Diagnostic {
+        Diagnostic {
+            code: Some(code),
+            ..self
+        }
+    }
+
+    /// Sets the lint that reports the problem. Its level applies to the
+    /// diagnostic once the lint is registered in the
+    /// [`LintLevels`](crate::LintLevels).
+    pub fn with_lint(self, lint: Lint) -> Diagnostic {
+        Diagnostic {
+            code: lint.code().or(self.code),
+            lint: Some(lint.name()),
+            ..self
+        }
+    }
+
+    /// Sets where the problem is.
+    pub(crate) fn with_span(self, span: Span) -> Diagnostic {
+        Diagnostic {
+            span: Some(span),
+            ..self
+        }
+    }
+
+    /// Sets where the problem is, for the diagnostics of custom passes.
+    pub fn with_location(self, location: &Location) -> Diagnostic {
+        self.with_span(location.0.clone())
+    }
+
+    /// Points at another place that explains the problem.
+    pub(crate) fn with_secondary(mut self, span: Span, label: impl Into<String>) -> Diagnostic {
+        self.secondary.push((span, label.into()));
+        self
+    }
+
+    pub(crate) fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.help.push(Help::Note(note.into()));
+        self
+    }
+
+    /// Suggests to replace the code `span` covers by `replacement`.
+    pub(crate) fn with_suggestion(
+        mut self,
+        span: Span,
+        message: impl Into<String>,
+        replacement: impl Into<String>,
+    ) -> Diagnostic {
+        self.help.push(Help::Suggestion(Suggestion {
+            span,
+            message: message.into(),
+            replacement: replacement.into(),
+        }));
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[cfg(test)]
+    pub(crate) fn code(&self) -> Option<Code> {
+        self.code
+    }
+
+    pub(crate) fn lint(&self) -> Option<&'static str> {
+        self.lint
+    }
+
+    /// Reports the same problem as an error.
+    pub(crate) fn into_error(self) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            ..self
+        }
+    }
+
+    pub(crate) fn suggestions(&self) -> impl Iterator<Item = &Suggestion> {
+        self.help.iter().filter_map(|help| match help {
+            Help::Suggestion(suggestion) => Some(suggestion),
+            Help::Note(_) => None,
+        })
+    }
+
+    /// Tells whether two diagnostics report the same problem at the same
+    /// place. Unlike `==`, which ignores them, the spans are compared.
+    pub(crate) fn is_same_as(&self, other: &Diagnostic) -> bool {
+        self.severity == other.severity
+            && self.code == other.code
+            && self.to_string() == other.to_string()
+    }
+
+    /// Formats the diagnostic the way it is shown to the user: its severity,
+    /// its code and its message, then each span along with the line of code
+    /// it is on, then the notes and the suggestions, along with the code the
+    /// suggestions lead to. ANSI colors are used if `colored` is set.
+    pub fn render(&self, colored: bool) -> String {
+        let style = Style(colored);
+        let mut out = String::new();
+
+        let color = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let header = match self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
+        };
+        let message = format!(": {}", self.message);
+        out.push_str(&style.paint(&header, color));
+        out.push_str(&style.paint(&message, BOLD));
+        out.push('\n');
+
+        // Line numbers are aligned on the widest one.
+        let width = self
+            .span
+            .iter()
+            .chain(self.secondary.iter().map(|(span, _)| span))
+            .chain(self.suggestions().map(Suggestion::span))
+            .map(|span| span.line().to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        if let Some(span) = &self.span {
+            render_span(&mut out, span, ("-->", '^', color), "", width, style);
+        }
+
+        for (span, label) in &self.secondary {
+            render_span(&mut out, span, (":::", '-', BLUE), label, width, style);
+        }
+
+        for help in &self.help {
+            match help {
+                Help::Note(note) => {
+                    let equals = style.paint("=", BLUE);
+                    let _ = writeln!(out, "{:width$} {} note: {}", "", equals, note);
+                }
+                Help::Suggestion(suggestion) => {
+                    render_suggestion(&mut out, suggestion, width, style)
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Formats the diagnostic as a single line of JSON, for the tools that
+    /// read the diagnostics: an object with its severity, its code, its
+    /// message, its spans (the primary one first), its notes, its
+    /// suggestions, and the diagnostic as `render` formats it.
+    pub fn to_json(&self) -> String {
+        ToJson::to_json(self).to_string()
+    }
+}
+
+impl ToJson for Diagnostic {
+    fn to_json(&self) -> Json {
+        let span = |span: &Span, primary: bool, label: Option<&str>| {
+            Json::Object(vec![
+                ("span", span.to_json()),
+                ("primary", primary.into()),
+                ("label", label.map_or(Json::Null, Into::into)),
+            ])
+        };
+
+        let spans = self
+            .span
+            .iter()
+            .map(|primary| span(primary, true, None))
+            .chain(
+                self.secondary
+                    .iter()
+                    .map(|(secondary, label)| span(secondary, false, Some(label))),
+            )
+            .collect();
+
+        let notes = self
+            .help
+            .iter()
+            .filter_map(|help| match help {
+                Help::Note(note) => Some(note.as_str().into()),
+                Help::Suggestion(_) => None,
+            })
+            .collect();
+
+        let code = self
+            .code
+            .map_or(Json::Null, |code| code.to_string().as_str().into());
+
+        Json::Object(vec![
+            ("severity", self.severity.to_string().as_str().into()),
+            ("code", code),
+            ("message", self.message().into()),
+            ("spans", Json::Array(spans)),
+            ("notes", Json::Array(notes)),
+            ("suggestions", ast_json::array(self.suggestions())),
+            ("rendered", self.render(false).as_str().into()),
+        ])
+    }
+}
+
+impl ToJson for Suggestion {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("span", self.span.to_json()),
+            ("message", self.message.as_str().into()),
+            ("replacement", self.replacement.as_str().into()),
+        ])
+    }
+}
+
+/// Returns the candidate that is the most likely to be what was meant when
+/// `name` was written, if one is close enough to it.
+pub(crate) fn closest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    // Only a third of a name may be mistyped, so that short names are not
+    // taken for each other.
+    let max_distance = name.chars().count() / 3;
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Counts the characters to insert, remove or replace, and the pairs of
+/// adjacent characters to swap, to go from `a` to `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+
+    // `distances[i][j]` is the distance between the first `i` characters of
+    // `a` and the first `j` characters of `b`.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    (0..=a.len()).for_each(|i| distances[i][0] = i);
+    (0..=b.len()).for_each(|j| distances[0][j] = j);
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replaced = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = replaced
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy)]
+struct Style(bool);
+
+impl Style {
+    fn paint(self, text: &str, color: &str) -> String {
+        if self.0 {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+/// Writes where a span is, then the line of code it starts on, with the
+/// code it covers on that line underlined by `marker`s and followed by
+/// `label`.
+///
+/// Only the location is written if the code the span comes from is not
+/// known.
+fn render_span(
+    out: &mut String,
+    span: &Span,
+    (arrow, marker, color): (&str, char, &str),
+    label: &str,
+    width: usize,
+    style: Style,
+) {
+    let gutter = style.paint("|", BLUE);
+    let arrow = style.paint(arrow, BLUE);
+
+    let (text, start, end) = match source_line(span) {
+        Some(line) => line,
+        None if label.is_empty() => {
+            let _ = writeln!(out, "{:width$}{} {}", "", arrow, span);
+            return;
+        }
+        None => {
+            let _ = writeln!(out, "{:width$}{} {}: {}", "", arrow, span, label);
+            return;
+        }
+    };
+
+    let length = text[start..end].chars().count().max(1);
+    let underline = marker.to_string().repeat(length);
+    let underline = match label {
+        "" => underline,
+        _ => format!("{} {}", underline, label),
+    };
+    let line_number = style.paint(&format!("{:>width$}", span.line()), BLUE);
+
+    let _ = writeln!(out, "{:width$}{} {}", "", arrow, span);
+    let _ = writeln!(out, "{:width$} {}", "", gutter);
+    let _ = writeln!(out, "{} {} {}", line_number, gutter, text);
+    let _ = writeln!(
+        out,
+        "{:width$} {} {}{}",
+        "",
+        gutter,
+        blanks(&text[..start]),
+        style.paint(&underline, color)
+    );
+}
+
+/// Writes what a suggestion does, then the line of code it changes as it is
+/// once changed, with the new code marked by `+`s if it is only inserted,
+/// and by `~`s otherwise.
+///
+/// Only the message is written if the code the suggestion changes is not
+/// known, or if the new code spans several lines.
+fn render_suggestion(out: &mut String, suggestion: &Suggestion, width: usize, style: Style) {
+    let help = style.paint("help", BOLD);
+    let replacement = suggestion.replacement();
+
+    let (text, start, end) = match source_line(suggestion.span()) {
+        Some(line) if !replacement.contains('\n') => line,
+        _ => {
+            let equals = style.paint("=", BLUE);
+            let _ = writeln!(
+                out,
+                "{:width$} {} {}: {}",
+                "",
+                equals,
+                help,
+                suggestion.message()
+            );
+            return;
+        }
+    };
+
+    let gutter = style.paint("|", BLUE);
+    let changed = format!("{}{}{}", &text[..start], replacement, &text[end..]);
+    let marker = if start == end { "+" } else { "~" };
+    let markers = marker.repeat(replacement.chars().count().max(1));
+    let line_number = style.paint(&format!("{:>width$}", suggestion.span().line()), BLUE);
+
+    let _ = writeln!(out, "{}: {}", help, suggestion.message());
+    let _ = writeln!(out, "{:width$} {}", "", gutter);
+    let _ = writeln!(out, "{} {} {}", line_number, gutter, changed);
+    let _ = writeln!(
+        out,
+        "{:width$} {} {}{}",
+        "",
+        gutter,
+        blanks(&text[..start]),
+        style.paint(&markers, GREEN)
+    );
+}
+
+/// Replaces every character of some code by a space, but keeps the tabs, so
+/// that what follows is aligned with the code.
+fn blanks(code: &str) -> String {
+    code.chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// Returns the line of code a span starts on, and where the code the span
+/// covers starts and ends in that line, in bytes.
+fn source_line(span: &Span) -> Option<(&str, usize, usize)> {
+    let content = span.file()?.content();
+    let start = span.offset();
+
+    if start > content.len() || !content.is_char_boundary(start) {
+        return None;
+    }
+
+    let line_start = content[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = content[start..]
+        .find('\n')
+        .map_or(content.len(), |idx| start + idx);
+    let text = content[line_start..line_end].trim_end_matches('\r');
+
+    let start = (start - line_start).min(text.len());
+    let mut end = (start + span.length()).clamp(start, text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    Some((text, start, end))
+}
+
+/// Formats the message, preceded by the location of the problem, with a
+/// line for each secondary span, each note and each suggestion.
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+
+        write!(f, "{}", self.message)?;
+
+        for (span, label) in &self.secondary {
+            write!(f, "\n  {}: {}", span, label)?;
+        }
+
+        for help in &self.help {
+            match help {
+                Help::Note(note) => write!(f, "\n  note: {}", note)?,
+                Help::Suggestion(suggestion) => write!(f, "\n  help: {}", suggestion.message())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The diagnostics reported while compiling a program, in the order they
+/// were found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Tells whether one of the diagnostics is an error.
+    pub fn has_errors(&self) -> bool {
+        self.iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl FromIterator<Diagnostic> for Diagnostics {
+    fn from_iter<I: IntoIterator<Item = Diagnostic>>(diagnostics: I) -> Diagnostics {
+        Diagnostics(diagnostics.into_iter().collect())
+    }
+}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(diagnostic: Diagnostic) -> Diagnostics {
+        Diagnostics(vec![diagnostic])
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Renders every diagnostic, without colors, the way the compiler shows
+/// them.
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        self.iter()
+            .try_for_each(|diagnostic| writeln!(f, "{}", diagnostic.render(false)))
+    }
+}
+
+impl Error for Diagnostics {}
+
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Diagnostic {
+        Diagnostic::error(message)
+    }
+}
+
+impl From<&str> for Diagnostic {
+    fn from(message: &str) -> Diagnostic {
+        Diagnostic::error(message)
+    }
+}
+
+impl From<UnexpectedTypeError> for Diagnostic {
+    fn from(e: UnexpectedTypeError) -> Diagnostic {
+        Diagnostic::error(e.to_string()).with_code(Code::TypeMismatch)
+    }
+}
+
+impl From<UnificationError> for Diagnostic {
+    fn from(e: UnificationError) -> Diagnostic {
+        Diagnostic::error(e.to_string()).with_code(Code::TypeMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::io::SourceFile;
+
+    use super::*;
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic::error("`x` is declared more than once")
+            .with_span(Span::new(3, 9))
+            .with_secondary(Span::new(2, 9), "first declared here")
+            .with_note("names must be unique");
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "3:9: `x` is declared more than once\n  \
+             2:9: first declared here\n  \
+             note: names must be unique"
+        );
+    }
+
+    #[test]
+    fn render() {
+        let error = Diagnostic::error("Undefined variable `a`")
+            .with_code(Code::UndefinedName)
+            .with_span(Span::new(1, 5));
+        let warning = Diagnostic::warning("Unreachable code");
+
+        assert_eq!(
+            error.render(false),
+            "error[E0003]: Undefined variable `a`\n --> 1:5\n"
+        );
+        assert_eq!(warning.render(false), "warning: Unreachable code\n");
+    }
+
+    #[test]
+    fn json() {
+        let diagnostic = Diagnostic::warning("Unused variable `x`")
+            .with_code(Code::UnusedVariable)
+            .with_span(Span::new(3, 9))
+            .with_secondary(Span::new(2, 9), "declared here")
+            .with_note("it is never read");
+
+        assert_eq!(
+            diagnostic.to_json(),
+            "{\"severity\":\"warning\",\"code\":\"W0001\",\"message\":\"Unused variable `x`\",\
+             \"spans\":[\
+             {\"span\":{\"file\":null,\"line\":3,\"column\":9,\"offset\":0,\"length\":0},\
+             \"primary\":true,\"label\":null},\
+             {\"span\":{\"file\":null,\"line\":2,\"column\":9,\"offset\":0,\"length\":0},\
+             \"primary\":false,\"label\":\"declared here\"}],\
+             \"notes\":[\"it is never read\"],\"suggestions\":[],\
+             \"rendered\":\"warning[W0001]: Unused variable `x`\\n --> 3:9\\n \
+             ::: 2:9: declared here\\n  = note: it is never read\\n\"}"
+        );
+    }
+
+    #[test]
+    fn render_with_code() {
+        let code = "fn main() {\n\tlet x = 1;\n\tlet x = 2;\n}\n";
+        let file = Arc::new(SourceFile::new("a.dyl".to_owned(), code.to_owned()));
+        let at = |line, column, offset| {
+            Span::new(line, column)
+                .with_offset(offset)
+                .with_length(1)
+                .in_file(Arc::clone(&file))
+        };
+
+        let diagnostic = Diagnostic::error("`x` is declared more than once")
+            .with_code(Code::DuplicateName)
+            .with_span(at(3, 6, 29))
+            .with_secondary(at(2, 6, 17), "first declared here")
+            .with_note("names must be unique");
+
+        assert_eq!(
+            diagnostic.render(false),
+            "error[E0002]: `x` is declared more than once\n \
+             --> a.dyl:3:6\n  \
+             |\n\
+             3 | \tlet x = 2;\n  \
+             | \t    ^\n \
+             ::: a.dyl:2:6\n  \
+             |\n\
+             2 | \tlet x = 1;\n  \
+             | \t    - first declared here\n  \
+             = note: names must be unique\n"
+        );
+    }
+
+    #[test]
+    fn render_suggestions() {
+        let code = "let x = 1\nx";
+        let file = Arc::new(SourceFile::new("a.dyl".to_owned(), code.to_owned()));
+        let at = |line, column, offset, length| {
+            Span::new(line, column)
+                .with_offset(offset)
+                .with_length(length)
+                .in_file(Arc::clone(&file))
+        };
+
+        let diagnostic = Diagnostic::error("Expected `;`")
+            .with_span(at(2, 1, 10, 1))
+            .with_suggestion(at(1, 10, 9, 0), "insert `;` here", ";")
+            .with_suggestion(at(1, 5, 4, 1), "rename it", "_x");
+
+        assert_eq!(
+            diagnostic.render(false),
+            "error: Expected `;`\n \
+             --> a.dyl:2:1\n  \
+             |\n\
+             2 | x\n  \
+             | ^\n\
+             help: insert `;` here\n  \
+             |\n\
+             1 | let x = 1;\n  \
+             |          +\n\
+             help: rename it\n  \
+             |\n\
+             1 | let _x = 1\n  \
+             |     ~~\n"
+        );
+    }
+
+    #[test]
+    fn closest_names() {
+        let names = ["count", "counter", "LIMIT"];
+
+        assert_eq!(closest_name("cuont", names), Some("count"));
+        assert_eq!(closest_name("countr", names), Some("count"));
+        assert_eq!(closest_name("limit", names), None);
+        assert_eq!(closest_name("x", ["y"]), None);
+    }
+
+    #[test]
+    fn render_in_color() {
+        let warning = Diagnostic::warning("Unreachable code").with_code(Code::Unreachable);
+
+        assert_eq!(
+            warning.render(true),
+            "\x1b[1;33mwarning[W0002]\x1b[0m\x1b[1m: Unreachable code\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn spans_tell_diagnostics_apart() {
+        let at = |line| Diagnostic::error("Syntax error").with_span(Span::new(line, 1));
+
+        assert!(at(1).is_same_as(&at(1)));
+        assert!(!at(1).is_same_as(&at(2)));
+    }
+}
@@ -0,0 +1,574 @@
+//! Expands the macros of a program.
+//!
+//! An invocation `name!(args)` is replaced by the expansion of the macro,
+//! where each parameter is replaced by the code of its argument. The
+//! arguments are not evaluated before the expansion: an argument that is
+//! used twice is evaluated twice, and one that is not used is never
+//! evaluated.
+//!
+//! The other names of the expansion are looked up where the macro is
+//! invoked, and the code of the expansion is located at the invocation, so
+//! that the errors it contains point to the code that was written. Macros
+//! are visible from the whole program, whatever the module they are declared
+//! in, and may invoke each other, up to a limit.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        Binding, Const, ExprKind, Function, Index, IndexAssignment, Item, Logical, Macro,
+        MacroCall, MatchArm, Module, Program, Span,
+    },
+    context::{ErrorContext, ParsingContext, PassResult},
+    diagnostic::{Code, Diagnostic},
+};
+
+/// The number of invocations that can be expanded one inside another.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+pub(crate) fn expand_macros(
+    program: &Program,
+    ctxt: ParsingContext,
+) -> PassResult<ParsingContext, Program> {
+    let expanded = expand_program(program, ctxt.errors());
+
+    ctxt.wrap_result(Ok(expanded))
+}
+
+fn expand_program(program: &Program, errors: &ErrorContext) -> Program {
+    let mut expander = Expander {
+        macros: HashMap::new(),
+        errors,
+        invocations: Vec::new(),
+        overflowed: false,
+    };
+    expander.declare(program);
+
+    Program::new(expander.items(program))
+}
+
+struct Expander<'a> {
+    macros: HashMap<&'a str, &'a Macro>,
+    errors: &'a ErrorContext,
+
+    /// The invocations being expanded, the innermost last.
+    invocations: Vec<Invocation>,
+
+    /// Whether an expansion went past the depth limit. The invocations are
+    /// no longer expanded afterwards, as a recursive macro may invoke itself
+    /// many times.
+    overflowed: bool,
+}
+
+/// An invocation being expanded.
+struct Invocation {
+    /// The argument of each parameter.
+    args: Vec<(String, ExprKind)>,
+
+    /// The names bound in the expansion, which hide the parameters.
+    shadowed: Vec<String>,
+
+    /// The location of the invocation, which is given to the code of the
+    /// expansion.
+    span: Span,
+}
+
+impl<'a> Expander<'a> {
+    /// Registers the macros of a program and of its modules.
+    fn declare(&mut self, program: &'a Program) {
+        for m in program.macros() {
+            match self.macros.get(m.name()) {
+                Some(first) => {
+                    let error =
+                        Diagnostic::error(format!("`{}` is declared more than once", m.name()))
+                            .with_code(Code::DuplicateName)
+                            .with_span(m.span().clone())
+                            .with_secondary(first.span().clone(), "first declared here");
+
+                    self.errors.add(error);
+                }
+                None => {
+                    self.macros.insert(m.name(), m);
+                }
+            }
+        }
+
+        for module in program.modules() {
+            self.declare(module.content());
+        }
+    }
+
+    /// Expands the invocations in the items of a program. Its macros are
+    /// dropped.
+    fn items(&mut self, program: &Program) -> Vec<Item> {
+        let functions = program
+            .functions()
+            .iter()
+            .map(|f| Item::Function(self.function(f)))
+            .collect::<Vec<_>>();
+
+        let consts = program
+            .consts()
+            .iter()
+            .map(|c| {
+                let value = self.expr(c.value());
+                Item::Const(Const::new(c.name().to_owned(), value).with_span(c.span().clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let modules = program
+            .modules()
+            .iter()
+            .map(|m| {
+                let items = self.items(m.content());
+                Item::Module(Module::new(m.name().to_owned(), items).with_span(m.span().clone()))
+            })
+            .collect::<Vec<_>>();
+
+        program
+            .structs()
+            .iter()
+            .cloned()
+            .map(Item::Struct)
+            .chain(program.enums().iter().cloned().map(Item::Enum))
+            .chain(consts)
+            .chain(functions)
+            .chain(modules)
+            .chain(program.imports().iter().cloned().map(Item::Import))
+            .collect()
+    }
+
+    fn function(&mut self, f: &Function) -> Function {
+        let scope_start = self.shadow(f.args().iter().map(String::as_str).collect());
+        let body = self.expr(f.body());
+        self.unshadow(scope_start);
+
+        Function::new(f.name().to_owned(), f.args().to_vec(), body)
+            .with_signature(
+                f.generics().to_vec(),
+                f.arg_types().to_vec(),
+                f.ret_type().cloned(),
+            )
+            .with_span(self.span(f.span()))
+    }
+
+    fn expr(&mut self, expr: &ExprKind) -> ExprKind {
+        let expanded = match expr {
+            ExprKind::Addition(a) => {
+                let (left, right) = self.pair(a.left(), a.right());
+                ExprKind::addition(left, right)
+            }
+            ExprKind::Subtraction(s) => {
+                let (left, right) = self.pair(s.left(), s.right());
+                ExprKind::subtraction(left, right)
+            }
+            ExprKind::Multiplication(m) => {
+                let (left, right) = self.pair(m.left(), m.right());
+                ExprKind::multiplication(left, right)
+            }
+            ExprKind::Exponentiation(e) => {
+                let (left, right) = self.pair(e.left(), e.right());
+                ExprKind::exponentiation(left, right)
+            }
+            ExprKind::Comparison(c) => {
+                let (left, right) = self.pair(c.left(), c.right());
+                ExprKind::comparison(c.operator(), left, right)
+            }
+            ExprKind::Logical(l) => {
+                let (left, right) = self.pair(l.left(), l.right());
+                ExprKind::Logical(Logical::new(l.operator(), left, right))
+            }
+
+            ExprKind::Cast(c) => ExprKind::cast(self.expr(c.value()), c.target().clone()),
+
+            ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_) => expr.clone(),
+
+            // Arguments are located where they are written.
+            ExprKind::Ident(i) => match self.argument(i.name()) {
+                Some(arg) => return arg,
+                None => expr.clone(),
+            },
+
+            ExprKind::If(i) => {
+                let condition = self.expr(i.condition());
+                let consequent = self.expr(i.consequent());
+
+                match i.alternative() {
+                    Some(alternative) => {
+                        ExprKind::if_(condition, consequent, self.expr(alternative))
+                    }
+                    None => ExprKind::if_without_else(condition, consequent),
+                }
+            }
+
+            ExprKind::Bindings(b) => {
+                let scope_start = self.shadowed_len();
+
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .map(|binding| {
+                        let value = self.expr(binding.value());
+                        self.shadow(binding.names());
+
+                        Binding::from_pattern(
+                            binding.pattern().clone(),
+                            value,
+                            binding.is_mutable(),
+                        )
+                        .with_annotation(binding.annotation().cloned())
+                        .with_span(self.span(binding.span()))
+                    })
+                    .collect();
+
+                let ending = self.expr(b.ending_expression());
+                self.unshadow(scope_start);
+
+                b.rebuild(bindings, ending)
+            }
+
+            ExprKind::Statements(s) => {
+                let statements = self.exprs(s.statements());
+                let ending = self.expr(s.ending_expression());
+
+                s.rebuild(statements, ending)
+            }
+
+            // A parameter whose argument is a name can be called.
+            ExprKind::FnCall(c) => {
+                let name = self.renamed(c.name());
+                c.rebuild(name, self.exprs(c.args()))
+            }
+
+            ExprKind::Return(r) => ExprKind::return_(self.expr(r.value())),
+
+            ExprKind::Assignment(a) => {
+                ExprKind::assignment(self.renamed(a.name()), self.expr(a.value()))
+            }
+            ExprKind::CompoundAssignment(a) => ExprKind::compound_assignment(
+                a.operator(),
+                self.renamed(a.name()),
+                self.expr(a.value()),
+            ),
+
+            ExprKind::Tuple(t) => ExprKind::tuple(self.exprs(t.elements())),
+            ExprKind::Field(f) => ExprKind::field(self.expr(f.tuple()), f.idx()),
+            ExprKind::Array(a) => ExprKind::array(self.exprs(a.elements())),
+
+            ExprKind::Index(i) => {
+                let (array, index) = self.pair(i.array(), i.index());
+                ExprKind::index(array, index)
+            }
+
+            ExprKind::IndexAssignment(a) => {
+                let (array, index) = self.pair(a.target().array(), a.target().index());
+                let value = self.expr(a.value());
+
+                ExprKind::IndexAssignment(IndexAssignment::new(Index::new(array, index), value))
+            }
+
+            ExprKind::StructLiteral(s) => {
+                let fields = s
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.expr(value)))
+                    .collect();
+
+                ExprKind::struct_literal(s.name().to_owned(), fields)
+            }
+
+            ExprKind::VariantLiteral(v) => {
+                let enum_name = v.enum_name().to_owned();
+                let variant = v.variant().to_owned();
+
+                if v.is_parenthesized() {
+                    ExprKind::variant_literal(enum_name, variant, self.exprs(v.args()))
+                } else {
+                    ExprKind::unit_variant(enum_name, variant)
+                }
+            }
+
+            ExprKind::Match(m) => {
+                let scrutinee = self.expr(m.scrutinee());
+
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let scope_start = self.shadow(arm.pattern().names());
+
+                        let guard = arm.guard().map(|guard| self.expr(guard));
+                        let body = self.expr(arm.body());
+                        self.unshadow(scope_start);
+
+                        MatchArm::new(arm.pattern().clone(), body)
+                            .with_guard(guard)
+                            .with_span(self.span(arm.span()))
+                    })
+                    .collect();
+
+                ExprKind::match_(scrutinee, arms)
+            }
+
+            ExprKind::Closure(c) => {
+                let scope_start = self.shadow(c.params().iter().map(String::as_str).collect());
+                let body = self.expr(c.body());
+                self.unshadow(scope_start);
+
+                ExprKind::closure(c.params().to_vec(), body)
+            }
+
+            ExprKind::LocalFunctions(l) => {
+                let functions = l.functions().iter().map(|f| self.function(f)).collect();
+                let body = self.expr(l.body());
+
+                ExprKind::local_functions(functions, body)
+            }
+
+            ExprKind::MacroCall(m) => return self.invocation(m),
+        };
+
+        expanded.with_span(self.span(expr.span()))
+    }
+
+    /// Expands an invocation, after the invocations in its arguments.
+    fn invocation(&mut self, call: &MacroCall) -> ExprKind {
+        let span = self.span(call.span());
+        let placeholder = ExprKind::unit().with_span(span.clone());
+
+        if self.overflowed {
+            return placeholder;
+        }
+
+        let m = match self.macros.get(call.name()) {
+            Some(m) => *m,
+            None => {
+                let error = Diagnostic::error(format!("Undefined macro `{}`", call.name()))
+                    .with_code(Code::UndefinedName)
+                    .with_span(span);
+                self.errors.add(error);
+
+                return placeholder;
+            }
+        };
+
+        if m.params().len() != call.args().len() {
+            let error = Diagnostic::error(format!(
+                "Wrong number of arguments for `{}!`: expected {}, found {}",
+                m.name(),
+                m.params().len(),
+                call.args().len(),
+            ))
+            .with_code(Code::WrongArgumentCount)
+            .with_span(span)
+            .with_secondary(m.span().clone(), "macro declared here");
+            self.errors.add(error);
+
+            return placeholder;
+        }
+
+        if self.invocations.len() == MAX_EXPANSION_DEPTH {
+            let error = Diagnostic::error(format!(
+                "Recursion limit reached while expanding `{}!`",
+                m.name()
+            ))
+            .with_code(Code::MacroExpansion)
+            .with_span(span)
+            .with_note(format!(
+                "at most {} invocations can be expanded one inside another",
+                MAX_EXPANSION_DEPTH
+            ));
+            self.errors.add(error);
+            self.overflowed = true;
+
+            return placeholder;
+        }
+
+        let args = m
+            .params()
+            .iter()
+            .cloned()
+            .zip(self.exprs(call.args()))
+            .collect();
+
+        self.invocations.push(Invocation {
+            args,
+            shadowed: Vec::new(),
+            span,
+        });
+        let expanded = self.expr(m.body());
+        self.invocations.pop();
+
+        expanded
+    }
+
+    fn exprs(&mut self, exprs: &[ExprKind]) -> Vec<ExprKind> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn pair(&mut self, left: &ExprKind, right: &ExprKind) -> (ExprKind, ExprKind) {
+        (self.expr(left), self.expr(right))
+    }
+
+    /// Returns the argument a name is replaced by, if it is a parameter of
+    /// the macro being expanded.
+    fn argument(&self, name: &str) -> Option<ExprKind> {
+        let invocation = self.invocations.last()?;
+
+        if invocation.shadowed.iter().any(|shadowed| shadowed == name) {
+            return None;
+        }
+
+        invocation
+            .args
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, arg)| arg.clone())
+    }
+
+    /// Returns the name a function or a variable is called by, which is the
+    /// argument of a parameter when that argument is a name.
+    fn renamed(&self, name: &str) -> String {
+        match self.argument(name) {
+            Some(ExprKind::Ident(ident)) => ident.name().to_owned(),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Returns the location of some code, which is the invocation when the
+    /// code comes from an expansion.
+    fn span(&self, span: &Span) -> Span {
+        match self.invocations.last() {
+            Some(invocation) => invocation.span.clone(),
+            None => span.clone(),
+        }
+    }
+
+    fn shadowed_len(&self) -> usize {
+        self.invocations
+            .last()
+            .map_or(0, |invocation| invocation.shadowed.len())
+    }
+
+    /// Hides the parameters that have the given names, and returns how many
+    /// names were hidden before, to be passed to `unshadow`.
+    fn shadow(&mut self, names: Vec<&str>) -> usize {
+        let scope_start = self.shadowed_len();
+
+        if let Some(invocation) = self.invocations.last_mut() {
+            invocation
+                .shadowed
+                .extend(names.into_iter().map(str::to_owned));
+        }
+
+        scope_start
+    }
+
+    fn unshadow(&mut self, scope_start: usize) {
+        if let Some(invocation) = self.invocations.last_mut() {
+            invocation.shadowed.truncate(scope_start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
+
+    use super::*;
+
+    fn expand(code: &str) -> (Program, String) {
+        let (_, program) = parser::parse_input(code).unwrap();
+        let errors = ErrorContext::default();
+
+        let expanded = expand_program(&program, &errors);
+
+        (expanded, errors.to_string())
+    }
+
+    fn parse(code: &str) -> Program {
+        parser::parse_input(code).unwrap().1
+    }
+
+    #[test]
+    fn arguments_replace_parameters() {
+        let (left, _) = expand("macro sq(x) => x * x; fn main() { sq!(1 + 2) }");
+
+        assert_eq!(left, parse("fn main() { (1 + 2) * (1 + 2) }"));
+    }
+
+    #[test]
+    fn nested_invocations() {
+        let (left, _) = expand(
+            "macro sq(x) => x * x; macro max(a, b) => if a > b { a } else { b }; \
+             fn main() { max!(sq!(2), sq!(3)) }",
+        );
+        let right = parse("fn main() { if 2 * 2 > 3 * 3 { 2 * 2 } else { 3 * 3 } }");
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn macros_of_modules_are_visible_everywhere() {
+        let (left, _) = expand("mod m { macro one() => 1; } fn main() { one!() }");
+
+        assert_eq!(left, parse("mod m {} fn main() { 1 }"));
+    }
+
+    #[test]
+    fn bindings_hide_parameters() {
+        let (left, _) = expand("macro m(x) => { let x = x + 1; x }; fn main() { m!(2) }");
+
+        assert_eq!(left, parse("fn main() { let x = 2 + 1; x }"));
+    }
+
+    #[test]
+    fn names_can_be_called() {
+        let (left, _) = expand("macro twice(f, x) => f(f(x)); fn main() { twice!(g, 1) }");
+
+        assert_eq!(left, parse("fn main() { g(g(1)) }"));
+    }
+
+    #[test]
+    fn expansions_are_located_at_the_invocation() {
+        let (left, _) = expand("macro m(x) =>\n    x + y;\nfn main() {\n    m!(1)\n}");
+        let body = left.functions()[0].body();
+
+        assert_eq!(body.span().line(), 4);
+        match body {
+            ExprKind::Addition(a) => {
+                assert_eq!(a.left().span().column(), 8);
+                assert_eq!(a.right().span().column(), 5);
+            }
+            _ => panic!("Expected an addition, found {:?}", body),
+        }
+    }
+
+    #[test]
+    fn undefined_macros() {
+        let (_, errors) = expand("fn main() { m!() }");
+
+        assert!(errors.contains("Undefined macro `m`"));
+    }
+
+    #[test]
+    fn wrong_number_of_arguments() {
+        let (_, errors) = expand("macro m(x) => x; fn main() { m!(1, 2) }");
+
+        assert!(errors.contains("Wrong number of arguments for `m!`: expected 1, found 2"));
+    }
+
+    #[test]
+    fn duplicate_macros() {
+        let (_, errors) = expand("macro m() => 1; mod a { macro m() => 2; } fn main() { 0 }");
+
+        assert!(errors.contains("`m` is declared more than once"));
+    }
+
+    #[test]
+    fn recursion_is_limited() {
+        let (_, errors) = expand("macro m(x) => m!(x) + m!(x); fn main() { m!(1) }");
+
+        assert_eq!(errors.matches("Recursion limit reached").count(), 1);
+    }
+}
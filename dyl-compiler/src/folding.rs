@@ -0,0 +1,378 @@
+//! Evaluates at compile time the expressions that only depend on literals.
+//!
+//! Arithmetic on integer literals and comparisons between them are replaced
+//! by their result, a logical operator whose left operand is a literal is
+//! replaced by the operand it evaluates to, and an `if` whose condition is a
+//! literal is replaced by the branch it takes. Uses of immutable variables
+//! bound to a literal are replaced by the literal itself, the binding is
+//! kept.
+//!
+//! Operations that overflow or fail at runtime are left as is, so that they
+//! behave as if they were not folded: an overflow wraps around, or fails
+//! with debug checks.
+
+use crate::ast::{
+    Binding, BindingPattern, ComparisonOperator, ExprKind, Function, Item, Logical,
+    LogicalOperator, MatchArm, Program,
+};
+
+pub(crate) fn fold_program(program: &Program) -> Program {
+    let items = program
+        .structs()
+        .iter()
+        .cloned()
+        .map(Item::Struct)
+        .chain(program.enums().iter().cloned().map(Item::Enum))
+        .chain(program.consts().iter().cloned().map(Item::Const))
+        .chain(
+            program
+                .functions()
+                .iter()
+                .map(fold_function)
+                .map(Item::Function),
+        )
+        .collect();
+
+    Program::new(items)
+}
+
+fn fold_function(f: &Function) -> Function {
+    let mut folder = Folder {
+        scope: f.args().iter().map(|arg| (arg.clone(), None)).collect(),
+    };
+    let body = folder.expr(f.body());
+
+    Function::new(f.name().to_owned(), f.args().to_vec(), body)
+        .with_signature(
+            f.generics().to_vec(),
+            f.arg_types().to_vec(),
+            f.ret_type().cloned(),
+        )
+        .with_span(f.span().clone())
+}
+
+struct Folder {
+    /// The variables in scope, innermost last, with the literal they are
+    /// bound to if they are immutable and bound to a literal.
+    scope: Vec<(String, Option<ExprKind>)>,
+}
+
+impl Folder {
+    fn expr(&mut self, expr: &ExprKind) -> ExprKind {
+        match expr {
+            ExprKind::Addition(a) => {
+                let (left, right) = self.pair(a.left(), a.right());
+
+                fold_arithmetic(&left, &right, i32::checked_add)
+                    .unwrap_or_else(|| ExprKind::addition(left, right).with_span(a.span().clone()))
+            }
+
+            ExprKind::Subtraction(s) => {
+                let (left, right) = self.pair(s.left(), s.right());
+
+                fold_arithmetic(&left, &right, i32::checked_sub).unwrap_or_else(|| {
+                    ExprKind::subtraction(left, right).with_span(s.span().clone())
+                })
+            }
+
+            ExprKind::Multiplication(m) => {
+                let (left, right) = self.pair(m.left(), m.right());
+
+                fold_arithmetic(&left, &right, i32::checked_mul).unwrap_or_else(|| {
+                    ExprKind::multiplication(left, right).with_span(m.span().clone())
+                })
+            }
+
+            ExprKind::Exponentiation(e) => {
+                let (left, right) = self.pair(e.left(), e.right());
+
+                fold_arithmetic(&left, &right, |l, r| {
+                    u32::try_from(r).ok().and_then(|r| l.checked_pow(r))
+                })
+                .unwrap_or_else(|| {
+                    ExprKind::exponentiation(left, right).with_span(e.span().clone())
+                })
+            }
+
+            ExprKind::Comparison(c) => {
+                let (left, right) = self.pair(c.left(), c.right());
+
+                match fold_comparison(c.operator(), &left, &right) {
+                    Some(holds) => ExprKind::bool_(holds),
+                    None => {
+                        ExprKind::comparison(c.operator(), left, right).with_span(c.span().clone())
+                    }
+                }
+            }
+
+            ExprKind::Logical(l) => {
+                let (left, right) = self.pair(l.left(), l.right());
+
+                match (l.operator(), as_bool(&left)) {
+                    (LogicalOperator::And, Some(true)) | (LogicalOperator::Or, Some(false)) => {
+                        right
+                    }
+                    (LogicalOperator::And, Some(false)) | (LogicalOperator::Or, Some(true)) => left,
+
+                    (operator, None) => ExprKind::Logical(Logical::new(operator, left, right))
+                        .with_span(l.span().clone()),
+                }
+            }
+
+            ExprKind::Cast(c) => {
+                let value = self.expr(c.value());
+
+                match (&value, c.target().is_numeric()) {
+                    (ExprKind::Integer(_), true) => value,
+                    (ExprKind::Bool(b), true) => ExprKind::integer(b.value() as i32),
+                    _ => ExprKind::cast(value, c.target().clone()),
+                }
+            }
+
+            ExprKind::If(i) => {
+                let condition = self.expr(i.condition());
+                let consequent = self.expr(i.consequent());
+                let alternative = i.alternative().map(|alternative| self.expr(alternative));
+
+                match (as_bool(&condition), alternative) {
+                    (Some(true), Some(_)) => consequent,
+                    (Some(false), Some(alternative)) => alternative,
+
+                    // The value of the consequent is discarded when there is
+                    // no `else` branch.
+                    (Some(true), None) => ExprKind::statements(vec![consequent], ExprKind::unit()),
+                    (Some(false), None) => ExprKind::unit(),
+
+                    (None, Some(alternative)) => ExprKind::if_(condition, consequent, alternative)
+                        .with_span(i.span().clone()),
+                    (None, None) => {
+                        ExprKind::if_without_else(condition, consequent).with_span(i.span().clone())
+                    }
+                }
+            }
+
+            ExprKind::Bindings(b) => {
+                let scope_start = self.scope.len();
+
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .map(|binding| {
+                        let value = self.expr(binding.value());
+
+                        match binding.pattern() {
+                            BindingPattern::Name(name)
+                                if !binding.is_mutable() && is_literal(&value) =>
+                            {
+                                self.scope.push((name.clone(), Some(value.clone())))
+                            }
+                            _ => self.scope.extend(
+                                binding
+                                    .names()
+                                    .into_iter()
+                                    .map(|name| (name.to_owned(), None)),
+                            ),
+                        }
+
+                        Binding::from_pattern(
+                            binding.pattern().clone(),
+                            value,
+                            binding.is_mutable(),
+                        )
+                        .with_annotation(binding.annotation().cloned())
+                        .with_span(binding.span().clone())
+                    })
+                    .collect();
+
+                let ending = self.expr(b.ending_expression());
+                self.scope.truncate(scope_start);
+
+                ExprKind::bindings(bindings, ending)
+            }
+
+            ExprKind::Ident(i) => {
+                match self.scope.iter().rev().find(|(name, _)| name == i.name()) {
+                    Some((_, Some(literal))) => literal.clone(),
+                    _ => expr.clone(),
+                }
+            }
+
+            ExprKind::Match(m) => {
+                let scrutinee = self.expr(m.scrutinee());
+
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let scope_start = self.scope.len();
+                        self.shadow(arm.pattern().names());
+
+                        let guard = arm.guard().map(|guard| self.expr(guard));
+                        let body = self.expr(arm.body());
+                        self.scope.truncate(scope_start);
+
+                        MatchArm::new(arm.pattern().clone(), body).with_guard(guard)
+                    })
+                    .collect();
+
+                ExprKind::match_(scrutinee, arms)
+            }
+
+            ExprKind::Closure(c) => {
+                let scope_start = self.scope.len();
+                self.shadow(c.params().iter().map(String::as_str).collect());
+
+                let body = self.expr(c.body());
+                self.scope.truncate(scope_start);
+
+                ExprKind::closure(c.params().to_vec(), body)
+            }
+
+            _ => expr.map_children(|child| self.expr(child)),
+        }
+    }
+
+    fn pair(&mut self, left: &ExprKind, right: &ExprKind) -> (ExprKind, ExprKind) {
+        (self.expr(left), self.expr(right))
+    }
+
+    /// Adds names whose value is not known to the scope.
+    fn shadow(&mut self, names: Vec<&str>) {
+        self.scope
+            .extend(names.into_iter().map(|name| (name.to_owned(), None)));
+    }
+}
+
+fn is_literal(expr: &ExprKind) -> bool {
+    matches!(
+        expr,
+        ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_)
+    )
+}
+
+fn as_int(expr: &ExprKind) -> Option<i32> {
+    match expr {
+        ExprKind::Integer(i) => Some(i.value()),
+        _ => None,
+    }
+}
+
+fn as_bool(expr: &ExprKind) -> Option<bool> {
+    match expr {
+        ExprKind::Bool(b) => Some(b.value()),
+        _ => None,
+    }
+}
+
+fn fold_arithmetic(
+    left: &ExprKind,
+    right: &ExprKind,
+    f: impl Fn(i32, i32) -> Option<i32>,
+) -> Option<ExprKind> {
+    f(as_int(left)?, as_int(right)?).map(ExprKind::integer)
+}
+
+fn fold_comparison(
+    operator: ComparisonOperator,
+    left: &ExprKind,
+    right: &ExprKind,
+) -> Option<bool> {
+    Some(operator.holds_for(as_int(left)?.cmp(&as_int(right)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monomorphization;
+
+    use super::*;
+
+    fn assert_folds_to(code: &str, expected: &str) {
+        monomorphization::assert_optimizes_to(fold_program, code, expected)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_folds_to("fn main() { 2 + 3 * 4 - 2 ** 3 }", "fn main() { 6 }");
+    }
+
+    #[test]
+    fn comparisons_and_logical_operators() {
+        assert_folds_to(
+            "fn main() { (1 < 2 && 3 == 3, false || 2 >= 3, true && f(), f() || true) } \
+             fn f() { true }",
+            "fn main() { (true, false, f(), f() || true) } fn f() { true }",
+        );
+    }
+
+    #[test]
+    fn failing_operations_are_kept() {
+        assert_folds_to(
+            "fn main() { (65536 * 65536, 2 ** (0 - 1)) }",
+            "fn main() { (65536 * 65536, 2 ** -1) }",
+        );
+    }
+
+    #[test]
+    fn overflowing_additions_are_kept() {
+        assert_folds_to(
+            "fn main() { (2147483647 + 1, 2147483646 + 1) }",
+            "fn main() { (2147483647 + 1, 2147483647) }",
+        );
+    }
+
+    #[test]
+    fn overflowing_subtractions_are_kept() {
+        assert_folds_to(
+            "fn main() { (0 - 2147483647 - 2, 5 - 7) }",
+            "fn main() { (-2147483647 - 2, -2) }",
+        );
+    }
+
+    #[test]
+    fn overflowing_multiplications_are_kept() {
+        assert_folds_to(
+            "fn main() { (65536 * 32768, 65536 * 32767) }",
+            "fn main() { (65536 * 32768, 2147418112) }",
+        );
+    }
+
+    #[test]
+    fn overflowing_exponentiations_are_kept() {
+        assert_folds_to(
+            "fn main() { let x = 2; (x ** 40, x ** 30) }",
+            "fn main() { let x = 2; (2 ** 40, 1073741824) }",
+        );
+    }
+
+    #[test]
+    fn if_with_constant_condition() {
+        assert_folds_to(
+            "fn main() { if 1 + 1 == 2 { 1 } else { 2 }; if false { 3 } else { 4 } }",
+            "fn main() { 1; 4 }",
+        );
+    }
+
+    #[test]
+    fn if_without_else() {
+        assert_folds_to(
+            "fn main() { if true { println(1) }; if false { println(2) } }",
+            "fn main() { { println(1); () }; () }",
+        );
+    }
+
+    #[test]
+    fn constant_bindings() {
+        assert_folds_to(
+            "fn main() { let a = 2; let b = a * 3; b + 1 }",
+            "fn main() { let a = 2; let b = 6; 7 }",
+        );
+    }
+
+    #[test]
+    fn mutable_and_shadowed_bindings_are_kept() {
+        assert_folds_to(
+            "fn main() { let mut a = 1; let b = 2; let f = |b| a + b; f(b) }",
+            "fn main() { let mut a = 1; let b = 2; let f = |b| a + b; f(2) }",
+        );
+    }
+}
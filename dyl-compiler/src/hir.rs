@@ -0,0 +1,1043 @@
+//! The typed tree code is generated from.
+//!
+//! Once a program is checked, the type checker rebuilds it as a tree where
+//! every expression carries its type, and every name the definition it
+//! refers to. The nodes are named after the ones of the syntax tree, but
+//! only the core of the language is left: macros are expanded, shorthands
+//! desugared and local functions hoisted.
+//!
+//! Patterns, type annotations and the declarations that don't contain code
+//! are the ones of the syntax tree.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    ast::{
+        BindingPattern, ComparisonOperator, Const, Enum, LogicalOperator, Pattern, Span, Struct,
+        Type,
+    },
+    ty::Ty,
+};
+
+/// Identifies a definition: a variable, a parameter, a constant or a
+/// function. Two names that refer to the same definition get the same id,
+/// even when other definitions share their name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct DefId(u32);
+
+impl DefId {
+    pub(crate) fn new(id: u32) -> DefId {
+        DefId(id)
+    }
+}
+
+impl Display for DefId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Program {
+    functions: Vec<Function>,
+    structs: Vec<Struct>,
+    enums: Vec<Enum>,
+
+    /// Constants are evaluated at compile time, from their syntax tree.
+    consts: Vec<Const>,
+}
+
+impl Program {
+    pub(crate) fn new(
+        functions: Vec<Function>,
+        structs: Vec<Struct>,
+        enums: Vec<Enum>,
+        consts: Vec<Const>,
+    ) -> Program {
+        Program {
+            functions,
+            structs,
+            enums,
+            consts,
+        }
+    }
+
+    pub(crate) fn functions(&self) -> &[Function] {
+        self.functions.as_slice()
+    }
+
+    pub(crate) fn structs(&self) -> &[Struct] {
+        self.structs.as_slice()
+    }
+
+    pub(crate) fn enums(&self) -> &[Enum] {
+        self.enums.as_slice()
+    }
+
+    pub(crate) fn consts(&self) -> &[Const] {
+        self.consts.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Function {
+    name: String,
+    def: DefId,
+    args: Vec<String>,
+    arg_defs: Vec<DefId>,
+    body: ExprKind,
+    ret_ty: Ty,
+    span: Span,
+}
+
+impl Function {
+    pub(crate) fn new(
+        name: String,
+        def: DefId,
+        args: Vec<(String, DefId)>,
+        body: ExprKind,
+        ret_ty: Ty,
+        span: Span,
+    ) -> Function {
+        let (args, arg_defs) = args.into_iter().unzip();
+
+        Function {
+            name,
+            def,
+            args,
+            arg_defs,
+            body,
+            ret_ty,
+            span,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn args(&self) -> &[String] {
+        self.args.as_slice()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ExprKind {
+    Addition(Addition),
+    Subtraction(Subtraction),
+    Multiplication(Multiplication),
+    Exponentiation(Exponentiation),
+    Comparison(Comparison),
+    Logical(Logical),
+    Cast(Cast),
+    Integer(Integer),
+    If(If),
+    Bindings(Bindings),
+    Statements(Statements),
+    Ident(Ident),
+    Bool(Bool),
+    String(Str),
+    FnCall(FnCall),
+    Return(Return),
+    Assignment(Assignment),
+    Tuple(Tuple),
+    Field(Field),
+    Array(Array),
+    Index(Index),
+    IndexAssignment(IndexAssignment),
+    StructLiteral(StructLiteral),
+    VariantLiteral(VariantLiteral),
+    Match(Match),
+    Closure(Closure),
+}
+
+impl ExprKind {
+    /// Returns the location of an expression, which is the one of the
+    /// expression it comes from.
+    pub(crate) fn span(&self) -> &Span {
+        self.node().1
+    }
+
+    /// Returns the type of the value an expression produces.
+    pub(crate) fn ty(&self) -> &Ty {
+        self.node().0
+    }
+
+    fn node(&self) -> (&Ty, &Span) {
+        match self {
+            ExprKind::Addition(Addition(_, ty, span))
+            | ExprKind::Subtraction(Subtraction(_, ty, span))
+            | ExprKind::Multiplication(Multiplication(_, ty, span))
+            | ExprKind::Exponentiation(Exponentiation(_, ty, span))
+            | ExprKind::Comparison(Comparison(_, _, ty, span))
+            | ExprKind::Logical(Logical(_, _, ty, span))
+            | ExprKind::Cast(Cast(_, _, ty, span))
+            | ExprKind::Integer(Integer(_, ty, span))
+            | ExprKind::If(If(_, ty, span))
+            | ExprKind::Bindings(Bindings(_, _, ty, span))
+            | ExprKind::Statements(Statements(_, _, ty, span))
+            | ExprKind::Ident(Ident(_, _, ty, span))
+            | ExprKind::Bool(Bool(_, ty, span))
+            | ExprKind::String(Str(_, ty, span))
+            | ExprKind::FnCall(FnCall(_, _, _, ty, span))
+            | ExprKind::Return(Return(_, ty, span))
+            | ExprKind::Assignment(Assignment(_, _, _, ty, span))
+            | ExprKind::Tuple(Tuple(_, ty, span))
+            | ExprKind::Field(Field(_, _, ty, span))
+            | ExprKind::Array(Array(_, ty, span))
+            | ExprKind::Index(Index(_, ty, span))
+            | ExprKind::IndexAssignment(IndexAssignment(_, _, ty, span))
+            | ExprKind::StructLiteral(StructLiteral(_, _, ty, span))
+            | ExprKind::VariantLiteral(VariantLiteral(_, _, _, ty, span))
+            | ExprKind::Match(Match(_, _, ty, span))
+            | ExprKind::Closure(Closure { ty, span, .. }) => (ty, span),
+        }
+    }
+}
+
+/// Declares a node made of two operands.
+macro_rules! binary_node {
+    ($name:ident) => {
+        #[derive(Clone, Debug, PartialEq)]
+        pub(crate) struct $name(Box<(ExprKind, ExprKind)>, Ty, Span);
+
+        impl $name {
+            pub(crate) fn new(lhs: ExprKind, rhs: ExprKind, ty: Ty, span: Span) -> $name {
+                $name(Box::new((lhs, rhs)), ty, span)
+            }
+
+            pub(crate) fn left(&self) -> &ExprKind {
+                &self.0 .0
+            }
+
+            pub(crate) fn right(&self) -> &ExprKind {
+                &self.0 .1
+            }
+        }
+    };
+}
+
+binary_node!(Addition);
+binary_node!(Subtraction);
+binary_node!(Multiplication);
+binary_node!(Exponentiation);
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Comparison(ComparisonOperator, Box<(ExprKind, ExprKind)>, Ty, Span);
+
+impl Comparison {
+    pub(crate) fn new(
+        operator: ComparisonOperator,
+        lhs: ExprKind,
+        rhs: ExprKind,
+        ty: Ty,
+        span: Span,
+    ) -> Comparison {
+        Comparison(operator, Box::new((lhs, rhs)), ty, span)
+    }
+
+    pub(crate) fn operator(&self) -> ComparisonOperator {
+        self.0
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.1 .0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.1 .1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Logical(LogicalOperator, Box<(ExprKind, ExprKind)>, Ty, Span);
+
+impl Logical {
+    pub(crate) fn new(
+        operator: LogicalOperator,
+        lhs: ExprKind,
+        rhs: ExprKind,
+        ty: Ty,
+        span: Span,
+    ) -> Logical {
+        Logical(operator, Box::new((lhs, rhs)), ty, span)
+    }
+
+    pub(crate) fn operator(&self) -> LogicalOperator {
+        self.0
+    }
+
+    pub(crate) fn left(&self) -> &ExprKind {
+        &self.1 .0
+    }
+
+    pub(crate) fn right(&self) -> &ExprKind {
+        &self.1 .1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Cast(Box<ExprKind>, Type, Ty, Span);
+
+impl Cast {
+    pub(crate) fn new(value: ExprKind, target: Type, ty: Ty, span: Span) -> Cast {
+        Cast(Box::new(value), target, ty, span)
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.0
+    }
+
+    pub(crate) fn target(&self) -> &Type {
+        &self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Integer(i32, Ty, Span);
+
+impl Integer {
+    pub(crate) fn new(value: i32, ty: Ty, span: Span) -> Integer {
+        Integer(value, ty, span)
+    }
+
+    pub(crate) fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct If(Box<(ExprKind, ExprKind, Option<ExprKind>)>, Ty, Span);
+
+impl If {
+    pub(crate) fn new(
+        condition: ExprKind,
+        consequent: ExprKind,
+        alternative: Option<ExprKind>,
+        ty: Ty,
+        span: Span,
+    ) -> If {
+        If(Box::new((condition, consequent, alternative)), ty, span)
+    }
+
+    pub(crate) fn condition(&self) -> &ExprKind {
+        &self.0 .0
+    }
+
+    pub(crate) fn consequent(&self) -> &ExprKind {
+        &self.0 .1
+    }
+
+    pub(crate) fn alternative(&self) -> Option<&ExprKind> {
+        self.0 .2.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Bindings(Vec<Binding>, Box<ExprKind>, Ty, Span);
+
+impl Bindings {
+    pub(crate) fn new(bs: Vec<Binding>, next: ExprKind, ty: Ty, span: Span) -> Bindings {
+        Bindings(bs, Box::new(next), ty, span)
+    }
+
+    pub(crate) fn defines(&self) -> &[Binding] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn ending_expression(&self) -> &ExprKind {
+        &self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Statements(Vec<ExprKind>, Box<ExprKind>, Ty, Span);
+
+impl Statements {
+    pub(crate) fn new(statements: Vec<ExprKind>, next: ExprKind, ty: Ty, span: Span) -> Statements {
+        Statements(statements, Box::new(next), ty, span)
+    }
+
+    pub(crate) fn statements(&self) -> &[ExprKind] {
+        self.0.as_slice()
+    }
+
+    pub(crate) fn ending_expression(&self) -> &ExprKind {
+        &self.1
+    }
+}
+
+/// A binding of a value to a pattern. Its type is the one of the bound
+/// value, which is the annotated type if any.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Binding {
+    pattern: BindingPattern,
+
+    /// The definition of each name of the pattern, in order.
+    defs: Vec<DefId>,
+    value: ExprKind,
+    mutable: bool,
+    ty: Ty,
+    span: Span,
+}
+
+impl Binding {
+    pub(crate) fn new(
+        pattern: BindingPattern,
+        defs: Vec<DefId>,
+        value: ExprKind,
+        mutable: bool,
+        ty: Ty,
+        span: Span,
+    ) -> Binding {
+        Binding {
+            pattern,
+            defs,
+            value,
+            mutable,
+            ty,
+            span,
+        }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    pub(crate) fn pattern(&self) -> &BindingPattern {
+        &self.pattern
+    }
+
+    pub(crate) fn defs(&self) -> &[DefId] {
+        self.defs.as_slice()
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.value
+    }
+
+    pub(crate) fn ty(&self) -> &Ty {
+        &self.ty
+    }
+}
+
+/// A name used as a value. Builtins and undefined names have no
+/// definition.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Ident(String, Option<DefId>, Ty, Span);
+
+impl Ident {
+    pub(crate) fn new(name: String, def: Option<DefId>, ty: Ty, span: Span) -> Ident {
+        Ident(name, def, ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn def(&self) -> Option<DefId> {
+        self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Bool(bool, Ty, Span);
+
+impl Bool {
+    pub(crate) fn new(value: bool, ty: Ty, span: Span) -> Bool {
+        Bool(value, ty, span)
+    }
+
+    pub(crate) fn value(&self) -> bool {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Str(String, Ty, Span);
+
+impl Str {
+    pub(crate) fn new(value: String, ty: Ty, span: Span) -> Str {
+        Str(value, ty, span)
+    }
+
+    pub(crate) fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// A call to a function, a builtin or a closure stored in a variable. The
+/// definition is the one of the function or of the variable.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FnCall(String, Option<DefId>, Vec<ExprKind>, Ty, Span);
+
+impl FnCall {
+    pub(crate) fn new(
+        name: String,
+        def: Option<DefId>,
+        args: Vec<ExprKind>,
+        ty: Ty,
+        span: Span,
+    ) -> FnCall {
+        FnCall(name, def, args, ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.4
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn def(&self) -> Option<DefId> {
+        self.1
+    }
+
+    pub(crate) fn args(&self) -> &[ExprKind] {
+        self.2.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Return(Box<ExprKind>, Ty, Span);
+
+impl Return {
+    pub(crate) fn new(value: ExprKind, ty: Ty, span: Span) -> Return {
+        Return(Box::new(value), ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.2
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Assignment(String, Option<DefId>, Box<ExprKind>, Ty, Span);
+
+impl Assignment {
+    pub(crate) fn new(
+        name: String,
+        def: Option<DefId>,
+        value: ExprKind,
+        ty: Ty,
+        span: Span,
+    ) -> Assignment {
+        Assignment(name, def, Box::new(value), ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.4
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn def(&self) -> Option<DefId> {
+        self.1
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.2
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Tuple(Vec<ExprKind>, Ty, Span);
+
+impl Tuple {
+    pub(crate) fn new(elements: Vec<ExprKind>, ty: Ty, span: Span) -> Tuple {
+        Tuple(elements, ty, span)
+    }
+
+    pub(crate) fn elements(&self) -> &[ExprKind] {
+        self.0.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Field(Box<ExprKind>, u16, Ty, Span);
+
+impl Field {
+    pub(crate) fn new(tuple: ExprKind, idx: u16, ty: Ty, span: Span) -> Field {
+        Field(Box::new(tuple), idx, ty, span)
+    }
+
+    pub(crate) fn tuple(&self) -> &ExprKind {
+        &self.0
+    }
+
+    pub(crate) fn idx(&self) -> u16 {
+        self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Array(Vec<ExprKind>, Ty, Span);
+
+impl Array {
+    pub(crate) fn new(elements: Vec<ExprKind>, ty: Ty, span: Span) -> Array {
+        Array(elements, ty, span)
+    }
+
+    pub(crate) fn elements(&self) -> &[ExprKind] {
+        self.0.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Index(Box<(ExprKind, ExprKind)>, Ty, Span);
+
+impl Index {
+    pub(crate) fn new(array: ExprKind, index: ExprKind, ty: Ty, span: Span) -> Index {
+        Index(Box::new((array, index)), ty, span)
+    }
+
+    pub(crate) fn array(&self) -> &ExprKind {
+        &self.0 .0
+    }
+
+    pub(crate) fn index(&self) -> &ExprKind {
+        &self.0 .1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IndexAssignment(Index, Box<ExprKind>, Ty, Span);
+
+impl IndexAssignment {
+    pub(crate) fn new(target: Index, value: ExprKind, ty: Ty, span: Span) -> IndexAssignment {
+        IndexAssignment(target, Box::new(value), ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
+    }
+
+    pub(crate) fn target(&self) -> &Index {
+        &self.0
+    }
+
+    pub(crate) fn value(&self) -> &ExprKind {
+        &self.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StructLiteral(String, Vec<(String, ExprKind)>, Ty, Span);
+
+impl StructLiteral {
+    pub(crate) fn new(
+        name: String,
+        fields: Vec<(String, ExprKind)>,
+        ty: Ty,
+        span: Span,
+    ) -> StructLiteral {
+        StructLiteral(name, fields, ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn fields(&self) -> &[(String, ExprKind)] {
+        self.1.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VariantLiteral(String, String, Vec<ExprKind>, Ty, Span);
+
+impl VariantLiteral {
+    pub(crate) fn new(
+        enum_name: String,
+        variant: String,
+        args: Vec<ExprKind>,
+        ty: Ty,
+        span: Span,
+    ) -> VariantLiteral {
+        VariantLiteral(enum_name, variant, args, ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.4
+    }
+
+    pub(crate) fn enum_name(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub(crate) fn variant(&self) -> &str {
+        self.1.as_str()
+    }
+
+    pub(crate) fn args(&self) -> &[ExprKind] {
+        self.2.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Match(Box<ExprKind>, Vec<MatchArm>, Ty, Span);
+
+impl Match {
+    pub(crate) fn new(scrutinee: ExprKind, arms: Vec<MatchArm>, ty: Ty, span: Span) -> Match {
+        Match(Box::new(scrutinee), arms, ty, span)
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.3
+    }
+
+    pub(crate) fn scrutinee(&self) -> &ExprKind {
+        &self.0
+    }
+
+    pub(crate) fn arms(&self) -> &[MatchArm] {
+        self.1.as_slice()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MatchArm {
+    pattern: Pattern,
+
+    /// The definition of each name of the pattern, in order.
+    defs: Vec<DefId>,
+    guard: Option<ExprKind>,
+    body: ExprKind,
+}
+
+impl MatchArm {
+    pub(crate) fn new(
+        pattern: Pattern,
+        defs: Vec<DefId>,
+        guard: Option<ExprKind>,
+        body: ExprKind,
+    ) -> MatchArm {
+        MatchArm {
+            pattern,
+            defs,
+            guard,
+            body,
+        }
+    }
+
+    pub(crate) fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub(crate) fn defs(&self) -> &[DefId] {
+        self.defs.as_slice()
+    }
+
+    pub(crate) fn guard(&self) -> Option<&ExprKind> {
+        self.guard.as_ref()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Closure {
+    params: Vec<String>,
+    param_defs: Vec<DefId>,
+
+    /// The names the body uses without defining them, in the order they
+    /// first appear. Some of them may be constants or undefined names.
+    free_variables: Vec<String>,
+    body: Box<ExprKind>,
+    ty: Ty,
+    span: Span,
+}
+
+impl Closure {
+    pub(crate) fn new(
+        params: Vec<(String, DefId)>,
+        free_variables: Vec<String>,
+        body: ExprKind,
+        ty: Ty,
+        span: Span,
+    ) -> Closure {
+        let (params, param_defs) = params.into_iter().unzip();
+
+        Closure {
+            params,
+            param_defs,
+            free_variables,
+            body: Box::new(body),
+            ty,
+            span,
+        }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn params(&self) -> &[String] {
+        self.params.as_slice()
+    }
+
+    pub(crate) fn param_defs(&self) -> &[DefId] {
+        self.param_defs.as_slice()
+    }
+
+    pub(crate) fn free_variables(&self) -> &[String] {
+        self.free_variables.as_slice()
+    }
+
+    pub(crate) fn body(&self) -> &ExprKind {
+        &self.body
+    }
+}
+
+/// Shows the typed tree, one node per line, children indented below their
+/// parent. Each expression is followed by its type, and each name by its
+/// definition.
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (idx, function) in self.functions.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+
+            let args = function
+                .args
+                .iter()
+                .zip(&function.arg_defs)
+                .map(|(arg, def)| format!("{}{}", arg, def))
+                .collect::<Vec<_>>();
+
+            writeln!(
+                f,
+                "fn {}{}({}) -> {}",
+                function.name,
+                function.def,
+                args.join(", "),
+                ty_name(&function.ret_ty)
+            )?;
+            write_expr(f, &function.body, 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Names a type. Types that are not known, such as the type of an
+/// unannotated parameter, are shown as `_`.
+fn ty_name(ty: &Ty) -> String {
+    match ty {
+        Ty::Err => "_".to_owned(),
+        ty => ty.to_string(),
+    }
+}
+
+fn defined_names(names: Vec<&str>, defs: &[DefId]) -> String {
+    let names = names
+        .into_iter()
+        .zip(defs)
+        .map(|(name, def)| format!("{}{}", name, def))
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        "_".to_owned()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Writes a pattern as in the source code, each name followed by its
+/// definition.
+fn pattern_text<'a>(pattern: &Pattern, defs: &mut impl Iterator<Item = &'a DefId>) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_owned(),
+        Pattern::Binding(name) => used_name(name, defs.next().copied()),
+        Pattern::Integer(value) => value.to_string(),
+
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        } if fields.is_empty() => format!("{}::{}", enum_name, variant),
+
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        } => {
+            let fields = fields
+                .iter()
+                .map(|field| pattern_text(field, defs))
+                .collect::<Vec<_>>();
+
+            format!("{}::{}({})", enum_name, variant, fields.join(", "))
+        }
+    }
+}
+
+fn used_name(name: &str, def: Option<DefId>) -> String {
+    match def {
+        Some(def) => format!("{}{}", name, def),
+        None => name.to_owned(),
+    }
+}
+
+fn write_line(f: &mut Formatter, depth: usize, line: &str) -> fmt::Result {
+    // Chains of operations can be deeper than a width can be.
+    writeln!(f, "{}{}", "    ".repeat(depth), line)
+}
+
+fn write_expr(f: &mut Formatter, expr: &ExprKind, depth: usize) -> fmt::Result {
+    let (label, children): (String, Vec<&ExprKind>) = match expr {
+        ExprKind::Addition(a) => ("Addition".to_owned(), vec![a.left(), a.right()]),
+        ExprKind::Subtraction(s) => ("Subtraction".to_owned(), vec![s.left(), s.right()]),
+        ExprKind::Multiplication(m) => ("Multiplication".to_owned(), vec![m.left(), m.right()]),
+        ExprKind::Exponentiation(e) => ("Exponentiation".to_owned(), vec![e.left(), e.right()]),
+        ExprKind::Comparison(c) => (
+            format!("Comparison {:?}", c.operator()),
+            vec![c.left(), c.right()],
+        ),
+        ExprKind::Logical(l) => (
+            format!("Logical {:?}", l.operator()),
+            vec![l.left(), l.right()],
+        ),
+        ExprKind::Cast(c) => (format!("Cast as {}", c.target()), vec![c.value()]),
+        ExprKind::Integer(i) => (format!("Integer {}", i.value()), Vec::new()),
+        ExprKind::Bool(b) => (format!("Bool {}", b.value()), Vec::new()),
+        ExprKind::String(s) => (format!("String {:?}", s.value()), Vec::new()),
+        ExprKind::Ident(i) => (
+            format!("Ident {}", used_name(i.name(), i.def())),
+            Vec::new(),
+        ),
+
+        ExprKind::If(i) => {
+            let mut children = vec![i.condition(), i.consequent()];
+            children.extend(i.alternative());
+
+            ("If".to_owned(), children)
+        }
+
+        ExprKind::Bindings(b) => {
+            write_line(f, depth, &format!("Bindings: {}", ty_name(expr.ty())))?;
+
+            for binding in b.defines() {
+                let mutable = if binding.is_mutable() { "mut " } else { "" };
+                let names = defined_names(binding.pattern().names(), binding.defs());
+                let line = format!("let {}{}: {}", mutable, names, ty_name(binding.ty()));
+
+                write_line(f, depth + 1, &line)?;
+                write_expr(f, binding.value(), depth + 2)?;
+            }
+
+            return write_expr(f, b.ending_expression(), depth + 1);
+        }
+
+        ExprKind::Statements(s) => {
+            let mut children = s.statements().iter().collect::<Vec<_>>();
+            children.push(s.ending_expression());
+
+            ("Statements".to_owned(), children)
+        }
+
+        ExprKind::FnCall(c) => (
+            format!("FnCall {}", used_name(c.name(), c.def())),
+            c.args().iter().collect(),
+        ),
+        ExprKind::Return(r) => ("Return".to_owned(), vec![r.value()]),
+        ExprKind::Assignment(a) => (
+            format!("Assignment {}", used_name(a.name(), a.def())),
+            vec![a.value()],
+        ),
+        ExprKind::Tuple(t) => ("Tuple".to_owned(), t.elements().iter().collect()),
+        ExprKind::Field(field) => (format!("Field {}", field.idx()), vec![field.tuple()]),
+        ExprKind::Array(a) => ("Array".to_owned(), a.elements().iter().collect()),
+        ExprKind::Index(i) => ("Index".to_owned(), vec![i.array(), i.index()]),
+        ExprKind::IndexAssignment(a) => (
+            "IndexAssignment".to_owned(),
+            vec![a.target().array(), a.target().index(), a.value()],
+        ),
+
+        ExprKind::StructLiteral(s) => {
+            write_line(
+                f,
+                depth,
+                &format!("StructLiteral {}: {}", s.name(), ty_name(expr.ty())),
+            )?;
+
+            for (name, value) in s.fields() {
+                write_line(f, depth + 1, &format!("{}:", name))?;
+                write_expr(f, value, depth + 2)?;
+            }
+
+            return Ok(());
+        }
+
+        ExprKind::VariantLiteral(v) => (
+            format!("VariantLiteral {}::{}", v.enum_name(), v.variant()),
+            v.args().iter().collect(),
+        ),
+
+        ExprKind::Match(m) => {
+            write_line(f, depth, &format!("Match: {}", ty_name(expr.ty())))?;
+            write_expr(f, m.scrutinee(), depth + 1)?;
+
+            for arm in m.arms() {
+                let pattern = pattern_text(arm.pattern(), &mut arm.defs().iter());
+                write_line(f, depth + 1, &format!("arm {}", pattern))?;
+
+                if let Some(guard) = arm.guard() {
+                    write_line(f, depth + 2, "if")?;
+                    write_expr(f, guard, depth + 3)?;
+                }
+
+                write_expr(f, arm.body(), depth + 2)?;
+            }
+
+            return Ok(());
+        }
+
+        ExprKind::Closure(c) => {
+            let params = c
+                .params()
+                .iter()
+                .zip(c.param_defs())
+                .map(|(param, def)| format!("{}{}", param, def))
+                .collect::<Vec<_>>();
+
+            (format!("Closure |{}|", params.join(", ")), vec![c.body()])
+        }
+    };
+
+    write_line(f, depth, &format!("{}: {}", label, ty_name(expr.ty())))?;
+
+    children
+        .into_iter()
+        .try_for_each(|child| write_expr(f, child, depth + 1))
+}
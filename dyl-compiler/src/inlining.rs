@@ -0,0 +1,323 @@
+//! Replaces calls to small functions by their body.
+//!
+//! Only the functions whose body is small, does not `return`, does not
+//! assign variables and only refers to its own parameters are inlined. Such
+//! a body can be moved into any caller without changing what its names
+//! refer to, and it never calls a function, so that inlining always ends.
+//!
+//! The arguments are evaluated in order and bound to the parameters before
+//! the body, as they would be by a call.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::{Binding, BindingPattern, ExprKind, Function, Item, MatchArm, Program},
+    captures,
+};
+
+/// The number of nodes above which the body of a function is not inlined.
+const MAX_INLINED_SIZE: usize = 16;
+
+pub(crate) fn inline_functions(program: &Program) -> Program {
+    let inlinable = program
+        .functions()
+        .iter()
+        .filter(|f| is_inlinable(f))
+        .map(|f| (f.name().to_owned(), f.clone()))
+        .collect();
+
+    let items = program
+        .structs()
+        .iter()
+        .cloned()
+        .map(Item::Struct)
+        .chain(program.enums().iter().cloned().map(Item::Enum))
+        .chain(program.consts().iter().cloned().map(Item::Const))
+        .chain(
+            program
+                .functions()
+                .iter()
+                .map(|f| inline_in_function(f, &inlinable))
+                .map(Item::Function),
+        )
+        .collect();
+
+    Program::new(items)
+}
+
+fn is_inlinable(f: &Function) -> bool {
+    let refers_to_params_only = captures::free_variables_of(f.body())
+        .iter()
+        .all(|name| f.args().contains(name));
+
+    refers_to_params_only && size(f.body()).is_some_and(|size| size <= MAX_INLINED_SIZE)
+}
+
+fn inline_in_function(f: &Function, inlinable: &HashMap<String, Function>) -> Function {
+    let mut inliner = Inliner {
+        inlinable,
+        scope: f.args().to_vec(),
+        temporaries: 0,
+    };
+    let body = inliner.expr(f.body());
+
+    Function::new(f.name().to_owned(), f.args().to_vec(), body)
+        .with_signature(
+            f.generics().to_vec(),
+            f.arg_types().to_vec(),
+            f.ret_type().cloned(),
+        )
+        .with_span(f.span().clone())
+}
+
+struct Inliner<'a> {
+    inlinable: &'a HashMap<String, Function>,
+
+    /// The variables in scope. A call to a name that is in scope calls a
+    /// closure, not a function.
+    scope: Vec<String>,
+
+    /// The number of temporary variables created in the function so far.
+    temporaries: usize,
+}
+
+impl Inliner<'_> {
+    fn expr(&mut self, expr: &ExprKind) -> ExprKind {
+        match expr {
+            ExprKind::Bindings(b) => {
+                let scope_start = self.scope.len();
+
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .map(|binding| {
+                        let value = self.expr(binding.value());
+                        self.shadow(binding.names());
+
+                        Binding::from_pattern(
+                            binding.pattern().clone(),
+                            value,
+                            binding.is_mutable(),
+                        )
+                        .with_annotation(binding.annotation().cloned())
+                        .with_span(binding.span().clone())
+                    })
+                    .collect();
+
+                let ending = self.expr(b.ending_expression());
+                self.scope.truncate(scope_start);
+
+                ExprKind::bindings(bindings, ending)
+            }
+
+            ExprKind::FnCall(c) => {
+                let args = self.exprs(c.args());
+
+                match self.inlinable.get(c.name()) {
+                    Some(callee)
+                        if !self.scope.iter().any(|name| name == c.name())
+                            && callee.args().len() == args.len() =>
+                    {
+                        self.inline(callee, args)
+                    }
+                    _ => c.rebuild(c.name().to_owned(), args),
+                }
+            }
+
+            ExprKind::Match(m) => {
+                let scrutinee = self.expr(m.scrutinee());
+
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let scope_start = self.scope.len();
+                        self.shadow(arm.pattern().names());
+
+                        let guard = arm.guard().map(|guard| self.expr(guard));
+                        let body = self.expr(arm.body());
+                        self.scope.truncate(scope_start);
+
+                        MatchArm::new(arm.pattern().clone(), body).with_guard(guard)
+                    })
+                    .collect();
+
+                ExprKind::match_(scrutinee, arms)
+            }
+
+            ExprKind::Closure(c) => {
+                let scope_start = self.scope.len();
+                self.shadow(c.params().iter().map(String::as_str).collect());
+
+                let body = self.expr(c.body());
+                self.scope.truncate(scope_start);
+
+                ExprKind::closure(c.params().to_vec(), body)
+            }
+
+            _ => expr.map_children(|child| self.expr(child)),
+        }
+    }
+
+    /// Binds the arguments of a call to the parameters of the callee, then
+    /// evaluates its body.
+    ///
+    /// When there are several arguments, they are all evaluated before any
+    /// parameter is bound, as a parameter may have the name of a variable
+    /// that a following argument refers to.
+    fn inline(&mut self, callee: &Function, args: Vec<ExprKind>) -> ExprKind {
+        let body = callee.body().clone();
+        let params = callee.args();
+
+        if args.len() <= 1 {
+            let bindings = params
+                .iter()
+                .zip(args)
+                .map(|(param, arg)| bind(param.clone(), arg))
+                .collect::<Vec<_>>();
+
+            return if bindings.is_empty() {
+                body
+            } else {
+                ExprKind::bindings(bindings, body)
+            };
+        }
+
+        // Temporaries are named so that they can not be written in the
+        // source code.
+        let temporaries = (0..args.len())
+            .map(|_| {
+                self.temporaries += 1;
+                format!("%inlined{}", self.temporaries)
+            })
+            .collect::<Vec<_>>();
+
+        let bindings =
+            temporaries
+                .iter()
+                .zip(args)
+                .map(|(temporary, arg)| bind(temporary.clone(), arg))
+                .chain(params.iter().zip(&temporaries).map(|(param, temporary)| {
+                    bind(param.clone(), ExprKind::ident(temporary.clone()))
+                }))
+                .collect();
+
+        ExprKind::bindings(bindings, body)
+    }
+
+    fn exprs(&mut self, exprs: &[ExprKind]) -> Vec<ExprKind> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn shadow(&mut self, names: Vec<&str>) {
+        self.scope.extend(names.into_iter().map(str::to_owned));
+    }
+}
+
+fn bind(name: String, value: ExprKind) -> Binding {
+    Binding::from_pattern(BindingPattern::Name(name), value, false)
+}
+
+/// Returns the number of nodes of an expression, or `None` if it can not be
+/// moved to another function.
+fn size(expr: &ExprKind) -> Option<usize> {
+    if matches!(expr, ExprKind::Return(_) | ExprKind::Assignment(_)) {
+        return None;
+    }
+
+    expr.children()
+        .into_iter()
+        .map(size)
+        .sum::<Option<usize>>()
+        .map(|size| size + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monomorphization;
+
+    use super::*;
+
+    fn assert_inlines_to(code: &str, expected: &str) {
+        monomorphization::assert_optimizes_to(inline_functions, code, expected)
+    }
+
+    #[test]
+    fn single_argument() {
+        let program = inline_functions(&monomorphization::monomorphize_input(
+            "fn double(x) { x * 2 } fn main() { double(3) + 1 }",
+        ));
+
+        let expected = ExprKind::addition(
+            ExprKind::bindings(
+                vec![bind("x".to_owned(), ExprKind::integer(3))],
+                ExprKind::multiplication(ExprKind::ident("x".to_owned()), ExprKind::integer(2)),
+            ),
+            ExprKind::integer(1),
+        );
+
+        assert_eq!(program.main_body(), &expected);
+    }
+
+    #[test]
+    fn no_argument() {
+        assert_inlines_to("fn one() { 1 } fn main() { one() }", "fn main() { 1 }");
+    }
+
+    #[test]
+    fn arguments_are_evaluated_before_binding() {
+        let program = inline_functions(&monomorphization::monomorphize_input(
+            "fn sub(a, b) { a - b } fn main() { let b = 1; sub(b, 2) }",
+        ));
+
+        let expected = ExprKind::bindings(
+            vec![bind("b".to_owned(), ExprKind::integer(1))],
+            ExprKind::bindings(
+                vec![
+                    bind("%inlined1".to_owned(), ExprKind::ident("b".to_owned())),
+                    bind("%inlined2".to_owned(), ExprKind::integer(2)),
+                    bind("a".to_owned(), ExprKind::ident("%inlined1".to_owned())),
+                    bind("b".to_owned(), ExprKind::ident("%inlined2".to_owned())),
+                ],
+                ExprKind::subtraction(
+                    ExprKind::ident("a".to_owned()),
+                    ExprKind::ident("b".to_owned()),
+                ),
+            ),
+        );
+
+        assert_eq!(program.main_body(), &expected);
+    }
+
+    #[test]
+    fn callers_are_not_inlined() {
+        assert_inlines_to(
+            "fn one() { 1 } fn two() { one() + one() } fn main() { two() }",
+            "fn one() { 1 } fn two() { one() + one() } fn main() { two() }",
+        );
+    }
+
+    #[test]
+    fn functions_that_return_are_not_inlined() {
+        assert_inlines_to(
+            "fn one() { return 1; } fn main() { one() }",
+            "fn one() { return 1; } fn main() { one() }",
+        );
+    }
+
+    #[test]
+    fn large_functions_are_not_inlined() {
+        assert_inlines_to(
+            "fn f(x) { x + x + x + x + x + x + x + x + x } fn main() { f(1) }",
+            "fn f(x) { x + x + x + x + x + x + x + x + x } fn main() { f(1) }",
+        );
+    }
+
+    #[test]
+    fn closures_shadow_functions() {
+        assert_inlines_to(
+            "fn one() { 1 } fn main() { let one = || 2; one() }",
+            "fn one() { 1 } fn main() { let one = || 2; one() }",
+        );
+    }
+}
@@ -1,9 +1,12 @@
-use dyl_bytecode::operations as resolved_operations;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use dyl_bytecode::operations::{self as resolved_operations, ArithmeticOp};
 use dyl_bytecode::Instruction as ResolvedInstruction;
 
+use crate::ast::ComparisonOperator;
 use crate::context::{LabelResolutionContext, Resolvable};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Instruction {
     PushI(PushI),
     AddI(AddI),
@@ -16,6 +19,56 @@ pub(crate) enum Instruction {
     Pop(Pop),
     PushCopy(PushCopy),
     Ret(Ret),
+    Call(Call),
+    ResV(ResV),
+    PushS(PushS),
+    Pack(Pack),
+    Field(Field),
+    NewArray(NewArray),
+    Index(Index),
+    StoreIndex(StoreIndex),
+    MakeClosure(MakeClosure),
+    CallClosure(CallClosure),
+    Pow(Pow),
+    Out(Out),
+    InI(InI),
+    Trap(Trap),
+    Len(Len),
+    Substr(Substr),
+    CharAt(CharAt),
+    PushBack(PushBack),
+    PopBack(PopBack),
+    CheckFrame(CheckFrame),
+    CheckOverflow(CheckOverflow),
+    SubI(SubI),
+    DivI(DivI),
+    ModI(ModI),
+    EqI(EqI),
+    NeqI(NeqI),
+    LtI(LtI),
+    LeI(LeI),
+    GtI(GtI),
+    GeI(GeI),
+    JmpIfZero(JmpIfZero),
+    JmpIfNotZero(JmpIfNotZero),
+    Swap(Swap),
+    Nop(Nop),
+    AndI(AndI),
+    OrI(OrI),
+    XorI(XorI),
+    ShlI(ShlI),
+    ShrI(ShrI),
+    NotI(NotI),
+    PushF(PushF),
+    AddF(AddF),
+    SubF(SubF),
+    MulF(MulF),
+    DivF(DivF),
+    IToF(IToF),
+    FToI(FToI),
+    PushConst(PushConst),
+    LoadLocal(LoadLocal),
+    StoreLocal(StoreLocal),
 }
 
 macro_rules! map_instruction {
@@ -32,6 +85,56 @@ macro_rules! map_instruction {
             Instruction::Pop($name) => $do,
             Instruction::PushCopy($name) => $do,
             Instruction::Ret($name) => $do,
+            Instruction::Call($name) => $do,
+            Instruction::ResV($name) => $do,
+            Instruction::PushS($name) => $do,
+            Instruction::Pack($name) => $do,
+            Instruction::Field($name) => $do,
+            Instruction::NewArray($name) => $do,
+            Instruction::Index($name) => $do,
+            Instruction::StoreIndex($name) => $do,
+            Instruction::MakeClosure($name) => $do,
+            Instruction::CallClosure($name) => $do,
+            Instruction::Pow($name) => $do,
+            Instruction::Out($name) => $do,
+            Instruction::InI($name) => $do,
+            Instruction::Trap($name) => $do,
+            Instruction::Len($name) => $do,
+            Instruction::Substr($name) => $do,
+            Instruction::CharAt($name) => $do,
+            Instruction::PushBack($name) => $do,
+            Instruction::PopBack($name) => $do,
+            Instruction::CheckFrame($name) => $do,
+            Instruction::CheckOverflow($name) => $do,
+            Instruction::SubI($name) => $do,
+            Instruction::DivI($name) => $do,
+            Instruction::ModI($name) => $do,
+            Instruction::EqI($name) => $do,
+            Instruction::NeqI($name) => $do,
+            Instruction::LtI($name) => $do,
+            Instruction::LeI($name) => $do,
+            Instruction::GtI($name) => $do,
+            Instruction::GeI($name) => $do,
+            Instruction::JmpIfZero($name) => $do,
+            Instruction::JmpIfNotZero($name) => $do,
+            Instruction::Swap($name) => $do,
+            Instruction::Nop($name) => $do,
+            Instruction::AndI($name) => $do,
+            Instruction::OrI($name) => $do,
+            Instruction::XorI($name) => $do,
+            Instruction::ShlI($name) => $do,
+            Instruction::ShrI($name) => $do,
+            Instruction::NotI($name) => $do,
+            Instruction::PushF($name) => $do,
+            Instruction::AddF($name) => $do,
+            Instruction::SubF($name) => $do,
+            Instruction::MulF($name) => $do,
+            Instruction::DivF($name) => $do,
+            Instruction::IToF($name) => $do,
+            Instruction::FToI($name) => $do,
+            Instruction::PushConst($name) => $do,
+            Instruction::LoadLocal($name) => $do,
+            Instruction::StoreLocal($name) => $do,
         }
     };
 }
@@ -48,7 +151,7 @@ macro_rules! impl_from_variants {
     };
 }
 
-impl_from_variants! { PushI, AddI, FStop, Neg, CondJmp, Goto, Mul, PopCopy, Pop, PushCopy, Ret }
+impl_from_variants! { PushI, AddI, FStop, Neg, CondJmp, Goto, Mul, PopCopy, Pop, PushCopy, Ret, Call, ResV, PushS, Pack, Field, NewArray, Index, StoreIndex, MakeClosure, CallClosure, Pow, Out, InI, Trap, Len, Substr, CharAt, PushBack, PopBack, CheckFrame, CheckOverflow, SubI, DivI, ModI, EqI, NeqI, LtI, LeI, GtI, GeI, JmpIfZero, JmpIfNotZero, Swap, Nop, AndI, OrI, XorI, ShlI, ShrI, NotI, PushF, AddF, SubF, MulF, DivF, IToF, FToI, PushConst, LoadLocal, StoreLocal }
 
 impl Instruction {
     pub(crate) fn push_i(i: i32) -> Instruction {
@@ -75,10 +178,22 @@ impl Instruction {
         Instruction::Goto(Goto(addr))
     }
 
+    pub(crate) fn jmp_if_zero(addr: u32) -> Instruction {
+        Instruction::JmpIfZero(JmpIfZero(addr))
+    }
+
+    pub(crate) fn jmp_if_not_zero(addr: u32) -> Instruction {
+        Instruction::JmpIfNotZero(JmpIfNotZero(addr))
+    }
+
     pub(crate) fn mul() -> Instruction {
         Instruction::Mul(Mul)
     }
 
+    pub(crate) fn shl_i() -> Instruction {
+        Instruction::ShlI(ShlI)
+    }
+
     pub(crate) fn pop_copy(offset: u16) -> Instruction {
         Instruction::PopCopy(PopCopy(offset))
     }
@@ -91,11 +206,277 @@ impl Instruction {
         Instruction::PushCopy(PushCopy(offset))
     }
 
-    pub(crate) fn ret() -> Instruction {
-        // Note: our current functions always return a 1-sized value. As such,
-        // we don't handle complex situations where the stack must be shrunk of
-        // a variable amount depending on the output type size.
-        Instruction::Ret(Ret)
+    pub(crate) fn load_local(idx: i16) -> Instruction {
+        Instruction::LoadLocal(LoadLocal(idx))
+    }
+
+    pub(crate) fn store_local(idx: i16) -> Instruction {
+        Instruction::StoreLocal(StoreLocal(idx))
+    }
+
+    pub(crate) fn ret(ip_offset: u16, shrink: u16) -> Instruction {
+        // Note: our current functions always return a 1-sized value, which is
+        // written in the slot reserved by the caller. As such, we only have
+        // to shrink the stack of the arguments, of the return address, and of
+        // whatever has been pushed on top of them.
+        Instruction::Ret(Ret { ip_offset, shrink })
+    }
+
+    pub(crate) fn call(label: u32) -> Instruction {
+        Instruction::Call(Call(label))
+    }
+
+    pub(crate) fn res_v(amount: u16) -> Instruction {
+        Instruction::ResV(ResV(amount))
+    }
+
+    pub(crate) fn push_s(value: String) -> Instruction {
+        Instruction::PushS(PushS(value))
+    }
+
+    pub(crate) fn push_const(idx: u16) -> Instruction {
+        Instruction::PushConst(PushConst(idx))
+    }
+
+    pub(crate) fn pack(len: u16) -> Instruction {
+        Instruction::Pack(Pack(len))
+    }
+
+    pub(crate) fn field(idx: u16) -> Instruction {
+        Instruction::Field(Field(idx))
+    }
+
+    pub(crate) fn new_array(len: u16) -> Instruction {
+        Instruction::NewArray(NewArray(len))
+    }
+
+    pub(crate) fn index() -> Instruction {
+        Instruction::Index(Index)
+    }
+
+    pub(crate) fn store_index() -> Instruction {
+        Instruction::StoreIndex(StoreIndex)
+    }
+
+    pub(crate) fn make_closure(label: u32, captures: u16, arity: u16) -> Instruction {
+        Instruction::MakeClosure(MakeClosure {
+            label,
+            captures,
+            arity,
+        })
+    }
+
+    pub(crate) fn call_closure(arity: u16) -> Instruction {
+        Instruction::CallClosure(CallClosure(arity))
+    }
+
+    pub(crate) fn pow() -> Instruction {
+        Instruction::Pow(Pow)
+    }
+
+    /// Returns the instruction that compares the two integers on top of the
+    /// stack with an operator.
+    pub(crate) fn comparison(operator: ComparisonOperator) -> Instruction {
+        match operator {
+            ComparisonOperator::Equal => EqI.into(),
+            ComparisonOperator::NotEqual => NeqI.into(),
+            ComparisonOperator::Less => LtI.into(),
+            ComparisonOperator::LessOrEqual => LeI.into(),
+            ComparisonOperator::Greater => GtI.into(),
+            ComparisonOperator::GreaterOrEqual => GeI.into(),
+        }
+    }
+
+    pub(crate) fn out() -> Instruction {
+        Instruction::Out(Out)
+    }
+
+    pub(crate) fn in_i() -> Instruction {
+        Instruction::InI(InI)
+    }
+
+    pub(crate) fn trap() -> Instruction {
+        Instruction::Trap(Trap)
+    }
+
+    pub(crate) fn len() -> Instruction {
+        Instruction::Len(Len)
+    }
+
+    pub(crate) fn substr() -> Instruction {
+        Instruction::Substr(Substr)
+    }
+
+    pub(crate) fn char_at() -> Instruction {
+        Instruction::CharAt(CharAt)
+    }
+
+    pub(crate) fn push_back() -> Instruction {
+        Instruction::PushBack(PushBack)
+    }
+
+    pub(crate) fn pop_back() -> Instruction {
+        Instruction::PopBack(PopBack)
+    }
+
+    pub(crate) fn check_frame(offset: u16) -> Instruction {
+        Instruction::CheckFrame(CheckFrame(offset))
+    }
+
+    pub(crate) fn check_overflow(op: ArithmeticOp) -> Instruction {
+        Instruction::CheckOverflow(CheckOverflow(op))
+    }
+}
+
+impl Instruction {
+    /// Returns the instruction as bytecode whose addresses are the labels
+    /// it targets, unresolved.
+    pub(crate) fn to_labelled(&self) -> ResolvedInstruction {
+        use resolved_operations as op;
+
+        match self.clone() {
+            Instruction::CondJmp(CondJmp(negative_addr, null_addr, positive_addr)) => op::CondJmp {
+                negative_addr,
+                null_addr,
+                positive_addr,
+            }
+            .into(),
+            Instruction::Goto(Goto(label)) => op::Goto(label).into(),
+            Instruction::JmpIfZero(JmpIfZero(label)) => op::JmpIfZero(label).into(),
+            Instruction::JmpIfNotZero(JmpIfNotZero(label)) => op::JmpIfNotZero(label).into(),
+            Instruction::Call(Call(label)) => op::Call(label).into(),
+            Instruction::MakeClosure(MakeClosure {
+                label,
+                captures,
+                arity,
+            }) => op::MakeClosure {
+                addr: label,
+                captures,
+                arity,
+            }
+            .into(),
+            // The other instructions target no label.
+            instruction => instruction.resolve(&LabelResolutionContext::default()),
+        }
+    }
+
+    /// Makes an instruction of bytecode whose addresses are labels, the
+    /// reverse of `to_labelled`.
+    pub(crate) fn from_labelled(instruction: ResolvedInstruction) -> Instruction {
+        match instruction {
+            ResolvedInstruction::PushI(op) => PushI(op.0).into(),
+            ResolvedInstruction::AddI(_) => AddI.into(),
+            ResolvedInstruction::FStop(_) => FStop.into(),
+            ResolvedInstruction::PushCopy(op) => PushCopy(op.0).into(),
+            ResolvedInstruction::Call(op) => Call(op.0).into(),
+            ResolvedInstruction::Ret(op) => Ret {
+                ip_offset: op.ip_offset,
+                shrink: op.shrink_offset,
+            }
+            .into(),
+            ResolvedInstruction::ResV(op) => ResV(op.0).into(),
+            ResolvedInstruction::PopCopy(op) => PopCopy(op.0).into(),
+            ResolvedInstruction::Goto(op) => Goto(op.0).into(),
+            ResolvedInstruction::CondJmp(op) => {
+                CondJmp(op.negative_addr, op.null_addr, op.positive_addr).into()
+            }
+            ResolvedInstruction::Neg(_) => Neg.into(),
+            ResolvedInstruction::Mul(_) => Mul.into(),
+            ResolvedInstruction::Pop(op) => Pop(op.0).into(),
+            ResolvedInstruction::PushS(op) => PushS(op.0).into(),
+            ResolvedInstruction::Pack(op) => Pack(op.0).into(),
+            ResolvedInstruction::Field(op) => Field(op.0).into(),
+            ResolvedInstruction::NewArray(op) => NewArray(op.0).into(),
+            ResolvedInstruction::Index(_) => Index.into(),
+            ResolvedInstruction::StoreIndex(_) => StoreIndex.into(),
+            ResolvedInstruction::MakeClosure(op) => MakeClosure {
+                label: op.addr,
+                captures: op.captures,
+                arity: op.arity,
+            }
+            .into(),
+            ResolvedInstruction::CallClosure(op) => CallClosure(op.0).into(),
+            ResolvedInstruction::Pow(_) => Pow.into(),
+            ResolvedInstruction::Out(_) => Out.into(),
+            ResolvedInstruction::InI(_) => InI.into(),
+            ResolvedInstruction::Trap(_) => Trap.into(),
+            ResolvedInstruction::Len(_) => Len.into(),
+            ResolvedInstruction::Substr(_) => Substr.into(),
+            ResolvedInstruction::CharAt(_) => CharAt.into(),
+            ResolvedInstruction::PushBack(_) => PushBack.into(),
+            ResolvedInstruction::PopBack(_) => PopBack.into(),
+            ResolvedInstruction::CheckFrame(op) => CheckFrame(op.0).into(),
+            ResolvedInstruction::CheckOverflow(op) => CheckOverflow(op.0).into(),
+            ResolvedInstruction::SubI(_) => SubI.into(),
+            ResolvedInstruction::DivI(_) => DivI.into(),
+            ResolvedInstruction::ModI(_) => ModI.into(),
+            ResolvedInstruction::EqI(_) => EqI.into(),
+            ResolvedInstruction::NeqI(_) => NeqI.into(),
+            ResolvedInstruction::LtI(_) => LtI.into(),
+            ResolvedInstruction::LeI(_) => LeI.into(),
+            ResolvedInstruction::GtI(_) => GtI.into(),
+            ResolvedInstruction::GeI(_) => GeI.into(),
+            ResolvedInstruction::JmpIfZero(op) => JmpIfZero(op.0).into(),
+            ResolvedInstruction::JmpIfNotZero(op) => JmpIfNotZero(op.0).into(),
+            ResolvedInstruction::Swap(_) => Swap.into(),
+            ResolvedInstruction::Nop(_) => Nop.into(),
+            ResolvedInstruction::AndI(_) => AndI.into(),
+            ResolvedInstruction::OrI(_) => OrI.into(),
+            ResolvedInstruction::XorI(_) => XorI.into(),
+            ResolvedInstruction::ShlI(_) => ShlI.into(),
+            ResolvedInstruction::ShrI(_) => ShrI.into(),
+            ResolvedInstruction::NotI(_) => NotI.into(),
+            ResolvedInstruction::PushF(op) => PushF(op.0).into(),
+            ResolvedInstruction::AddF(_) => AddF.into(),
+            ResolvedInstruction::SubF(_) => SubF.into(),
+            ResolvedInstruction::MulF(_) => MulF.into(),
+            ResolvedInstruction::DivF(_) => DivF.into(),
+            ResolvedInstruction::IToF(_) => IToF.into(),
+            ResolvedInstruction::FToI(_) => FToI.into(),
+            ResolvedInstruction::PushConst(op) => PushConst(op.0).into(),
+            ResolvedInstruction::LoadLocal(op) => LoadLocal(op.0).into(),
+            ResolvedInstruction::StoreLocal(op) => StoreLocal(op.0).into(),
+        }
+    }
+
+    /// Returns the labels the instruction jumps to or calls.
+    pub(crate) fn targets(&self) -> Vec<u32> {
+        match self {
+            Instruction::CondJmp(CondJmp(negative, null, positive)) => {
+                vec![*negative, *null, *positive]
+            }
+            Instruction::Goto(Goto(label))
+            | Instruction::JmpIfZero(JmpIfZero(label))
+            | Instruction::JmpIfNotZero(JmpIfNotZero(label))
+            | Instruction::Call(Call(label)) => vec![*label],
+            Instruction::MakeClosure(closure) => vec![closure.label],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Writes the instruction in the mnemonic format of the disassembler, such
+/// as `ret 1 0`. The labels an instruction targets are written as `L0`,
+/// `L1`..., as in the assembly.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Instruction::CondJmp(CondJmp(negative, null, positive)) => {
+                write!(f, "cond_jmp L{} L{} L{}", negative, null, positive)
+            }
+            Instruction::Goto(Goto(label)) => write!(f, "goto L{}", label),
+            Instruction::JmpIfZero(JmpIfZero(label)) => write!(f, "jmp_if_zero L{}", label),
+            Instruction::JmpIfNotZero(JmpIfNotZero(label)) => {
+                write!(f, "jmp_if_not_zero L{}", label)
+            }
+            Instruction::Call(Call(label)) => write!(f, "call L{}", label),
+            Instruction::MakeClosure(closure) => write!(
+                f,
+                "make_closure L{} {} {}",
+                closure.label, closure.captures, closure.arity
+            ),
+            instruction => write!(f, "{}", instruction.to_labelled()),
+        }
     }
 }
 
@@ -244,15 +625,596 @@ impl Resolvable for PushCopy {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) struct Ret;
+pub(crate) struct Ret {
+    pub ip_offset: u16,
+    pub shrink: u16,
+}
 
 impl Resolvable for Ret {
     type Output = resolved_operations::Ret;
 
     fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
         resolved_operations::Ret {
-            shrink_offset: 1,
-            ip_offset: 0,
+            shrink_offset: self.shrink,
+            ip_offset: self.ip_offset,
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Call(pub u32);
+
+impl Resolvable for Call {
+    type Output = resolved_operations::Call;
+
+    fn resolve(&self, ctxt: &LabelResolutionContext) -> Self::Output {
+        let dest = ctxt
+            .labels()
+            .resolve_anonymous(self.0)
+            .expect("Failed to resolve function address");
+
+        resolved_operations::Call(dest)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ResV(pub u16);
+
+impl Resolvable for ResV {
+    type Output = resolved_operations::ResV;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::ResV(self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PushS(pub String);
+
+impl Resolvable for PushS {
+    type Output = resolved_operations::PushS;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::PushS(self.0.clone())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Pack(pub u16);
+
+impl Resolvable for Pack {
+    type Output = resolved_operations::Pack;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Pack(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Field(pub u16);
+
+impl Resolvable for Field {
+    type Output = resolved_operations::Field;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Field(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct NewArray(pub u16);
+
+impl Resolvable for NewArray {
+    type Output = resolved_operations::NewArray;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::NewArray(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Index;
+
+impl Resolvable for Index {
+    type Output = resolved_operations::Index;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Index
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct StoreIndex;
+
+impl Resolvable for StoreIndex {
+    type Output = resolved_operations::StoreIndex;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::StoreIndex
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct MakeClosure {
+    pub label: u32,
+    pub captures: u16,
+    pub arity: u16,
+}
+
+impl Resolvable for MakeClosure {
+    type Output = resolved_operations::MakeClosure;
+
+    fn resolve(&self, ctxt: &LabelResolutionContext) -> Self::Output {
+        let addr = ctxt
+            .labels()
+            .resolve_anonymous(self.label)
+            .expect("Failed to resolve closure address");
+
+        resolved_operations::MakeClosure {
+            addr,
+            captures: self.captures,
+            arity: self.arity,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct CallClosure(pub u16);
+
+impl Resolvable for CallClosure {
+    type Output = resolved_operations::CallClosure;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::CallClosure(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Pow;
+
+impl Resolvable for Pow {
+    type Output = resolved_operations::Pow;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Pow
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Out;
+
+impl Resolvable for Out {
+    type Output = resolved_operations::Out;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Out
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct InI;
+
+impl Resolvable for InI {
+    type Output = resolved_operations::InI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::InI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Trap;
+
+impl Resolvable for Trap {
+    type Output = resolved_operations::Trap;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Trap
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Len;
+
+impl Resolvable for Len {
+    type Output = resolved_operations::Len;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Len
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Substr;
+
+impl Resolvable for Substr {
+    type Output = resolved_operations::Substr;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Substr
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct CharAt;
+
+impl Resolvable for CharAt {
+    type Output = resolved_operations::CharAt;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::CharAt
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct PushBack;
+
+impl Resolvable for PushBack {
+    type Output = resolved_operations::PushBack;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::PushBack
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct PopBack;
+
+impl Resolvable for PopBack {
+    type Output = resolved_operations::PopBack;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::PopBack
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct CheckFrame(pub u16);
+
+impl Resolvable for CheckFrame {
+    type Output = resolved_operations::CheckFrame;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::CheckFrame(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct CheckOverflow(pub ArithmeticOp);
+
+impl Resolvable for CheckOverflow {
+    type Output = resolved_operations::CheckOverflow;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::CheckOverflow(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct SubI;
+
+impl Resolvable for SubI {
+    type Output = resolved_operations::SubI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::SubI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct DivI;
+
+impl Resolvable for DivI {
+    type Output = resolved_operations::DivI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::DivI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ModI;
+
+impl Resolvable for ModI {
+    type Output = resolved_operations::ModI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::ModI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct EqI;
+
+impl Resolvable for EqI {
+    type Output = resolved_operations::EqI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::EqI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct NeqI;
+
+impl Resolvable for NeqI {
+    type Output = resolved_operations::NeqI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::NeqI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct LtI;
+
+impl Resolvable for LtI {
+    type Output = resolved_operations::LtI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::LtI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct LeI;
+
+impl Resolvable for LeI {
+    type Output = resolved_operations::LeI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::LeI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct GtI;
+
+impl Resolvable for GtI {
+    type Output = resolved_operations::GtI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::GtI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct GeI;
+
+impl Resolvable for GeI {
+    type Output = resolved_operations::GeI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::GeI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct JmpIfZero(pub u32);
+
+impl Resolvable for JmpIfZero {
+    type Output = resolved_operations::JmpIfZero;
+
+    fn resolve(&self, ctxt: &LabelResolutionContext) -> Self::Output {
+        let dest = ctxt
+            .labels()
+            .resolve_anonymous(self.0)
+            .expect("Failed to resolve conditional jump destination");
+
+        resolved_operations::JmpIfZero(dest)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct JmpIfNotZero(pub u32);
+
+impl Resolvable for JmpIfNotZero {
+    type Output = resolved_operations::JmpIfNotZero;
+
+    fn resolve(&self, ctxt: &LabelResolutionContext) -> Self::Output {
+        let dest = ctxt
+            .labels()
+            .resolve_anonymous(self.0)
+            .expect("Failed to resolve conditional jump destination");
+
+        resolved_operations::JmpIfNotZero(dest)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Swap;
+
+impl Resolvable for Swap {
+    type Output = resolved_operations::Swap;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Swap
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Nop;
+
+impl Resolvable for Nop {
+    type Output = resolved_operations::Nop;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::Nop
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AndI;
+
+impl Resolvable for AndI {
+    type Output = resolved_operations::AndI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::AndI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct OrI;
+
+impl Resolvable for OrI {
+    type Output = resolved_operations::OrI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::OrI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct XorI;
+
+impl Resolvable for XorI {
+    type Output = resolved_operations::XorI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::XorI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ShlI;
+
+impl Resolvable for ShlI {
+    type Output = resolved_operations::ShlI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::ShlI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ShrI;
+
+impl Resolvable for ShrI {
+    type Output = resolved_operations::ShrI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::ShrI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct NotI;
+
+impl Resolvable for NotI {
+    type Output = resolved_operations::NotI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::NotI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct PushF(pub f64);
+
+impl Resolvable for PushF {
+    type Output = resolved_operations::PushF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::PushF(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AddF;
+
+impl Resolvable for AddF {
+    type Output = resolved_operations::AddF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::AddF
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct SubF;
+
+impl Resolvable for SubF {
+    type Output = resolved_operations::SubF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::SubF
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct MulF;
+
+impl Resolvable for MulF {
+    type Output = resolved_operations::MulF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::MulF
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct DivF;
+
+impl Resolvable for DivF {
+    type Output = resolved_operations::DivF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::DivF
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct IToF;
+
+impl Resolvable for IToF {
+    type Output = resolved_operations::IToF;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::IToF
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct FToI;
+
+impl Resolvable for FToI {
+    type Output = resolved_operations::FToI;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::FToI
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct PushConst(pub u16);
+
+impl Resolvable for PushConst {
+    type Output = resolved_operations::PushConst;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::PushConst(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct LoadLocal(pub i16);
+
+impl Resolvable for LoadLocal {
+    type Output = resolved_operations::LoadLocal;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::LoadLocal(self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct StoreLocal(pub i16);
+
+impl Resolvable for StoreLocal {
+    type Output = resolved_operations::StoreLocal;
+
+    fn resolve(&self, _ctxt: &LabelResolutionContext) -> Self::Output {
+        resolved_operations::StoreLocal(self.0)
+    }
+}
@@ -1,8 +1,82 @@
-use anyhow::{Error, Result};
+use anyhow::{bail, Context, Error, Result};
 
 use std::fs;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// The extension of the files that contain source code.
+const SOURCE_EXTENSION: &str = "dyl";
+
+/// A file of source code, and its content.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SourceFile {
+    name: String,
+    content: String,
+}
+
+impl SourceFile {
+    pub(crate) fn new(name: String, content: String) -> SourceFile {
+        SourceFile { name, content }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn content(&self) -> &str {
+        self.content.as_str()
+    }
+}
+
+/// Reads the source files of a program.
+///
+/// Each path may either be a file, or a directory, in which case every
+/// source file it contains is read, in alphabetical order.
+pub(crate) fn read_sources(paths: &[impl AsRef<Path>]) -> Result<Vec<SourceFile>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        let path = path.as_ref();
+
+        if path.is_dir() {
+            files.extend(source_files_in(path)?);
+        } else {
+            files.push(path.to_owned());
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|path| {
+            let content = read_program(&path)
+                .with_context(|| format!("Failed to read input file `{}`", path.display()))?;
+            let name = path.display().to_string();
+
+            Ok(SourceFile::new(name, content))
+        })
+        .collect()
+}
+
+fn source_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .with_context(|| format!("Failed to read directory `{}`", dir.display()))?;
+
+    files.retain(|path| {
+        path.is_file() && path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION)
+    });
+    files.sort();
+
+    if files.is_empty() {
+        bail!("No source file found in `{}`", dir.display());
+    }
+
+    Ok(files)
+}
 
 pub(crate) fn read_program(path: impl AsRef<Path>) -> Result<String> {
     read_to_string(path).map_err(Error::new)
@@ -11,3 +85,7 @@ pub(crate) fn read_program(path: impl AsRef<Path>) -> Result<String> {
 pub(crate) fn write_bytecode(path: impl AsRef<Path>, code: &[u8]) -> Result<()> {
     fs::write(path, code).map(drop).map_err(Error::new)
 }
+
+pub(crate) fn write_text(path: impl AsRef<Path>, text: &str) -> Result<()> {
+    fs::write(path, text).map_err(Error::new)
+}
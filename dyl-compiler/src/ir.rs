@@ -0,0 +1,372 @@
+//! A representation of the lowered code as basic blocks.
+//!
+//! A basic block is a sequence of instructions that can only be entered
+//! through its first instruction and only left through its last one.
+//!
+//! The code is compiled in two phases around this IR. The lowering turns the
+//! typed tree into instructions whose jumps target labels, split into
+//! blocks, so that the passes that need to know how the control flows have
+//! somewhere to run. The blocks are then laid out again, and the labels are
+//! resolved to addresses, which gives the bytecode.
+//!
+//! The IR is printed block by block, in the mnemonic format of the
+//! disassembler.
+//!
+//! Each instruction keeps the location of the code it is lowered from, if
+//! any, through the whole process.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    mem,
+};
+
+use crate::{ast::Span, context::LabelContext, instruction::Instruction};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Program(Vec<Block>);
+
+impl Program {
+    /// Splits lowered code whose instructions have no location into basic
+    /// blocks.
+    #[cfg(test)]
+    pub(crate) fn from_instructions(
+        instructions: Vec<Instruction>,
+        labels: &LabelContext,
+    ) -> Program {
+        let locations = vec![None; instructions.len()];
+
+        Program::from_located_instructions(instructions, locations, labels)
+    }
+
+    /// Splits lowered code into basic blocks, given the location of each
+    /// instruction.
+    ///
+    /// A block starts at every position a label is placed at, after every
+    /// instruction that does not continue to the next one, and after every
+    /// conditional jump.
+    pub(crate) fn from_located_instructions(
+        instructions: Vec<Instruction>,
+        locations: Vec<Option<Span>>,
+        labels: &LabelContext,
+    ) -> Program {
+        let mut leaders = BTreeMap::<u32, Vec<u32>>::new();
+        labels
+            .positions()
+            .for_each(|(label, pos)| leaders.entry(pos).or_default().push(label));
+
+        let mut blocks = Vec::new();
+        let mut current = Block::default();
+
+        let located = instructions.into_iter().zip(locations);
+
+        for (pos, (instruction, location)) in located.enumerate() {
+            if let Some(labels) = leaders.remove(&(pos as u32)) {
+                if !current.instructions.is_empty() {
+                    blocks.push(mem::take(&mut current));
+                }
+
+                current.labels.extend(labels);
+            }
+
+            let ends_block = ends_block(&instruction) || is_conditional_jump(&instruction);
+            current.instructions.push(instruction);
+            current.locations.push(location);
+
+            if ends_block {
+                blocks.push(mem::take(&mut current));
+            }
+        }
+
+        // Labels may be placed right after the last instruction.
+        current.labels.extend(leaders.into_values().flatten());
+
+        if !current.labels.is_empty() || !current.instructions.is_empty() {
+            blocks.push(current);
+        }
+
+        Program(blocks)
+    }
+
+    pub(crate) fn blocks(&self) -> &[Block] {
+        &self.0
+    }
+
+    /// Returns the number of instructions of every block.
+    pub(crate) fn instruction_count(&self) -> usize {
+        self.0.iter().map(|block| block.instructions.len()).sum()
+    }
+
+    pub(crate) fn blocks_mut(&mut self) -> &mut [Block] {
+        &mut self.0
+    }
+
+    /// Returns the index of the block that starts at `label`.
+    pub(crate) fn block_of(&self, label: u32) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|block| block.labels.contains(&label))
+    }
+
+    /// Lays the blocks out one after the other, and moves each label to the
+    /// start of its block.
+    #[cfg(test)]
+    pub(crate) fn into_instructions(self, labels: &mut LabelContext) -> Vec<Instruction> {
+        self.into_located_instructions(labels).0
+    }
+
+    /// Lays the blocks out like `into_instructions`, and returns the location
+    /// of each instruction as well.
+    pub(crate) fn into_located_instructions(
+        self,
+        labels: &mut LabelContext,
+    ) -> (Vec<Instruction>, Vec<Option<Span>>) {
+        let mut instructions = Vec::new();
+        let mut locations = Vec::new();
+
+        for block in self.0 {
+            let start = instructions.len() as u32;
+
+            block.labels.iter().for_each(|label| {
+                labels
+                    .move_to(*label, start)
+                    .expect("Block labels come from the label context")
+            });
+
+            instructions.extend(block.instructions);
+            locations.extend(block.locations);
+        }
+
+        (instructions, locations)
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for (idx, block) in self.0.iter().enumerate() {
+            writeln!(f, "{}:", block.header(idx))?;
+
+            for instruction in &block.instructions {
+                writeln!(f, "    {}", instruction)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Block {
+    labels: Vec<u32>,
+    instructions: Vec<Instruction>,
+
+    /// The location of each instruction.
+    locations: Vec<Option<Span>>,
+}
+
+impl Block {
+    /// Names the block, whose index is `idx`, along with its labels.
+    pub(crate) fn header(&self, idx: usize) -> String {
+        if self.labels.is_empty() {
+            return format!("block {}", idx);
+        }
+
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| format!("L{}", label))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("block {} (labels {})", idx, labels)
+    }
+
+    pub(crate) fn labels(&self) -> &[u32] {
+        &self.labels
+    }
+
+    pub(crate) fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub(crate) fn locations(&self) -> &[Option<Span>] {
+        &self.locations
+    }
+
+    /// Returns the instructions of the block, along with their locations,
+    /// which must be kept in sync.
+    pub(crate) fn instructions_mut(&mut self) -> (&mut Vec<Instruction>, &mut Vec<Option<Span>>) {
+        (&mut self.instructions, &mut self.locations)
+    }
+}
+
+/// Tells whether the instruction following `instruction` is never the next
+/// one to be executed.
+pub(crate) fn ends_block(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Goto(_)
+            | Instruction::CondJmp(_)
+            | Instruction::Ret(_)
+            | Instruction::FStop(_)
+            | Instruction::Trap(_)
+    )
+}
+
+/// Tells whether the instruction either jumps or continues to the next one.
+pub(crate) fn is_conditional_jump(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JmpIfZero(_) | Instruction::JmpIfNotZero(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        context::LoweringContext, lowering, monomorphization, parser, resolution, type_checker,
+    };
+
+    use super::*;
+
+    fn lower_to_ir(code: &str) -> (Program, LoweringContext) {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (ctxt, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+        let ctxt = ctxt.into_typing_context().into_lowering_context();
+        let program = type_checker::type_program(&program);
+
+        let (ctxt, program) = lowering::lower_program(&program, ctxt).unwrap();
+        (program, ctxt)
+    }
+
+    /// Lowers some code, and lays it out as a flat list of instructions.
+    fn lower(code: &str) -> (Vec<Instruction>, LoweringContext) {
+        let (program, mut ctxt) = lower_to_ir(code);
+        let instructions = program.into_instructions(ctxt.labels_mut());
+
+        (instructions, ctxt)
+    }
+
+    #[test]
+    fn blocks_end_at_jumps() {
+        let (instructions, ctxt) = lower("fn main() { 42 }");
+        let program = Program::from_instructions(instructions, ctxt.labels());
+
+        assert_eq!(
+            program.blocks(),
+            [
+                Block {
+                    labels: vec![],
+                    instructions: vec![
+                        Instruction::res_v(1),
+                        Instruction::call(0),
+                        Instruction::f_stop(),
+                    ],
+                    locations: vec![None; 3],
+                },
+                Block {
+                    labels: vec![0],
+                    instructions: vec![
+                        Instruction::push_i(42),
+                        Instruction::pop_copy(2),
+                        Instruction::ret(0, 1),
+                    ],
+                    locations: vec![None; 3],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks_start_at_labels() {
+        let (instructions, ctxt) = lower("fn main() { if 1 == 2 { 3 } else { 4 } }");
+        let program = Program::from_instructions(instructions, ctxt.labels());
+
+        program.blocks().windows(2).for_each(|blocks| {
+            let (last, body) = blocks[0].instructions().split_last().unwrap();
+
+            let is_jump = |i| ends_block(i) || is_conditional_jump(i);
+
+            assert!(!body.iter().any(is_jump));
+            assert!(is_jump(last) || !blocks[1].labels().is_empty());
+        });
+    }
+
+    #[test]
+    fn laying_out_restores_the_code() {
+        let (instructions, mut ctxt) = lower(
+            "fn main() { let f = |x| x + 1; if 1 == 2 { f(3) } else { f(4) } }
+             fn g(a) { a * 2 }",
+        );
+
+        let positions = ctxt.labels().positions().collect::<Vec<_>>();
+        let program = Program::from_instructions(instructions.clone(), ctxt.labels());
+
+        assert_eq!(program.into_instructions(ctxt.labels_mut()), instructions);
+        assert_eq!(ctxt.labels().positions().collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    fn laying_out_keeps_the_locations() {
+        let (program, mut ctxt) = lower_to_ir("fn main() {\n    if 1 == 2 { 3 } else { 4 }\n}");
+        let (laid_out, locations) = program.into_located_instructions(ctxt.labels_mut());
+
+        let relocated =
+            Program::from_located_instructions(laid_out.clone(), locations.clone(), ctxt.labels());
+        let (instructions, relocated_locations) =
+            relocated.into_located_instructions(ctxt.labels_mut());
+
+        let lines = laid_out
+            .iter()
+            .zip(&locations)
+            .map(|(instruction, location)| {
+                let line = location.as_ref().map(|span| span.line());
+                (instruction.to_string(), line)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(laid_out, instructions);
+        assert_eq!(locations, relocated_locations);
+        assert_eq!(lines[0], ("res_v 1".to_owned(), Some(1)));
+        assert!(lines.contains(&("push_i 3".to_owned(), Some(2))));
+        assert_eq!(lines.last().unwrap().1, Some(1));
+    }
+
+    #[test]
+    fn labels_at_the_end() {
+        let mut labels = LabelContext::default();
+        let end = labels.new_anonymous();
+        labels.set_position(end, 1).unwrap();
+
+        let program = Program::from_instructions(vec![Instruction::goto(end)], &labels);
+
+        assert_eq!(
+            program.blocks(),
+            [
+                Block {
+                    labels: vec![],
+                    instructions: vec![Instruction::goto(end)],
+                    locations: vec![None],
+                },
+                Block {
+                    labels: vec![end],
+                    instructions: vec![],
+                    locations: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn printer() {
+        let (instructions, ctxt) = lower("fn main() { 42 }");
+        let program = Program::from_instructions(instructions, ctxt.labels());
+
+        assert_eq!(
+            program.to_string(),
+            "block 0:\n    res_v 1\n    call L0\n    f_stop\n\
+             block 1 (labels L0):\n    push_i 42\n    pop_copy 2\n    ret 1 0\n"
+        );
+    }
+}
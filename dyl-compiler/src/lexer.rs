@@ -0,0 +1,330 @@
+//! The tokens of the language.
+//!
+//! The source code is split in tokens, each with the location it comes from.
+//! Every character belongs to exactly one token, trivia included, so that
+//! the text of the tokens, put back together, is the source code: tools that
+//! rewrite the code, such as a formatter, can keep the comments where they
+//! were written.
+//!
+//! Lexing never fails. Characters that do not start a token, along with
+//! unterminated strings and comments, become error tokens, and are reported
+//! by the parser, which reads its keywords, names, literals and punctuation
+//! one token at a time with [`next_token`].
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::ast::Span;
+
+/// The words that have a meaning of their own, and can't be used as names.
+const KEYWORDS: &[&str] = &[
+    "as", "const", "else", "enum", "false", "fn", "if", "import", "let", "macro", "match", "mod",
+    "mut", "return", "struct", "true",
+];
+
+/// The punctuation of the language.
+///
+/// Punctuation is matched in order, so a token must come before the tokens
+/// it starts with.
+const PUNCTUATION: &[&str] = &[
+    "::", "=>", "->", "==", "!=", "<=", ">=", "||", "&&", "**=", "**", "+=", "-=", "*=", "!", "{",
+    "}", "(", ")", "[", "]", "<", ">", "|", ":", ";", ".", ",", "=", "+", "-", "*",
+];
+
+/// Returns whether a word is a keyword.
+pub(crate) fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Keyword,
+    Ident,
+
+    /// An integer literal, without its sign: the minus sign is punctuation,
+    /// which the parser makes part of the literal where an operand is
+    /// expected.
+    Integer,
+
+    /// A string literal, quotes and escapes included.
+    String,
+    Punctuation,
+    Whitespace,
+    Comment,
+
+    /// A character that starts no token, or a string or a comment that is
+    /// not terminated.
+    Error,
+}
+
+impl TokenKind {
+    /// Returns whether the token can be left out without changing the
+    /// meaning of the program.
+    pub(crate) fn is_trivia(self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            TokenKind::Keyword => "keyword",
+            TokenKind::Ident => "ident",
+            TokenKind::Integer => "integer",
+            TokenKind::String => "string",
+            TokenKind::Punctuation => "punctuation",
+            TokenKind::Whitespace => "whitespace",
+            TokenKind::Comment => "comment",
+            TokenKind::Error => "error",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    span: Span,
+}
+
+impl<'a> Token<'a> {
+    pub(crate) fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub(crate) fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+/// Splits some code in tokens.
+pub(crate) fn tokenize(code: &str) -> Vec<Token<'_>> {
+    let mut lexer = Lexer {
+        code,
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+    let mut tokens = Vec::new();
+
+    while let Some((kind, length)) = next_token(&code[lexer.offset..]) {
+        tokens.push(lexer.token(kind, length));
+    }
+
+    tokens
+}
+
+struct Lexer<'a> {
+    code: &'a str,
+
+    /// Where the next token starts, in bytes.
+    offset: usize,
+    line: u32,
+    column: usize,
+}
+
+/// Returns the kind and the length, in bytes, of the token `rest` starts
+/// with, if it is not empty.
+pub(crate) fn next_token(rest: &str) -> Option<(TokenKind, usize)> {
+    let first = rest.chars().next()?;
+
+    let token = if first.is_whitespace() {
+        (TokenKind::Whitespace, prefix_len(rest, char::is_whitespace))
+    } else if rest.starts_with("/*") {
+        block_comment(rest)
+    } else if first.is_ascii_alphabetic() || first == '_' {
+        let length = prefix_len(rest, |c| c.is_ascii_alphanumeric() || c == '_');
+        let kind = if is_keyword(&rest[..length]) {
+            TokenKind::Keyword
+        } else {
+            TokenKind::Ident
+        };
+
+        (kind, length)
+    } else if first.is_ascii_digit() {
+        // Prefixed literals and malformed digits are made of letters as
+        // well, and are checked by the parser.
+        (
+            TokenKind::Integer,
+            prefix_len(rest, |c| c.is_alphanumeric() || c == '_'),
+        )
+    } else if first == '"' {
+        string(rest)
+    } else if let Some(punctuation) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) {
+        (TokenKind::Punctuation, punctuation.len())
+    } else {
+        (TokenKind::Error, first.len_utf8())
+    };
+
+    Some(token)
+}
+
+impl<'a> Lexer<'a> {
+    /// Makes a token of the next `length` bytes, and moves past them.
+    fn token(&mut self, kind: TokenKind, length: usize) -> Token<'a> {
+        let text = &self.code[self.offset..self.offset + length];
+
+        let span = Span::new(self.line, self.column)
+            .with_offset(self.offset)
+            .with_length(length);
+
+        for c in text.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset += length;
+
+        Token { kind, text, span }
+    }
+}
+
+/// Returns the length, in bytes, of the characters at the start of `text`
+/// that satisfy a predicate.
+fn prefix_len(text: &str, predicate: impl Fn(char) -> bool) -> usize {
+    text.find(|c| !predicate(c)).unwrap_or(text.len())
+}
+
+/// Lexes a block comment, which may contain other block comments.
+fn block_comment(text: &str) -> (TokenKind, usize) {
+    let mut depth = 0;
+    let mut idx = 0;
+
+    while idx < text.len() {
+        let rest = &text[idx..];
+
+        if rest.starts_with("/*") {
+            depth += 1;
+            idx += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            idx += 2;
+
+            if depth == 0 {
+                return (TokenKind::Comment, idx);
+            }
+        } else {
+            idx += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    (TokenKind::Error, text.len())
+}
+
+fn string(text: &str) -> (TokenKind, usize) {
+    let mut chars = text.char_indices().skip(1);
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return (TokenKind::String, idx + 1),
+            '\\' => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    (TokenKind::Error, text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(code)
+            .into_iter()
+            .filter(|token| token.kind() != TokenKind::Whitespace)
+            .map(|token| (token.kind(), token.text()))
+            .collect()
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            kinds("fn if_ iffy _ mut"),
+            [
+                (TokenKind::Keyword, "fn"),
+                (TokenKind::Ident, "if_"),
+                (TokenKind::Ident, "iffy"),
+                (TokenKind::Ident, "_"),
+                (TokenKind::Keyword, "mut"),
+            ]
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            kinds(r#"-0x1F 1_000 "a \"b\"" 2a"#),
+            [
+                (TokenKind::Punctuation, "-"),
+                (TokenKind::Integer, "0x1F"),
+                (TokenKind::Integer, "1_000"),
+                (TokenKind::String, r#""a \"b\"""#),
+                (TokenKind::Integer, "2a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn punctuation_is_as_long_as_possible() {
+        assert_eq!(
+            kinds("a::b=>**<="),
+            [
+                (TokenKind::Ident, "a"),
+                (TokenKind::Punctuation, "::"),
+                (TokenKind::Ident, "b"),
+                (TokenKind::Punctuation, "=>"),
+                (TokenKind::Punctuation, "**"),
+                (TokenKind::Punctuation, "<="),
+            ]
+        );
+    }
+
+    #[test]
+    fn trivia_is_kept() {
+        let code = "fn /* a /* nested */ comment */\n  main";
+        let tokens = tokenize(code);
+
+        assert_eq!(
+            tokens.iter().map(Token::text).collect::<String>(),
+            code,
+            "The tokens cover the whole code"
+        );
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.span().line(), token.span().column(), token.kind()))
+                .collect::<Vec<_>>(),
+            [
+                (1, 1, TokenKind::Keyword),
+                (1, 3, TokenKind::Whitespace),
+                (1, 4, TokenKind::Comment),
+                (1, 32, TokenKind::Whitespace),
+                (2, 3, TokenKind::Ident),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            kinds("a # \"b"),
+            [
+                (TokenKind::Ident, "a"),
+                (TokenKind::Error, "#"),
+                (TokenKind::Error, "\"b"),
+            ]
+        );
+        assert_eq!(kinds("/* a"), [(TokenKind::Error, "/* a")]);
+    }
+}
@@ -1,70 +1,683 @@
-use std::path::Path;
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Error, Result};
 
-use dyl_bytecode::Instruction;
+use dyl_bytecode::{constant_pool::ConstantPool, line_table::LineTable, Instruction};
+
+use crate::{io::SourceFile, session::Session};
 
 #[cfg(test)]
 mod macros;
 
 mod ast;
+mod ast_json;
+mod builtins;
+mod captures;
+mod cfg;
+mod code_builder;
+mod const_eval;
 mod context;
+mod dead_code;
+mod desugaring;
+mod diagnostic;
+mod expansion;
+mod folding;
+mod hir;
+mod inlining;
 mod instruction;
 mod io;
+mod ir;
+mod lexer;
+mod lint;
+mod liveness;
 mod lowering;
+mod monomorphization;
 mod parser;
+mod passes;
+mod patterns;
+mod peephole;
+mod resolution;
+mod session;
+mod ssa;
+mod stack_check;
+mod symbol;
+// Comments are collected while parsing, but no tool reads them yet.
+#[allow(dead_code)]
+mod trivia;
+
 mod ty;
 mod type_checker;
 
-pub fn compile<PA, PB>(i: PA, o: PB) -> Result<()>
+pub use diagnostic::{Diagnostic, Diagnostics, Severity};
+pub use lint::{Lint, LintLevel, LintLevels};
+pub use passes::{AstPass, FunctionInfo, Ir, IrPass, Location, PassManager, SyntaxTree};
+
+/// The name given to the code compiled by `compile_str` in diagnostics.
+const STR_SOURCE_NAME: &str = "<input>";
+
+/// The number of errors reported before the compilation stops, by default.
+pub const DEFAULT_ERROR_LIMIT: usize = 20;
+
+/// How deep code can be nested, by default.
+pub const DEFAULT_NESTING_LIMIT: usize = 256;
+
+/// Options that change how a program is compiled.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// What is done with the warnings of each lint.
+    pub lints: LintLevels,
+
+    /// Selects the optimizations that are performed.
+    pub opt_level: OptLevel,
+
+    /// Writes the control-flow graph of the compiled code to this file, in
+    /// the Graphviz dot format.
+    pub cfg_output: Option<PathBuf>,
+
+    /// The passes that run between the phases of the compiler.
+    pub passes: PassManager,
+
+    /// Makes the compiled code check, at runtime, that the stack is laid out
+    /// as the compiler expects and that its arithmetic does not overflow. The
+    /// compiler also checks that the code reads no local before writing it.
+    pub debug_checks: bool,
+
+    /// Stops the compilation once this many errors are reported, the others
+    /// being left out. There is no limit if it is `None`.
+    pub error_limit: Option<usize>,
+
+    /// How deep code can be nested: each parenthesis, block, right operand
+    /// of `**` or comment in another one is a level. Deeper code is a syntax
+    /// error, which keeps the parser from overflowing its stack. Chains of
+    /// operations are not nested, however long they are.
+    pub nesting_limit: usize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            lints: LintLevels::default(),
+            opt_level: OptLevel::default(),
+            cfg_output: None,
+            passes: PassManager::default(),
+            debug_checks: false,
+            error_limit: Some(DEFAULT_ERROR_LIMIT),
+            nesting_limit: DEFAULT_NESTING_LIMIT,
+        }
+    }
+}
+
+/// How much effort is put into making the compiled code faster.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// The code is compiled as written.
+    #[default]
+    O0,
+
+    /// Evaluates constant expressions at compile time, removes the code
+    /// that has no effect on the result of the program, and stores variables
+    /// in the stack slots of the ones that are no longer used.
+    O1,
+
+    /// Additionally inlines small functions, and replaces sequences of
+    /// instructions by cheaper ones.
+    O2,
+}
+
+impl FromStr for OptLevel {
+    type Err = Error;
+
+    fn from_str(level: &str) -> Result<OptLevel> {
+        match level {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            _ => Err(anyhow!("Unknown optimization level `{}`", level)),
+        }
+    }
+}
+
+/// A stage of the compilation whose result can be written out, to see what
+/// the compiler does with a program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Artifact {
+    /// The tokens of each file, trivia aside, along with their location.
+    Tokens,
+
+    /// The syntax tree, as JSON.
+    Ast,
+
+    /// The typed tree code is generated from, once the program is checked.
+    Hir,
+
+    /// The lowered program, split in basic blocks, once optimized.
+    Ir,
+
+    /// The instructions, along with their address and the code they come
+    /// from.
+    Bytecode,
+
+    /// The instructions as assembly, with labels instead of addresses.
+    Asm,
+}
+
+impl FromStr for Artifact {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Artifact> {
+        match name {
+            "tokens" => Ok(Artifact::Tokens),
+            "ast" | "ast-json" => Ok(Artifact::Ast),
+            "hir" => Ok(Artifact::Hir),
+            "ir" => Ok(Artifact::Ir),
+            "bytecode" => Ok(Artifact::Bytecode),
+            "asm" => Ok(Artifact::Asm),
+            _ => Err(anyhow!("Unknown output `{}`", name)),
+        }
+    }
+}
+
+impl Display for Artifact {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Artifact::Tokens => "tokens",
+            Artifact::Ast => "ast",
+            Artifact::Hir => "hir",
+            Artifact::Ir => "ir",
+            Artifact::Bytecode => "bytecode",
+            Artifact::Asm => "asm",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A compiled program.
+#[derive(Clone, Debug)]
+pub struct Compiled {
+    pub instructions: Vec<Instruction>,
+
+    /// Tells which code each instruction comes from.
+    pub line_table: LineTable,
+
+    /// The constants the `push_const` instructions refer to.
+    pub constants: ConstantPool,
+
+    /// The number of instructions before and after the optimizations, if
+    /// the code is optimized.
+    pub sizes: Option<(usize, usize)>,
+}
+
+pub fn compile<PA, PB>(i: PA, o: PB) -> Result<Diagnostics, Diagnostics>
 where
     PA: AsRef<Path>,
     PB: AsRef<Path>,
 {
-    let content = io::read_program(i.as_ref())
-        .with_context(|| format!("Failed to read input file `{}`", i.as_ref().display()))?;
+    compile_with_options(i, o, &Options::default())
+}
 
-    let (ctxt, ast) = parser::parse_input(content.as_str())?;
+/// Compiles a program, and writes its bytecode to `o`, preceded by its
+/// constant pool. Its line table is written next to it, in a file with the
+/// same name followed by `.lines`. Returns the warnings.
+pub fn compile_with_options<PA, PB>(
+    i: PA,
+    o: PB,
+    options: &Options,
+) -> Result<Diagnostics, Diagnostics>
+where
+    PA: AsRef<Path>,
+    PB: AsRef<Path>,
+{
+    let (compiled, warnings) = compile_programs(&[i], options)?;
 
-    let ctxt = ctxt.into_typing_context();
+    let output = Instruction::encode_with_constants(&compiled.instructions, &compiled.constants);
+    io::write_bytecode(&o, output.as_slice())
+        .context("Failed to write output bytecode")
+        .map_err(io_error)?;
 
-    // let ctxt = type_checker::check_ast(&ast, ctxt)?;
+    let mut line_table_path = o.as_ref().as_os_str().to_owned();
+    line_table_path.push(".lines");
+    io::write_bytecode(line_table_path, compiled.line_table.encode().as_slice())
+        .context("Failed to write line table")
+        .map_err(io_error)?;
 
-    let ctxt = ctxt.into_lowering_context();
+    Ok(warnings)
+}
 
-    let (ctxt, instructions) = lowering::lower_ast(&ast, ctxt)?;
+pub fn bytecode_from_program<P>(path: P) -> Result<(Compiled, Diagnostics), Diagnostics>
+where
+    P: AsRef<Path>,
+{
+    bytecode_from_program_with_options(path, &Options::default())
+}
 
-    let ctxt = ctxt.into_label_resolution_context();
+pub fn bytecode_from_program_with_options<P>(
+    path: P,
+    options: &Options,
+) -> Result<(Compiled, Diagnostics), Diagnostics>
+where
+    P: AsRef<Path>,
+{
+    compile_programs(&[path], options)
+}
 
-    let instructions = context::resolve_labels(instructions.as_slice(), &ctxt);
+/// Compiles a program made of several source files, and writes its
+/// control-flow graph if the options ask for it.
+///
+/// Each path is either a source file or a directory containing source files.
+/// All the files share the same namespace: an item declared in one of them
+/// can be used in any other.
+///
+/// Nothing is printed: the errors are returned along with the warnings
+/// reported before them, for the caller to show.
+pub fn compile_programs<P>(
+    paths: &[P],
+    options: &Options,
+) -> Result<(Compiled, Diagnostics), Diagnostics>
+where
+    P: AsRef<Path>,
+{
+    let files = io::read_sources(paths).map_err(io_error)?;
+    let mut session = Session::new(files, options.clone());
+
+    let compiled = compile_session(&mut session, options)?;
+
+    if let (Some(path), Some(dot)) = (&options.cfg_output, session.cfg()) {
+        io::write_text(path, dot)
+            .with_context(|| format!("Failed to write control-flow graph to `{}`", path.display()))
+            .map_err(io_error)?;
+    }
+
+    Ok(compiled)
+}
+
+/// Compiles a program written in a string, and returns it along with the
+/// warnings, or the errors along with the warnings reported before them.
+pub fn compile_str(code: &str) -> Result<(Compiled, Diagnostics), Diagnostics> {
+    let file = SourceFile::new(STR_SOURCE_NAME.to_owned(), code.to_owned());
+    let options = Options::default();
+
+    compile_session(&mut Session::new(vec![file], options.clone()), &options)
+}
+
+/// Compiles a program made of a source file, or of a directory containing
+/// source files, like `compile_str`.
+pub fn compile_file<P>(path: P) -> Result<(Compiled, Diagnostics), Diagnostics>
+where
+    P: AsRef<Path>,
+{
+    let files = io::read_sources(&[path]).map_err(io_error)?;
+    let options = Options::default();
+
+    compile_session(&mut Session::new(files, options.clone()), &options)
+}
 
-    let output = Instruction::encode_multiple(&instructions);
+fn compile_session(
+    session: &mut Session,
+    options: &Options,
+) -> Result<(Compiled, Diagnostics), Diagnostics> {
+    let (instructions, line_table) = session.compile().map_err(|()| session.take_diagnostics())?;
 
-    io::write_bytecode(o, output.as_slice()).context("Failed to write output bytecode")?;
+    let compiled = Compiled {
+        instructions,
+        line_table,
+        constants: session.take_constants(),
+        sizes: session.sizes().filter(|_| options.opt_level > OptLevel::O0),
+    };
 
-    Ok(())
+    Ok((compiled, session.take_diagnostics()))
 }
 
-pub fn bytecode_from_program<P>(path: P) -> Result<Vec<Instruction>>
+/// Reports an error that is not about the code, such as a file that can't
+/// be read, as a diagnostic.
+fn io_error(e: Error) -> Diagnostics {
+    Diagnostics::from(Diagnostic::error(format!("{:#}", e)))
+}
+
+/// Compiles a program made of several source files as far as the last of
+/// the given artifacts, and returns each of them, in the order the
+/// compilation produces them, along with the warnings.
+pub fn artifacts_from_programs<P>(
+    paths: &[P],
+    options: &Options,
+    artifacts: &[Artifact],
+) -> Result<(Vec<(Artifact, String)>, Diagnostics), Diagnostics>
 where
     P: AsRef<Path>,
 {
-    let content = io::read_program(path.as_ref())
-        .with_context(|| format!("Failed to read input file `{}`", path.as_ref().display()))?;
+    let files = io::read_sources(paths).map_err(io_error)?;
+    let mut session = Session::new(files, options.clone());
+
+    // Tokens are listed without parsing the program.
+    let rslt = if artifacts
+        .iter()
+        .all(|artifact| *artifact == Artifact::Tokens)
+    {
+        produce_artifacts(&mut session, artifacts)
+    } else {
+        session.on_compiler_thread(|session| produce_artifacts(session, artifacts))
+    };
+    let diagnostics = session.take_diagnostics();
+
+    match rslt {
+        Ok(produced) => Ok((produced, diagnostics)),
+        Err(()) => Err(diagnostics),
+    }
+}
+
+fn produce_artifacts(
+    session: &mut Session,
+    artifacts: &[Artifact],
+) -> Result<Vec<(Artifact, String)>, ()> {
+    let mut produced = Vec::new();
+    let last = match artifacts.iter().max() {
+        Some(last) => *last,
+        None => return Ok(produced),
+    };
+    let mut produce = |artifact, text: &dyn Fn() -> String| {
+        if artifacts.contains(&artifact) {
+            produced.push((artifact, text()));
+        }
+    };
+
+    produce(Artifact::Tokens, &|| session.tokens());
+
+    if last == Artifact::Tokens {
+        return Ok(produced);
+    }
+
+    let (ctxt, ast) = session.parse()?;
+    produce(Artifact::Ast, &|| {
+        format!("{}\n", ast_json::program_to_json(&ast))
+    });
+
+    if last > Artifact::Ast {
+        let (ctxt, ast, typed) = session.analyze(ctxt, &ast)?;
+        produce(Artifact::Hir, &|| typed.to_string());
+
+        if last == Artifact::Hir {
+            return Ok(produced);
+        }
+
+        let (ctxt, program) = session.lower(ctxt, &ast, &typed)?;
+        produce(Artifact::Ir, &|| program.to_string());
+
+        if last > Artifact::Ir {
+            let (instructions, line_table) = session.emit(ctxt, program)?;
+
+            produce(Artifact::Bytecode, &|| {
+                let bytecode = Instruction::encode_multiple(&instructions);
+                dyl_bytecode::display::disassembly(&bytecode, &line_table)
+                    .expect("Compiled bytecode can be decoded")
+            });
+            produce(Artifact::Asm, &|| {
+                dyl_bytecode::display::assembly(&instructions)
+            });
+        }
+    }
+
+    Ok(produced)
+}
+
+#[cfg(test)]
+mod tests {
+    use dyl_bytecode::constant_pool::Constant;
+
+    use super::*;
+
+    #[test]
+    fn compiles_a_string() {
+        let (compiled, warnings) = compile_str("fn main() { 1 + 2 }").unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(
+            compiled.instructions[..3],
+            [
+                Instruction::res_v(1),
+                Instruction::call(3),
+                Instruction::f_stop()
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_are_constants() {
+        let (compiled, _) =
+            compile_str("fn main() { print(\"a\"); print(\"b\"); print(\"a\") }").unwrap();
+
+        let pushed = compiled
+            .instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::PushConst(_)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            pushed,
+            [
+                &Instruction::push_const(0),
+                &Instruction::push_const(1),
+                &Instruction::push_const(0)
+            ]
+        );
+        assert_eq!(compiled.constants.len(), 2);
+        assert_eq!(
+            compiled.constants.get(1),
+            Some(&Constant::String("b".to_owned()))
+        );
+    }
+
+    #[test]
+    fn identical_strings_share_a_constant() {
+        let code = "const NAME = \"dyl\";
+            fn f() { \"dyl\" }
+            fn main() { let g = || \"dyl\"; (f(), g(), NAME, \"dyl\") }";
+        let (compiled, _) = compile_str(code).unwrap();
+
+        let mut expected = ConstantPool::default();
+        expected.add(Constant::String("dyl".to_owned())).unwrap();
+        assert_eq!(compiled.constants, expected);
+    }
+
+    #[test]
+    fn constants_are_written_before_the_bytecode() {
+        let dir = std::env::temp_dir().join(format!("dyl-constants-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let source = dir.join("main.dyl");
+        std::fs::write(&source, "fn main() { print(\"hello\") }").unwrap();
+        compile(&source, dir.join("main.dylc")).unwrap();
+
+        let bytecode = std::fs::read(dir.join("main.dylc")).unwrap();
+        let decoded = Instruction::from_bytes_with_constants(&bytecode);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (instructions, constants) = decoded.unwrap();
+        let mut expected = ConstantPool::default();
+        expected.add(Constant::String("hello".to_owned())).unwrap();
+        assert_eq!(constants, expected);
+        assert_eq!(
+            instructions,
+            compile_str("fn main() { print(\"hello\") }")
+                .unwrap()
+                .0
+                .instructions
+        );
+    }
+
+    #[test]
+    fn returns_the_warnings() {
+        let (_, warnings) = compile_str("fn main() {\n    let a = 1;\n    2\n}").unwrap();
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|diagnostic| (diagnostic.severity(), diagnostic.message()))
+                .collect::<Vec<_>>(),
+            [(Severity::Warning, "Unused variable `a`")]
+        );
+        assert!(warnings.to_string().contains(" --> <input>:2:9"));
+    }
+
+    #[test]
+    fn returns_the_diagnostics() {
+        let diagnostics = compile_str("fn main() {\n    let a = 1;\n    b\n}").unwrap_err();
+
+        assert!(diagnostics.has_errors());
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity(), diagnostic.message()))
+                .collect::<Vec<_>>(),
+            [
+                (Severity::Warning, "Unused variable `a`"),
+                (Severity::Error, "Undefined variable `b`")
+            ]
+        );
+        assert!(diagnostics.to_string().contains(" --> <input>:3:5"));
+    }
+
+    #[test]
+    fn lowering_errors_are_located() {
+        let located = |code: &str| {
+            let diagnostics = compile_str(code).unwrap_err();
+            let rendered = diagnostics.to_string();
+
+            rendered
+                .lines()
+                .find_map(|line| line.strip_prefix(" --> <input>:"))
+                .map(str::to_owned)
+        };
+
+        assert_eq!(
+            located("const N = 1; fn main() { N = 2 }").as_deref(),
+            Some("1:26")
+        );
+        assert_eq!(
+            located("fn f(a) { a } fn main() { f(1, 2) }").as_deref(),
+            Some("1:27")
+        );
+        assert_eq!(
+            located("enum E { A, B } fn main() { match E::A { E::A => 1 } }").as_deref(),
+            Some("1:29")
+        );
+        assert_eq!(
+            located("struct P { x, y } fn main() { P { x: 1 } }").as_deref(),
+            Some("1:31")
+        );
+        assert_eq!(
+            located("fn f() { 1 } fn f() { 2 } fn main() { 0 }").as_deref(),
+            Some("1:17")
+        );
+        assert_eq!(located("fn main(a) { 0 }").as_deref(), Some("1:4"));
+        assert_eq!(
+            located("fn f() { 1 } const N = f(); fn main() { N }").as_deref(),
+            Some("1:24")
+        );
+        assert_eq!(
+            located("fn id<T>(x: T) -> T { x } fn main() { let f = id; f }").as_deref(),
+            Some("1:47")
+        );
+        assert_eq!(
+            located("fn main() { let (a, b) = (1, 2, 3); a + b }").as_deref(),
+            Some("1:17")
+        );
+    }
+
+    #[test]
+    fn lowering_errors_have_codes() {
+        let code_of = |code: &str| {
+            let diagnostics = compile_str(code).unwrap_err();
+            let rendered = diagnostics.to_string();
+
+            rendered
+                .lines()
+                .find_map(|line| line.strip_prefix("error["))
+                .and_then(|line| line.split(']').next())
+                .map(str::to_owned)
+        };
+
+        assert_eq!(code_of("fn f() { 1 }").as_deref(), Some("E0009"));
+        assert_eq!(
+            code_of("enum E { A, B } fn main() { match E::A { E::A => 1 } }").as_deref(),
+            Some("E0010")
+        );
+        assert_eq!(
+            code_of("struct P { x, y } fn main() { P { x: 1 } }").as_deref(),
+            Some("E0011")
+        );
+        assert_eq!(
+            code_of("fn f() { 1 } const N = f(); fn main() { N }").as_deref(),
+            Some("E0007")
+        );
+        assert_eq!(
+            code_of("fn id<T>(x: T) -> T { x } fn main() { let f = id; f }").as_deref(),
+            Some("E0004")
+        );
+    }
+
+    fn artifacts(code: &str, artifacts: &[Artifact]) -> Result<Vec<Artifact>, ()> {
+        let file = SourceFile::new("a.dyl".to_owned(), code.to_owned());
+        let mut session = Session::new(vec![file], Options::default());
+
+        produce_artifacts(&mut session, artifacts)
+            .map(|produced| produced.into_iter().map(|(artifact, _)| artifact).collect())
+    }
 
-    let (ctxt, ast) = parser::parse_input(content.as_str())?;
+    #[test]
+    fn artifacts_come_in_the_order_of_the_compilation() {
+        assert_eq!(
+            artifacts(
+                "fn main() { 1 }",
+                &[Artifact::Asm, Artifact::Ast, Artifact::Ir]
+            ),
+            Ok(vec![Artifact::Ast, Artifact::Ir, Artifact::Asm])
+        );
+    }
 
-    let ctxt = ctxt.into_typing_context();
+    #[test]
+    fn compilation_stops_at_the_last_artifact() {
+        // The program parses, but does not type check.
+        let code = "fn main() { a }";
 
-    // let ctxt = type_checker::check_ast(&ast, ctxt)?;
+        assert_eq!(
+            artifacts("fn main( {", &[Artifact::Tokens]),
+            Ok(vec![Artifact::Tokens])
+        );
+        assert_eq!(artifacts(code, &[Artifact::Ast]), Ok(vec![Artifact::Ast]));
+        assert_eq!(artifacts(code, &[Artifact::Ast, Artifact::Ir]), Err(()));
+    }
 
-    let ctxt = ctxt.into_lowering_context();
+    #[test]
+    fn typed_tree_comes_before_the_ir() {
+        assert_eq!(
+            artifacts("fn main() { 1 }", &[Artifact::Ir, Artifact::Hir]),
+            Ok(vec![Artifact::Hir, Artifact::Ir])
+        );
+        assert_eq!(
+            artifacts("fn main() { a }", &[Artifact::Hir]),
+            Err(()),
+            "The typed tree is only produced for checked programs"
+        );
+    }
 
-    let (ctxt, instructions) = lowering::lower_ast(&ast, ctxt)?;
+    #[test]
+    fn deep_programs_compile_on_small_stacks() {
+        let ifs = "if true { ".repeat(250) + "1" + &" } else { 0 }".repeat(250);
+        let code = format!("fn main() {{ {} }}", ifs);
 
-    let ctxt = ctxt.into_label_resolution_context();
+        assert!(compile_str(&code).is_ok());
+    }
 
-    let final_instructions = context::resolve_labels(instructions.as_slice(), &ctxt);
+    #[test]
+    fn long_operation_chains_compile() {
+        let chain = |terms: usize| format!("fn main() -> int {{ 1{} }}", " + 1".repeat(terms - 1));
 
-    Ok(final_instructions)
+        for terms in [300, 2000] {
+            assert!(compile_str(&chain(terms)).is_ok(), "{} terms", terms);
+        }
+    }
 }
@@ -0,0 +1,272 @@
+//! The warnings that can be silenced, or turned into errors.
+//!
+//! Each lint is a check done by one of the passes, which always reports the
+//! warnings it finds. Once a pass is over, the warnings are filtered by the
+//! level of their lint: an allowed lint is dropped, a denied one becomes an
+//! error, which stops the compilation.
+//!
+//! The custom passes define their own lints, which they register in the
+//! [`LintLevels`] so that they can be set by name, like the built-in ones.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Error as AnyError};
+
+use crate::diagnostic::{Code, Diagnostic};
+
+/// A check that reports warnings, with [`Diagnostic::with_lint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Lint {
+    name: &'static str,
+    default_level: LintLevel,
+
+    /// The code of the warnings of a built-in lint.
+    code: Option<Code>,
+}
+
+impl Lint {
+    /// A variable is never read.
+    pub const UNUSED_VARIABLE: Lint =
+        Lint::builtin("unused-variable", LintLevel::Warn, Code::UnusedVariable);
+
+    /// Some code is never run.
+    pub const UNREACHABLE: Lint = Lint::builtin("unreachable", LintLevel::Warn, Code::Unreachable);
+
+    /// A `let` binding has the same name as a variable that is still in
+    /// scope.
+    pub const SHADOWING: Lint = Lint::builtin("shadowing", LintLevel::Allow, Code::Shadowing);
+
+    /// The condition of an `if` is known at compile time.
+    pub const CONSTANT_CONDITION: Lint = Lint::builtin(
+        "constant-condition",
+        LintLevel::Warn,
+        Code::ConstantCondition,
+    );
+
+    /// Every built-in lint, in the order of their codes.
+    pub const BUILTIN: [Lint; 4] = [
+        Lint::UNUSED_VARIABLE,
+        Lint::UNREACHABLE,
+        Lint::SHADOWING,
+        Lint::CONSTANT_CONDITION,
+    ];
+
+    /// Defines a lint, which is named on the command line by `name`.
+    pub const fn new(name: &'static str, default_level: LintLevel) -> Lint {
+        Lint {
+            name,
+            default_level,
+            code: None,
+        }
+    }
+
+    const fn builtin(name: &'static str, default_level: LintLevel, code: Code) -> Lint {
+        Lint {
+            name,
+            default_level,
+            code: Some(code),
+        }
+    }
+
+    /// The name of the lint on the command line.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The level of the lint when no option sets it.
+    pub fn default_level(&self) -> LintLevel {
+        self.default_level
+    }
+
+    pub(crate) fn code(&self) -> Option<Code> {
+        self.code
+    }
+}
+
+/// What is done with the warnings of a lint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintLevel {
+    /// The warnings are dropped.
+    Allow,
+
+    /// The warnings are reported.
+    Warn,
+
+    /// The warnings are reported as errors.
+    Deny,
+}
+
+/// The known lints, and the level of each lint, which is its default level
+/// unless it is set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LintLevels {
+    /// The lints registered on top of the built-in ones.
+    registered: Vec<Lint>,
+
+    levels: HashMap<Lint, LintLevel>,
+
+    /// The level of the lints that warn by default, and are not set.
+    warnings: Option<LintLevel>,
+}
+
+impl LintLevels {
+    /// The name of the group of the lints that warn by default, on the
+    /// command line.
+    pub const WARNINGS: &'static str = "warnings";
+
+    /// Makes a lint known by its name. Fails if another lint has that name.
+    pub fn register(&mut self, lint: Lint) -> Result<(), AnyError> {
+        match self.lint(lint.name()) {
+            Ok(known) if known == lint => Ok(()),
+            Ok(_) => bail!("Lint `{}` is already registered", lint.name()),
+            Err(_) => {
+                self.registered.push(lint);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the built-in or registered lint that has a given name.
+    pub fn lint(&self, name: &str) -> Result<Lint, AnyError> {
+        Lint::BUILTIN
+            .iter()
+            .chain(&self.registered)
+            .find(|lint| lint.name() == name)
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown lint `{}`", name))
+    }
+
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    /// Sets the level of every lint that warns by default. The lints set one
+    /// by one keep their level, whether they are set before or after.
+    pub fn set_warnings(&mut self, level: LintLevel) {
+        self.warnings = Some(level);
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        if let Some(level) = self.levels.get(&lint) {
+            return *level;
+        }
+
+        match (lint.default_level(), self.warnings) {
+            (LintLevel::Warn, Some(level)) => level,
+            (default, _) => default,
+        }
+    }
+
+    /// Applies the level of its lint to a diagnostic, which is dropped if
+    /// the lint is allowed. Diagnostics that no known lint reports are kept
+    /// as they are.
+    pub(crate) fn apply(&self, diagnostic: Diagnostic) -> Option<Diagnostic> {
+        let lint = match diagnostic.lint().and_then(|name| self.lint(name).ok()) {
+            Some(lint) => lint,
+            None => return Some(diagnostic),
+        };
+
+        match self.level(lint) {
+            LintLevel::Allow => None,
+            LintLevel::Warn => Some(diagnostic),
+            LintLevel::Deny => Some(
+                diagnostic
+                    .into_error()
+                    .with_note(format!("the `{}` lint is denied", lint.name())),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Severity;
+
+    use super::*;
+
+    fn shadowing() -> Diagnostic {
+        Diagnostic::warning("Binding `x` shadows a previous variable").with_lint(Lint::SHADOWING)
+    }
+
+    const TODO: Lint = Lint::new("todo", LintLevel::Warn);
+
+    #[test]
+    fn names() {
+        let levels = LintLevels::default();
+
+        for lint in Lint::BUILTIN {
+            assert_eq!(levels.lint(lint.name()).unwrap(), lint);
+        }
+
+        assert!(levels.lint("shadow").is_err());
+    }
+
+    #[test]
+    fn registered_lints() {
+        let warning = || Diagnostic::warning("Unfinished code").with_lint(TODO);
+        let mut levels = LintLevels::default();
+        assert!(levels.lint("todo").is_err());
+
+        levels.set(TODO, LintLevel::Allow);
+        assert_eq!(levels.apply(warning()), Some(warning()));
+
+        levels.register(TODO).unwrap();
+        levels.register(TODO).unwrap();
+        assert_eq!(levels.lint("todo").unwrap(), TODO);
+
+        assert!(levels
+            .register(Lint::new("shadowing", LintLevel::Warn))
+            .is_err());
+        assert_eq!(levels.apply(warning()), None);
+
+        levels.set(TODO, LintLevel::Deny);
+        assert_eq!(levels.apply(warning()).unwrap().severity(), Severity::Error);
+    }
+
+    #[test]
+    fn default_levels() {
+        let levels = LintLevels::default();
+
+        assert_eq!(levels.apply(shadowing()), None);
+        assert_eq!(
+            levels.apply(Diagnostic::warning("Unreachable code").with_lint(Lint::UNREACHABLE)),
+            Some(Diagnostic::warning("Unreachable code").with_lint(Lint::UNREACHABLE))
+        );
+    }
+
+    #[test]
+    fn denied_lints_are_errors() {
+        let mut levels = LintLevels::default();
+        levels.set(Lint::SHADOWING, LintLevel::Deny);
+
+        let diagnostic = levels.apply(shadowing()).unwrap();
+
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(
+            diagnostic.to_string(),
+            "Binding `x` shadows a previous variable\n  note: the `shadowing` lint is denied"
+        );
+    }
+
+    #[test]
+    fn warnings_group() {
+        let mut levels = LintLevels::default();
+        levels.set(Lint::UNREACHABLE, LintLevel::Warn);
+        levels.set_warnings(LintLevel::Deny);
+
+        assert_eq!(levels.level(Lint::UNUSED_VARIABLE), LintLevel::Deny);
+        assert_eq!(levels.level(Lint::UNREACHABLE), LintLevel::Warn);
+        assert_eq!(levels.level(Lint::SHADOWING), LintLevel::Allow);
+    }
+
+    #[test]
+    fn other_diagnostics_are_kept() {
+        let mut levels = LintLevels::default();
+        levels.set(Lint::UNREACHABLE, LintLevel::Allow);
+
+        assert_eq!(
+            levels.apply(Diagnostic::error("Undefined variable `a`")),
+            Some(Diagnostic::error("Undefined variable `a`"))
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Finds when the variables of a block stop being used.
+//!
+//! Each variable bound by `let` takes a slot of the stack until the end of
+//! its block. Once the last expression that uses a variable is evaluated,
+//! its slot can hold the value of a later binding instead, which keeps the
+//! stack of the function shallower.
+//!
+//! The definitions a name refers to come from the typed tree, so a variable
+//! and another one that shadows it are told apart. There are no loops in the
+//! language: the code of a block is evaluated in order, once.
+
+use std::collections::HashMap;
+
+use crate::hir::{Bindings, DefId, ExprKind};
+
+/// Returns, for each variable read in a block, the last part of the block
+/// that reads it: the index of a binding, or the number of bindings for the
+/// ending expression.
+///
+/// Assigning to a variable counts as reading it, since its slot must still
+/// be there. Variables that are never read are left out.
+pub(crate) fn last_uses(bindings: &Bindings) -> HashMap<DefId, usize> {
+    let mut last_uses = HashMap::new();
+
+    let values = bindings.defines().iter().map(|b| b.value());
+    for (idx, e) in values.chain([bindings.ending_expression()]).enumerate() {
+        let mut defs = Vec::new();
+        uses(e, &mut defs);
+
+        last_uses.extend(defs.into_iter().map(|def| (def, idx)));
+    }
+
+    last_uses
+}
+
+/// Lists the definitions an expression uses, closures included.
+fn uses(e: &ExprKind, defs: &mut Vec<DefId>) {
+    let mut visit = |e| uses(e, defs);
+
+    match e {
+        ExprKind::Integer(_) | ExprKind::Bool(_) | ExprKind::String(_) => {}
+
+        ExprKind::Ident(i) => defs.extend(i.def()),
+        ExprKind::Assignment(a) => {
+            defs.extend(a.def());
+            uses(a.value(), defs);
+        }
+        ExprKind::FnCall(c) => {
+            defs.extend(c.def());
+            c.args().iter().for_each(|arg| uses(arg, defs));
+        }
+
+        ExprKind::Addition(a) => [a.left(), a.right()].into_iter().for_each(visit),
+        ExprKind::Subtraction(s) => [s.left(), s.right()].into_iter().for_each(visit),
+        ExprKind::Multiplication(m) => [m.left(), m.right()].into_iter().for_each(visit),
+        ExprKind::Exponentiation(e) => [e.left(), e.right()].into_iter().for_each(visit),
+        ExprKind::Comparison(c) => [c.left(), c.right()].into_iter().for_each(visit),
+        ExprKind::Logical(l) => [l.left(), l.right()].into_iter().for_each(visit),
+        ExprKind::Cast(c) => visit(c.value()),
+
+        ExprKind::If(i) => [i.condition(), i.consequent()]
+            .into_iter()
+            .chain(i.alternative())
+            .for_each(visit),
+
+        ExprKind::Bindings(b) => b
+            .defines()
+            .iter()
+            .map(|b| b.value())
+            .chain([b.ending_expression()])
+            .for_each(visit),
+
+        ExprKind::Statements(s) => s
+            .statements()
+            .iter()
+            .chain([s.ending_expression()])
+            .for_each(visit),
+
+        ExprKind::Return(r) => visit(r.value()),
+        ExprKind::Tuple(t) => t.elements().iter().for_each(visit),
+        ExprKind::Field(f) => visit(f.tuple()),
+        ExprKind::Array(a) => a.elements().iter().for_each(visit),
+        ExprKind::Index(i) => [i.array(), i.index()].into_iter().for_each(visit),
+
+        ExprKind::IndexAssignment(a) => [a.target().array(), a.target().index(), a.value()]
+            .into_iter()
+            .for_each(visit),
+
+        ExprKind::StructLiteral(s) => s.fields().iter().map(|(_, e)| e).for_each(visit),
+        ExprKind::VariantLiteral(v) => v.args().iter().for_each(visit),
+
+        ExprKind::Match(m) => {
+            uses(m.scrutinee(), defs);
+
+            for arm in m.arms() {
+                arm.guard().into_iter().for_each(|g| uses(g, defs));
+                uses(arm.body(), defs);
+            }
+        }
+
+        // The captures of a closure are copied when it is made.
+        ExprKind::Closure(c) => visit(c.body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hir, parser, type_checker};
+
+    use super::*;
+
+    /// Returns the variables of the block of a function, with their last
+    /// uses.
+    fn block(code: &str) -> (Vec<DefId>, HashMap<DefId, usize>) {
+        let (_, program) = parser::parse_input(code).unwrap();
+        let program = type_checker::type_program(&program);
+
+        match program.functions()[0].body() {
+            hir::ExprKind::Bindings(b) => {
+                let defs = b.defines().iter().flat_map(|b| b.defs()).copied();
+                (defs.collect(), last_uses(b))
+            }
+            e => panic!("Expected bindings, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn last_read_is_found() {
+        let (defs, last_uses) =
+            block("fn f() { let a = 1; let b = a; let c = a + b; let d = 4; c }");
+
+        assert_eq!(last_uses.get(&defs[0]), Some(&2));
+        assert_eq!(last_uses.get(&defs[1]), Some(&2));
+        assert_eq!(last_uses.get(&defs[2]), Some(&4));
+        assert_eq!(last_uses.get(&defs[3]), None);
+    }
+
+    #[test]
+    fn shadowed_variable_dies_first() {
+        let (defs, last_uses) = block("fn f() { let a = 1; let a = a; let b = 2; a }");
+
+        assert_eq!(last_uses.get(&defs[0]), Some(&1));
+        assert_eq!(last_uses.get(&defs[1]), Some(&3));
+    }
+
+    #[test]
+    fn nested_code_uses_variables() {
+        let (defs, last_uses) = block(
+            "fn f() { let a = 1; let b = 2; let mut c = 3; \
+             let g = |x| x + a; let h = if true { b } else { 0 }; { c = 1; } }",
+        );
+
+        assert_eq!(last_uses.get(&defs[0]), Some(&3));
+        assert_eq!(last_uses.get(&defs[1]), Some(&4));
+        assert_eq!(last_uses.get(&defs[2]), Some(&5));
+    }
+}
@@ -1,134 +1,438 @@
+use dyl_bytecode::{constant_pool::Constant, operations::ArithmeticOp};
+
 use crate::{
-    ast::{
-        Addition, Binding, Bindings, Bool, ExprKind, Function, Ident, If, Integer, Multiplication,
-        Program, Subtraction,
+    ast::{BindingPattern, LogicalOperator, Pattern, Span},
+    builtins::Builtin,
+    code_builder::CodeBuilder,
+    const_eval::{self, ConstValue},
+    context::{
+        CompilerPassError, ConstDeclarationError, EnumDeclarationError, Frame, LoweringContext,
+        PendingClosure, StructDeclarationError, VariantResolutionError,
+    },
+    diagnostic::{Code, Diagnostic},
+    hir::{
+        Addition, Array, Assignment, Binding, Bindings, Bool, Cast, Closure, Comparison, DefId,
+        Exponentiation, ExprKind, Field, FnCall, Function, Ident, If, Index, IndexAssignment,
+        Integer, Logical, Match, MatchArm, Multiplication, Program, Return, Statements, Str,
+        StructLiteral, Subtraction, Tuple, VariantLiteral,
     },
-    context::{CompilerPassError, LoweringContext},
     instruction::Instruction,
+    ir,
+    lint::Lint,
+    liveness, patterns,
 };
 
-pub(crate) fn lower_ast(
-    ast: &Program,
+/// Lowers the typed tree of a checked program to the IR, whose
+/// instructions target labels. The IR becomes bytecode once laid out, and
+/// once its labels are resolved.
+pub(crate) fn lower_program(
+    program: &Program,
     mut ctxt: LoweringContext,
-) -> Result<(LoweringContext, Vec<Instruction>), CompilerPassError> {
-    let mut tmp = Vec::new();
+) -> Result<(LoweringContext, ir::Program), CompilerPassError> {
+    let mut tmp = CodeBuilder::default();
 
-    let lowering_rslt = ast.lower(&mut tmp, &mut ctxt).map(|()| tmp);
+    let lowering_rslt = program.lower(&mut tmp, &mut ctxt).map(|()| {
+        let instructions = tmp.into_instructions();
+        let locations = ctxt.take_locations(instructions.len());
+
+        ir::Program::from_located_instructions(instructions, locations, ctxt.labels())
+    });
 
     ctxt.wrap_result(lowering_rslt)
 }
 
 trait Lowerable {
-    fn lower(&self, collector: &mut Vec<Instruction>, ctxt: &mut LoweringContext)
-        -> LoweringResult;
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult;
 }
 
 type LoweringResult = Result<(), ()>;
 
 impl Lowerable for Program {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
-        let main_fn_data = self
-            .functions()
-            .iter()
-            .enumerate()
-            .find(|(_, f)| f.name() == "main");
-
-        if main_fn_data.is_none() {
-            ctxt.errors().add("No `main` function found");
-        }
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let declarations = declare_functions(self, ctxt)
+            .and(declare_structs(self, ctxt))
+            .and(declare_enums(self, ctxt))
+            .and(declare_consts(self, ctxt));
 
-        // We want to lower the main function first, so that the instructions
-        // to be executed start at offset 0.
-        //
-        // In the future, we may prefer starting with a `push_i 0; call main;`
-        // directive at the beginning of the bytecode. This should be done next.
+        let main_rslt = lower_startup_stub(self, collector, ctxt);
 
         // We don't perform early return because we want to catch as much
         // lowering errors as possible.
-        let main_fn_lowering = main_fn_data
-            .ok_or(())
-            .and_then(|(_, node)| node.lower(collector, ctxt));
-
-        if main_fn_lowering.is_ok() {
-            // We must remove the final `pop_copy` and `ret` instruction, as
-            // the main function does not return the way other functions do.
-            collector.truncate(collector.len() - 2);
-
-            // For the same reason, we must add the full stop instruction.
-            let full_stop = Instruction::f_stop();
-            collector.push(full_stop);
-        }
-
-        let idx_to_avoid = main_fn_data.map(|(idx, _)| idx);
-
         let rslt = self
             .functions()
             .iter()
-            .enumerate()
-            .filter_map(|(idx, function)| {
-                if Some(idx) != idx_to_avoid {
-                    Some(function)
-                } else {
-                    None
-                }
-            })
             .map(|function| function.lower(collector, ctxt))
             .fold(Ok(()), Result::and)
-            .and(main_fn_lowering);
+            .and(main_rslt)
+            .and(declarations);
 
-        rslt
+        // Closures are created while lowering functions, so their bodies can
+        // only be lowered once every function has been.
+        let closures_rslt = lower_pending_closures(collector, ctxt);
+
+        rslt.and(closures_rslt)
+    }
+}
+
+/// Declares every function of the program before any function body is
+/// lowered.
+///
+/// Each function gets a label whose position is only known once its body
+/// is lowered. Calls refer to that label, and are backpatched with the
+/// actual function address during label resolution. This allows a function
+/// to call itself, or any function defined after it, which is required for
+/// recursive and mutually recursive functions.
+fn declare_functions(program: &Program, ctxt: &mut LoweringContext) -> LoweringResult {
+    program
+        .functions()
+        .iter()
+        .map(|f| {
+            ctxt.declare_function(f.name().to_owned(), f.args().len())
+                .map_err(|_| {
+                    ctxt.errors().add(
+                        Diagnostic::error(format!(
+                            "Function `{}` is defined multiple times",
+                            f.name()
+                        ))
+                        .with_code(Code::DuplicateName)
+                        .with_span(f.span().clone()),
+                    )
+                })
+        })
+        .fold(Ok(()), Result::and)
+}
+
+/// Fills the struct declarations table, so that struct literals can be
+/// lowered no matter where the struct is defined.
+fn declare_structs(program: &Program, ctxt: &mut LoweringContext) -> LoweringResult {
+    program
+        .structs()
+        .iter()
+        .map(|s| {
+            ctxt.declare_struct(s.name().to_owned(), s.fields().to_vec())
+                .map_err(|e| match e {
+                    StructDeclarationError::AlreadyDefined => ctxt.errors().add(
+                        Diagnostic::error(format!(
+                            "Struct `{}` is defined multiple times",
+                            s.name()
+                        ))
+                        .with_code(Code::DuplicateName)
+                        .with_span(s.span().clone()),
+                    ),
+                    StructDeclarationError::DuplicateField(field) => ctxt.errors().add(
+                        Diagnostic::error(format!(
+                            "Field `{}` is declared multiple times in struct `{}`",
+                            field,
+                            s.name()
+                        ))
+                        .with_code(Code::DuplicateName)
+                        .with_span(s.span().clone()),
+                    ),
+                })
+        })
+        .fold(Ok(()), Result::and)
+}
+
+/// Fills the enum declarations table, so that variants can be constructed no
+/// matter where the enum is defined.
+fn declare_enums(program: &Program, ctxt: &mut LoweringContext) -> LoweringResult {
+    program
+        .enums()
+        .iter()
+        .map(|e| {
+            let variants = e
+                .variants()
+                .iter()
+                .map(|v| (v.name().to_owned(), v.fields().len()))
+                .collect();
+
+            ctxt.declare_enum(e.name().to_owned(), variants)
+                .map_err(|err| match err {
+                    EnumDeclarationError::AlreadyDefined => ctxt.errors().add(
+                        Diagnostic::error(format!("Enum `{}` is defined multiple times", e.name()))
+                            .with_code(Code::DuplicateName)
+                            .with_span(e.span().clone()),
+                    ),
+                    EnumDeclarationError::DuplicateVariant(variant) => ctxt.errors().add(
+                        Diagnostic::error(format!(
+                            "Variant `{}` is declared multiple times in enum `{}`",
+                            variant,
+                            e.name()
+                        ))
+                        .with_code(Code::DuplicateName)
+                        .with_span(e.span().clone()),
+                    ),
+                })
+        })
+        .fold(Ok(()), Result::and)
+}
+
+/// Evaluates the constants of the program, so that their value can be
+/// inlined where they are used.
+fn declare_consts(program: &Program, ctxt: &mut LoweringContext) -> LoweringResult {
+    let values = const_eval::evaluate_consts(program.consts(), ctxt.errors());
+
+    // The values come in the order the constants are defined in.
+    values
+        .into_iter()
+        .zip(program.consts())
+        .map(|((name, value), const_)| {
+            let evaluation = value.as_ref().map(drop).map_err(drop);
+
+            ctxt.declare_const(name.clone(), value)
+                .map_err(|err| match err {
+                    ConstDeclarationError::AlreadyDefined => ctxt.errors().add(
+                        Diagnostic::error(format!("Constant `{}` is defined multiple times", name))
+                            .with_code(Code::DuplicateName)
+                            .with_span(const_.span().clone()),
+                    ),
+                })
+                .and(evaluation)
+        })
+        .fold(Ok(()), Result::and)
+}
+
+/// Lowers the instructions the program starts with.
+///
+/// The `main` function is called like any other function, and the value it
+/// returns is handed to the full stop instruction. It is then the only value
+/// of the stack.
+fn lower_startup_stub(
+    program: &Program,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let main = match program.functions().iter().find(|f| f.name() == "main") {
+        Some(main) => main,
+        None => {
+            ctxt.errors()
+                .add(Diagnostic::error("No `main` function found").with_code(Code::InvalidMain));
+            return Err(());
+        }
+    };
+
+    if !main.args().is_empty() {
+        ctxt.errors().add(
+            Diagnostic::error("The `main` function must not take any argument")
+                .with_code(Code::InvalidMain)
+                .with_span(main.span().clone()),
+        );
+        return Err(());
     }
+
+    let signature = ctxt
+        .functions()
+        .resolve(main.name())
+        .expect("Functions are declared before the startup stub is lowered");
+
+    let start = collector.position();
+    collector.extend([
+        Instruction::res_v(1),
+        Instruction::call(signature.label()),
+        Instruction::f_stop(),
+    ]);
+    ctxt.locate(start, collector.position(), main.span());
+
+    Ok(())
 }
 
 impl Lowerable for Function {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
-        ctxt.labels_mut()
-            .new_named(self.name().to_string(), collector.len() as u32);
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let start = collector.position();
+
+        ctxt.labels_mut().new_named(self.name().to_string(), start);
 
-        self.body().lower(collector, ctxt)?;
+        if let Some(signature) = ctxt.functions().resolve(self.name()) {
+            // If the function is defined multiple times, then its label has
+            // already been placed by the first definition. The error has been
+            // reported when declaring the function.
+            let _ = ctxt.labels_mut().set_position(signature.label(), start);
+        }
+
+        let rslt = lower_function_body(self.args(), self.body(), collector, ctxt);
+        ctxt.locate(start, collector.position(), self.span());
+
+        rslt
+    }
+}
 
-        let rslt_copy_instr = Instruction::pop_copy(1);
-        let return_instr = Instruction::ret();
+/// Lowers the body of a function, and the sequence that returns its value.
+fn lower_function_body(
+    args: &[String],
+    body: &ExprKind,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    // When a function is called, the caller reserves a slot for the
+    // return value, pushes every argument from left to right, and then
+    // the return address.
+    let subcontext = ctxt.stack().new_subcontext();
+    let frame = Frame::new(subcontext, args.len() as u16);
+    ctxt.set_frame(frame);
 
-        collector.extend([rslt_copy_instr, return_instr]);
+    ctxt.stack_mut().push_anonymous();
+    args.iter()
+        .for_each(|arg| ctxt.stack_mut().push_named(arg.clone()));
+    ctxt.stack_mut().push_anonymous();
 
-        Ok(())
+    let body_exp = body.lower(collector, ctxt);
+
+    lower_return_sequence(frame, collector, ctxt);
+
+    ctxt.stack_mut().drop_subcontext(subcontext);
+
+    body_exp
+}
+
+/// Lowers the body of every closure created so far, including the ones
+/// created in the body of another closure.
+fn lower_pending_closures(
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let mut rslt = Ok(());
+
+    while let Some(pending) = ctxt.next_pending_closure() {
+        rslt = rslt.and(lower_closure_body(&pending, collector, ctxt));
+    }
+
+    rslt
+}
+
+/// Lowers the code executed when a closure is called.
+///
+/// The captured values are pushed before the arguments, so that the body of
+/// a closure is lowered like a function whose first arguments are the
+/// captured variables.
+fn lower_closure_body(
+    pending: &PendingClosure,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let start = collector.position();
+    collector.place(pending.label(), ctxt.labels_mut());
+
+    let closure = pending.closure();
+    let args = pending
+        .captures()
+        .iter()
+        .chain(closure.params())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let rslt = lower_function_body(&args, closure.body(), collector, ctxt);
+    ctxt.locate(start, collector.position(), closure.span());
+
+    rslt
+}
+
+/// Emits the instructions that leave the function of `frame`, returning the
+/// value that lies on top of the stack.
+///
+/// Every value that has been pushed since the beginning of the frame is
+/// dropped, no matter how many blocks enclose the current position.
+fn lower_return_sequence(frame: Frame, collector: &mut CodeBuilder, ctxt: &LoweringContext) {
+    let depth = ctxt.stack().depth();
+
+    // The return value slot is located at the frame base, below the
+    // arguments and the return address. Once the value has been copied
+    // there, everything above it can be removed.
+    let above_slot = (depth - frame.base() - 1) as u16;
+    let above_return_address = above_slot - frame.arity() - 2;
+
+    lower_frame_check(collector, ctxt);
+    collector.extend([
+        Instruction::pop_copy(above_slot),
+        Instruction::ret(above_return_address, above_slot - 1),
+    ]);
+}
+
+/// Emits, if debug checks are enabled, the instruction that checks that the
+/// return address of the current function is where the variables of the
+/// function are expected to start.
+fn lower_frame_check(collector: &mut CodeBuilder, ctxt: &LoweringContext) {
+    let frame = match ctxt.frame() {
+        Some(frame) if ctxt.has_debug_checks() => frame,
+        _ => return,
+    };
+
+    let return_address = frame.base() + frame.arity() as usize + 1;
+    let offset = ctxt.stack().depth() - 1 - return_address;
+
+    collector.push(Instruction::check_frame(offset as u16));
+}
+
+/// Returns the index, relative to the frame of the current function, of the
+/// slot `offset` slots below the top of the stack. Slots are counted from the
+/// first one above the return address: the arguments are at negative indices.
+fn frame_slot(offset: u16, ctxt: &LoweringContext) -> Option<i16> {
+    let frame = ctxt.frame()?;
+
+    let slot = ctxt.stack().depth() - 1 - offset as usize;
+    let first_local = frame.base() + frame.arity() as usize + 2;
+
+    Some((slot as isize - first_local as isize) as i16)
+}
+
+/// Emits the instruction that pushes a copy of the variable `offset` slots
+/// below the top of the stack. Within a function, the variable is read
+/// relative to the frame, which does not depend on what is pushed above it.
+fn lower_variable_read(offset: u16, collector: &mut CodeBuilder, ctxt: &LoweringContext) {
+    match frame_slot(offset, ctxt) {
+        Some(idx) => collector.push(Instruction::load_local(idx)),
+        None => collector.push(Instruction::push_copy(offset)),
+    }
+}
+
+/// Emits, if debug checks are enabled, the instruction that checks that an
+/// operation on the two integers on top of the stack does not overflow.
+fn lower_overflow_check(op: ArithmeticOp, collector: &mut CodeBuilder, ctxt: &LoweringContext) {
+    if ctxt.has_debug_checks() {
+        collector.push(Instruction::check_overflow(op));
     }
 }
 
 impl Lowerable for ExprKind {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
-        match self {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let start = collector.position();
+
+        let rslt = match self {
             ExprKind::Addition(e) => e.lower(collector, ctxt),
             ExprKind::Integer(e) => e.lower(collector, ctxt),
             ExprKind::Subtraction(e) => e.lower(collector, ctxt),
             ExprKind::If(e) => e.lower(collector, ctxt),
             ExprKind::Multiplication(e) => e.lower(collector, ctxt),
+            ExprKind::Exponentiation(e) => e.lower(collector, ctxt),
+            ExprKind::Comparison(e) => e.lower(collector, ctxt),
+            ExprKind::Logical(e) => e.lower(collector, ctxt),
+            ExprKind::Cast(c) => c.lower(collector, ctxt),
             ExprKind::Bindings(e) => e.lower(collector, ctxt),
+            ExprKind::Statements(e) => e.lower(collector, ctxt),
             ExprKind::Ident(e) => e.lower(collector, ctxt),
             ExprKind::Bool(e) => e.lower(collector, ctxt),
-        }
+            ExprKind::String(e) => e.lower(collector, ctxt),
+            ExprKind::FnCall(e) => e.lower(collector, ctxt),
+            ExprKind::Return(e) => e.lower(collector, ctxt),
+            ExprKind::Assignment(e) => e.lower(collector, ctxt),
+            ExprKind::Tuple(e) => e.lower(collector, ctxt),
+            ExprKind::Array(e) => e.lower(collector, ctxt),
+            ExprKind::Index(e) => e.lower(collector, ctxt),
+            ExprKind::IndexAssignment(e) => e.lower(collector, ctxt),
+            ExprKind::StructLiteral(e) => e.lower(collector, ctxt),
+            ExprKind::VariantLiteral(e) => e.lower(collector, ctxt),
+            ExprKind::Match(e) => e.lower(collector, ctxt),
+            ExprKind::Field(e) => e.lower(collector, ctxt),
+            ExprKind::Closure(e) => e.lower(collector, ctxt),
+        };
+
+        ctxt.locate(start, collector.position(), self.span());
+
+        rslt
     }
 }
 
 impl Lowerable for Integer {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let instr = Instruction::push_i(self.value());
         collector.push(instr);
         ctxt.stack_mut().push_anonymous();
@@ -138,14 +442,11 @@ impl Lowerable for Integer {
 }
 
 impl Lowerable for Addition {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let left_exp = self.left().lower(collector, ctxt);
         let right_exp = self.right().lower(collector, ctxt);
 
+        lower_overflow_check(ArithmeticOp::Add, collector, ctxt);
         let instr = Instruction::add_i();
         collector.push(instr);
         ctxt.stack_mut().pop_top_anonymous().unwrap();
@@ -155,17 +456,14 @@ impl Lowerable for Addition {
 }
 
 impl Lowerable for Subtraction {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let left_exp = self.left().lower(collector, ctxt);
         let right_exp = self.right().lower(collector, ctxt);
 
         let instructions = [Instruction::neg(), Instruction::add_i()];
 
-        collector.extend_from_slice(&instructions);
+        lower_overflow_check(ArithmeticOp::Sub, collector, ctxt);
+        collector.extend(instructions);
         ctxt.stack_mut().pop_top_anonymous().unwrap();
 
         left_exp.and(right_exp)
@@ -173,13 +471,10 @@ impl Lowerable for Subtraction {
 }
 
 impl Lowerable for Multiplication {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let left_exp = self.left().lower(collector, ctxt);
         let right_exp = self.right().lower(collector, ctxt);
+        lower_overflow_check(ArithmeticOp::Mul, collector, ctxt);
         collector.push(Instruction::mul());
         ctxt.stack_mut().pop_top_anonymous().unwrap();
 
@@ -187,28 +482,91 @@ impl Lowerable for Multiplication {
     }
 }
 
+impl Lowerable for Cast {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        // Booleans are already represented by 0 and 1 at runtime, so the
+        // value needs no conversion.
+        self.value().lower(collector, ctxt)
+    }
+}
+
+impl Lowerable for Exponentiation {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let left_exp = self.left().lower(collector, ctxt);
+        let right_exp = self.right().lower(collector, ctxt);
+        lower_overflow_check(ArithmeticOp::Pow, collector, ctxt);
+        collector.push(Instruction::pow());
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+        left_exp.and(right_exp)
+    }
+}
+
+impl Lowerable for Comparison {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let left_exp = self.left().lower(collector, ctxt);
+        let right_exp = self.right().lower(collector, ctxt);
+
+        collector.push(Instruction::comparison(self.operator()));
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+        left_exp.and(right_exp)
+    }
+}
+
+impl Lowerable for Logical {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let left_exp = self.left().lower(collector, ctxt);
+
+        let evaluate_right = ctxt.labels_mut().new_anonymous();
+        let end = ctxt.labels_mut().new_anonymous();
+
+        // When the left operand determines the result, the result is the
+        // left operand itself.
+        let (cond, short_circuit_value) = match self.operator() {
+            LogicalOperator::And => (Instruction::jmp_if_not_zero(evaluate_right), 0),
+            LogicalOperator::Or => (Instruction::jmp_if_zero(evaluate_right), 1),
+        };
+
+        collector.push(cond);
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+        collector.extend([
+            Instruction::push_i(short_circuit_value),
+            Instruction::goto(end),
+        ]);
+
+        collector.place(evaluate_right, ctxt.labels_mut());
+        let right_exp = self.right().lower(collector, ctxt);
+
+        collector.place(end, ctxt.labels_mut());
+
+        left_exp.and(right_exp)
+    }
+}
+
 impl Lowerable for If {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        if let Some(condition) = constant_condition(self.condition(), ctxt) {
+            return lower_constant_if(self, condition, collector, ctxt);
+        }
+
+        let alternative = match self.alternative() {
+            Some(alternative) => alternative,
+            None => return lower_if_without_else(self, collector, ctxt),
+        };
+
         let condition_exp = self.condition().lower(collector, ctxt);
 
-        let consequent_start = ctxt.labels_mut().new_anonymous();
         let alt_start = ctxt.labels_mut().new_anonymous();
         let consequent_end = ctxt.labels_mut().new_anonymous();
 
-        let cond = Instruction::cond_jmp(consequent_start, alt_start, consequent_start);
+        let cond = Instruction::jmp_if_zero(alt_start);
         let goto_end = Instruction::goto(consequent_end);
 
         collector.push(cond);
         ctxt.stack_mut().pop_top_anonymous().unwrap();
 
-        ctxt.labels_mut()
-            .set_position(consequent_start, collector.len() as u32)
-            .unwrap();
-
         let branches_subcontext = ctxt.stack().new_subcontext();
 
         let consequent_exp = self.consequent().lower(collector, ctxt);
@@ -217,39 +575,117 @@ impl Lowerable for If {
 
         ctxt.stack_mut().drop_subcontext(branches_subcontext);
 
-        ctxt.labels_mut()
-            .set_position(alt_start, collector.len() as u32)
-            .unwrap();
+        collector.place(alt_start, ctxt.labels_mut());
 
-        let alternative_exp = self.alternative().lower(collector, ctxt);
+        let alternative_exp = alternative.lower(collector, ctxt);
 
         ctxt.stack_mut().drop_subcontext(branches_subcontext);
         ctxt.stack_mut().push_anonymous();
 
-        ctxt.labels_mut()
-            .set_position(consequent_end, collector.len() as u32)
-            .unwrap();
+        collector.place(consequent_end, ctxt.labels_mut());
 
         condition_exp.and(consequent_exp).and(alternative_exp)
     }
 }
 
+/// Returns the value of a condition that is known at compile time: a boolean
+/// literal, or a boolean constant that no local variable hides.
+fn constant_condition(condition: &ExprKind, ctxt: &LoweringContext) -> Option<bool> {
+    match condition {
+        ExprKind::Bool(b) => Some(b.value()),
+        ExprKind::Ident(ident) if ctxt.stack().resolve(ident.name()).is_none() => {
+            match ctxt.consts().resolve(ident.name()) {
+                Some(Ok(ConstValue::Bool(value))) => Some(*value),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Lowers an `if` whose condition is known at compile time: only the branch
+/// that is taken is lowered, and the other one is reported.
+fn lower_constant_if(
+    if_: &If,
+    condition: bool,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    ctxt.warnings().add(
+        Diagnostic::warning(format!("Condition is always {}", condition))
+            .with_lint(Lint::CONSTANT_CONDITION)
+            .with_span(if_.condition().span().clone()),
+    );
+
+    let branch = match (condition, if_.alternative()) {
+        (true, _) => if_.consequent(),
+        (false, Some(alternative)) => alternative,
+        (false, None) => {
+            collector.push(Instruction::pack(0));
+            ctxt.stack_mut().push_anonymous();
+            return Ok(());
+        }
+    };
+
+    let branch_subcontext = ctxt.stack().new_subcontext();
+    let branch_exp = branch.lower(collector, ctxt);
+
+    // Without an `else` branch, the value of the consequent is discarded.
+    if if_.alternative().is_none() {
+        collector.extend([Instruction::pop(1), Instruction::pack(0)]);
+    }
+
+    ctxt.stack_mut().drop_subcontext(branch_subcontext);
+    ctxt.stack_mut().push_anonymous();
+
+    branch_exp
+}
+
+/// Lowers an `if` that has no `else` branch.
+///
+/// The value of the consequent is discarded, and both paths push the unit
+/// value (an empty tuple), so that the stack has the same shape afterwards.
+fn lower_if_without_else(
+    if_: &If,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let condition_exp = if_.condition().lower(collector, ctxt);
+
+    let consequent_end = ctxt.labels_mut().new_anonymous();
+
+    collector.push(Instruction::jmp_if_zero(consequent_end));
+    ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+    let consequent_subcontext = ctxt.stack().new_subcontext();
+    let consequent_exp = if_.consequent().lower(collector, ctxt);
+    collector.push(Instruction::pop(1));
+    ctxt.stack_mut().drop_subcontext(consequent_subcontext);
+
+    collector.place(consequent_end, ctxt.labels_mut());
+
+    collector.push(Instruction::pack(0));
+    ctxt.stack_mut().push_anonymous();
+
+    condition_exp.and(consequent_exp)
+}
+
 impl Lowerable for Bindings {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let subcontext_id = ctxt.stack().new_subcontext();
-        let defines_exp = self
-            .defines()
-            .iter()
-            .map(|b| b.lower(collector, ctxt))
-            .fold(Ok(()), Result::and);
+        let defines_exp = if ctxt.reuses_slots() {
+            lower_reusing_slots(self, collector, ctxt)
+        } else {
+            self.defines()
+                .iter()
+                .map(|b| b.lower(collector, ctxt))
+                .fold(Ok(()), Result::and)
+        };
 
         let ending_exp = self.ending_expression().lower(collector, ctxt);
 
-        let len = self.defines().len() as u16;
+        // Destructuring bindings take more than one slot.
+        let len = (ctxt.stack().depth() - subcontext_id - 1) as u16;
 
         collector.push(Instruction::pop_copy(len));
         collector.push(Instruction::pop(len - 1));
@@ -261,53 +697,334 @@ impl Lowerable for Bindings {
     }
 }
 
+/// Lowers the bindings of a block, each of which binds its value to the
+/// slot of a variable that is no longer used, if there is one.
+///
+/// Only the bindings of a single name take or give a slot.
+fn lower_reusing_slots(
+    bindings: &Bindings,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let last_uses = liveness::last_uses(bindings);
+
+    // The variables of the block that have a slot of their own, with the
+    // position of the slot from the bottom of the stack.
+    let mut slots: Vec<(DefId, usize)> = Vec::new();
+
+    bindings
+        .defines()
+        .iter()
+        .enumerate()
+        .map(|(idx, binding)| {
+            let (name, def) = match (binding.pattern(), binding.defs()) {
+                (BindingPattern::Name(name), [def]) => (name, *def),
+                _ => return binding.lower(collector, ctxt),
+            };
+
+            let value_exp = binding.value().lower(collector, ctxt);
+
+            // A slot below the variable this one shadows would be hidden by
+            // it.
+            let depth = ctxt.stack().depth();
+            let lowest = ctxt
+                .stack()
+                .resolve(name)
+                .map_or(0, |shadowed| depth - 1 - shadowed as usize);
+
+            let dead = slots.iter().position(|(def, slot)| {
+                *slot >= lowest && last_uses.get(def).is_none_or(|last| *last <= idx)
+            });
+
+            let pattern_exp = bind_pattern(binding.pattern(), binding, collector, ctxt);
+
+            match dead {
+                Some(dead) => {
+                    let (_, slot) = slots.remove(dead);
+                    let offset = (depth - 1 - slot) as u16;
+
+                    collector.push(Instruction::pop_copy(offset));
+                    ctxt.stack_mut().move_top(offset);
+                    slots.push((def, slot));
+                }
+                None => slots.push((def, depth - 1)),
+            }
+
+            value_exp.and(pattern_exp)
+        })
+        .fold(Ok(()), Result::and)
+}
+
+impl Lowerable for Statements {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let statements_exp = self
+            .statements()
+            .iter()
+            .map(|statement| {
+                let exp = statement.lower(collector, ctxt);
+
+                // The value of a statement is never used.
+                collector.push(Instruction::pop(1));
+                ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+                exp
+            })
+            .fold(Ok(()), Result::and);
+
+        let ending_exp = self.ending_expression().lower(collector, ctxt);
+
+        statements_exp.and(ending_exp)
+    }
+}
+
 impl Lowerable for Binding {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let value_exp = self.value().lower(collector, ctxt);
-        ctxt.stack_mut()
-            .name_top_anonymous(self.name().to_owned())
-            .unwrap();
+        let pattern_exp = bind_pattern(self.pattern(), self, collector, ctxt);
+
+        value_exp.and(pattern_exp)
+    }
+}
+
+/// Binds the value on top of the stack to a pattern.
+///
+/// A name is given to the slot of the value itself. Destructuring patterns
+/// leave this slot anonymous, and copy each part of the value they bind
+/// into a slot of its own.
+fn bind_pattern(
+    pattern: &BindingPattern,
+    binding: &Binding,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let fields = match pattern {
+        BindingPattern::Name(name) => {
+            check_shadowing(name, binding, ctxt);
+
+            if binding.is_mutable() {
+                ctxt.stack_mut()
+                    .name_top_anonymous_mut(name.clone())
+                    .unwrap();
+            } else {
+                ctxt.stack_mut().name_top_anonymous(name.clone()).unwrap();
+            }
+
+            ctxt.stack_mut().set_top_span(binding.span().clone());
+
+            return Ok(());
+        }
+
+        BindingPattern::Wildcard => return Ok(()),
+
+        BindingPattern::Tuple(elements) => elements.iter().enumerate().collect::<Vec<_>>(),
+
+        BindingPattern::Struct { name, fields } => {
+            match struct_pattern_fields(name, fields, ctxt) {
+                Ok(fields) => fields,
+                Err(e) => {
+                    ctxt.errors().add(e.with_span(binding.span().clone()));
+                    return Err(());
+                }
+            }
+        }
+    };
+
+    let value_slot = ctxt.stack().depth() - 1;
+
+    fields
+        .into_iter()
+        .filter(|(_, field)| **field != BindingPattern::Wildcard)
+        .map(|(idx, field)| {
+            let offset = ctxt.stack().depth() - 1 - value_slot;
+
+            collector.extend([
+                Instruction::push_copy(offset as u16),
+                Instruction::field(idx as u16),
+            ]);
+            ctxt.stack_mut().push_anonymous();
+
+            bind_pattern(field, binding, collector, ctxt)
+        })
+        .fold(Ok(()), Result::and)
+}
+
+/// Returns the index of each field a struct pattern binds.
+fn struct_pattern_fields<'a>(
+    name: &str,
+    fields: &'a [(String, BindingPattern)],
+    ctxt: &LoweringContext,
+) -> Result<Vec<(usize, &'a BindingPattern)>, Diagnostic> {
+    let declared_fields = ctxt.structs().resolve(name).ok_or_else(|| {
+        Diagnostic::error(format!("Undefined struct `{}`", name)).with_code(Code::UndefinedName)
+    })?;
+
+    let names = fields.iter().map(|(f, _)| f.as_str()).collect::<Vec<_>>();
+    check_struct_fields(name, &names, declared_fields, "pattern")?;
+
+    Ok(fields
+        .iter()
+        .map(|(f, pattern)| {
+            let idx = declared_fields.iter().position(|d| d == f).unwrap();
+            (idx, pattern)
+        })
+        .collect())
+}
+
+/// Warns if a binding hides a variable that is still in scope.
+///
+/// Shadowing is allowed: the new binding gets its own stack slot, and the
+/// previous variable is simply no longer reachable by its name.
+fn check_shadowing(name: &str, binding: &Binding, ctxt: &LoweringContext) {
+    if ctxt.stack().resolve(name).is_none() {
+        return;
+    }
+
+    let warning = Diagnostic::warning(format!("Binding `{}` shadows a previous variable", name))
+        .with_lint(Lint::SHADOWING)
+        .with_span(binding.span().clone());
+
+    let warning = match ctxt.stack().resolve_span(name) {
+        Some(previous) => warning.with_secondary(previous, "previous binding"),
+        None => warning.with_note("the previous variable is a function argument"),
+    };
+
+    ctxt.warnings().add(warning);
+}
+
+impl Lowerable for Assignment {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let value_exp = self.value().lower(collector, ctxt);
+
+        let stack_offset = match ctxt.stack().resolve(self.name()) {
+            Some(offset) => offset,
+            None if ctxt.consts().resolve(self.name()).is_some() => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!("Cannot assign to constant `{}`", self.name()))
+                        .with_code(Code::ImmutableAssignment)
+                        .with_span(self.span().clone()),
+                );
+
+                return Err(());
+            }
+            None => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!("Undefined variable `{}`", self.name()))
+                        .with_code(Code::UndefinedName)
+                        .with_span(self.span().clone()),
+                );
+
+                return Err(());
+            }
+        };
+
+        if ctxt.stack().is_mutable(self.name()) != Some(true) {
+            ctxt.errors().add(
+                Diagnostic::error(format!(
+                    "Cannot assign to immutable variable `{}`",
+                    self.name()
+                ))
+                .with_code(Code::ImmutableAssignment)
+                .with_span(self.span().clone()),
+            );
+
+            return Err(());
+        }
+
+        // The value is duplicated so that the assignment itself evaluates to
+        // the assigned value. The copy is then moved to the variable slot,
+        // which lies one slot further once the copy has been pushed.
+        let store = match frame_slot(stack_offset, ctxt) {
+            Some(idx) => Instruction::store_local(idx),
+            None => Instruction::pop_copy(stack_offset + 1),
+        };
+        collector.extend([Instruction::push_copy(0), store]);
 
         value_exp
     }
 }
 
 impl Lowerable for Ident {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let stack_offset = match ctxt.stack().resolve(self.name()) {
             Some(offset) => offset,
             None => {
-                ctxt.errors()
-                    .add(format!("Undefined variable `{}`", self.name()));
-
                 ctxt.stack_mut().push_anonymous();
 
-                return Err(());
+                // Local variables take precedence over constants.
+                return match ctxt.consts().resolve(self.name()) {
+                    Some(Ok(value)) => {
+                        let instruction = lower_const_value(&value.clone(), ctxt);
+                        collector.push(instruction);
+                        Ok(())
+                    }
+
+                    // The constant could not be evaluated, which has already
+                    // been reported.
+                    Some(Err(())) => Err(()),
+
+                    None => lower_function_value(self, collector, ctxt),
+                };
             }
         };
 
-        collector.push(Instruction::push_copy(stack_offset));
-
+        lower_variable_read(stack_offset, collector, ctxt);
         ctxt.stack_mut().push_anonymous();
 
         Ok(())
     }
 }
 
+/// Lowers a function used as a value.
+///
+/// The function is turned into a closure that captures nothing: calling it
+/// pushes the arguments and the return address, which is exactly the frame
+/// the function expects.
+fn lower_function_value(
+    ident: &Ident,
+    collector: &mut CodeBuilder,
+    ctxt: &LoweringContext,
+) -> LoweringResult {
+    match ctxt.functions().resolve(ident.name()) {
+        Some(signature) => {
+            let arity = signature.arity() as u16;
+            collector.push(Instruction::make_closure(signature.label(), 0, arity));
+
+            Ok(())
+        }
+
+        None => {
+            ctxt.errors().add(
+                Diagnostic::error(format!("Undefined variable `{}`", ident.name()))
+                    .with_code(Code::UndefinedName)
+                    .with_span(ident.span().clone()),
+            );
+
+            Err(())
+        }
+    }
+}
+
+fn lower_const_value(value: &ConstValue, ctxt: &mut LoweringContext) -> Instruction {
+    match value {
+        ConstValue::Int(i) => Instruction::push_i(*i),
+        ConstValue::Bool(b) => Instruction::push_i(*b as i32),
+        ConstValue::Str(s) => lower_string(s.clone(), ctxt),
+    }
+}
+
+/// Returns the instruction that pushes a string, which is stored once in the
+/// constant pool: the program has a single pool, so identical strings share
+/// an entry, wherever they are written. Once the pool is full, strings are
+/// stored in the instructions instead.
+fn lower_string(s: String, ctxt: &mut LoweringContext) -> Instruction {
+    match ctxt.constants_mut().add(Constant::String(s.clone())) {
+        Ok(idx) => Instruction::push_const(idx),
+        Err(_) => Instruction::push_s(s),
+    }
+}
+
 impl Lowerable for Bool {
-    fn lower(
-        &self,
-        collector: &mut Vec<Instruction>,
-        ctxt: &mut LoweringContext,
-    ) -> LoweringResult {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
         let num = if self.value() { 1 } else { 0 };
 
         collector.push(Instruction::push_i(num));
@@ -317,428 +1034,3381 @@ impl Lowerable for Bool {
     }
 }
 
-#[cfg(test)]
-fn lower(expr: &impl Lowerable) -> (Vec<Instruction>, LoweringContext) {
-    let mut collector = Vec::new();
-    let mut ctxt = LoweringContext::new();
+impl Lowerable for Str {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let instruction = lower_string(self.value().to_owned(), ctxt);
+        collector.push(instruction);
+        ctxt.stack_mut().push_anonymous();
 
-    if expr.lower(&mut collector, &mut ctxt).is_err() {
-        println!("{}", ctxt.errors());
-        panic!("called `Result::unwrap()` on an `Err` value: ()")
+        Ok(())
     }
-
-    (collector, ctxt)
 }
 
-#[cfg(test)]
-#[test]
-fn lowering_can_fail() {
-    let ast = ExprKind::ident("undefined".to_owned());
-    let mut collector = Vec::new();
-    let mut ctxt = LoweringContext::new();
+impl Lowerable for Tuple {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let elements_exp = self
+            .elements()
+            .iter()
+            .map(|element| element.lower(collector, ctxt))
+            .fold(Ok(()), Result::and);
 
-    assert!(ast.lower(&mut collector, &mut ctxt).is_err());
+        // Tuples are stored in a single stack slot, no matter how many
+        // elements they contain.
+        let len = self.elements().len();
+        collector.push(Instruction::pack(len as u16));
+
+        (0..len).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+        ctxt.stack_mut().push_anonymous();
+
+        elements_exp
+    }
 }
 
-#[cfg(test)]
-mod program {
-    use crate::inline_program;
+impl Lowerable for Array {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let elements_exp = self
+            .elements()
+            .iter()
+            .map(|element| element.lower(collector, ctxt))
+            .fold(Ok(()), Result::and);
 
-    use super::*;
+        // The elements are moved to the heap, only a reference to the array
+        // stays on the stack.
+        let len = self.elements().len();
+        collector.push(Instruction::new_array(len as u16));
 
-    #[test]
-    fn simplest_test() {
-        let program: Program = inline_program! { fn main() { 42 } };
-        let (instrs, _) = lower(&program);
+        (0..len).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+        ctxt.stack_mut().push_anonymous();
 
-        assert_eq!(instrs, [Instruction::push_i(42), Instruction::f_stop()]);
+        elements_exp
     }
+}
 
-    #[test]
-    fn main_is_lowered_first() {
-        let program: Program = inline_program! {
-            fn ___() { 41 }
-            fn main() { 42 }
-        };
+impl Lowerable for StructLiteral {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let declared_fields = match ctxt.structs().resolve(self.name()) {
+            Some(fields) => fields.to_vec(),
+            None => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!("Undefined struct `{}`", self.name()))
+                        .with_code(Code::UndefinedName)
+                        .with_span(self.span().clone()),
+                );
+                ctxt.stack_mut().push_anonymous();
 
+                return Err(());
+            }
+        };
+
+        let fields_are_valid = self
+            .check_fields(&declared_fields)
+            .map_err(|e| ctxt.errors().add(e.with_span(self.span().clone())));
+
+        if fields_are_valid.is_err() {
+            ctxt.stack_mut().push_anonymous();
+            return Err(());
+        }
+
+        // Fields are evaluated in declaration order, which is also the order
+        // in which they are stored.
+        let fields_exp = declared_fields
+            .iter()
+            .map(|name| {
+                let (_, value) = self.fields().iter().find(|(f, _)| f == name).unwrap();
+                value.lower(collector, ctxt)
+            })
+            .fold(Ok(()), Result::and);
+
+        let len = declared_fields.len();
+        collector.push(Instruction::pack(len as u16));
+
+        (0..len).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+        ctxt.stack_mut().push_anonymous();
+
+        fields_exp
+    }
+}
+
+impl StructLiteral {
+    fn check_fields(&self, declared_fields: &[String]) -> Result<(), Diagnostic> {
+        let names = self
+            .fields()
+            .iter()
+            .map(|(f, _)| f.as_str())
+            .collect::<Vec<_>>();
+
+        check_struct_fields(self.name(), &names, declared_fields, "initializer")
+    }
+}
+
+/// Checks that the fields written in an initializer or a pattern are the
+/// ones the struct declares, each written once.
+fn check_struct_fields(
+    struct_name: &str,
+    fields: &[&str],
+    declared_fields: &[String],
+    place: &str,
+) -> Result<(), Diagnostic> {
+    for (idx, name) in fields.iter().enumerate() {
+        if !declared_fields.iter().any(|d| d == name) {
+            return Err(Diagnostic::error(format!(
+                "Struct `{}` has no field named `{}`",
+                struct_name, name
+            ))
+            .with_code(Code::UndefinedName));
+        }
+
+        if fields[..idx].contains(name) {
+            return Err(
+                Diagnostic::error(format!("Field `{}` is specified more than once", name))
+                    .with_code(Code::DuplicateName),
+            );
+        }
+    }
+
+    let missing = declared_fields
+        .iter()
+        .filter(|name| !fields.contains(&name.as_str()))
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        return Err(Diagnostic::error(format!(
+            "Missing fields {} in {} of `{}`",
+            missing.join(", "),
+            place,
+            struct_name
+        ))
+        .with_code(Code::MissingFields));
+    }
+
+    Ok(())
+}
+
+impl Lowerable for VariantLiteral {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let arity = self.args().len();
+        let tag = match resolve_variant(self.enum_name(), self.variant(), arity, self.span(), ctxt)
+        {
+            Ok(tag) => tag,
+            Err(()) => {
+                ctxt.stack_mut().push_anonymous();
+                return Err(());
+            }
+        };
+
+        // The tag comes first, so that it can be read without knowing the
+        // variant.
+        collector.push(Instruction::push_i(tag as i32));
+        ctxt.stack_mut().push_anonymous();
+
+        let args_exp = self
+            .args()
+            .iter()
+            .map(|arg| arg.lower(collector, ctxt))
+            .fold(Ok(()), Result::and);
+
+        collector.push(Instruction::pack(arity as u16 + 1));
+
+        (0..=arity).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+        ctxt.stack_mut().push_anonymous();
+
+        args_exp
+    }
+}
+
+/// Returns the tag of a variant, after checking that it exists and has the
+/// given number of fields. Errors are reported at `span`.
+fn resolve_variant(
+    enum_name: &str,
+    variant: &str,
+    arity: usize,
+    span: &Span,
+    ctxt: &mut LoweringContext,
+) -> Result<u16, ()> {
+    let msg = match ctxt.enums().resolve_variant(enum_name, variant) {
+        Ok((tag, expected)) if expected == arity => return Ok(tag),
+
+        Ok((_, expected)) => Diagnostic::error(format!(
+            "Variant `{}::{}` takes {} field(s), but {} were supplied",
+            enum_name, variant, expected, arity
+        ))
+        .with_code(Code::WrongArgumentCount),
+        Err(VariantResolutionError::UnknownEnum) => {
+            Diagnostic::error(format!("Undefined enum `{}`", enum_name))
+                .with_code(Code::UndefinedName)
+        }
+        Err(VariantResolutionError::UnknownVariant) => Diagnostic::error(format!(
+            "Enum `{}` has no variant named `{}`",
+            enum_name, variant
+        ))
+        .with_code(Code::UndefinedName),
+    };
+
+    ctxt.errors().add(msg.with_span(span.clone()));
+    Err(())
+}
+
+impl Lowerable for Match {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let scrutinee_exp = self.scrutinee().lower(collector, ctxt);
+
+        let patterns_exp = self
+            .arms()
+            .iter()
+            .map(|arm| check_pattern(arm.pattern(), self.span(), ctxt))
+            .fold(Ok(()), Result::and);
+
+        // Exhaustiveness can only be checked on well-formed patterns. A
+        // guarded arm may not be taken, whatever its pattern.
+        let exhaustiveness_exp = patterns_exp.and_then(|()| {
+            let patterns = self
+                .arms()
+                .iter()
+                .filter(|arm| arm.guard().is_none())
+                .map(MatchArm::pattern)
+                .collect::<Vec<_>>();
+
+            patterns::check_exhaustiveness(&patterns, ctxt.enums())
+                .map_err(|e| ctxt.errors().add(e.with_span(self.span().clone())))
+        });
+
+        let end = ctxt.labels_mut().new_anonymous();
+        let arms_subcontext = ctxt.stack().new_subcontext();
+
+        let arms_exp = self
+            .arms()
+            .iter()
+            .map(|arm| {
+                let next_arm = ctxt.labels_mut().new_anonymous();
+
+                let pattern_exp =
+                    lower_pattern_test(arm.pattern(), &mut Vec::new(), next_arm, collector, ctxt);
+                let bindings = bind_pattern_names(arm.pattern(), collector, ctxt);
+                let guard_exp = match arm.guard() {
+                    Some(guard) => lower_guard(guard, bindings, next_arm, collector, ctxt),
+                    None => Ok(()),
+                };
+                let body_exp = arm.body().lower(collector, ctxt);
+
+                // The arm value replaces the scrutinee, and the bindings are
+                // dropped.
+                collector.push(Instruction::pop_copy(bindings + 1));
+                if bindings > 0 {
+                    collector.push(Instruction::pop(bindings));
+                }
+                collector.push(Instruction::goto(end));
+                ctxt.stack_mut().drop_subcontext(arms_subcontext);
+
+                collector.place(next_arm, ctxt.labels_mut());
+
+                pattern_exp.and(guard_exp).and(body_exp)
+            })
+            .fold(Ok(()), Result::and);
+
+        collector.place(end, ctxt.labels_mut());
+
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+        ctxt.stack_mut().push_anonymous();
+
+        scrutinee_exp.and(exhaustiveness_exp).and(arms_exp)
+    }
+}
+
+/// Checks a pattern of a `match`, reporting errors at `span`.
+fn check_pattern(pattern: &Pattern, span: &Span, ctxt: &mut LoweringContext) -> LoweringResult {
+    let names = pattern.names();
+    let names_exp = match names
+        .iter()
+        .enumerate()
+        .find(|(idx, name)| names[..*idx].contains(name))
+    {
+        Some((_, name)) => {
+            ctxt.errors().add(
+                Diagnostic::error(format!(
+                    "Identifier `{}` is bound more than once in the same pattern",
+                    name
+                ))
+                .with_code(Code::DuplicateName)
+                .with_span(span.clone()),
+            );
+            Err(())
+        }
+        None => Ok(()),
+    };
+
+    names_exp.and(check_pattern_variants(pattern, span, ctxt))
+}
+
+fn check_pattern_variants(
+    pattern: &Pattern,
+    span: &Span,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) | Pattern::Integer(_) => Ok(()),
+
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        } => {
+            let variant_exp =
+                resolve_variant(enum_name, variant, fields.len(), span, ctxt).map(drop);
+
+            fields
+                .iter()
+                .map(|field| check_pattern_variants(field, span, ctxt))
+                .fold(variant_exp, Result::and)
+        }
+    }
+}
+
+/// Emits the instructions that jump to `next_arm` if the guard of an arm
+/// does not hold, once the bindings of its pattern are dropped.
+fn lower_guard(
+    guard: &ExprKind,
+    bindings: u16,
+    next_arm: u32,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let guard_exp = guard.lower(collector, ctxt);
+    ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+    if bindings == 0 {
+        collector.push(Instruction::jmp_if_zero(next_arm));
+    } else {
+        let holds = ctxt.labels_mut().new_anonymous();
+
+        collector.push(Instruction::jmp_if_not_zero(holds));
+        collector.extend([Instruction::pop(bindings), Instruction::goto(next_arm)]);
+        collector.place(holds, ctxt.labels_mut());
+    }
+
+    guard_exp
+}
+
+/// Copies each part of the value on top of the stack that a pattern binds
+/// into a slot named after it. Returns the number of slots pushed.
+fn bind_pattern_names(
+    pattern: &Pattern,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> u16 {
+    let mut bindings = Vec::new();
+    collect_pattern_bindings(pattern, &mut Vec::new(), &mut bindings);
+
+    let scrutinee_slot = ctxt.stack().depth() - 1;
+
+    for (name, path) in &bindings {
+        let offset = ctxt.stack().depth() - 1 - scrutinee_slot;
+
+        collector.push(Instruction::push_copy(offset as u16));
+        collector.extend(path.iter().map(|idx| Instruction::field(*idx)));
+
+        ctxt.stack_mut().push_anonymous();
+        ctxt.stack_mut()
+            .name_top_anonymous(name.to_string())
+            .unwrap();
+    }
+
+    bindings.len() as u16
+}
+
+/// Lists the names a pattern binds, with the sequence of fields leading to
+/// the value each of them is bound to.
+fn collect_pattern_bindings<'a>(
+    pattern: &'a Pattern,
+    path: &mut Vec<u16>,
+    bindings: &mut Vec<(&'a str, Vec<u16>)>,
+) {
+    match pattern {
+        Pattern::Binding(name) => bindings.push((name, path.clone())),
+        Pattern::Wildcard | Pattern::Integer(_) => {}
+
+        // Fields are stored right after the tag.
+        Pattern::Variant { fields, .. } => {
+            for (idx, field) in fields.iter().enumerate() {
+                path.push(idx as u16 + 1);
+                collect_pattern_bindings(field, path, bindings);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// Emits the instructions that jump to `mismatch` if the value on top of the
+/// stack does not match a pattern. `path` is the sequence of fields leading
+/// from the top of the stack to the value being tested.
+///
+/// The stack is left untouched.
+fn lower_pattern_test(
+    pattern: &Pattern,
+    path: &mut Vec<u16>,
+    mismatch: u32,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) => Ok(()),
+
+        Pattern::Integer(value) => {
+            lower_equality_test(path, *value, mismatch, collector, ctxt);
+            Ok(())
+        }
+
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        } => {
+            // Invalid variants have already been reported by `check_pattern`.
+            let (tag, _) = ctxt
+                .enums()
+                .resolve_variant(enum_name, variant)
+                .map_err(drop)?;
+
+            path.push(0);
+            lower_equality_test(path, tag as i32, mismatch, collector, ctxt);
+            path.pop();
+
+            // Fields are stored right after the tag.
+            fields
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| {
+                    path.push(idx as u16 + 1);
+                    let field_exp = lower_pattern_test(field, path, mismatch, collector, ctxt);
+                    path.pop();
+
+                    field_exp
+                })
+                .fold(Ok(()), Result::and)
+        }
+    }
+}
+
+fn lower_equality_test(
+    path: &[u16],
+    value: i32,
+    mismatch: u32,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) {
+    let matched = ctxt.labels_mut().new_anonymous();
+
+    collector.push(Instruction::push_copy(0));
+    collector.extend(path.iter().copied().map(Instruction::field));
+
+    // The wrapping difference of two integers is null if and only if they are
+    // equal, even when it overflows.
+    collector.extend([
+        Instruction::push_i(value.wrapping_neg()),
+        Instruction::add_i(),
+        Instruction::cond_jmp(mismatch, matched, mismatch),
+    ]);
+
+    collector.place(matched, ctxt.labels_mut());
+}
+
+impl Lowerable for Index {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let array_exp = self.array().lower(collector, ctxt);
+        let index_exp = self.index().lower(collector, ctxt);
+
+        collector.push(Instruction::index());
+
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+
+        array_exp.and(index_exp)
+    }
+}
+
+impl Lowerable for IndexAssignment {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        // Arrays live on the heap, so modifying an element does not touch
+        // the variable slot. It is still forbidden through immutable
+        // variables, for consistency with plain assignments.
+        let mutability_exp = match root_variable(self.target().array()) {
+            Some(name) if ctxt.stack().is_mutable(name) == Some(false) => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!(
+                        "Cannot assign to an element of immutable variable `{}`",
+                        name
+                    ))
+                    .with_code(Code::ImmutableAssignment)
+                    .with_span(self.span().clone()),
+                );
+
+                Err(())
+            }
+            _ => Ok(()),
+        };
+
+        let array_exp = self.target().array().lower(collector, ctxt);
+        let index_exp = self.target().index().lower(collector, ctxt);
+        let value_exp = self.value().lower(collector, ctxt);
+
+        collector.push(Instruction::store_index());
+
+        (0..2).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+
+        mutability_exp.and(array_exp).and(index_exp).and(value_exp)
+    }
+}
+
+fn root_variable(expr: &ExprKind) -> Option<&str> {
+    match expr {
+        ExprKind::Ident(ident) => Some(ident.name()),
+        ExprKind::Index(index) => root_variable(index.array()),
+        ExprKind::Field(field) => root_variable(field.tuple()),
+        _ => None,
+    }
+}
+
+impl Lowerable for Field {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let tuple_exp = self.tuple().lower(collector, ctxt);
+
+        collector.push(Instruction::field(self.idx()));
+
+        tuple_exp
+    }
+}
+
+impl Lowerable for Closure {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        // The other names are either constants or undefined, which is
+        // handled when lowering the closure body.
+        let captures = self
+            .free_variables()
+            .iter()
+            .filter(|name| ctxt.stack().resolve(name).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Captured variables are copied: the closure does not see the
+        // changes made to them after its creation.
+        for name in &captures {
+            let offset = ctxt.stack().resolve(name).unwrap();
+
+            lower_variable_read(offset, collector, ctxt);
+            ctxt.stack_mut().push_anonymous();
+        }
+
+        captures
+            .iter()
+            .for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+
+        let captures_len = captures.len() as u16;
+        let arity = self.params().len() as u16;
+        let label = ctxt.defer_closure(captures, self.clone());
+
+        collector.push(Instruction::make_closure(label, captures_len, arity));
+        ctxt.stack_mut().push_anonymous();
+
+        Ok(())
+    }
+}
+
+/// Lowers a call to a closure stored in a variable.
+fn lower_closure_call(
+    call: &FnCall,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    collector.push(Instruction::res_v(1));
+    ctxt.stack_mut().push_anonymous();
+
+    let offset = ctxt.stack().resolve(call.name()).unwrap();
+    lower_variable_read(offset, collector, ctxt);
+    ctxt.stack_mut().push_anonymous();
+
+    let args_exp = call
+        .args()
+        .iter()
+        .map(|arg| arg.lower(collector, ctxt))
+        .fold(Ok(()), Result::and);
+
+    // The arity is checked at runtime, as the closure stored in the
+    // variable is not known yet.
+    collector.push(Instruction::call_closure(call.args().len() as u16));
+
+    // The closure and its arguments are consumed by the call.
+    (0..=call.args().len()).for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+
+    args_exp
+}
+
+fn lower_builtin_call(
+    builtin: Builtin,
+    call: &FnCall,
+    collector: &mut CodeBuilder,
+    ctxt: &mut LoweringContext,
+) -> LoweringResult {
+    let arity_exp = if call.args().len() == builtin.arity() {
+        Ok(())
+    } else {
+        ctxt.errors().add(
+            Diagnostic::error(format!(
+                "Wrong number of arguments for `{}`: expected {}, found {}",
+                builtin.name(),
+                builtin.arity(),
+                call.args().len(),
+            ))
+            .with_code(Code::WrongArgumentCount)
+            .with_span(call.span().clone()),
+        );
+        Err(())
+    };
+
+    // Arrays are modified in place, which is forbidden through immutable
+    // variables, as writing to their elements.
+    let mutability_exp = match (builtin, call.args().first().and_then(root_variable)) {
+        (Builtin::Push | Builtin::Pop, Some(name))
+            if ctxt.stack().is_mutable(name) == Some(false) =>
+        {
+            ctxt.errors().add(
+                Diagnostic::error(format!(
+                    "Cannot modify immutable variable `{}` with `{}`",
+                    name,
+                    builtin.name()
+                ))
+                .with_code(Code::ImmutableAssignment)
+                .with_span(call.span().clone()),
+            );
+
+            Err(())
+        }
+        _ => Ok(()),
+    };
+
+    let args_exp = call
+        .args()
+        .iter()
+        .map(|arg| arg.lower(collector, ctxt))
+        .fold(Ok(()), Result::and);
+
+    // The instructions consume the arguments and push the result.
+    match builtin {
+        Builtin::Print => collector.extend([Instruction::out(), Instruction::pack(0)]),
+
+        Builtin::Println => {
+            let newline = lower_string("\n".to_owned(), ctxt);
+            collector.extend([
+                Instruction::out(),
+                newline,
+                Instruction::out(),
+                Instruction::pack(0),
+            ])
+        }
+
+        Builtin::ReadInt => collector.push(Instruction::in_i()),
+
+        Builtin::Assert => {
+            let holds = ctxt.labels_mut().new_anonymous();
+            let message = format!("{}: Assertion failed", call.span());
+
+            collector.push(Instruction::jmp_if_not_zero(holds));
+            let message = lower_string(message, ctxt);
+            collector.extend([message, Instruction::trap()]);
+
+            collector.place(holds, ctxt.labels_mut());
+
+            collector.push(Instruction::pack(0));
+        }
+
+        // The execution never continues after the trap, but the stack
+        // still gets a slot for the result, as any other call.
+        Builtin::Panic => collector.push(Instruction::trap()),
+
+        Builtin::Len => collector.push(Instruction::len()),
+        Builtin::Substr => collector.push(Instruction::substr()),
+        Builtin::CharAt => collector.push(Instruction::char_at()),
+
+        Builtin::Push => collector.extend([Instruction::push_back(), Instruction::pack(0)]),
+        Builtin::Pop => collector.push(Instruction::pop_back()),
+    }
+
+    call.args()
+        .iter()
+        .for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+    ctxt.stack_mut().push_anonymous();
+
+    arity_exp.and(mutability_exp).and(args_exp)
+}
+
+impl Lowerable for FnCall {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        // Local variables take precedence over functions.
+        if ctxt.stack().resolve(self.name()).is_some() {
+            return lower_closure_call(self, collector, ctxt);
+        }
+
+        let signature = ctxt.functions().resolve(self.name());
+
+        if signature.is_none() {
+            if let Some(builtin) = Builtin::from_name(self.name()) {
+                return lower_builtin_call(builtin, self, collector, ctxt);
+            }
+        }
+
+        let signature_exp = match signature {
+            Some(signature) if signature.arity() == self.args().len() => Ok(()),
+
+            Some(signature) => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!(
+                        "Wrong number of arguments for `{}`: expected {}, found {}",
+                        self.name(),
+                        signature.arity(),
+                        self.args().len(),
+                    ))
+                    .with_code(Code::WrongArgumentCount)
+                    .with_span(self.span().clone()),
+                );
+                Err(())
+            }
+
+            None => {
+                ctxt.errors().add(
+                    Diagnostic::error(format!("Undefined function `{}`", self.name()))
+                        .with_code(Code::UndefinedName)
+                        .with_span(self.span().clone()),
+                );
+                Err(())
+            }
+        };
+
+        // The callee writes its return value in a slot that is reserved
+        // before anything else.
+        collector.push(Instruction::res_v(1));
+        ctxt.stack_mut().push_anonymous();
+
+        // Arguments are evaluated from left to right.
+        let args_exp = self
+            .args()
+            .iter()
+            .map(|arg| arg.lower(collector, ctxt))
+            .fold(Ok(()), Result::and);
+
+        if let Some(signature) = signature {
+            collector.push(Instruction::call(signature.label()));
+        }
+
+        // The callee removes the arguments from the stack when returning.
+        self.args()
+            .iter()
+            .for_each(|_| ctxt.stack_mut().pop_top_anonymous().unwrap());
+
+        signature_exp.and(args_exp)
+    }
+}
+
+impl Lowerable for Return {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let frame = match ctxt.frame() {
+            Some(frame) => frame,
+            None => {
+                ctxt.errors().add(
+                    Diagnostic::error("Found `return` outside of a function")
+                        .with_code(Code::ReturnOutsideFunction)
+                        .with_span(self.span().clone()),
+                );
+                ctxt.stack_mut().push_anonymous();
+
+                return Err(());
+            }
+        };
+
+        let value_exp = self.value().lower(collector, ctxt);
+        lower_return_sequence(frame, collector, ctxt);
+
+        // The code that follows is never executed, but the stack must be
+        // kept consistent with any other expression, which pushes a single
+        // value.
+        ctxt.stack_mut().pop_top_anonymous().unwrap();
+        ctxt.stack_mut().push_anonymous();
+
+        value_exp
+    }
+}
+
+/// Lets the tests lower syntax trees, typed without knowing anything about
+/// the names they use.
+#[cfg(test)]
+impl Lowerable for crate::ast::ExprKind {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        crate::type_checker::type_expr(self).lower(collector, ctxt)
+    }
+}
+
+#[cfg(test)]
+impl Lowerable for crate::ast::Program {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        crate::type_checker::type_program(self).lower(collector, ctxt)
+    }
+}
+
+#[cfg(test)]
+impl Lowerable for crate::ast::Function {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        let program = crate::ast::Program::new(vec![crate::ast::Item::Function(self.clone())]);
+        crate::type_checker::type_program(&program).functions()[0].lower(collector, ctxt)
+    }
+}
+
+#[cfg(test)]
+impl Lowerable for crate::ast::Binding {
+    fn lower(&self, collector: &mut CodeBuilder, ctxt: &mut LoweringContext) -> LoweringResult {
+        crate::type_checker::type_binding(self).lower(collector, ctxt)
+    }
+}
+
+#[cfg(test)]
+fn lower(expr: &impl Lowerable) -> (Vec<Instruction>, LoweringContext) {
+    let mut collector = CodeBuilder::default();
+    let mut ctxt = LoweringContext::new();
+
+    if expr.lower(&mut collector, &mut ctxt).is_err() {
+        println!("{}", ctxt.errors());
+        panic!("called `Result::unwrap()` on an `Err` value: ()")
+    }
+
+    (collector.into_instructions(), ctxt)
+}
+
+#[cfg(test)]
+#[test]
+fn lowering_can_fail() {
+    let ast = crate::ast::ExprKind::ident("undefined".to_owned());
+    let mut collector = CodeBuilder::default();
+    let mut ctxt = LoweringContext::new();
+
+    assert!(ast.lower(&mut collector, &mut ctxt).is_err());
+}
+
+#[cfg(test)]
+mod program {
+    use dyl_bytecode::Instruction as ResolvedInstruction;
+
+    use crate::{ast::Program, context, inline_program};
+
+    use super::*;
+
+    #[test]
+    fn simplest_test() {
+        let program: Program = inline_program! { fn main() { 42 } };
+        let (instrs, _) = lower(&program);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::res_v(1),
+                Instruction::call(0),
+                Instruction::f_stop(),
+                Instruction::push_i(42),
+                Instruction::pop_copy(2),
+                Instruction::ret(0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn program_starts_by_calling_main() {
+        let program: Program = inline_program! {
+            fn ___() { 41 }
+            fn main() { 42 }
+        };
+
+        let (instrs, ctxt) = lower(&program);
+
+        assert!(instrs.starts_with(&[
+            Instruction::res_v(1),
+            Instruction::call(1),
+            Instruction::f_stop(),
+        ]));
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(6));
+    }
+
+    #[test]
+    fn main_can_be_called() {
+        let program: Program = inline_program! {
+            fn main() { if 0 { main() } else { 42 } }
+        };
+
+        let (instrs, _) = lower(&program);
+
+        assert_eq!(
+            instrs
+                .iter()
+                .filter(|i| **i == Instruction::call(0))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn functions_can_be_called_before_definition() {
+        let program: Program = inline_program! {
+            fn main() { add(40, 2) }
+            fn add(a, b) { a + b }
+        };
+
+        let (instrs, ctxt) = lower(&program);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::res_v(1),
+                Instruction::call(0),
+                Instruction::f_stop(),
+                Instruction::res_v(1),
+                Instruction::push_i(40),
+                Instruction::push_i(2),
+                Instruction::call(1),
+                Instruction::pop_copy(2),
+                Instruction::ret(0, 1),
+                Instruction::load_local(-3),
+                Instruction::load_local(-2),
+                Instruction::add_i(),
+                Instruction::pop_copy(4),
+                Instruction::ret(0, 3),
+            ]
+        );
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(9));
+    }
+
+    #[test]
+    fn recursive_call_targets_function_start() {
+        let program: Program = inline_program! {
+            fn main() { count(3) }
+            fn count(n) { if n { count(n - 1) } else { 0 } }
+        };
+
+        let (instrs, ctxt) = lower(&program);
+        let count_start = 8;
+
+        assert_eq!(instrs[count_start - 1], Instruction::ret(0, 1));
+        assert_eq!(
+            instrs
+                .iter()
+                .filter(|i| **i == Instruction::call(1))
+                .count(),
+            2
+        );
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(count_start as u32));
+    }
+
+    #[test]
+    fn mutually_recursive_calls_are_backpatched() {
+        let program: Program = inline_program! {
+            fn main() { is_even(4) }
+            fn is_even(n) { if n { is_odd(n - 1) } else { true } }
+            fn is_odd(n) { if n { is_even(n - 1) } else { false } }
+        };
+
+        let (instrs, ctxt) = lower(&program);
+        let is_even_start = ctxt.labels().resolve_anonymous(1).unwrap();
+        let is_odd_start = ctxt.labels().resolve_anonymous(2).unwrap();
+
+        let resolved = context::resolve_labels(&instrs, &ctxt.into_label_resolution_context());
+
+        assert!(resolved.contains(&ResolvedInstruction::call(is_even_start)));
+        assert!(resolved.contains(&ResolvedInstruction::call(is_odd_start)));
+        assert!(is_even_start < is_odd_start);
+    }
+
+    #[test]
+    fn main_with_arguments_fails() {
+        let program: Program = inline_program! { fn main(a) { 42 } };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: The `main` function must not take any argument\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_function_fails() {
+        let program: Program = inline_program! {
+            fn main() { 42 }
+            fn f() { 1 }
+            fn f() { 2 }
+        };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Function `f` is defined multiple times\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod function {
+    use crate::inline_fn;
+
+    use crate::ast::Function;
+
+    use super::*;
+
+    #[test]
+    fn body_is_lowered() {
+        let f: Function = inline_fn! { fn f() { 42 } };
+        let (instrs, _) = lower(&f);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(42),
+                Instruction::pop_copy(2),
+                Instruction::ret(0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn args_are_resolved() {
+        let f: Function = inline_fn! { fn f(a, b) { a } };
+        let (instrs, _) = lower(&f);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::load_local(-3),
+                Instruction::pop_copy(4),
+                Instruction::ret(0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let f: Function = inline_fn! { fn f(a, b) { a + b } };
+        let (_, ctxt) = lower(&f);
+
+        assert_eq!(ctxt.stack().depth(), 0);
+    }
+
+    #[test]
+    fn label_is_added() {
+        let f: Function = inline_fn! { fn foo() { 42 } };
+        let (_, ctxt) = lower(&f);
+
+        assert!(ctxt.labels().resolve_named("foo").is_ok());
+    }
+}
+
+#[cfg(test)]
+mod integer {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn lower_42() {
+        let expr: ExprKind = inline_expr! { 42 };
+        let (left, _) = lower(&expr);
+
+        assert_eq!(left, [Instruction::push_i(42)]);
+    }
+}
+
+#[cfg(test)]
+mod addition {
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_addition() -> ExprKind {
+        ExprKind::addition(ExprKind::integer(40), ExprKind::integer(2))
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (left, _) = lower(&simple_addition());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(40),
+                Instruction::push_i(2),
+                Instruction::add_i(),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_addition());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod multiplication {
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_multiplication() -> ExprKind {
+        ExprKind::multiplication(ExprKind::integer(7), ExprKind::integer(6))
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (left, _) = lower(&simple_multiplication());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(7),
+                Instruction::push_i(6),
+                Instruction::mul(),
+            ]
+        )
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_multiplication());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod exponentiation {
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn base_is_pushed_first() {
+        let expr = ExprKind::exponentiation(ExprKind::integer(2), ExprKind::integer(10));
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(2),
+                Instruction::push_i(10),
+                Instruction::pow(),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod comparison {
+    use crate::ast::ComparisonOperator;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn less_or_equal() -> ExprKind {
+        ExprKind::comparison(
+            ComparisonOperator::LessOrEqual,
+            ExprKind::integer(1),
+            ExprKind::integer(2),
+        )
+    }
+
+    #[test]
+    fn compares_the_operands() {
+        let (left, _) = lower(&less_or_equal());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::comparison(ComparisonOperator::LessOrEqual),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&less_or_equal());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod logical {
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn and_skips_right_operand_when_false() {
+        let expr = ExprKind::and(ExprKind::bool_(false), ExprKind::bool_(true));
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(0),
+                Instruction::jmp_if_not_zero(0),
+                Instruction::push_i(0),
+                Instruction::goto(1),
+                Instruction::push_i(1),
+            ]
+        );
+        assert_eq!(ctxt.labels().resolve_anonymous(0), Ok(4));
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(5));
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn or_skips_right_operand_when_true() {
+        let expr = ExprKind::or(ExprKind::bool_(true), ExprKind::bool_(false));
+        let (left, _) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(1),
+                Instruction::jmp_if_zero(0),
+                Instruction::push_i(1),
+                Instruction::goto(1),
+                Instruction::push_i(0),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod cast {
+    use crate::ast::Type;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn bool_is_already_an_integer() {
+        let expr = ExprKind::cast(ExprKind::bool_(true), Type::named("int"));
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(left, [Instruction::push_i(1)]);
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod subtraction {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_subtraction() -> ExprKind {
+        inline_expr! { 43 - 1 }
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (left, _) = lower(&simple_subtraction());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(43),
+                Instruction::push_i(1),
+                Instruction::neg(),
+                Instruction::add_i(),
+            ],
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_subtraction());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod if_ {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_if() -> ExprKind {
+        inline_expr! { if 1 { 42 } else { -1 } }
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (left, _) = lower(&simple_if());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(1),
+                Instruction::jmp_if_zero(0),
+                Instruction::push_i(42),
+                Instruction::goto(1),
+                Instruction::push_i(-1),
+            ],
+        );
+    }
+
+    #[test]
+    fn label_effects() {
+        let (_, ctxt) = lower(&simple_if());
+
+        assert_eq!(ctxt.labels().resolve_anonymous(0).unwrap(), 4);
+        assert_eq!(ctxt.labels().resolve_anonymous(1).unwrap(), 5);
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_if());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    fn if_without_else() -> ExprKind {
+        inline_expr! { if 1 { 42 } }
+    }
+
+    #[test]
+    fn without_else_generated_instructions() {
+        let (left, ctxt) = lower(&if_without_else());
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(1),
+                Instruction::jmp_if_zero(0),
+                Instruction::push_i(42),
+                Instruction::pop(1),
+                Instruction::pack(0),
+            ],
+        );
+
+        assert_eq!(ctxt.labels().resolve_anonymous(0).unwrap(), 4);
+    }
+
+    #[test]
+    fn without_else_stack_effects() {
+        let (_, ctxt) = lower(&if_without_else());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn constant_conditions() {
+        let if_: ExprKind = inline_expr! { if true { 42 } else { -1 } };
+        let (left, ctxt) = lower(&if_);
+
+        assert_eq!(left, [Instruction::push_i(42)]);
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert_eq!(
+            ctxt.warnings().to_string(),
+            "0:0: Condition is always true\n"
+        );
+
+        let if_: ExprKind = inline_expr! { if false { 42 } };
+        let (left, ctxt) = lower(&if_);
+
+        assert_eq!(left, [Instruction::pack(0)]);
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert_eq!(
+            ctxt.warnings().to_string(),
+            "0:0: Condition is always false\n"
+        );
+    }
+
+    #[test]
+    fn constant_conditions_without_else_discard_the_value() {
+        let if_: ExprKind = inline_expr! { if true { 42 } };
+        let (left, ctxt) = lower(&if_);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(42),
+                Instruction::pop(1),
+                Instruction::pack(0)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod bindings {
+    use crate::{
+        ast::{Binding, ExprKind, Span},
+        inline_expr,
+    };
+
+    use super::*;
+
+    fn simple_bindings() -> ExprKind {
+        inline_expr! {
+            {
+                let foo = 101;
+                42
+            }
+        }
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (bytecode, _) = lower(&simple_bindings());
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(101),
+                Instruction::push_i(42),
+                Instruction::pop_copy(1),
+                Instruction::pop(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_bindings());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn destructuring_bindings_are_popped() {
+        let pattern = BindingPattern::Tuple(vec![
+            BindingPattern::Name("a".to_owned()),
+            BindingPattern::Name("b".to_owned()),
+        ]);
+        let value = ExprKind::tuple(vec![ExprKind::integer(1), ExprKind::integer(2)]);
+        let expr = ExprKind::bindings(
+            vec![Binding::from_pattern(pattern, value, false)],
+            ExprKind::ident("b".to_owned()),
+        );
+        let (bytecode, ctxt) = lower(&expr);
+
+        assert_eq!(
+            &bytecode[bytecode.len() - 3..],
+            [
+                Instruction::push_copy(0),
+                Instruction::pop_copy(3),
+                Instruction::pop(2),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn recovers_from_error() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let a = b;
+                let c = d;
+                e
+            }
+        };
+        let mut ctxt = LoweringContext::new();
+        let mut instructions = CodeBuilder::default();
+
+        let rslt = expr.lower(&mut instructions, &mut ctxt);
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Undefined variable `b`\n0:0: Undefined variable `d`\n0:0: Undefined variable `e`\n"
+        )
+    }
+
+    fn shadowing() -> ExprKind {
+        ExprKind::bindings(
+            vec![
+                Binding::new("x".to_owned(), ExprKind::integer(1)).with_span(Span::new(1, 5)),
+                Binding::new(
+                    "x".to_owned(),
+                    ExprKind::addition(ExprKind::ident("x".to_owned()), ExprKind::integer(1)),
+                )
+                .with_span(Span::new(2, 5)),
+            ],
+            ExprKind::ident("x".to_owned()),
+        )
+    }
+
+    #[test]
+    fn shadowing_uses_new_slot() {
+        let (bytecode, _) = lower(&shadowing());
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(1),
+                Instruction::push_copy(0),
+                Instruction::push_i(1),
+                Instruction::add_i(),
+                Instruction::push_copy(0),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowing_lint() {
+        let (_, ctxt) = lower(&shadowing());
+
+        assert_eq!(
+            ctxt.warnings().to_string(),
+            "2:5: Binding `x` shadows a previous variable\n  1:5: previous binding\n"
+        );
+    }
+
+    fn lower_reusing_slots(expr: &ExprKind) -> (Vec<Instruction>, LoweringContext) {
+        let mut collector = CodeBuilder::default();
+        let mut ctxt = LoweringContext::new();
+        ctxt.reuse_dead_slots();
+
+        expr.lower(&mut collector, &mut ctxt).unwrap();
+
+        (collector.into_instructions(), ctxt)
+    }
+
+    #[test]
+    fn shadowing_reuses_dead_slot() {
+        let (bytecode, ctxt) = lower_reusing_slots(&shadowing());
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(1),
+                Instruction::push_copy(0),
+                Instruction::push_i(1),
+                Instruction::add_i(),
+                Instruction::pop_copy(1),
+                Instruction::push_copy(0),
+                Instruction::pop_copy(1),
+                Instruction::pop(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(!ctxt.warnings().to_string().is_empty());
+    }
+
+    #[test]
+    fn live_variables_keep_their_slot() {
+        let (bytecode, _) = lower_reusing_slots(&inline_expr! {
+            {
+                let a = 1;
+                let b = 2;
+                a + b
+            }
+        });
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::push_copy(1),
+                Instruction::push_copy(1),
+                Instruction::add_i(),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowed_variable_hides_the_slots_below() {
+        let (bytecode, ctxt) = lower_reusing_slots(&inline_expr! {
+            {
+                let x = 1;
+                let y = 2;
+                let y = x;
+                y
+            }
+        });
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::push_copy(1),
+                Instruction::pop_copy(1),
+                Instruction::push_copy(0),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod statements {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_statements() -> ExprKind {
+        inline_expr! {
+            {
+                1;
+                2;
+                3
+            }
+        }
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (bytecode, _) = lower(&simple_statements());
+
+        assert_eq!(
+            bytecode,
+            [
+                Instruction::push_i(1),
+                Instruction::pop(1),
+                Instruction::push_i(2),
+                Instruction::pop(1),
+                Instruction::push_i(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_statements());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod binding {
+    use crate::ast::{Binding, ExprKind};
+
+    use super::*;
+
+    fn simple_binding() -> Binding {
+        Binding::new("foo".to_owned(), ExprKind::integer(101))
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (bytecode, _) = lower(&simple_binding());
+
+        assert_eq!(bytecode, [Instruction::push_i(101)]);
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower(&simple_binding());
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert_eq!(ctxt.stack().top().unwrap(), "foo");
+    }
+
+    fn name(name: &str) -> BindingPattern {
+        BindingPattern::Name(name.to_owned())
+    }
+
+    #[test]
+    fn destructuring_copies_each_part() {
+        let pattern = BindingPattern::Tuple(vec![
+            name("a"),
+            BindingPattern::Wildcard,
+            BindingPattern::Tuple(vec![name("b"), name("c")]),
+        ]);
+        let binding = Binding::from_pattern(pattern, ExprKind::ident("t".to_owned()), false);
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_named("t".to_owned());
+        let mut instructions = CodeBuilder::default();
+
+        binding.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [
+                Instruction::push_copy(0),
+                Instruction::push_copy(0),
+                Instruction::field(0),
+                Instruction::push_copy(1),
+                Instruction::field(2),
+                Instruction::push_copy(0),
+                Instruction::field(0),
+                Instruction::push_copy(1),
+                Instruction::field(1),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 6);
+        assert_eq!(ctxt.stack().resolve("a"), Some(3));
+        assert_eq!(ctxt.stack().resolve("b"), Some(1));
+        assert_eq!(ctxt.stack().resolve("c"), Some(0));
+    }
+
+    fn struct_binding(fields: &[&str]) -> Binding {
+        let pattern = BindingPattern::Struct {
+            name: "Point".to_owned(),
+            fields: fields.iter().map(|f| (f.to_string(), name(f))).collect(),
+        };
+
+        Binding::from_pattern(pattern, ExprKind::ident("p".to_owned()), false)
+    }
+
+    fn lower_struct_binding(fields: &[&str]) -> (Result<Vec<Instruction>, ()>, LoweringContext) {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_struct("Point".to_owned(), vec!["x".to_owned(), "y".to_owned()])
+            .unwrap();
+        ctxt.stack_mut().push_named("p".to_owned());
+        let mut instructions = CodeBuilder::default();
+
+        let rslt = struct_binding(fields).lower(&mut instructions, &mut ctxt);
+
+        (rslt.map(|()| instructions.into_instructions()), ctxt)
+    }
+
+    #[test]
+    fn struct_fields_are_found_by_name() {
+        let (instructions, ctxt) = lower_struct_binding(&["y", "x"]);
+
+        assert_eq!(
+            instructions.unwrap(),
+            [
+                Instruction::push_copy(0),
+                Instruction::push_copy(0),
+                Instruction::field(1),
+                Instruction::push_copy(1),
+                Instruction::field(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().resolve("y"), Some(1));
+        assert_eq!(ctxt.stack().resolve("x"), Some(0));
+    }
+
+    #[test]
+    fn struct_fields_are_checked() {
+        let (rslt, ctxt) = lower_struct_binding(&["x"]);
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Missing fields `y` in pattern of `Point`\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod ident {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_ident() -> ExprKind {
+        inline_expr! { foo }
+    }
+
+    fn lower_simple_ident() -> (Vec<Instruction>, LoweringContext) {
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_named("foo".to_owned());
+        ctxt.stack_mut().push_named("bar".to_owned());
+
+        let mut instructions = CodeBuilder::default();
+
+        simple_ident().lower(&mut instructions, &mut ctxt).unwrap();
+
+        (instructions.into_instructions(), ctxt)
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (bytecode, _) = lower_simple_ident();
+
+        assert_eq!(bytecode, [Instruction::push_copy(1)]);
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower_simple_ident();
+
+        assert_eq!(ctxt.stack().depth(), 3);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fails_when_not_found() {
+        let exp: ExprKind = inline_expr! { foo };
+        let mut ctxt = LoweringContext::new();
+        let mut instructions = CodeBuilder::default();
+
+        let rslt = exp.lower(&mut instructions, &mut ctxt);
+
+        assert!(rslt.is_err());
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn emits_when_not_found() {
+        let expr: ExprKind = inline_expr! { undefined };
+        let mut ctxt = LoweringContext::new();
+        let mut instructions = CodeBuilder::default();
+
+        let rslt = expr.lower(&mut instructions, &mut ctxt);
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Undefined variable `undefined`\n"
+        );
+    }
+
+    #[test]
+    fn function_value() {
+        let expr: ExprKind = inline_expr! { square };
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("square".to_owned(), 1).unwrap();
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [Instruction::make_closure(0, 0, 1)]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn local_variables_take_precedence_over_functions() {
+        let expr: ExprKind = inline_expr! { square };
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("square".to_owned(), 1).unwrap();
+        ctxt.stack_mut().push_named("square".to_owned());
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(instructions.instructions(), [Instruction::push_copy(0)]);
+    }
+}
+
+#[cfg(test)]
+mod bool {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn simple_bool() -> ExprKind {
+        inline_expr! { true }
+    }
+
+    fn lower_simple_bool() -> (Vec<Instruction>, LoweringContext) {
+        let mut ctxt = LoweringContext::new();
+        let mut collector = CodeBuilder::default();
+
+        simple_bool().lower(&mut collector, &mut ctxt).unwrap();
+
+        (collector.into_instructions(), ctxt)
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let (bytecode, _) = lower_simple_bool();
+        assert_eq!(bytecode, [Instruction::push_i(1)]);
+    }
+
+    #[test]
+    fn stack_effects() {
+        let (_, ctxt) = lower_simple_bool();
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod fn_call {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    fn lower_with_function(expr: &ExprKind, name: &str, arity: usize) -> LoweringContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function(name.to_owned(), arity).unwrap();
+
+        expr.lower(&mut CodeBuilder::default(), &mut ctxt).ok();
+
+        ctxt
+    }
+
+    #[test]
+    fn generated_instructions() {
+        let expr: ExprKind = inline_expr! { f(1, 2) };
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("f".to_owned(), 2).unwrap();
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [
+                Instruction::res_v(1),
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::call(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_effects() {
+        let expr: ExprKind = inline_expr! { f(1, 2) };
+        let ctxt = lower_with_function(&expr, "f", 2);
+
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wrong_arity() {
+        let expr: ExprKind = inline_expr! { f(1) };
+        let ctxt = lower_with_function(&expr, "f", 2);
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Wrong number of arguments for `f`: expected 2, found 1\n"
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn undefined_function() {
+        let expr: ExprKind = inline_expr! { g() };
+        let ctxt = lower_with_function(&expr, "f", 0);
+
+        assert_eq!(ctxt.errors().to_string(), "0:0: Undefined function `g`\n");
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod builtin {
+    use crate::{
+        ast::{ExprKind, FnCall, Span},
+        inline_expr,
+    };
+
+    use super::*;
+
+    #[test]
+    fn print() {
+        let expr: ExprKind = inline_expr! { print(42) };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(42),
+                Instruction::out(),
+                Instruction::pack(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn println() {
+        let expr: ExprKind = inline_expr! { println(42) };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(42),
+                Instruction::out(),
+                Instruction::push_const(0),
+                Instruction::out(),
+                Instruction::pack(0),
+            ]
+        );
+        assert_eq!(
+            ctxt.constants().get(0),
+            Some(&Constant::String("\n".to_owned()))
+        );
+    }
+
+    #[test]
+    fn read_int() {
+        let expr: ExprKind = inline_expr! { read_int() };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(left, [Instruction::in_i()]);
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn assert() {
+        let call = FnCall::new("assert".to_owned(), vec![ExprKind::bool_(true)])
+            .with_span(Span::new(3, 5));
+        let (left, ctxt) = lower(&ExprKind::FnCall(call));
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_i(1),
+                Instruction::jmp_if_not_zero(0),
+                Instruction::push_const(0),
+                Instruction::trap(),
+                Instruction::pack(0),
+            ]
+        );
+        assert_eq!(
+            ctxt.constants().get(0),
+            Some(&Constant::String("3:5: Assertion failed".to_owned()))
+        );
+        assert_eq!(ctxt.labels().resolve_anonymous(0).unwrap(), 4);
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn panic() {
+        let expr: ExprKind = inline_expr! { panic("oops") };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(left, [Instruction::push_const(0), Instruction::trap()]);
+        assert_eq!(
+            ctxt.constants().get(0),
+            Some(&Constant::String("oops".to_owned()))
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn substr() {
+        let expr: ExprKind = inline_expr! { substr("hello", 1, len("abc")) };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_const(0),
+                Instruction::push_i(1),
+                Instruction::push_const(1),
+                Instruction::len(),
+                Instruction::substr(),
+            ]
+        );
+        assert_eq!(ctxt.constants().len(), 2);
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn char_at() {
+        let expr: ExprKind = inline_expr! { char_at("abc", 1) };
+        let (left, ctxt) = lower(&expr);
+
+        assert_eq!(
+            left,
+            [
+                Instruction::push_const(0),
+                Instruction::push_i(1),
+                Instruction::char_at(),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn push() {
+        let expr: ExprKind = inline_expr! { push(a, 42) };
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_anonymous();
+        ctxt.stack_mut()
+            .name_top_anonymous_mut("a".to_owned())
+            .unwrap();
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [
+                Instruction::push_copy(0),
+                Instruction::push_i(42),
+                Instruction::push_back(),
+                Instruction::pack(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 2);
+    }
+
+    #[test]
+    fn pop() {
+        let expr: ExprKind = inline_expr! { pop(a) };
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_anonymous();
+        ctxt.stack_mut()
+            .name_top_anonymous_mut("a".to_owned())
+            .unwrap();
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [Instruction::push_copy(0), Instruction::pop_back()]
+        );
+        assert_eq!(ctxt.stack().depth(), 2);
+    }
+
+    #[test]
+    fn modifying_immutable_array_fails() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let a = [1];
+                push(a, 2);
+                pop(a)
+            }
+        };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Cannot modify immutable variable `a` with `push`\n\
+             0:0: Cannot modify immutable variable `a` with `pop`\n"
+        );
+    }
+
+    #[test]
+    fn modifying_array_through_immutable_alias_fails() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let mut a = [1, 2];
+                let b = a;
+                push(b, 3);
+                len(a)
+            }
+        };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Cannot modify immutable variable `b` with `push`\n"
+        );
+    }
+
+    #[test]
+    fn declared_function_takes_precedence() {
+        let expr: ExprKind = inline_expr! { print(42) };
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_function("print".to_owned(), 1).unwrap();
+        let mut instructions = CodeBuilder::default();
+
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions().last(),
+            Some(&Instruction::call(0))
+        );
+    }
+
+    #[test]
+    fn wrong_arity() {
+        let expr: ExprKind = inline_expr! { println(1, 2) };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Wrong number of arguments for `println`: expected 1, found 2\n"
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod closure {
+    use crate::{
+        ast::{ExprKind, Program},
+        inline_expr, inline_program, type_checker,
+    };
+
+    use super::*;
+
+    /// Types a closure without parameters, to lower it as a pending one.
+    fn typed_closure(body: ExprKind) -> Closure {
+        match type_checker::type_expr(&ExprKind::closure(Vec::new(), body)) {
+            crate::hir::ExprKind::Closure(c) => c,
+            e => unreachable!("Expected a closure, found {:?}", e),
+        }
+    }
+
+    fn add_y() -> ExprKind {
+        ExprKind::closure(vec!["x".to_owned()], inline_expr! { x + y })
+    }
+
+    #[test]
+    fn captures_are_copied() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_named("y".to_owned());
+        ctxt.stack_mut().push_named("z".to_owned());
+        let mut instructions = CodeBuilder::default();
+
+        add_y().lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [
+                Instruction::push_copy(1),
+                Instruction::make_closure(0, 1, 1)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 3);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_local_variables_are_captured() {
+        // `y` is not a local variable, so it is reported when the closure
+        // body is lowered.
+        let (instructions, _) = lower(&add_y());
+
+        assert_eq!(instructions, [Instruction::make_closure(0, 0, 1)]);
+    }
+
+    #[test]
+    fn call() {
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_named("f".to_owned());
+        let mut instructions = CodeBuilder::default();
+
+        let expr: ExprKind = inline_expr! { f(1, 2) };
+        expr.lower(&mut instructions, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instructions.instructions(),
+            [
+                Instruction::res_v(1),
+                Instruction::push_copy(1),
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::call_closure(2),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 2);
+    }
+
+    #[test]
+    fn body_is_lowered_after_functions() {
+        let program: Program = inline_program! {
+            fn main() { f(1) }
+            fn f(x) { x }
+        };
+        let (instructions, mut ctxt) = lower(&program);
+        let functions_len = instructions.len() as u32;
+
+        let mut collector = CodeBuilder::default();
+        collector.extend(instructions);
+        ctxt.defer_closure(Vec::new(), typed_closure(ExprKind::integer(42)));
+        lower_pending_closures(&mut collector, &mut ctxt).unwrap();
+
+        let label = ctxt.labels().resolve_anonymous(2).unwrap();
+
+        assert_eq!(label, functions_len);
+        assert_eq!(
+            collector.instructions()[label as usize],
+            Instruction::push_i(42)
+        );
+    }
+
+    #[test]
+    fn undefined_variable_in_body() {
+        let program: Program = inline_program! {
+            fn main() { 0 }
+        };
+        let mut ctxt = LoweringContext::new();
+        ctxt.defer_closure(Vec::new(), typed_closure(inline_expr! { y }));
+
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
+        assert_eq!(ctxt.errors().to_string(), "0:0: Undefined variable `y`\n");
+    }
+}
+
+#[cfg(test)]
+mod return_ {
+    use crate::{
+        ast::{ExprKind, Function, Program},
+        inline_fn, inline_program,
+    };
+
+    use super::*;
+
+    #[test]
+    fn as_function_body() {
+        let f: Function = inline_fn! { fn f(a) { return a } };
+        let (instrs, _) = lower(&f);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::load_local(-2),
+                Instruction::pop_copy(3),
+                Instruction::ret(0, 2),
+                Instruction::pop_copy(3),
+                Instruction::ret(0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_enclosing_bindings() {
+        let f: Function = inline_fn! {
+            fn f(a) {
+                let b = 1;
+                let c = return b;
+                c
+            }
+        };
+        let (instrs, _) = lower(&f);
+
+        assert_eq!(
+            instrs[..4],
+            [
+                Instruction::push_i(1),
+                Instruction::load_local(0),
+                Instruction::pop_copy(4),
+                Instruction::ret(1, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_main() {
+        let program: Program = inline_program! {
+            fn main() {
+                let a = 1;
+                let b = return a;
+                b
+            }
+        };
         let (instrs, _) = lower(&program);
 
-        assert!(instrs.starts_with(&[Instruction::push_i(42), Instruction::f_stop()]));
+        assert_eq!(
+            instrs[3..7],
+            [
+                Instruction::push_i(1),
+                Instruction::load_local(0),
+                Instruction::pop_copy(3),
+                Instruction::ret(1, 2),
+            ]
+        );
     }
 
     #[test]
-    fn main_instrs_are_removed() {
-        let program: Program = inline_program! { fn main() { 42 } };
-        let (instrs, _) = lower(&program);
+    fn stack_effects() {
+        let expr = ExprKind::return_(ExprKind::integer(42));
+        let mut ctxt = LoweringContext::new();
+        ctxt.set_frame(Frame::new(0, 0));
+        ctxt.stack_mut().push_anonymous();
+        ctxt.stack_mut().push_anonymous();
+
+        expr.lower(&mut CodeBuilder::default(), &mut ctxt).unwrap();
+
+        assert_eq!(ctxt.stack().depth(), 3);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn outside_of_function_fails() {
+        let expr = ExprKind::return_(ExprKind::integer(42));
+        let mut ctxt = LoweringContext::new();
+        let mut instructions = CodeBuilder::default();
+
+        assert!(expr.lower(&mut instructions, &mut ctxt).is_err());
 
-        assert!(!instrs.ends_with(&[Instruction::ret()]));
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Found `return` outside of a function\n"
+        );
+        assert!(instructions.instructions().is_empty());
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 }
 
 #[cfg(test)]
-mod function {
-    use crate::inline_fn;
+mod assignment {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
 
     use super::*;
 
     #[test]
-    fn body_is_lowered() {
-        let f: Function = inline_fn! { fn f() { 42 } };
-        let (instrs, _) = lower(&f);
+    fn simple() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let mut a = 1;
+                a = 2
+            }
+        };
+        let (instrs, _) = lower(&expr);
 
         assert_eq!(
             instrs,
             [
-                Instruction::push_i(42),
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::push_copy(0),
+                Instruction::pop_copy(2),
                 Instruction::pop_copy(1),
-                Instruction::ret()
+                Instruction::pop(0),
             ]
         );
     }
 
     #[test]
-    fn label_is_added() {
-        let f: Function = inline_fn! { fn foo() { 42 } };
-        let (_, ctxt) = lower(&f);
+    fn stack_effects() {
+        let expr = ExprKind::assignment("a".to_owned(), ExprKind::integer(42));
+        let mut ctxt = LoweringContext::new();
+        ctxt.stack_mut().push_anonymous();
+        ctxt.stack_mut()
+            .name_top_anonymous_mut("a".to_owned())
+            .unwrap();
 
-        assert!(ctxt.labels().resolve_named("foo").is_ok());
+        expr.lower(&mut CodeBuilder::default(), &mut ctxt).unwrap();
+
+        assert_eq!(ctxt.stack().depth(), 2);
+        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn immutable_variable_fails() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let a = 1;
+                a = 2
+            }
+        };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Cannot assign to immutable variable `a`\n"
+        );
+    }
+
+    #[test]
+    fn undefined_variable_fails() {
+        let expr = ExprKind::assignment("a".to_owned(), ExprKind::integer(42));
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(ctxt.errors().to_string(), "0:0: Undefined variable `a`\n");
     }
 }
 
 #[cfg(test)]
-mod integer {
+mod string {
     use crate::inline_expr;
 
+    use crate::ast::ExprKind;
+
     use super::*;
 
     #[test]
-    fn lower_42() {
-        let expr: ExprKind = inline_expr! { 42 };
-        let (left, _) = lower(&expr);
+    fn simple() {
+        let expr: ExprKind = inline_expr! { "hello" };
+        let (instrs, ctxt) = lower(&expr);
 
-        assert_eq!(left, [Instruction::push_i(42)]);
+        assert_eq!(instrs, [Instruction::push_const(0)]);
+        assert_eq!(
+            ctxt.constants().get(0),
+            Some(&Constant::String("hello".to_owned()))
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+
+    #[test]
+    fn stored_once() {
+        let expr: ExprKind = inline_expr! { ("a", "b", "a") };
+        let (instrs, ctxt) = lower(&expr);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_const(0),
+                Instruction::push_const(1),
+                Instruction::push_const(0),
+                Instruction::pack(3),
+            ]
+        );
+        assert_eq!(ctxt.constants().len(), 2);
     }
 }
 
 #[cfg(test)]
-mod addition {
+mod tuple {
+    use crate::ast::ExprKind;
+
     use super::*;
 
-    fn simple_addition() -> ExprKind {
-        ExprKind::addition(ExprKind::integer(40), ExprKind::integer(2))
+    #[test]
+    fn elements_are_packed() {
+        let expr = ExprKind::tuple(vec![ExprKind::integer(1), ExprKind::integer(2)]);
+        let (instrs, ctxt) = lower(&expr);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::pack(2)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn generated_instructions() {
-        let (left, _) = lower(&simple_addition());
+    fn field_access() {
+        let expr = ExprKind::field(ExprKind::tuple(vec![ExprKind::integer(1)]), 0);
+        let (instrs, ctxt) = lower(&expr);
 
         assert_eq!(
-            left,
+            instrs,
             [
-                Instruction::push_i(40),
+                Instruction::push_i(1),
+                Instruction::pack(1),
+                Instruction::field(0)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod array {
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn elements_are_moved_to_the_heap() {
+        let expr = ExprKind::array(vec![ExprKind::integer(1), ExprKind::integer(2)]);
+        let (instrs, ctxt) = lower(&expr);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(1),
                 Instruction::push_i(2),
-                Instruction::add_i(),
+                Instruction::new_array(2)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
+}
+
+#[cfg(test)]
+mod index {
+    use crate::inline_expr;
+
+    use crate::ast::ExprKind;
+
+    use super::*;
+
+    #[test]
+    fn read() {
+        let expr = ExprKind::index(
+            ExprKind::array(vec![ExprKind::integer(1)]),
+            ExprKind::integer(0),
+        );
+        let (instrs, ctxt) = lower(&expr);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(1),
+                Instruction::new_array(1),
+                Instruction::push_i(0),
+                Instruction::index()
             ]
         );
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_addition());
+    fn write() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let mut a = [1];
+                a[0] = 42
+            }
+        };
+        let (instrs, _) = lower(&expr);
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(1),
+                Instruction::new_array(1),
+                Instruction::push_copy(0),
+                Instruction::push_i(0),
+                Instruction::push_i(42),
+                Instruction::store_index(),
+                Instruction::pop_copy(1),
+                Instruction::pop(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_through_immutable_variable_fails() {
+        let expr: ExprKind = inline_expr! {
+            {
+                let a = [1];
+                a[0] = 42
+            }
+        };
+        let mut ctxt = LoweringContext::new();
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Cannot assign to an element of immutable variable `a`\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod struct_literal {
+    use crate::ast::{Item, Struct};
+
+    use crate::ast::{ExprKind, Function, Program};
+
+    use super::*;
+
+    fn point_ctxt() -> LoweringContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_struct("Point".to_owned(), vec!["x".to_owned(), "y".to_owned()])
+            .unwrap();
+        ctxt
+    }
+
+    fn literal(fields: &[(&str, i32)]) -> ExprKind {
+        let fields = fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), ExprKind::integer(*value)))
+            .collect();
+
+        ExprKind::struct_literal("Point".to_owned(), fields)
+    }
+
+    #[test]
+    fn fields_are_stored_in_declaration_order() {
+        let mut ctxt = point_ctxt();
+        let mut instrs = CodeBuilder::default();
+
+        literal(&[("y", 2), ("x", 1)])
+            .lower(&mut instrs, &mut ctxt)
+            .unwrap();
+
+        assert_eq!(
+            instrs.instructions(),
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::pack(2)
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
+    }
 
+    #[test]
+    fn undefined_struct() {
+        let mut ctxt = LoweringContext::new();
+        let expr = literal(&[]);
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(ctxt.errors().to_string(), "0:0: Undefined struct `Point`\n");
         assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let mut ctxt = point_ctxt();
+        let expr = literal(&[("x", 1), ("y", 2), ("z", 3)]);
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Struct `Point` has no field named `z`\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_field() {
+        let mut ctxt = point_ctxt();
+        let expr = literal(&[("x", 1), ("x", 2)]);
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Field `x` is specified more than once\n"
+        );
+    }
+
+    #[test]
+    fn missing_fields() {
+        let mut ctxt = point_ctxt();
+        let expr = literal(&[]);
+
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Missing fields `x`, `y` in initializer of `Point`\n"
+        );
+    }
+
+    #[test]
+    fn declared_from_program() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                literal(&[("x", 1), ("y", 2)]),
+            )),
+            Item::Struct(Struct::new(
+                "Point".to_owned(),
+                vec!["x".to_owned(), "y".to_owned()],
+            )),
+        ]);
+
+        let mut ctxt = LoweringContext::new();
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_ok());
+    }
+
+    #[test]
+    fn struct_defined_twice() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                ExprKind::integer(0),
+            )),
+            Item::Struct(Struct::new("Unit".to_owned(), Vec::new())),
+            Item::Struct(Struct::new("Unit".to_owned(), Vec::new())),
+        ]);
+
+        let mut ctxt = LoweringContext::new();
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Struct `Unit` is defined multiple times\n"
+        );
     }
 }
 
 #[cfg(test)]
-mod multiplication {
+mod variant_literal {
+    use crate::ast::{Enum, EnumVariant, Item};
+
+    use crate::ast::{ExprKind, Function, Program};
+
     use super::*;
 
-    fn simple_multiplication() -> ExprKind {
-        ExprKind::multiplication(ExprKind::integer(7), ExprKind::integer(6))
+    fn shape_ctxt() -> LoweringContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_enum(
+            "Shape".to_owned(),
+            vec![("Empty".to_owned(), 0), ("Circle".to_owned(), 1)],
+        )
+        .unwrap();
+        ctxt
+    }
+
+    fn variant(name: &str, args: Vec<ExprKind>) -> ExprKind {
+        ExprKind::variant_literal("Shape".to_owned(), name.to_owned(), args)
+    }
+
+    #[test]
+    fn unit_variant_is_tagged() {
+        let mut ctxt = shape_ctxt();
+        let mut instrs = CodeBuilder::default();
+
+        variant("Empty", Vec::new())
+            .lower(&mut instrs, &mut ctxt)
+            .unwrap();
+
+        assert_eq!(
+            instrs.instructions(),
+            [Instruction::push_i(0), Instruction::pack(1)]
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn generated_instructions() {
-        let (left, _) = lower(&simple_multiplication());
+    fn fields_follow_the_tag() {
+        let mut ctxt = shape_ctxt();
+        let mut instrs = CodeBuilder::default();
+
+        variant("Circle", vec![ExprKind::integer(3)])
+            .lower(&mut instrs, &mut ctxt)
+            .unwrap();
 
         assert_eq!(
-            left,
+            instrs.instructions(),
             [
-                Instruction::push_i(7),
-                Instruction::push_i(6),
-                Instruction::mul(),
+                Instruction::push_i(1),
+                Instruction::push_i(3),
+                Instruction::pack(2)
             ]
-        )
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_multiplication());
+    fn wrong_field_count() {
+        let mut ctxt = shape_ctxt();
+        let expr = variant("Circle", Vec::new());
 
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Variant `Shape::Circle` takes 1 field(s), but 0 were supplied\n"
+        );
         assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
     }
-}
-
-#[cfg(test)]
-mod subtraction {
-    use crate::inline_expr;
 
-    use super::*;
+    #[test]
+    fn unknown_variant() {
+        let mut ctxt = shape_ctxt();
+        let expr = variant("Square", Vec::new());
 
-    fn simple_subtraction() -> ExprKind {
-        inline_expr! { 43 - 1 }
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Enum `Shape` has no variant named `Square`\n"
+        );
     }
 
     #[test]
-    fn generated_instructions() {
-        let (left, _) = lower(&simple_subtraction());
+    fn unknown_enum() {
+        let mut ctxt = LoweringContext::new();
+        let expr = variant("Empty", Vec::new());
 
-        assert_eq!(
-            left,
-            [
-                Instruction::push_i(43),
-                Instruction::push_i(1),
-                Instruction::neg(),
-                Instruction::add_i(),
-            ],
-        );
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(ctxt.errors().to_string(), "0:0: Undefined enum `Shape`\n");
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_subtraction());
+    fn declared_from_program() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                variant("Empty", Vec::new()),
+            )),
+            Item::Enum(Enum::new(
+                "Shape".to_owned(),
+                vec![EnumVariant::new("Empty".to_owned(), Vec::new())],
+            )),
+        ]);
 
-        assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
+        let mut ctxt = LoweringContext::new();
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_ok());
     }
 }
 
 #[cfg(test)]
-mod if_ {
-    use crate::inline_expr;
+mod match_ {
+    use crate::ast::{ExprKind, MatchArm};
 
     use super::*;
 
-    fn simple_if() -> ExprKind {
-        inline_expr! { if 1 { 42 } else { -1 } }
+    fn option_ctxt() -> LoweringContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_enum(
+            "Option".to_owned(),
+            vec![("None".to_owned(), 0), ("Some".to_owned(), 1)],
+        )
+        .unwrap();
+        ctxt
+    }
+
+    fn some(field: Pattern) -> Pattern {
+        Pattern::variant("Option".to_owned(), "Some".to_owned(), vec![field])
     }
 
     #[test]
-    fn generated_instructions() {
-        let (left, _) = lower(&simple_if());
+    fn integer_patterns() {
+        let expr = ExprKind::match_(
+            ExprKind::integer(2),
+            vec![
+                MatchArm::new(Pattern::Integer(1), ExprKind::integer(10)),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(20)),
+            ],
+        );
+        let (instrs, ctxt) = lower(&expr);
 
         assert_eq!(
-            left,
+            instrs,
             [
-                Instruction::push_i(1),
-                Instruction::cond_jmp(0, 1, 0),
-                Instruction::push_i(42),
-                Instruction::goto(2),
+                Instruction::push_i(2),
+                Instruction::push_copy(0),
                 Instruction::push_i(-1),
-            ],
+                Instruction::add_i(),
+                Instruction::cond_jmp(1, 2, 1),
+                Instruction::push_i(10),
+                Instruction::pop_copy(1),
+                Instruction::goto(0),
+                Instruction::push_i(20),
+                Instruction::pop_copy(1),
+                Instruction::goto(0),
+            ]
         );
+        assert_eq!(ctxt.stack().depth(), 1);
+        assert_eq!(ctxt.labels().resolve_anonymous(0), Ok(11));
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(8));
+        assert_eq!(ctxt.labels().resolve_anonymous(2), Ok(5));
     }
 
     #[test]
-    fn label_effects() {
-        let (_, ctxt) = lower(&simple_if());
+    fn variant_fields_are_tested() {
+        let mut ctxt = option_ctxt();
+        let expr = ExprKind::match_(
+            ExprKind::ident("o".to_owned()),
+            vec![
+                MatchArm::new(some(Pattern::Integer(0)), ExprKind::integer(1)),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(2)),
+            ],
+        );
+        ctxt.stack_mut().push_named("o".to_owned());
 
-        assert_eq!(ctxt.labels().resolve_anonymous(0).unwrap(), 2);
-        assert_eq!(ctxt.labels().resolve_anonymous(1).unwrap(), 4);
-        assert_eq!(ctxt.labels().resolve_anonymous(2).unwrap(), 5);
+        let mut instrs = CodeBuilder::default();
+        expr.lower(&mut instrs, &mut ctxt).unwrap();
+
+        assert_eq!(
+            instrs.instructions()[1..9],
+            [
+                Instruction::push_copy(0),
+                Instruction::field(0),
+                Instruction::push_i(-1),
+                Instruction::add_i(),
+                Instruction::cond_jmp(1, 2, 1),
+                Instruction::push_copy(0),
+                Instruction::field(1),
+                Instruction::push_i(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 2);
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_if());
+    fn non_exhaustive() {
+        let mut ctxt = option_ctxt();
+        let expr = ExprKind::match_(
+            ExprKind::integer(0),
+            vec![MatchArm::new(some(Pattern::Wildcard), ExprKind::integer(1))],
+        );
 
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Non-exhaustive `match`: pattern `Option::None` is not covered\n"
+        );
         assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
     }
-}
 
-#[cfg(test)]
-mod bindings {
-    use crate::inline_expr;
+    #[test]
+    fn bindings_are_copied_and_dropped() {
+        let mut ctxt = option_ctxt();
+        let expr = ExprKind::match_(
+            ExprKind::ident("o".to_owned()),
+            vec![
+                MatchArm::new(
+                    some(Pattern::Binding("x".to_owned())),
+                    ExprKind::ident("x".to_owned()),
+                ),
+                MatchArm::new(Pattern::Binding("y".to_owned()), ExprKind::integer(0)),
+            ],
+        );
+        ctxt.stack_mut().push_named("o".to_owned());
 
-    use super::*;
+        let mut instrs = CodeBuilder::default();
+        expr.lower(&mut instrs, &mut ctxt).unwrap();
 
-    fn simple_bindings() -> ExprKind {
-        inline_expr! {
-            {
-                let foo = 101;
-                42
-            }
-        }
+        assert_eq!(
+            instrs.instructions()[6..],
+            [
+                Instruction::push_copy(0),
+                Instruction::field(1),
+                Instruction::push_copy(0),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+                Instruction::goto(0),
+                Instruction::push_copy(0),
+                Instruction::push_i(0),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+                Instruction::goto(0),
+            ]
+        );
+        assert_eq!(ctxt.stack().depth(), 2);
     }
 
     #[test]
-    fn generated_instructions() {
-        let (bytecode, _) = lower(&simple_bindings());
+    fn failed_guard_drops_bindings() {
+        let expr = ExprKind::match_(
+            ExprKind::integer(3),
+            vec![
+                MatchArm::new(Pattern::Binding("x".to_owned()), ExprKind::integer(1))
+                    .with_guard(Some(ExprKind::ident("x".to_owned()))),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(0)),
+            ],
+        );
+        let (instrs, ctxt) = lower(&expr);
 
         assert_eq!(
-            bytecode,
+            instrs[..10],
             [
-                Instruction::push_i(101),
-                Instruction::push_i(42),
-                Instruction::pop_copy(1),
-                Instruction::pop(0),
+                Instruction::push_i(3),
+                Instruction::push_copy(0),
+                Instruction::push_copy(0),
+                Instruction::jmp_if_not_zero(2),
+                Instruction::pop(1),
+                Instruction::goto(1),
+                Instruction::push_i(1),
+                Instruction::pop_copy(2),
+                Instruction::pop(1),
+                Instruction::goto(0),
             ]
         );
+        assert_eq!(ctxt.labels().resolve_anonymous(1), Ok(10));
+        assert_eq!(ctxt.labels().resolve_anonymous(2), Ok(6));
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_bindings());
+    fn guard_without_bindings_jumps_to_next_arm() {
+        let expr = ExprKind::match_(
+            ExprKind::integer(3),
+            vec![
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(1))
+                    .with_guard(Some(ExprKind::bool_(false))),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(0)),
+            ],
+        );
+        let (instrs, _) = lower(&expr);
 
-        assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
+        assert_eq!(
+            instrs[1..3],
+            [Instruction::push_i(0), Instruction::jmp_if_zero(1)]
+        );
     }
 
     #[test]
-    fn recovers_from_error() {
-        let expr: ExprKind = inline_expr! {
-            {
-                let a = b;
-                let c = d;
-                e
-            }
-        };
+    fn guarded_arms_are_not_exhaustive() {
+        let expr = ExprKind::match_(
+            ExprKind::integer(3),
+            vec![MatchArm::new(Pattern::Wildcard, ExprKind::integer(1))
+                .with_guard(Some(ExprKind::bool_(true)))],
+        );
         let mut ctxt = LoweringContext::new();
-        let mut instructions = Vec::new();
-
-        let rslt = expr.lower(&mut instructions, &mut ctxt);
 
-        assert!(rslt.is_err());
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
         assert_eq!(
             ctxt.errors().to_string(),
-            "Undefined variable `b`\nUndefined variable `d`\nUndefined variable `e`\n"
-        )
-    }
-}
-
-#[cfg(test)]
-mod binding {
-    use super::*;
-
-    fn simple_binding() -> Binding {
-        Binding::new("foo".to_owned(), ExprKind::integer(101))
+            "0:0: Non-exhaustive `match`: consider adding a wildcard arm `_`\n"
+        );
     }
 
     #[test]
-    fn generated_instructions() {
-        let (bytecode, _) = lower(&simple_binding());
+    fn binding_twice_in_a_pattern() {
+        let mut ctxt = option_ctxt();
+        ctxt.declare_enum("Pair".to_owned(), vec![("P".to_owned(), 2)])
+            .unwrap();
+        let x = || Pattern::Binding("x".to_owned());
+        let expr = ExprKind::match_(
+            ExprKind::integer(0),
+            vec![MatchArm::new(
+                Pattern::variant("Pair".to_owned(), "P".to_owned(), vec![x(), x()]),
+                ExprKind::integer(1),
+            )],
+        );
 
-        assert_eq!(bytecode, [Instruction::push_i(101)]);
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Identifier `x` is bound more than once in the same pattern\n"
+        );
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower(&simple_binding());
+    fn unknown_variant_pattern() {
+        let mut ctxt = option_ctxt();
+        let expr = ExprKind::match_(
+            ExprKind::integer(0),
+            vec![MatchArm::new(
+                Pattern::variant("Option".to_owned(), "Any".to_owned(), Vec::new()),
+                ExprKind::integer(1),
+            )],
+        );
 
-        assert_eq!(ctxt.stack().depth(), 1);
-        assert_eq!(ctxt.stack().top().unwrap(), "foo");
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Enum `Option` has no variant named `Any`\n"
+        );
     }
 }
 
 #[cfg(test)]
-mod ident {
-    use crate::inline_expr;
+mod const_ {
+    use crate::{
+        ast::{Const, Item},
+        inline_expr,
+    };
 
-    use super::*;
+    use crate::ast::{ExprKind, Function, Program};
 
-    fn simple_ident() -> ExprKind {
-        inline_expr! { foo }
-    }
+    use super::*;
 
-    fn lower_simple_ident() -> (Vec<Instruction>, LoweringContext) {
+    fn ctxt_with_consts() -> LoweringContext {
         let mut ctxt = LoweringContext::new();
-        ctxt.stack_mut().push_named("foo".to_owned());
-        ctxt.stack_mut().push_named("bar".to_owned());
+        ctxt.declare_const("N".to_owned(), Ok(ConstValue::Int(10)))
+            .unwrap();
+        ctxt.declare_const("NAME".to_owned(), Ok(ConstValue::Str("dyl".to_owned())))
+            .unwrap();
 
-        let mut instructions = Vec::new();
+        ctxt
+    }
 
-        simple_ident().lower(&mut instructions, &mut ctxt).unwrap();
+    #[test]
+    fn inlined_at_use_site() {
+        let mut ctxt = ctxt_with_consts();
+        let mut bytecode = CodeBuilder::default();
+        let expr: ExprKind = inline_expr! { (N + 1, NAME) };
 
-        (instructions, ctxt)
+        expr.lower(&mut bytecode, &mut ctxt).unwrap();
+
+        assert_eq!(
+            bytecode.instructions(),
+            [
+                Instruction::push_i(10),
+                Instruction::push_i(1),
+                Instruction::add_i(),
+                Instruction::push_const(0),
+                Instruction::pack(2),
+            ]
+        );
+        assert_eq!(
+            ctxt.constants().get(0),
+            Some(&Constant::String("dyl".to_owned()))
+        );
+        assert_eq!(ctxt.stack().depth(), 1);
     }
 
     #[test]
-    fn generated_instructions() {
-        let (bytecode, _) = lower_simple_ident();
+    fn local_variable_takes_precedence() {
+        let mut ctxt = ctxt_with_consts();
+        let mut bytecode = CodeBuilder::default();
+        let expr: ExprKind = inline_expr! {
+            {
+                let N = 1;
+                N
+            }
+        };
 
-        assert_eq!(bytecode, [Instruction::push_copy(1)]);
+        expr.lower(&mut bytecode, &mut ctxt).unwrap();
+
+        assert_eq!(bytecode.instructions()[1], Instruction::push_copy(0));
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower_simple_ident();
+    fn cannot_be_assigned() {
+        let mut ctxt = ctxt_with_consts();
+        let expr: ExprKind = inline_expr! { N = 1 };
 
-        assert_eq!(ctxt.stack().depth(), 3);
-        assert!(ctxt.stack().top().unwrap().is_empty());
+        assert!(expr.lower(&mut CodeBuilder::default(), &mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Cannot assign to constant `N`\n"
+        );
     }
 
     #[test]
-    fn fails_when_not_found() {
-        let exp: ExprKind = inline_expr! { foo };
-        let mut ctxt = LoweringContext::new();
-        let mut instructions = Vec::new();
+    fn declared_from_program() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                inline_expr! { N },
+            )),
+            Item::Const(Const::new("N".to_owned(), inline_expr! { M * 2 })),
+            Item::Const(Const::new("M".to_owned(), ExprKind::integer(21))),
+        ]);
 
-        let rslt = exp.lower(&mut instructions, &mut ctxt);
+        let mut ctxt = LoweringContext::new();
+        let mut bytecode = CodeBuilder::default();
+        program.lower(&mut bytecode, &mut ctxt).unwrap();
 
-        assert!(rslt.is_err());
-        assert_eq!(ctxt.stack().depth(), 1);
+        assert_eq!(bytecode.instructions()[3], Instruction::push_i(42));
     }
 
     #[test]
-    fn emits_when_not_found() {
-        let expr: ExprKind = inline_expr! { undefined };
+    fn defined_twice() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                ExprKind::integer(0),
+            )),
+            Item::Const(Const::new("N".to_owned(), ExprKind::integer(1))),
+            Item::Const(Const::new("N".to_owned(), ExprKind::integer(2))),
+        ]);
+
         let mut ctxt = LoweringContext::new();
-        let mut instructions = Vec::new();
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "0:0: Constant `N` is defined multiple times\n"
+        );
+    }
 
-        let rslt = expr.lower(&mut instructions, &mut ctxt);
+    #[test]
+    fn invalid_constant_is_reported_once() {
+        let program = Program::new(vec![
+            Item::Function(Function::new(
+                "main".to_owned(),
+                Vec::new(),
+                inline_expr! { N },
+            )),
+            Item::Const(Const::new("N".to_owned(), inline_expr! { N })),
+        ]);
 
-        assert!(rslt.is_err());
+        let mut ctxt = LoweringContext::new();
+        assert!(program
+            .lower(&mut CodeBuilder::default(), &mut ctxt)
+            .is_err());
         assert_eq!(
             ctxt.errors().to_string(),
-            "Undefined variable `undefined`\n"
+            "0:0: Cycle detected when evaluating constant `N`: `N` -> `N`\n"
         );
     }
 }
 
 #[cfg(test)]
-mod bool {
-    use crate::inline_expr;
+mod debug_checks {
+    use dyl_bytecode::operations::ArithmeticOp;
 
-    use super::*;
+    use crate::{
+        ast::{ExprKind, Function},
+        inline_fn,
+    };
 
-    fn simple_bool() -> ExprKind {
-        inline_expr! { true }
-    }
+    use super::*;
 
-    fn lower_simple_bool() -> (Vec<Instruction>, LoweringContext) {
+    fn lower_with_checks(code: &impl Lowerable) -> Vec<Instruction> {
+        let mut collector = CodeBuilder::default();
         let mut ctxt = LoweringContext::new();
-        let mut collector = Vec::new();
+        ctxt.add_debug_checks();
 
-        simple_bool().lower(&mut collector, &mut ctxt).unwrap();
+        code.lower(&mut collector, &mut ctxt).unwrap();
 
-        (collector, ctxt)
+        collector.into_instructions()
     }
 
     #[test]
-    fn generated_instructions() {
-        let (bytecode, _) = lower_simple_bool();
-        assert_eq!(bytecode, [Instruction::push_i(1)]);
+    fn arithmetic_is_checked() {
+        let power = ExprKind::exponentiation(ExprKind::integer(2), ExprKind::integer(3));
+        let instrs = lower_with_checks(&ExprKind::multiplication(ExprKind::integer(1), power));
+
+        assert_eq!(
+            instrs,
+            [
+                Instruction::push_i(1),
+                Instruction::push_i(2),
+                Instruction::push_i(3),
+                Instruction::check_overflow(ArithmeticOp::Pow),
+                Instruction::pow(),
+                Instruction::check_overflow(ArithmeticOp::Mul),
+                Instruction::mul(),
+            ]
+        );
     }
 
     #[test]
-    fn stack_effects() {
-        let (_, ctxt) = lower_simple_bool();
+    fn returns_check_the_frame() {
+        let f: Function = inline_fn! { fn f(a) { a - 1 } };
+        let instrs = lower_with_checks(&f);
 
-        assert_eq!(ctxt.stack().depth(), 1);
-        assert!(ctxt.stack().top().unwrap().is_empty());
+        // Variables are read relative to the frame, which needs no check.
+        assert_eq!(
+            instrs,
+            [
+                Instruction::load_local(-2),
+                Instruction::push_i(1),
+                Instruction::check_overflow(ArithmeticOp::Sub),
+                Instruction::neg(),
+                Instruction::add_i(),
+                Instruction::check_frame(1),
+                Instruction::pop_copy(3),
+                Instruction::ret(0, 2),
+            ]
+        );
     }
 }
@@ -31,6 +31,47 @@ macro_rules! parse_expr_inner {
         $crate::parse_if! { [ $( $tail )* ] $parsed }
     };
 
+    (
+        [ return $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $( $parsed )* $crate::node!(return_($crate::parse_expr! { $( $tail )* }))
+    };
+
+    (
+        [ $id:ident = $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $( $parsed )* $crate::node!(assignment(stringify!($id), $crate::parse_expr! { $( $tail )* }))
+    };
+
+    (
+        [ $id:ident [ $( $idx:tt )* ] = $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $( $parsed )* $crate::node!(index_assignment(
+            $crate::node!(ident(stringify!($id))),
+            $crate::parse_expr! { $( $idx )* },
+            $crate::parse_expr! { $( $tail )* }
+        ))
+    };
+
+    (
+        [ $id:ident [ $( $idx:tt )* ] $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_expr_inner! {
+            [ $( $tail )* ]
+            [
+                $( $parsed )*
+                $crate::node!(index(
+                    $crate::node!(ident(stringify!($id))),
+                    $crate::parse_expr! { $( $idx )* }
+                ))
+            ]
+        }
+    };
+
     (
         [ - $( $tail:tt )* ]
         [ $( $parsed:tt )* ]
@@ -51,6 +92,22 @@ macro_rules! parse_expr_inner {
         }
     };
 
+    (
+        [ $id:ident ( $( $args:tt )* ) $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_expr_inner! {
+            [ $( $tail )* ]
+            [
+                $( $parsed )*
+                $crate::node!(fn_call(
+                    stringify!($id),
+                    $crate::parse_fn_call_args! { [ $( $args )* ] [] [] },
+                ))
+            ]
+        }
+    };
+
     (
         [ $id:ident $( $tail:tt )* ]
         [ $( $parsed:tt )* ]
@@ -61,6 +118,39 @@ macro_rules! parse_expr_inner {
         }
     };
 
+    (
+        [ ( $( $inner:tt )* ) $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_expr_inner! {
+            [ $( $tail )* ]
+            [ $( $parsed )* $crate::parse_parenthesized! { [ $( $inner )* ] [] [] } ]
+        }
+    };
+
+    (
+        [ [ $( $inner:tt )* ] $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_expr_inner! {
+            [ $( $tail )* ]
+            [
+                $( $parsed )*
+                $crate::node!(array($crate::parse_fn_call_args! { [ $( $inner )* ] [] [] }))
+            ]
+        }
+    };
+
+    (
+        [ . $idx:literal $( $tail:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_expr_inner! {
+            [ $( $tail )* ]
+            [ $( $parsed )* .field($idx) ]
+        }
+    };
+
     (
         [ { $( $block_content:tt )* } $( $tail:tt )* ]
         [ $( $parsed:tt )* ]
@@ -82,6 +172,84 @@ macro_rules! parse_expr_inner {
     }
 }
 
+#[macro_export]
+macro_rules! parse_fn_call_args {
+    ( [] [] [ $( $args:expr, )* ] ) => {
+        [ $( $args, )* ]
+    };
+
+    ( [] [ $( $current:tt )+ ] [ $( $args:expr, )* ] ) => {
+        [ $( $args, )* $crate::parse_expr! { $( $current )+ }, ]
+    };
+
+    (
+        [ , $( $tail:tt )* ]
+        [ $( $current:tt )+ ]
+        [ $( $args:expr, )* ]
+    ) => {
+        $crate::parse_fn_call_args! {
+            [ $( $tail )* ]
+            []
+            [ $( $args, )* $crate::parse_expr! { $( $current )+ }, ]
+        }
+    };
+
+    (
+        [ $head:tt $( $tail:tt )* ]
+        [ $( $current:tt )* ]
+        $args:tt
+    ) => {
+        $crate::parse_fn_call_args! {
+            [ $( $tail )* ]
+            [ $( $current )* $head ]
+            $args
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! parse_parenthesized {
+    ( [] [] [] ) => {
+        $crate::node!(tuple([]))
+    };
+
+    ( [] [ $( $current:tt )+ ] [] ) => {
+        ( $crate::parse_expr! { $( $current )+ } )
+    };
+
+    ( [] [] [ $( $elements:expr, )+ ] ) => {
+        $crate::node!(tuple([ $( $elements, )+ ]))
+    };
+
+    ( [] [ $( $current:tt )+ ] [ $( $elements:expr, )+ ] ) => {
+        $crate::node!(tuple([ $( $elements, )+ $crate::parse_expr! { $( $current )+ }, ]))
+    };
+
+    (
+        [ , $( $tail:tt )* ]
+        [ $( $current:tt )+ ]
+        [ $( $elements:expr, )* ]
+    ) => {
+        $crate::parse_parenthesized! {
+            [ $( $tail )* ]
+            []
+            [ $( $elements, )* $crate::parse_expr! { $( $current )+ }, ]
+        }
+    };
+
+    (
+        [ $head:tt $( $tail:tt )* ]
+        [ $( $current:tt )* ]
+        $elements:tt
+    ) => {
+        $crate::parse_parenthesized! {
+            [ $( $tail )* ]
+            [ $( $current )* $head ]
+            $elements
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! parse_block {
     ( $( $tt:tt )* ) => {
@@ -91,23 +259,40 @@ macro_rules! parse_block {
 
 #[macro_export]
 macro_rules! parse_block_inner {
+    (
+        [ let mut $name:ident = $( $tt:tt )* ]
+        $parsed:tt
+    ) => {
+        $crate::parse_block_inner! {
+            @munching_expr [ $( $tt )* ]  [ $name true ]
+            $parsed
+        }
+    };
+
     (
         [ let $name:ident = $( $tt:tt )* ]
         $parsed:tt
     ) => {
-        crate::parse_block_inner! {
-            @munching_expr [ $( $tt )* ]  [ $name ]
+        $crate::parse_block_inner! {
+            @munching_expr [ $( $tt )* ]  [ $name false ]
             $parsed
         }
     };
 
     (
-        @munching_expr [ ; $( $tt:tt )* ] [ $name:ident $( $value:tt )* ]
+        @munching_expr [ ; $( $tt:tt )* ] [ $name:ident $mutable:literal $( $value:tt )* ]
         [ $( $parsed:tt )* ]
     ) => {
-        crate::parse_block_inner! {
+        $crate::parse_block_inner! {
             [ $( $tt )* ]
-            [ $( $parsed )* ($name, $( $value )* ) ]
+            [
+                $( $parsed )*
+                $crate::macros::expression::Stmt::Let(
+                    stringify!($name),
+                    $mutable,
+                    $crate::parse_expr! { $( $value )* },
+                ),
+            ]
         }
     };
 
@@ -115,25 +300,64 @@ macro_rules! parse_block_inner {
         @munching_expr [ $head:tt $( $tail:tt )* ] [ $( $current:tt )* ]
         $parsed:tt
     ) => {
-        crate::parse_block_inner! {
+        $crate::parse_block_inner! {
             @munching_expr [ $( $tail )* ] [ $( $current )* $head ]
             $parsed
         }
     };
 
     (
-        [ $( $tt:tt )* ]
-        [
-            $(
-                ( $key:ident, $( $value:tt )* )
-            )*
-        ]
+        @munching_stmt [ ; $( $tt:tt )* ] [ $( $stmt:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::parse_block_inner! {
+            [ $( $tt )* ]
+            [
+                $( $parsed )*
+                $crate::macros::expression::Stmt::Expr($crate::parse_expr! { $( $stmt )* }),
+            ]
+        }
+    };
+
+    (
+        @munching_stmt [ $head:tt $( $tail:tt )* ] [ $( $current:tt )* ]
+        $parsed:tt
+    ) => {
+        $crate::parse_block_inner! {
+            @munching_stmt [ $( $tail )* ] [ $( $current )* $head ]
+            $parsed
+        }
+    };
+
+    (
+        @munching_stmt [] []
+        [ $( $parsed:tt )* ]
+    ) => {
+        $crate::node!(block_with_statements(
+            [ $( $parsed )* ],
+            $crate::node!(tuple([])),
+        ))
+    };
+
+    (
+        @munching_stmt [] [ $( $ending:tt )* ]
+        [ $( $parsed:tt )* ]
     ) => {
-        $crate::node!(block(
-            [ $( (stringify!($key), $crate::parse_expr! { $( $value )* }) ),* ],
-            $crate::parse_expr! { $( $tt )* },
+        $crate::node!(block_with_statements(
+            [ $( $parsed )* ],
+            $crate::parse_expr! { $( $ending )* },
         ))
     };
+
+    (
+        [ $( $tt:tt )* ]
+        $parsed:tt
+    ) => {
+        $crate::parse_block_inner! {
+            @munching_stmt [ $( $tt )* ] []
+            $parsed
+        }
+    };
 }
 
 #[macro_export]
@@ -168,6 +392,35 @@ macro_rules! parse_if_inner {
         }
     };
 
+    (
+        [
+            { $( $cons:tt )* }
+            else
+            if $( $alt:tt )*
+        ]
+        [ $( $cond:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $( $parsed )*
+        $crate::node!(if_(
+            $crate::parse_expr! { $( $cond )* },
+            $crate::parse_block! { $( $cons )* },
+            $crate::parse_expr! { if $( $alt )* },
+        ))
+    };
+
+    (
+        [ { $( $cons:tt )* } ]
+        [ $( $cond:tt )* ]
+        [ $( $parsed:tt )* ]
+    ) => {
+        $( $parsed )*
+        $crate::node!(if_without_else(
+            $crate::parse_expr! { $( $cond )* },
+            $crate::parse_block! { $( $cons )* },
+        ))
+    };
+
     (
         [
             $tok:tt $( $tail:tt )*
@@ -183,30 +436,87 @@ macro_rules! parse_if_inner {
     };
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Stmt {
+    Let(&'static str, bool, Expr),
+    Expr(Expr),
+}
+
+impl From<Stmt> for ast::Statement {
+    fn from(stmt: Stmt) -> ast::Statement {
+        match stmt {
+            Stmt::Let(name, mutable, value) => {
+                let name = name.to_string();
+                let value = value.into();
+
+                if mutable {
+                    ast::Statement::Let(ast::Binding::new_mut(name, value))
+                } else {
+                    ast::Statement::Let(ast::Binding::new(name, value))
+                }
+            }
+
+            Stmt::Expr(expr) => ast::Statement::Expr(expr.into(), ast::Span::default()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Expr {
+    Array(Vec<Expr>),
+
     Addition {
         lhs: Box<Expr>,
         rhs: Box<Expr>,
     },
 
+    Assignment {
+        name: &'static str,
+        value: Box<Expr>,
+    },
+
     Block {
-        bindings: Vec<(&'static str, Expr)>,
+        statements: Vec<Stmt>,
         ending: Box<Expr>,
     },
 
     Bool(bool),
 
+    Field {
+        tuple: Box<Expr>,
+        idx: u16,
+    },
+
+    FnCall {
+        name: &'static str,
+        args: Vec<Expr>,
+    },
+
     Ident(&'static str),
 
+    Index {
+        array: Box<Expr>,
+        index: Box<Expr>,
+    },
+
+    IndexAssignment {
+        array: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+
+    Return(Box<Expr>),
+
     If {
         cond: Box<Expr>,
         cons: Box<Expr>,
-        alt: Box<Expr>,
+        alt: Option<Box<Expr>>,
     },
 
     Integer(i32),
 
+    String(&'static str),
+
     Multiplication {
         lhs: Box<Expr>,
         rhs: Box<Expr>,
@@ -216,6 +526,14 @@ pub(crate) enum Expr {
         lhs: Box<Expr>,
         rhs: Box<Expr>,
     },
+
+    Tuple(Vec<Expr>),
+}
+
+impl Expr {
+    pub(crate) fn field(self, idx: u16) -> Expr {
+        nodes::field(self, idx)
+    }
 }
 
 impl From<i32> for Expr {
@@ -224,6 +542,12 @@ impl From<i32> for Expr {
     }
 }
 
+impl From<&'static str> for Expr {
+    fn from(s: &'static str) -> Expr {
+        Expr::String(s)
+    }
+}
+
 impl From<bool> for Expr {
     fn from(b: bool) -> Expr {
         Expr::Bool(b)
@@ -271,30 +595,49 @@ impl From<Expr> for ast::ExprKind {
         match expr {
             Expr::Addition { lhs, rhs } => ast::ExprKind::addition((*lhs).into(), (*rhs).into()),
 
-            Expr::Block { bindings, ending } => {
-                if bindings.is_empty() {
-                    (*ending).into()
-                } else {
-                    ast::ExprKind::bindings(
-                        bindings
-                            .into_iter()
-                            .map(|(name, value)| ast::Binding::new(name.to_string(), value.into()))
-                            .collect(),
-                        (*ending).into(),
-                    )
-                }
+            Expr::Assignment { name, value } => {
+                ast::ExprKind::assignment(name.to_string(), (*value).into())
+            }
+
+            Expr::Block { statements, ending } => ast::ExprKind::block(
+                statements.into_iter().map(Into::into).collect(),
+                (*ending).into(),
+                ast::Span::default(),
+            ),
+
+            Expr::Array(elements) => {
+                ast::ExprKind::array(elements.into_iter().map(Into::into).collect())
             }
 
             Expr::Bool(b) => ast::ExprKind::bool_(b),
 
-            Expr::Ident(name) => ast::ExprKind::ident(name.to_string()),
+            Expr::Field { tuple, idx } => ast::ExprKind::field((*tuple).into(), idx),
 
-            Expr::If { cond, cons, alt } => {
-                ast::ExprKind::if_((*cond).into(), (*cons).into(), (*alt).into())
+            Expr::FnCall { name, args } => {
+                ast::ExprKind::fn_call(name.to_string(), args.into_iter().map(Into::into).collect())
             }
 
+            Expr::Ident(name) => ast::ExprKind::ident(name.to_string()),
+
+            Expr::Index { array, index } => ast::ExprKind::index((*array).into(), (*index).into()),
+
+            Expr::IndexAssignment {
+                array,
+                index,
+                value,
+            } => ast::ExprKind::index_assignment((*array).into(), (*index).into(), (*value).into()),
+
+            Expr::Return(value) => ast::ExprKind::return_((*value).into()),
+
+            Expr::If { cond, cons, alt } => match alt {
+                Some(alt) => ast::ExprKind::if_((*cond).into(), (*cons).into(), (*alt).into()),
+                None => ast::ExprKind::if_without_else((*cond).into(), (*cons).into()),
+            },
+
             Expr::Integer(value) => ast::ExprKind::integer(value),
 
+            Expr::String(value) => ast::ExprKind::string(value.to_string()),
+
             Expr::Multiplication { lhs, rhs } => {
                 ast::ExprKind::multiplication((*lhs).into(), (*rhs).into())
             }
@@ -302,6 +645,10 @@ impl From<Expr> for ast::ExprKind {
             Expr::Subtraction { lhs, rhs } => {
                 ast::ExprKind::subtraction((*lhs).into(), (*rhs).into())
             }
+
+            Expr::Tuple(elements) => {
+                ast::ExprKind::tuple(elements.into_iter().map(Into::into).collect())
+            }
         }
     }
 }
@@ -437,6 +784,146 @@ mod tests {
         assert_eq!(left, right);
     }
 
+    #[test]
+    fn fn_call_() {
+        let left = parse_expr! { f(1, a + b) };
+
+        let right = fn_call("f", [integer(1), addition(ident("a"), ident("b"))]);
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn fn_call_without_args() {
+        let left = parse_expr! { f() + 1 };
+
+        let right = addition(fn_call("f", []), integer(1));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn return_() {
+        let left = parse_expr! { return a + 1 };
+
+        let right = nodes::return_(addition(ident("a"), integer(1)));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn mutable_binding() {
+        let left = parse_block! {
+            let mut a = 1;
+            a = a + 1
+        };
+
+        let right = block_with_statements(
+            [Stmt::Let("a", true, integer(1))],
+            assignment("a", addition(ident("a"), integer(1))),
+        );
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn expression_statements() {
+        let left = parse_block! {
+            let a = 1;
+            f(a);
+            a
+        };
+
+        let right = block_with_statements(
+            [
+                Stmt::Let("a", false, integer(1)),
+                Stmt::Expr(fn_call("f", [ident("a")])),
+            ],
+            ident("a"),
+        );
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn string() {
+        let left = parse_expr! { "hello" };
+        let right = nodes::string("hello");
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn tuple() {
+        let left = parse_expr! { (1, a,) };
+        let right = nodes::tuple([integer(1), ident("a")]);
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn array() {
+        let left = parse_expr! { [1, a + 1] };
+        let right = nodes::array([integer(1), addition(ident("a"), integer(1))]);
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn index() {
+        let left = parse_expr! { a[0] + 1 };
+        let right = addition(nodes::index(ident("a"), integer(0)), integer(1));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn index_assignment() {
+        let left = parse_expr! { a[i] = 1 };
+        let right = nodes::index_assignment(ident("a"), ident("i"), integer(1));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn if_without_else() {
+        let left = parse_expr! { if a { b } };
+        let right = nodes::if_without_else(ident("a"), block([], ident("b")));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn else_if() {
+        let left = parse_expr! { if a { b } else if c { d } else { e } };
+        let right = if_(
+            ident("a"),
+            block([], ident("b")),
+            if_(ident("c"), block([], ident("d")), block([], ident("e"))),
+        );
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn parenthesized() {
+        let left = parse_expr! { (a + 1) * 2 };
+        let right = multiplication(addition(ident("a"), integer(1)), integer(2));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn field() {
+        let left = parse_expr! { (1, 2).1 + a.0 };
+        let right = addition(
+            nodes::field(nodes::tuple([integer(1), integer(2)]), 1),
+            nodes::field(ident("a"), 0),
+        );
+
+        assert_eq!(left, right);
+    }
+
     #[test]
     fn bindings_indirection_yeeting() {
         let left: ast::ExprKind = parse_expr! {
@@ -11,22 +11,31 @@ macro_rules! inline_fn {
 
 #[macro_export]
 macro_rules! parse_fn {
-    ( fn $name:ident() $body:tt  ) => {
-        $crate::node!(function(stringify!($name), $crate::parse_expr! { $body },))
+    ( fn $name:ident( $( $arg:ident ),* $(,)? ) $body:tt  ) => {
+        $crate::node!(function(
+            stringify!($name),
+            [ $( stringify!($arg) ),* ],
+            $crate::parse_expr! { $body },
+        ))
     };
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Function(pub(crate) &'static str, pub(crate) Expr);
+pub(crate) struct Function(
+    pub(crate) &'static str,
+    pub(crate) Vec<&'static str>,
+    pub(crate) Expr,
+);
 
 impl From<Function> for ast::Function {
     fn from(function: Function) -> ast::Function {
-        let Function(name, body) = function;
+        let Function(name, args, body) = function;
 
         let name = name.to_string();
+        let args = args.into_iter().map(str::to_string).collect();
         let body = ast::ExprKind::from(body);
 
-        ast::Function::new(name, body)
+        ast::Function::new(name, args, body)
     }
 }
 
@@ -42,7 +51,24 @@ mod tests {
             }
         };
 
-        let right = function("foo", block([], ident("bar")));
+        let right = function("foo", [], block([], ident("bar")));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn function_with_args() {
+        let left = parse_fn! {
+            fn add(a, b) {
+                a + b
+            }
+        };
+
+        let right = function(
+            "add",
+            ["a", "b"],
+            block([], addition(ident("a"), ident("b"))),
+        );
 
         assert_eq!(left, right);
     }
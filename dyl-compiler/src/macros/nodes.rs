@@ -1,4 +1,8 @@
-use super::{expression::Expr, function::Function, program::Program};
+use super::{
+    expression::{Expr, Stmt},
+    function::Function,
+    program::Program,
+};
 
 pub(crate) fn addition(lhs: Expr, rhs: Expr) -> Expr {
     let lhs = Box::new(lhs);
@@ -7,15 +11,53 @@ pub(crate) fn addition(lhs: Expr, rhs: Expr) -> Expr {
     Expr::Addition { lhs, rhs }
 }
 
+pub(crate) fn array<const N: usize>(elements: [Expr; N]) -> Expr {
+    Expr::Array(elements.to_vec())
+}
+
+pub(crate) fn assignment(name: &'static str, value: Expr) -> Expr {
+    let value = Box::new(value);
+
+    Expr::Assignment { name, value }
+}
+
 pub(crate) fn block<const N: usize>(bs: [(&'static str, Expr); N], ending: Expr) -> Expr {
-    let bindings = bs.to_vec();
+    let statements = bs
+        .into_iter()
+        .map(|(name, value)| Stmt::Let(name, false, value))
+        .collect();
     let ending = Box::new(ending);
 
-    Expr::Block { bindings, ending }
+    Expr::Block { statements, ending }
 }
 
-pub(crate) fn function(name: &'static str, body: Expr) -> Function {
-    Function(name, body)
+pub(crate) fn block_with_statements<const N: usize>(statements: [Stmt; N], ending: Expr) -> Expr {
+    let statements = statements.to_vec();
+    let ending = Box::new(ending);
+
+    Expr::Block { statements, ending }
+}
+
+pub(crate) fn field(tuple: Expr, idx: u16) -> Expr {
+    let tuple = Box::new(tuple);
+
+    Expr::Field { tuple, idx }
+}
+
+pub(crate) fn fn_call<const N: usize>(name: &'static str, args: [Expr; N]) -> Expr {
+    let args = args.to_vec();
+
+    Expr::FnCall { name, args }
+}
+
+pub(crate) fn function<const N: usize>(
+    name: &'static str,
+    args: [&'static str; N],
+    body: Expr,
+) -> Function {
+    let args = args.to_vec();
+
+    Function(name, args, body)
 }
 
 pub(crate) fn ident(name: &'static str) -> Expr {
@@ -25,11 +67,41 @@ pub(crate) fn ident(name: &'static str) -> Expr {
 pub(crate) fn if_(cond: Expr, cons: Expr, alt: Expr) -> Expr {
     let cond = Box::new(cond);
     let cons = Box::new(cons);
-    let alt = Box::new(alt);
+    let alt = Some(Box::new(alt));
 
     Expr::If { cond, cons, alt }
 }
 
+pub(crate) fn if_without_else(cond: Expr, cons: Expr) -> Expr {
+    let cond = Box::new(cond);
+    let cons = Box::new(cons);
+
+    Expr::If {
+        cond,
+        cons,
+        alt: None,
+    }
+}
+
+pub(crate) fn index(array: Expr, index: Expr) -> Expr {
+    let array = Box::new(array);
+    let index = Box::new(index);
+
+    Expr::Index { array, index }
+}
+
+pub(crate) fn index_assignment(array: Expr, index: Expr, value: Expr) -> Expr {
+    let array = Box::new(array);
+    let index = Box::new(index);
+    let value = Box::new(value);
+
+    Expr::IndexAssignment {
+        array,
+        index,
+        value,
+    }
+}
+
 pub(crate) fn integer(value: i32) -> Expr {
     Expr::Integer(value)
 }
@@ -54,6 +126,18 @@ pub(crate) fn program<const N: usize>(functions: [Function; N]) -> Program {
     Program(functions)
 }
 
+pub(crate) fn return_(value: Expr) -> Expr {
+    Expr::Return(Box::new(value))
+}
+
+pub(crate) fn string(value: &'static str) -> Expr {
+    Expr::String(value)
+}
+
+pub(crate) fn tuple<const N: usize>(elements: [Expr; N]) -> Expr {
+    Expr::Tuple(elements.to_vec())
+}
+
 pub(crate) fn subtraction(lhs: Expr, rhs: Expr) -> Expr {
     let lhs = Box::new(lhs);
     let rhs = Box::new(rhs);
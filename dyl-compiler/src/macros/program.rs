@@ -12,11 +12,11 @@ macro_rules! inline_program {
 #[macro_export]
 macro_rules! parse_program {
     ($(
-        fn $name:ident() $body:tt
+        fn $name:ident( $( $arg:ident ),* $(,)? ) $body:tt
     )*) => {
         $crate::node!(program([
             $(
-                $crate::inline_fn! { fn $name() $body },
+                $crate::inline_fn! { fn $name( $( $arg ),* ) $body },
             )*
         ]))
     };
@@ -28,9 +28,12 @@ pub(crate) struct Program(pub(crate) Vec<Function>);
 impl From<Program> for ast::Program {
     fn from(program: Program) -> ast::Program {
         let Program(functions) = program;
-        let functions = functions.into_iter().map(ast::Function::from).collect();
+        let items = functions
+            .into_iter()
+            .map(|f| ast::Item::Function(f.into()))
+            .collect();
 
-        ast::Program::new(functions)
+        ast::Program::new(items)
     }
 }
 
@@ -44,7 +47,7 @@ mod tests {
             fn main() { foo }
         };
 
-        let right = program([function("main", block([], ident("foo")))]);
+        let right = program([function("main", [], block([], ident("foo")))]);
 
         assert_eq!(left, right);
     }
@@ -57,8 +60,8 @@ mod tests {
         };
 
         let right = program([
-            function("a", block([], ident("foo"))),
-            function("b", block([], ident("bar"))),
+            function("a", [], block([], ident("foo"))),
+            function("b", [], block([], ident("bar"))),
         ]);
 
         assert_eq!(left, right);
@@ -72,7 +75,7 @@ mod tests {
             }
         };
 
-        let right = program([function("a", block([], integer(42)))]);
+        let right = program([function("a", [], block([], integer(42)))]);
 
         assert_eq!(left, right);
     }
@@ -0,0 +1,925 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    ast::{
+        Binding, BindingPattern, ExprKind, Function, Ident, Item, Logical, MatchArm, Pattern,
+        Program, Span, Type, VariantLiteral,
+    },
+    builtins::Builtin,
+    context::{ErrorContext, ParsingContext, PassResult},
+    diagnostic::{Code, Diagnostic},
+};
+
+/// How many generic functions may be instantiated from one another before
+/// giving up. This stops functions that call themselves with ever-growing
+/// types, such as `fn f<T>(x: T) { f((x, x)) }`.
+const MAX_INSTANTIATION_DEPTH: usize = 16;
+
+/// Replaces generic functions by one copy per instantiation.
+///
+/// The type arguments of a call are inferred from the types of its
+/// arguments, which are deduced from literals, type annotations and the
+/// return type of the functions they call. Each instance is named after its
+/// type arguments, for instance `id<int>`, and calls to generic functions
+/// made in its body are instantiated in turn. Generic functions that are
+/// never called produce no code.
+///
+/// Casts are checked along the way, as their operand types are known here.
+pub(crate) fn monomorphize(
+    program: &Program,
+    ctxt: ParsingContext,
+) -> PassResult<ParsingContext, Program> {
+    let rslt = monomorphize_program(program, ctxt.errors());
+
+    ctxt.wrap_result(rslt)
+}
+
+/// Parses a program, and takes it through the passes that run before the
+/// optimizations.
+#[cfg(test)]
+pub(crate) fn monomorphize_input(code: &str) -> Program {
+    let (ctxt, program) = crate::parser::parse_input(code).unwrap();
+    let (ctxt, program) = crate::resolution::resolve_paths(&program, ctxt).unwrap();
+    let (_, program) = monomorphize(&program, ctxt).unwrap();
+
+    program
+}
+
+/// Optimizes `code`, and checks that `main` ends up with the body of `main`
+/// in `expected`.
+#[cfg(test)]
+pub(crate) fn assert_optimizes_to(
+    optimize: impl Fn(&Program) -> Program,
+    code: &str,
+    expected: &str,
+) {
+    let optimized = optimize(&monomorphize_input(code));
+
+    assert_eq!(
+        optimized.main_body(),
+        monomorphize_input(expected).main_body()
+    );
+}
+
+fn monomorphize_program(program: &Program, errors: &ErrorContext) -> Result<Program, ()> {
+    let mut monomorphizer = Monomorphizer {
+        functions: program.functions().iter().map(|f| (f.name(), f)).collect(),
+        consts: program
+            .consts()
+            .iter()
+            .map(|c| (c.name(), c.value()))
+            .collect(),
+        consts_in_progress: Vec::new(),
+        instances: HashSet::new(),
+        pending: VecDeque::new(),
+        bindings: Vec::new(),
+        depth: 0,
+        failed: false,
+        errors,
+    };
+
+    let mut items = program
+        .structs()
+        .iter()
+        .cloned()
+        .map(Item::Struct)
+        .chain(program.enums().iter().cloned().map(Item::Enum))
+        .chain(program.consts().iter().cloned().map(Item::Const))
+        .collect::<Vec<_>>();
+
+    for f in program.functions().iter().filter(|f| !f.is_generic()) {
+        let function = monomorphizer.function(f, f.name().to_owned(), &[]);
+        items.push(Item::Function(function));
+    }
+
+    while let Some(instance) = monomorphizer.pending.pop_front() {
+        monomorphizer.depth = instance.depth;
+
+        let function = monomorphizer.function(instance.function, instance.name, &instance.bindings);
+        items.push(Item::Function(function));
+    }
+
+    if monomorphizer.failed {
+        Err(())
+    } else {
+        Ok(Program::new(items))
+    }
+}
+
+/// A generic function called with a given set of type arguments, whose body
+/// is yet to be generated.
+struct Instance<'a> {
+    name: String,
+    function: &'a Function,
+    bindings: Vec<(String, Type)>,
+    depth: usize,
+}
+
+struct Monomorphizer<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    consts: HashMap<&'a str, &'a ExprKind>,
+
+    /// The constants whose type is being computed. Cycles are reported by
+    /// the constant evaluator, they are simply left untyped here.
+    consts_in_progress: Vec<&'a str>,
+
+    /// The name of every instance created so far.
+    instances: HashSet<String>,
+    pending: VecDeque<Instance<'a>>,
+
+    /// The type arguments of the function being generated.
+    bindings: Vec<(String, Type)>,
+
+    /// The instantiation depth of the function being generated.
+    depth: usize,
+
+    failed: bool,
+    errors: &'a ErrorContext,
+}
+
+/// The variables in scope, with their type when it is known.
+type Scope = Vec<(String, Option<Type>)>;
+
+impl<'a> Monomorphizer<'a> {
+    /// Generates a function whose type parameters are replaced by the types
+    /// they are bound to.
+    fn function(&mut self, f: &Function, name: String, bindings: &[(String, Type)]) -> Function {
+        let arg_types = f
+            .arg_types()
+            .iter()
+            .map(|ty| ty.as_ref().map(|ty| ty.substitute(bindings)))
+            .collect::<Vec<_>>();
+        let ret_type = f.ret_type().map(|ty| ty.substitute(bindings));
+        self.bindings = bindings.to_vec();
+
+        let mut scope = f
+            .args()
+            .iter()
+            .cloned()
+            .zip(arg_types.iter().cloned())
+            .collect();
+        let (body, _) = self.expr(f.body(), &mut scope);
+
+        Function::new(name, f.args().to_vec(), body)
+            .with_signature(Vec::new(), arg_types, ret_type)
+            .with_span(f.span().clone())
+    }
+
+    /// Rewrites the calls to generic functions made in an expression, and
+    /// returns the type of the expression when it can be deduced. The
+    /// expression keeps its location.
+    fn expr(&mut self, expr: &ExprKind, scope: &mut Scope) -> (ExprKind, Option<Type>) {
+        let int = || Some(Type::named("int"));
+        let bool_ = || Some(Type::named("bool"));
+
+        let (rewritten, ty) = match expr {
+            ExprKind::Integer(_) => (expr.clone(), int()),
+            ExprKind::Bool(_) => (expr.clone(), bool_()),
+            ExprKind::String(_) => (expr.clone(), Some(Type::named("string"))),
+
+            ExprKind::Addition(a) => {
+                let (left, right) = self.pair(a.left(), a.right(), scope);
+                (ExprKind::addition(left, right), int())
+            }
+
+            ExprKind::Subtraction(s) => {
+                let (left, right) = self.pair(s.left(), s.right(), scope);
+                (ExprKind::subtraction(left, right), int())
+            }
+
+            ExprKind::Multiplication(m) => {
+                let (left, right) = self.pair(m.left(), m.right(), scope);
+                (ExprKind::multiplication(left, right), int())
+            }
+
+            ExprKind::Exponentiation(e) => {
+                let (left, right) = self.pair(e.left(), e.right(), scope);
+                (ExprKind::exponentiation(left, right), int())
+            }
+
+            ExprKind::Comparison(c) => {
+                let (left, right) = self.pair(c.left(), c.right(), scope);
+                let comparison = ExprKind::comparison(c.operator(), left, right);
+
+                (comparison, bool_())
+            }
+
+            ExprKind::Logical(l) => {
+                let (left, right) = self.pair(l.left(), l.right(), scope);
+                let logical = Logical::new(l.operator(), left, right);
+
+                (ExprKind::Logical(logical), bool_())
+            }
+
+            ExprKind::Cast(c) => {
+                let (value, value_ty) = self.expr(c.value(), scope);
+                let target = c.target().substitute(&self.bindings);
+
+                if !target.is_numeric() {
+                    self.error(
+                        Diagnostic::error(format!(
+                            "Cannot cast to `{}`, which is not a numeric type",
+                            target
+                        ))
+                        .with_code(Code::TypeMismatch)
+                        .with_span(expr.span().clone()),
+                    );
+                } else if let Some(value_ty) = value_ty.filter(|ty| !ty.can_cast_to(&target)) {
+                    self.error(
+                        Diagnostic::error(format!("Cannot cast `{}` as `{}`", value_ty, target))
+                            .with_code(Code::TypeMismatch)
+                            .with_span(expr.span().clone()),
+                    );
+                }
+
+                (ExprKind::cast(value, target.clone()), Some(target))
+            }
+
+            ExprKind::If(i) => {
+                let (condition, _) = self.expr(i.condition(), scope);
+                let (consequent, consequent_ty) = self.expr(i.consequent(), scope);
+
+                let (if_, ty) = match i.alternative() {
+                    Some(alternative) => {
+                        let (alternative, alternative_ty) = self.expr(alternative, scope);
+                        let ty = consequent_ty.or(alternative_ty);
+
+                        (ExprKind::if_(condition, consequent, alternative), ty)
+                    }
+                    None => (
+                        ExprKind::if_without_else(condition, consequent),
+                        Some(Type::Tuple(Vec::new())),
+                    ),
+                };
+
+                (if_, ty)
+            }
+
+            ExprKind::Bindings(b) => {
+                let scope_start = scope.len();
+
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .map(|binding| {
+                        let (value, ty) = self.expr(binding.value(), scope);
+                        let annotation =
+                            binding.annotation().map(|ty| ty.substitute(&self.bindings));
+
+                        // The annotation is checked against the value later
+                        // on, it is trusted here.
+                        let ty = annotation.clone().or(ty);
+                        self.bind_pattern(binding.pattern(), ty, binding.span(), scope);
+
+                        Binding::from_pattern(
+                            binding.pattern().clone(),
+                            value,
+                            binding.is_mutable(),
+                        )
+                        .with_annotation(annotation)
+                        .with_span(binding.span().clone())
+                    })
+                    .collect();
+
+                let (ending, ty) = self.expr(b.ending_expression(), scope);
+                scope.truncate(scope_start);
+
+                (b.rebuild(bindings, ending), ty)
+            }
+
+            ExprKind::Statements(s) => {
+                let statements = self.exprs(s.statements(), scope).0;
+                let (ending, ty) = self.expr(s.ending_expression(), scope);
+
+                (s.rebuild(statements, ending), ty)
+            }
+
+            ExprKind::Ident(i) => (expr.clone(), self.ident_type(i, scope)),
+
+            ExprKind::FnCall(c) => {
+                let (args, arg_types) = self.exprs(c.args(), scope);
+
+                // Local variables may hold a closure, whose type is unknown.
+                if is_local(c.name(), scope) {
+                    return (c.rebuild(c.name().to_owned(), args), None);
+                }
+
+                match self.functions.get(c.name()).copied() {
+                    Some(f) if f.is_generic() => match self.instantiate(f, &arg_types, c.span()) {
+                        Some((name, ty)) => (c.rebuild(name, args), ty),
+                        None => (c.rebuild(c.name().to_owned(), args), None),
+                    },
+
+                    Some(f) => (c.rebuild(c.name().to_owned(), args), f.ret_type().cloned()),
+
+                    None => (
+                        c.rebuild(c.name().to_owned(), args),
+                        Builtin::from_name(c.name())
+                            .and_then(|builtin| builtin.ret_type(&arg_types)),
+                    ),
+                }
+            }
+
+            ExprKind::Tuple(t) => {
+                let (elements, types) = self.exprs(t.elements(), scope);
+                let ty = types.into_iter().collect::<Option<_>>().map(Type::Tuple);
+
+                (ExprKind::tuple(elements), ty)
+            }
+
+            ExprKind::Field(f) => {
+                let (tuple, tuple_ty) = self.expr(f.tuple(), scope);
+                let ty = match tuple_ty {
+                    Some(Type::Tuple(elements)) => elements.get(f.idx() as usize).cloned(),
+                    _ => None,
+                };
+
+                (ExprKind::field(tuple, f.idx()), ty)
+            }
+
+            ExprKind::Array(a) => {
+                let (elements, types) = self.exprs(a.elements(), scope);
+                let ty = types.into_iter().flatten().next().map(Type::array);
+
+                (ExprKind::array(elements), ty)
+            }
+
+            ExprKind::Index(i) => {
+                let (array, array_ty) = self.expr(i.array(), scope);
+                let (index, _) = self.expr(i.index(), scope);
+                let ty = match array_ty {
+                    Some(Type::Array(element)) => Some(*element),
+                    _ => None,
+                };
+
+                (ExprKind::index(array, index), ty)
+            }
+
+            ExprKind::StructLiteral(s) => {
+                let fields = s
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.expr(value, scope).0))
+                    .collect();
+
+                (
+                    ExprKind::struct_literal(s.name().to_owned(), fields),
+                    Some(Type::named(s.name())),
+                )
+            }
+
+            ExprKind::VariantLiteral(v) => (
+                self.variant_literal(v, scope),
+                Some(Type::named(v.enum_name())),
+            ),
+
+            ExprKind::Match(m) => {
+                let (scrutinee, scrutinee_ty) = self.expr(m.scrutinee(), scope);
+
+                let mut ty = None;
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let scope_start = scope.len();
+
+                        // The type of variant fields is not declared.
+                        match arm.pattern() {
+                            Pattern::Binding(name) => {
+                                scope.push((name.clone(), scrutinee_ty.clone()))
+                            }
+                            pattern => scope.extend(
+                                pattern
+                                    .names()
+                                    .into_iter()
+                                    .map(|name| (name.to_owned(), None)),
+                            ),
+                        }
+
+                        let guard = arm.guard().map(|g| self.expr(g, scope).0);
+                        let (body, body_ty) = self.expr(arm.body(), scope);
+                        ty = ty.take().or(body_ty);
+                        scope.truncate(scope_start);
+
+                        MatchArm::new(arm.pattern().clone(), body)
+                            .with_guard(guard)
+                            .with_span(arm.span().clone())
+                    })
+                    .collect();
+
+                (ExprKind::match_(scrutinee, arms), ty)
+            }
+
+            ExprKind::Closure(c) => {
+                let scope_start = scope.len();
+                scope.extend(c.params().iter().map(|param| (param.clone(), None)));
+
+                let (body, _) = self.expr(c.body(), scope);
+                scope.truncate(scope_start);
+
+                (ExprKind::closure(c.params().to_vec(), body), None)
+            }
+
+            // Returns and assignments evaluate to no value the rest of the
+            // function could use.
+            _ => (expr.map_children(|child| self.expr(child, scope).0), None),
+        };
+
+        (rewritten.with_span(expr.span().clone()), ty)
+    }
+
+    fn exprs(
+        &mut self,
+        exprs: &[ExprKind],
+        scope: &mut Scope,
+    ) -> (Vec<ExprKind>, Vec<Option<Type>>) {
+        exprs.iter().map(|expr| self.expr(expr, scope)).unzip()
+    }
+
+    fn pair(
+        &mut self,
+        left: &ExprKind,
+        right: &ExprKind,
+        scope: &mut Scope,
+    ) -> (ExprKind, ExprKind) {
+        let (left, _) = self.expr(left, scope);
+        let (right, _) = self.expr(right, scope);
+
+        (left, right)
+    }
+
+    /// Adds the names a pattern defines to the scope, with the type of the
+    /// part of the value they are bound to when it is known.
+    fn bind_pattern(
+        &mut self,
+        pattern: &BindingPattern,
+        ty: Option<Type>,
+        span: &Span,
+        scope: &mut Scope,
+    ) {
+        match pattern {
+            BindingPattern::Name(name) => scope.push((name.clone(), ty)),
+            BindingPattern::Wildcard => {}
+
+            BindingPattern::Tuple(elements) => {
+                let types = match ty {
+                    Some(Type::Tuple(types)) if types.len() == elements.len() => types,
+
+                    Some(Type::Tuple(types)) => {
+                        self.error(
+                            Diagnostic::error(format!(
+                                "Cannot destructure `{}` with a pattern of {} elements",
+                                Type::Tuple(types),
+                                elements.len()
+                            ))
+                            .with_code(Code::TypeMismatch)
+                            .with_span(span.clone()),
+                        );
+                        Vec::new()
+                    }
+
+                    _ => Vec::new(),
+                };
+
+                let mut types = types.into_iter();
+                for element in elements {
+                    self.bind_pattern(element, types.next(), span, scope);
+                }
+            }
+
+            // The type of the fields is not declared.
+            BindingPattern::Struct { fields, .. } => fields
+                .iter()
+                .for_each(|(_, field)| self.bind_pattern(field, None, span, scope)),
+        }
+    }
+
+    fn variant_literal(&mut self, literal: &VariantLiteral, scope: &mut Scope) -> ExprKind {
+        let enum_name = literal.enum_name().to_owned();
+        let variant = literal.variant().to_owned();
+
+        if literal.is_parenthesized() {
+            let (args, _) = self.exprs(literal.args(), scope);
+            ExprKind::variant_literal(enum_name, variant, args)
+        } else {
+            ExprKind::unit_variant(enum_name, variant)
+        }
+    }
+
+    fn ident_type(&mut self, ident: &Ident, scope: &Scope) -> Option<Type> {
+        let name = ident.name();
+
+        if let Some((_, ty)) = scope.iter().rev().find(|(local, _)| local == name) {
+            return ty.clone();
+        }
+
+        if let Some((&name, &value)) = self.consts.get_key_value(name) {
+            if self.consts_in_progress.contains(&name) {
+                return None;
+            }
+
+            self.consts_in_progress.push(name);
+            let (_, ty) = self.expr(value, &mut Vec::new());
+            self.consts_in_progress.pop();
+
+            return ty;
+        }
+
+        if let Some(f) = self.functions.get(name) {
+            if f.is_generic() {
+                self.error(
+                    Diagnostic::error(format!(
+                        "Generic function `{}` cannot be used as a value",
+                        name
+                    ))
+                    .with_code(Code::TypeMismatch)
+                    .with_span(ident.span().clone()),
+                );
+            }
+        }
+
+        None
+    }
+
+    /// Returns the name of the instance of a generic function that matches
+    /// the types of the arguments it is called with, and its return type.
+    /// The instance is created if it does not exist yet.
+    fn instantiate(
+        &mut self,
+        f: &'a Function,
+        arg_types: &[Option<Type>],
+        span: &Span,
+    ) -> Option<(String, Option<Type>)> {
+        if f.args().len() != arg_types.len() {
+            self.error(
+                Diagnostic::error(format!(
+                    "Wrong number of arguments for `{}`: expected {}, found {}",
+                    f.name(),
+                    f.args().len(),
+                    arg_types.len()
+                ))
+                .with_code(Code::WrongArgumentCount)
+                .with_span(span.clone()),
+            );
+            return None;
+        }
+
+        let mut bindings = Vec::new();
+
+        for (param_ty, arg_ty) in f.arg_types().iter().zip(arg_types) {
+            if let (Some(param_ty), Some(arg_ty)) = (param_ty, arg_ty) {
+                if let Err((param, a, b)) = bind(param_ty, arg_ty, f.generics(), &mut bindings) {
+                    self.error(
+                        Diagnostic::error(format!(
+                            "Conflicting types for type parameter `{}` of `{}`: `{}` and `{}`",
+                            param,
+                            f.name(),
+                            a,
+                            b
+                        ))
+                        .with_code(Code::TypeMismatch)
+                        .with_span(span.clone()),
+                    );
+                    return None;
+                }
+            }
+        }
+
+        // The type arguments are listed in the order the parameters are
+        // declared.
+        let type_args = f
+            .generics()
+            .iter()
+            .map(|param| {
+                let bound = bindings.iter().find(|(bound, _)| bound == param);
+                match bound {
+                    Some((_, ty)) => Some(ty.clone()),
+                    None => {
+                        self.error(
+                            Diagnostic::error(format!(
+                                "Cannot infer type parameter `{}` of `{}`",
+                                param,
+                                f.name()
+                            ))
+                            .with_code(Code::TypeMismatch)
+                            .with_span(span.clone()),
+                        );
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        let type_args = type_args.into_iter().collect::<Option<Vec<_>>>()?;
+
+        let name = format!(
+            "{}<{}>",
+            f.name(),
+            type_args
+                .iter()
+                .map(Type::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let bindings = f
+            .generics()
+            .iter()
+            .cloned()
+            .zip(type_args)
+            .collect::<Vec<_>>();
+        let ret_type = f.ret_type().map(|ty| ty.substitute(&bindings));
+
+        if !self.instances.contains(&name) {
+            if self.depth == MAX_INSTANTIATION_DEPTH {
+                self.error(
+                    Diagnostic::error(format!(
+                        "Reached the instantiation depth limit when instantiating `{}`",
+                        name
+                    ))
+                    .with_span(span.clone()),
+                );
+                return None;
+            }
+
+            self.instances.insert(name.clone());
+            self.pending.push_back(Instance {
+                name: name.clone(),
+                function: f,
+                bindings,
+                depth: self.depth + 1,
+            });
+        }
+
+        Some((name, ret_type))
+    }
+
+    fn error(&mut self, error: impl Into<Diagnostic>) {
+        self.errors.add(error);
+        self.failed = true;
+    }
+}
+
+fn is_local(name: &str, scope: &Scope) -> bool {
+    scope.iter().any(|(local, _)| local == name)
+}
+
+/// Binds the type parameters that appear in the type of a parameter to the
+/// corresponding parts of the type of an argument.
+///
+/// Returns the parameter and its two types if it is bound to different
+/// types. Parts that don't match are not reported, as types are not
+/// checked yet.
+fn bind(
+    param_ty: &Type,
+    arg_ty: &Type,
+    generics: &[String],
+    bindings: &mut Vec<(String, Type)>,
+) -> Result<(), (String, Type, Type)> {
+    match (param_ty, arg_ty) {
+        (Type::Named(name), _) if generics.contains(name) => {
+            match bindings.iter().find(|(bound, _)| bound == name) {
+                Some((_, ty)) if ty != arg_ty => Err((name.clone(), ty.clone(), arg_ty.clone())),
+                Some(_) => Ok(()),
+                None => {
+                    bindings.push((name.clone(), arg_ty.clone()));
+                    Ok(())
+                }
+            }
+        }
+
+        (Type::Tuple(params), Type::Tuple(args)) if params.len() == args.len() => params
+            .iter()
+            .zip(args)
+            .try_for_each(|(param, arg)| bind(param, arg, generics, bindings)),
+
+        (Type::Array(param), Type::Array(arg)) => bind(param, arg, generics, bindings),
+
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser, resolution};
+
+    use super::*;
+
+    fn monomorphize(code: &str) -> (Result<Program, ()>, String) {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (_, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let errors = ErrorContext::default();
+
+        let rslt = monomorphize_program(&program, &errors);
+
+        (rslt, errors.to_string())
+    }
+
+    fn names(program: &Program) -> Vec<&str> {
+        program.functions().iter().map(Function::name).collect()
+    }
+
+    fn body<'a>(program: &'a Program, name: &str) -> &'a ExprKind {
+        program
+            .functions()
+            .iter()
+            .find(|f| f.name() == name)
+            .unwrap()
+            .body()
+    }
+
+    #[test]
+    fn one_instance_per_type() {
+        let (program, _) = monomorphize(
+            "fn id<T>(x: T) -> T { x } \
+             fn main() { (id(1), id(true), id(2), id(\"a\")) }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(
+            names(&program),
+            ["main", "id<int>", "id<bool>", "id<string>"]
+        );
+        assert_eq!(
+            body(&program, "main"),
+            &ExprKind::tuple(vec![
+                ExprKind::fn_call("id<int>".to_owned(), vec![ExprKind::integer(1)]),
+                ExprKind::fn_call("id<bool>".to_owned(), vec![ExprKind::bool_(true)]),
+                ExprKind::fn_call("id<int>".to_owned(), vec![ExprKind::integer(2)]),
+                ExprKind::fn_call(
+                    "id<string>".to_owned(),
+                    vec![ExprKind::string("a".to_owned())]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn unused_generic_function_is_dropped() {
+        let (program, _) = monomorphize("fn id<T>(x: T) -> T { x } fn main() { 1 }");
+
+        assert_eq!(names(&program.unwrap()), ["main"]);
+    }
+
+    #[test]
+    fn types_flow_through_variables_and_calls() {
+        let (program, _) = monomorphize(
+            "fn id<T>(x: T) -> T { x } fn first<T>(a: [T]) -> T { a[0] } \
+             struct P { x } fn make() -> P { P { x: 1 } } \
+             fn main() { let a = [(1, true)]; let p = make(); (first(a), id(id(p)), id(a[0].1)) }",
+        );
+
+        assert_eq!(
+            names(&program.unwrap()),
+            ["make", "main", "first<(int, bool)>", "id<P>", "id<bool>"]
+        );
+    }
+
+    #[test]
+    fn builtins_have_types() {
+        let (program, _) =
+            monomorphize("fn id<T>(x: T) -> T { x } fn main() { (id(read_int()), id(print(1))) }");
+
+        assert_eq!(names(&program.unwrap()), ["main", "id<int>", "id<()>"]);
+    }
+
+    #[test]
+    fn popped_elements_have_the_element_type() {
+        let (program, _) =
+            monomorphize("fn id<T>(x: T) -> T { x } fn main() { id(pop([true, false])) }");
+
+        assert_eq!(names(&program.unwrap()), ["main", "id<bool>"]);
+    }
+
+    #[test]
+    fn panic_does_not_constrain_types() {
+        let (program, _) = monomorphize(
+            "fn id<T>(x: T) -> T { x } fn main() { id(if 1 { panic(\"no\") } else { true }) }",
+        );
+
+        assert_eq!(names(&program.unwrap()), ["main", "id<bool>"]);
+    }
+
+    #[test]
+    fn casts_from_bool_and_int() {
+        let (program, errors) = monomorphize("fn main() { (true as int, 1 as int) }");
+
+        assert!(program.is_ok());
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn nonsensical_casts() {
+        let (program, errors) = monomorphize(
+            "struct P { x } fn main() { (\"1\" as int, 1 as bool, P { x: 1 } as int) }",
+        );
+
+        assert!(program.is_err());
+        assert_eq!(
+            errors,
+            "1:29: Cannot cast `string` as `int`\n\
+             1:41: Cannot cast to `bool`, which is not a numeric type\n\
+             1:52: Cannot cast `P` as `int`\n"
+        );
+    }
+
+    #[test]
+    fn casts_in_instances() {
+        let (_, errors) = monomorphize(
+            "fn to<T, U>(x: T, y: U) -> U { x as U } fn main() { (to(true, 1), to(\"a\", 1)) }",
+        );
+
+        assert_eq!(errors, "1:32: Cannot cast `string` as `int`\n");
+    }
+
+    #[test]
+    fn types_flow_through_destructuring() {
+        let (program, _) = monomorphize(
+            "fn id<T>(x: T) -> T { x } fn main() { let (a, (_, b)) = (1, (2, true)); (id(a), id(b)) }",
+        );
+
+        assert_eq!(names(&program.unwrap()), ["main", "id<int>", "id<bool>"]);
+    }
+
+    #[test]
+    fn destructuring_with_wrong_arity() {
+        let (_, errors) = monomorphize("fn main() { let (a, b) = (1, 2, 3); a }");
+
+        assert_eq!(
+            errors,
+            "1:17: Cannot destructure `(int, int, int)` with a pattern of 2 elements\n"
+        );
+    }
+
+    #[test]
+    fn instances_call_other_instances() {
+        let (program, _) = monomorphize(
+            "fn id<T>(x: T) -> T { x } fn twice<U>(x: U) -> (U, U) { (id(x), id(x)) } \
+             fn main() { twice([1]) }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(names(&program), ["main", "twice<[int]>", "id<[int]>"]);
+        assert_eq!(
+            program.functions()[1].arg_types(),
+            [Some(Type::array(Type::named("int")))]
+        );
+    }
+
+    #[test]
+    fn recursive_instance() {
+        let (program, errors) =
+            monomorphize("fn f<T>(x: T, n) { f(x, n - 1) } fn main() { f(1, 3) }");
+
+        assert_eq!(names(&program.unwrap()), ["main", "f<int>"]);
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn unbounded_instantiation() {
+        let (program, errors) = monomorphize("fn f<T>(x: T) { f((x, x)) } fn main() { f(1) }");
+
+        assert!(program.is_err());
+        assert!(errors
+            .starts_with("1:17: Reached the instantiation depth limit when instantiating `f<"));
+    }
+
+    #[test]
+    fn conflicting_types() {
+        let (program, errors) =
+            monomorphize("fn pair<T>(a: T, b: T) { (a, b) } fn main() { pair(1, true) }");
+
+        assert!(program.is_err());
+        assert_eq!(
+            errors,
+            "1:47: Conflicting types for type parameter `T` of `pair`: `int` and `bool`\n"
+        );
+    }
+
+    #[test]
+    fn type_parameter_cannot_be_inferred() {
+        let (program, errors) = monomorphize("fn f<T>(x) { x } fn main() { f(1) }");
+
+        assert!(program.is_err());
+        assert_eq!(errors, "1:30: Cannot infer type parameter `T` of `f`\n");
+    }
+
+    #[test]
+    fn wrong_number_of_arguments() {
+        let (_, errors) = monomorphize("fn id<T>(x: T) -> T { x } fn main() { id(1, 2) }");
+
+        assert_eq!(
+            errors,
+            "1:39: Wrong number of arguments for `id`: expected 1, found 2\n"
+        );
+    }
+
+    #[test]
+    fn generic_function_as_value() {
+        let (_, errors) = monomorphize("fn id<T>(x: T) -> T { x } fn main() { let f = id; f(1) }");
+
+        assert_eq!(
+            errors,
+            "1:47: Generic function `id` cannot be used as a value\n"
+        );
+    }
+}
@@ -1,404 +1,3681 @@
 use nom::{
     branch::alt,
-    bytes::complete::tag as nom_tag,
-    character::complete::{
-        alpha1 as nom_alpha1, alphanumeric1 as nom_alphanumeric1, digit1, multispace0,
-    },
-    combinator::{all_consuming, map, opt, recognize},
-    error::{Error as NomError, ErrorKind, ParseError},
-    multi::{fold_many1, many0, many1},
-    sequence::{delimited, pair, preceded, terminated, tuple},
-    Err, Parser,
+    bytes::complete::take,
+    character::complete::anychar,
+    combinator::{all_consuming, map, not, opt},
+    error::{Error as NomError, ErrorKind},
+    multi::{fold_many0, many0, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated},
+    Err, Parser, Slice,
 };
 use nom_locate::LocatedSpan;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
 
 use crate::{
-    ast::{Binding, ExprKind, Function, Program},
-    context::{ParsingContext, PassResult},
+    ast::{
+        ArithmeticOperator, Binding, BindingPattern, ComparisonOperator, Const, Enum, EnumVariant,
+        ExprKind, Function, Import, IndexAssignment, Item, Macro, MatchArm, Module, Pattern,
+        Program, Span, Statement, Struct, Type,
+    },
+    context::{ErrorContext, ParsingContext, PassResult},
+    diagnostic::{Code, Diagnostic},
+    io::SourceFile,
+    lexer::{self, TokenKind},
+    symbol::Symbol,
+    trivia::Comment,
 };
 
+/// Parses the content of several files, and merges them into a single
+/// program. Code is nested at most `nesting_limit` levels deep.
+pub(crate) fn parse_files(
+    files: &[SourceFile],
+    nesting_limit: usize,
+) -> PassResult<ParsingContext, Program> {
+    let parsing_ctxt = ParsingContext::new();
+
+    // The files do not depend on each other, so they are parsed in parallel,
+    // each with its own context. Every file is parsed, so that the errors of
+    // all of them are reported, in the order of the files.
+    let workers = match parser_threads(stack_size(nesting_limit)) {
+        Ok(workers) => workers,
+        Err(e) => {
+            parsing_ctxt.errors().add(Diagnostic::error(format!(
+                "Failed to start the parser threads: {}",
+                e
+            )));
+            return parsing_ctxt.wrap_result(Err(()));
+        }
+    };
+
+    let parsed = workers.install(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                let ctxt = ParsingContext::new().with_nesting_limit(nesting_limit);
+                let shared = Arc::new(file.clone());
+                let source = Source::new(&ctxt, Some(&shared));
+
+                let parsed = program(LocatedSpan::new_extra(file.content(), source));
+                (ctxt, parsed)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let programs = parsed
+        .into_iter()
+        .map(|(ctxt, parsed)| {
+            parsing_ctxt.merge(ctxt);
+            parsed
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<Result<Vec<_>, ()>>();
+
+    parsing_ctxt.wrap_result(programs.map(Program::merge))
+}
+
+/// Returns the threads that parse files, whose stacks have the given size.
+/// They are started the first time they are needed, and kept for the next
+/// programs.
+fn parser_threads(stack_size: usize) -> Result<Arc<ThreadPool>, ThreadPoolBuildError> {
+    static THREADS: OnceLock<Mutex<HashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+
+    let mut threads = THREADS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(workers) = threads.get(&stack_size) {
+        return Ok(workers.clone());
+    }
+
+    let workers = Arc::new(ThreadPoolBuilder::new().stack_size(stack_size).build()?);
+    threads.insert(stack_size, workers.clone());
+
+    Ok(workers)
+}
+
+/// Returns the stack a thread needs to parse, or compile, a program whose
+/// code is nested at most `depth` levels deep. The phases of the compiler
+/// walk the program recursively.
+pub(crate) fn stack_size(depth: usize) -> usize {
+    const BASE: usize = 4 * 1024 * 1024;
+    const PER_LEVEL: usize = 64 * 1024;
+
+    BASE + depth * PER_LEVEL
+}
+
+/// Returns the stack a thread needs to compile a program whose code is
+/// nested at most `nesting_limit` levels deep, and whose deepest code is
+/// `deepest` levels deep once the operations are counted.
+pub(crate) fn compiler_stack_size(nesting_limit: usize, deepest: usize) -> usize {
+    // The phases walk down an operand in fewer steps than into a nesting
+    // level.
+    const PER_OPERATION: usize = 32 * 1024;
+
+    stack_size(nesting_limit) + deepest * PER_OPERATION
+}
+
+#[cfg(test)]
 pub(crate) fn parse_input(input_code: &str) -> PassResult<ParsingContext, Program> {
     let parsing_ctxt = ParsingContext::new();
-    let input = LocatedSpan::new_extra(input_code, &parsing_ctxt);
+    let input = LocatedSpan::new_extra(input_code, Source::new(&parsing_ctxt, None));
 
     let parsed = program(input);
 
     parsing_ctxt.wrap_result(parsed)
 }
 
-type Input<'a> = LocatedSpan<&'a str, &'a ParsingContext>;
+/// The code being parsed, along with the context errors are reported to.
+#[derive(Clone, Copy, Debug)]
+struct Source<'a> {
+    ctxt: &'a ParsingContext,
+
+    /// The file the code comes from, if any.
+    file: Option<&'a Arc<SourceFile>>,
+}
+
+impl<'a> Source<'a> {
+    fn new(ctxt: &'a ParsingContext, file: Option<&'a Arc<SourceFile>>) -> Source<'a> {
+        Source { ctxt, file }
+    }
+
+    fn errors(&self) -> &'a ErrorContext {
+        self.ctxt.errors()
+    }
+}
+
+type Input<'a> = LocatedSpan<&'a str, Source<'a>>;
 type IResult<'a, O, E = NomError<Input<'a>>> = nom::IResult<Input<'a>, O, E>;
 
 fn program(input: Input) -> Result<Program, ()> {
-    program_with_tail(input).map(|(_, ast)| ast).map_err(drop)
+    program_with_tail(input)
+        .map(|(_, ast)| ast)
+        .map_err(|e| match e {
+            Err::Error(e) => report_error(e.input, "Syntax error"),
+            // The parsers that fail for good report why before.
+            Err::Failure(_) => {}
+            Err::Incomplete(_) => unreachable!("Complete parsers never return `Incomplete`"),
+        })
 }
 
 fn program_with_tail(input: Input) -> IResult<Program> {
-    map(all_consuming(many0(function)), Program::new)(input)
+    map(
+        all_consuming(delimited(trivia, many0(item), trivia)),
+        Program::new,
+    )(input)
 }
 
-fn function(input: Input) -> IResult<Function> {
-    let (tail, _) = keyword("fn")(input)?;
-    let (tail, name) = ident(tail)?;
+fn item(input: Input) -> IResult<Item> {
+    alt((
+        map(function, Item::Function),
+        map(spanned(struct_), |(s, span)| {
+            Item::Struct(s.with_span(span))
+        }),
+        map(spanned(enum_), |(e, span)| Item::Enum(e.with_span(span))),
+        map(spanned(const_), |(c, span)| Item::Const(c.with_span(span))),
+        map(spanned(module), |(m, span)| Item::Module(m.with_span(span))),
+        map(spanned(import), |(i, span)| Item::Import(i.with_span(span))),
+        map(spanned(macro_), |(m, span)| Item::Macro(m.with_span(span))),
+    ))(input)
+}
 
-    let (tail, _) = left_par(tail)?;
+fn module(input: Input) -> IResult<Module> {
+    let (tail, _) = keyword("mod")(input)?;
+    let (tail, name) = declared_name(tail)?;
+    let (tail, items) = delimited(left_curly, nested(many0(item)), right_curly)(tail)?;
 
-    let (tail, _) = right_par(tail)?;
+    Ok((tail, Module::new(name, items)))
+}
 
-    let (tail, body) = block(tail)?;
+fn import(input: Input) -> IResult<Import> {
+    let (tail, _) = keyword("import")(input)?;
+    let (tail, path) = terminated(separated_list1(double_colon, ident), semicolon)(tail)?;
 
-    let function = Function::new(name, body);
+    Ok((tail, Import::new(path)))
+}
 
-    Ok((tail, function))
+/// Parses a path to an item, such as `math::square`.
+fn path(input: Input) -> IResult<String> {
+    map(separated_list1(double_colon, ident), |segments| {
+        segments.join("::")
+    })(input)
 }
 
-fn block(input: Input) -> IResult<ExprKind> {
-    delimited(left_curly, alt((bindings, expr)), right_curly)(input)
+fn const_(input: Input) -> IResult<Const> {
+    let (tail, _) = keyword("const")(input)?;
+    let (tail, name) = declared_name(tail)?;
+    let (tail, _) = equal(tail)?;
+    let (tail, value) = terminated(expr, semicolon)(tail)?;
+
+    Ok((tail, Const::new(name, value)))
 }
 
-fn expr(input: Input) -> IResult<ExprKind> {
-    alt((level_0_expression, level_1_expression, atomic_expr))(input)
+fn macro_(input: Input) -> IResult<Macro> {
+    let (tail, _) = keyword("macro")(input)?;
+    let (tail, name) = declared_name(tail)?;
+    let (tail, params) = delimited(
+        left_par,
+        terminated(separated_list0(comma, spanned_declared_name), opt(comma)),
+        right_par,
+    )(tail)?;
+    report_duplicate_names(input, &params);
+
+    let (tail, _) = fat_arrow(tail)?;
+    let (tail, body) = terminated(expr, semicolon)(tail)?;
+
+    Ok((tail, Macro::new(name, names_of(params), body)))
 }
 
-fn integer(input: Input) -> IResult<ExprKind> {
-    let maybe_minus = opt(tag("-"));
+fn struct_(input: Input) -> IResult<Struct> {
+    let (tail, _) = keyword("struct")(input)?;
+    let (tail, name) = declared_name(tail)?;
 
-    map(
-        space_insignificant(recognize(tuple((maybe_minus, digit1)))),
-        |i| ExprKind::integer(i.fragment().parse().unwrap()),
-    )(input)
+    let (tail, fields) = delimited(
+        left_curly,
+        terminated(separated_list0(comma, declared_name), opt(comma)),
+        right_curly,
+    )(tail)?;
+
+    Ok((tail, Struct::new(name, fields)))
 }
 
-fn level_0_expression(input: Input) -> IResult<ExprKind> {
-    let (tail, first) = alt((level_1_expression, atomic_expr))(input)?;
+fn function(input: Input) -> IResult<Function> {
+    let (tail, _) = keyword("fn")(input)?;
+    let (tail, (name, span)) = spanned_declared_name(tail)?;
+
+    let (tail, generics) = opt(delimited(
+        left_angle,
+        terminated(separated_list1(comma, spanned_declared_name), opt(comma)),
+        right_angle,
+    ))(tail)?;
+    let generics = generics.unwrap_or_default();
+    report_duplicate_names(input, &generics);
+
+    let (tail, args) = delimited(
+        left_par,
+        separated_list0(
+            comma,
+            pair(spanned_declared_name, opt(preceded(colon, type_))),
+        ),
+        right_par,
+    )(tail)?;
+
+    let (args, arg_types): (Vec<_>, _) = args.into_iter().unzip();
+    report_duplicate_names(input, &args);
+
+    let (tail, ret_type) = opt(preceded(arrow, type_))(tail)?;
+
+    let (tail, body) = block(tail)?;
+
+    let function = Function::new(name, names_of(args), body)
+        .with_signature(names_of(generics), arg_types, ret_type)
+        .with_span(span);
 
-    fold_many1(
-        tuple((level_0_operator, alt((level_1_expression, atomic_expr)))),
-        first,
-        |left, (operator, right)| operator.make_expr(left, right),
-    )(tail)
+    Ok((tail, function))
 }
 
-fn level_0_operator(input: Input) -> IResult<Level0Operator> {
-    map(alt((tag("+"), tag("-"))), |operator| match operator {
-        "+" => Level0Operator::Plus,
-        "-" => Level0Operator::Minus,
-        _ => unreachable!(),
-    })(input)
+fn type_(input: Input) -> IResult<Type> {
+    nested(alt((
+        map(path, Type::Named),
+        map(delimited(left_bracket, type_, right_bracket), Type::array),
+        tuple_type,
+    )))(input)
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Level0Operator {
-    Plus,
-    Minus,
+fn tuple_type(input: Input) -> IResult<Type> {
+    let (tail, _) = left_par(input)?;
+    let (tail, elements) = separated_list0(comma, type_)(tail)?;
+    let (tail, trailing_comma) = opt(comma)(tail)?;
+    let (tail, _) = right_par(tail)?;
+
+    // `(T)` is a parenthesized type, `(T,)` is a tuple of one element.
+    let ty = match elements.as_slice() {
+        [element] if trailing_comma.is_none() => element.clone(),
+        _ => Type::Tuple(elements),
+    };
+
+    Ok((tail, ty))
 }
 
-impl Level0Operator {
-    fn make_expr(self, lhs: ExprKind, rhs: ExprKind) -> ExprKind {
-        let expression_maker = match self {
-            Level0Operator::Plus => ExprKind::addition,
-            Level0Operator::Minus => ExprKind::subtraction,
-        };
+fn block(input: Input) -> IResult<ExprKind> {
+    let (tail, (block, errors)) = delimited(left_curly, block_body, right_curly)(input)?;
+    report_all(input, errors);
 
-        expression_maker(lhs, rhs)
-    }
+    Ok((tail, block))
 }
 
-fn level_1_expression(input: Input) -> IResult<ExprKind> {
-    let (tail, first) = atomic_expr(input)?;
-    fold_many1(tuple((star, atomic_expr)), first, |lhs, (_, rhs)| {
-        ExprKind::multiplication(lhs, rhs)
-    })(tail)
+fn expr(input: Input) -> IResult<ExprKind> {
+    nested(assignment_or_binary_expr)(input)
 }
 
-fn star(input: Input) -> IResult<()> {
-    map(space_insignificant(tag("*")), drop)(input)
+/// Parses code that other code contains, unless it is nested so deep that
+/// the parser would overflow its stack.
+///
+/// Each syntactic construct that can contain itself counts as one level:
+/// expressions, statements that end with a block, local functions, modules,
+/// types and patterns.
+fn nested<'a, O>(
+    mut parser: impl FnMut(Input<'a>) -> IResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> IResult<'a, O> {
+    move |input| {
+        enter_nested(input)?;
+        let parsed = parser(input);
+        input.extra.ctxt.leave_nested();
+
+        parsed
+    }
 }
 
-fn if_else(input: Input) -> IResult<ExprKind> {
-    let (tail, _) = if_(input)?;
-    let (tail, condition) = expr(tail)?;
-    let (tail, consequent) = block(tail)?;
-    let (tail, _) = else_(tail)?;
-    let (tail, alternative) = block(tail)?;
+/// Enters a level of nesting, or reports that the program is nested too
+/// deep. Each level that is entered must be left.
+fn enter_nested(input: Input) -> Result<(), Err<NomError<Input>>> {
+    let ctxt = input.extra.ctxt;
+    if ctxt.enter_nested() {
+        return Ok(());
+    }
 
-    let if_ = ExprKind::if_(condition, consequent, alternative);
-    Ok((tail, if_))
+    let error = Diagnostic::error("Program too deeply nested").with_note(format!(
+        "Code can be nested at most {} levels deep",
+        ctxt.nesting_limit()
+    ));
+    report(input, error);
+
+    Err(Err::Failure(NomError::new(input, ErrorKind::TooLarge)))
 }
 
-fn bindings(input: Input) -> IResult<ExprKind> {
-    let (tail, bs) = many1(binding)(input)?;
-    let (tail, ending) = expr(tail)?;
-    let bindings = ExprKind::bindings(bs, ending);
+/// Parses an expression that may assign to a variable or to an element of
+/// an array.
+///
+/// The target of the assignment is parsed once, and becomes the left operand
+/// of a binary expression if no assignment operator follows it. Parsing it
+/// again would take exponential time on nested parentheses.
+fn assignment_or_binary_expr(input: Input) -> IResult<ExprKind> {
+    let (start, ()) = trivia(input)?;
+    let (tail, target) = atomic_expr(start)?;
+
+    let (after_assignment, assignment) = match &target {
+        ExprKind::Ident(i) if !i.name().contains("::") => {
+            let name = i.name();
+
+            opt(alt((
+                map(preceded(assignment_operator, expr), |value| {
+                    ExprKind::assignment(name.to_owned(), value)
+                }),
+                map(pair(compound_operator, expr), |(operator, value)| {
+                    ExprKind::compound_assignment(operator, name.to_owned(), value)
+                }),
+            )))(tail)?
+        }
+
+        ExprKind::Index(target) => opt(map(preceded(assignment_operator, expr), |value| {
+            ExprKind::IndexAssignment(IndexAssignment::new(target.clone(), value))
+        }))(tail)?,
+
+        _ => (tail, None),
+    };
+
+    match assignment {
+        Some(assignment) => {
+            let span = span_between(start, after_assignment);
+            Ok((after_assignment, assignment.with_span(span)))
+        }
 
-    Ok((tail, bindings))
+        None => binary_operations(target, tail, 0),
+    }
 }
 
-fn binding(input: Input) -> IResult<Binding> {
-    let (tail, name) = delimited(let_, ident, expect(equal, epsilon_recover("`=`")))(input)?;
-    let (tail, value) = terminated(expr, expect(semicolon, epsilon_recover("`;`")))(tail)?;
-    Ok((tail, Binding::new(name, value)))
+/// Parses the `=` of an assignment, which is not the start of `==` or `=>`.
+fn assignment_operator(input: Input) -> IResult<()> {
+    // `==` and `=>` are tokens of their own.
+    equal(input)
 }
 
-fn atomic_expr(input: Input) -> IResult<ExprKind> {
-    alt((integer, if_else, block, bool_expr, ident_expr))(input)
+fn compound_operator(input: Input) -> IResult<ArithmeticOperator> {
+    let operators = [
+        ArithmeticOperator::Add,
+        ArithmeticOperator::Subtract,
+        ArithmeticOperator::Multiply,
+        ArithmeticOperator::Exponentiate,
+    ];
+
+    operators
+        .into_iter()
+        .find_map(|operator| {
+            let rslt: IResult<_> =
+                space_insignificant(punctuation(operator.assignment_token()))(input);
+            rslt.ok().map(|(tail, _)| (tail, operator))
+        })
+        .ok_or_else(|| Err::Error(NomError::new(input, ErrorKind::Tag)))
 }
 
-fn ident_expr(input: Input) -> IResult<ExprKind> {
-    let (tail, name) = ident(input)?;
-    Ok((tail, ExprKind::ident(name)))
+fn integer(input: Input) -> IResult<ExprKind> {
+    map(space_insignificant(integer_literal), ExprKind::integer)(input)
 }
 
-fn bool_expr(input: Input) -> IResult<ExprKind> {
-    alt((true_expr, false_expr))(input)
+/// Parses an integer token, along with the minus sign written right before
+/// it.
+fn integer_literal(input: Input) -> IResult<i32> {
+    let (tail, minus) = opt(punctuation("-"))(input)?;
+    let (tail, token) = token_of(TokenKind::Integer)(tail)?;
+
+    let literal = consumed(input, tail);
+    let (radix, digits) = split_radix(token.fragment());
+
+    // Malformed literals are reported, and replaced by a dummy value so that
+    // parsing can continue.
+    let value = parse_digits(digits, radix, minus.is_some())
+        .map_err(|e| report_error(input, e.message(literal, radix).as_str()))
+        .unwrap_or(0);
+
+    Ok((tail, value))
 }
 
-fn true_expr(input: Input) -> IResult<ExprKind> {
-    map(true_, |()| ExprKind::bool_(true))(input)
+fn consumed<'a>(input: Input<'a>, tail: Input<'a>) -> &'a str {
+    let len = input.fragment().len() - tail.fragment().len();
+    &input.fragment()[..len]
 }
 
-fn false_expr(input: Input) -> IResult<ExprKind> {
-    map(false_, |()| ExprKind::bool_(false))(input)
+fn strip_underscores(digits: &str) -> Result<String, IntegerLiteralError> {
+    if digits.starts_with('_') {
+        Err(IntegerLiteralError::LeadingUnderscore)
+    } else if digits.ends_with('_') {
+        Err(IntegerLiteralError::TrailingUnderscore)
+    } else {
+        Ok(digits.replace('_', ""))
+    }
 }
 
-fn ident(input: Input) -> IResult<String> {
-    let (tail, name) = space_insignificant(recognize(pair(
-        alt((alpha1, tag("_"))),
-        many0(alt((alphanumeric1, tag("_")))),
-    )))(input)?;
+/// Splits an integer token into its radix and its digits.
+fn split_radix(token: &str) -> (Radix, &str) {
+    [Radix::Hexadecimal, Radix::Octal, Radix::Binary]
+        .into_iter()
+        .find_map(|radix| {
+            token
+                .strip_prefix(radix.prefix())
+                .map(|digits| (radix, digits))
+        })
+        .unwrap_or((Radix::Decimal, token))
+}
+
+// The sign of a literal is part of the literal itself. As such, the range of
+// accepted values is the range of `i32`, and `-2147483648` is a valid literal
+// while `2147483648` is not.
+fn parse_digits(digits: &str, radix: Radix, negative: bool) -> Result<i32, IntegerLiteralError> {
+    if digits.is_empty() {
+        return Err(IntegerLiteralError::MissingDigits);
+    }
+
+    let digits = strip_underscores(digits)?;
+
+    if let Some(invalid) = digits.chars().find(|c| !c.is_digit(radix.base())) {
+        return Err(IntegerLiteralError::InvalidDigit(invalid));
+    }
 
-    Ok((tail, name.to_string()))
+    let magnitude =
+        i64::from_str_radix(&digits, radix.base()).map_err(|_| IntegerLiteralError::Overflow)?;
+    let value = if negative { -magnitude } else { magnitude };
+
+    i32::try_from(value).map_err(|_| IntegerLiteralError::Overflow)
 }
 
-fn if_(input: Input) -> IResult<()> {
-    keyword("if")(input)
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
 }
 
-fn else_(input: Input) -> IResult<()> {
-    keyword("else")(input)
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Radix::Binary => "0b",
+            Radix::Octal => "0o",
+            Radix::Decimal => "",
+            Radix::Hexadecimal => "0x",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Radix::Binary => "binary",
+            Radix::Octal => "octal",
+            Radix::Decimal => "decimal",
+            Radix::Hexadecimal => "hexadecimal",
+        }
+    }
 }
 
-fn let_(input: Input) -> IResult<()> {
-    keyword("let")(input)
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum IntegerLiteralError {
+    MissingDigits,
+    InvalidDigit(char),
+    LeadingUnderscore,
+    TrailingUnderscore,
+    Overflow,
 }
 
-fn true_(input: Input) -> IResult<()> {
-    keyword("true")(input)
+impl IntegerLiteralError {
+    fn message(self, literal: &str, radix: Radix) -> String {
+        match self {
+            IntegerLiteralError::MissingDigits => {
+                format!("Missing digits after the `{}` prefix", radix.prefix())
+            }
+            IntegerLiteralError::InvalidDigit(digit) => {
+                format!("Invalid digit `{}` in {} literal", digit, radix.name())
+            }
+            IntegerLiteralError::LeadingUnderscore => {
+                format!(
+                    "Integer literal `{}` must not start with an underscore",
+                    literal
+                )
+            }
+            IntegerLiteralError::TrailingUnderscore => {
+                format!(
+                    "Integer literal `{}` must not end with an underscore",
+                    literal
+                )
+            }
+            IntegerLiteralError::Overflow => {
+                format!("Integer literal `{}` does not fit in 32 bits", literal)
+            }
+        }
+    }
 }
 
-fn false_(input: Input) -> IResult<()> {
-    keyword("false")(input)
+fn string(input: Input) -> IResult<ExprKind> {
+    map(space_insignificant(string_literal), ExprKind::string)(input)
 }
 
-fn equal(input: Input) -> IResult<()> {
-    map(space_insignificant(tag("=")), drop)(input)
+fn string_literal(input: Input) -> IResult<String> {
+    let (tail, (kind, token)) = token(input)?;
+
+    match kind {
+        TokenKind::String => {}
+        TokenKind::Error if token.fragment().starts_with('"') => {
+            report_error(input, "Unterminated string literal");
+            return Err(Err::Failure(NomError::new(tail, ErrorKind::Char)));
+        }
+        _ => return Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+
+    let quoted = token.fragment();
+    let mut content = String::new();
+    let mut chars = quoted[1..quoted.len() - 1].char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        if c != '\\' {
+            content.push(c);
+            continue;
+        }
+
+        // The lexer never ends a string right after a backslash.
+        if let Some((_, escaped)) = chars.next() {
+            match unescape(escaped) {
+                Some(unescaped) => content.push(unescaped),
+                None => {
+                    let message = format!("Unknown character escape `\\{}`", escaped);
+                    report_error(token.slice(idx + 1..), message.as_str());
+                }
+            }
+        }
+    }
+
+    Ok((tail, content))
 }
 
-fn semicolon(input: Input) -> IResult<()> {
-    map(space_insignificant(tag(";")), drop)(input)
+fn unescape(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        _ => None,
+    }
 }
 
-fn keyword(kw: &str) -> impl Fn(Input) -> IResult<()> + '_ {
+/// Parses operands separated by binary operators whose precedence is at
+/// least `min_precedence`, using precedence climbing.
+///
+/// Each operand is parsed as an atomic expression. After an operator, the
+/// right-hand side only extends over operators that bind tighter, or as
+/// tight for right-associative ones.
+fn binary_expr(min_precedence: u8) -> impl Fn(Input) -> IResult<ExprKind> {
     move |input| {
-        let (tail, _) = map(preceded(multispace0, tag(kw)), drop)(input)?;
-        let next_is_alphabetic = tail
-            .chars()
-            .next()
-            .map(char::is_alphabetic)
-            .unwrap_or(false);
-
-        if next_is_alphabetic {
-            Err(Err::Error(NomError::new(input, ErrorKind::Tag)))
+        let (tail, lhs) = atomic_expr(input)?;
+        binary_operations(lhs, tail, min_precedence)
+    }
+}
+
+/// Parses the operators and operands that follow the left operand of a
+/// binary expression, as long as the operators bind at least as tight as
+/// `min_precedence`.
+///
+/// The operations are parsed in a loop, each one becoming the left operand
+/// of the next. Only the right operand of a right-associative operator is
+/// parsed recursively, so it counts as a level of nesting, which is left
+/// once the whole chain is parsed.
+fn binary_operations<'a>(
+    lhs: ExprKind,
+    tail: Input<'a>,
+    min_precedence: u8,
+) -> IResult<'a, ExprKind> {
+    let mut levels = 0;
+    let parsed = binary_chain(lhs, tail, min_precedence, &mut levels);
+
+    for _ in 0..levels {
+        tail.extra.ctxt.leave_nested();
+    }
+
+    parsed
+}
+
+fn binary_chain<'a>(
+    mut lhs: ExprKind,
+    mut tail: Input<'a>,
+    min_precedence: u8,
+    levels: &mut usize,
+) -> IResult<'a, ExprKind> {
+    let mut previous: Option<&BinaryOperator> = None;
+
+    while let (after_operator, Some(operator)) = opt(binary_operator)(tail)? {
+        if operator.precedence < min_precedence {
+            break;
+        }
+
+        let is_chained = previous.is_some_and(|previous| {
+            previous.associativity == Associativity::None
+                && previous.precedence == operator.precedence
+        });
+
+        // The chain is parsed as if it were left-associative, so that
+        // the rest of the code is checked as well.
+        if is_chained {
+            report_error(
+                tail,
+                "Comparison operators cannot be chained, \
+                 use `&&` to combine them, as in `a < b && b < c`",
+            );
+        }
+
+        let rhs_min_precedence = match operator.associativity {
+            Associativity::Left | Associativity::None => operator.precedence + 1,
+            Associativity::Right => operator.precedence,
+        };
+
+        if operator.associativity == Associativity::Right {
+            enter_nested(after_operator)?;
+            *levels += 1;
         } else {
-            let (tail, _) = multispace0(tail)?;
-            Ok((tail, ()))
+            tail.extra.ctxt.nest_in_operation();
         }
+
+        let (after_rhs, rhs) = binary_expr(rhs_min_precedence)(after_operator)?;
+        let (_, operator_span) = token_span(tail)?;
+        let operator_span = operator_span.with_length(operator.token.len());
+
+        lhs = (operator.make_expr)(lhs, rhs).with_span(operator_span);
+        tail = after_rhs;
+        previous = Some(operator);
     }
+
+    Ok((tail, lhs))
 }
 
-fn left_curly(input: Input) -> IResult<()> {
-    map(space_insignificant(tag("{")), drop)(input)
+fn binary_operator(input: Input) -> IResult<&'static BinaryOperator> {
+    let (tail, token) = space_insignificant(token_of(TokenKind::Punctuation))(input)?;
+
+    let operator = BINARY_OPERATORS
+        .iter()
+        .find(|operator| operator.token == *token.fragment())
+        .ok_or_else(|| Err::Error(NomError::new(input, ErrorKind::Tag)))?;
+
+    Ok((tail, operator))
 }
 
-fn right_curly(input: Input) -> IResult<()> {
-    map(space_insignificant(tag("}")), drop)(input)
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Associativity {
+    Left,
+    Right,
+
+    /// The operator can not follow another operator of the same precedence
+    /// without parentheses. Only comparison operators are non-associative.
+    None,
 }
 
-fn left_par(input: Input) -> IResult<()> {
-    map(space_insignificant(tag("(")), drop)(input)
+struct BinaryOperator {
+    token: &'static str,
+
+    /// Operators with a higher precedence bind tighter.
+    precedence: u8,
+    associativity: Associativity,
+    make_expr: fn(ExprKind, ExprKind) -> ExprKind,
 }
 
-fn right_par(input: Input) -> IResult<()> {
-    map(space_insignificant(tag(")")), drop)(input)
+/// Every binary operator of the language.
+const BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator {
+        token: "||",
+        precedence: 1,
+        associativity: Associativity::Left,
+        make_expr: ExprKind::or,
+    },
+    BinaryOperator {
+        token: "&&",
+        precedence: 2,
+        associativity: Associativity::Left,
+        make_expr: ExprKind::and,
+    },
+    BinaryOperator {
+        token: "==",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::Equal, l, r),
+    },
+    BinaryOperator {
+        token: "!=",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::NotEqual, l, r),
+    },
+    BinaryOperator {
+        token: "<=",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::LessOrEqual, l, r),
+    },
+    BinaryOperator {
+        token: "<",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::Less, l, r),
+    },
+    BinaryOperator {
+        token: ">=",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::GreaterOrEqual, l, r),
+    },
+    BinaryOperator {
+        token: ">",
+        precedence: 3,
+        associativity: Associativity::None,
+        make_expr: |l, r| ExprKind::comparison(ComparisonOperator::Greater, l, r),
+    },
+    BinaryOperator {
+        token: "+",
+        precedence: 4,
+        associativity: Associativity::Left,
+        make_expr: ExprKind::addition,
+    },
+    BinaryOperator {
+        token: "-",
+        precedence: 4,
+        associativity: Associativity::Left,
+        make_expr: ExprKind::subtraction,
+    },
+    BinaryOperator {
+        token: "**",
+        precedence: 6,
+        associativity: Associativity::Right,
+        make_expr: ExprKind::exponentiation,
+    },
+    BinaryOperator {
+        token: "*",
+        precedence: 5,
+        associativity: Associativity::Left,
+        make_expr: ExprKind::multiplication,
+    },
+];
+
+fn if_else(input: Input) -> IResult<ExprKind> {
+    let (tail, _) = if_(input)?;
+    let (tail, condition) = expr(tail)?;
+    let (tail, consequent) = block(tail)?;
+    let (tail, alternative) = opt(preceded(else_, nested(alt((located(if_else), block)))))(tail)?;
+
+    let if_ = match alternative {
+        Some(alternative) => ExprKind::if_(condition, consequent, alternative),
+        None => ExprKind::if_without_else(condition, consequent),
+    };
+
+    Ok((tail, if_))
 }
 
-fn space_insignificant<'a, O, E>(
-    parser: impl Parser<Input<'a>, O, E>,
-) -> impl FnMut(Input<'a>) -> IResult<'a, O, E>
-where
-    E: ParseError<Input<'a>>,
-{
-    delimited(multispace0, parser, multispace0)
+#[cfg(test)]
+fn block_content(input: Input) -> IResult<ExprKind> {
+    let (tail, (block, errors)) = block_body(input)?;
+    report_all(input, errors);
+
+    Ok((tail, block))
 }
 
-fn expect<O, P, R>(mut parser: P, mut recovery: R) -> impl FnMut(Input) -> IResult<Option<O>>
-where
-    P: FnMut(Input) -> IResult<O>,
-    R: FnMut(Input, ErrorKind) -> Option<Input>,
-{
-    move |input| match parser(input) {
-        Ok((tail, data)) => Ok((tail, Some(data))),
-        Err(Err::Incomplete(_)) => panic!("Parser returned Incomplete variant"),
-        Err(Err::Error(NomError { input, code })) | Err(Err::Failure(NomError { input, code })) => {
-            match recovery(input, code) {
-                Some(tail) => Ok((tail, None)),
-                None => Ok((input, None)),
+/// Parses the content of a block, along with the errors of the statements
+/// that are skipped because they are invalid.
+///
+/// These errors are only reported once the end of the block is parsed, as
+/// the block may be part of an alternative that turns out to be wrong, in
+/// which case the statements are not actually invalid.
+///
+/// The value of a block is its ending expression. Expressions followed by a
+/// semicolon are statements, whose value is dropped. Expressions ending with
+/// a block, such as `if` and `match`, are statements as well when they are
+/// not at the end of the block, even without a semicolon. Using one as the
+/// operand of a binary operator at the start of a statement thus requires
+/// parentheses.
+fn block_body(input: Input) -> IResult<(ExprKind, Vec<Diagnostic>)> {
+    let (start, ()) = trivia(input)?;
+    let (tail, mut items) = many0(alt((
+        map(nested(function), BlockItem::Function),
+        map(semicolon, |_| BlockItem::Empty),
+        map(
+            pair(spanned(block_like_expr), opt(semicolon)),
+            |((expr, span), semicolon)| match semicolon {
+                Some(_) => BlockItem::Statement(Statement::Expr(expr, span)),
+                None => BlockItem::BlockLike(expr, span),
+            },
+        ),
+        map(statement, BlockItem::Statement),
+        invalid_statement,
+    )))(start)?;
+
+    let (tail, (ending_span, ending)) = pair(token_span, opt(spanned(expr)))(tail)?;
+
+    // A block that does not end with an expression evaluates to `()`.
+    let (ending, ending_span) = match ending {
+        Some(ending) => ending,
+        None => match items.pop() {
+            Some(BlockItem::BlockLike(expr, span)) => (expr, span),
+            Some(item) => {
+                items.push(item);
+                (ExprKind::unit(), ending_span)
             }
+            None => (ExprKind::unit(), ending_span),
+        },
+    };
+
+    let mut functions = Vec::new();
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    for item in items {
+        match item {
+            BlockItem::Function(function) => functions.push(function),
+            BlockItem::Statement(statement) => statements.push(statement),
+            BlockItem::BlockLike(expr, span) => statements.push(Statement::Expr(expr, span)),
+            BlockItem::Invalid(error) => errors.push(error),
+            BlockItem::Empty => {}
         }
     }
+
+    let block = ExprKind::block(statements, ending, ending_span);
+
+    if functions.is_empty() {
+        return Ok((tail, (block, errors)));
+    }
+
+    let local_functions = ExprKind::local_functions(functions, block);
+    let block = local_functions.with_span(span_between(start, tail));
+
+    Ok((tail, (block, errors)))
 }
 
-fn epsilon_recover(token: &str) -> impl Fn(Input, ErrorKind) -> Option<Input> + '_ {
-    move |input, _| {
-        let line = input.location_line();
-        let col = input.get_utf8_column();
+/// Something written in a block, before its ending expression.
+enum BlockItem {
+    Function(Function),
+    Statement(Statement),
 
-        let message = format!("{}:{}: Excepted {}", line, col, token);
-        input.extra.errors().add(message);
+    /// An expression ending with a block, not followed by a semicolon, along
+    /// with its location. It is the ending expression of the block if nothing
+    /// follows it.
+    BlockLike(ExprKind, Span),
 
-        Some(input)
+    /// A lone semicolon.
+    Empty,
+
+    /// Code that is not a statement, which is skipped, along with the error
+    /// to report.
+    Invalid(Diagnostic),
+}
+
+/// Recovers from code that is not a statement: it is skipped up to the next
+/// `;`, which is skipped as well, or up to the next `let` or `}`. The rest
+/// of the block is then parsed, so that its errors are reported too.
+///
+/// Fails where the block ends, and before its ending expression.
+fn invalid_statement(input: Input) -> IResult<BlockItem> {
+    let (start, ()) = trivia(input)?;
+
+    let ends_block =
+        |input: Input| input.fragment().is_empty() || input.fragment().starts_with('}');
+    let is_ending_expr = matches!(
+        terminated(expr, trivia)(start),
+        Ok((tail, _)) if ends_block(tail)
+    );
+
+    if ends_block(start) || is_ending_expr {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Verify)));
     }
+
+    let mut end = start;
+    let tail = loop {
+        let is_next_statement =
+            end.location_offset() > start.location_offset() && keyword("let")(end).is_ok();
+
+        if ends_block(end) || is_next_statement {
+            break end;
+        }
+
+        if let Ok((tail, ())) = semicolon(end) {
+            break tail;
+        }
+
+        let (tail, ()) = skip_token(end)?;
+        (end, ()) = trivia(tail)?;
+    };
+
+    let error = Diagnostic::error("Syntax error")
+        .with_code(Code::SyntaxError)
+        .with_span(span_between(start, end));
+
+    Ok((tail, BlockItem::Invalid(error)))
 }
 
-fn tag(t: &str) -> impl FnMut(Input) -> IResult<&str> + '_ {
-    move |input: Input| {
-        map(nom_tag(t), |matched: LocatedSpan<&str, _>| {
-            *matched.fragment()
-        })(input)
+/// Skips a word, a string, a group of tokens between brackets, or else a
+/// single character.
+fn skip_token(input: Input) -> IResult<()> {
+    let closing = match input.fragment().chars().next() {
+        Some('{') => '}',
+        Some('(') => ')',
+        Some('[') => ']',
+        _ => return alt((map(string_literal, drop), map(token, drop)))(input),
+    };
+
+    let (mut tail, _) = anychar(input)?;
+
+    loop {
+        (tail, ()) = trivia(tail)?;
+
+        match tail.fragment().chars().next() {
+            Some(c) if c == closing => return map(anychar, drop)(tail),
+            None | Some(')' | ']' | '}') => return Ok((tail, ())),
+            Some(_) => (tail, ()) = skip_token(tail)?,
+        }
     }
 }
 
-fn alphanumeric1(input: Input) -> IResult<&str> {
-    map(nom_alphanumeric1, |matched: LocatedSpan<&str, _>| {
-        *matched.fragment()
-    })(input)
+/// Parses an expression that ends with a block.
+fn block_like_expr(input: Input) -> IResult<ExprKind> {
+    nested(alt((located(if_else), located(match_expr), block)))(input)
 }
 
-fn alpha1(input: Input) -> IResult<&str> {
-    map(nom_alpha1, |matched: LocatedSpan<&str, _>| {
-        *matched.fragment()
-    })(input)
+fn statement(input: Input) -> IResult<Statement> {
+    alt((
+        map(binding, Statement::Let),
+        map(terminated(spanned(expr), semicolon), |(expr, span)| {
+            Statement::Expr(expr, span)
+        }),
+    ))(input)
 }
 
-#[cfg(test)]
-fn parse_and_own<O>(
-    f: impl Fn(Input) -> IResult<O>,
-    input: &str,
-) -> (Result<O, Err<NomError<()>>>, ParsingContext) {
-    let parsing_ctxt = ParsingContext::new();
-    let input = LocatedSpan::new_extra(input, &parsing_ctxt);
-    let parsing_status = f(input).map_err(own_nom_err).map(|(_, parsed)| parsed);
+fn binding(input: Input) -> IResult<Binding> {
+    let (tail, (mutable, ((pattern, names), pattern_span))) =
+        preceded(let_, pair(opt(mut_), spanned(binding_pattern)))(input)?;
+    report_duplicate_names(input, &names);
 
-    (parsing_status, parsing_ctxt)
+    let (tail, annotation) = terminated(
+        opt(preceded(colon, type_)),
+        expect(equal, epsilon_recover("=")),
+    )(tail)?;
+    let (tail, value) = terminated(expr, expect(semicolon, epsilon_recover(";")))(tail)?;
+
+    let binding = Binding::from_pattern(pattern, value, mutable.is_some())
+        .with_annotation(annotation)
+        .with_span(pattern_span);
+
+    Ok((tail, binding))
 }
 
-#[cfg(test)]
-fn own_nom_err(err: Err<nom::error::Error<Input>>) -> Err<nom::error::Error<()>> {
-    match err {
-        Err::Error(e) => Err::Error(own_nom_error(e)),
-        Err::Failure(f) => Err::Failure(own_nom_error(f)),
-        Err::Incomplete(needed) => Err::Incomplete(needed),
-    }
+/// Parses a pattern of a `let`, along with the names it binds.
+fn binding_pattern(input: Input) -> IResult<(BindingPattern, Vec<BoundName>)> {
+    nested(alt((
+        tuple_binding_pattern,
+        struct_binding_pattern,
+        map(keyword("_"), |()| (BindingPattern::Wildcard, Vec::new())),
+        map(spanned_declared_name, |bound| {
+            (BindingPattern::Name(bound.0.clone()), vec![bound])
+        }),
+    )))(input)
 }
 
-#[cfg(test)]
-fn own_nom_error(err: NomError<Input>) -> NomError<()> {
-    let NomError { code, .. } = err;
+fn tuple_binding_pattern(input: Input) -> IResult<(BindingPattern, Vec<BoundName>)> {
+    let (tail, _) = left_par(input)?;
+    let (tail, elements) = separated_list0(comma, binding_pattern)(tail)?;
+    let (tail, trailing_comma) = opt(comma)(tail)?;
+    let (tail, _) = right_par(tail)?;
+
+    let (elements, names): (Vec<_>, Vec<_>) = elements.into_iter().unzip();
+    let names = names.into_iter().flatten().collect();
+
+    // `(a)` is a parenthesized pattern, `(a,)` is a tuple of one element.
+    let pattern = match elements.as_slice() {
+        [element] if trailing_comma.is_none() => element.clone(),
+        _ => BindingPattern::Tuple(elements),
+    };
+
+    Ok((tail, (pattern, names)))
+}
+
+/// Parses `P { x, y: pattern }`, where `x` is short for `x: x`.
+fn struct_binding_pattern(input: Input) -> IResult<(BindingPattern, Vec<BoundName>)> {
+    let (tail, name) = ident(input)?;
+
+    let field = map(
+        pair(spanned_declared_name, opt(preceded(colon, binding_pattern))),
+        |(field, pattern)| match pattern {
+            Some((pattern, names)) => ((field.0, pattern), names),
+            None => (
+                (field.0.clone(), BindingPattern::Name(field.0.clone())),
+                vec![field],
+            ),
+        },
+    );
+    let (tail, fields) = delimited(
+        left_curly,
+        terminated(separated_list0(comma, field), opt(comma)),
+        right_curly,
+    )(tail)?;
+
+    let (fields, names): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+    let names = names.into_iter().flatten().collect();
+
+    Ok((tail, (BindingPattern::Struct { name, fields }, names)))
+}
+
+/// Returns the location of the next token, skipping the trivia before it.
+fn token_span(input: Input) -> IResult<Span> {
+    let (tail, ()) = trivia(input)?;
+
+    Ok((tail, span_of(tail)))
+}
+
+/// Returns the location `input` starts at, which is empty.
+fn span_of(input: Input) -> Span {
+    let span = Span::new(input.location_line(), input.get_utf8_column())
+        .with_offset(input.location_offset());
+
+    match input.extra.file {
+        Some(file) => span.in_file(Arc::clone(file)),
+        None => span,
+    }
+}
+
+/// Returns the location of the code between `start` and `end`, without the
+/// whitespace at its end.
+fn span_between(start: Input, end: Input) -> Span {
+    let length = consumed(start, end).trim_end().len();
+
+    span_of(start).with_length(length)
+}
+
+/// Runs a parser, and returns the location of the code it parsed along with
+/// its output. The trivia around the code is not part of the location.
+fn spanned<'a, O>(
+    mut parser: impl FnMut(Input<'a>) -> IResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> IResult<'a, (O, Span)> {
+    move |input| {
+        let (start, ()) = trivia(input)?;
+        let (tail, output) = parser(start)?;
+
+        Ok((tail, (output, span_between(start, tail))))
+    }
+}
+
+/// Runs an expression parser, and sets the location of the expression to
+/// the code it parsed.
+fn located<'a>(
+    parser: impl FnMut(Input<'a>) -> IResult<'a, ExprKind>,
+) -> impl FnMut(Input<'a>) -> IResult<'a, ExprKind> {
+    map(spanned(parser), |(expr, span)| expr.with_span(span))
+}
+
+fn atomic_expr(input: Input) -> IResult<ExprKind> {
+    let (start, ()) = trivia(input)?;
+    let (mut tail, mut expr) = primary_expr(start)?;
+
+    while let (after_postfix, Some(postfix)) = opt(postfix)(tail)? {
+        expr = match postfix {
+            Postfix::Field(idx) => ExprKind::field(expr, idx),
+            Postfix::Index(index) => ExprKind::index(expr, index),
+            Postfix::Cast(target) => ExprKind::cast(expr, target),
+        }
+        .with_span(span_between(start, after_postfix));
+
+        tail.extra.ctxt.nest_in_operation();
+        tail = after_postfix;
+    }
+
+    Ok((tail, expr))
+}
+
+enum Postfix {
+    Field(u16),
+    Index(ExprKind),
+    Cast(Type),
+}
+
+fn postfix(input: Input) -> IResult<Postfix> {
+    alt((
+        map(preceded(dot, field_index), Postfix::Field),
+        map(delimited(left_bracket, expr, right_bracket), Postfix::Index),
+        map(preceded(as_, type_), Postfix::Cast),
+    ))(input)
+}
+
+fn field_index(input: Input) -> IResult<u16> {
+    let (tail, idx) = space_insignificant(token_of(TokenKind::Integer))(input)?;
+
+    let idx = idx.fragment().parse().unwrap_or_else(|_| {
+        report_error(
+            idx,
+            format!("Invalid tuple index `{}`", idx.fragment()).as_str(),
+        );
+        0
+    });
+
+    Ok((tail, idx))
+}
+
+fn parenthesized_expr(input: Input) -> IResult<ExprKind> {
+    let (start, ()) = trivia(input)?;
+    let (tail, _) = left_par(start)?;
+    let (tail, first) = expr(tail)?;
+    let (tail, others) = many0(preceded(comma, expr))(tail)?;
+    let (tail, trailing_comma) = opt(comma)(tail)?;
+    let (tail, _) = right_par(tail)?;
+
+    // A single expression without trailing comma is a simple grouping, not a
+    // tuple.
+    let expr = if others.is_empty() && trailing_comma.is_none() {
+        first
+    } else {
+        let mut elements = vec![first];
+        elements.extend(others);
+        ExprKind::tuple(elements).with_span(span_between(start, tail))
+    };
+
+    Ok((tail, expr))
+}
+
+fn enum_(input: Input) -> IResult<Enum> {
+    let (tail, _) = keyword("enum")(input)?;
+    let (tail, name) = declared_name(tail)?;
+
+    let (tail, variants) = delimited(
+        left_curly,
+        terminated(separated_list0(comma, enum_variant), opt(comma)),
+        right_curly,
+    )(tail)?;
+
+    Ok((tail, Enum::new(name, variants)))
+}
+
+fn enum_variant(input: Input) -> IResult<EnumVariant> {
+    let (tail, ((name, fields), span)) = spanned(pair(
+        declared_name,
+        opt(delimited(
+            left_par,
+            terminated(separated_list0(comma, declared_name), opt(comma)),
+            right_par,
+        )),
+    ))(input)?;
+
+    let variant = EnumVariant::new(name, fields.unwrap_or_default()).with_span(span);
+
+    Ok((tail, variant))
+}
+
+fn variant_literal(input: Input) -> IResult<ExprKind> {
+    let (tail, (enum_name, variant)) = pair(ident, preceded(double_colon, ident))(input)?;
+
+    // Longer paths can only refer to items declared in modules.
+    let (tail, _) = not(double_colon)(tail)?;
+
+    let (tail, args) = opt(delimited(
+        left_par,
+        terminated(separated_list0(comma, expr), opt(comma)),
+        right_par,
+    ))(tail)?;
+
+    let literal = match args {
+        Some(args) => ExprKind::variant_literal(enum_name, variant, args),
+        None => ExprKind::unit_variant(enum_name, variant),
+    };
+
+    Ok((tail, literal))
+}
+
+fn match_expr(input: Input) -> IResult<ExprKind> {
+    let (tail, _) = keyword("match")(input)?;
+    let (tail, scrutinee) = expr(tail)?;
+    let (tail, arms) = delimited(left_curly, many0(spanned(match_arm)), right_curly)(tail)?;
+    let arms = arms
+        .into_iter()
+        .map(|(arm, span)| arm.with_span(span))
+        .collect();
+
+    Ok((tail, ExprKind::match_(scrutinee, arms)))
+}
+
+fn match_arm(input: Input) -> IResult<MatchArm> {
+    let (tail, (pattern, names)) = pattern(input)?;
+    report_duplicate_names(input, &names);
+
+    let (tail, guard) = opt(preceded(if_, expr))(tail)?;
+    let (tail, body) = preceded(fat_arrow, expr)(tail)?;
+    let (tail, _) = opt(comma)(tail)?;
+
+    Ok((tail, MatchArm::new(pattern, body).with_guard(guard)))
+}
+
+/// Parses the pattern of a match arm, along with the names it binds.
+fn pattern(input: Input) -> IResult<(Pattern, Vec<BoundName>)> {
+    nested(alt((
+        map(keyword("_"), |()| (Pattern::Wildcard, Vec::new())),
+        map(space_insignificant(integer_literal), |integer| {
+            (Pattern::Integer(integer), Vec::new())
+        }),
+        variant_pattern,
+        map(spanned_declared_name, |bound| {
+            (Pattern::Binding(bound.0.clone()), vec![bound])
+        }),
+    )))(input)
+}
+
+fn variant_pattern(input: Input) -> IResult<(Pattern, Vec<BoundName>)> {
+    let (tail, (enum_name, variant)) = pair(ident, preceded(double_colon, ident))(input)?;
+    let (tail, fields) = opt(delimited(
+        left_par,
+        terminated(separated_list0(comma, pattern), opt(comma)),
+        right_par,
+    ))(tail)?;
+
+    let (fields, names): (Vec<_>, Vec<_>) = fields.unwrap_or_default().into_iter().unzip();
+    let names = names.into_iter().flatten().collect();
+
+    Ok((tail, (Pattern::variant(enum_name, variant, fields), names)))
+}
+
+fn struct_literal(input: Input) -> IResult<ExprKind> {
+    let (tail, name) = ident(input)?;
+
+    let (tail, fields) = delimited(
+        left_curly,
+        terminated(
+            separated_list0(comma, pair(ident, preceded(colon, expr))),
+            opt(comma),
+        ),
+        right_curly,
+    )(tail)?;
+
+    Ok((tail, ExprKind::struct_literal(name, fields)))
+}
+
+fn array_expr(input: Input) -> IResult<ExprKind> {
+    let (tail, elements) = delimited(
+        left_bracket,
+        terminated(separated_list0(comma, expr), opt(comma)),
+        right_bracket,
+    )(input)?;
+
+    Ok((tail, ExprKind::array(elements)))
+}
+
+/// Parses an expression that is not an operation.
+///
+/// Blocks and parenthesized expressions are located by the expression they
+/// contain.
+fn primary_expr(input: Input) -> IResult<ExprKind> {
+    alt((
+        located(integer),
+        located(string),
+        located(if_else),
+        located(match_expr),
+        located(return_expr),
+        located(closure),
+        located(unit_expr),
+        parenthesized_expr,
+        located(array_expr),
+        block,
+        located(bool_expr),
+        located(variant_literal),
+        located(struct_literal),
+        located(macro_call),
+        located(fn_call),
+        located(ident_expr),
+    ))(input)
+}
+
+fn return_expr(input: Input) -> IResult<ExprKind> {
+    let (tail, value) = preceded(return_, opt(expr))(input)?;
+    let value = value.unwrap_or_else(ExprKind::unit);
+
+    Ok((tail, ExprKind::return_(value)))
+}
+
+fn closure(input: Input) -> IResult<ExprKind> {
+    // `||` is a single token, which starts closures without parameters.
+    let no_params = map(space_insignificant(punctuation("||")), |()| Vec::new());
+    let (tail, params) = alt((
+        no_params,
+        delimited(
+            pipe,
+            terminated(separated_list0(comma, spanned_declared_name), opt(comma)),
+            pipe,
+        ),
+    ))(input)?;
+    report_duplicate_names(input, &params);
+
+    let (tail, body) = expr(tail)?;
+
+    Ok((tail, ExprKind::closure(names_of(params), body)))
+}
+
+fn unit_expr(input: Input) -> IResult<ExprKind> {
+    map(pair(left_par, right_par), |_| ExprKind::unit())(input)
+}
+
+fn fn_call(input: Input) -> IResult<ExprKind> {
+    let (tail, name) = path(input)?;
+    let (tail, args) = delimited(left_par, separated_list0(comma, expr), right_par)(tail)?;
+
+    Ok((tail, ExprKind::fn_call(name, args)))
+}
+
+/// Parses an invocation of a macro, such as `max!(a, b)`.
+fn macro_call(input: Input) -> IResult<ExprKind> {
+    let (tail, name) = terminated(ident, bang)(input)?;
+    let (tail, args) = delimited(left_par, separated_list0(comma, expr), right_par)(tail)?;
+
+    Ok((tail, ExprKind::macro_call(name, args)))
+}
+
+/// Parses an identifier, whose name is only allocated the first time it is
+/// seen.
+fn ident_expr(input: Input) -> IResult<ExprKind> {
+    let (tail, segments) = separated_list1(double_colon, ident_fragment)(input)?;
+
+    let symbol = match segments.as_slice() {
+        [name] => Symbol::intern(name),
+        _ => Symbol::intern(&segments.join("::")),
+    };
+
+    Ok((tail, ExprKind::ident(symbol)))
+}
+
+fn bool_expr(input: Input) -> IResult<ExprKind> {
+    alt((true_expr, false_expr))(input)
+}
+
+fn true_expr(input: Input) -> IResult<ExprKind> {
+    map(true_, |()| ExprKind::bool_(true))(input)
+}
+
+fn false_expr(input: Input) -> IResult<ExprKind> {
+    map(false_, |()| ExprKind::bool_(false))(input)
+}
+
+/// Parses a name that refers to something declared elsewhere.
+fn ident(input: Input) -> IResult<String> {
+    map(ident_fragment, str::to_owned)(input)
+}
+
+fn ident_fragment<'a>(input: Input<'a>) -> IResult<'a, &'a str> {
+    let (tail, word) = word(input)?;
+
+    if lexer::is_keyword(word.fragment()) {
+        return Err(Err::Error(NomError::new(input, ErrorKind::Verify)));
+    }
+
+    Ok((tail, *word.fragment()))
+}
+
+/// Parses the name of something being declared.
+///
+/// Keywords are reported, but accepted so that the rest of the declaration
+/// is parsed.
+fn declared_name(input: Input) -> IResult<String> {
+    map(spanned_declared_name, |(name, _)| name)(input)
+}
+
+/// A declared name, along with the place it is declared at.
+type BoundName = (String, Span);
+
+fn spanned_declared_name(input: Input) -> IResult<BoundName> {
+    let (tail, word) = word(input)?;
+
+    if lexer::is_keyword(word.fragment()) {
+        let message = format!("Keyword `{}` cannot be used as a name", word.fragment());
+        report_error(word, message.as_str());
+    }
+
+    let span = span_of(word).with_length(word.fragment().len());
+
+    Ok((tail, (word.to_string(), span)))
+}
+
+fn names_of(bound: Vec<BoundName>) -> Vec<String> {
+    bound.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Reports the names that are declared more than once in the same scope,
+/// such as the parameters of a function, along with where they were first
+/// declared.
+fn report_duplicate_names(input: Input, names: &[BoundName]) {
+    for (idx, (name, span)) in names.iter().enumerate() {
+        let first = names[..idx].iter().find(|(previous, _)| previous == name);
+
+        if let Some((_, first)) = first {
+            let error = Diagnostic::error(format!("`{}` is declared more than once", name))
+                .with_code(Code::DuplicateName)
+                .with_span(span.clone())
+                .with_secondary(first.clone(), "first declared here");
+
+            input.extra.errors().add_once(error);
+        }
+    }
+}
+
+/// Parses a keyword or a name, which ends where the lexer ends it: `if0`
+/// is a name, not `if` followed by `0`.
+fn word(input: Input) -> IResult<Input> {
+    space_insignificant(|input| match token(input)? {
+        (tail, (TokenKind::Keyword | TokenKind::Ident, word)) => Ok((tail, word)),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Alpha))),
+    })(input)
+}
+
+fn if_(input: Input) -> IResult<()> {
+    keyword("if")(input)
+}
+
+fn else_(input: Input) -> IResult<()> {
+    keyword("else")(input)
+}
+
+fn return_(input: Input) -> IResult<()> {
+    keyword("return")(input)
+}
+
+fn as_(input: Input) -> IResult<()> {
+    keyword("as")(input)
+}
+
+fn let_(input: Input) -> IResult<()> {
+    keyword("let")(input)
+}
+
+fn mut_(input: Input) -> IResult<()> {
+    keyword("mut")(input)
+}
+
+fn true_(input: Input) -> IResult<()> {
+    keyword("true")(input)
+}
+
+fn false_(input: Input) -> IResult<()> {
+    keyword("false")(input)
+}
+
+fn equal(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("="))(input)
+}
+
+fn fat_arrow(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("=>"))(input)
+}
+
+fn arrow(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("->"))(input)
+}
+
+fn left_angle(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("<"))(input)
+}
+
+fn right_angle(input: Input) -> IResult<()> {
+    space_insignificant(punctuation(">"))(input)
+}
+
+fn pipe(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("|"))(input)
+}
+
+fn double_colon(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("::"))(input)
+}
+
+fn colon(input: Input) -> IResult<()> {
+    space_insignificant(punctuation(":"))(input)
+}
+
+fn semicolon(input: Input) -> IResult<()> {
+    space_insignificant(punctuation(";"))(input)
+}
+
+fn dot(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("."))(input)
+}
+
+fn bang(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("!"))(input)
+}
+
+fn comma(input: Input) -> IResult<()> {
+    space_insignificant(punctuation(","))(input)
+}
+
+fn keyword(kw: &str) -> impl Fn(Input) -> IResult<()> + '_ {
+    move |input| match word(input)? {
+        (tail, word) if *word.fragment() == kw => Ok((tail, ())),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+/// Reads the next token, trivia included, as the lexer splits it.
+fn token(input: Input) -> IResult<(TokenKind, Input)> {
+    let (kind, length) = lexer::next_token(input.fragment())
+        .ok_or_else(|| Err::Error(NomError::new(input, ErrorKind::Eof)))?;
+    let (tail, text) = take(length)(input)?;
+
+    Ok((tail, (kind, text)))
+}
+
+/// Parses a token of the given kind, and returns its text.
+fn token_of<'a>(kind: TokenKind) -> impl Fn(Input<'a>) -> IResult<'a, Input<'a>> {
+    move |input| match token(input)? {
+        (tail, (found, text)) if found == kind => Ok((tail, text)),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+/// Parses a punctuation token, such as `(` or `=>`.
+fn punctuation(p: &str) -> impl Fn(Input) -> IResult<()> + '_ {
+    move |input| match token_of(TokenKind::Punctuation)(input)? {
+        (tail, text) if *text.fragment() == p => Ok((tail, ())),
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn left_curly(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("{"))(input)
+}
+
+fn right_curly(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("}"))(input)
+}
+
+fn left_par(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("("))(input)
+}
+
+fn right_par(input: Input) -> IResult<()> {
+    space_insignificant(punctuation(")"))(input)
+}
+
+fn left_bracket(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("["))(input)
+}
+
+fn right_bracket(input: Input) -> IResult<()> {
+    space_insignificant(punctuation("]"))(input)
+}
+
+fn space_insignificant<'a, O>(
+    parser: impl Parser<Input<'a>, O, NomError<Input<'a>>>,
+) -> impl FnMut(Input<'a>) -> IResult<'a, O> {
+    delimited(trivia, parser, trivia)
+}
+
+fn trivia(input: Input) -> IResult<()> {
+    let whitespace = map(token_of(TokenKind::Whitespace), drop);
+    fold_many0(alt((whitespace, comment)), (), |(), ()| ())(input)
+}
+
+/// Parses a comment, and keeps it in the trivia of the program.
+fn comment(input: Input) -> IResult<()> {
+    let (tail, (kind, text)) = token(input)?;
+
+    match kind {
+        TokenKind::Comment => {
+            let comment = Comment::new(span_of(input), text.fragment().to_string());
+            input.extra.ctxt.trivia().add(comment);
+
+            Ok((tail, ()))
+        }
+
+        TokenKind::Error if text.fragment().starts_with("/*") => {
+            report_error(input, "Unterminated block comment");
+
+            Err(Err::Failure(NomError::new(input, ErrorKind::TakeUntil)))
+        }
+
+        _ => Err(Err::Error(NomError::new(input, ErrorKind::Tag))),
+    }
+}
+
+fn expect<O, P, R>(mut parser: P, mut recovery: R) -> impl FnMut(Input) -> IResult<Option<O>>
+where
+    P: FnMut(Input) -> IResult<O>,
+    R: FnMut(Input, ErrorKind) -> Option<Input>,
+{
+    move |input| match parser(input) {
+        Ok((tail, data)) => Ok((tail, Some(data))),
+        Err(Err::Incomplete(_)) => panic!("Parser returned Incomplete variant"),
+        Err(Err::Error(NomError { input, code })) | Err(Err::Failure(NomError { input, code })) => {
+            match recovery(input, code) {
+                Some(tail) => Ok((tail, None)),
+                None => Ok((input, None)),
+            }
+        }
+    }
+}
+
+/// Reports that a token is missing, and suggests to insert it after the
+/// code that comes before.
+fn epsilon_recover(token: &str) -> impl Fn(Input, ErrorKind) -> Option<Input> + '_ {
+    move |input, _| {
+        let error = Diagnostic::error(format!("Expected `{}`", token)).with_suggestion(
+            end_of_previous_token(input),
+            format!("insert `{}` here", token),
+            token,
+        );
+        report(input, error);
+
+        Some(input)
+    }
+}
+
+/// Returns where the code before `input` ends, not counting the whitespace
+/// in between, which is where a missing token goes.
+///
+/// That code is only known when parsing a file, otherwise the location
+/// `input` starts at is returned.
+fn end_of_previous_token(input: Input) -> Span {
+    let file = match input.extra.file {
+        Some(file) => file,
+        None => return span_of(input),
+    };
+
+    let before = file.content()[..input.location_offset()].trim_end();
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+
+    Span::new(line as u32, column)
+        .with_offset(before.len())
+        .in_file(Arc::clone(file))
+}
+
+fn report_all(position: Input, errors: Vec<Diagnostic>) {
+    errors
+        .into_iter()
+        .for_each(|error| position.extra.errors().add_once(error));
+}
+
+fn report_error(position: Input, message: &str) {
+    report(position, Diagnostic::error(message));
+}
+
+fn report(position: Input, error: Diagnostic) {
+    // The same input may be parsed multiple times because of backtracking,
+    // which must not lead to duplicated errors.
+    let error = error
+        .with_code(Code::SyntaxError)
+        .with_span(span_of(position));
+
+    position.extra.errors().add_once(error);
+}
+
+#[cfg(test)]
+fn parse_and_own<O>(
+    f: impl Fn(Input) -> IResult<O>,
+    input: &str,
+) -> (Result<O, Err<NomError<()>>>, ParsingContext) {
+    parse_with_nesting_limit(f, input, crate::DEFAULT_NESTING_LIMIT)
+}
+
+#[cfg(test)]
+fn parse_with_nesting_limit<O>(
+    f: impl Fn(Input) -> IResult<O>,
+    input: &str,
+    nesting_limit: usize,
+) -> (Result<O, Err<NomError<()>>>, ParsingContext) {
+    let parsing_ctxt = ParsingContext::new().with_nesting_limit(nesting_limit);
+    let input = LocatedSpan::new_extra(input, Source::new(&parsing_ctxt, None));
+    let parsing_status = f(input).map_err(own_nom_err).map(|(_, parsed)| parsed);
+
+    (parsing_status, parsing_ctxt)
+}
+
+#[cfg(test)]
+fn own_nom_err(err: Err<nom::error::Error<Input>>) -> Err<nom::error::Error<()>> {
+    match err {
+        Err::Error(e) => Err::Error(own_nom_error(e)),
+        Err::Failure(f) => Err::Failure(own_nom_error(f)),
+        Err::Incomplete(needed) => Err::Incomplete(needed),
+    }
+}
+
+#[cfg(test)]
+fn own_nom_error(err: NomError<Input>) -> NomError<()> {
+    let NomError { code, .. } = err;
     let input = ();
     NomError { input, code }
 }
 
 #[cfg(test)]
-macro_rules! parse {
-    ($rule:ident $slice:expr) => {{
-        parse_and_own($rule, $slice)
-    }};
+macro_rules! parse {
+    ($rule:ident $slice:expr) => {{
+        parse_and_own($rule, $slice)
+    }};
+}
+
+#[cfg(test)]
+mod program {
+    use crate::inline_program;
+
+    use super::*;
+
+    #[test]
+    fn single_main_func() {
+        let (left, _) = parse! { program_with_tail "fn main() { 42 }" };
+        let right = Ok(inline_program! {
+            fn main() { 42 }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_multiple_functions() {
+        let (left, _) = parse! { program_with_tail "fn main() { 42 } fn main_() { 42 }" };
+        let right = Ok(inline_program! {
+            fn main() { 42 }
+            fn main_() { 42 }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn merges_files() {
+        let files = [
+            SourceFile::new("a.dyl".to_owned(), "fn main() { f() }".to_owned()),
+            SourceFile::new("b.dyl".to_owned(), "fn f() { 42 }".to_owned()),
+        ];
+
+        let (_, left) = parse_files(&files, crate::DEFAULT_NESTING_LIMIT).unwrap();
+        let right = inline_program! {
+            fn main() { f() }
+            fn f() { 42 }
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn errors_of_every_file_are_reported_in_order() {
+        let files = (0..8)
+            .map(|idx| SourceFile::new(format!("{}.dyl", idx), "fn f( {".to_owned()))
+            .collect::<Vec<_>>();
+
+        let diagnostics = parse_files(&files, crate::DEFAULT_NESTING_LIMIT)
+            .unwrap_err()
+            .into_diagnostics();
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            (0..8)
+                .map(|idx| format!("{}.dyl:1:1: Syntax error", idx))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn errors_refer_to_the_file() {
+        let ctxt = ParsingContext::new();
+        let code = "fn f() { 1 }\n\nfn g( {";
+        let file = Arc::new(SourceFile::new("b.dyl".to_owned(), code.to_owned()));
+        let input = LocatedSpan::new_extra(code, Source::new(&ctxt, Some(&file)));
+
+        assert!(program(input).is_err());
+        assert_eq!(ctxt.errors().to_string(), "b.dyl:3:1: Syntax error\n");
+    }
+
+    #[test]
+    fn spans_refer_to_the_file() {
+        let ctxt = ParsingContext::new();
+        let file = Arc::new(SourceFile::new("a.dyl".to_owned(), "let x = 1;".to_owned()));
+        let input = LocatedSpan::new_extra(file.content(), Source::new(&ctxt, Some(&file)));
+
+        let (_, binding) = binding(input).unwrap();
+
+        assert_eq!(binding.span().to_string(), "a.dyl:1:5");
+    }
+
+    #[test]
+    fn missing_tokens_are_suggested() {
+        let ctxt = ParsingContext::new();
+        let code = "fn main() {\n    let x = 1\n    x\n}";
+        let file = Arc::new(SourceFile::new("c.dyl".to_owned(), code.to_owned()));
+        let input = LocatedSpan::new_extra(code, Source::new(&ctxt, Some(&file)));
+
+        program(input).unwrap();
+
+        let errors = ctxt.errors().diagnostics();
+        let suggestion = errors[0].suggestions().next().unwrap();
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "c.dyl:3:5: Expected `;`\n  help: insert `;` here\n"
+        );
+        assert_eq!(suggestion.span().to_string(), "c.dyl:2:14");
+        assert_eq!(suggestion.span().offset(), 25);
+        assert_eq!(suggestion.replacement(), ";");
+    }
+
+    #[test]
+    fn calls_have_spans() {
+        let ctxt = ParsingContext::new();
+        let input = LocatedSpan::new_extra("f(1)", Source::new(&ctxt, None));
+
+        let (_, call) = expr(input).unwrap();
+
+        match call {
+            ExprKind::FnCall(call) => assert_eq!(call.span().to_string(), "1:1"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn spans_cover_their_node() {
+        let ctxt = ParsingContext::new();
+        let input = LocatedSpan::new_extra(
+            "{\n  (a, f(1)) as int; /* ( */\n  x[0].1 == 2\n}",
+            Source::new(&ctxt, None),
+        );
+
+        let (_, block) = expr(input).unwrap();
+        let (statements, ending) = match &block {
+            ExprKind::Statements(s) => (s.statements(), s.ending_expression()),
+            _ => unreachable!(),
+        };
+        let extent = |expr: &ExprKind| (expr.span().offset(), expr.span().length());
+
+        let cast = match &statements[0] {
+            ExprKind::Cast(c) => c,
+            _ => unreachable!(),
+        };
+        let tuple = match cast.value() {
+            ExprKind::Tuple(t) => t,
+            _ => unreachable!(),
+        };
+        assert_eq!(extent(&statements[0]), (4, 16));
+        assert_eq!(extent(cast.value()), (4, 9));
+        assert_eq!(extent(&tuple.elements()[1]), (8, 4));
+
+        let comparison = match ending {
+            ExprKind::Comparison(c) => c,
+            _ => unreachable!(),
+        };
+        assert_eq!(extent(ending), (39, 2));
+        assert_eq!(extent(comparison.left()), (32, 6));
+        assert_eq!(extent(comparison.right()), (42, 1));
+    }
+
+    #[test]
+    fn identifiers_have_spans() {
+        let ctxt = ParsingContext::new();
+        let input = LocatedSpan::new_extra("f(a, b)", Source::new(&ctxt, None));
+
+        let (_, call) = fn_call(input).unwrap();
+
+        match call {
+            ExprKind::FnCall(call) => match &call.args()[1] {
+                ExprKind::Ident(ident) => assert_eq!(ident.span().to_string(), "1:6"),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod const_ {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn declaration() {
+        let (left, _) = parse! { const_ "const N = 2 * M;" };
+        let right = Ok(Const::new("N".to_owned(), inline_expr! { 2 * M }));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn in_program() {
+        let (left, _) = parse! { program_with_tail "const N = 1; fn main() { N }" };
+        let left = left.unwrap();
+
+        assert_eq!(
+            left.consts(),
+            [Const::new("N".to_owned(), ExprKind::integer(1))]
+        );
+        assert_eq!(left.functions().len(), 1);
+    }
+
+    #[test]
+    fn requires_semicolon() {
+        assert!(parse! { const_ "const N = 1" }.0.is_err());
+    }
+}
+
+#[cfg(test)]
+mod macro_ {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn declaration() {
+        let (left, _) = parse! { macro_ "macro sq(x) => x * x;" };
+        let right = Ok(Macro::new(
+            "sq".to_owned(),
+            vec!["x".to_owned()],
+            inline_expr! { x * x },
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn in_program() {
+        let (left, _) = parse! { program_with_tail "macro one() => 1; fn main() { one!() }" };
+        let left = left.unwrap();
+
+        assert_eq!(left.macros().len(), 1);
+        assert_eq!(
+            left.functions()[0].body(),
+            &ExprKind::macro_call("one".to_owned(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn invocation() {
+        let (left, _) = parse! { expr "max!(a, 1 + 2) * 2" };
+        let right = Ok(ExprKind::multiplication(
+            ExprKind::macro_call(
+                "max".to_owned(),
+                vec![inline_expr! { a }, inline_expr! { 1 + 2 }],
+            ),
+            ExprKind::integer(2),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn not_equal_is_not_an_invocation() {
+        let (left, _) = parse! { expr "a != (b)" };
+        let right = Ok(ExprKind::comparison(
+            ComparisonOperator::NotEqual,
+            inline_expr! { a },
+            inline_expr! { b },
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn duplicate_parameters() {
+        let (left, ctxt) = parse! { macro_ "macro m(a, a) => a;" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:12: `a` is declared more than once\n  1:9: first declared here\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod module {
+    use super::*;
+
+    #[test]
+    fn declaration() {
+        let (left, _) = parse! { module "mod math { const N = 1; mod int {} }" };
+        let right = Ok(Module::new(
+            "math".to_owned(),
+            vec![
+                Item::Const(Const::new("N".to_owned(), ExprKind::integer(1))),
+                Item::Module(Module::new("int".to_owned(), Vec::new())),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn import_() {
+        let (left, _) = parse! { import "import math::int::square;" };
+        let right = Import::new(vec![
+            "math".to_owned(),
+            "int".to_owned(),
+            "square".to_owned(),
+        ]);
+
+        assert_eq!(right.alias(), "square");
+        assert_eq!(left, Ok(right));
+    }
+
+    #[test]
+    fn qualified_fn_call() {
+        let (left, _) = parse! { expr "math::int::square(2)" };
+        let right = Ok(ExprKind::fn_call(
+            "math::int::square".to_owned(),
+            vec![ExprKind::integer(2)],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn qualified_ident() {
+        let (left, _) = parse! { expr "math::consts::PI" };
+        let right = Ok(ExprKind::ident("math::consts::PI".to_owned()));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn in_program() {
+        let (left, _) = parse! { program_with_tail "mod m {} import m::f; fn main() { 0 }" };
+        let left = left.unwrap();
+
+        assert_eq!(left.modules().len(), 1);
+        assert_eq!(
+            left.imports(),
+            [Import::new(vec!["m".to_owned(), "f".to_owned()])]
+        );
+    }
+}
+
+#[cfg(test)]
+mod struct_ {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn declaration() {
+        let (left, _) = parse! { program_with_tail "struct Point { x, y, } fn main() { 0 }" };
+        let left = left.unwrap();
+
+        assert_eq!(
+            left.structs(),
+            [Struct::new(
+                "Point".to_owned(),
+                vec!["x".to_owned(), "y".to_owned()]
+            )]
+        );
+        assert_eq!(left.functions().len(), 1);
+    }
+
+    #[test]
+    fn empty_declaration() {
+        let (left, _) = parse! { struct_ "struct Unit {}" };
+        let right = Ok(Struct::new("Unit".to_owned(), Vec::new()));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn literal() {
+        let (left, _) = parse! { expr "Point { x: 1, y: a + 1 }" };
+        let right = Ok(ExprKind::struct_literal(
+            "Point".to_owned(),
+            vec![
+                ("x".to_owned(), ExprKind::integer(1)),
+                ("y".to_owned(), inline_expr! { a + 1 }),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn literal_field_access() {
+        let (left, _) = parse! { expr "Wrapper { inner: 1 }.0" };
+        let right = Ok(ExprKind::field(
+            ExprKind::struct_literal(
+                "Wrapper".to_owned(),
+                vec![("inner".to_owned(), ExprKind::integer(1))],
+            ),
+            0,
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn if_condition_is_not_a_literal() {
+        let (left, _) = parse! { expr "if a { b } else { c }" };
+        let right = Ok(inline_expr! { if a { b } else { c } });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod enum_ {
+    use super::*;
+
+    #[test]
+    fn declaration() {
+        let (left, _) = parse! { enum_ "enum Color { Red, Green, Blue, }" };
+        let right = Ok(Enum::new(
+            "Color".to_owned(),
+            vec![
+                EnumVariant::new("Red".to_owned(), Vec::new()),
+                EnumVariant::new("Green".to_owned(), Vec::new()),
+                EnumVariant::new("Blue".to_owned(), Vec::new()),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn declaration_with_fields() {
+        let (left, _) = parse! { enum_ "enum Shape { Circle(radius), Rect(w, h) }" };
+        let right = Ok(Enum::new(
+            "Shape".to_owned(),
+            vec![
+                EnumVariant::new("Circle".to_owned(), vec!["radius".to_owned()]),
+                EnumVariant::new("Rect".to_owned(), vec!["w".to_owned(), "h".to_owned()]),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn program_with_enum() {
+        let (left, _) = parse! { program_with_tail "enum E { A } fn main() { E::A }" };
+        let left = left.unwrap();
+
+        assert_eq!(left.enums().len(), 1);
+        assert_eq!(left.functions().len(), 1);
+    }
+
+    #[test]
+    fn unit_variant() {
+        let (left, _) = parse! { expr "Color :: Red" };
+        let right = Ok(ExprKind::unit_variant("Color".to_owned(), "Red".to_owned()));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn variant_with_fields() {
+        let (left, _) = parse! { expr "Shape::Rect(1, a)" };
+        let right = Ok(ExprKind::variant_literal(
+            "Shape".to_owned(),
+            "Rect".to_owned(),
+            vec![ExprKind::integer(1), ExprKind::ident("a".to_owned())],
+        ));
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod match_ {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn simple() {
+        let (left, _) = parse! { expr "match a { 0 => 1, -1 => b + 1, _ => 2, }" };
+        let right = Ok(ExprKind::match_(
+            ExprKind::ident("a".to_owned()),
+            vec![
+                MatchArm::new(Pattern::Integer(0), ExprKind::integer(1)),
+                MatchArm::new(Pattern::Integer(-1), inline_expr! { b + 1 }),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(2)),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn binding_patterns() {
+        let (left, _) = parse! { expr "match o { Option::Some(x) => x, other => 0 }" };
+        let right = Ok(ExprKind::match_(
+            ExprKind::ident("o".to_owned()),
+            vec![
+                MatchArm::new(
+                    Pattern::variant(
+                        "Option".to_owned(),
+                        "Some".to_owned(),
+                        vec![Pattern::Binding("x".to_owned())],
+                    ),
+                    ExprKind::ident("x".to_owned()),
+                ),
+                MatchArm::new(Pattern::Binding("other".to_owned()), ExprKind::integer(0)),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn guards() {
+        let (left, _) = parse! { expr "match n { x if x => 1, _ => 0 }" };
+        let right = Ok(ExprKind::match_(
+            ExprKind::ident("n".to_owned()),
+            vec![
+                MatchArm::new(Pattern::Binding("x".to_owned()), ExprKind::integer(1))
+                    .with_guard(Some(ExprKind::ident("x".to_owned()))),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(0)),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn variant_patterns() {
+        let (left, _) =
+            parse! { expr "match s { Shape::Rect(0x10, _) => { 1 } Shape::Empty => 0 }" };
+        let right = Ok(ExprKind::match_(
+            ExprKind::ident("s".to_owned()),
+            vec![
+                MatchArm::new(
+                    Pattern::variant(
+                        "Shape".to_owned(),
+                        "Rect".to_owned(),
+                        vec![Pattern::Integer(16), Pattern::Wildcard],
+                    ),
+                    ExprKind::integer(1),
+                ),
+                MatchArm::new(
+                    Pattern::variant("Shape".to_owned(), "Empty".to_owned(), Vec::new()),
+                    ExprKind::integer(0),
+                ),
+            ],
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn duplicate_bindings() {
+        let (_, ctxt) = parse! { expr "match p { Pair::Of(x, x) => x, x => 0 }" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:23: `x` is declared more than once\n  1:20: first declared here\n"
+        );
+    }
+
+    #[test]
+    fn wildcard_is_not_an_identifier_prefix() {
+        let (left, _) = parse! { pattern "_a::B" };
+        let left = left.map(|(pattern, _)| pattern);
+        let right = Ok(Pattern::variant(
+            "_a".to_owned(),
+            "B".to_owned(),
+            Vec::new(),
+        ));
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod trivia {
+    use crate::{inline_expr, inline_program};
+
+    use super::*;
+
+    #[test]
+    fn block_comment() {
+        let (left, _) = parse! { trivia "/* foo */ " };
+        let right = Ok(());
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let (left, _) = parse! { expr "1 /* a /* b */ c */ + 2" };
+        let right = Ok(inline_expr! { 1 + 2 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn comments_between_any_tokens() {
+        let (left, _) = parse! {
+            program_with_tail
+            "/* head */ fn/**/main/**/(/**/)/**/{/**/let/**/a/**/=/**/42/**/;/**/a/**/}/* tail */"
+        };
+        let right = Ok(inline_program! {
+            fn main() {
+                let a = 42;
+                a
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let (_, ctxt) = parse! {
+            program_with_tail
+            "/* head */ fn main() {\n    /* a /* nested */ */ 42 /* b */\n}"
+        };
+
+        let comments = ctxt
+            .trivia()
+            .comments()
+            .iter()
+            .map(|comment| format!("{}: {}", comment.span(), comment.text()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            comments,
+            [
+                "1:1: /* head */",
+                "2:5: /* a /* nested */ */",
+                "2:29: /* b */"
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let (left, ctxt) = parse! { expr "1 + /* a /* b */ 2" };
+
+        assert!(matches!(left, Err(Err::Failure(_))));
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:5: Unterminated block comment\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod function {
+    use crate::inline_fn;
+
+    use super::*;
+
+    #[test]
+    fn handles_bindings() {
+        let (left, _) = parse! { function "fn main() { let a = 40; let b = 2; a + b }" };
+        let right = Ok(inline_fn! {
+            fn main() {
+                let a = 40;
+                let b = 2;
+                a + b
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_arguments() {
+        let (left, _) = parse! { function "fn add(a, b) { a + b }" };
+        let right = Ok(inline_fn! {
+            fn add(a, b) {
+                a + b
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_expression() {
+        let (left, _) = parse! { function "fn main() { 1 + 2 + 2 }" };
+        let right = Ok(inline_fn! {
+            fn main() {
+                1 + 2 + 2
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_generics() {
+        let (left, _) = parse! { function "fn id<T>(x: T, y) -> T { x }" };
+        let right = Ok(Function::new(
+            "id".to_owned(),
+            vec!["x".to_owned(), "y".to_owned()],
+            ExprKind::ident("x".to_owned()),
+        )
+        .with_signature(
+            vec!["T".to_owned()],
+            vec![Some(Type::named("T")), None],
+            Some(Type::named("T")),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_compound_types() {
+        let (left, _) = parse! { function "fn f(a: [int], b: (T, m::S), c: (int,), d: (bool)) {}" };
+        let types = left.unwrap().arg_types().to_vec();
+
+        assert_eq!(
+            types,
+            [
+                Some(Type::array(Type::named("int"))),
+                Some(Type::Tuple(vec![Type::named("T"), Type::named("m::S")])),
+                Some(Type::Tuple(vec![Type::named("int")])),
+                Some(Type::named("bool")),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_params() {
+        let (_, ctxt) = parse! { function "fn f<T, T>(a, b, a) {}" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:9: `T` is declared more than once\n  1:6: first declared here\n\
+             1:18: `a` is declared more than once\n  1:12: first declared here\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod block {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn handles_bindings() {
+        let (left, _) = parse! { block "{ let a = 42; a }" };
+        let right = Ok(inline_expr! {
+            {
+                let a = 42;
+                a
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn handles_expression() {
+        let (left, _) = parse! { block "{ 42 }" };
+        let right = Ok(inline_expr! {
+            {
+                42
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod expr {
+    use super::*;
+
+    use crate::inline_expr;
+
+    #[test]
+    fn if_addition_parses() {
+        let (left, _) = parse! { expr "if 1 { 1 } else { 1 } + 1" };
+        let right = Ok(inline_expr! {
+            if 1 { 1 } else { 1 } + 1
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn comparison_binds_looser_than_arithmetic() {
+        let (left, _) = parse! { expr "a + 1 <= b * 2" };
+        let right = Ok(ExprKind::comparison(
+            ComparisonOperator::LessOrEqual,
+            inline_expr! { a + 1 },
+            inline_expr! { b * 2 },
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn logical_operators_bind_looser_than_comparisons() {
+        let (left, _) = parse! { expr "a || b == 1 && c" };
+        let right = Ok(ExprKind::or(
+            ExprKind::ident("a".to_owned()),
+            ExprKind::and(
+                ExprKind::comparison(
+                    ComparisonOperator::Equal,
+                    ExprKind::ident("b".to_owned()),
+                    ExprKind::integer(1),
+                ),
+                ExprKind::ident("c".to_owned()),
+            ),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn chained_comparisons_are_reported() {
+        let (left, ctxt) = parse! { expr "a < b >= c" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:7: Comparison operators cannot be chained, \
+             use `&&` to combine them, as in `a < b && b < c`\n"
+        );
+    }
+
+    #[test]
+    fn parenthesized_comparisons_are_not_chained() {
+        let (left, ctxt) = parse! { expr "(a != b) == c" };
+
+        assert!(left.is_ok());
+        assert!(ctxt.errors().to_string().is_empty());
+    }
+
+    /// Parses a file with the default nesting limit, and returns the
+    /// messages of the errors it reports.
+    fn nesting_errors(code: &str) -> Vec<String> {
+        let files = [SourceFile::new("a.dyl".to_owned(), code.to_owned())];
+
+        match parse_files(&files, crate::DEFAULT_NESTING_LIMIT) {
+            Ok(_) => Vec::new(),
+            Err(e) => e
+                .into_diagnostics()
+                .iter()
+                .map(|d| d.message().to_owned())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_reported() {
+        let code = format!("{}1{}", "{".repeat(10_000), "}".repeat(10_000));
+        let (left, ctxt) = parse_with_nesting_limit(expr, &code, 32);
+
+        assert!(matches!(left, Err(Err::Failure(_))));
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:33: Program too deeply nested\n  \
+             note: Code can be nested at most 32 levels deep\n"
+        );
+    }
+
+    #[test]
+    fn nested_parentheses_are_parsed_in_linear_time() {
+        let code = format!("a[0] = {}1{}", "(".repeat(30), ")".repeat(30));
+
+        let start = std::time::Instant::now();
+        let (left, ctxt) = parse_and_own(expr, &code);
+
+        assert!(left.is_ok());
+        assert!(ctxt.errors().to_string().is_empty());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn right_operands_count_as_nested() {
+        let code = vec!["2"; 1_000].join(" ** ");
+        let (left, ctxt) = parse_with_nesting_limit(expr, &code, 32);
+
+        assert!(left.is_err());
+        assert!(!ctxt.errors().to_string().is_empty());
+    }
+
+    #[test]
+    fn each_block_counts_once() {
+        let code = format!("{}1{}", "{".repeat(31), "}".repeat(31));
+        let (left, ctxt) = parse_with_nesting_limit(expr, &code, 32);
+
+        assert!(left.is_ok());
+        assert!(ctxt.errors().to_string().is_empty());
+    }
+
+    #[test]
+    fn long_operation_chains_are_parsed() {
+        let code = format!("fn main() {{ {} }}", vec!["1"; 2_000].join(" + "));
+
+        assert!(nesting_errors(&code).is_empty());
+    }
+
+    #[test]
+    fn operations_count_in_the_depth() {
+        let deepest = |code: &str| {
+            let files = [SourceFile::new("a.dyl".to_owned(), code.to_owned())];
+            let (ctxt, _) = parse_files(&files, crate::DEFAULT_NESTING_LIMIT).unwrap();
+            ctxt.deepest()
+        };
+
+        let flat = deepest("fn main() { 1 }");
+        let chained = deepest("fn main() { (1 + 2 + 3).0 + 4 } fn f() { 1 }");
+
+        assert!(chained >= flat + 5, "{} {}", flat, chained);
+    }
+
+    #[test]
+    fn deeply_nested_comments_are_lexed() {
+        let code = format!(
+            "{}{} fn main() {{ 1 }}",
+            "/* ".repeat(10_000),
+            " */".repeat(10_000)
+        );
+
+        assert!(nesting_errors(&code).is_empty());
+    }
+
+    #[test]
+    fn deep_programs_within_the_limit_are_parsed() {
+        let ifs = "if true { ".repeat(200) + "1" + &" } else { 0 }".repeat(200);
+        let code = format!("fn main() {{ {} }}", ifs);
+
+        assert!(nesting_errors(&code).is_empty());
+    }
+
+    #[test]
+    fn nesting_limit_is_configurable() {
+        let files = [SourceFile::new(
+            "a.dyl".to_owned(),
+            "fn main() { ((1)) }".to_owned(),
+        )];
+
+        assert!(parse_files(&files, 3).is_ok());
+        assert!(parse_files(&files, 2).is_err());
+    }
+}
+
+#[cfg(test)]
+mod integer {
+    use super::*;
+
+    use crate::inline_expr;
+
+    #[test]
+    fn integer_simple() {
+        let (left, _) = parse! { integer "42" };
+        let right = Ok(inline_expr! { 42 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn integer_with_tail() {
+        let ctxt = ParsingContext::new();
+        let file = LocatedSpan::new_extra("101 !", Source::new(&ctxt, None));
+
+        let left = integer(file).map(|(tail, parsed)| (*tail.fragment(), parsed));
+        let right = Ok(("!", ExprKind::integer(101)));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn integer_failing_when_not_digit() {
+        assert!(parse! { integer "abc" }.0.is_err());
+        assert!(parse! { integer "" }.0.is_err());
+    }
+
+    #[test]
+    fn integer_eats_whitespaces_before_and_after() {
+        let (left, _) = parse! { integer " 42 " };
+        let right = Ok(inline_expr! { 42 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn negative() {
+        let (left, _) = parse! { integer "-101" };
+        let right = Ok(inline_expr! { -101 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn decimal_overflow() {
+        let (left, ctxt) = parse! { integer "99999999999" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `99999999999` does not fit in 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn decimal_overflow_span() {
+        let (_, ctxt) = parse! { expr "1 +\n  2147483648" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "2:3: Integer literal `2147483648` does not fit in 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn i32_bounds() {
+        let (left, ctxt) = parse! { integer "-2147483648" };
+        assert_eq!(left, Ok(ExprKind::integer(i32::MIN)));
+        assert!(ctxt.errors().to_string().is_empty());
+
+        let (left, ctxt) = parse! { integer "2147483647" };
+        assert_eq!(left, Ok(ExprKind::integer(i32::MAX)));
+        assert!(ctxt.errors().to_string().is_empty());
+    }
+
+    #[test]
+    fn negative_decimal_overflow() {
+        let (_, ctxt) = parse! { integer "-2147483649" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `-2147483649` does not fit in 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn hexadecimal() {
+        let (left, _) = parse! { integer "0xFf" };
+        let right = Ok(inline_expr! { 255 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn octal() {
+        let (left, _) = parse! { integer "0o77" };
+        let right = Ok(inline_expr! { 63 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn binary() {
+        let (left, _) = parse! { integer "0b1010" };
+        let right = Ok(inline_expr! { 10 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn negative_prefixed() {
+        let (left, _) = parse! { integer "-0x80000000" };
+        let right = Ok(ExprKind::integer(i32::MIN));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn prefixed_overflow() {
+        let (left, ctxt) = parse! { integer "0x80000000" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `0x80000000` does not fit in 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn prefixed_overflow_with_many_digits() {
+        let (_, ctxt) = parse! { integer "0b11111111111111111111111111111111111111111111111111111111111111111" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `0b11111111111111111111111111111111111111111111111111111111111111111` does not fit in 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn invalid_digit() {
+        let (left, ctxt) = parse! { integer "0b102" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Invalid digit `2` in binary literal\n"
+        );
+    }
+
+    #[test]
+    fn letters_after_decimal_digits() {
+        let (left, ctxt) = parse! { integer "12ab" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Invalid digit `a` in decimal literal\n"
+        );
+    }
+
+    #[test]
+    fn underscore_separators() {
+        let (left, _) = parse! { integer "-1_000__000" };
+        let right = Ok(ExprKind::integer(-1_000_000));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn prefixed_underscore_separators() {
+        let (left, _) = parse! { integer "0b1111_0000" };
+        let right = Ok(ExprKind::integer(0b1111_0000));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn trailing_underscore() {
+        let (left, ctxt) = parse! { integer "1_000_" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `1_000_` must not end with an underscore\n"
+        );
+    }
+
+    #[test]
+    fn leading_underscore_after_prefix() {
+        let (left, ctxt) = parse! { integer "0x_ff" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Integer literal `0x_ff` must not start with an underscore\n"
+        );
+    }
+
+    #[test]
+    fn leading_underscore_is_an_identifier() {
+        assert!(parse! { integer "_1" }.0.is_err());
+    }
+
+    #[test]
+    fn missing_digits() {
+        let (left, ctxt) = parse! { integer "0x" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Missing digits after the `0x` prefix\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod add_and_sub {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn single_operand() {
+        let (left, _) = parse! { expr "42" };
+        let right = Ok(ExprKind::integer(42));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn addition_simple() {
+        let (left, _) = parse! { expr "1+1" };
+        let right = Ok(inline_expr! {
+            1 + 1
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn addition_right_associative() {
+        let (left, _) = parse! { expr "1+1+1" };
+        let right = Ok(inline_expr! { 1 + 1 + 1 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn subtraction_simple() {
+        let (left, _) = parse! { expr "43-1" };
+        let right = Ok(inline_expr! {
+            43 - 1
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn subtraction_right_associative() {
+        let (left, _) = parse! { expr "44-1-1" };
+        let right = Ok(inline_expr! {
+            44 - 1 - 1
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn addition_subtraction_mixed() {
+        let (left, _) = parse! { expr "42-1+1" };
+        let right = Ok(inline_expr! {
+            42 - 1 + 1
+        });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod mul {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn parse_simple() {
+        let (left, _) = parse! { expr "7*6" };
+        let right = Ok(inline_expr! {
+            7 * 6
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn when_spaced() {
+        let (left, _) = parse! { expr "21 * 2" };
+        let right = Ok(inline_expr! {
+            21 * 2
+        });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod pow {
+    use super::*;
+
+    fn pow(base: ExprKind, exponent: ExprKind) -> ExprKind {
+        ExprKind::exponentiation(base, exponent)
+    }
+
+    #[test]
+    fn right_associative() {
+        let (left, _) = parse! { expr "2 ** 3 ** 2" };
+        let right = Ok(pow(
+            ExprKind::integer(2),
+            pow(ExprKind::integer(3), ExprKind::integer(2)),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn binds_tighter_than_mul() {
+        let (left, _) = parse! { expr "2 * 3 ** 2 * 4" };
+        let right = Ok(ExprKind::multiplication(
+            ExprKind::multiplication(
+                ExprKind::integer(2),
+                pow(ExprKind::integer(3), ExprKind::integer(2)),
+            ),
+            ExprKind::integer(4),
+        ));
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod cast {
+    use super::*;
+
+    #[test]
+    fn binds_tighter_than_binary_operators() {
+        let (left, _) = parse! { expr "1 + x as int * 2" };
+        let right = Ok(ExprKind::addition(
+            ExprKind::integer(1),
+            ExprKind::multiplication(
+                ExprKind::cast(ExprKind::ident("x".to_owned()), Type::named("int")),
+                ExprKind::integer(2),
+            ),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn chained() {
+        let (left, _) = parse! { expr "a.0 as int as int" };
+        let right = Ok(ExprKind::cast(
+            ExprKind::cast(
+                ExprKind::field(ExprKind::ident("a".to_owned()), 0),
+                Type::named("int"),
+            ),
+            Type::named("int"),
+        ));
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod math {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn priority_simple() {
+        let (left, _) = parse! { expr "10 * 4 + 2" };
+        let right = Ok(inline_expr! { 10 * 4 + 2 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn priority_on_the_right() {
+        let (left, _) = parse! { expr "2 + 10 * 4 - 1" };
+        let right = Ok(inline_expr! { 2 + 10 * 4 - 1 });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn operands_are_atomic_expressions() {
+        let (left, _) = parse! { expr "a[1] * (2 + b.0)" };
+        let right = Ok(inline_expr! { a[1] * (2 + b.0) });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod if_else {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn if_else_simple() {
+        let (left, _) = parse! { if_else "if 0{1}else{42}" };
+        let right = Ok(inline_expr! {
+            if 0 {
+                1
+            } else {
+                42
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn if_without_else() {
+        let (left, _) = parse! { if_else "if a { b = 1 } + 1" };
+        let right = Ok(inline_expr! {
+            if a {
+                b = 1
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn else_if() {
+        let (left, _) = parse! { if_else "if a { 1 } else if b { 2 } else { 3 }" };
+        let right = Ok(inline_expr! {
+            if a {
+                1
+            } else if b {
+                2
+            } else {
+                3
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn else_if_without_else() {
+        let (left, _) = parse! { if_else "if a { b = 1 } else if c { b = 2 }" };
+        let right = Ok(inline_expr! {
+            if a {
+                b = 1
+            } else if c {
+                b = 2
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn if_else_spaced_braces() {
+        let (left, _) = parse! { if_else "if 0 { 1 } else { 42 }" };
+        let right = Ok(inline_expr! {
+            if 0 {
+                1
+            } else {
+                42
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn addition_as_condition() {
+        let (left, _) = parse! { if_else "if 1 + 1 { 1 } else { 1 }" };
+        let right = Ok(inline_expr! {
+            if 1 + 1 {
+                1
+            } else {
+                1
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn bindings_as_consequent_and_alternative() {
+        let (left, _) = parse! { if_else "if 1 { let a = 0; a } else { let a = 0; a }" };
+        let right = Ok(inline_expr! {
+            if 1 {
+                let a = 0;
+                a
+            } else {
+                let a = 0;
+                a
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod keyword {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let if_ = keyword("if");
+        let (left, _) = parse! { if_ "if" };
+        let right = Ok(());
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn fails_when_followed_by_letter() {
+        let if_ = keyword("if");
+        assert!(parse! { if_ "iff" }.0.is_err());
+    }
+
+    #[test]
+    fn works_when_followed_by_punctuation() {
+        let if_ = keyword("if");
+        let (left, _) = parse! { if_ "if(" };
+        let right = Ok(());
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn fails_when_followed_by_digit() {
+        let if_ = keyword("if");
+        assert!(parse! { if_ "if42" }.0.is_err());
+        assert_eq!(parse! { ident "if0" }.0, Ok("if0".to_owned()));
+    }
+
+    #[test]
+    fn kw_followed_by_space_and_letter() {
+        let let_ = keyword("let");
+        assert!(parse! { let_ "let a" }.0.is_ok());
+    }
+
+    #[test]
+    fn is_not_an_ident() {
+        assert!(parse! { ident "let" }.0.is_err());
+        assert!(parse! { ident "letter" }.0.is_ok());
+    }
+
+    #[test]
+    fn used_as_binding_name() {
+        let (left, ctxt) = parse! { binding "let let = 1;" };
+
+        assert_eq!(
+            left,
+            Ok(Binding::new("let".to_owned(), ExprKind::integer(1)))
+        );
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:5: Keyword `let` cannot be used as a name\n"
+        );
+    }
+
+    #[test]
+    fn used_as_function_and_argument_names() {
+        let (_, ctxt) = parse! { program_with_tail "fn if(a,\n  match) { a }" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:4: Keyword `if` cannot be used as a name\n\
+             2:3: Keyword `match` cannot be used as a name\n"
+        );
+    }
 }
 
 #[cfg(test)]
-mod program {
-    use crate::inline_program;
+mod binding {
+    use super::*;
+
+    use crate::inline_expr;
+
+    #[test]
+    fn simple() {
+        let (left, _) = parse! { binding "let a = 42;" };
+        let right = Ok(Binding::new("a".to_owned(), ExprKind::integer(42)));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn with_annotation() {
+        let (left, _) = parse! { binding "let a: [(int, bool)] = x;" };
+        let annotation = Type::array(Type::Tuple(vec![Type::named("int"), Type::named("bool")]));
+        let right = Ok(
+            Binding::new("a".to_owned(), ExprKind::ident("x".to_owned()))
+                .with_annotation(Some(annotation)),
+        );
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn with_if_else() {
+        let (left, _) = parse! { binding "let foo = if 5 { 42 } else { 101 };" };
+        let right = Ok(Binding::new(
+            "foo".to_owned(),
+            inline_expr! {
+                if 5 {
+                    42
+                } else {
+                    101
+                }
+            },
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    fn name(name: &str) -> BindingPattern {
+        BindingPattern::Name(name.to_owned())
+    }
+
+    #[test]
+    fn tuple_pattern() {
+        let (left, _) = parse! { binding "let mut (a, (b,), (c), _) = x;" };
+        let pattern = BindingPattern::Tuple(vec![
+            name("a"),
+            BindingPattern::Tuple(vec![name("b")]),
+            name("c"),
+            BindingPattern::Wildcard,
+        ]);
+        let right = Ok(Binding::from_pattern(
+            pattern,
+            ExprKind::ident("x".to_owned()),
+            true,
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn struct_pattern() {
+        let (left, _) = parse! { binding "let P { x, y: (a, b), } = p;" };
+        let pattern = BindingPattern::Struct {
+            name: "P".to_owned(),
+            fields: vec![
+                ("x".to_owned(), name("x")),
+                (
+                    "y".to_owned(),
+                    BindingPattern::Tuple(vec![name("a"), name("b")]),
+                ),
+            ],
+        };
+        let right = Ok(Binding::from_pattern(
+            pattern,
+            ExprKind::ident("p".to_owned()),
+            false,
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let (_, ctxt) = parse! { binding "let (a, P { a, b: (b,) }) = p;" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:13: `a` is declared more than once\n  1:6: first declared here\n"
+        );
+    }
+
+    #[test]
+    fn recovers_on_missing_equal() {
+        assert!(parse! { binding "let x 42;" }.0.is_ok());
+    }
+
+    #[test]
+    fn recovers_on_missing_semicolon() {
+        assert!(parse! { binding "let x = 42" }.0.is_ok());
+    }
+
+    #[test]
+    fn recovers_from_invalid_statements() {
+        let (left, ctxt) =
+            parse! { block_content "let a = ) 1;\nf(a, {) +;\nlet b = 2;\n* b let c = 3;\nb + c" };
+        let right = Ok(inline_expr! {
+            {
+                let b = 2;
+                let c = 3;
+                b + c
+            }
+        });
+
+        assert_eq!(left, right);
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Syntax error\n2:1: Syntax error\n4:1: Syntax error\n"
+        );
+    }
+
+    #[test]
+    fn invalid_ending_expression() {
+        let (left, ctxt) = parse! { block "{ let a = 1; a + }" };
+
+        assert_eq!(left, Ok(inline_expr! { { let a = 1; } }));
+        assert_eq!(ctxt.errors().to_string(), "1:14: Syntax error\n");
+    }
+
+    #[test]
+    fn errors_of_every_function_are_reported() {
+        let (left, ctxt) = parse! { program_with_tail "fn f() { 1 + }\nfn g() { let = 2; 3 }" };
+
+        assert!(left.is_ok());
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:10: Syntax error\n2:10: Syntax error\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod bindings {
+    use crate::inline_expr;
 
     use super::*;
 
     #[test]
-    fn single_main_func() {
-        let (left, _) = parse! { program_with_tail "fn main() { 42 }" };
-        let right = Ok(inline_program! {
-            fn main() { 42 }
+    fn bindings_simple() {
+        let (left, _) = parse! { block_content "let a = 42; a" };
+        let right = Ok(inline_expr! {
+            {
+                let a = 42;
+                a
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn binding_span() {
+        let (binding, _) = parse! { binding "let\n  mut a = 1;" };
+
+        assert_eq!(binding.unwrap().span().to_string(), "2:7");
+    }
+
+    #[test]
+    fn expression_statements() {
+        let (left, _) = parse! { block_content "f(x); x = 1; x + 1" };
+        let right = Ok(inline_expr! {
+            {
+                f(x);
+                x = 1;
+                x + 1
+            }
+        });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn without_ending_expression() {
+        let (left, _) = parse! { block "{ let a = 1; f(a); }" };
+        let right = Ok(inline_expr! {
+            {
+                let a = 1;
+                f(a);
+            }
         });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn handles_multiple_functions() {
-        let (left, _) = parse! { program_with_tail "fn main() { 42 } fn main_() { 42 }" };
-        let right = Ok(inline_program! {
-            fn main() { 42 }
-            fn main_() { 42 }
-        });
+    fn empty_block() {
+        let (left, _) = parse! { block "{}" };
+
+        assert_eq!(left, Ok(ExprKind::unit()));
+    }
+
+    #[test]
+    fn statements_and_bindings() {
+        let (left, _) = parse! { block_content "let a = 1; f(a); let b = a; let c = b; g(c); c" };
+        let right = Ok(ExprKind::bindings(
+            vec![Binding::new("a".to_owned(), ExprKind::integer(1))],
+            ExprKind::statements(
+                vec![inline_expr! { f(a) }],
+                ExprKind::bindings(
+                    vec![
+                        Binding::new("b".to_owned(), ExprKind::ident("a".to_owned())),
+                        Binding::new("c".to_owned(), ExprKind::ident("b".to_owned())),
+                    ],
+                    ExprKind::statements(
+                        vec![inline_expr! { g(c) }],
+                        ExprKind::ident("c".to_owned()),
+                    ),
+                ),
+            ),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn block_like_statements() {
+        let (left, _) = parse! { block_content "if a { f() } match b { _ => g() } { h() } c" };
+        let right = Ok(ExprKind::statements(
+            vec![
+                ExprKind::if_without_else(ExprKind::ident("a".to_owned()), inline_expr! { f() }),
+                ExprKind::match_(
+                    ExprKind::ident("b".to_owned()),
+                    vec![MatchArm::new(Pattern::Wildcard, inline_expr! { g() })],
+                ),
+                inline_expr! { h() },
+            ],
+            ExprKind::ident("c".to_owned()),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn block_like_ending_expression() {
+        let (left, _) = parse! { block_content "f(); if a { 1 } else { 2 }" };
+        let right = Ok(ExprKind::statements(
+            vec![inline_expr! { f() }],
+            ExprKind::if_(
+                ExprKind::ident("a".to_owned()),
+                ExprKind::integer(1),
+                ExprKind::integer(2),
+            ),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn block_like_statement_with_semicolon() {
+        let (left, _) = parse! { block_content "{ 1 };" };
+        let right = Ok(ExprKind::statements(
+            vec![ExprKind::integer(1)],
+            ExprKind::unit(),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn empty_statements() {
+        let (left, _) = parse! { block_content ";f();; ;" };
+        let right = Ok(ExprKind::statements(
+            vec![inline_expr! { f() }],
+            ExprKind::unit(),
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn local_functions() {
+        let (left, _) = parse! { block_content "let a = f(1); fn f(x) { x } fn g() { 2 } a" };
+        let right = Ok(ExprKind::local_functions(
+            vec![
+                Function::new(
+                    "f".to_owned(),
+                    vec!["x".to_owned()],
+                    ExprKind::ident("x".to_owned()),
+                ),
+                Function::new("g".to_owned(), Vec::new(), ExprKind::integer(2)),
+            ],
+            ExprKind::bindings(
+                vec![Binding::new("a".to_owned(), inline_expr! { f(1) })],
+                ExprKind::ident("a".to_owned()),
+            ),
+        ));
+
+        assert_eq!(left, right);
+    }
+}
+
+#[cfg(test)]
+mod fn_call {
+    use crate::inline_expr;
+
+    use super::*;
+
+    #[test]
+    fn no_arguments() {
+        let (left, _) = parse! { fn_call "f()" };
+        let right = Ok(inline_expr! { f() });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn with_arguments() {
+        let (left, _) = parse! { fn_call "f(1, 2 + 3)" };
+        let right = Ok(inline_expr! { f(1, 2 + 3) });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn as_part_of_expression() {
+        let (left, _) = parse! { expr "f(1) * g(a, b)" };
+        let right = Ok(inline_expr! { f(1) * g(a, b) });
 
         assert_eq!(left, right);
     }
+
+    #[test]
+    fn ident_is_not_a_call() {
+        assert!(parse! { fn_call "f" }.0.is_err());
+    }
 }
 
 #[cfg(test)]
-mod function {
-    use crate::inline_fn;
+mod assignment {
+    use crate::inline_expr;
 
     use super::*;
 
     #[test]
-    fn handles_bindings() {
-        let (left, _) = parse! { function "fn main() { let a = 40; let b = 2; a + b }" };
-        let right = Ok(inline_fn! {
-            fn main() {
-                let a = 40;
-                let b = 2;
-                a + b
-            }
-        });
+    fn simple() {
+        let (left, _) = parse! { expr "a = a + 1" };
+        let right = Ok(inline_expr! { a = a + 1 });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn handles_expression() {
-        let (left, _) = parse! { function "fn main() { 1 + 2 + 2 }" };
-        let right = Ok(inline_fn! {
-            fn main() {
-                1 + 2 + 2
-            }
-        });
+    fn compound() {
+        let (left, _) = parse! { expr "a **= b -= 1 + 2" };
+        let right = Ok(ExprKind::compound_assignment(
+            ArithmeticOperator::Exponentiate,
+            "a".to_owned(),
+            ExprKind::compound_assignment(
+                ArithmeticOperator::Subtract,
+                "b".to_owned(),
+                inline_expr! { 1 + 2 },
+            ),
+        ));
 
         assert_eq!(left, right);
     }
-}
 
-#[cfg(test)]
-mod block {
-    use crate::inline_expr;
+    #[test]
+    fn is_right_associative() {
+        let (left, _) = parse! { expr "a = b = 1" };
+        let right = Ok(ExprKind::assignment(
+            "a".to_owned(),
+            ExprKind::assignment("b".to_owned(), ExprKind::integer(1)),
+        ));
 
-    use super::*;
+        assert_eq!(left, right);
+    }
 
     #[test]
-    fn handles_bindings() {
-        let (left, _) = parse! { block "{ let a = 42; a }" };
+    fn mutable_binding() {
+        let (left, _) = parse! { block "{ let mut a = 1; a = 2 }" };
         let right = Ok(inline_expr! {
             {
-                let a = 42;
-                a
+                let mut a = 1;
+                a = 2
             }
         });
 
@@ -406,213 +3683,242 @@ mod block {
     }
 
     #[test]
-    fn handles_expression() {
-        let (left, _) = parse! { block "{ 42 }" };
-        let right = Ok(inline_expr! {
-            {
-                42
-            }
-        });
+    fn mutable_is_not_a_keyword() {
+        let (left, _) = parse! { binding "let mutable = 1;" };
+        let right = Ok(Binding::new("mutable".to_owned(), ExprKind::integer(1)));
 
         assert_eq!(left, right);
     }
 }
 
 #[cfg(test)]
-mod expr {
-    use super::*;
-
+mod tuple {
     use crate::inline_expr;
 
+    use super::*;
+
     #[test]
-    fn if_addition_parses() {
-        let (left, _) = parse! { expr "if 1 { 1 } else { 1 } + 1" };
-        let right = Ok(inline_expr! {
-            if 1 { 1 } else { 1 } + 1
-        });
+    fn unit() {
+        let (left, _) = parse! { expr "( )" };
+        let right = Ok(inline_expr! { () });
 
         assert_eq!(left, right);
     }
-}
 
-#[cfg(test)]
-mod integer {
-    use super::*;
+    #[test]
+    fn simple() {
+        let (left, _) = parse! { expr "(1, a, true)" };
+        let right = Ok(inline_expr! { (1, a, true) });
 
-    use crate::inline_expr;
+        assert_eq!(left, right);
+    }
 
     #[test]
-    fn integer_simple() {
-        let (left, _) = parse! { integer "42" };
-        let right = Ok(inline_expr! { 42 });
+    fn single_element_needs_trailing_comma() {
+        let (left, _) = parse! { expr "(1,)" };
+        let right = Ok(ExprKind::tuple(vec![ExprKind::integer(1)]));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn integer_with_tail() {
-        let ctxt = ParsingContext::new();
-        let file = LocatedSpan::new_extra("101 !", &ctxt);
-
-        let left = integer(file).map(|(tail, parsed)| (*tail.fragment(), parsed));
-        let right = Ok(("!", ExprKind::integer(101)));
+    fn parenthesized_expression() {
+        let (left, _) = parse! { expr "(1 + 2) * 3" };
+        let right = Ok(inline_expr! { (1 + 2) * 3 });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn integer_failing_when_not_digit() {
-        assert!(parse! { integer "abc" }.0.is_err());
-        assert!(parse! { integer "" }.0.is_err());
+    fn field_access() {
+        let (left, _) = parse! { expr "a.0 + f().1" };
+        let right = Ok(ExprKind::addition(
+            ExprKind::field(ExprKind::ident("a".to_owned()), 0),
+            ExprKind::field(ExprKind::fn_call("f".to_owned(), Vec::new()), 1),
+        ));
+
+        assert_eq!(left, right);
     }
 
     #[test]
-    fn integer_eats_whitespaces_before_and_after() {
-        let (left, _) = parse! { integer " 42 " };
-        let right = Ok(inline_expr! { 42 });
+    fn nested_field_access() {
+        let (left, _) = parse! { expr "a.0 . 1" };
+        let right = Ok(ExprKind::field(
+            ExprKind::field(ExprKind::ident("a".to_owned()), 0),
+            1,
+        ));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn negative() {
-        let (left, _) = parse! { integer "-101" };
-        let right = Ok(inline_expr! { -101 });
+    fn invalid_index() {
+        let (_, ctxt) = parse! { expr "a.70000" };
 
-        assert_eq!(left, right);
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:3: Invalid tuple index `70000`\n"
+        );
     }
 }
 
 #[cfg(test)]
-mod add_and_sub {
+mod array {
     use crate::inline_expr;
 
     use super::*;
 
     #[test]
-    fn single_factor_fails() {
-        assert!(parse! { level_0_expression "42" }.0.is_err());
+    fn simple() {
+        let (left, _) = parse! { expr "[1, a, 2 + 3]" };
+        let right = Ok(inline_expr! { [1, a, 2 + 3] });
+
+        assert_eq!(left, right);
     }
 
     #[test]
-    fn addition_simple() {
-        let (left, _) = parse! { level_0_expression "1+1" };
-        let right = Ok(inline_expr! {
-            1 + 1
-        });
+    fn empty() {
+        let (left, _) = parse! { expr "[ ]" };
+        let right = Ok(ExprKind::array(Vec::new()));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn addition_right_associative() {
-        let (left, _) = parse! { level_0_expression "1+1+1" };
-        let right = Ok(inline_expr! { 1 + 1 + 1 });
+    fn trailing_comma() {
+        let (left, _) = parse! { expr "[1, 2,]" };
+        let right = Ok(inline_expr! { [1, 2] });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn subtraction_simple() {
-        let (left, _) = parse! { level_0_expression "43-1" };
-        let right = Ok(inline_expr! {
-            43 - 1
-        });
+    fn index() {
+        let (left, _) = parse! { expr "a[1] + 2" };
+        let right = Ok(inline_expr! { a[1] + 2 });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn subtraction_right_associative() {
-        let (left, _) = parse! { level_0_expression "44-1-1" };
-        let right = Ok(inline_expr! {
-            44 - 1 - 1
-        });
+    fn chained_index() {
+        let (left, _) = parse! { expr "f()[0][i + 1].1" };
+        let right = Ok(ExprKind::field(
+            ExprKind::index(
+                ExprKind::index(
+                    ExprKind::fn_call("f".to_owned(), Vec::new()),
+                    ExprKind::integer(0),
+                ),
+                inline_expr! { i + 1 },
+            ),
+            1,
+        ));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn addition_subtraction_mixed() {
-        let (left, _) = parse! { level_0_expression "42-1+1" };
-        let right = Ok(inline_expr! {
-            42 - 1 + 1
-        });
+    fn index_assignment() {
+        let (left, _) = parse! { expr "a[0] = a[1] = 2" };
+        let right = Ok(ExprKind::index_assignment(
+            ExprKind::ident("a".to_owned()),
+            ExprKind::integer(0),
+            inline_expr! { a[1] = 2 },
+        ));
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn nested() {
+        let (left, _) = parse! { expr "[[1], []]" };
+        let right = Ok(ExprKind::array(vec![
+            ExprKind::array(vec![ExprKind::integer(1)]),
+            ExprKind::array(Vec::new()),
+        ]));
 
         assert_eq!(left, right);
     }
 }
 
 #[cfg(test)]
-mod mul {
+mod closure {
     use crate::inline_expr;
 
     use super::*;
 
     #[test]
-    fn parse_simple() {
-        let (left, _) = parse! { level_1_expression "7*6" };
-        let right = Ok(inline_expr! {
-            7 * 6
-        });
+    fn simple() {
+        let (left, _) = parse! { expr "|x| x + 1" };
+        let right = Ok(ExprKind::closure(
+            vec!["x".to_owned()],
+            inline_expr! { x + 1 },
+        ));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn when_spaced() {
-        let (left, _) = parse! { level_1_expression "21 * 2" };
-        let right = Ok(inline_expr! {
-            21 * 2
-        });
+    fn without_params() {
+        let (left, _) = parse! { expr "|| 42" };
+        let right = Ok(ExprKind::closure(Vec::new(), ExprKind::integer(42)));
 
         assert_eq!(left, right);
     }
-}
 
-#[cfg(test)]
-mod math {
-    use crate::inline_expr;
+    #[test]
+    fn multiple_params() {
+        let (left, _) = parse! { expr "|a, b,| { a * b }" };
+        let right = Ok(ExprKind::closure(
+            vec!["a".to_owned(), "b".to_owned()],
+            inline_expr! { { a * b } },
+        ));
 
-    use super::*;
+        assert_eq!(left, right);
+    }
 
     #[test]
-    fn priority_simple() {
-        let (left, _) = parse! { level_0_expression "10 * 4 + 2" };
-        let right = Ok(inline_expr! { 10 * 4 + 2 });
+    fn duplicate_params() {
+        let (_, ctxt) = parse! { expr "|x, y, x| x" };
+
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:8: `x` is declared more than once\n  1:2: first declared here\n"
+        );
+    }
+
+    #[test]
+    fn in_binding() {
+        let (left, _) = parse! { binding "let f = |x| x;" };
+        let right = Ok(Binding::new(
+            "f".to_owned(),
+            ExprKind::closure(vec!["x".to_owned()], inline_expr! { x }),
+        ));
 
         assert_eq!(left, right);
     }
 }
 
 #[cfg(test)]
-mod if_else {
+mod return_ {
     use crate::inline_expr;
 
     use super::*;
 
     #[test]
-    fn if_else_simple() {
-        let (left, _) = parse! { if_else "if0{1}else{42}" };
-        let right = Ok(inline_expr! {
-            if 0 {
-                1
-            } else {
-                42
-            }
-        });
+    fn simple() {
+        let (left, _) = parse! { return_expr "return 42" };
+        let right = Ok(inline_expr! { return 42 });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn if_else_spaced_braces() {
-        let (left, _) = parse! { if_else "if 0 { 1 } else { 42 }" };
+    fn without_value() {
+        let (left, _) = parse! { block "{ return; }" };
         let right = Ok(inline_expr! {
-            if 0 {
-                1
-            } else {
-                42
+            {
+                return ();
             }
         });
 
@@ -620,130 +3926,77 @@ mod if_else {
     }
 
     #[test]
-    fn addition_as_condition() {
-        let (left, _) = parse! { if_else "if 1 + 1 { 1 } else { 1 }" };
-        let right = Ok(inline_expr! {
-            if 1 + 1 {
-                1
-            } else {
-                1
-            }
-        });
+    fn returns_whole_expression() {
+        let (left, _) = parse! { expr "return a + 1" };
+        let right = Ok(inline_expr! { return a + 1 });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn bindings_as_consequent_and_alternative() {
-        let (left, _) = parse! { if_else "if 1 { let a = 0; a } else { let a = 0; a }" };
+    fn in_binding() {
+        let (left, _) = parse! { block "{ let a = return 1; a }" };
         let right = Ok(inline_expr! {
-            if 1 {
-                let a = 0;
-                a
-            } else {
-                let a = 0;
+            {
+                let a = return 1;
                 a
             }
         });
 
         assert_eq!(left, right);
     }
-}
-
-#[cfg(test)]
-mod keyword {
-    use super::*;
-
-    #[test]
-    fn parses() {
-        let if_ = keyword("if");
-        let (left, _) = parse! { if_ "if" };
-        let right = Ok(());
-
-        assert_eq!(left, right);
-    }
 
     #[test]
-    fn fails_when_followed_by_letter() {
-        let if_ = keyword("if");
-        assert!(parse! { if_ "iff" }.0.is_err());
-    }
-
-    #[test]
-    fn works_when_followed_by_non_letter() {
-        let if_ = keyword("if");
-        let (left, _) = parse! { if_ "if42" };
-        let right = Ok(());
+    fn returned_is_not_an_identifier() {
+        let (left, _) = parse! { expr "returned" };
+        let right = Ok(inline_expr! { returned });
 
         assert_eq!(left, right);
     }
-
-    #[test]
-    fn kw_followed_by_space_and_letter() {
-        let let_ = keyword("let");
-        assert!(parse! { let_ "let a" }.0.is_ok());
-    }
 }
 
 #[cfg(test)]
-mod binding {
-    use super::*;
-
+mod string {
     use crate::inline_expr;
 
+    use super::*;
+
     #[test]
     fn simple() {
-        let (left, _) = parse! { binding "let a = 42;" };
-        let right = Ok(Binding::new("a".to_owned(), ExprKind::integer(42)));
+        let (left, _) = parse! { expr " \"hello, world\" " };
+        let right = Ok(inline_expr! { "hello, world" });
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn with_if_else() {
-        let (left, _) = parse! { binding "let foo = if 5 { 42 } else { 101 };" };
-        let right = Ok(Binding::new(
-            "foo".to_owned(),
-            inline_expr! {
-                if 5 {
-                    42
-                } else {
-                    101
-                }
-            },
-        ));
+    fn escape_sequences() {
+        let (left, _) = parse! { string r#""a\"b\\c\n\t\r\0""# };
+        let right = Ok(ExprKind::string("a\"b\\c\n\t\r\0".to_owned()));
 
         assert_eq!(left, right);
     }
 
     #[test]
-    fn recovers_on_missing_equal() {
-        assert!(parse! { binding "let x 42;" }.0.is_ok());
-    }
-
-    #[test]
-    fn recovers_on_missing_semicolon() {
-        assert!(parse! { binding "let x = 42" }.0.is_ok());
+    fn unknown_escape_sequence() {
+        let (left, ctxt) = parse! { string r#""a\qb""# };
+
+        assert_eq!(left, Ok(ExprKind::string("ab".to_owned())));
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:3: Unknown character escape `\\q`\n"
+        );
     }
-}
-
-#[cfg(test)]
-mod bindings {
-    use crate::inline_expr;
-
-    use super::*;
 
     #[test]
-    fn bindings_simple() {
-        let (left, _) = parse! { bindings "let a = 42; a" };
-        let right = Ok(inline_expr! {
-            {
-                let a = 42;
-                a
-            }
-        });
-
-        assert_eq!(left, right);
+    fn unterminated() {
+        let (left, ctxt) = parse! { string r#""hello"# };
+
+        assert!(matches!(left, Err(Err::Failure(_))));
+        assert_eq!(
+            ctxt.errors().to_string(),
+            "1:1: Unterminated string literal\n"
+        );
     }
 }
 
@@ -0,0 +1,481 @@
+//! Passes written outside of the compiler.
+//!
+//! A [`PassManager`] holds passes that run between the built-in phases, and
+//! is given to the compiler through the [`Options`](crate::Options): tools
+//! can add their own lints, or instrument the compiled code, without
+//! changing the compiler.
+//!
+//! The syntax tree is not part of the API of the compiler, so the passes
+//! that run on it can only read it, through a [`SyntaxTree`]. The lowered
+//! code is made of bytecode instructions, which the passes that run on it
+//! can change through an [`Ir`]: the built-in phases that follow check the
+//! changed code like any other.
+//!
+//! A pass reports what it finds as diagnostics. Its errors stop the
+//! compilation once every pass of the same phase has run. Its warnings can
+//! belong to a [`Lint`](crate::Lint) of its own, registered in the
+//! [`LintLevels`](crate::LintLevels) to be set by name.
+
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+
+use dyl_bytecode::Instruction as Bytecode;
+
+use crate::{
+    ast::{Program, Span},
+    ast_json,
+    diagnostic::Diagnostic,
+    instruction::Instruction,
+    ir,
+};
+
+/// A pass that reads the syntax tree.
+pub trait AstPass: Send + Sync {
+    /// The name of the pass, in the diagnostics that it gets wrong.
+    fn name(&self) -> &str;
+
+    fn run(&self, tree: &SyntaxTree, diagnostics: &mut Vec<Diagnostic>);
+}
+
+/// A pass that reads and changes the lowered code.
+pub trait IrPass: Send + Sync {
+    /// The name of the pass, in the diagnostics that it gets wrong.
+    fn name(&self) -> &str;
+
+    fn run(&self, ir: &mut Ir, diagnostics: &mut Vec<Diagnostic>);
+}
+
+/// The custom passes, by the phase they follow. Passes added after the same
+/// phase run in the order they are added.
+#[derive(Clone, Default)]
+pub struct PassManager {
+    after_parsing: Vec<Arc<dyn AstPass>>,
+    after_analysis: Vec<Arc<dyn AstPass>>,
+    after_lowering: Vec<Arc<dyn IrPass>>,
+}
+
+impl PassManager {
+    /// Adds a pass that runs on the syntax tree as written, before the macros
+    /// are expanded.
+    pub fn add_after_parsing(&mut self, pass: impl AstPass + 'static) -> &mut PassManager {
+        self.after_parsing.push(Arc::new(pass));
+        self
+    }
+
+    /// Adds a pass that runs on the syntax tree once its types are checked:
+    /// the paths are resolved and the generic functions are instantiated.
+    pub fn add_after_analysis(&mut self, pass: impl AstPass + 'static) -> &mut PassManager {
+        self.after_analysis.push(Arc::new(pass));
+        self
+    }
+
+    /// Adds a pass that runs on the lowered code, once optimized, before its
+    /// labels are resolved.
+    pub fn add_after_lowering(&mut self, pass: impl IrPass + 'static) -> &mut PassManager {
+        self.after_lowering.push(Arc::new(pass));
+        self
+    }
+
+    /// Runs the passes that follow the parsing or the analysis.
+    pub(crate) fn run_on_ast(&self, analyzed: bool, program: &Program) -> Vec<Diagnostic> {
+        let passes = if analyzed {
+            &self.after_analysis
+        } else {
+            &self.after_parsing
+        };
+        let tree = SyntaxTree(program);
+        let mut diagnostics = Vec::new();
+
+        passes
+            .iter()
+            .for_each(|pass| pass.run(&tree, &mut diagnostics));
+
+        diagnostics
+    }
+
+    /// Runs the passes that follow the lowering.
+    ///
+    /// A pass that makes an instruction target a label that starts no block
+    /// is reported, as the code could not be laid out.
+    pub(crate) fn run_on_ir(&self, program: &mut ir::Program) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for pass in &self.after_lowering {
+            pass.run(&mut Ir(program), &mut diagnostics);
+
+            let unknown = program
+                .blocks()
+                .iter()
+                .flat_map(|block| block.instructions())
+                .flat_map(Instruction::targets)
+                .find(|label| program.block_of(*label).is_none());
+
+            if let Some(label) = unknown {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Pass `{}` targets label {}, which starts no block",
+                    pass.name(),
+                    label
+                )));
+                break;
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Debug for PassManager {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("PassManager")
+            .field(
+                "after_parsing",
+                &self
+                    .after_parsing
+                    .iter()
+                    .map(|p| p.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "after_analysis",
+                &self
+                    .after_analysis
+                    .iter()
+                    .map(|p| p.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "after_lowering",
+                &self
+                    .after_lowering
+                    .iter()
+                    .map(|p| p.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Where some code is, to report diagnostics about it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location(pub(crate) Span);
+
+impl Location {
+    /// The name of the file, if the code comes from one.
+    pub fn file(&self) -> Option<&str> {
+        self.0.file().map(|file| file.name())
+    }
+
+    pub fn line(&self) -> u32 {
+        self.0.line()
+    }
+
+    pub fn column(&self) -> u32 {
+        self.0.column() as u32
+    }
+}
+
+/// A syntax tree, which passes can read.
+pub struct SyntaxTree<'a>(&'a Program);
+
+impl<'a> SyntaxTree<'a> {
+    /// Returns the whole tree as JSON, in the format of `--emit=ast`.
+    pub fn to_json(&self) -> String {
+        ast_json::program_to_json(self.0)
+    }
+
+    /// Lists the functions, those of the modules included. The name of a
+    /// function declared in a module is its path.
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        let mut functions = Vec::new();
+        collect_functions(self.0, "", &mut functions);
+
+        functions
+    }
+}
+
+/// A function of a syntax tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionInfo {
+    name: String,
+    arity: usize,
+    location: Location,
+}
+
+impl FunctionInfo {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Where the name of the function is.
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+fn collect_functions(program: &Program, prefix: &str, functions: &mut Vec<FunctionInfo>) {
+    functions.extend(program.functions().iter().map(|function| FunctionInfo {
+        name: format!("{}{}", prefix, function.name()),
+        arity: function.args().len(),
+        location: Location(function.span().clone()),
+    }));
+
+    program.modules().iter().for_each(|module| {
+        let prefix = format!("{}{}::", prefix, module.name());
+        collect_functions(module.content(), &prefix, functions)
+    });
+}
+
+/// The lowered code, as basic blocks of instructions, which passes can read
+/// and change.
+///
+/// The instructions are bytecode whose addresses are labels: a jump targets
+/// the block that a label starts, and a call the function it starts. New
+/// instructions can only target the labels that already start a block.
+pub struct Ir<'a>(&'a mut ir::Program);
+
+impl<'a> Ir<'a> {
+    /// The number of blocks. They are laid out in order.
+    pub fn blocks(&self) -> usize {
+        self.0.blocks().len()
+    }
+
+    /// The labels that start a block.
+    pub fn labels(&self, block: usize) -> &[u32] {
+        self.0.blocks()[block].labels()
+    }
+
+    pub fn instructions(&self, block: usize) -> Vec<Bytecode> {
+        self.0.blocks()[block]
+            .instructions()
+            .iter()
+            .map(Instruction::to_labelled)
+            .collect()
+    }
+
+    /// Where the code an instruction is lowered from is, if anywhere.
+    pub fn location(&self, block: usize, idx: usize) -> Option<Location> {
+        self.0.blocks()[block].locations()[idx]
+            .clone()
+            .map(Location)
+    }
+
+    /// Inserts an instruction at a position of a block. It is located where
+    /// the instruction it comes before is, or else the one it follows.
+    pub fn insert(&mut self, block: usize, idx: usize, instruction: Bytecode) {
+        let (instructions, locations) = self.0.blocks_mut()[block].instructions_mut();
+
+        let location = locations
+            .get(idx)
+            .or_else(|| locations.get(idx.wrapping_sub(1)))
+            .cloned()
+            .flatten();
+
+        instructions.insert(idx, Instruction::from_labelled(instruction));
+        locations.insert(idx, location);
+    }
+
+    /// Removes the instruction at a position of a block, and returns it.
+    pub fn remove(&mut self, block: usize, idx: usize) -> Bytecode {
+        let (instructions, locations) = self.0.blocks_mut()[block].instructions_mut();
+        locations.remove(idx);
+
+        instructions.remove(idx).to_labelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dyl_bytecode::operations::{Out, PushI, PushS};
+
+    use crate::{io::SourceFile, session::Session, Lint, LintLevel, Options, Severity};
+
+    use super::*;
+
+    /// Warns about the functions that take more than two arguments.
+    struct TooManyArgs;
+
+    const TOO_MANY_ARGS: Lint = Lint::new("too-many-args", LintLevel::Warn);
+
+    impl AstPass for TooManyArgs {
+        fn name(&self) -> &str {
+            "too-many-args"
+        }
+
+        fn run(&self, tree: &SyntaxTree, diagnostics: &mut Vec<Diagnostic>) {
+            diagnostics.extend(
+                tree.functions()
+                    .iter()
+                    .filter(|function| function.arity() > 2)
+                    .map(|function| {
+                        Diagnostic::warning(format!(
+                            "`{}` takes too many arguments",
+                            function.name()
+                        ))
+                        .with_location(function.location())
+                        .with_lint(TOO_MANY_ARGS)
+                    }),
+            );
+        }
+    }
+
+    /// Prints a string before the body of each function.
+    struct Trace;
+
+    impl IrPass for Trace {
+        fn name(&self) -> &str {
+            "trace"
+        }
+
+        fn run(&self, ir: &mut Ir, _diagnostics: &mut Vec<Diagnostic>) {
+            for block in 0..ir.blocks() {
+                if !ir.labels(block).is_empty() && !ir.instructions(block).is_empty() {
+                    ir.insert(block, 0, PushS("enter".to_owned()).into());
+                    ir.insert(block, 1, Out.into());
+                }
+            }
+        }
+    }
+
+    /// Jumps to a label that does not exist.
+    struct Broken;
+
+    impl IrPass for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn run(&self, ir: &mut Ir, _diagnostics: &mut Vec<Diagnostic>) {
+            ir.insert(0, 0, dyl_bytecode::operations::Goto(1000).into());
+        }
+    }
+
+    fn session(code: &str, passes: PassManager) -> Session {
+        let file = SourceFile::new("a.dyl".to_owned(), code.to_owned());
+        let options = Options {
+            passes,
+            ..Options::default()
+        };
+
+        Session::new(vec![file], options)
+    }
+
+    #[test]
+    fn ast_passes_report_diagnostics() {
+        let mut passes = PassManager::default();
+        passes
+            .add_after_parsing(TooManyArgs)
+            .add_after_analysis(TooManyArgs);
+
+        let mut session = session(
+            "mod m { fn f(a, b, c) { a + b + c } }\nfn main() { m::f(1, 2, 3) }",
+            passes,
+        );
+
+        assert!(session.compile().is_ok());
+
+        let diagnostics = session.take_diagnostics();
+        let messages = diagnostics
+            .iter()
+            .map(|d| (d.severity(), d.message()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                (Severity::Warning, "`m::f` takes too many arguments"),
+                (Severity::Warning, "`m::f` takes too many arguments"),
+            ]
+        );
+        assert!(diagnostics.to_string().contains(" --> a.dyl:1:12"));
+    }
+
+    #[test]
+    fn ast_passes_have_lints() {
+        let code = "fn f(a, b, c) { a + b + c }\nfn main() { f(1, 2, 3) }";
+        let mut passes = PassManager::default();
+        passes.add_after_parsing(TooManyArgs);
+
+        let mut options = Options {
+            passes,
+            ..Options::default()
+        };
+        options.lints.register(TOO_MANY_ARGS).unwrap();
+
+        let lint = options.lints.lint("too-many-args").unwrap();
+        options.lints.set(lint, LintLevel::Allow);
+        let mut allowed = Session::new(
+            vec![SourceFile::new("a.dyl".to_owned(), code.to_owned())],
+            options.clone(),
+        );
+        assert!(allowed.compile().is_ok());
+        assert!(allowed.take_diagnostics().is_empty());
+
+        options.lints.set(lint, LintLevel::Deny);
+        let mut denied = Session::new(
+            vec![SourceFile::new("a.dyl".to_owned(), code.to_owned())],
+            options,
+        );
+        assert_eq!(denied.compile(), Err(()));
+        assert_eq!(
+            denied.take_diagnostics().iter().next().unwrap().severity(),
+            Severity::Error
+        );
+    }
+
+    #[test]
+    fn ir_passes_change_the_code() {
+        let mut passes = PassManager::default();
+        passes.add_after_lowering(Trace);
+
+        let (instructions, _) = session("fn main() { 42 }", passes).compile().unwrap();
+
+        assert_eq!(
+            instructions
+                .iter()
+                .map(|instruction| instruction.to_string())
+                .collect::<Vec<_>>()[3..6],
+            [
+                Bytecode::from(PushS("enter".to_owned())).to_string(),
+                Bytecode::from(Out).to_string(),
+                Bytecode::from(PushI(42)).to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ir_passes_keep_labels() {
+        let mut passes = PassManager::default();
+        passes.add_after_lowering(Broken);
+
+        let mut session = session("fn main() { 42 }", passes);
+
+        assert_eq!(session.compile(), Err(()));
+        assert_eq!(
+            session.take_diagnostics().iter().next().unwrap().message(),
+            "Pass `broken` targets label 1000, which starts no block"
+        );
+    }
+
+    #[test]
+    fn instructions_keep_their_labels() {
+        let instructions = [
+            Instruction::cond_jmp(1, 2, 3),
+            Instruction::make_closure(4, 1, 2),
+            Instruction::push_s("a".to_owned()),
+            Instruction::ret(1, 2),
+        ];
+
+        instructions.iter().for_each(|instruction| {
+            assert_eq!(
+                &Instruction::from_labelled(instruction.to_labelled()),
+                instruction
+            )
+        });
+    }
+}
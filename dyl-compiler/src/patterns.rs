@@ -0,0 +1,204 @@
+use std::iter;
+
+use crate::{
+    ast::Pattern,
+    context::EnumContext,
+    diagnostic::{Code, Diagnostic},
+};
+
+static WILDCARD: Pattern = Pattern::Wildcard;
+
+/// Checks that a list of patterns matches every possible value.
+///
+/// Returns an error telling what is not covered otherwise.
+pub(crate) fn check_exhaustiveness(
+    patterns: &[&Pattern],
+    enums: &EnumContext,
+) -> Result<(), Diagnostic> {
+    let rows = patterns
+        .iter()
+        .map(|pattern| vec![*pattern])
+        .collect::<Vec<_>>();
+
+    if is_exhaustive(&rows, enums) {
+        return Ok(());
+    }
+
+    // Pointing at a missing variant is more helpful than a generic message,
+    // when possible.
+    let missing_variant = enum_of_column(&rows).and_then(|enum_name| {
+        enums
+            .variants(enum_name)?
+            .iter()
+            .find(|(variant, arity)| !is_exhaustive(&specialize(&rows, variant, *arity), enums))
+            .map(|(variant, arity)| match arity {
+                0 => format!("`{}::{}`", enum_name, variant),
+                _ => format!("`{}::{}(..)`", enum_name, variant),
+            })
+    });
+
+    Err(Diagnostic::error(match missing_variant {
+        Some(variant) => format!("Non-exhaustive `match`: pattern {} is not covered", variant),
+        None => "Non-exhaustive `match`: consider adding a wildcard arm `_`".to_owned(),
+    })
+    .with_code(Code::NonExhaustiveMatch))
+}
+
+/// Returns whether the rows of a pattern matrix match every possible
+/// sequence of values.
+///
+/// Each column is specialized by the constructors it contains: an enum
+/// column is exhaustive if every variant is, and an integer column can only
+/// be covered by wildcards.
+fn is_exhaustive(rows: &[Vec<&Pattern>], enums: &EnumContext) -> bool {
+    match rows.first() {
+        None => return false,
+        Some(row) if row.is_empty() => return true,
+        Some(_) => {}
+    }
+
+    let variants = enum_of_column(rows).and_then(|enum_name| enums.variants(enum_name));
+
+    match variants {
+        Some(variants) => variants
+            .iter()
+            .all(|(variant, arity)| is_exhaustive(&specialize(rows, variant, *arity), enums)),
+
+        None => {
+            let default = rows
+                .iter()
+                .filter(|row| row[0].matches_anything())
+                .map(|row| row[1..].to_vec())
+                .collect::<Vec<_>>();
+
+            is_exhaustive(&default, enums)
+        }
+    }
+}
+
+fn enum_of_column<'a>(rows: &[Vec<&'a Pattern>]) -> Option<&'a str> {
+    rows.iter().find_map(|row| match row[0] {
+        Pattern::Variant { enum_name, .. } => Some(enum_name.as_str()),
+        _ => None,
+    })
+}
+
+/// Keeps the rows whose first pattern matches a given variant, and replaces
+/// this pattern by the patterns of the variant fields.
+fn specialize<'a>(rows: &[Vec<&'a Pattern>], variant: &str, arity: usize) -> Vec<Vec<&'a Pattern>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, tail) = row.split_first().unwrap();
+
+            let fields = match head {
+                _ if head.matches_anything() => iter::repeat_n(&WILDCARD, arity).collect(),
+                Pattern::Variant {
+                    variant: name,
+                    fields,
+                    ..
+                } if name == variant => fields.iter().collect::<Vec<_>>(),
+                _ => return None,
+            };
+
+            Some(fields.into_iter().chain(tail.iter().copied()).collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::LoweringContext;
+
+    use super::*;
+
+    fn enums() -> EnumContext {
+        let mut ctxt = LoweringContext::new();
+        ctxt.declare_enum(
+            "Option".to_owned(),
+            vec![("None".to_owned(), 0), ("Some".to_owned(), 1)],
+        )
+        .unwrap();
+
+        ctxt.enums().clone()
+    }
+
+    fn variant(name: &str, fields: Vec<Pattern>) -> Pattern {
+        Pattern::variant("Option".to_owned(), name.to_owned(), fields)
+    }
+
+    fn check(patterns: &[Pattern]) -> Result<(), String> {
+        let patterns = patterns.iter().collect::<Vec<_>>();
+        check_exhaustiveness(&patterns, &enums()).map_err(|e| e.message().to_owned())
+    }
+
+    #[test]
+    fn wildcard_is_exhaustive() {
+        assert!(check(&[Pattern::Integer(1), Pattern::Wildcard]).is_ok());
+    }
+
+    #[test]
+    fn integers_are_not_exhaustive() {
+        assert_eq!(
+            check(&[Pattern::Integer(0), Pattern::Integer(1)]),
+            Err("Non-exhaustive `match`: consider adding a wildcard arm `_`".to_owned())
+        );
+    }
+
+    #[test]
+    fn all_variants_are_exhaustive() {
+        let patterns = [
+            variant("Some", vec![Pattern::Wildcard]),
+            variant("None", Vec::new()),
+        ];
+
+        assert!(check(&patterns).is_ok());
+    }
+
+    #[test]
+    fn bindings_are_exhaustive() {
+        let patterns = [
+            variant("Some", vec![Pattern::Binding("x".to_owned())]),
+            variant("None", Vec::new()),
+        ];
+
+        assert!(check(&patterns).is_ok());
+        assert!(check(&[Pattern::Binding("x".to_owned())]).is_ok());
+    }
+
+    #[test]
+    fn missing_variant() {
+        assert_eq!(
+            check(&[variant("Some", vec![Pattern::Wildcard])]),
+            Err("Non-exhaustive `match`: pattern `Option::None` is not covered".to_owned())
+        );
+    }
+
+    #[test]
+    fn refutable_field() {
+        let patterns = [
+            variant("Some", vec![Pattern::Integer(0)]),
+            variant("None", Vec::new()),
+        ];
+
+        assert_eq!(
+            check(&patterns),
+            Err("Non-exhaustive `match`: pattern `Option::Some(..)` is not covered".to_owned())
+        );
+    }
+
+    #[test]
+    fn nested_variants() {
+        let patterns = [
+            variant("Some", vec![variant("Some", vec![Pattern::Wildcard])]),
+            variant("Some", vec![variant("None", Vec::new())]),
+            variant("None", Vec::new()),
+        ];
+
+        assert!(check(&patterns).is_ok());
+    }
+
+    #[test]
+    fn no_arm() {
+        assert!(check(&[]).is_err());
+    }
+}
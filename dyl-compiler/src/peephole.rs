@@ -0,0 +1,285 @@
+//! Replaces short sequences of instructions by cheaper equivalent ones.
+//!
+//! The rewrites only look at the instructions of a single block, so that a
+//! jump never lands in the middle of a rewritten sequence:
+//!
+//!   - a value that is pushed then popped right away is never pushed,
+//!   - consecutive `Pop`s are merged, and `Pop(0)` is removed,
+//!   - copying the top of the stack back to its own slot is removed,
+//!   - multiplying by one and adding zero are removed,
+//!   - multiplying by a power of two is replaced by a left shift,
+//!   - a `Goto` to the block that follows is removed.
+
+use crate::{
+    ast::Span,
+    instruction::{Goto, Instruction, LoadLocal, Pop, PopCopy, PushCopy, PushI, StoreLocal},
+    ir,
+};
+
+pub(crate) fn optimize(program: &mut ir::Program) {
+    for block in program.blocks_mut() {
+        let (instructions, locations) = block.instructions_mut();
+        while rewrite(instructions, locations) {}
+    }
+
+    remove_gotos_to_next_block(program);
+}
+
+/// Applies the first rewrite that matches, and tells whether there was one.
+///
+/// The instructions a rewrite produces are located where the first of the
+/// instructions they replace is.
+fn rewrite(instructions: &mut Vec<Instruction>, locations: &mut Vec<Option<Span>>) -> bool {
+    for idx in 0..instructions.len() {
+        if let Instruction::Pop(Pop(0)) = instructions[idx] {
+            instructions.remove(idx);
+            locations.remove(idx);
+            return true;
+        }
+
+        let (first, second) = match instructions.get(idx..idx + 2) {
+            Some([first, second]) => (first, second),
+            _ => continue,
+        };
+
+        let replacement = match (first, second) {
+            (Instruction::Pop(Pop(a)), Instruction::Pop(Pop(b))) => match a.checked_add(*b) {
+                Some(amount) => vec![Instruction::pop(amount)],
+                None => continue,
+            },
+
+            (_, Instruction::Pop(Pop(amount))) if *amount > 0 && is_pure_push(first) => {
+                vec![Instruction::pop(amount - 1)]
+            }
+
+            (Instruction::PushCopy(PushCopy(offset)), Instruction::PopCopy(PopCopy(dest)))
+                if offset + 1 == *dest =>
+            {
+                Vec::new()
+            }
+
+            (Instruction::LoadLocal(LoadLocal(src)), Instruction::StoreLocal(StoreLocal(dest)))
+                if src == dest =>
+            {
+                Vec::new()
+            }
+
+            // Both operations are commutative, so the constant may be either
+            // operand.
+            (Instruction::PushI(PushI(1)), Instruction::Mul(_))
+            | (Instruction::PushI(PushI(0)), Instruction::AddI(_)) => Vec::new(),
+
+            // Both wrap around on overflow, so they agree on every value. A
+            // checked multiplication has its check in between, and is kept.
+            (Instruction::PushI(PushI(factor)), Instruction::Mul(_))
+                if (*factor as u32).is_power_of_two() =>
+            {
+                vec![
+                    Instruction::push_i(factor.trailing_zeros() as i32),
+                    Instruction::shl_i(),
+                ]
+            }
+
+            _ => continue,
+        };
+
+        let location = locations[idx].clone();
+        locations.splice(idx..idx + 2, vec![location; replacement.len()]);
+        instructions.splice(idx..idx + 2, replacement);
+        return true;
+    }
+
+    false
+}
+
+/// Tells whether an instruction pushes exactly one value without consuming
+/// any.
+fn is_pure_push(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::PushI(_)
+        | Instruction::PushS(_)
+        | Instruction::PushConst(_)
+        | Instruction::PushCopy(_)
+        | Instruction::LoadLocal(_) => true,
+        Instruction::Pack(pack) => pack.0 == 0,
+        _ => false,
+    }
+}
+
+fn remove_gotos_to_next_block(program: &mut ir::Program) {
+    let next_blocks = (0..program.blocks().len())
+        .map(|idx| match program.blocks()[idx].instructions().last() {
+            Some(Instruction::Goto(Goto(label))) => program.block_of(*label) == Some(idx + 1),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+
+    program
+        .blocks_mut()
+        .iter_mut()
+        .zip(next_blocks)
+        .filter(|(_, jumps_to_next)| *jumps_to_next)
+        .for_each(|(block, _)| {
+            let (instructions, locations) = block.instructions_mut();
+            instructions.pop();
+            locations.pop();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::LabelContext;
+
+    use super::*;
+
+    fn optimized(instructions: Vec<Instruction>, labels: &LabelContext) -> Vec<Instruction> {
+        let mut program = ir::Program::from_instructions(instructions, labels);
+        optimize(&mut program);
+
+        program.into_instructions(&mut labels.clone())
+    }
+
+    #[test]
+    fn pushed_then_popped() {
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::push_i(1),
+                    Instruction::pack(0),
+                    Instruction::pop(1),
+                    Instruction::pop(1),
+                    Instruction::push_s("a".to_owned()),
+                    Instruction::pop(2),
+                ],
+                &LabelContext::default(),
+            ),
+            [Instruction::pop(1)]
+        );
+    }
+
+    #[test]
+    fn values_that_are_not_pushed_alone_are_kept() {
+        let code = vec![
+            Instruction::push_i(1),
+            Instruction::pack(1),
+            Instruction::pop(1),
+        ];
+
+        assert_eq!(optimized(code.clone(), &LabelContext::default()), code);
+    }
+
+    #[test]
+    fn copies_to_the_same_slot() {
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::push_copy(2),
+                    Instruction::pop_copy(3),
+                    Instruction::push_copy(2),
+                    Instruction::pop_copy(2),
+                ],
+                &LabelContext::default(),
+            ),
+            [Instruction::push_copy(2), Instruction::pop_copy(2)]
+        );
+
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::load_local(-2),
+                    Instruction::store_local(-2),
+                    Instruction::load_local(1),
+                    Instruction::pop(1),
+                ],
+                &LabelContext::default(),
+            ),
+            []
+        );
+    }
+
+    #[test]
+    fn identities() {
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::push_copy(0),
+                    Instruction::push_i(1),
+                    Instruction::mul(),
+                    Instruction::push_i(0),
+                    Instruction::add_i(),
+                    Instruction::push_i(3),
+                    Instruction::mul(),
+                ],
+                &LabelContext::default(),
+            ),
+            [
+                Instruction::push_copy(0),
+                Instruction::push_i(3),
+                Instruction::mul()
+            ]
+        );
+    }
+
+    #[test]
+    fn multiplications_by_powers_of_two() {
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::push_copy(0),
+                    Instruction::push_i(8),
+                    Instruction::mul(),
+                    Instruction::push_i(i32::MIN),
+                    Instruction::mul(),
+                    Instruction::push_i(-8),
+                    Instruction::mul(),
+                ],
+                &LabelContext::default(),
+            ),
+            [
+                Instruction::push_copy(0),
+                Instruction::push_i(3),
+                Instruction::shl_i(),
+                Instruction::push_i(31),
+                Instruction::shl_i(),
+                Instruction::push_i(-8),
+                Instruction::mul(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rewrites_do_not_cross_blocks() {
+        let mut labels = LabelContext::default();
+        let label = labels.new_anonymous();
+        labels.set_position(label, 1).unwrap();
+
+        let code = vec![Instruction::push_i(1), Instruction::pop(1)];
+
+        assert_eq!(optimized(code.clone(), &labels), code);
+    }
+
+    #[test]
+    fn gotos_to_the_next_block() {
+        let mut labels = LabelContext::default();
+        let (next, other) = (labels.new_anonymous(), labels.new_anonymous());
+        labels.set_position(next, 1).unwrap();
+        labels.set_position(other, 3).unwrap();
+
+        assert_eq!(
+            optimized(
+                vec![
+                    Instruction::goto(next),
+                    Instruction::push_i(1),
+                    Instruction::goto(next),
+                    Instruction::f_stop(),
+                ],
+                &labels,
+            ),
+            [
+                Instruction::push_i(1),
+                Instruction::goto(next),
+                Instruction::f_stop()
+            ]
+        );
+    }
+}
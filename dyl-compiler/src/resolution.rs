@@ -0,0 +1,1254 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    ast::{
+        Binding, BindingPattern, Const, Enum, ExprKind, Function, Ident, Item, LocalFunctions,
+        MatchArm, Pattern, Program, Span, Struct, Type, VariantLiteral,
+    },
+    builtins::Builtin,
+    context::{ErrorContext, ParsingContext, PassResult, WarningContext},
+    diagnostic::{self, Code, Diagnostic},
+    lint::Lint,
+    symbol::Symbol,
+};
+
+/// Flattens the modules of a program into a single namespace.
+///
+/// Items declared in a module are renamed after their full path (for
+/// instance `math::square`), and every path written in an expression is
+/// rewritten to the full path of the item it refers to.
+///
+/// A path is looked up in the module it is written in, then in each of the
+/// enclosing modules, up to the top-level items. In each of these scopes, the
+/// items brought in by `import` declarations are considered as well. Local
+/// variables take precedence over any item.
+///
+/// Functions defined in a block are hoisted out of it, under the path of the
+/// function that encloses them (for instance `main::helper`). They are only
+/// visible in that block, where they take precedence over items but not over
+/// local variables.
+///
+/// An identifier that refers neither to a variable in scope nor to a constant
+/// or a function is reported as an undefined variable, at the location it is
+/// written at.
+///
+/// A `let` binding that is never read is reported as a warning, unless its
+/// name starts with an underscore. So is the first statement following a
+/// `return` or a call to `panic`, which is never executed.
+pub(crate) fn resolve_paths(
+    program: &Program,
+    ctxt: ParsingContext,
+) -> PassResult<ParsingContext, Program> {
+    let rslt = flatten_program(program, ctxt.errors(), ctxt.warnings());
+
+    ctxt.wrap_result(rslt)
+}
+
+fn flatten_program(
+    program: &Program,
+    errors: &ErrorContext,
+    warnings: &WarningContext,
+) -> Result<Program, ()> {
+    let mut resolver = Resolver::new(program, errors, warnings);
+
+    let mut items = Vec::new();
+    resolver
+        .flatten(program, &[], &mut items)
+        .map(|()| Program::new(items))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ItemKind {
+    Function,
+    Const,
+    Struct,
+    Enum,
+    Module,
+}
+
+/// A variable in scope.
+struct Local {
+    name: Symbol,
+
+    /// Where the variable is bound, for the variables that are reported when
+    /// they are never read.
+    span: Option<Span>,
+    is_read: Cell<bool>,
+}
+
+impl Local {
+    fn new(name: impl Into<Symbol>, span: Option<Span>) -> Local {
+        Local {
+            name: name.into(),
+            span,
+            is_read: Cell::new(false),
+        }
+    }
+}
+
+/// Tells whether `name` is a variable in scope, and marks it as read if it
+/// is.
+fn read_local(locals: &[Local], name: &Symbol) -> bool {
+    match locals.iter().rev().find(|local| local.name == *name) {
+        Some(local) => {
+            local.is_read.set(true);
+            true
+        }
+        None => false,
+    }
+}
+
+struct Resolver<'a> {
+    /// Every item of the program, by full path.
+    items: HashMap<String, ItemKind>,
+
+    /// The imports of each module, by full path of the module. Each import
+    /// is stored as its alias and the full path of the item it refers to.
+    imports: HashMap<String, Vec<(String, String)>>,
+
+    /// The functions defined in the blocks enclosing the current expression,
+    /// innermost last. Each one is stored as its name and the path it is
+    /// hoisted under.
+    local_functions: Vec<(String, String)>,
+
+    /// The local functions hoisted so far, which are yet to be added to the
+    /// program.
+    hoisted: Vec<Function>,
+
+    /// The paths given to local functions so far.
+    hoisted_paths: HashSet<String>,
+
+    /// The full path of the item the current expression belongs to.
+    enclosing: String,
+
+    /// The type parameters of the function the current expression belongs
+    /// to.
+    generics: Vec<String>,
+
+    errors: &'a ErrorContext,
+    warnings: &'a WarningContext,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(
+        program: &Program,
+        errors: &'a ErrorContext,
+        warnings: &'a WarningContext,
+    ) -> Resolver<'a> {
+        let mut resolver = Resolver {
+            items: HashMap::new(),
+            imports: HashMap::new(),
+            local_functions: Vec::new(),
+            hoisted: Vec::new(),
+            hoisted_paths: HashSet::new(),
+            enclosing: String::new(),
+            generics: Vec::new(),
+            errors,
+            warnings,
+        };
+
+        resolver.declare(program, &[]);
+
+        resolver
+    }
+
+    fn declare(&mut self, program: &Program, module: &[String]) {
+        let declarations = program
+            .functions()
+            .iter()
+            .map(|f| (f.name(), ItemKind::Function))
+            .chain(program.consts().iter().map(|c| (c.name(), ItemKind::Const)))
+            .chain(
+                program
+                    .structs()
+                    .iter()
+                    .map(|s| (s.name(), ItemKind::Struct)),
+            )
+            .chain(program.enums().iter().map(|e| (e.name(), ItemKind::Enum)))
+            .chain(
+                program
+                    .modules()
+                    .iter()
+                    .map(|m| (m.name(), ItemKind::Module)),
+            );
+
+        for (name, kind) in declarations {
+            self.items.insert(qualify(module, name), kind);
+        }
+
+        let imports = program
+            .imports()
+            .iter()
+            .map(|i| (i.alias().to_owned(), i.path().join("::")))
+            .collect();
+        self.imports.insert(module.join("::"), imports);
+
+        for m in program.modules() {
+            let mut path = module.to_vec();
+            path.push(m.name().to_owned());
+
+            self.declare(m.content(), &path);
+        }
+    }
+
+    /// Adds the items of a module to `items`, with their full path as name
+    /// and their paths resolved.
+    fn flatten(
+        &mut self,
+        program: &Program,
+        module: &[String],
+        items: &mut Vec<Item>,
+    ) -> Result<(), ()> {
+        let imports_rslt = program
+            .imports()
+            .iter()
+            .map(|i| {
+                let path = i.path().join("::");
+
+                if self.items.contains_key(&path) {
+                    Ok(())
+                } else {
+                    let error = Diagnostic::error(format!("Unresolved import `{}`", path))
+                        .with_code(Code::UndefinedName)
+                        .with_span(i.span().clone());
+
+                    self.errors.add(error);
+                    Err(())
+                }
+            })
+            .fold(Ok(()), Result::and);
+
+        for s in program.structs() {
+            let name = qualify(module, s.name());
+            let struct_ = Struct::new(name, s.fields().to_vec()).with_span(s.span().clone());
+            items.push(Item::Struct(struct_));
+        }
+
+        for e in program.enums() {
+            let name = qualify(module, e.name());
+            let enum_ = Enum::new(name, e.variants().to_vec()).with_span(e.span().clone());
+            items.push(Item::Enum(enum_));
+        }
+
+        for c in program.consts() {
+            let name = qualify(module, c.name());
+            self.enclosing = name.clone();
+
+            let value = self.expr(c.value(), module, &mut Vec::new());
+            items.push(Item::Const(
+                Const::new(name, value).with_span(c.span().clone()),
+            ));
+        }
+
+        for f in program.functions() {
+            let function = self.function(f, qualify(module, f.name()), module);
+            items.push(Item::Function(function));
+        }
+
+        items.extend(self.hoisted.drain(..).map(Item::Function));
+
+        program
+            .modules()
+            .iter()
+            .map(|m| {
+                let mut path = module.to_vec();
+                path.push(m.name().to_owned());
+
+                self.flatten(m.content(), &path, items)
+            })
+            .fold(imports_rslt, Result::and)
+    }
+
+    /// Resolves the paths of a function, which is renamed to `name`.
+    fn function(&mut self, f: &Function, name: String, module: &[String]) -> Function {
+        let enclosing = std::mem::replace(&mut self.enclosing, name.clone());
+        let generics = std::mem::replace(&mut self.generics, f.generics().to_vec());
+
+        let mut locals = f
+            .args()
+            .iter()
+            .map(|arg| Local::new(arg.as_str(), None))
+            .collect();
+        let body = self.expr(f.body(), module, &mut locals);
+
+        let arg_types = f
+            .arg_types()
+            .iter()
+            .map(|ty| ty.as_ref().map(|ty| self.type_(ty, module, f.generics())))
+            .collect();
+        let ret_type = f.ret_type().map(|ty| self.type_(ty, module, f.generics()));
+
+        self.enclosing = enclosing;
+        self.generics = generics;
+
+        Function::new(name, f.args().to_vec(), body)
+            .with_signature(f.generics().to_vec(), arg_types, ret_type)
+            .with_span(f.span().clone())
+    }
+
+    /// Hoists the functions defined in a block, and resolves the rest of the
+    /// block.
+    fn local_functions(
+        &mut self,
+        local: &LocalFunctions,
+        module: &[String],
+        locals: &mut Vec<Local>,
+    ) -> ExprKind {
+        let scope_start = self.local_functions.len();
+
+        for f in local.functions() {
+            let is_duplicate = self.local_functions[scope_start..]
+                .iter()
+                .any(|(name, _)| name == f.name());
+
+            if is_duplicate {
+                self.errors.add(
+                    Diagnostic::error(format!(
+                        "Function `{}` is defined more than once in the same block",
+                        f.name()
+                    ))
+                    .with_code(Code::DuplicateName)
+                    .with_span(f.span().clone()),
+                );
+                continue;
+            }
+
+            let path = self.hoisted_path(f.name());
+            self.local_functions.push((f.name().to_owned(), path));
+        }
+
+        // Each function sees every function of the block, including itself.
+        let scope = self.local_functions[scope_start..].to_vec();
+        for (f, (_, path)) in local.functions().iter().zip(scope) {
+            let function = self.function(f, path, module);
+            self.hoisted.push(function);
+        }
+
+        let body = self.expr(local.body(), module, locals);
+        self.local_functions.truncate(scope_start);
+
+        body
+    }
+
+    /// Returns a path that is not used by any item nor by any other local
+    /// function.
+    fn hoisted_path(&mut self, name: &str) -> String {
+        let base = format!("{}::{}", self.enclosing, name);
+
+        let path = (1..)
+            .map(|n| match n {
+                1 => base.clone(),
+                n => format!("{}#{}", base, n),
+            })
+            .find(|path| !self.items.contains_key(path) && !self.hoisted_paths.contains(path))
+            .unwrap();
+
+        self.hoisted_paths.insert(path.clone());
+
+        path
+    }
+
+    /// Returns the path a local function is hoisted under, if `name` refers
+    /// to one.
+    fn local_function(&self, name: &str) -> Option<String> {
+        self.local_functions
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, path)| path.clone())
+    }
+
+    /// Returns the full path and the kind of the item a path refers to.
+    fn lookup(&self, module: &[String], path: &str) -> Option<(String, ItemKind)> {
+        let (first, rest) = match path.split_once("::") {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (path, None),
+        };
+
+        (0..=module.len()).rev().find_map(|depth| {
+            let scope = &module[..depth];
+
+            let declared = qualify(scope, path);
+            if let Some(kind) = self.items.get(&declared) {
+                return Some((declared, *kind));
+            }
+
+            let (_, target) = self
+                .imports
+                .get(&scope.join("::"))?
+                .iter()
+                .find(|(alias, _)| alias == first)?;
+
+            let imported = match rest {
+                Some(rest) => format!("{}::{}", target, rest),
+                None => target.clone(),
+            };
+
+            self.items.get(&imported).map(|kind| (imported, *kind))
+        })
+    }
+
+    /// Resolves a path that is expected to refer to an item of a given kind.
+    ///
+    /// Paths that can not be resolved are kept as is, so that later passes
+    /// report them.
+    fn resolve(&self, module: &[String], path: &str, expected: &[ItemKind]) -> String {
+        match self.lookup(module, path) {
+            Some((full_path, kind)) if expected.contains(&kind) => full_path,
+            _ => path.to_owned(),
+        }
+    }
+
+    /// Returns what a path used as a value refers to: a local variable, a
+    /// local function, a constant or a function.
+    fn lookup_value(&self, module: &[String], path: &str, locals: &[Local]) -> Option<String> {
+        let symbol = Symbol::from(path);
+        if locals.iter().any(|local| local.name == symbol) {
+            return Some(path.to_owned());
+        }
+
+        if let Some(hoisted) = self.local_function(path) {
+            return Some(hoisted);
+        }
+
+        match self.lookup(module, path) {
+            Some((full_path, ItemKind::Const | ItemKind::Function)) => Some(full_path),
+            _ => None,
+        }
+    }
+
+    /// Returns the names that refer to a value without a path in a module,
+    /// sorted.
+    fn value_names(&self, module: &[String], locals: &[Local]) -> Vec<String> {
+        let is_value = |path: &String| {
+            matches!(
+                self.items.get(path),
+                Some(ItemKind::Const | ItemKind::Function)
+            )
+        };
+
+        let mut names = locals
+            .iter()
+            .map(|local| local.name.to_string())
+            .chain(self.local_functions.iter().map(|(name, _)| name.clone()))
+            .collect::<Vec<_>>();
+
+        for depth in 0..=module.len() {
+            let scope = &module[..depth];
+            let prefix = qualify(scope, "");
+
+            names.extend(
+                self.items
+                    .keys()
+                    .filter(|path| is_value(path))
+                    .filter_map(|path| path.strip_prefix(prefix.as_str()))
+                    .filter(|name| !name.contains("::"))
+                    .map(str::to_owned),
+            );
+
+            if let Some(imports) = self.imports.get(&scope.join("::")) {
+                names.extend(
+                    imports
+                        .iter()
+                        .filter(|(_, target)| is_value(target))
+                        .map(|(alias, _)| alias.clone()),
+                );
+            }
+        }
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn resolve_value(&self, module: &[String], path: &str, locals: &[Local]) -> String {
+        self.lookup_value(module, path, locals)
+            .unwrap_or_else(|| path.to_owned())
+    }
+
+    fn ident(&self, ident: &Ident, module: &[String], locals: &[Local]) -> ExprKind {
+        read_local(locals, ident.symbol());
+
+        match self.lookup_value(module, ident.name(), locals) {
+            Some(name) => ident.rebuild(name),
+            None => {
+                let mut error = Diagnostic::error(format!("Undefined variable `{}`", ident.name()))
+                    .with_code(Code::UndefinedName)
+                    .with_span(ident.span().clone());
+
+                let names = self.value_names(module, locals);
+                let closest =
+                    diagnostic::closest_name(ident.name(), names.iter().map(String::as_str));
+                if let Some(name) = closest {
+                    error = error.with_suggestion(
+                        ident.span().clone(),
+                        format!("did you mean `{}`?", name),
+                        name,
+                    );
+                }
+
+                self.errors.add(error);
+
+                ident.rebuild(ident.name().to_owned())
+            }
+        }
+    }
+
+    /// Resolves the paths of an expression, which keeps its location.
+    fn expr(&mut self, expr: &ExprKind, module: &[String], locals: &mut Vec<Local>) -> ExprKind {
+        let resolved = match expr {
+            ExprKind::Bindings(b) => {
+                let scope_start = locals.len();
+                let mut diverging = None;
+
+                let bindings = b
+                    .defines()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, binding)| {
+                        if diverging.is_none() && self.diverges(binding.value(), module, locals) {
+                            diverging = Some(idx);
+                        }
+
+                        let value = self.expr(binding.value(), module, locals);
+                        let pattern = self.binding_pattern(binding.pattern(), module);
+                        let annotation = binding
+                            .annotation()
+                            .map(|ty| self.type_(ty, module, &self.generics));
+
+                        locals.extend(
+                            binding
+                                .names()
+                                .into_iter()
+                                .map(|name| Local::new(name, Some(binding.span().clone()))),
+                        );
+
+                        Binding::from_pattern(pattern, value, binding.is_mutable())
+                            .with_annotation(annotation)
+                            .with_span(binding.span().clone())
+                    })
+                    .collect();
+
+                if let Some(idx) = diverging {
+                    match b.defines().get(idx + 1) {
+                        Some(next) => self.report_unreachable(next.span()),
+                        None if !is_implicit_unit(b.ending_expression()) => {
+                            self.report_unreachable(b.ending_span())
+                        }
+                        None => {}
+                    }
+                }
+
+                let ending = self.expr(b.ending_expression(), module, locals);
+                locals
+                    .drain(scope_start..)
+                    .for_each(|local| self.check_read(&local));
+
+                b.rebuild(bindings, ending)
+            }
+
+            ExprKind::Statements(s) => {
+                let diverging = s
+                    .statements()
+                    .iter()
+                    .position(|statement| self.diverges(statement, module, locals));
+
+                match diverging {
+                    Some(idx) if idx + 1 < s.statements().len() => {
+                        self.report_unreachable(s.span(idx + 1))
+                    }
+                    Some(idx) if !is_implicit_unit(s.ending_expression()) => {
+                        self.report_unreachable(s.span(idx + 1))
+                    }
+                    _ => {}
+                }
+
+                s.rebuild(
+                    self.exprs(s.statements(), module, locals),
+                    self.expr(s.ending_expression(), module, locals),
+                )
+            }
+
+            ExprKind::Ident(i) => self.ident(i, module, locals),
+
+            ExprKind::FnCall(c) => {
+                // Local variables may hold a closure.
+                let name = if read_local(locals, &Symbol::from(c.name())) {
+                    c.name().to_owned()
+                } else if let Some(hoisted) = self.local_function(c.name()) {
+                    hoisted
+                } else {
+                    self.resolve(module, c.name(), &[ItemKind::Function])
+                };
+
+                c.rebuild(name, self.exprs(c.args(), module, locals))
+            }
+
+            ExprKind::Assignment(a) => ExprKind::assignment(
+                self.resolve_value(module, a.name(), locals),
+                self.expr(a.value(), module, locals),
+            ),
+
+            ExprKind::StructLiteral(s) => {
+                let fields = s
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.expr(value, module, locals)))
+                    .collect();
+
+                ExprKind::struct_literal(
+                    self.resolve(module, s.name(), &[ItemKind::Struct]),
+                    fields,
+                )
+            }
+
+            ExprKind::VariantLiteral(v) => self.variant_literal(v, module, locals),
+
+            ExprKind::Match(m) => {
+                let scrutinee = self.expr(m.scrutinee(), module, locals);
+                let arms = m
+                    .arms()
+                    .iter()
+                    .map(|arm| {
+                        let scope_start = locals.len();
+                        locals.extend(
+                            arm.pattern()
+                                .names()
+                                .into_iter()
+                                .map(|name| Local::new(name, None)),
+                        );
+
+                        let guard = arm.guard().map(|g| self.expr(g, module, locals));
+                        let body = self.expr(arm.body(), module, locals);
+                        locals.truncate(scope_start);
+
+                        MatchArm::new(self.pattern(arm.pattern(), module), body)
+                            .with_guard(guard)
+                            .with_span(arm.span().clone())
+                    })
+                    .collect();
+
+                ExprKind::match_(scrutinee, arms)
+            }
+
+            ExprKind::Closure(c) => {
+                let scope_start = locals.len();
+                locals.extend(c.params().iter().map(|p| Local::new(p.as_str(), None)));
+
+                let body = self.expr(c.body(), module, locals);
+                locals.truncate(scope_start);
+
+                ExprKind::closure(c.params().to_vec(), body)
+            }
+
+            ExprKind::LocalFunctions(l) => self.local_functions(l, module, locals),
+
+            // Only builtin types can be cast to, they need no qualification.
+            _ => expr.map_children(|child| self.expr(child, module, locals)),
+        };
+
+        match expr {
+            // Only the body of the block is left, at its own location.
+            ExprKind::LocalFunctions(_) => resolved,
+            _ => resolved.with_span(expr.span().clone()),
+        }
+    }
+
+    /// Tells whether control never goes past an expression, because it
+    /// returns or panics on every path.
+    fn diverges(&self, expr: &ExprKind, module: &[String], locals: &[Local]) -> bool {
+        match expr {
+            ExprKind::Return(_) => true,
+
+            // A function or a variable named `panic` hides the builtin.
+            ExprKind::FnCall(c) => {
+                Builtin::from_name(c.name()) == Some(Builtin::Panic)
+                    && self.lookup_value(module, c.name(), locals).is_none()
+            }
+
+            ExprKind::If(i) => {
+                self.diverges(i.condition(), module, locals)
+                    || i.alternative().is_some_and(|alternative| {
+                        self.diverges(i.consequent(), module, locals)
+                            && self.diverges(alternative, module, locals)
+                    })
+            }
+
+            ExprKind::Match(m) => {
+                self.diverges(m.scrutinee(), module, locals)
+                    || (!m.arms().is_empty()
+                        && m.arms()
+                            .iter()
+                            .all(|arm| self.diverges(arm.body(), module, locals)))
+            }
+
+            ExprKind::Bindings(b) => {
+                b.defines()
+                    .iter()
+                    .any(|binding| self.diverges(binding.value(), module, locals))
+                    || self.diverges(b.ending_expression(), module, locals)
+            }
+
+            ExprKind::Statements(s) => {
+                s.statements()
+                    .iter()
+                    .any(|statement| self.diverges(statement, module, locals))
+                    || self.diverges(s.ending_expression(), module, locals)
+            }
+
+            ExprKind::LocalFunctions(l) => self.diverges(l.body(), module, locals),
+
+            _ => false,
+        }
+    }
+
+    fn report_unreachable(&self, span: &Span) {
+        let warning = Diagnostic::warning("Unreachable code")
+            .with_lint(Lint::UNREACHABLE)
+            .with_span(span.clone());
+
+        self.warnings.add(warning);
+    }
+
+    /// Warns about a variable going out of scope without having been read.
+    fn check_read(&self, local: &Local) {
+        let span = match &local.span {
+            Some(span) if !local.is_read.get() && !local.name.as_str().starts_with('_') => span,
+            _ => return,
+        };
+
+        let warning = Diagnostic::warning(format!("Unused variable `{}`", local.name))
+            .with_lint(Lint::UNUSED_VARIABLE)
+            .with_span(span.clone())
+            .with_note(format!(
+                "prefix it with an underscore to silence this: `_{}`",
+                local.name
+            ));
+
+        self.warnings.add(warning);
+    }
+
+    fn exprs(
+        &mut self,
+        exprs: &[ExprKind],
+        module: &[String],
+        locals: &mut Vec<Local>,
+    ) -> Vec<ExprKind> {
+        exprs
+            .iter()
+            .map(|expr| self.expr(expr, module, locals))
+            .collect()
+    }
+
+    /// Resolves an expression of the form `a::b` or `a::b(..)`.
+    ///
+    /// The parser can not tell an enum variant from an item declared in a
+    /// module. The latter are turned into a function call, a function value
+    /// or a constant here. A path that names neither is reported.
+    fn variant_literal(
+        &mut self,
+        literal: &VariantLiteral,
+        module: &[String],
+        locals: &mut Vec<Local>,
+    ) -> ExprKind {
+        let args = self.exprs(literal.args(), module, locals);
+        let parenthesized = literal.is_parenthesized();
+
+        let rebuild = |enum_name: String, args| {
+            let variant = literal.variant().to_owned();
+
+            if parenthesized {
+                ExprKind::variant_literal(enum_name, variant, args)
+            } else {
+                ExprKind::unit_variant(enum_name, variant)
+            }
+        };
+
+        if let Some((enum_name, ItemKind::Enum)) = self.lookup(module, literal.enum_name()) {
+            return rebuild(enum_name, args);
+        }
+
+        let path = format!("{}::{}", literal.enum_name(), literal.variant());
+
+        match self.lookup(module, &path) {
+            Some((function, ItemKind::Function)) if parenthesized => {
+                ExprKind::fn_call(function, args)
+            }
+            Some((function, ItemKind::Function)) => ExprKind::ident(function),
+            Some((constant, ItemKind::Const)) if !parenthesized => ExprKind::ident(constant),
+
+            _ => {
+                let error = Diagnostic::error(format!("Undefined item `{}`", path))
+                    .with_code(Code::UndefinedName)
+                    .with_span(literal.span().clone());
+                self.errors.add(error);
+
+                rebuild(literal.enum_name().to_owned(), args)
+            }
+        }
+    }
+
+    /// Resolves the structs and enums a type refers to. Type parameters
+    /// shadow any item.
+    fn type_(&self, ty: &Type, module: &[String], generics: &[String]) -> Type {
+        match ty {
+            Type::Named(name) if generics.contains(name) => ty.clone(),
+            Type::Named(name) => {
+                Type::Named(self.resolve(module, name, &[ItemKind::Struct, ItemKind::Enum]))
+            }
+
+            Type::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.type_(element, module, generics))
+                    .collect(),
+            ),
+
+            Type::Array(element) => Type::array(self.type_(element, module, generics)),
+        }
+    }
+
+    fn binding_pattern(&self, pattern: &BindingPattern, module: &[String]) -> BindingPattern {
+        match pattern {
+            BindingPattern::Name(_) | BindingPattern::Wildcard => pattern.clone(),
+
+            BindingPattern::Tuple(elements) => BindingPattern::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.binding_pattern(element, module))
+                    .collect(),
+            ),
+
+            BindingPattern::Struct { name, fields } => BindingPattern::Struct {
+                name: self.resolve(module, name, &[ItemKind::Struct]),
+                fields: fields
+                    .iter()
+                    .map(|(field, pattern)| (field.clone(), self.binding_pattern(pattern, module)))
+                    .collect(),
+            },
+        }
+    }
+
+    fn pattern(&self, pattern: &Pattern, module: &[String]) -> Pattern {
+        match pattern {
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => Pattern::variant(
+                self.resolve(module, enum_name, &[ItemKind::Enum]),
+                variant.clone(),
+                fields.iter().map(|f| self.pattern(f, module)).collect(),
+            ),
+
+            Pattern::Wildcard | Pattern::Binding(_) | Pattern::Integer(_) => pattern.clone(),
+        }
+    }
+}
+
+/// Tells whether a block ending is the `()` a block without ending expression
+/// evaluates to.
+fn is_implicit_unit(ending: &ExprKind) -> bool {
+    *ending == ExprKind::unit()
+}
+
+fn qualify(module: &[String], name: &str) -> String {
+    if module.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}::{}", module.join("::"), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{inline_expr, parser};
+
+    use super::*;
+
+    fn resolve(code: &str) -> (Result<Program, ()>, String) {
+        let (_, program) = parser::parse_input(code).unwrap();
+        let errors = ErrorContext::default();
+
+        let rslt = flatten_program(&program, &errors, &WarningContext::default());
+
+        (rslt, errors.to_string())
+    }
+
+    fn warnings(code: &str) -> String {
+        let (_, program) = parser::parse_input(code).unwrap();
+        let warnings = WarningContext::default();
+
+        flatten_program(&program, &ErrorContext::default(), &warnings).unwrap();
+
+        warnings.to_string()
+    }
+
+    fn function<'a>(program: &'a Program, name: &str) -> &'a Function {
+        program
+            .functions()
+            .iter()
+            .find(|f| f.name() == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn items_are_renamed_after_their_path() {
+        let (program, _) = resolve("mod math { mod int { fn square(x) { x * x } } const N = 1; }");
+        let program = program.unwrap();
+
+        assert_eq!(program.functions()[0].name(), "math::int::square");
+        assert_eq!(program.consts()[0].name(), "math::N");
+        assert!(program.modules().is_empty());
+    }
+
+    #[test]
+    fn qualified_path() {
+        let (program, _) = resolve(
+            "mod math { fn square(x) { x * x } mod int { fn one() { 1 } } } \
+             fn main() { math::square(math::int::one()) }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::fn_call(
+                "math::square".to_owned(),
+                vec![ExprKind::fn_call("math::int::one".to_owned(), Vec::new())],
+            )
+        );
+    }
+
+    #[test]
+    fn items_of_enclosing_modules() {
+        let (program, _) = resolve("const N = 1; mod m { const M = 2; fn f() { N + M } }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "m::f").body(),
+            &ExprKind::addition(
+                ExprKind::ident("N".to_owned()),
+                ExprKind::ident("m::M".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn imported_items() {
+        let (program, _) = resolve(
+            "mod math { fn square(x) { x * x } const PI = 3; } \
+             import math::square; import math::PI; \
+             fn main() { square(PI) }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::fn_call(
+                "math::square".to_owned(),
+                vec![ExprKind::ident("math::PI".to_owned())],
+            )
+        );
+    }
+
+    #[test]
+    fn local_variables_take_precedence() {
+        let (program, _) = resolve("const x = 1; fn main() { let x = 2; x }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &inline_expr! {
+                {
+                    let x = 2;
+                    x
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn closure_params_and_calls_are_local() {
+        let (program, _) = resolve("mod m { const x = 1; fn f() { let f = |x| x; f(2) } }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "m::f").body(),
+            &ExprKind::bindings(
+                vec![Binding::new(
+                    "f".to_owned(),
+                    ExprKind::closure(vec!["x".to_owned()], ExprKind::ident("x".to_owned())),
+                )],
+                ExprKind::fn_call("f".to_owned(), vec![ExprKind::integer(2)]),
+            )
+        );
+    }
+
+    #[test]
+    fn imported_enum() {
+        let (program, _) = resolve(
+            "mod shapes { enum Shape { Empty } } import shapes::Shape; \
+             fn main() { match Shape::Empty { Shape::Empty => 0 } }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(program.enums()[0].name(), "shapes::Shape");
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::match_(
+                ExprKind::unit_variant("shapes::Shape".to_owned(), "Empty".to_owned()),
+                vec![MatchArm::new(
+                    Pattern::variant("shapes::Shape".to_owned(), "Empty".to_owned(), Vec::new()),
+                    ExprKind::integer(0),
+                )],
+            )
+        );
+    }
+
+    #[test]
+    fn qualified_function_value() {
+        let (program, _) = resolve(
+            "mod math { fn square(x) { x * x } fn one() { 1 } } \
+             fn main() { (math::square, math::one()) }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::tuple(vec![
+                ExprKind::ident("math::square".to_owned()),
+                ExprKind::fn_call("math::one".to_owned(), Vec::new()),
+            ])
+        );
+    }
+
+    #[test]
+    fn signature_types() {
+        let (program, _) = resolve(
+            "mod shapes { struct Point { x } fn f<Point>(a: Point) {} fn g(a: [Point]) -> int {} }",
+        );
+        let program = program.unwrap();
+
+        let f = function(&program, "shapes::f");
+        assert_eq!(f.generics(), ["Point"]);
+        assert_eq!(f.arg_types(), [Some(Type::named("Point"))]);
+
+        let g = function(&program, "shapes::g");
+        assert_eq!(
+            g.arg_types(),
+            [Some(Type::array(Type::named("shapes::Point")))]
+        );
+        assert_eq!(g.ret_type(), Some(&Type::named("int")));
+    }
+
+    #[test]
+    fn unresolved_import() {
+        let (program, errors) = resolve("mod math {} import math::cube;");
+
+        assert!(program.is_err());
+        assert_eq!(errors, "1:13: Unresolved import `math::cube`\n");
+    }
+
+    #[test]
+    fn unknown_paths_are_reported() {
+        let (_, errors) = resolve("fn main() { math::square(1) + foo::bar }");
+
+        assert_eq!(
+            errors,
+            "1:13: Undefined item `math::square`\n1:31: Undefined item `foo::bar`\n"
+        );
+    }
+
+    #[test]
+    fn local_functions_are_hoisted() {
+        let (program, _) = resolve("fn main() { fn f(x) { g(x) } fn g(x) { x } f(1) }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::fn_call("main::f".to_owned(), vec![ExprKind::integer(1)])
+        );
+        assert_eq!(
+            function(&program, "main::f").body(),
+            &ExprKind::fn_call("main::g".to_owned(), vec![ExprKind::ident("x".to_owned())])
+        );
+        assert_eq!(
+            function(&program, "main::g").body(),
+            &ExprKind::ident("x".to_owned())
+        );
+    }
+
+    #[test]
+    fn local_functions_are_scoped_to_their_block() {
+        let (program, _) = resolve("fn f() { 0 } fn main() { let a = { fn f() { 1 } f() }; f() }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::bindings(
+                vec![Binding::new(
+                    "a".to_owned(),
+                    ExprKind::fn_call("main::f".to_owned(), Vec::new()),
+                )],
+                ExprKind::fn_call("f".to_owned(), Vec::new()),
+            )
+        );
+    }
+
+    #[test]
+    fn hoisted_paths_are_unique() {
+        let (program, _) = resolve(
+            "mod main { fn f() { 0 } } \
+             fn main() { let a = { fn f() { 1 } f() }; let b = { fn f() { 2 } f() }; 0 }",
+        );
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main::f#2").body(),
+            &ExprKind::integer(1)
+        );
+        assert_eq!(
+            function(&program, "main::f#3").body(),
+            &ExprKind::integer(2)
+        );
+    }
+
+    #[test]
+    fn local_variables_take_precedence_over_local_functions() {
+        let (program, _) = resolve("fn main() { fn f() { 1 } let f = || 2; f() }");
+        let program = program.unwrap();
+
+        assert_eq!(
+            function(&program, "main").body(),
+            &ExprKind::bindings(
+                vec![Binding::new(
+                    "f".to_owned(),
+                    ExprKind::closure(Vec::new(), ExprKind::integer(2)),
+                )],
+                ExprKind::fn_call("f".to_owned(), Vec::new()),
+            )
+        );
+    }
+
+    #[test]
+    fn duplicate_local_function() {
+        let (_, errors) = resolve("fn main() { fn f() { 1 } fn f() { 2 } f() }");
+
+        assert_eq!(
+            errors,
+            "1:29: Function `f` is defined more than once in the same block\n"
+        );
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let (_, errors) = resolve("fn main() {\n    let a = b;\n    |x| x + a + c\n}");
+
+        assert_eq!(
+            errors,
+            "2:13: Undefined variable `b`\n3:17: Undefined variable `c`\n"
+        );
+    }
+
+    #[test]
+    fn similar_names_are_suggested() {
+        let (_, program) = parser::parse_input(
+            "const LIMIT = 3; fn main() { let count = 1; cuont + LIMTI + other }",
+        )
+        .unwrap();
+        let errors = ErrorContext::default();
+
+        flatten_program(&program, &errors, &WarningContext::default()).unwrap();
+
+        let suggestions = errors
+            .diagnostics()
+            .iter()
+            .map(|error| {
+                error
+                    .suggestions()
+                    .next()
+                    .map(|s| s.replacement().to_owned())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            suggestions,
+            [Some("count".to_owned()), Some("LIMIT".to_owned()), None]
+        );
+    }
+
+    #[test]
+    fn variable_is_not_in_scope_of_its_value() {
+        let (_, errors) = resolve("fn main() { let a = a; a }");
+
+        assert_eq!(errors, "1:21: Undefined variable `a`\n");
+    }
+
+    #[test]
+    fn items_used_as_values_are_defined() {
+        let (_, errors) = resolve(
+            "mod m { const N = 1; fn f() { N } } \
+             import m::f; fn main() { fn g() { 1 } (m::N, f, g) }",
+        );
+
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn unused_variables() {
+        let warnings = warnings(
+            "fn main() {\n    let a = 1;\n    let (b, c) = (2, 3);\n    let mut d = 4;\n    d = c;\n    0\n}",
+        );
+
+        assert_eq!(
+            warnings,
+            "2:9: Unused variable `a`\n  note: prefix it with an underscore to silence this: `_a`\n\
+             3:9: Unused variable `b`\n  note: prefix it with an underscore to silence this: `_b`\n\
+             4:13: Unused variable `d`\n  note: prefix it with an underscore to silence this: `_d`\n"
+        );
+    }
+
+    #[test]
+    fn variables_read_by_closures_and_calls_are_used() {
+        let warnings = warnings(
+            "fn main() { let a = 1; let f = |x| x + a; let g = || 2; let h = 3; let h = h; f(g()) + h }",
+        );
+
+        assert_eq!(warnings, "");
+    }
+
+    #[test]
+    fn underscore_prefixed_variables_are_not_reported() {
+        assert_eq!(
+            warnings("fn main() { let _a = 1; let (_, _b) = (2, 3); 0 }"),
+            ""
+        );
+    }
+
+    #[test]
+    fn unreachable_statements() {
+        let warnings = warnings(
+            "fn f(a) {\n    if a { return 1; } else { panic(\"no\") }\n    print(a);\n    2\n}\n\
+             fn main() {\n    let x = return 1;\n    let _y = x;\n    0\n}\n\
+             fn g() {\n    return 1;\n    3\n}",
+        );
+
+        assert_eq!(
+            warnings,
+            "3:5: Unreachable code\n8:9: Unreachable code\n13:5: Unreachable code\n"
+        );
+    }
+
+    #[test]
+    fn code_after_a_branch_that_may_not_diverge_is_reachable() {
+        let warnings = warnings(
+            "fn f(a) { if a { return 1; }; match a { 1 => 2, _ => panic(\"no\") }; 3 } \
+             fn main() { fn panic(m) { 0 } panic(1); return 0; }",
+        );
+
+        assert_eq!(warnings, "");
+    }
+}
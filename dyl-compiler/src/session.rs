@@ -0,0 +1,589 @@
+//! The state of the compilation of a program.
+//!
+//! A [`Session`] holds what the compilation is given (the source files and
+//! the options) and what it produces besides the bytecode (the diagnostics,
+//! the control-flow graph and the statistics). Each phase of the pipeline is
+//! a method that reads the session and records what it reports in it, so
+//! that the phases can be run, and tested, one at a time.
+//!
+//! The contexts of the passes are still handed from a phase to the next:
+//! they hold what a pass learns about the program, the session holds the rest.
+
+use std::{io, mem, panic, thread};
+
+use dyl_bytecode::{
+    constant_pool::ConstantPool,
+    line_table::{LineTable, SourceLocation},
+    Instruction,
+};
+
+use crate::{
+    ast::{Program, Span},
+    cfg,
+    context::{
+        self, CompilerPassError, LoweringContext, ParsingContext, PassResult, TypingContext,
+    },
+    dead_code, desugaring,
+    diagnostic::{Diagnostic, Diagnostics, Severity},
+    expansion, folding, hir, inlining,
+    io::SourceFile,
+    ir, lexer, lowering, monomorphization, parser, peephole, resolution, ssa,
+    stack_check::{self, StackError},
+    type_checker, OptLevel, Options,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Session {
+    files: Vec<SourceFile>,
+    options: Options,
+
+    /// The diagnostics reported so far, in the order they were reported.
+    diagnostics: Vec<Diagnostic>,
+
+    /// The control-flow graph of the compiled code, in the Graphviz dot
+    /// format, if the options ask for it.
+    cfg: Option<String>,
+
+    /// The constants of the emitted code.
+    constants: ConstantPool,
+
+    /// The number of instructions before the optimizations.
+    unoptimized_len: usize,
+
+    /// The number of instructions before and after the optimizations.
+    sizes: Option<(usize, usize)>,
+
+    /// Whether diagnostics were left out, once the error limit was reached.
+    truncated: bool,
+
+    /// The program parsed ahead of the phases, until they take it.
+    parsed: Option<PassResult<ParsingContext, Program>>,
+}
+
+impl Session {
+    pub(crate) fn new(files: Vec<SourceFile>, options: Options) -> Session {
+        Session {
+            files,
+            options,
+            diagnostics: Vec::new(),
+            cfg: None,
+            constants: ConstantPool::default(),
+            unoptimized_len: 0,
+            sizes: None,
+            truncated: false,
+            parsed: None,
+        }
+    }
+
+    /// Runs every phase, and returns the bytecode along with its line table.
+    pub(crate) fn compile(&mut self) -> Result<(Vec<Instruction>, LineTable), ()> {
+        self.on_compiler_thread(Session::run_phases)
+    }
+
+    /// Runs some phases on a thread whose stack is large enough for the
+    /// program, whatever the stack of the calling thread.
+    ///
+    /// The phases walk the program recursively, so it is parsed first, to
+    /// know how deep it is. Long chains of operations are as deep as they are
+    /// long, even though they are not nested.
+    pub(crate) fn on_compiler_thread<T: Send>(
+        &mut self,
+        phases: impl FnOnce(&mut Session) -> Result<T, ()> + Send,
+    ) -> Result<T, ()> {
+        let parsed = parser::parse_files(&self.files, self.options.nesting_limit);
+        let depth = match &parsed {
+            Ok((ctxt, _)) => ctxt.deepest(),
+            Err(_) => 0,
+        };
+        self.parsed = Some(parsed);
+
+        let stack_size = parser::compiler_stack_size(self.options.nesting_limit, depth);
+
+        let rslt = thread::scope(|scope| {
+            let compilation = thread::Builder::new()
+                .name("compiler".to_owned())
+                .stack_size(stack_size)
+                .spawn_scoped(scope, || {
+                    let rslt = phases(self);
+                    // Dropping the program walks it too.
+                    self.parsed = None;
+                    rslt
+                })?;
+
+            Ok(compilation
+                .join()
+                .unwrap_or_else(|panic| panic::resume_unwind(panic)))
+        });
+
+        rslt.unwrap_or_else(|e: io::Error| {
+            let message = format!("Failed to start the compiler thread: {}", e);
+            self.diagnostics.push(Diagnostic::error(message));
+            Err(())
+        })
+    }
+
+    fn run_phases(&mut self) -> Result<(Vec<Instruction>, LineTable), ()> {
+        let (ctxt, ast) = self.parse()?;
+        let (ctxt, ast, typed) = self.analyze(ctxt, &ast)?;
+        let (ctxt, program) = self.lower(ctxt, &ast, &typed)?;
+
+        self.emit(ctxt, program)
+    }
+
+    /// Lists the tokens of the source files, trivia aside, one per line.
+    pub(crate) fn tokens(&self) -> String {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                lexer::tokenize(file.content())
+                    .into_iter()
+                    .filter(|token| !token.kind().is_trivia())
+                    .map(move |token| {
+                        format!(
+                            "{}:{}:{} {} {:?}\n",
+                            file.name(),
+                            token.span().line(),
+                            token.span().column(),
+                            token.kind(),
+                            token.text()
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Parses the source files into a single program. Each phase ends with
+    /// the custom passes that follow it.
+    pub(crate) fn parse(&mut self) -> Result<(ParsingContext, Program), ()> {
+        let rslt = match self.parsed.take() {
+            Some(parsed) => parsed,
+            None => parser::parse_files(&self.files, self.options.nesting_limit),
+        };
+        let (ctxt, ast) = self.report(rslt)?;
+
+        let custom = self.options.passes.run_on_ast(false, &ast);
+        self.record_custom(custom)?;
+
+        Ok((ctxt, ast))
+    }
+
+    /// Expands the macros of a parsed program, desugars it, resolves its
+    /// paths, creates the instances of its generic functions and checks its
+    /// types. Returns the syntax tree of the checked program along with its
+    /// typed tree.
+    pub(crate) fn analyze(
+        &mut self,
+        ctxt: ParsingContext,
+        ast: &Program,
+    ) -> Result<(TypingContext, Program, hir::Program), ()> {
+        let rslt = expansion::expand_macros(ast, ctxt)
+            .and_then(|(ctxt, ast)| {
+                resolution::resolve_paths(&desugaring::desugar_program(&ast), ctxt)
+            })
+            .and_then(|(ctxt, ast)| monomorphization::monomorphize(&ast, ctxt))
+            .and_then(|(ctxt, ast)| {
+                type_checker::check_program(&ast, ctxt.into_typing_context())
+                    .map(|(ctxt, typed)| (ctxt, (ast, typed)))
+            });
+        let (ctxt, (ast, typed)) = self.report(rslt)?;
+
+        let custom = self.options.passes.run_on_ast(true, &ast);
+        self.record_custom(custom)?;
+
+        Ok((ctxt, ast, typed))
+    }
+
+    /// Lowers the typed tree of a checked program to the IR, optimized
+    /// as the options ask for, and changed by the custom passes.
+    ///
+    /// The optimizations rewrite the syntax tree, which is typed again once
+    /// optimized.
+    pub(crate) fn lower(
+        &mut self,
+        ctxt: TypingContext,
+        ast: &Program,
+        typed: &hir::Program,
+    ) -> Result<(LoweringContext, ir::Program), ()> {
+        let mut ctxt = ctxt.into_lowering_context();
+        if self.options.debug_checks {
+            ctxt.add_debug_checks();
+        }
+
+        let mut optimized_ctxt = ctxt.clone();
+        // The warnings are taken from the code as written.
+        optimized_ctxt.warnings().take();
+        optimized_ctxt.reuse_dead_slots();
+
+        let rslt = lowering::lower_program(typed, ctxt);
+        let (ctxt, program) = self.report(rslt)?;
+        self.unoptimized_len = program.instruction_count();
+
+        // Denied lints stop the compilation here, once every warning is known.
+        self.record(ctxt.warnings().take());
+        if self
+            .diagnostics
+            .iter()
+            .any(|d| d.severity() == Severity::Error)
+        {
+            return Err(());
+        }
+
+        // The whole program is lowered first, so that the errors and warnings
+        // of the code that optimizations remove are still reported.
+        let (ctxt, mut program) = if self.options.opt_level > OptLevel::O0 {
+            let optimized = optimize_ast(ast, self.options.opt_level);
+            let typed = type_checker::type_program(&optimized);
+            let rslt = lowering::lower_program(&typed, optimized_ctxt);
+            self.report(rslt)?
+        } else {
+            (ctxt, program)
+        };
+
+        if self.options.opt_level >= OptLevel::O2 {
+            peephole::optimize(&mut program);
+        }
+
+        let custom = self.options.passes.run_on_ir(&mut program);
+        self.record_custom(custom)?;
+
+        if self.options.cfg_output.is_some() {
+            self.cfg = Some(cfg::Cfg::new(&program).to_dot(&program));
+        }
+
+        Ok((ctxt, program))
+    }
+
+    /// Lays out the lowered program, resolves its labels and checks its
+    /// stack accesses. With debug checks, the values of the frames are
+    /// checked too, once the stack is known to be sound.
+    pub(crate) fn emit(
+        &mut self,
+        mut ctxt: LoweringContext,
+        program: ir::Program,
+    ) -> Result<(Vec<Instruction>, LineTable), ()> {
+        let verified = self
+            .options
+            .debug_checks
+            .then(|| (program.clone(), ctxt.functions().clone()));
+
+        let (instructions, locations) = program.into_located_instructions(ctxt.labels_mut());
+        self.sizes = Some((self.unoptimized_len, instructions.len()));
+        self.constants = mem::take(ctxt.constants_mut());
+
+        // The functions that optimizations removed have no position.
+        let functions = ctxt
+            .functions()
+            .signatures()
+            .filter_map(|signature| {
+                let addr = ctxt.labels().resolve_anonymous(signature.label()).ok()?;
+                Some((addr, signature.arity()))
+            })
+            .collect();
+
+        let ctxt = ctxt.into_label_resolution_context();
+        let instructions = context::resolve_labels(instructions.as_slice(), &ctxt);
+
+        if let Err(e) = stack_check::check_stack(&instructions, &functions) {
+            self.diagnostics.push(stack_diagnostic(e, &locations));
+            return Err(());
+        }
+
+        if let Some((program, functions)) = verified {
+            if let Err(e) = ssa::verify(&ssa::construct(&program, &functions)) {
+                let message = format!("Internal compiler error: {}", e);
+                self.diagnostics.push(Diagnostic::error(message));
+                return Err(());
+            }
+        }
+
+        Ok((instructions, line_table(&locations)))
+    }
+
+    /// Returns the diagnostics reported so far, which are forgotten.
+    pub(crate) fn take_diagnostics(&mut self) -> Diagnostics {
+        self.diagnostics.drain(..).collect()
+    }
+
+    pub(crate) fn cfg(&self) -> Option<&str> {
+        self.cfg.as_deref()
+    }
+
+    /// Returns the number of instructions before and after the
+    /// optimizations, once the program is emitted.
+    pub(crate) fn sizes(&self) -> Option<(usize, usize)> {
+        self.sizes
+    }
+
+    /// Returns the constants of the code, once it is emitted, which are
+    /// forgotten.
+    pub(crate) fn take_constants(&mut self) -> ConstantPool {
+        mem::take(&mut self.constants)
+    }
+
+    /// Records the diagnostics of a failed pass.
+    fn report<T>(&mut self, rslt: Result<T, CompilerPassError>) -> Result<T, ()> {
+        rslt.map_err(|e| self.record(e.into_diagnostics()))
+    }
+
+    /// Records the diagnostics of custom passes, whose errors stop the
+    /// compilation.
+    fn record_custom(&mut self, diagnostics: Vec<Diagnostic>) -> Result<(), ()> {
+        let failed = diagnostics.iter().any(|d| d.severity() == Severity::Error);
+        self.record(diagnostics);
+
+        if failed {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Records diagnostics, once the level of their lint is applied.
+    ///
+    /// Once the error limit is reached, the diagnostics that follow are left
+    /// out, and the last error tells so.
+    fn record(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        let levels = &self.options.lints;
+        let limit = self.options.error_limit.unwrap_or(usize::MAX);
+        let mut errors = self
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity() == Severity::Error)
+            .count();
+
+        for d in diagnostics.into_iter().filter_map(|d| levels.apply(d)) {
+            if self.truncated {
+                return;
+            }
+
+            // Nothing is recorded after the last error.
+            if errors >= limit {
+                self.truncated = true;
+                if let Some(last) = self.diagnostics.pop() {
+                    let note = format!("The compilation stopped at the error limit ({})", limit);
+                    self.diagnostics.push(last.with_note(note));
+                }
+
+                return;
+            }
+
+            if d.severity() == Severity::Error {
+                errors += 1;
+            }
+            self.diagnostics.push(d);
+        }
+    }
+}
+
+/// Runs the passes of an optimization level that work on the syntax tree.
+fn optimize_ast(ast: &Program, level: OptLevel) -> Program {
+    let ast = if level >= OptLevel::O2 {
+        inlining::inline_functions(ast)
+    } else {
+        ast.clone()
+    };
+
+    dead_code::eliminate_dead_code(&folding::fold_program(&ast))
+}
+
+/// Reports a failed stack check, which is a bug of the compiler, where the
+/// faulty instruction comes from.
+fn stack_diagnostic(e: StackError, locations: &[Option<Span>]) -> Diagnostic {
+    let diagnostic = Diagnostic::error(format!("Internal compiler error: {}", e));
+
+    match locations.get(e.addr() as usize).cloned().flatten() {
+        Some(span) => diagnostic.with_span(span),
+        None => diagnostic,
+    }
+}
+
+/// Creates the line table of the bytecode, given the location of each of its
+/// instructions.
+fn line_table(locations: &[Option<Span>]) -> LineTable {
+    LineTable::from_locations(locations.iter().map(|location| {
+        location.as_ref().map(|span| SourceLocation {
+            file: span.file().map(|file| file.name().to_owned()),
+            line: span.line(),
+            column: span.column() as u32,
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Lint, LintLevel};
+
+    use super::*;
+
+    fn session(code: &str, options: Options) -> Session {
+        let file = SourceFile::new("a.dyl".to_owned(), code.to_owned());
+        Session::new(vec![file], options)
+    }
+
+    #[test]
+    fn phases_run_one_at_a_time() {
+        let mut session = session("fn main() { let a = 1; 2 }", Options::default());
+
+        let (ctxt, ast) = session.parse().unwrap();
+        let (ctxt, ast, typed) = session.analyze(ctxt, &ast).unwrap();
+
+        let diagnostics = session.take_diagnostics();
+        assert!(diagnostics.is_empty());
+
+        let (ctxt, program) = session.lower(ctxt, &ast, &typed).unwrap();
+        let (instructions, _) = session.emit(ctxt, program).unwrap();
+
+        assert_eq!(instructions[0], Instruction::res_v(1));
+        assert_eq!(
+            session
+                .take_diagnostics()
+                .iter()
+                .map(Diagnostic::message)
+                .collect::<Vec<_>>(),
+            ["Unused variable `a`"]
+        );
+    }
+
+    #[test]
+    fn failures_are_recorded() {
+        let mut session = session("fn main() { a }", Options::default());
+
+        assert!(session.compile().is_err());
+        assert_eq!(
+            session
+                .take_diagnostics()
+                .iter()
+                .map(Diagnostic::message)
+                .collect::<Vec<_>>(),
+            ["Undefined variable `a`"]
+        );
+    }
+
+    fn messages(session: &mut Session) -> Vec<String> {
+        session
+            .take_diagnostics()
+            .iter()
+            .map(|d| format!("{}: {}", d.severity(), d.message()))
+            .collect()
+    }
+
+    #[test]
+    fn lints_have_levels() {
+        let code = "fn main() { let x = 1; let x = x; 2 }";
+
+        let mut default = session(code, Options::default());
+        default.compile().unwrap();
+        assert_eq!(messages(&mut default), ["warning: Unused variable `x`"]);
+
+        let mut options = Options::default();
+        options.lints.set(Lint::SHADOWING, LintLevel::Warn);
+        options.lints.set(Lint::UNUSED_VARIABLE, LintLevel::Allow);
+        let mut configured = session(code, options);
+        configured.compile().unwrap();
+        assert_eq!(
+            messages(&mut configured),
+            ["warning: Binding `x` shadows a previous variable"]
+        );
+    }
+
+    #[test]
+    fn denied_lints_stop_the_compilation() {
+        let mut options = Options::default();
+        options.lints.set(Lint::UNUSED_VARIABLE, LintLevel::Deny);
+        let mut session = session("fn main() { let a = 1; 2 }", options);
+
+        assert!(session.compile().is_err());
+        assert_eq!(messages(&mut session), ["error: Unused variable `a`"]);
+    }
+
+    #[test]
+    fn errors_stop_at_the_limit() {
+        let code = "fn main() { b + c + d }";
+        let options = Options {
+            error_limit: Some(2),
+            ..Options::default()
+        };
+        let mut session = session(code, options);
+
+        assert!(session.compile().is_err());
+        let diagnostics = session.take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(Diagnostic::message)
+                .collect::<Vec<_>>(),
+            ["Undefined variable `b`", "Undefined variable `c`"]
+        );
+        assert!(diagnostics
+            .to_string()
+            .contains("The compilation stopped at the error limit (2)"));
+    }
+
+    #[test]
+    fn constants_are_constant_conditions() {
+        let code = "const DEBUG = false; fn main() { if DEBUG { 1 } else { 2 } }";
+        let mut session = session(code, Options::default());
+
+        let (instructions, _) = session.compile().unwrap();
+
+        assert!(!instructions.contains(&Instruction::push_i(1)));
+        assert_eq!(
+            messages(&mut session),
+            ["warning: Condition is always false"]
+        );
+    }
+
+    #[test]
+    fn cfg_is_kept() {
+        let options = Options {
+            cfg_output: Some("cfg.dot".into()),
+            ..Options::default()
+        };
+        let mut session = session("fn main() { 1 }", options);
+
+        session.compile().unwrap();
+
+        assert!(session.cfg().unwrap().starts_with("digraph cfg {"));
+    }
+
+    #[test]
+    fn compilation_is_reproducible() {
+        let files = [
+            "mod geo { fn area(w, h) { w * h } } enum Shape { Square(side), Rect(w, h) }",
+            "import geo::area; struct P { x, y } const N = 3; fn id<T>(x: T) -> T { x } \
+             fn size(s) { \
+                 match s { Shape::Square(c) => area(c, c), Shape::Rect(w, h) => area(w, h) } \
+             } \
+             fn main() { let P { x, y } = P { x: 1, y: N }; let f = |a| a + y; \
+             print(size(Shape::Rect(x, 2)) + f(id(4))); id(true); 0 }",
+        ];
+        let compile = |opt_level| {
+            let files = files
+                .iter()
+                .enumerate()
+                .map(|(idx, code)| SourceFile::new(format!("{}.dyl", idx), code.to_string()))
+                .collect();
+            let options = Options {
+                opt_level,
+                ..Options::default()
+            };
+
+            Session::new(files, options).compile().unwrap()
+        };
+
+        // Every compilation uses new hash maps, with different seeds.
+        for opt_level in [OptLevel::O0, OptLevel::O2] {
+            let (instructions, line_table) = compile(opt_level);
+
+            for _ in 0..4 {
+                let (other_instructions, other_line_table) = compile(opt_level);
+
+                assert_eq!(
+                    Instruction::encode_multiple(&other_instructions),
+                    Instruction::encode_multiple(&instructions)
+                );
+                assert_eq!(other_line_table, line_table);
+            }
+        }
+    }
+}
@@ -0,0 +1,958 @@
+//! Static single assignment form of the basic blocks.
+//!
+//! The lowered code moves values around the stack. In SSA form, every value
+//! an instruction computes gets a name, and instructions refer to the values
+//! they consume by that name. Copying a value from a stack slot to another
+//! does not create a new value, and a phi node picks the value of a stack
+//! slot when it depends on the block control comes from.
+//!
+//! The construction follows "Simple and Efficient Construction of Static
+//! Single Assignment Form" (Braun et al., 2013), where the variables are the
+//! stack slots of the function being built.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use crate::{
+    cfg::Cfg,
+    context::FunctionContext,
+    instruction::{
+        Call, CallClosure, Instruction, LoadLocal, MakeClosure, NewArray, Pack, Pop, PopCopy,
+        PushCopy, ResV, Ret, StoreLocal,
+    },
+    ir,
+};
+
+/// Builds the SSA form of every function of a program, including the
+/// startup code and closures.
+pub(crate) fn construct(program: &ir::Program, functions: &FunctionContext) -> Vec<Function> {
+    let cfg = Cfg::new(program);
+    let mut entries = vec![0];
+
+    for instruction in program.blocks().iter().flat_map(ir::Block::instructions) {
+        let label = match instruction {
+            Instruction::Call(Call(label)) => *label,
+            Instruction::MakeClosure(MakeClosure { label, .. }) => *label,
+            _ => continue,
+        };
+
+        let entry = program
+            .block_of(label)
+            .expect("Functions are placed in the code");
+
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+
+    entries.sort_unstable();
+
+    entries
+        .into_iter()
+        .map(|entry| Builder::new(program, &cfg, functions, entry).build())
+        .collect()
+}
+
+/// Checks that every value is defined once before it is used, and that the
+/// code never reads a slot of a frame before writing to it, which the stack
+/// check cannot see as it does not follow the values.
+pub(crate) fn verify(functions: &[Function]) -> Result<(), String> {
+    functions.iter().try_for_each(Function::verify)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Value(u32);
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "v{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Function {
+    /// The blocks of the function, entry block first. A block always comes
+    /// after one of its predecessors.
+    blocks: Vec<Block>,
+}
+
+impl Function {
+    pub(crate) fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    fn verify(&self) -> Result<(), String> {
+        let mut defined = HashSet::new();
+
+        for block in self.blocks() {
+            let results = block
+                .phis()
+                .iter()
+                .map(Phi::result)
+                .chain(block.statements().iter().filter_map(Statement::result));
+
+            for result in results {
+                if !defined.insert(result) {
+                    return Err(format!(
+                        "{} is defined twice, in block {}",
+                        result,
+                        block.idx()
+                    ));
+                }
+            }
+        }
+
+        for block in self.blocks() {
+            let operands = block
+                .phis()
+                .iter()
+                .flat_map(|phi| phi.operands().iter().map(|(_, value)| value));
+            let args = block.statements().iter().flat_map(Statement::args);
+
+            if let Some(value) = operands.chain(args).find(|value| !defined.contains(value)) {
+                return Err(format!(
+                    "{} is used in block {} but never defined",
+                    value,
+                    block.idx()
+                ));
+            }
+
+            for statement in block.statements() {
+                if let Operation::Unwritten(slot) = statement.operation() {
+                    return Err(format!("Read of the unwritten local slot {}", slot));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        self.blocks.iter().try_for_each(|block| block.fmt(f))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Block {
+    /// The index of the block in the IR.
+    idx: usize,
+    phis: Vec<Phi>,
+    statements: Vec<Statement>,
+}
+
+impl Block {
+    pub(crate) fn idx(&self) -> usize {
+        self.idx
+    }
+
+    pub(crate) fn phis(&self) -> &[Phi] {
+        &self.phis
+    }
+
+    pub(crate) fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        writeln!(f, "block {}:", self.idx)?;
+
+        for phi in &self.phis {
+            writeln!(f, "    {}", phi)?;
+        }
+
+        for statement in &self.statements {
+            writeln!(f, "    {}", statement)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Phi {
+    result: Value,
+    /// The value coming from each predecessor block.
+    operands: Vec<(usize, Value)>,
+}
+
+impl Phi {
+    pub(crate) fn result(&self) -> Value {
+        self.result
+    }
+
+    pub(crate) fn operands(&self) -> &[(usize, Value)] {
+        &self.operands
+    }
+}
+
+impl Display for Phi {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let operands = self
+            .operands
+            .iter()
+            .map(|(block, value)| format!("block {}: {}", block, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "{} = phi [{}]", self.result, operands)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Statement {
+    result: Option<Value>,
+    operation: Operation,
+    args: Vec<Value>,
+}
+
+impl Statement {
+    pub(crate) fn result(&self) -> Option<Value> {
+        self.result
+    }
+
+    pub(crate) fn operation(&self) -> &Operation {
+        &self.operation
+    }
+
+    pub(crate) fn args(&self) -> &[Value] {
+        &self.args
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let Some(result) = self.result {
+            write!(f, "{} = ", result)?;
+        }
+
+        match &self.operation {
+            Operation::Incoming(offset) => write!(f, "incoming {}", offset)?,
+            Operation::Unwritten(slot) => write!(f, "unwritten {}", slot)?,
+            Operation::Instruction(instruction) => write!(f, "{}", instruction)?,
+        }
+
+        if !self.args.is_empty() {
+            let args = self
+                .args
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            write!(f, " {}", args)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Operation {
+    /// A value that is on the stack when the function is entered, such as
+    /// its arguments. The offset is counted from the top of the stack.
+    Incoming(u32),
+
+    /// A slot of the frame that is read before anything is written to it on
+    /// some path. Only broken code has one.
+    Unwritten(u32),
+
+    /// An instruction, whose operands are popped from the stack in order.
+    /// Instructions that only move values around the stack never appear.
+    Instruction(Instruction),
+}
+
+/// A stack slot, relative to the top of the stack when the function is
+/// entered. The values that were already there have a negative slot.
+type Slot = i32;
+
+struct Builder<'a> {
+    program: &'a ir::Program,
+    cfg: &'a Cfg,
+    functions: &'a FunctionContext,
+    entry: usize,
+    order: Vec<usize>,
+    preds: HashMap<usize, Vec<usize>>,
+    sealed: HashSet<usize>,
+    /// The value of each slot at the end of what has been built of each
+    /// block.
+    defs: HashMap<(usize, Slot), Value>,
+    /// The phis created before every predecessor of their block is built.
+    incomplete_phis: HashMap<usize, Vec<(Slot, Value)>>,
+    phis: HashMap<usize, Vec<Phi>>,
+    statements: HashMap<usize, Vec<Statement>>,
+    incoming: Vec<Statement>,
+    next_value: u32,
+}
+
+impl<'a> Builder<'a> {
+    fn new(
+        program: &'a ir::Program,
+        cfg: &'a Cfg,
+        functions: &'a FunctionContext,
+        entry: usize,
+    ) -> Builder<'a> {
+        let order = reverse_postorder(cfg, entry);
+
+        // Unreachable code may jump into the function, but it is not part
+        // of it.
+        let preds = order
+            .iter()
+            .map(|block| {
+                let preds = cfg
+                    .predecessors(*block)
+                    .iter()
+                    .filter(|pred| order.contains(pred))
+                    .copied()
+                    .collect();
+
+                (*block, preds)
+            })
+            .collect();
+
+        Builder {
+            program,
+            cfg,
+            functions,
+            entry,
+            order,
+            preds,
+            sealed: HashSet::new(),
+            defs: HashMap::new(),
+            incomplete_phis: HashMap::new(),
+            phis: HashMap::new(),
+            statements: HashMap::new(),
+            incoming: Vec::new(),
+            next_value: 0,
+        }
+    }
+
+    fn build(mut self) -> Function {
+        let mut depths = HashMap::from([(self.entry, 0)]);
+        let mut filled = HashSet::new();
+
+        for block in self.order.clone() {
+            if self.preds[&block].iter().all(|pred| filled.contains(pred)) {
+                self.seal(block);
+            }
+
+            let depth = self.fill(block, depths[&block]);
+            filled.insert(block);
+
+            for successor in self.cfg.successors(block) {
+                depths.entry(*successor).or_insert(depth);
+            }
+        }
+
+        // Blocks that are jumped to from a block built after them.
+        for block in self.order.clone() {
+            if !self.sealed.contains(&block) {
+                self.seal(block);
+            }
+        }
+
+        self.finish()
+    }
+
+    /// Builds the statements of a block, and returns the depth of the stack
+    /// when it ends.
+    fn fill(&mut self, block: usize, mut depth: Slot) -> Slot {
+        for instruction in self.program.blocks()[block].instructions() {
+            match instruction {
+                Instruction::PushCopy(PushCopy(offset)) => {
+                    let value = self.read(block, depth - 1 - *offset as Slot);
+                    self.write(block, depth, value);
+                    depth += 1;
+                }
+
+                Instruction::PopCopy(PopCopy(offset)) => {
+                    depth -= 1;
+                    let value = self.read(block, depth);
+                    self.write(block, depth - *offset as Slot, value);
+                }
+
+                // The slots of the function start at the base of its frame.
+                Instruction::LoadLocal(LoadLocal(idx)) => {
+                    let value = self.read(block, *idx as Slot);
+                    self.write(block, depth, value);
+                    depth += 1;
+                }
+
+                Instruction::StoreLocal(StoreLocal(idx)) => {
+                    depth -= 1;
+                    let value = self.read(block, depth);
+                    self.write(block, *idx as Slot, value);
+                }
+
+                Instruction::Pop(Pop(amount)) => depth -= *amount as Slot,
+
+                Instruction::Swap(_) => {
+                    let top = self.read(block, depth - 1);
+                    let below = self.read(block, depth - 2);
+                    self.write(block, depth - 1, below);
+                    self.write(block, depth - 2, top);
+                }
+
+                Instruction::Nop(_) => {}
+
+                Instruction::ResV(ResV(amount)) => {
+                    for _ in 0..*amount {
+                        let value = self.define(block, Instruction::push_i(0), Vec::new());
+                        self.write(block, depth, value);
+                        depth += 1;
+                    }
+                }
+
+                // The callee writes its result in a slot the caller reserved
+                // before pushing the arguments.
+                Instruction::Call(_) | Instruction::CallClosure(_) => {
+                    let popped = self.popped(instruction);
+                    let args = self.read_many(block, depth, popped);
+                    depth -= popped + 1;
+
+                    let value = self.define(block, instruction.clone(), args);
+                    self.write(block, depth, value);
+                    depth += 1;
+                }
+
+                Instruction::Goto(_) | Instruction::CheckFrame(_) => {
+                    self.perform(block, instruction, Vec::new())
+                }
+
+                // The operands are checked, and left on the stack.
+                Instruction::CheckOverflow(_) => {
+                    let args = self.read_many(block, depth, 2);
+                    self.perform(block, instruction, args);
+                }
+
+                Instruction::CondJmp(_)
+                | Instruction::JmpIfZero(_)
+                | Instruction::JmpIfNotZero(_)
+                | Instruction::FStop(_)
+                | Instruction::Trap(_)
+                | Instruction::Out(_)
+                | Instruction::PushBack(_) => {
+                    let popped = self.popped(instruction);
+                    let args = self.read_many(block, depth, popped);
+                    depth -= popped;
+
+                    self.perform(block, instruction, args);
+                }
+
+                // Returning shrinks the stack down to the slot that holds the
+                // return value.
+                Instruction::Ret(Ret { shrink, .. }) => {
+                    let value = self.read(block, depth - *shrink as Slot - 1);
+                    self.perform(block, instruction, vec![value]);
+                }
+
+                _ => {
+                    let popped = self.popped(instruction);
+                    let args = self.read_many(block, depth, popped);
+                    depth -= popped;
+
+                    let value = self.define(block, instruction.clone(), args);
+                    self.write(block, depth, value);
+                    depth += 1;
+                }
+            }
+        }
+
+        depth
+    }
+
+    /// Returns how many values an instruction consumes.
+    fn popped(&self, instruction: &Instruction) -> Slot {
+        let popped = match instruction {
+            Instruction::PushI(_)
+            | Instruction::PushF(_)
+            | Instruction::PushConst(_)
+            | Instruction::PushS(_)
+            | Instruction::InI(_)
+            | Instruction::Goto(_) => 0,
+
+            Instruction::Neg(_)
+            | Instruction::FToI(_)
+            | Instruction::IToF(_)
+            | Instruction::NotI(_)
+            | Instruction::CondJmp(_)
+            | Instruction::JmpIfZero(_)
+            | Instruction::JmpIfNotZero(_)
+            | Instruction::Field(_)
+            | Instruction::Len(_)
+            | Instruction::PopBack(_)
+            | Instruction::Out(_)
+            | Instruction::FStop(_)
+            | Instruction::Trap(_) => 1,
+
+            Instruction::AddI(_)
+            | Instruction::Mul(_)
+            | Instruction::Pow(_)
+            | Instruction::DivF(_)
+            | Instruction::MulF(_)
+            | Instruction::SubF(_)
+            | Instruction::AddF(_)
+            | Instruction::ShrI(_)
+            | Instruction::ShlI(_)
+            | Instruction::XorI(_)
+            | Instruction::OrI(_)
+            | Instruction::AndI(_)
+            | Instruction::GeI(_)
+            | Instruction::GtI(_)
+            | Instruction::LeI(_)
+            | Instruction::LtI(_)
+            | Instruction::NeqI(_)
+            | Instruction::EqI(_)
+            | Instruction::ModI(_)
+            | Instruction::DivI(_)
+            | Instruction::SubI(_)
+            | Instruction::Index(_)
+            | Instruction::CharAt(_)
+            | Instruction::PushBack(_) => 2,
+
+            Instruction::StoreIndex(_) | Instruction::Substr(_) => 3,
+
+            Instruction::Pack(Pack(len)) | Instruction::NewArray(NewArray(len)) => *len,
+            Instruction::MakeClosure(MakeClosure { captures, .. }) => *captures,
+
+            // The closure is popped along with its arguments.
+            Instruction::CallClosure(CallClosure(arity)) => arity + 1,
+
+            Instruction::Call(Call(label)) => {
+                return self
+                    .functions
+                    .resolve_label(*label)
+                    .expect("Called labels are functions")
+                    .arity() as Slot
+            }
+
+            Instruction::PushCopy(_)
+            | Instruction::PopCopy(_)
+            | Instruction::LoadLocal(_)
+            | Instruction::StoreLocal(_)
+            | Instruction::Pop(_)
+            | Instruction::Swap(_)
+            | Instruction::Nop(_)
+            | Instruction::ResV(_)
+            | Instruction::Ret(_) => unreachable!("Stack moves do not consume values"),
+
+            Instruction::CheckFrame(_) | Instruction::CheckOverflow(_) => {
+                unreachable!("Checks leave the stack untouched")
+            }
+        };
+
+        popped as Slot
+    }
+
+    /// Reads the `amount` values at the top of a stack of the given depth,
+    /// the deepest one first.
+    fn read_many(&mut self, block: usize, depth: Slot, amount: Slot) -> Vec<Value> {
+        (depth - amount..depth)
+            .map(|slot| self.read(block, slot))
+            .collect()
+    }
+
+    fn define(&mut self, block: usize, instruction: Instruction, args: Vec<Value>) -> Value {
+        let value = self.new_value();
+
+        self.statements.entry(block).or_default().push(Statement {
+            result: Some(value),
+            operation: Operation::Instruction(instruction),
+            args,
+        });
+
+        value
+    }
+
+    /// Adds a statement that does not produce a value.
+    fn perform(&mut self, block: usize, instruction: &Instruction, args: Vec<Value>) {
+        self.statements.entry(block).or_default().push(Statement {
+            result: None,
+            operation: Operation::Instruction(instruction.clone()),
+            args,
+        });
+    }
+
+    fn new_value(&mut self) -> Value {
+        let value = Value(self.next_value);
+        self.next_value += 1;
+        value
+    }
+
+    fn write(&mut self, block: usize, slot: Slot, value: Value) {
+        self.defs.insert((block, slot), value);
+    }
+
+    fn read(&mut self, block: usize, slot: Slot) -> Value {
+        if let Some(value) = self.defs.get(&(block, slot)) {
+            return *value;
+        }
+
+        let value = if !self.sealed.contains(&block) {
+            let phi = self.new_phi(block);
+            self.incomplete_phis
+                .entry(block)
+                .or_default()
+                .push((slot, phi));
+            phi
+        } else {
+            match self.preds[&block].as_slice() {
+                [] => {
+                    let operation = if slot < 0 {
+                        Operation::Incoming((-slot - 1) as u32)
+                    } else {
+                        Operation::Unwritten(slot as u32)
+                    };
+
+                    let value = self.new_value();
+                    self.incoming.push(Statement {
+                        result: Some(value),
+                        operation,
+                        args: Vec::new(),
+                    });
+                    value
+                }
+
+                [pred] => {
+                    let pred = *pred;
+                    self.read(pred, slot)
+                }
+
+                _ => {
+                    // The phi is written first, so that reading the slot in
+                    // a loop ends on it.
+                    let phi = self.new_phi(block);
+                    self.write(block, slot, phi);
+                    self.add_phi_operands(block, slot, phi);
+                    phi
+                }
+            }
+        };
+
+        self.write(block, slot, value);
+        value
+    }
+
+    fn new_phi(&mut self, block: usize) -> Value {
+        let value = self.new_value();
+        self.phis.entry(block).or_default().push(Phi {
+            result: value,
+            operands: Vec::new(),
+        });
+
+        value
+    }
+
+    fn add_phi_operands(&mut self, block: usize, slot: Slot, phi: Value) {
+        let operands = self.preds[&block]
+            .clone()
+            .into_iter()
+            .map(|pred| (pred, self.read(pred, slot)))
+            .collect();
+
+        self.phis
+            .get_mut(&block)
+            .unwrap()
+            .iter_mut()
+            .find(|p| p.result == phi)
+            .unwrap()
+            .operands = operands;
+    }
+
+    fn seal(&mut self, block: usize) {
+        for (slot, phi) in self.incomplete_phis.remove(&block).unwrap_or_default() {
+            self.add_phi_operands(block, slot, phi);
+        }
+
+        self.sealed.insert(block);
+    }
+
+    /// Removes the phis that always pick the same value, and assembles the
+    /// blocks.
+    fn finish(mut self) -> Function {
+        let mut replaced = HashMap::new();
+
+        loop {
+            let mut changed = false;
+
+            // The blocks are visited in order, rather than in the order of
+            // the map, so that the values picked are the same on every run.
+            for phi in self
+                .order
+                .iter()
+                .filter_map(|idx| self.phis.get(idx))
+                .flatten()
+            {
+                if replaced.contains_key(&phi.result) {
+                    continue;
+                }
+
+                let picked = phi
+                    .operands
+                    .iter()
+                    .map(|(_, value)| resolve(&replaced, *value))
+                    .filter(|value| *value != phi.result)
+                    .collect::<HashSet<_>>();
+
+                if picked.len() == 1 {
+                    replaced.insert(phi.result, picked.into_iter().next().unwrap());
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        let mut incoming = Some(self.incoming);
+
+        let blocks = self
+            .order
+            .iter()
+            .map(|idx| {
+                let phis = self
+                    .phis
+                    .remove(idx)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|phi| !replaced.contains_key(&phi.result))
+                    .map(|phi| Phi {
+                        result: phi.result,
+                        operands: phi
+                            .operands
+                            .into_iter()
+                            .map(|(pred, value)| (pred, resolve(&replaced, value)))
+                            .collect(),
+                    })
+                    .collect();
+
+                let statements = self.statements.remove(idx).unwrap_or_default();
+                let statements = if *idx == self.entry {
+                    incoming
+                        .take()
+                        .unwrap()
+                        .into_iter()
+                        .chain(statements)
+                        .collect()
+                } else {
+                    statements
+                };
+
+                let statements = statements
+                    .into_iter()
+                    .map(|statement| Statement {
+                        args: statement
+                            .args
+                            .into_iter()
+                            .map(|value| resolve(&replaced, value))
+                            .collect(),
+                        ..statement
+                    })
+                    .collect();
+
+                Block {
+                    idx: *idx,
+                    phis,
+                    statements,
+                }
+            })
+            .collect();
+
+        Function { blocks }
+    }
+}
+
+fn resolve(replaced: &HashMap<Value, Value>, mut value: Value) -> Value {
+    while let Some(replacement) = replaced.get(&value) {
+        value = *replacement;
+    }
+
+    value
+}
+
+/// Returns the blocks reachable from `entry`, each one after at least one
+/// of its predecessors.
+fn reverse_postorder(cfg: &Cfg, entry: usize) -> Vec<usize> {
+    fn visit(cfg: &Cfg, block: usize, seen: &mut HashSet<usize>, out: &mut Vec<usize>) {
+        if !seen.insert(block) {
+            return;
+        }
+
+        for successor in cfg.successors(block) {
+            visit(cfg, *successor, seen, out);
+        }
+
+        out.push(block);
+    }
+
+    let mut postorder = Vec::new();
+    visit(cfg, entry, &mut HashSet::new(), &mut postorder);
+
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        context::LabelContext, lowering, monomorphization, parser, resolution, type_checker,
+    };
+
+    use super::*;
+
+    fn construct_from(code: &str) -> Vec<Function> {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (ctxt, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+        let ctxt = ctxt.into_typing_context().into_lowering_context();
+        let program = type_checker::type_program(&program);
+
+        let (ctxt, program) = lowering::lower_program(&program, ctxt).unwrap();
+
+        construct(&program, ctxt.functions())
+    }
+
+    fn definitions(function: &Function) -> HashMap<Value, Option<&Operation>> {
+        function
+            .blocks()
+            .iter()
+            .flat_map(|block| {
+                let phis = block.phis().iter().map(|phi| (phi.result(), None));
+                let statements = block
+                    .statements()
+                    .iter()
+                    .filter_map(|s| s.result().map(|result| (result, Some(s.operation()))));
+
+                phis.chain(statements).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn straight_line_code() {
+        let functions = construct_from("fn main() { 1 + 2 }");
+
+        assert_eq!(
+            functions[1].to_string(),
+            "block 1:\n    \
+                 v0 = push_i 1\n    \
+                 v1 = push_i 2\n    \
+                 v2 = add_i v0, v1\n    \
+                 ret 1 0 v2\n"
+        );
+    }
+
+    #[test]
+    fn startup_code() {
+        let functions = construct_from("fn main() { 1 }");
+
+        assert_eq!(
+            functions[0].to_string(),
+            "block 0:\n    \
+                 v0 = push_i 0\n    \
+                 v1 = call L0\n    \
+                 f_stop v1\n"
+        );
+    }
+
+    #[test]
+    fn copies_are_not_values() {
+        let functions = construct_from("fn id(a) { let b = a; let c = b; c } fn main() { id(1) }");
+        let id = &functions[1];
+        let statements = id.blocks()[0].statements();
+
+        assert_eq!(statements[0].operation(), &Operation::Incoming(1), "{}", id);
+        assert_eq!(
+            statements.last().unwrap().args(),
+            [statements[0].result().unwrap()]
+        );
+    }
+
+    #[test]
+    fn branches_join_with_a_phi() {
+        let functions = construct_from("fn main() { if read_int() == 0 { 1 } else { 2 } }");
+        let main = &functions[1];
+        let definitions = definitions(main);
+
+        // The comparison is lowered with branches as well, so the value of
+        // the `if` is picked in the last block.
+        let phis = main.blocks().last().unwrap().phis();
+        assert_eq!(phis.len(), 1, "{}", main);
+
+        let picked = phis[0]
+            .operands()
+            .iter()
+            .map(|(_, value)| definitions[value].unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(picked.len(), 2, "{}", main);
+        assert!(picked.contains(&&Operation::Instruction(Instruction::push_i(1))));
+        assert!(picked.contains(&&Operation::Instruction(Instruction::push_i(2))));
+    }
+
+    #[test]
+    fn values_are_defined_once_before_use() {
+        let functions = construct_from(
+            "fn f(a, b) { let c = a * b; if c == 0 { b } else { let d = (a, c); d.1 } }
+             fn main() { let g = |x| f(x, 2) + 1; print(g(3)); match 4 { 4 => 5, _ => 6 } }",
+        );
+
+        for function in &functions {
+            let mut defined = HashSet::new();
+
+            for block in function.blocks() {
+                let results = block
+                    .phis()
+                    .iter()
+                    .map(Phi::result)
+                    .chain(block.statements().iter().filter_map(Statement::result));
+
+                for result in results {
+                    assert!(defined.insert(result), "{}", function);
+                }
+            }
+
+            let definitions = definitions(function);
+            let used = function.blocks().iter().flat_map(|block| {
+                let phis = block
+                    .phis()
+                    .iter()
+                    .flat_map(|phi| phi.operands().iter().map(|(_, v)| *v));
+                let args = block.statements().iter().flat_map(|s| s.args().to_vec());
+
+                phis.chain(args).collect::<Vec<_>>()
+            });
+
+            for value in used {
+                assert!(definitions.contains_key(&value), "{}", function);
+            }
+        }
+
+        assert_eq!(verify(&functions), Ok(()));
+    }
+
+    #[test]
+    fn unwritten_slots_are_reported() {
+        let labels = LabelContext::default();
+        let instructions = vec![
+            Instruction::push_i(1),
+            Instruction::load_local(1),
+            Instruction::add_i(),
+            Instruction::f_stop(),
+        ];
+
+        let program = ir::Program::from_instructions(instructions, &labels);
+        let functions = construct(&program, &FunctionContext::default());
+
+        assert_eq!(
+            verify(&functions),
+            Err("Read of the unwritten local slot 1".to_owned())
+        );
+    }
+}
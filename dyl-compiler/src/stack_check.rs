@@ -0,0 +1,390 @@
+//! Checks that the emitted code never reads or writes outside of the stack.
+//!
+//! The depth of the stack is computed before each reachable instruction, by
+//! following every jump and every call from the start of the program. The
+//! depth is counted from the start of the current function, whose return
+//! slot, arguments and return address are already on the stack: a call only
+//! consumes the arguments of the callee and leaves its return slot.
+//! Frame-relative instructions index the slots of the function from the first
+//! value above the return address, the arguments being below it.
+//!
+//! The instructions come from the compiler, so a failed check is a bug of the
+//! compiler, which is better reported now than by the VM.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use dyl_bytecode::{operations::*, Instruction};
+
+/// Checks the stack accesses of a program, given the address and the arity
+/// of each function.
+pub(crate) fn check_stack(
+    code: &[Instruction],
+    functions: &HashMap<u32, usize>,
+) -> Result<(), StackError> {
+    let mut depths = vec![None; code.len()];
+    let mut pending = vec![(0, 0, 0)];
+
+    // The base of the frame is the depth the function starts at.
+    while let Some((addr, depth, base)) = pending.pop() {
+        let instruction = code.get(addr as usize).ok_or(StackError::OutOfCode(addr))?;
+
+        match depths[addr as usize] {
+            Some(known) if known == depth => continue,
+            Some(known) => return Err(StackError::Mismatch(addr, known, depth)),
+            None => depths[addr as usize] = Some(depth),
+        }
+
+        let next = |depth| vec![(addr + 1, depth, base)];
+
+        let successors = match instruction {
+            Instruction::FStop(_) if depth == 1 => Vec::new(),
+            Instruction::FStop(_) => return Err(StackError::Stop(addr, depth)),
+
+            Instruction::Ret(Ret {
+                shrink_offset,
+                ip_offset,
+            }) => {
+                ensure_depth(addr, depth, (*ip_offset).max(*shrink_offset) as u32 + 1)?;
+                Vec::new()
+            }
+
+            Instruction::Trap(_) => {
+                ensure_depth(addr, depth, 1)?;
+                Vec::new()
+            }
+
+            Instruction::Goto(Goto(dest)) => vec![(*dest, depth, base)],
+
+            Instruction::JmpIfZero(JmpIfZero(dest))
+            | Instruction::JmpIfNotZero(JmpIfNotZero(dest)) => {
+                let depth = pop(addr, depth, 1)?;
+                vec![(*dest, depth, base), (addr + 1, depth, base)]
+            }
+
+            Instruction::CondJmp(CondJmp {
+                negative_addr,
+                null_addr,
+                positive_addr,
+            }) => {
+                let depth = pop(addr, depth, 1)?;
+                vec![
+                    (*negative_addr, depth, base),
+                    (*null_addr, depth, base),
+                    (*positive_addr, depth, base),
+                ]
+            }
+
+            Instruction::Call(Call(dest)) => {
+                let arity = *functions
+                    .get(dest)
+                    .ok_or(StackError::UnknownFunction(addr))?;
+
+                let entry = arity as u32 + 2;
+                let mut successors = next(pop(addr, depth, arity as u32)?);
+                successors.push((*dest, entry, entry));
+                successors
+            }
+
+            Instruction::CallClosure(CallClosure(args)) => {
+                next(pop(addr, depth, *args as u32 + 1)?)
+            }
+
+            Instruction::MakeClosure(MakeClosure {
+                addr: dest,
+                captures,
+                arity,
+            }) => {
+                let entry = *captures as u32 + *arity as u32 + 2;
+                let mut successors = next(pop(addr, depth, *captures as u32)? + 1);
+                successors.push((*dest, entry, entry));
+                successors
+            }
+
+            Instruction::PushCopy(PushCopy(offset)) => {
+                ensure_depth(addr, depth, *offset as u32 + 1)?;
+                next(depth + 1)
+            }
+
+            Instruction::PopCopy(PopCopy(offset)) => {
+                let depth = pop(addr, depth, 1)?;
+                if *offset == 0 {
+                    return Err(StackError::Underflow(addr, depth));
+                }
+                ensure_depth(addr, depth, *offset as u32)?;
+                next(depth)
+            }
+
+            Instruction::LoadLocal(LoadLocal(idx)) => {
+                ensure_slot(addr, depth, base, *idx)?;
+                next(depth + 1)
+            }
+
+            Instruction::StoreLocal(StoreLocal(idx)) => {
+                let depth = pop(addr, depth, 1)?;
+                ensure_slot(addr, depth, base, *idx)?;
+                next(depth)
+            }
+
+            Instruction::CheckFrame(CheckFrame(offset)) => {
+                ensure_depth(addr, depth, *offset as u32 + 1)?;
+                next(depth)
+            }
+
+            Instruction::CheckOverflow(_) | Instruction::Swap(_) => {
+                ensure_depth(addr, depth, 2)?;
+                next(depth)
+            }
+
+            Instruction::Nop(_) => next(depth),
+
+            Instruction::ResV(ResV(count)) => next(depth + *count as u32),
+            Instruction::Pop(Pop(count)) => next(pop(addr, depth, *count as u32)?),
+            Instruction::Pack(Pack(count)) => next(pop(addr, depth, *count as u32)? + 1),
+            Instruction::NewArray(NewArray(count)) => next(pop(addr, depth, *count as u32)? + 1),
+
+            Instruction::PushI(_)
+            | Instruction::PushF(_)
+            | Instruction::PushConst(_)
+            | Instruction::PushS(_)
+            | Instruction::InI(_) => next(depth + 1),
+
+            Instruction::Neg(_)
+            | Instruction::FToI(_)
+            | Instruction::IToF(_)
+            | Instruction::NotI(_)
+            | Instruction::Field(_)
+            | Instruction::Len(_)
+            | Instruction::PopBack(_) => next(pop(addr, depth, 1)? + 1),
+
+            Instruction::AddI(_)
+            | Instruction::Mul(_)
+            | Instruction::Pow(_)
+            | Instruction::DivF(_)
+            | Instruction::MulF(_)
+            | Instruction::SubF(_)
+            | Instruction::AddF(_)
+            | Instruction::ShrI(_)
+            | Instruction::ShlI(_)
+            | Instruction::XorI(_)
+            | Instruction::OrI(_)
+            | Instruction::AndI(_)
+            | Instruction::GeI(_)
+            | Instruction::GtI(_)
+            | Instruction::LeI(_)
+            | Instruction::LtI(_)
+            | Instruction::NeqI(_)
+            | Instruction::EqI(_)
+            | Instruction::ModI(_)
+            | Instruction::DivI(_)
+            | Instruction::SubI(_)
+            | Instruction::Index(_)
+            | Instruction::CharAt(_) => next(pop(addr, depth, 2)? + 1),
+
+            Instruction::StoreIndex(_) | Instruction::Substr(_) => next(pop(addr, depth, 3)? + 1),
+
+            Instruction::Out(_) => next(pop(addr, depth, 1)?),
+            Instruction::PushBack(_) => next(pop(addr, depth, 2)?),
+        };
+
+        pending.extend(successors);
+    }
+
+    Ok(())
+}
+
+/// Returns the depth of the stack once `count` values are removed from it.
+fn pop(addr: u32, depth: u32, count: u32) -> Result<u32, StackError> {
+    depth
+        .checked_sub(count)
+        .ok_or(StackError::Underflow(addr, depth))
+}
+
+fn ensure_depth(addr: u32, depth: u32, needed: u32) -> Result<(), StackError> {
+    if depth < needed {
+        return Err(StackError::Underflow(addr, depth));
+    }
+
+    Ok(())
+}
+
+/// Checks that a slot of the frame that starts at `base` is on the stack.
+fn ensure_slot(addr: u32, depth: u32, base: u32, idx: i16) -> Result<(), StackError> {
+    match base.checked_add_signed(idx as i32) {
+        Some(slot) if slot < depth => Ok(()),
+        _ => Err(StackError::Underflow(addr, depth)),
+    }
+}
+
+/// A stack access that is out of range, identified by the address of its
+/// instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum StackError {
+    /// The instruction accesses more values than the stack holds, given its
+    /// depth.
+    Underflow(u32, u32),
+
+    /// The instruction is reached with two different depths.
+    Mismatch(u32, u32, u32),
+
+    /// The program stops with a depth other than one.
+    Stop(u32, u32),
+
+    /// The instruction calls an address where no function starts.
+    UnknownFunction(u32),
+
+    /// The execution goes past the end of the code.
+    OutOfCode(u32),
+}
+
+impl StackError {
+    /// Returns the address of the faulty instruction.
+    pub(crate) fn addr(&self) -> u32 {
+        match self {
+            StackError::Underflow(addr, _)
+            | StackError::Mismatch(addr, _, _)
+            | StackError::Stop(addr, _)
+            | StackError::UnknownFunction(addr)
+            | StackError::OutOfCode(addr) => *addr,
+        }
+    }
+}
+
+impl Display for StackError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            StackError::Underflow(addr, depth) => write!(
+                f,
+                "Instruction {} accesses the stack out of range, with a depth of {}",
+                addr, depth
+            ),
+            StackError::Mismatch(addr, a, b) => write!(
+                f,
+                "Instruction {} is reached with a stack depth of {} and of {}",
+                addr, a, b
+            ),
+            StackError::Stop(addr, depth) => write!(
+                f,
+                "Instruction {} stops the program with a stack depth of {}",
+                addr, depth
+            ),
+            StackError::UnknownFunction(addr) => {
+                write!(f, "Instruction {} calls an unknown function", addr)
+            }
+            StackError::OutOfCode(addr) => write!(f, "Address {} is out of the code", addr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &[Instruction]) -> Result<(), StackError> {
+        check_stack(code, &HashMap::from([(4, 1)]))
+    }
+
+    #[test]
+    fn valid_program() {
+        let code = [
+            Instruction::res_v(1),
+            Instruction::push_i(1),
+            Instruction::call(4),
+            Instruction::f_stop(),
+            Instruction::push_cpy(1),
+            Instruction::push_cpy(0),
+            Instruction::add_i(),
+            Instruction::pop_cpy(3),
+            Instruction::ret(0, 2),
+        ];
+
+        assert_eq!(check(&code), Ok(()));
+    }
+
+    #[test]
+    fn copies_out_of_range() {
+        let code = [Instruction::push_i(1), Instruction::push_cpy(1)];
+
+        assert_eq!(check(&code), Err(StackError::Underflow(1, 1)));
+    }
+
+    #[test]
+    fn stores_out_of_range() {
+        let code = [
+            Instruction::push_i(1),
+            Instruction::push_i(2),
+            Instruction::pop_cpy(2),
+        ];
+
+        assert_eq!(check(&code), Err(StackError::Underflow(2, 1)));
+    }
+
+    #[test]
+    fn locals_start_above_the_return_address() {
+        let code = [
+            Instruction::res_v(1),
+            Instruction::push_i(1),
+            Instruction::call(4),
+            Instruction::f_stop(),
+            Instruction::push_i(5),
+            Instruction::load_local(0),
+            Instruction::store_local(0),
+            Instruction::load_local(1),
+        ];
+
+        assert_eq!(check(&code), Err(StackError::Underflow(7, 4)));
+    }
+
+    #[test]
+    fn arguments_are_below_the_return_address() {
+        let code = [
+            Instruction::res_v(1),
+            Instruction::push_i(1),
+            Instruction::call(4),
+            Instruction::f_stop(),
+            Instruction::load_local(-2),
+            Instruction::store_local(-3),
+            Instruction::load_local(-4),
+        ];
+
+        assert_eq!(check(&code), Err(StackError::Underflow(6, 3)));
+    }
+
+    #[test]
+    fn swaps_need_two_values() {
+        let code = [Instruction::push_i(1), Instruction::swap()];
+
+        assert_eq!(check(&code), Err(StackError::Underflow(1, 1)));
+    }
+
+    #[test]
+    fn depths_must_agree() {
+        let code = [
+            Instruction::push_i(0),
+            Instruction::cond_jmp(2, 4, 4),
+            Instruction::push_i(1),
+            Instruction::goto(4),
+            Instruction::push_i(2),
+            Instruction::f_stop(),
+        ];
+
+        assert_eq!(check(&code), Err(StackError::Mismatch(4, 0, 1)));
+    }
+
+    #[test]
+    fn calls_consume_the_arguments() {
+        let code = [
+            Instruction::res_v(1),
+            Instruction::push_i(1),
+            Instruction::push_i(2),
+            Instruction::call(3),
+        ];
+
+        assert_eq!(
+            check_stack(&code, &HashMap::new()),
+            Err(StackError::UnknownFunction(3))
+        );
+    }
+}
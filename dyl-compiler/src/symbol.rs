@@ -0,0 +1,113 @@
+//! Interned names.
+//!
+//! Each name is stored once, by the interner that every file and every
+//! thread share, and a [`Symbol`] is the index of its name there: an
+//! identifier that is written many times only allocates its name the first
+//! time, and comparing or hashing two symbols compares or hashes two
+//! integers.
+//!
+//! Syntax trees outlive the contexts that build them, so the names are kept
+//! for as long as the compiler runs. There are as many of them as there are
+//! distinct names in the code it compiles.
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+/// A name, interned. Two symbols are equal if and only if their names are.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Symbol(u32);
+
+impl Symbol {
+    /// Returns the symbol of a name, which is interned if it is the first
+    /// time it is seen.
+    pub(crate) fn intern(name: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+
+        symbol
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        let interner = interner().lock().unwrap_or_else(PoisonError::into_inner);
+
+        interner.names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Symbol {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Symbol {
+        Symbol::intern(&name)
+    }
+}
+
+// Symbols are shown as their name, so that syntax trees stay readable.
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The names of every symbol, and the symbol of every name.
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+    INTERNER.get_or_init(Mutex::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_interned_once() {
+        let a = Symbol::intern("a");
+
+        assert_eq!(Symbol::intern("a").0, a.0);
+        assert_ne!(Symbol::intern("b"), a);
+        assert_eq!(a.as_str(), "a");
+    }
+
+    #[test]
+    fn symbols_of_other_threads_are_equal() {
+        let a = Symbol::intern("a");
+
+        assert_eq!(std::thread::spawn(|| Symbol::from("a")).join().unwrap(), a);
+    }
+
+    #[test]
+    fn symbols_show_their_name() {
+        let symbol = Symbol::from("x");
+
+        assert_eq!(symbol.to_string(), "x");
+        assert_eq!(format!("{:?}", symbol), "\"x\"");
+    }
+}
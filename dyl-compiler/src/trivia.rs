@@ -0,0 +1,118 @@
+//! Comments are not part of the syntax tree. They are collected while
+//! parsing in a side table, keyed by the position they start at, so that
+//! tools rewriting the source code can put them back where they were.
+
+use std::{cell::RefCell, collections::BTreeMap};
+
+use crate::ast::Span;
+
+/// A comment, including its delimiters.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Comment {
+    span: Span,
+    text: String,
+}
+
+impl Comment {
+    pub(crate) fn new(span: Span, text: String) -> Comment {
+        Comment { span, text }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        self.text.as_str()
+    }
+}
+
+type Position = (Option<String>, u32, usize);
+
+fn position_of(span: &Span) -> Position {
+    (
+        span.file().map(|file| file.name().to_owned()),
+        span.line(),
+        span.column(),
+    )
+}
+
+/// The comments of a program, in source order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Trivia(RefCell<BTreeMap<Position, Comment>>);
+
+impl Trivia {
+    /// Adds a comment, unless it has already been added.
+    pub(crate) fn add(&self, comment: Comment) {
+        self.0
+            .borrow_mut()
+            .entry(position_of(comment.span()))
+            .or_insert(comment);
+    }
+
+    pub(crate) fn comments(&self) -> Vec<Comment> {
+        self.0.borrow().values().cloned().collect()
+    }
+
+    /// Returns the comments starting between two positions of the same file.
+    /// The start position is included, the end position is not.
+    ///
+    /// Looking between the end of a node and the start of the next one gives
+    /// the comments separating them.
+    pub(crate) fn between(&self, start: &Span, end: &Span) -> Vec<Comment> {
+        let (start, end) = (position_of(start), position_of(end));
+
+        if start >= end {
+            return Vec::new();
+        }
+
+        self.0
+            .borrow()
+            .range(start..end)
+            .map(|(_, comment)| comment.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(line: u32, column: usize, text: &str) -> Comment {
+        Comment::new(Span::new(line, column), text.to_owned())
+    }
+
+    #[test]
+    fn comments_are_sorted() {
+        let trivia = Trivia::default();
+        trivia.add(comment(2, 1, "/* b */"));
+        trivia.add(comment(1, 5, "/* a */"));
+
+        let texts = trivia.comments();
+        let texts = texts.iter().map(Comment::text).collect::<Vec<_>>();
+
+        assert_eq!(texts, ["/* a */", "/* b */"]);
+    }
+
+    #[test]
+    fn comments_are_added_once() {
+        let trivia = Trivia::default();
+        trivia.add(comment(1, 1, "/* a */"));
+        trivia.add(comment(1, 1, "/* a */"));
+
+        assert_eq!(trivia.comments().len(), 1);
+    }
+
+    #[test]
+    fn between_excludes_end() {
+        let trivia = Trivia::default();
+        trivia.add(comment(1, 1, "/* a */"));
+        trivia.add(comment(1, 9, "/* b */"));
+        trivia.add(comment(3, 1, "/* c */"));
+
+        let found = trivia.between(&Span::new(1, 1), &Span::new(3, 1));
+        let found = found.iter().map(Comment::text).collect::<Vec<_>>();
+
+        assert_eq!(found, ["/* a */", "/* b */"]);
+    }
+}
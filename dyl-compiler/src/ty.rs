@@ -7,18 +7,55 @@ use std::{
 pub(crate) enum Ty {
     Bool,
     Int,
+    String,
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>),
+    Struct(String),
+    Enum(String),
+
+    /// A function or a closure, that takes this many arguments. The types
+    /// of its parameters and of its result are not known.
+    Fn(usize),
+
+    /// The type of expressions that never produce a value, such as `return`.
+    Never,
 
     Err,
 }
 
 impl Ty {
+    /// The type of expressions that produce no meaningful value.
+    pub(crate) fn unit() -> Ty {
+        Ty::Tuple(Vec::new())
+    }
+
     pub(crate) fn unify_with(self, other: Ty) -> Result<Ty, UnificationError> {
         match (self, other) {
+            (Ty::Never, other) | (other, Ty::Never) => Ok(other),
             (Ty::Err, Ty::Err) => Ok(Ty::Err),
             (Ty::Err, other) => Ok(other),
             (this, Ty::Err) => Ok(this),
             (lhs, rhs) if lhs == rhs => Ok(lhs),
 
+            (Ty::Array(lhs), Ty::Array(rhs)) => match (*lhs).clone().unify_with(*rhs.clone()) {
+                Ok(element) => Ok(Ty::Array(Box::new(element))),
+                Err(_) => Err(UnificationError {
+                    left: Ty::Array(lhs),
+                    right: Ty::Array(rhs),
+                }),
+            },
+
+            (Ty::Tuple(lhs), Ty::Tuple(rhs)) if lhs.len() == rhs.len() => lhs
+                .iter()
+                .zip(&rhs)
+                .map(|(l, r)| l.clone().unify_with(r.clone()))
+                .collect::<Result<_, _>>()
+                .map(Ty::Tuple)
+                .map_err(|_| UnificationError {
+                    left: Ty::Tuple(lhs),
+                    right: Ty::Tuple(rhs),
+                }),
+
             (left, right) => Err(UnificationError { left, right }),
         }
     }
@@ -31,11 +68,22 @@ impl Ty {
         self.expect(&Ty::Int)
     }
 
+    /// Checks that a value of this type can be used where a value of
+    /// another type is expected. Types that are not known, such as the type
+    /// of an unannotated parameter, are compatible with any type.
     #[inline]
-    fn expect(&self, expected: &Ty) -> Result<(), UnexpectedTypeError> {
+    pub(crate) fn expect(&self, expected: &Ty) -> Result<(), UnexpectedTypeError> {
         match (self, expected) {
             (lhs, rhs) if lhs == rhs => Ok(()),
-            (Ty::Err, _) => Ok(()),
+            (Ty::Err, _) | (Ty::Never, _) | (_, Ty::Err) => Ok(()),
+            (Ty::Array(got), Ty::Array(expected)) if got.expect(expected).is_ok() => Ok(()),
+
+            (Ty::Tuple(got), Ty::Tuple(expected))
+                if got.len() == expected.len()
+                    && got.iter().zip(expected).all(|(g, e)| g.expect(e).is_ok()) =>
+            {
+                Ok(())
+            }
 
             _ => Err(UnexpectedTypeError {
                 expected: expected.clone(),
@@ -47,14 +95,40 @@ impl Ty {
 
 impl Display for Ty {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
+        let name = match self {
             Ty::Bool => "bool",
             Ty::Int => "int",
+            Ty::String => "string",
+            Ty::Never => "!",
 
             Ty::Err => "{type error}",
+
+            Ty::Tuple(elements) => return fmt_tuple(elements, f),
+            Ty::Array(element) => return write!(f, "[{}]", element),
+            Ty::Fn(arity) => return write!(f, "fn({})", vec!["_"; *arity].join(", ")),
+            Ty::Struct(name) | Ty::Enum(name) => name.as_str(),
+        };
+
+        name.fmt(f)
+    }
+}
+
+fn fmt_tuple(elements: &[Ty], f: &mut Formatter) -> fmt::Result {
+    write!(f, "(")?;
+
+    for (idx, element) in elements.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
         }
-            .fmt(f)
+
+        element.fmt(f)?;
+    }
+
+    if elements.len() == 1 {
+        write!(f, ",")?;
     }
+
+    write!(f, ")")
 }
 
 #[derive(Clone, Debug, PartialEq)]
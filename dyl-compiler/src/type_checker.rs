@@ -1,82 +1,270 @@
-use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
+//! Checks that values are used where their type is expected, once generic
+//! functions are instantiated.
+//!
+//! Type annotations are trusted, and the values they apply to are checked
+//! against them. Bindings and functions without annotation get the type of
+//! their value, so annotations are only needed on parameters. Values whose
+//! type can not be known, such as unannotated parameters, can be used
+//! anywhere.
 
 use crate::{
     ast::{
-        Addition, Binding, Bindings, Bool, ExprKind, Ident, If, Integer, Multiplication,
-        Subtraction,
+        Addition, Array, Assignment, Binding, BindingPattern, Bindings, Bool, Cast, Closure,
+        Comparison, Exponentiation, ExprKind, Field, FnCall, Function, Ident, If, Index,
+        IndexAssignment, Integer, Logical, Match, MatchArm, Multiplication, Pattern, Program,
+        Return, Span, Statements, Str, StructLiteral, Subtraction, Tuple, VariantLiteral,
     },
+    builtins::Builtin,
+    captures,
     context::{CompilerPassError, TypingContext},
+    diagnostic::{Code, Diagnostic},
+    hir,
     ty::Ty,
 };
 
-pub(crate) fn check_ast(
-    ast: &ExprKind,
+/// Checks a program, and returns its typed tree.
+pub(crate) fn check_program(
+    program: &Program,
     mut ctxt: TypingContext,
-) -> Result<TypingContext, CompilerPassError> {
-    let children_check = ast.check_inputs(&mut ctxt);
+) -> Result<(TypingContext, hir::Program), CompilerPassError> {
+    let rslt = check_items(program, &mut ctxt).map(|()| typed_program(program, &mut ctxt));
 
-    let program_ty = ast.get_output(&mut ctxt).map_err(|e| ctxt.errs().add(e));
+    ctxt.wrap_result(rslt)
+}
+
+/// Returns the typed tree of a program that is known to be well typed,
+/// such as the optimized version of a checked program.
+pub(crate) fn type_program(program: &Program) -> hir::Program {
+    let mut ctxt = TypingContext::default();
+    declare_items(program, &mut ctxt);
+
+    typed_program(program, &mut ctxt)
+}
+
+fn check_items(program: &Program, ctxt: &mut TypingContext) -> Result<(), ()> {
+    declare_items(program, ctxt);
+
+    program
+        .functions()
+        .iter()
+        .map(|f| check_function(f, ctxt))
+        .fold(Ok(()), Result::and)
+}
+
+/// Gives a type to every function and constant, so that they can be used
+/// before they are defined.
+fn declare_items(program: &Program, ctxt: &mut TypingContext) {
+    program
+        .enums()
+        .iter()
+        .for_each(|enum_| ctxt.add_enum(enum_.name().to_owned()));
+
+    program.functions().iter().for_each(|f| {
+        let params = f
+            .arg_types()
+            .iter()
+            .map(|ty| ty.as_ref().map_or(Ty::Err, |ty| ctxt.ty_of(ty)))
+            .collect();
+        let ret = f.ret_type().map_or(Ty::Err, |ty| ctxt.ty_of(ty));
+
+        ctxt.add_function(f.name().to_owned(), params, ret);
+    });
+
+    // Constants are evaluated, and checked, while lowering. Only their type
+    // matters here.
+    program.consts().iter().for_each(|const_| {
+        let ty = const_.value().get_output(ctxt).unwrap_or(Ty::Err);
+        ctxt.add_binding(const_.name().to_owned(), ty);
+    });
+
+    infer_ret_types(program, ctxt);
+}
+
+/// Infers the return type of the functions that don't have one written.
+///
+/// The body of a function may call functions whose return type is not
+/// inferred yet, so the functions are gone over until no return type
+/// changes. A function whose body never produces a value, such as a body
+/// ending with `return`, is given an unknown return type.
+fn infer_ret_types(program: &Program, ctxt: &mut TypingContext) {
+    let unannotated = program
+        .functions()
+        .iter()
+        .filter(|f| f.ret_type().is_none())
+        .collect::<Vec<_>>();
+
+    // Return types that depend on each other in a loop may never settle.
+    for _ in 0..=unannotated.len() {
+        let mut changed = false;
+
+        for f in &unannotated {
+            let ret = with_params(f, None, ctxt, |ctxt| {
+                match f.body().get_output(ctxt).unwrap_or(Ty::Err) {
+                    Ty::Never => Ty::Err,
+                    ty => ty,
+                }
+            });
+
+            changed |= ctxt.set_function_ret(f.name(), ret);
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn check_function(f: &Function, ctxt: &mut TypingContext) -> Result<(), ()> {
+    let ret = ctxt
+        .function(f.name())
+        .map(|(_, ret)| ret.clone())
+        .expect("Functions are declared before being checked");
 
-    ctxt.wrap_result(program_ty.and(children_check))
-        .map(|(ctxt, ())| ctxt)
+    with_params(f, Some(ret.clone()), ctxt, |ctxt| {
+        let body_is_valid = f.body().check_inputs(ctxt);
+        let ret_matches = expect_ty(f.body(), &ret, f.span(), ctxt);
+
+        body_is_valid.and(ret_matches)
+    })
+}
+
+/// Runs `f` with the parameters of a function in scope, and the return type
+/// `ret` expected by `return`.
+fn with_params<T>(
+    function: &Function,
+    ret: Option<Ty>,
+    ctxt: &mut TypingContext,
+    f: impl FnOnce(&mut TypingContext) -> T,
+) -> T {
+    let subctxt = ctxt.new_subcontext();
+
+    let (params, _) = ctxt
+        .function(function.name())
+        .cloned()
+        .expect("Functions are declared before being checked");
+
+    function
+        .args()
+        .iter()
+        .zip(params)
+        .for_each(|(arg, ty)| ctxt.add_binding(arg.clone(), ty));
+
+    let prev_ret = ctxt.set_ret_ty(ret);
+    let rslt = f(ctxt);
+    ctxt.set_ret_ty(prev_ret);
+
+    ctxt.drop_subcontext(subctxt);
+
+    rslt
+}
+
+/// Reports an error at a given location.
+fn report(ctxt: &TypingContext, span: &Span, err: impl Into<Diagnostic>) {
+    ctxt.errs().add(err.into().with_span(span.clone()));
+}
+
+fn mismatch(message: String) -> Diagnostic {
+    Diagnostic::error(message).with_code(Code::TypeMismatch)
+}
+
+fn not_in_scope(name: &str) -> Diagnostic {
+    Diagnostic::error(format!("Variable `{}` not in scope", name)).with_code(Code::UndefinedName)
+}
+
+/// Checks that an expression produces a value of the expected type,
+/// reporting an error at `span` otherwise.
+fn expect_ty(
+    expr: &ExprKind,
+    expected: &Ty,
+    span: &Span,
+    ctxt: &mut TypingContext,
+) -> Result<(), ()> {
+    expr.get_output(ctxt)
+        .and_then(|ty| ty.expect(expected).map_err(Diagnostic::from))
+        .map_err(|e| report(ctxt, span, e))
+}
+
+/// Checks the operands of a binary operator, which must both be of the
+/// same type.
+fn check_operands(
+    left: &ExprKind,
+    right: &ExprKind,
+    expected: &Ty,
+    span: &Span,
+    ctxt: &mut TypingContext,
+) -> Result<(), ()> {
+    let operands_are_valid = left.check_inputs(ctxt).and(right.check_inputs(ctxt));
+
+    let left_matches = expect_ty(left, expected, span, ctxt);
+    let right_matches = expect_ty(right, expected, span, ctxt);
+
+    operands_are_valid.and(left_matches).and(right_matches)
 }
 
 trait Typed {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()>;
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty>;
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic>;
 }
 
 impl Typed for ExprKind {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        match self {
-            ExprKind::Addition(addition) => addition.check_inputs(ctxt),
-            ExprKind::Integer(integer) => integer.check_inputs(ctxt),
-            ExprKind::Bindings(bindings) => bindings.check_inputs(ctxt),
-            ExprKind::Ident(ident) => ident.check_inputs(ctxt),
-            ExprKind::Multiplication(multiplication) => multiplication.check_inputs(ctxt),
-            ExprKind::Subtraction(subtraction) => subtraction.check_inputs(ctxt),
-            ExprKind::If(if_) => if_.check_inputs(ctxt),
-            ExprKind::Bool(bool_) => bool_.check_inputs(ctxt),
-        }
+        typed_node(self).check_inputs(ctxt)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        typed_node(self).get_output(ctxt)
     }
+}
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty> {
-        match self {
-            ExprKind::Addition(addition) => addition.get_output(ctxt),
-            ExprKind::Integer(integer) => integer.get_output(ctxt),
-            ExprKind::Bindings(bindings) => bindings.get_output(ctxt),
-            ExprKind::Ident(ident) => ident.get_output(ctxt),
-            ExprKind::Multiplication(multiplication) => multiplication.get_output(ctxt),
-            ExprKind::Subtraction(subtraction) => subtraction.get_output(ctxt),
-            ExprKind::If(if_) => if_.get_output(ctxt),
-            ExprKind::Bool(bool_) => bool_.get_output(ctxt),
+/// Returns the node of an expression, which checks and gives its type.
+fn typed_node(expr: &ExprKind) -> &dyn Typed {
+    match expr {
+        ExprKind::Addition(addition) => addition,
+        ExprKind::Integer(integer) => integer,
+        ExprKind::Bindings(bindings) => bindings,
+        ExprKind::Statements(statements) => statements,
+        ExprKind::Ident(ident) => ident,
+        ExprKind::Multiplication(multiplication) => multiplication,
+        ExprKind::Exponentiation(exponentiation) => exponentiation,
+        ExprKind::Comparison(comparison) => comparison,
+        ExprKind::Logical(logical) => logical,
+        ExprKind::Cast(cast) => cast,
+        ExprKind::Subtraction(subtraction) => subtraction,
+        ExprKind::If(if_) => if_,
+        ExprKind::Bool(bool_) => bool_,
+        ExprKind::String(string) => string,
+        ExprKind::FnCall(fn_call) => fn_call,
+        ExprKind::Return(return_) => return_,
+        ExprKind::Assignment(assignment) => assignment,
+        ExprKind::Tuple(tuple) => tuple,
+        ExprKind::Array(array) => array,
+        ExprKind::Index(index) => index,
+        ExprKind::IndexAssignment(assignment) => assignment,
+        ExprKind::StructLiteral(literal) => literal,
+        ExprKind::VariantLiteral(literal) => literal,
+        ExprKind::Match(match_) => match_,
+        ExprKind::Field(field) => field,
+        ExprKind::Closure(closure) => closure,
+        ExprKind::LocalFunctions(_) | ExprKind::MacroCall(_) | ExprKind::CompoundAssignment(_) => {
+            unresolved(expr)
         }
     }
 }
 
+/// Fails on an expression that can not be left after path resolution, which
+/// hoists local functions out of blocks once macros are expanded and
+/// compound assignments desugared.
+fn unresolved(expr: &ExprKind) -> ! {
+    unreachable!("Unresolved expression {:?}", expr)
+}
+
 impl Typed for Addition {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        let operands_are_valid = self
-            .left()
-            .check_inputs(ctxt)
-            .and(self.right().check_inputs(ctxt));
-
-        let left_is_int = self
-            .left()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|err| ctxt.errs().add(err));
-        let right_is_int = self
-            .right()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|err| ctxt.errs().add(err));
-
-        operands_are_valid.and(left_is_int).and(right_is_int)
+        check_operands(self.left(), self.right(), &Ty::Int, self.span(), ctxt)
     }
 
-    fn get_output(&self, _ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         Ok(Ty::Int)
     }
 }
@@ -86,7 +274,7 @@ impl Typed for Integer {
         Ok(())
     }
 
-    fn get_output(&self, _ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         Ok(Ty::Int)
     }
 }
@@ -103,11 +291,10 @@ impl Typed for Bindings {
             // Next bindings and final expression may use this binding. Let's
             // add it to the context.
             let binding_ty = binding
-                .value()
                 .get_output(ctxt)
                 .map_err(|err| ctxt.errs().add(err))
                 .unwrap_or(Ty::Err);
-            ctxt.add_binding(binding.name().to_owned(), binding_ty);
+            add_binding(binding, binding_ty, ctxt);
         });
 
         let final_is_valid = self.ending_expression().check_inputs(ctxt);
@@ -116,14 +303,14 @@ impl Typed for Bindings {
         bindings_are_valid.and(final_is_valid)
     }
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         let subctxt = ctxt.new_subcontext();
 
         self.defines().iter().for_each(|binding| {
             // Next bindings and final expression may use this binding. Let's
             // add it to the context.
-            let binding_ty = binding.value().get_output(ctxt).unwrap_or(Ty::Err);
-            ctxt.add_binding(binding.name().to_owned(), binding_ty);
+            let binding_ty = binding.get_output(ctxt).unwrap_or(Ty::Err);
+            add_binding(binding, binding_ty, ctxt);
         });
 
         let expr_ty = self.ending_expression().get_output(ctxt);
@@ -133,76 +320,207 @@ impl Typed for Bindings {
     }
 }
 
+/// Adds the names defined by a binding to the context.
+fn add_binding(binding: &Binding, ty: Ty, ctxt: &mut TypingContext) {
+    add_pattern(binding.pattern(), ty, ctxt)
+}
+
+/// Adds the names of a pattern matching a value of a given type. The
+/// elements of a tuple get their own type, the fields of a struct are not
+/// typed.
+fn add_pattern(pattern: &BindingPattern, ty: Ty, ctxt: &mut TypingContext) {
+    match (pattern, ty) {
+        (BindingPattern::Name(name), ty) => ctxt.add_binding(name.clone(), ty),
+        (BindingPattern::Wildcard, _) => {}
+
+        (BindingPattern::Tuple(patterns), Ty::Tuple(elements))
+            if patterns.len() == elements.len() =>
+        {
+            patterns
+                .iter()
+                .zip(elements)
+                .for_each(|(pattern, ty)| add_pattern(pattern, ty, ctxt))
+        }
+
+        (BindingPattern::Tuple(patterns), _) => patterns
+            .iter()
+            .for_each(|pattern| add_pattern(pattern, Ty::Err, ctxt)),
+
+        (BindingPattern::Struct { fields, .. }, _) => fields
+            .iter()
+            .for_each(|(_, pattern)| add_pattern(pattern, Ty::Err, ctxt)),
+    }
+}
+
+impl Typed for Statements {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let statements_are_valid = self
+            .statements()
+            .iter()
+            .map(|statement| statement.check_inputs(ctxt))
+            .fold(Ok(()), Result::and);
+
+        let final_is_valid = self.ending_expression().check_inputs(ctxt);
+
+        statements_are_valid.and(final_is_valid)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        // The ending expression is not reached after a statement such as
+        // `return`.
+        let diverges = self
+            .statements()
+            .iter()
+            .any(|statement| matches!(statement.get_output(ctxt), Ok(Ty::Never)));
+
+        if diverges {
+            return Ok(Ty::Never);
+        }
+
+        self.ending_expression().get_output(ctxt)
+    }
+}
+
 impl Typed for Binding {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        self.value().check_inputs(ctxt)
+        let value_is_valid = self.value().check_inputs(ctxt);
+
+        let annotation_matches = match self.annotation() {
+            Some(annotation) => {
+                let expected = ctxt.ty_of(annotation);
+                expect_ty(self.value(), &expected, self.span(), ctxt)
+            }
+            None => Ok(()),
+        };
+
+        value_is_valid.and(annotation_matches)
     }
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty> {
-        self.value().get_output(ctxt)
+    /// Returns the type of the bound value, which is the annotated type if
+    /// any.
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        match self.annotation() {
+            Some(annotation) => Ok(ctxt.ty_of(annotation)),
+            None => self.value().get_output(ctxt),
+        }
     }
 }
 
 impl Typed for Ident {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        ctxt.resolve_binding(self.name()).map(drop).ok_or(())
+        self.get_output(ctxt).map(drop).map_err(drop)
     }
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty> {
-        ctxt.resolve_binding(self.name())
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        match ctxt.resolve_binding(self.name()) {
+            Some(ty) => Ok(ty.clone()),
+
+            None => match ctxt.function(self.name()) {
+                Some((params, _)) => Ok(Ty::Fn(params.len())),
+                None => Err(not_in_scope(self.name())),
+            },
+        }
+    }
+}
+
+impl Typed for Assignment {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let value_is_valid = self.value().check_inputs(ctxt);
+
+        let variable_ty = ctxt
+            .resolve_binding(self.name())
             .cloned()
-            .ok_or_else(|| anyhow!("Variable `{}` not in scope", self.name()))
+            .ok_or_else(|| not_in_scope(self.name()));
+
+        let types_match = variable_ty
+            .and_then(|variable_ty| {
+                let value_ty = self.value().get_output(ctxt)?;
+                value_ty.expect(&variable_ty).map_err(Diagnostic::from)
+            })
+            .map_err(|e| ctxt.errs().add(e));
+
+        value_is_valid.and(types_match)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        self.value().get_output(ctxt)
     }
 }
 
 impl Typed for Multiplication {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        let operands_are_valid = self
-            .left()
-            .check_inputs(ctxt)
-            .and(self.right().check_inputs(ctxt));
+        check_operands(self.left(), self.right(), &Ty::Int, self.span(), ctxt)
+    }
 
-        let left_is_int = self
-            .left()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|e| ctxt.errs().add(e.to_string()));
-        let right_is_int = self
-            .right()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|e| ctxt.errs().add(e.to_string()));
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Int)
+    }
+}
 
-        operands_are_valid.and(left_is_int).and(right_is_int)
+impl Typed for Exponentiation {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        check_operands(self.left(), self.right(), &Ty::Int, self.span(), ctxt)
     }
 
-    fn get_output(&self, _ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         Ok(Ty::Int)
     }
 }
 
-impl Typed for Subtraction {
+impl Typed for Comparison {
     fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
-        let operands_are_valid = self
-            .left()
+        self.left()
             .check_inputs(ctxt)
-            .and(self.right().check_inputs(ctxt));
+            .and(self.right().check_inputs(ctxt))
+    }
 
-        let left_is_int = self
-            .left()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|e| ctxt.errs().add(e.to_string()));
-        let right_is_int = self
-            .right()
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Bool)
+    }
+}
+
+impl Typed for Logical {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        check_operands(self.left(), self.right(), &Ty::Bool, self.span(), ctxt)
+    }
+
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Bool)
+    }
+}
+
+impl Typed for Cast {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let value_is_valid = self.value().check_inputs(ctxt);
+
+        let cast_is_valid = self
             .get_output(ctxt)
-            .and_then(|ty| ty.expect_int().map_err(AnyError::new))
-            .map_err(|e| ctxt.errs().add(e.to_string()));
+            .map(drop)
+            .map_err(|e| ctxt.errs().add(e));
+
+        value_is_valid.and(cast_is_valid)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        let value_ty = self.value().get_output(ctxt).unwrap_or(Ty::Err);
 
-        operands_are_valid.and(left_is_int).and(right_is_int)
+        match (value_ty, self.target().is_numeric()) {
+            (Ty::Int | Ty::Bool | Ty::Err | Ty::Never, true) => Ok(Ty::Int),
+            (other, _) => Err(mismatch(format!(
+                "Cannot cast `{}` as `{}`",
+                other,
+                self.target()
+            ))),
+        }
+    }
+}
+
+impl Typed for Subtraction {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        check_operands(self.left(), self.right(), &Ty::Int, self.span(), ctxt)
     }
 
-    fn get_output(&self, _ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         Ok(Ty::Int)
     }
 }
@@ -213,32 +531,55 @@ impl Typed for If {
             .condition()
             .check_inputs(ctxt)
             .and(self.consequent().check_inputs(ctxt))
-            .and(self.alternative().check_inputs(ctxt));
+            .and(
+                self.alternative()
+                    .map_or(Ok(()), |alternative| alternative.check_inputs(ctxt)),
+            );
 
         let consequent_ty = self.consequent().get_output(ctxt).unwrap_or(Ty::Err);
-        let alternative_ty = self.alternative().get_output(ctxt).unwrap_or(Ty::Err);
-
-        let branches_unify = consequent_ty
-            .unify_with(alternative_ty)
-            .map(drop)
-            .map_err(|e| ctxt.errs().add(e.to_string()));
 
-        let condition_is_bool = self
-            .condition()
-            .get_output(ctxt)
-            .and_then(|ty| ty.expect_bool().map_err(AnyError::new))
-            .map_err(|e| ctxt.errs().add(e.to_string()));
+        let branches_unify = match self.alternative() {
+            Some(alternative) => {
+                let alternative_ty = alternative.get_output(ctxt).unwrap_or(Ty::Err);
+
+                consequent_ty
+                    .unify_with(alternative_ty)
+                    .map(drop)
+                    .map_err(|e| report(ctxt, self.span(), e))
+            }
+
+            // Without an `else` branch, there is no value to produce when the
+            // condition does not hold. The consequent must therefore be a
+            // statement, not a value.
+            None => consequent_ty.expect(&Ty::unit()).map_err(|e| {
+                report(
+                    ctxt,
+                    self.span(),
+                    mismatch(format!(
+                        "`if` may be missing an `else` clause: expected `{}`, found `{}`",
+                        e.expected, e.got
+                    )),
+                )
+            }),
+        };
+
+        let condition_is_bool = expect_ty(self.condition(), &Ty::Bool, self.span(), ctxt);
 
         children_check.and(branches_unify).and(condition_is_bool)
     }
 
-    fn get_output(&self, ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        let alternative = match self.alternative() {
+            Some(alternative) => alternative,
+            None => return Ok(Ty::unit()),
+        };
+
         let consequent_ty = self.consequent().get_output(ctxt).unwrap_or(Ty::Err);
-        let alternative_ty = self.alternative().get_output(ctxt).unwrap_or(Ty::Err);
+        let alternative_ty = alternative.get_output(ctxt).unwrap_or(Ty::Err);
 
         consequent_ty
             .unify_with(alternative_ty)
-            .map_err(AnyError::new)
+            .map_err(Diagnostic::from)
     }
 }
 
@@ -247,316 +588,1710 @@ impl Typed for Bool {
         Ok(())
     }
 
-    fn get_output(&self, _ctxt: &mut TypingContext) -> AnyResult<Ty> {
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
         Ok(Ty::Bool)
     }
 }
 
-#[cfg(test)]
-mod addition {
-    use super::*;
-
-    fn sample_addition() -> ExprKind {
-        ExprKind::addition(ExprKind::integer(41), ExprKind::integer(1))
+impl Typed for Str {
+    fn check_inputs(&self, _ctxt: &mut TypingContext) -> Result<(), ()> {
+        Ok(())
     }
 
-    fn bool_on_left() -> ExprKind {
-        ExprKind::addition(ExprKind::bool_(true), ExprKind::integer(42))
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::String)
     }
+}
 
-    #[test]
-    fn returns_integer() {
-        let mut ctxt = TypingContext::new();
-        let expr = sample_addition();
-        let output = expr.get_output(&mut ctxt);
-
-        assert_eq!(output.unwrap(), Ty::Int);
+impl Typed for Tuple {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        self.elements()
+            .iter()
+            .map(|element| element.check_inputs(ctxt))
+            .fold(Ok(()), Result::and)
     }
 
-    #[test]
-    fn expects_integers_ok() {
-        let mut ctxt = TypingContext::new();
-        let expr = sample_addition();
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        let elements = self
+            .elements()
+            .iter()
+            .map(|element| element.get_output(ctxt).unwrap_or(Ty::Err))
+            .collect();
 
-        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        Ok(Ty::Tuple(elements))
     }
+}
 
-    #[test]
-    fn expects_integers_err() {
-        let mut ctxt = TypingContext::new();
+impl Typed for Array {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let elements_are_valid = self
+            .elements()
+            .iter()
+            .map(|element| element.check_inputs(ctxt))
+            .fold(Ok(()), Result::and);
+
+        let elements_unify = self
+            .element_ty(ctxt)
+            .map(drop)
+            .map_err(|e| ctxt.errs().add(e));
 
-        assert!(bool_on_left().check_inputs(&mut ctxt).is_err());
+        elements_are_valid.and(elements_unify)
     }
 
-    #[test]
-    fn double_addition_with_labels() {
-        let expr = ExprKind::addition(
-            ExprKind::addition(
-                ExprKind::ident("a".to_owned()),
-                ExprKind::ident("b".to_owned()),
-            ),
-            ExprKind::integer(1),
-        );
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        let element = self.element_ty(ctxt).unwrap_or(Ty::Err);
 
-        let mut ctxt = TypingContext::new();
-        ctxt.add_binding("a".to_owned(), Ty::Int);
-        ctxt.add_binding("b".to_owned(), Ty::Int);
+        Ok(Ty::Array(Box::new(element)))
+    }
+}
 
-        assert!(expr.check_inputs(&mut ctxt).is_ok());
-        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Int);
+impl Array {
+    fn element_ty(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        // An empty array can be used as an array of any type, just like
+        // `return` can be used as a value of any type.
+        self.elements()
+            .iter()
+            .map(|element| element.get_output(ctxt).unwrap_or(Ty::Err))
+            .try_fold(Ty::Never, |acc, ty| {
+                acc.unify_with(ty).map_err(|e| {
+                    mismatch(format!(
+                        "Mismatched array element types: `{}` and `{}`",
+                        e.left, e.right
+                    ))
+                })
+            })
     }
 }
 
-#[cfg(test)]
-mod integer {
-    use super::*;
+impl Typed for StructLiteral {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        self.fields()
+            .iter()
+            .map(|(_, value)| value.check_inputs(ctxt))
+            .fold(Ok(()), Result::and)
+    }
 
-    fn sample_integer() -> ExprKind {
-        ExprKind::integer(42)
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Struct(self.name().to_owned()))
     }
+}
 
-    #[test]
-    fn inputs_always_successful() {
-        let mut ctxt = TypingContext::new();
-        assert!(sample_integer().check_inputs(&mut ctxt).is_ok());
+impl Typed for VariantLiteral {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        self.args()
+            .iter()
+            .map(|arg| arg.check_inputs(ctxt))
+            .fold(Ok(()), Result::and)
     }
 
-    #[test]
-    fn output_always_integer() {
-        let mut ctxt = TypingContext::new();
-        assert_eq!(sample_integer().get_output(&mut ctxt).unwrap(), Ty::Int);
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Enum(self.enum_name().to_owned()))
     }
 }
 
-#[cfg(test)]
-mod bindings {
-    use crate::ast::Binding;
+impl Typed for Match {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let scrutinee_is_valid = self.scrutinee().check_inputs(ctxt);
+        let scrutinee_ty = self.scrutinee().get_output(ctxt).unwrap_or(Ty::Err);
 
-    use super::*;
+        let children_check = self
+            .arms()
+            .iter()
+            .map(|arm| {
+                with_pattern_bindings(arm, &scrutinee_ty, ctxt, |arm, ctxt| {
+                    let guard_is_valid = arm.guard().map_or(Ok(()), |guard| {
+                        let guard_is_bool = guard
+                            .get_output(ctxt)
+                            .and_then(|ty| ty.expect_bool().map_err(Diagnostic::from))
+                            .map_err(|e| ctxt.errs().add(e));
+
+                        guard.check_inputs(ctxt).and(guard_is_bool)
+                    });
+
+                    guard_is_valid.and(arm.body().check_inputs(ctxt))
+                })
+            })
+            .fold(scrutinee_is_valid, Result::and);
+
+        let scrutinee_ty = self.scrutinee().get_output(ctxt).unwrap_or(Ty::Err);
+
+        let patterns_match = self
+            .arms()
+            .iter()
+            .map(|arm| {
+                pattern_ty(arm.pattern())
+                    .unify_with(scrutinee_ty.clone())
+                    .map(drop)
+                    .map_err(|e| {
+                        ctxt.errs().add(mismatch(format!(
+                            "Mismatched pattern type: expected `{}`, found `{}`",
+                            e.right, e.left
+                        )))
+                    })
+            })
+            .fold(Ok(()), Result::and);
+
+        let arms_unify = self
+            .get_output(ctxt)
+            .map(drop)
+            .map_err(|e| ctxt.errs().add(e));
 
-    fn sample_bindings() -> ExprKind {
-        ExprKind::Bindings(Bindings::from_vec(
-            vec![
-                Binding::new("a".to_owned(), ExprKind::integer(40)),
-                Binding::new("b".to_owned(), ExprKind::integer(1)),
-            ],
-            ExprKind::addition(
-                ExprKind::addition(
-                    ExprKind::ident("a".to_owned()),
-                    ExprKind::ident("b".to_owned()),
-                ),
-                ExprKind::integer(1),
-            ),
-        ))
+        children_check.and(patterns_match).and(arms_unify)
     }
 
-    fn bindings_with_type_errors() -> ExprKind {
-        ExprKind::single_binding(
-            "foo".to_owned(),
-            ExprKind::addition(ExprKind::bool_(true), ExprKind::integer(42)),
-            ExprKind::ident("foo".to_owned()),
-        )
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        let scrutinee_ty = self.scrutinee().get_output(ctxt).unwrap_or(Ty::Err);
+
+        self.arms().iter().try_fold(Ty::Never, |acc, arm| {
+            let arm_ty = with_pattern_bindings(arm, &scrutinee_ty, ctxt, |arm, ctxt| {
+                arm.body().get_output(ctxt)
+            })
+            .unwrap_or(Ty::Err);
+            acc.unify_with(arm_ty).map_err(Diagnostic::from)
+        })
     }
+}
 
-    fn bindings_with_unknown_ident() -> ExprKind {
-        ExprKind::Bindings(Bindings::from_vec(
-            vec![Binding::new("foo".to_owned(), ExprKind::integer(42))],
-            ExprKind::ident("bar".to_owned()),
-        ))
+/// Runs `f` on an arm, with the names its pattern binds in scope. A name
+/// bound to the whole scrutinee has its type, the fields of variants are not
+/// typed.
+fn with_pattern_bindings<T>(
+    arm: &MatchArm,
+    scrutinee_ty: &Ty,
+    ctxt: &mut TypingContext,
+    f: impl FnOnce(&MatchArm, &mut TypingContext) -> T,
+) -> T {
+    let subctxt = ctxt.new_subcontext();
+
+    match arm.pattern() {
+        Pattern::Binding(name) => ctxt.add_binding(name.clone(), scrutinee_ty.clone()),
+
+        pattern => pattern
+            .names()
+            .into_iter()
+            .for_each(|name| ctxt.add_binding(name.to_owned(), Ty::Err)),
     }
 
-    #[test]
-    fn check_input_ok() {
-        let mut ctxt = TypingContext::new();
+    let rslt = f(arm, ctxt);
+    ctxt.drop_subcontext(subctxt);
 
-        assert!(sample_bindings().check_inputs(&mut ctxt).is_ok());
+    rslt
+}
+
+fn pattern_ty(pattern: &Pattern) -> Ty {
+    match pattern {
+        // A wildcard matches values of any type.
+        Pattern::Wildcard | Pattern::Binding(_) => Ty::Never,
+        Pattern::Integer(_) => Ty::Int,
+        Pattern::Variant { enum_name, .. } => Ty::Enum(enum_name.clone()),
     }
+}
 
-    #[test]
-    fn check_inputs_type_error() {
-        let mut ctxt = TypingContext::new();
+impl Typed for Index {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let children_check = self
+            .array()
+            .check_inputs(ctxt)
+            .and(self.index().check_inputs(ctxt));
 
-        assert!(bindings_with_type_errors().check_inputs(&mut ctxt).is_err());
+        let index_is_int = self
+            .index()
+            .get_output(ctxt)
+            .and_then(|ty| ty.expect_int().map_err(Diagnostic::from))
+            .map_err(|e| ctxt.errs().add(e));
+
+        let array_is_indexable = self
+            .element_ty(ctxt)
+            .map(drop)
+            .map_err(|e| report(ctxt, self.array().span(), e));
+
+        children_check.and(index_is_int).and(array_is_indexable)
     }
 
-    #[test]
-    fn get_output_unknown_ident() {
-        let mut ctxt = TypingContext::new();
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(self.element_ty(ctxt).unwrap_or(Ty::Err))
+    }
+}
 
-        assert!(bindings_with_unknown_ident().get_output(&mut ctxt).is_err());
+impl Index {
+    fn element_ty(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        match self.array().get_output(ctxt).unwrap_or(Ty::Err) {
+            Ty::Err => Ok(Ty::Err),
+            Ty::Never => Ok(Ty::Never),
+            Ty::Array(element) => Ok(*element),
+
+            other => Err(mismatch(format!(
+                "Cannot index into a value of type `{}`",
+                other
+            ))),
+        }
     }
 }
 
-#[cfg(test)]
-mod ident {
-    use super::*;
+impl Typed for IndexAssignment {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let target_is_valid = self.target().check_inputs(ctxt);
+        let value_is_valid = self.value().check_inputs(ctxt);
 
-    fn sample_ident() -> ExprKind {
-        ExprKind::ident("foo".to_owned())
-    }
+        let element_ty = self.target().get_output(ctxt).unwrap_or(Ty::Err);
+        let types_match = self
+            .value()
+            .get_output(ctxt)
+            .and_then(|value_ty| value_ty.expect(&element_ty).map_err(Diagnostic::from))
+            .map_err(|e| ctxt.errs().add(e));
 
-    #[test]
-    fn check_input_success() {
-        let mut ctxt = TypingContext::new();
-        ctxt.add_binding("foo".to_owned(), Ty::Int);
+        target_is_valid.and(value_is_valid).and(types_match)
+    }
 
-        assert!(sample_ident().check_inputs(&mut ctxt).is_ok());
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        self.value().get_output(ctxt)
+    }
+}
+
+impl Typed for Field {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let tuple_is_valid = self.tuple().check_inputs(ctxt);
+
+        let field_exists = self
+            .field_ty(ctxt)
+            .map(drop)
+            .map_err(|e| report(ctxt, self.tuple().span(), e));
+
+        tuple_is_valid.and(field_exists)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(self.field_ty(ctxt).unwrap_or(Ty::Err))
+    }
+}
+
+impl Field {
+    fn field_ty(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        match self.tuple().get_output(ctxt).unwrap_or(Ty::Err) {
+            Ty::Err => Ok(Ty::Err),
+
+            Ty::Tuple(elements) if (self.idx() as usize) < elements.len() => {
+                Ok(elements[self.idx() as usize].clone())
+            }
+
+            other => Err(mismatch(format!(
+                "No field `{}` on type `{}`",
+                self.idx(),
+                other
+            ))),
+        }
+    }
+}
+
+impl Typed for FnCall {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let args_are_valid = self
+            .args()
+            .iter()
+            .map(|arg| arg.check_inputs(ctxt))
+            .fold(Ok(()), Result::and);
+
+        // Closures are called through variables, whose type only tells how
+        // many arguments they take.
+        let params = match ctxt.resolve_binding(self.name()).cloned() {
+            Some(ty) => return args_are_valid.and(self.check_callee(&ty, ctxt)),
+            None => ctxt.function(self.name()).map(|(params, _)| params.clone()),
+        };
+
+        let args_match = params
+            .into_iter()
+            .flatten()
+            .zip(self.args())
+            .enumerate()
+            .map(|(idx, (param, arg))| {
+                let arg_ty = arg.get_output(ctxt).unwrap_or(Ty::Err);
+
+                arg_ty.expect(&param).map_err(|e| {
+                    report(
+                        ctxt,
+                        self.span(),
+                        mismatch(format!(
+                            "Mismatched argument {} of `{}`: expected type `{}`, found type `{}`",
+                            idx + 1,
+                            self.name(),
+                            e.expected,
+                            e.got
+                        )),
+                    )
+                })
+            })
+            .fold(Ok(()), Result::and);
+
+        args_are_valid.and(args_match)
+    }
+
+    fn get_output(&self, ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        if ctxt.resolve_binding(self.name()).is_some() {
+            return Ok(Ty::Err);
+        }
+
+        if let Some((_, ret)) = ctxt.function(self.name()) {
+            return Ok(ret.clone());
+        }
+
+        match Builtin::from_name(self.name()) {
+            Some(Builtin::Panic) => Ok(Ty::Never),
+
+            Some(Builtin::Pop) => match self.args().first().map(|arg| arg.get_output(ctxt)) {
+                Some(Ok(Ty::Array(element))) => Ok(*element),
+                _ => Ok(Ty::Err),
+            },
+
+            Some(builtin) => Ok(builtin.ret_type(&[]).map_or(Ty::Err, |ty| ctxt.ty_of(&ty))),
+
+            // Calls to unknown functions are reported while lowering.
+            None => Ok(Ty::Err),
+        }
+    }
+}
+
+impl FnCall {
+    /// Checks that a variable can be called with the arguments of the call.
+    /// Variables whose type is not known, such as parameters, can be called
+    /// with anything.
+    fn check_callee(&self, callee: &Ty, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let error = match callee {
+            Ty::Fn(arity) if *arity == self.args().len() => return Ok(()),
+            Ty::Err | Ty::Never => return Ok(()),
+
+            Ty::Fn(arity) => Diagnostic::error(format!(
+                "Wrong number of arguments for `{}`: expected {}, found {}",
+                self.name(),
+                arity,
+                self.args().len()
+            ))
+            .with_code(Code::WrongArgumentCount),
+
+            other => mismatch(format!(
+                "Cannot call `{}`, a value of type `{}`",
+                self.name(),
+                other
+            )),
+        };
+
+        report(ctxt, self.span(), error);
+        Err(())
+    }
+}
+
+impl Typed for Closure {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let subcontext = ctxt.new_subcontext();
+
+        // Parameters don't carry any type information yet.
+        self.params()
+            .iter()
+            .for_each(|param| ctxt.add_binding(param.clone(), Ty::Err));
+
+        // A `return` in the body leaves the closure, whose return type is
+        // not known.
+        let prev_ret = ctxt.set_ret_ty(None);
+        let body_is_valid = self.body().check_inputs(ctxt);
+        ctxt.set_ret_ty(prev_ret);
+
+        ctxt.drop_subcontext(subcontext);
+
+        body_is_valid
+    }
+
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Fn(self.params().len()))
+    }
+}
+
+impl Typed for Return {
+    fn check_inputs(&self, ctxt: &mut TypingContext) -> Result<(), ()> {
+        let value_is_valid = self.value().check_inputs(ctxt);
+
+        let ret_matches = match ctxt.ret_ty().cloned() {
+            Some(ret) => expect_ty(self.value(), &ret, self.span(), ctxt),
+            None => Ok(()),
+        };
+
+        value_is_valid.and(ret_matches)
+    }
+
+    fn get_output(&self, _ctxt: &mut TypingContext) -> Result<Ty, Diagnostic> {
+        Ok(Ty::Never)
+    }
+}
+
+/// Builds the typed tree of a program whose items are declared.
+fn typed_program(program: &Program, ctxt: &mut TypingContext) -> hir::Program {
+    let functions = program
+        .functions()
+        .iter()
+        .map(|f| typed_function(f, ctxt))
+        .collect();
+
+    hir::Program::new(
+        functions,
+        program.structs().to_vec(),
+        program.enums().to_vec(),
+        program.consts().to_vec(),
+    )
+}
+
+fn typed_function(f: &Function, ctxt: &mut TypingContext) -> hir::Function {
+    let (_, ret) = ctxt
+        .function(f.name())
+        .cloned()
+        .expect("Functions are declared before being typed");
+    let def = ctxt
+        .function_def(f.name())
+        .expect("Functions are declared before being typed");
+
+    let params_start = ctxt.new_subcontext();
+
+    with_params(f, Some(ret.clone()), ctxt, |ctxt| {
+        let args = f
+            .args()
+            .iter()
+            .cloned()
+            .zip(ctxt.defs_since(params_start))
+            .collect();
+        let body = typed_expr(f.body(), ctxt);
+
+        hir::Function::new(f.name().to_owned(), def, args, body, ret, f.span().clone())
+    })
+}
+
+/// Builds the typed tree of an expression, without knowing anything about
+/// the names it uses.
+#[cfg(test)]
+pub(crate) fn type_expr(e: &ExprKind) -> hir::ExprKind {
+    typed_expr(e, &mut TypingContext::new())
+}
+
+#[cfg(test)]
+pub(crate) fn type_binding(binding: &Binding) -> hir::Binding {
+    typed_binding(binding, &mut TypingContext::new())
+}
+
+fn typed_exprs(exprs: &[ExprKind], ctxt: &mut TypingContext) -> Vec<hir::ExprKind> {
+    exprs.iter().map(|e| typed_expr(e, ctxt)).collect()
+}
+
+/// Builds the typed tree of an expression, with the same scopes as when it
+/// is checked.
+fn typed_expr(e: &ExprKind, ctxt: &mut TypingContext) -> hir::ExprKind {
+    let ty = e.get_output(ctxt).unwrap_or(Ty::Err);
+    let span = e.span().clone();
+
+    match e {
+        ExprKind::Addition(a) => {
+            let (lhs, rhs) = (typed_expr(a.left(), ctxt), typed_expr(a.right(), ctxt));
+            hir::ExprKind::Addition(hir::Addition::new(lhs, rhs, ty, span))
+        }
+        ExprKind::Subtraction(s) => {
+            let (lhs, rhs) = (typed_expr(s.left(), ctxt), typed_expr(s.right(), ctxt));
+            hir::ExprKind::Subtraction(hir::Subtraction::new(lhs, rhs, ty, span))
+        }
+        ExprKind::Multiplication(m) => {
+            let (lhs, rhs) = (typed_expr(m.left(), ctxt), typed_expr(m.right(), ctxt));
+            hir::ExprKind::Multiplication(hir::Multiplication::new(lhs, rhs, ty, span))
+        }
+        ExprKind::Exponentiation(e) => {
+            let (lhs, rhs) = (typed_expr(e.left(), ctxt), typed_expr(e.right(), ctxt));
+            hir::ExprKind::Exponentiation(hir::Exponentiation::new(lhs, rhs, ty, span))
+        }
+        ExprKind::Comparison(c) => {
+            let (lhs, rhs) = (typed_expr(c.left(), ctxt), typed_expr(c.right(), ctxt));
+            hir::ExprKind::Comparison(hir::Comparison::new(c.operator(), lhs, rhs, ty, span))
+        }
+        ExprKind::Logical(l) => {
+            let (lhs, rhs) = (typed_expr(l.left(), ctxt), typed_expr(l.right(), ctxt));
+            hir::ExprKind::Logical(hir::Logical::new(l.operator(), lhs, rhs, ty, span))
+        }
+
+        ExprKind::Cast(c) => {
+            let value = typed_expr(c.value(), ctxt);
+            hir::ExprKind::Cast(hir::Cast::new(value, c.target().clone(), ty, span))
+        }
+
+        ExprKind::Integer(i) => hir::ExprKind::Integer(hir::Integer::new(i.value(), ty, span)),
+        ExprKind::Bool(b) => hir::ExprKind::Bool(hir::Bool::new(b.value(), ty, span)),
+        ExprKind::String(s) => hir::ExprKind::String(hir::Str::new(s.value().to_owned(), ty, span)),
+
+        ExprKind::Ident(i) => {
+            let def = ctxt.resolve_def(i.name());
+            hir::ExprKind::Ident(hir::Ident::new(i.name().to_owned(), def, ty, span))
+        }
+
+        ExprKind::If(i) => {
+            let condition = typed_expr(i.condition(), ctxt);
+            let consequent = typed_expr(i.consequent(), ctxt);
+            let alternative = i.alternative().map(|a| typed_expr(a, ctxt));
+
+            hir::ExprKind::If(hir::If::new(condition, consequent, alternative, ty, span))
+        }
+
+        ExprKind::Bindings(b) => {
+            let subctxt = ctxt.new_subcontext();
+
+            let bindings = b
+                .defines()
+                .iter()
+                .map(|binding| typed_binding(binding, ctxt))
+                .collect();
+            let ending = typed_expr(b.ending_expression(), ctxt);
+
+            ctxt.drop_subcontext(subctxt);
+
+            hir::ExprKind::Bindings(hir::Bindings::new(bindings, ending, ty, span))
+        }
+
+        ExprKind::Statements(s) => {
+            let statements = typed_exprs(s.statements(), ctxt);
+            let ending = typed_expr(s.ending_expression(), ctxt);
+
+            hir::ExprKind::Statements(hir::Statements::new(statements, ending, ty, span))
+        }
+
+        ExprKind::FnCall(c) => {
+            let def = ctxt.resolve_def(c.name());
+            let args = typed_exprs(c.args(), ctxt);
+
+            hir::ExprKind::FnCall(hir::FnCall::new(c.name().to_owned(), def, args, ty, span))
+        }
+
+        ExprKind::Return(r) => {
+            let value = typed_expr(r.value(), ctxt);
+            hir::ExprKind::Return(hir::Return::new(value, ty, span))
+        }
+
+        ExprKind::Assignment(a) => {
+            let def = ctxt.resolve_def(a.name());
+            let value = typed_expr(a.value(), ctxt);
+
+            hir::ExprKind::Assignment(hir::Assignment::new(
+                a.name().to_owned(),
+                def,
+                value,
+                ty,
+                span,
+            ))
+        }
+
+        ExprKind::Tuple(t) => {
+            let elements = typed_exprs(t.elements(), ctxt);
+            hir::ExprKind::Tuple(hir::Tuple::new(elements, ty, span))
+        }
+
+        ExprKind::Field(f) => {
+            let tuple = typed_expr(f.tuple(), ctxt);
+            hir::ExprKind::Field(hir::Field::new(tuple, f.idx(), ty, span))
+        }
+
+        ExprKind::Array(a) => {
+            let elements = typed_exprs(a.elements(), ctxt);
+            hir::ExprKind::Array(hir::Array::new(elements, ty, span))
+        }
+
+        ExprKind::Index(i) => hir::ExprKind::Index(typed_index(i, ctxt)),
+
+        ExprKind::IndexAssignment(a) => {
+            let target = typed_index(a.target(), ctxt);
+            let value = typed_expr(a.value(), ctxt);
+
+            hir::ExprKind::IndexAssignment(hir::IndexAssignment::new(target, value, ty, span))
+        }
+
+        ExprKind::StructLiteral(s) => {
+            let fields = s
+                .fields()
+                .iter()
+                .map(|(name, value)| (name.clone(), typed_expr(value, ctxt)))
+                .collect();
+
+            hir::ExprKind::StructLiteral(hir::StructLiteral::new(
+                s.name().to_owned(),
+                fields,
+                ty,
+                span,
+            ))
+        }
+
+        ExprKind::VariantLiteral(v) => {
+            let args = typed_exprs(v.args(), ctxt);
+
+            hir::ExprKind::VariantLiteral(hir::VariantLiteral::new(
+                v.enum_name().to_owned(),
+                v.variant().to_owned(),
+                args,
+                ty,
+                span,
+            ))
+        }
+
+        ExprKind::Match(m) => {
+            let scrutinee = typed_expr(m.scrutinee(), ctxt);
+            let scrutinee_ty = scrutinee.ty().clone();
+
+            let arms = m
+                .arms()
+                .iter()
+                .map(|arm| {
+                    let pattern_start = ctxt.new_subcontext();
+
+                    with_pattern_bindings(arm, &scrutinee_ty, ctxt, |arm, ctxt| {
+                        let defs = ctxt.defs_since(pattern_start);
+                        let guard = arm.guard().map(|guard| typed_expr(guard, ctxt));
+                        let body = typed_expr(arm.body(), ctxt);
+
+                        hir::MatchArm::new(arm.pattern().clone(), defs, guard, body)
+                    })
+                })
+                .collect();
+
+            hir::ExprKind::Match(hir::Match::new(scrutinee, arms, ty, span))
+        }
+
+        ExprKind::Closure(c) => {
+            let subctxt = ctxt.new_subcontext();
+
+            c.params()
+                .iter()
+                .for_each(|param| ctxt.add_binding(param.clone(), Ty::Err));
+            let params = c
+                .params()
+                .iter()
+                .cloned()
+                .zip(ctxt.defs_since(subctxt))
+                .collect();
+
+            let prev_ret = ctxt.set_ret_ty(None);
+            let body = typed_expr(c.body(), ctxt);
+            ctxt.set_ret_ty(prev_ret);
+
+            ctxt.drop_subcontext(subctxt);
+
+            let free_variables = captures::free_variables(c);
+
+            hir::ExprKind::Closure(hir::Closure::new(params, free_variables, body, ty, span))
+        }
+
+        ExprKind::LocalFunctions(_) | ExprKind::MacroCall(_) | ExprKind::CompoundAssignment(_) => {
+            unresolved(e)
+        }
+    }
+}
+
+/// Builds the typed tree of a binding, and adds the names it defines to the
+/// context.
+fn typed_binding(binding: &Binding, ctxt: &mut TypingContext) -> hir::Binding {
+    let value = typed_expr(binding.value(), ctxt);
+    let ty = binding.get_output(ctxt).unwrap_or(Ty::Err);
+
+    let pattern_start = ctxt.new_subcontext();
+    add_binding(binding, ty.clone(), ctxt);
+
+    hir::Binding::new(
+        binding.pattern().clone(),
+        ctxt.defs_since(pattern_start),
+        value,
+        binding.is_mutable(),
+        ty,
+        binding.span().clone(),
+    )
+}
+
+fn typed_index(index: &Index, ctxt: &mut TypingContext) -> hir::Index {
+    let ty = index.get_output(ctxt).unwrap_or(Ty::Err);
+    let array = typed_expr(index.array(), ctxt);
+    let idx = typed_expr(index.index(), ctxt);
+
+    hir::Index::new(array, idx, ty, index.span().clone())
+}
+
+#[cfg(test)]
+mod addition {
+    use super::*;
+
+    fn sample_addition() -> ExprKind {
+        ExprKind::addition(ExprKind::integer(41), ExprKind::integer(1))
+    }
+
+    fn bool_on_left() -> ExprKind {
+        ExprKind::addition(ExprKind::bool_(true), ExprKind::integer(42))
+    }
+
+    #[test]
+    fn returns_integer() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_addition();
+        let output = expr.get_output(&mut ctxt);
+
+        assert_eq!(output.unwrap(), Ty::Int);
+    }
+
+    #[test]
+    fn expects_integers_ok() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_addition();
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn expects_integers_err() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(bool_on_left().check_inputs(&mut ctxt).is_err());
+    }
+
+    #[test]
+    fn double_addition_with_labels() {
+        let expr = ExprKind::addition(
+            ExprKind::addition(
+                ExprKind::ident("a".to_owned()),
+                ExprKind::ident("b".to_owned()),
+            ),
+            ExprKind::integer(1),
+        );
+
+        let mut ctxt = TypingContext::new();
+        ctxt.add_binding("a".to_owned(), Ty::Int);
+        ctxt.add_binding("b".to_owned(), Ty::Int);
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Int);
+    }
+}
+
+#[cfg(test)]
+mod integer {
+    use super::*;
+
+    fn sample_integer() -> ExprKind {
+        ExprKind::integer(42)
+    }
+
+    #[test]
+    fn inputs_always_successful() {
+        let mut ctxt = TypingContext::new();
+        assert!(sample_integer().check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn output_always_integer() {
+        let mut ctxt = TypingContext::new();
+        assert_eq!(sample_integer().get_output(&mut ctxt).unwrap(), Ty::Int);
+    }
+}
+
+#[cfg(test)]
+mod bindings {
+    use crate::ast::Binding;
+
+    use super::*;
+
+    fn sample_bindings() -> ExprKind {
+        ExprKind::Bindings(Bindings::from_vec(
+            vec![
+                Binding::new("a".to_owned(), ExprKind::integer(40)),
+                Binding::new("b".to_owned(), ExprKind::integer(1)),
+            ],
+            ExprKind::addition(
+                ExprKind::addition(
+                    ExprKind::ident("a".to_owned()),
+                    ExprKind::ident("b".to_owned()),
+                ),
+                ExprKind::integer(1),
+            ),
+        ))
+    }
+
+    fn bindings_with_type_errors() -> ExprKind {
+        ExprKind::single_binding(
+            "foo".to_owned(),
+            ExprKind::addition(ExprKind::bool_(true), ExprKind::integer(42)),
+            ExprKind::ident("foo".to_owned()),
+        )
+    }
+
+    fn bindings_with_unknown_ident() -> ExprKind {
+        ExprKind::Bindings(Bindings::from_vec(
+            vec![Binding::new("foo".to_owned(), ExprKind::integer(42))],
+            ExprKind::ident("bar".to_owned()),
+        ))
+    }
+
+    #[test]
+    fn check_input_ok() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(sample_bindings().check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn check_inputs_type_error() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(bindings_with_type_errors().check_inputs(&mut ctxt).is_err());
+    }
+
+    #[test]
+    fn get_output_unknown_ident() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(bindings_with_unknown_ident().get_output(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod statements {
+    use super::*;
+
+    #[test]
+    fn output_is_ending_expression() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::statements(vec![ExprKind::integer(1)], ExprKind::bool_(true));
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Bool);
+    }
+
+    #[test]
+    fn statements_are_checked() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::statements(
+            vec![ExprKind::addition(
+                ExprKind::bool_(true),
+                ExprKind::integer(1),
+            )],
+            ExprKind::integer(1),
+        );
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod ident {
+    use super::*;
+
+    fn sample_ident() -> ExprKind {
+        ExprKind::ident("foo".to_owned())
+    }
+
+    #[test]
+    fn check_input_success() {
+        let mut ctxt = TypingContext::new();
+        ctxt.add_binding("foo".to_owned(), Ty::Int);
+
+        assert!(sample_ident().check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn check_input_err_not_defined() {
+        let mut ctxt = TypingContext::new();
+        ctxt.add_binding("bar".to_owned(), Ty::Bool);
+
+        assert!(sample_ident().check_inputs(&mut ctxt).is_err());
+    }
+
+    #[test]
+    fn get_output_success() {
+        let mut ctxt = TypingContext::new();
+        ctxt.add_binding("foo".to_owned(), Ty::Bool);
+
+        assert_eq!(sample_ident().get_output(&mut ctxt).unwrap(), Ty::Bool);
+    }
+
+    #[test]
+    fn get_output_not_found() {
+        let mut ctxt = TypingContext::new();
+        assert!(sample_ident().get_output(&mut ctxt).is_err())
+    }
+}
+
+#[cfg(test)]
+mod multiplication {
+    use super::*;
+
+    fn sample_multiplication() -> ExprKind {
+        ExprKind::multiplication(ExprKind::integer(41), ExprKind::integer(1))
+    }
+
+    #[test]
+    fn returns_integer() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_multiplication();
+        let output = expr.get_output(&mut ctxt);
+
+        assert_eq!(output.unwrap(), Ty::Int);
+    }
+
+    #[test]
+    fn expects_integers_ok() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_multiplication();
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn expects_integers_err() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::multiplication(ExprKind::bool_(true), ExprKind::integer(42));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod subtraction {
+    use super::*;
+
+    fn sample_subtraction() -> ExprKind {
+        ExprKind::subtraction(ExprKind::integer(41), ExprKind::integer(1))
+    }
+
+    #[test]
+    fn returns_integer() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_subtraction();
+        let output = expr.get_output(&mut ctxt);
+
+        assert_eq!(output.unwrap(), Ty::Int);
+    }
+
+    #[test]
+    fn expects_integers_ok() {
+        let mut ctxt = TypingContext::new();
+        let expr = sample_subtraction();
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn expects_integers_err() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::subtraction(ExprKind::bool_(true), ExprKind::integer(42));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod if_ {
+    use super::*;
+
+    fn sample_if() -> ExprKind {
+        ExprKind::if_(
+            ExprKind::bool_(true),
+            ExprKind::integer(101),
+            ExprKind::integer(42),
+        )
+    }
+
+    fn if_different_consequent_and_alternative() -> ExprKind {
+        ExprKind::if_(
+            ExprKind::bool_(false),
+            ExprKind::integer(101),
+            ExprKind::bool_(false),
+        )
+    }
+
+    #[test]
+    fn check_inputs_working() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(sample_if().check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn get_output_ok() {
+        let mut ctxt = TypingContext::new();
+
+        assert_eq!(sample_if().get_output(&mut ctxt).unwrap(), Ty::Int);
+    }
+
+    #[test]
+    fn get_output_consequent_and_alternative_different() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(if_different_consequent_and_alternative()
+            .get_output(&mut ctxt)
+            .is_err());
+    }
+
+    #[test]
+    fn without_else_is_unit() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::if_without_else(
+            ExprKind::bool_(true),
+            ExprKind::return_(ExprKind::integer(1)),
+        );
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::unit());
+    }
+
+    #[test]
+    fn without_else_must_not_produce_a_value() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::if_without_else(ExprKind::bool_(true), ExprKind::integer(1))
+            .with_span(Span::new(1, 1));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "1:1: `if` may be missing an `else` clause: expected `()`, found `int`\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod bool_ {
+    use super::*;
+
+    fn sample_bool() -> ExprKind {
+        ExprKind::bool_(true)
+    }
+
+    #[test]
+    fn input_always_checks() {
+        let mut ctxt = TypingContext::new();
+
+        assert!(sample_bool().check_inputs(&mut ctxt).is_ok());
+    }
+
+    #[test]
+    fn always_outputs_bool() {
+        let mut ctxt = TypingContext::new();
+
+        assert_eq!(sample_bool().get_output(&mut ctxt).unwrap(), Ty::Bool);
+    }
+}
+
+#[cfg(test)]
+mod return_ {
+    use super::*;
+
+    #[test]
+    fn output_is_never() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::return_(ExprKind::integer(42));
+
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Never);
+    }
+
+    #[test]
+    fn unifies_with_other_branch() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::if_(
+            ExprKind::bool_(true),
+            ExprKind::return_(ExprKind::integer(42)),
+            ExprKind::bool_(false),
+        );
+
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Bool);
+    }
+}
+
+#[cfg(test)]
+mod assignment {
+    use crate::ast::Binding;
+
+    use super::*;
+
+    fn assign_to_int(value: ExprKind) -> ExprKind {
+        ExprKind::Bindings(Bindings::from_vec(
+            vec![Binding::new_mut("a".to_owned(), ExprKind::integer(1))],
+            ExprKind::assignment("a".to_owned(), value),
+        ))
+    }
+
+    #[test]
+    fn output_is_assigned_value() {
+        let mut ctxt = TypingContext::new();
+        let expr = assign_to_int(ExprKind::integer(2));
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Int);
+    }
+
+    #[test]
+    fn type_mismatch_fails() {
+        let mut ctxt = TypingContext::new();
+        let expr = assign_to_int(ExprKind::bool_(true));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod string {
+    use super::*;
+
+    #[test]
+    fn output_is_string() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::string("hello".to_owned());
+
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::String);
+    }
+
+    #[test]
+    fn addition_with_string_fails() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::addition(ExprKind::integer(1), ExprKind::string("1".to_owned()));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+    }
+}
+
+#[cfg(test)]
+mod tuple {
+    use super::*;
+
+    fn pair() -> ExprKind {
+        ExprKind::tuple(vec![ExprKind::integer(1), ExprKind::bool_(true)])
     }
 
     #[test]
-    fn check_input_err_not_defined() {
+    fn output_is_tuple() {
         let mut ctxt = TypingContext::new();
-        ctxt.add_binding("bar".to_owned(), Ty::Bool);
 
-        assert!(sample_ident().check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            pair().get_output(&mut ctxt).unwrap(),
+            Ty::Tuple(vec![Ty::Int, Ty::Bool])
+        );
     }
 
     #[test]
-    fn get_output_success() {
+    fn unit() {
         let mut ctxt = TypingContext::new();
-        ctxt.add_binding("foo".to_owned(), Ty::Bool);
 
-        assert_eq!(sample_ident().get_output(&mut ctxt).unwrap(), Ty::Bool);
+        assert_eq!(ExprKind::unit().get_output(&mut ctxt).unwrap(), Ty::unit());
+        assert_eq!(Ty::unit().to_string(), "()");
     }
 
     #[test]
-    fn get_output_not_found() {
+    fn field_output() {
         let mut ctxt = TypingContext::new();
-        assert!(sample_ident().get_output(&mut ctxt).is_err())
+        let expr = ExprKind::field(pair(), 1);
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Bool);
+    }
+
+    #[test]
+    fn out_of_bounds_field_fails() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::field(pair(), 2);
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "0:0: No field `2` on type `(int, bool)`\n"
+        );
+    }
+
+    #[test]
+    fn field_on_non_tuple_fails() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::field(ExprKind::integer(1), 0);
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
     }
 }
 
 #[cfg(test)]
-mod multiplication {
+mod array {
     use super::*;
 
-    fn sample_multiplication() -> ExprKind {
-        ExprKind::multiplication(ExprKind::integer(41), ExprKind::integer(1))
+    #[test]
+    fn output_is_array() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::array(vec![ExprKind::integer(1), ExprKind::integer(2)]);
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(
+            expr.get_output(&mut ctxt).unwrap(),
+            Ty::Array(Box::new(Ty::Int))
+        );
     }
 
     #[test]
-    fn returns_integer() {
+    fn empty_array_unifies_with_any_array() {
+        let empty = Ty::Array(Box::new(Ty::Never));
+        let ints = Ty::Array(Box::new(Ty::Int));
+
+        assert_eq!(empty.unify_with(ints.clone()), Ok(ints));
+    }
+
+    #[test]
+    fn mismatched_elements_fail() {
         let mut ctxt = TypingContext::new();
-        let expr = sample_multiplication();
-        let output = expr.get_output(&mut ctxt);
+        let expr = ExprKind::array(vec![ExprKind::integer(1), ExprKind::bool_(true)]);
 
-        assert_eq!(output.unwrap(), Ty::Int);
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "Mismatched array element types: `int` and `bool`\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod index {
+    use super::*;
+
+    fn array() -> ExprKind {
+        ExprKind::array(vec![ExprKind::integer(1), ExprKind::integer(2)])
     }
 
     #[test]
-    fn expects_integers_ok() {
+    fn output_is_element_type() {
         let mut ctxt = TypingContext::new();
-        let expr = sample_multiplication();
+        let expr = ExprKind::index(array(), ExprKind::integer(0));
 
         assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Int);
     }
 
     #[test]
-    fn expects_integers_err() {
+    fn index_must_be_int() {
         let mut ctxt = TypingContext::new();
-        let expr = ExprKind::multiplication(ExprKind::bool_(true), ExprKind::integer(42));
+        let expr = ExprKind::index(array(), ExprKind::bool_(true));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "Expected type `int`, found type `bool`\n"
+        );
+    }
+
+    #[test]
+    fn indexing_non_array_fails() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::index(ExprKind::integer(1), ExprKind::integer(0));
 
         assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "0:0: Cannot index into a value of type `int`\n"
+        );
+    }
+
+    #[test]
+    fn assigned_value_must_match_element_type() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::index_assignment(array(), ExprKind::integer(0), ExprKind::bool_(true));
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "Expected type `int`, found type `bool`\n"
+        );
     }
 }
 
 #[cfg(test)]
-mod subtraction {
+mod struct_literal {
     use super::*;
 
-    fn sample_subtraction() -> ExprKind {
-        ExprKind::subtraction(ExprKind::integer(41), ExprKind::integer(1))
+    #[test]
+    fn output_is_struct() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::struct_literal(
+            "Point".to_owned(),
+            vec![("x".to_owned(), ExprKind::integer(1))],
+        );
+
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(
+            expr.get_output(&mut ctxt).unwrap(),
+            Ty::Struct("Point".to_owned())
+        );
     }
+}
+
+#[cfg(test)]
+mod variant_literal {
+    use super::*;
 
     #[test]
-    fn returns_integer() {
+    fn output_is_enum() {
         let mut ctxt = TypingContext::new();
-        let expr = sample_subtraction();
-        let output = expr.get_output(&mut ctxt);
+        let expr = ExprKind::variant_literal("Color".to_owned(), "Red".to_owned(), Vec::new());
 
-        assert_eq!(output.unwrap(), Ty::Int);
+        assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(
+            expr.get_output(&mut ctxt).unwrap(),
+            Ty::Enum("Color".to_owned())
+        );
     }
+}
+
+#[cfg(test)]
+mod match_ {
+    use crate::ast::MatchArm;
+
+    use super::*;
 
     #[test]
-    fn expects_integers_ok() {
+    fn output_is_arms_type() {
         let mut ctxt = TypingContext::new();
-        let expr = sample_subtraction();
+        let expr = ExprKind::match_(
+            ExprKind::integer(1),
+            vec![
+                MatchArm::new(Pattern::Integer(1), ExprKind::bool_(true)),
+                MatchArm::new(Pattern::Wildcard, ExprKind::return_(ExprKind::integer(0))),
+            ],
+        );
 
         assert!(expr.check_inputs(&mut ctxt).is_ok());
+        assert_eq!(expr.get_output(&mut ctxt).unwrap(), Ty::Bool);
     }
 
     #[test]
-    fn expects_integers_err() {
+    fn arms_must_unify() {
         let mut ctxt = TypingContext::new();
-        let expr = ExprKind::subtraction(ExprKind::bool_(true), ExprKind::integer(42));
+        let expr = ExprKind::match_(
+            ExprKind::integer(1),
+            vec![
+                MatchArm::new(Pattern::Integer(1), ExprKind::bool_(true)),
+                MatchArm::new(Pattern::Wildcard, ExprKind::integer(0)),
+            ],
+        );
+
+        assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "Expression returns two different types: `bool` and `int`\n"
+        );
+    }
+
+    #[test]
+    fn pattern_must_match_scrutinee() {
+        let mut ctxt = TypingContext::new();
+        let expr = ExprKind::match_(
+            ExprKind::bool_(true),
+            vec![MatchArm::new(Pattern::Integer(1), ExprKind::integer(0))],
+        );
 
         assert!(expr.check_inputs(&mut ctxt).is_err());
+        assert_eq!(
+            ctxt.errs().to_string(),
+            "Mismatched pattern type: expected `bool`, found `int`\n"
+        );
     }
 }
 
 #[cfg(test)]
-mod if_ {
+mod program {
+    use crate::{monomorphization, parser, resolution};
+
     use super::*;
 
-    fn sample_if() -> ExprKind {
-        ExprKind::if_(
-            ExprKind::bool_(true),
-            ExprKind::integer(101),
-            ExprKind::integer(42),
-        )
+    fn check(code: &str) -> (Result<(), ()>, String) {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (_, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+
+        let mut ctxt = TypingContext::new();
+        let rslt = check_items(&program, &mut ctxt);
+
+        (rslt, ctxt.errs().to_string())
     }
 
-    fn if_different_consequent_and_alternative() -> ExprKind {
-        ExprKind::if_(
-            ExprKind::bool_(false),
-            ExprKind::integer(101),
-            ExprKind::bool_(false),
-        )
+    #[test]
+    fn well_typed_program() {
+        let (rslt, errs) = check(
+            "fn double(a: int) -> int { a * 2 } \
+             fn main() { let b: bool = double(2) > 3; if b { println(1) } }",
+        );
+
+        assert!(rslt.is_ok(), "{}", errs);
     }
 
     #[test]
-    fn check_inputs_working() {
-        let mut ctxt = TypingContext::new();
+    fn arithmetic_operands() {
+        let (rslt, errs) = check("fn main() { 1 + true }");
 
-        assert!(sample_if().check_inputs(&mut ctxt).is_ok());
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:15: Expected type `int`, found type `bool`\n");
     }
 
     #[test]
-    fn get_output_ok() {
-        let mut ctxt = TypingContext::new();
+    fn if_condition() {
+        let (rslt, errs) = check("fn main() { if 1 { 2 } else { 3 } }");
 
-        assert_eq!(sample_if().get_output(&mut ctxt).unwrap(), Ty::Int);
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:13: Expected type `bool`, found type `int`\n");
     }
 
     #[test]
-    fn get_output_consequent_and_alternative_different() {
+    fn binding_annotation() {
+        let (rslt, errs) = check("fn main() { let a: bool = 1; a }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:17: Expected type `bool`, found type `int`\n");
+    }
+
+    #[test]
+    fn indexing_non_array_is_reported_once() {
+        let (ctxt, program) = parser::parse_input("fn main() { let a = 1; a[0] }").unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (_, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+
         let mut ctxt = TypingContext::new();
+        assert!(check_items(&program, &mut ctxt).is_err());
+
+        let diagnostics = ctxt.errs().diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), Some(Code::TypeMismatch));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "1:24: Cannot index into a value of type `int`"
+        );
+    }
 
-        assert!(if_different_consequent_and_alternative()
-            .get_output(&mut ctxt)
-            .is_err());
+    #[test]
+    fn missing_field_is_reported_once() {
+        let (rslt, errs) = check("fn main() { let a = (1, 2); a.2 }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:29: No field `2` on type `(int, int)`\n");
+    }
+
+    #[test]
+    fn annotated_binding_has_annotated_type() {
+        let (rslt, errs) = check("fn main() { let a: (int, bool) = (1, true); a.1 + 1 }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:49: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn function_return_type() {
+        let (rslt, errs) = check("fn f() -> int { true } fn main() { f() }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:4: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn returned_value() {
+        let (rslt, errs) = check("fn f() -> int { return true; } fn main() { f() }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:17: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn block_ending_with_return_has_any_type() {
+        let (rslt, errs) = check("fn f() -> int { return 1; } fn main() { f() }");
+
+        assert!(rslt.is_ok(), "{}", errs);
+    }
+
+    #[test]
+    fn return_in_closure_is_not_checked_against_function() {
+        let (rslt, errs) =
+            check("fn f() -> int { let c = |x| { return true; }; 1 } fn main() { f() }");
+
+        assert!(rslt.is_ok(), "{}", errs);
+    }
+
+    #[test]
+    fn call_arguments() {
+        let (rslt, errs) = check("fn f(a: int, b: bool) { a } fn main() { f(true, true) }");
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            errs,
+            "1:41: Mismatched argument 1 of `f`: expected type `int`, found type `bool`\n"
+        );
+    }
+
+    #[test]
+    fn calls_of_non_functions() {
+        let (rslt, errs) = check("fn main() { let f = 3; f(1) }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:24: Cannot call `f`, a value of type `int`\n");
+    }
+
+    #[test]
+    fn closure_arity() {
+        let (rslt, errs) = check("fn main() { let f = |x| x; f(1, 2) }");
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            errs,
+            "1:28: Wrong number of arguments for `f`: expected 1, found 2\n"
+        );
+    }
+
+    #[test]
+    fn function_value_arity() {
+        let (rslt, errs) = check("fn g(a, b) { a } fn main() { let f = g; f(1) + f(1, 2) }");
+
+        assert!(rslt.is_err());
+        assert_eq!(
+            errs,
+            "1:41: Wrong number of arguments for `f`: expected 2, found 1\n"
+        );
+    }
+
+    #[test]
+    fn closures_in_parameters_are_not_checked() {
+        let (rslt, errs) = check("fn apply(f, x) { f(x) } fn main() { apply(|x| x, 1) }");
+
+        assert!(rslt.is_ok(), "{}", errs);
+    }
+
+    #[test]
+    fn call_returns_annotated_type() {
+        let (rslt, errs) = check("fn f() -> bool { true } fn main() { f() + 1 }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:41: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn unannotated_params_are_not_checked() {
+        let (rslt, errs) = check("fn f(a) { a + 1 } fn main() { f(true) }");
+
+        assert!(rslt.is_ok(), "{}", errs);
+    }
+
+    #[test]
+    fn instances_of_generic_functions() {
+        let (rslt, errs) = check("fn id<T>(x: T) -> T { x } fn main() { id(1) + id(true) }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:45: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn inferred_return_type() {
+        let (rslt, errs) = check("fn f() { true } fn main() { f() + 1 }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:33: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn return_type_inferred_before_declaration() {
+        let (rslt, errs) = check("fn main() { f() + 1 } fn f() { g() } fn g() { (1, true).1 }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:17: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn recursive_return_type() {
+        let (rslt, errs) = check(
+            "fn fact(n: int) { if n == 0 { 1 } else { n * fact(n - 1) } } \
+             fn main() { let b: bool = fact(3); b }",
+        );
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:78: Expected type `bool`, found type `int`\n");
+    }
+
+    #[test]
+    fn diverging_body_has_unknown_return_type() {
+        let (rslt, errs) = check("fn f() { return 1; } fn main() { f() + 1 }");
+
+        assert!(rslt.is_ok(), "{}", errs);
+    }
+
+    #[test]
+    fn destructured_tuple() {
+        let (rslt, errs) = check("fn main() { let (a, (b, _)) = (1, (true, 2)); a + b }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:49: Expected type `int`, found type `bool`\n");
+    }
+
+    #[test]
+    fn match_binding_has_scrutinee_type() {
+        let (rslt, errs) = check("fn main() { match true { x => x + 1 } }");
+
+        assert!(rslt.is_err());
+        assert_eq!(errs, "1:33: Expected type `int`, found type `bool`\n");
     }
 }
 
 #[cfg(test)]
-mod bool_ {
+mod typed_tree {
+    use crate::{monomorphization, parser, resolution};
+
     use super::*;
 
-    fn sample_bool() -> ExprKind {
-        ExprKind::bool_(true)
+    fn typed(code: &str) -> hir::Program {
+        let (ctxt, program) = parser::parse_input(code).unwrap();
+        let (ctxt, program) = resolution::resolve_paths(&program, ctxt).unwrap();
+        let (_, program) = monomorphization::monomorphize(&program, ctxt).unwrap();
+
+        let (_, typed) = check_program(&program, TypingContext::new()).unwrap();
+        typed
     }
 
     #[test]
-    fn input_always_checks() {
-        let mut ctxt = TypingContext::new();
+    fn expressions_have_their_type() {
+        let program = typed("fn main() { let a = (1, true); a.1 }");
 
-        assert!(sample_bool().check_inputs(&mut ctxt).is_ok());
+        assert_eq!(program.functions()[0].body().ty(), &Ty::Bool, "{}", program);
+        assert!(program.to_string().contains("Tuple: (int, bool)"));
+    }
+
+    /// Returns the definition each identifier of a function reads, in order.
+    fn reads(f: &hir::Function) -> Vec<Option<hir::DefId>> {
+        let mut reads = Vec::new();
+        let mut visit = vec![f.body()];
+
+        while let Some(e) = visit.pop() {
+            match e {
+                hir::ExprKind::Ident(i) => reads.push(i.def()),
+                hir::ExprKind::Addition(a) => visit.extend([a.right(), a.left()]),
+                hir::ExprKind::Bindings(b) => {
+                    visit.push(b.ending_expression());
+                    visit.extend(b.defines().iter().rev().map(hir::Binding::value));
+                }
+                hir::ExprKind::FnCall(c) => visit.extend(c.args().iter().rev()),
+                _ => {}
+            }
+        }
+
+        reads
     }
 
     #[test]
-    fn always_outputs_bool() {
-        let mut ctxt = TypingContext::new();
+    fn shadowed_names_are_distinct_definitions() {
+        let program = typed("fn f(a) { let a = a + 1; a } fn main() { f(1) }");
 
-        assert_eq!(sample_bool().get_output(&mut ctxt).unwrap(), Ty::Bool);
+        let reads = reads(&program.functions()[0]);
+
+        assert_eq!(reads.len(), 2);
+        assert!(reads.iter().all(Option::is_some));
+        assert_ne!(reads[0], reads[1], "{}", program);
+    }
+
+    #[test]
+    fn calls_refer_to_the_function() {
+        let program = typed("fn f() { 1 } fn main() { let g = 2; f() }");
+        let text = program.to_string();
+
+        let def = text
+            .strip_prefix("fn f")
+            .unwrap()
+            .split('(')
+            .next()
+            .unwrap();
+
+        assert!(text.contains(&format!("FnCall f{}: int", def)), "{}", text);
     }
 }
@@ -1,9 +1,214 @@
-use anyhow::Result;
+use std::{
+    env,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use dyl_compiler::{Artifact, Diagnostics, LintLevel, LintLevels, OptLevel, Options, Severity};
+
+/// The file compiled when no source is given on the command line.
+const DEFAULT_SOURCE: &str = "main.dyl";
 
 fn main() -> Result<()> {
-    let bytecode = dyl_compiler::bytecode_from_program("main.dyl")?;
+    let args = parse_args()?;
+    let options = &args.options;
+
+    if !args.emit.is_empty() {
+        let artifacts = dyl_compiler::artifacts_from_programs(&args.sources, options, &args.emit);
+        let artifacts = report(artifacts, args.message_format)?;
+        let several = artifacts.len() > 1;
+
+        for (artifact, text) in artifacts {
+            if several {
+                println!("== {} ==", artifact);
+            }
+            print!("{}", text);
+        }
+
+        return Ok(());
+    }
+
+    let compiled = dyl_compiler::compile_programs(&args.sources, options);
+    let compiled = report(compiled, args.message_format)?;
+
+    if let Some((before, after)) = compiled.sizes.filter(|_| args.verbose) {
+        eprintln!(
+            "Optimizations: {} instructions before, {} after",
+            before, after
+        );
+    }
 
-    dyl_vm::run_program(bytecode)?;
+    dyl_vm::run_program_with_constants(
+        compiled.instructions,
+        compiled.line_table,
+        compiled.constants,
+    )?;
 
     Ok(())
 }
+
+/// What the command line asks for.
+struct Args {
+    sources: Vec<String>,
+    options: Options,
+    emit: Vec<Artifact>,
+    message_format: MessageFormat,
+
+    /// Prints statistics about the compilation to the standard error.
+    verbose: bool,
+}
+
+/// How the diagnostics of a compilation are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MessageFormat {
+    /// Each diagnostic is rendered for a person to read, on the standard
+    /// error.
+    Human,
+
+    /// Each diagnostic is a line of JSON on the standard output, for the
+    /// tools that read them.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<MessageFormat> {
+        match format {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(anyhow!("Unknown message format `{}`", format)),
+        }
+    }
+}
+
+/// Prints the diagnostics of a compilation, and fails if the compilation
+/// did.
+fn report<T>(
+    compilation: Result<(T, Diagnostics), Diagnostics>,
+    format: MessageFormat,
+) -> Result<T> {
+    let (compiled, diagnostics) = match compilation {
+        Ok((compiled, warnings)) => (Some(compiled), warnings),
+        Err(diagnostics) => (None, diagnostics),
+    };
+
+    match format {
+        MessageFormat::Human => {
+            let colored = use_colors();
+
+            diagnostics
+                .iter()
+                .for_each(|diagnostic| eprintln!("{}", diagnostic.render(colored)));
+        }
+        MessageFormat::Json => diagnostics
+            .iter()
+            .for_each(|diagnostic| println!("{}", diagnostic.to_json())),
+    }
+
+    compiled.ok_or_else(|| {
+        let count = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .count();
+        let error_word = if count == 1 { "error" } else { "errors" };
+
+        anyhow!("Compilation failed with {} {}", count, error_word)
+    })
+}
+
+/// Tells whether diagnostics printed to the standard error should be
+/// colored: it must be a terminal, and `NO_COLOR` must not be set.
+fn use_colors() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// Reads the source paths and the compiler options from the command line.
+///
+/// `-A <lint>`, `-W <lint>` and `-D <lint>` drop the warnings of a lint,
+/// report them, or report them as errors, `warnings` standing for every lint
+/// that warns by default, `-O0`, `-O1` and `-O2` set how
+/// much the code is optimized, `-O` being `-O1`, `-v` prints statistics
+/// about the compilation and `--cfg <file>` writes the control-flow graph of
+/// the code to a Graphviz file. `--debug-checks` makes the program check,
+/// while it runs, its stack accesses and its arithmetic.
+/// `--message-format=json` prints each diagnostic as a line of JSON on the
+/// standard output. `--error-limit=<n>` stops the compilation after `n`
+/// errors, 20 by default, `0` reporting them all. `--emit=<outputs>`
+/// prints what the compiler produces instead of running the program: the
+/// tokens (`tokens`), the syntax tree (`ast`, or `ast-json`), the typed tree
+/// (`hir`), the lowered program (`ir`), the
+/// instructions (`bytecode`) or the assembly (`asm`), several outputs being
+/// separated by commas. Any other argument is a source file, or a directory
+/// containing source files.
+fn parse_args() -> Result<Args> {
+    let mut sources = Vec::new();
+    let mut options = Options::default();
+    let mut emit = Vec::new();
+    let mut message_format = MessageFormat::Human;
+    let mut verbose = false;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-A" | "-W" | "-D" => {
+                let level = match arg.as_str() {
+                    "-A" => LintLevel::Allow,
+                    "-W" => LintLevel::Warn,
+                    _ => LintLevel::Deny,
+                };
+
+                match args.next() {
+                    Some(name) if name == LintLevels::WARNINGS => options.lints.set_warnings(level),
+                    Some(name) => {
+                        let lint = options.lints.lint(&name)?;
+                        options.lints.set(lint, level)
+                    }
+                    None => bail!("Expected a lint name after `{}`", arg),
+                }
+            }
+            "-O" => options.opt_level = OptLevel::O1,
+            _ if arg.starts_with("-O") => options.opt_level = arg[2..].parse()?,
+            "-v" => verbose = true,
+            "--debug-checks" => options.debug_checks = true,
+            "--cfg" => match args.next() {
+                Some(path) => options.cfg_output = Some(path.into()),
+                None => bail!("Expected a file name after `--cfg`"),
+            },
+            _ if arg.starts_with("--message-format=") => {
+                message_format = arg["--message-format=".len()..].parse()?
+            }
+            _ if arg.starts_with("--error-limit=") => {
+                options.error_limit = match arg["--error-limit=".len()..].parse() {
+                    Ok(0) => None,
+                    Ok(limit) => Some(limit),
+                    Err(_) => bail!("Expected a number of errors in `{}`", arg),
+                }
+            }
+            _ if arg.starts_with("--emit=") => {
+                for name in arg["--emit=".len()..].split(',') {
+                    let artifact = name.parse()?;
+                    if !emit.contains(&artifact) {
+                        emit.push(artifact);
+                    }
+                }
+            }
+            _ if arg.starts_with('-') => bail!("Unknown argument `{}`", arg),
+            _ => sources.push(arg),
+        }
+    }
+
+    if sources.is_empty() {
+        sources.push(DEFAULT_SOURCE.to_owned());
+    }
+
+    Ok(Args {
+        sources,
+        options,
+        emit,
+        message_format,
+        verbose,
+    })
+}
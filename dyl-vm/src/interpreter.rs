@@ -1,46 +1,136 @@
+use std::io::{self, BufRead, Write};
+
 use anyhow::{anyhow, bail, ensure, Context, Result};
 
-use dyl_bytecode::Instruction;
+use dyl_bytecode::{
+    constant_pool::{Constant, ConstantPool},
+    line_table::LineTable,
+    Instruction,
+};
 
 use crate::runnable::Runnable;
 use crate::{runnable::RunStatus, value::Value};
 
 pub(crate) struct Interpreter {
     code: Vec<Instruction>,
+
+    /// Tells where the code comes from, so that errors can point at it.
+    line_table: LineTable,
+
+    /// The values that `push_const` pushes.
+    constants: ConstantPool,
 }
 
 impl Interpreter {
     pub(crate) fn from_instructions(code: Vec<Instruction>) -> Interpreter {
-        Interpreter { code }
+        Interpreter {
+            code,
+            line_table: LineTable::default(),
+            constants: ConstantPool::default(),
+        }
+    }
+
+    pub(crate) fn with_line_table(self, line_table: LineTable) -> Interpreter {
+        Interpreter { line_table, ..self }
+    }
+
+    pub(crate) fn with_constants(self, constants: ConstantPool) -> Interpreter {
+        Interpreter { constants, ..self }
     }
 
     pub(crate) fn run(&mut self) -> Result<Value> {
+        self.run_with_io(&mut io::stdin().lock(), &mut io::stdout())
+    }
+
+    /// Runs the program, reading its input from `input` and writing its
+    /// output to `output` instead of the standard streams.
+    pub(crate) fn run_with_io(
+        &mut self,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<Value> {
         let mut state = RunningInterpreterState::new();
 
         let final_value = loop {
             match self.run_single(state)? {
                 RunStatus::Continue(new_state) => state = new_state,
                 RunStatus::Stop(val) => break val,
+
+                RunStatus::Output(new_state, value) => {
+                    write!(output, "{}", value).context("Failed to write to the output stream")?;
+                    state = new_state;
+                }
+
+                RunStatus::InputInteger(mut new_state) => {
+                    // The output may be a prompt, which must be visible
+                    // before waiting for the input.
+                    output
+                        .flush()
+                        .context("Failed to write to the output stream")?;
+
+                    new_state.stack_mut().push_integer(read_integer(input)?);
+                    state = new_state;
+                }
+
+                RunStatus::PushConstant(mut new_state, idx) => {
+                    let value = match self.constants.get(idx) {
+                        Some(Constant::Integer(i)) => Value::Integer(*i),
+                        Some(Constant::Float(x)) => Value::Float(*x),
+                        Some(Constant::String(s)) => Value::String(s.as_str().into()),
+                        None => bail!("Unknown constant: `{}`", idx),
+                    };
+
+                    new_state.stack_mut().push_value(value);
+                    state = new_state;
+                }
             }
         };
 
+        output
+            .flush()
+            .context("Failed to write to the output stream")?;
+
         Ok(final_value)
     }
 
     fn run_single(&mut self, state: RunningInterpreterState) -> Result<RunStatus> {
+        let ip = state.ip;
         let instr = self
             .code
-            .get(state.ip as usize)
-            .ok_or_else(|| anyhow!("Failed to read instruction at index `{}`", state.ip))?;
+            .get(ip as usize)
+            .ok_or_else(|| anyhow!("Failed to read instruction at index `{}`", ip))?;
 
-        instr.run(state)
+        let rslt = instr.run(state);
+
+        match self.line_table.location_of(ip) {
+            Some(location) => rslt.with_context(|| format!("Runtime error at {}", location)),
+            None => rslt,
+        }
     }
 }
 
+/// Reads a line from the input stream, which must contain a single integer.
+fn read_integer(input: &mut dyn BufRead) -> Result<i32> {
+    let mut line = String::new();
+
+    let read = input
+        .read_line(&mut line)
+        .context("Failed to read from the input stream")?;
+    ensure!(read > 0, "Reached the end of the input stream");
+
+    let line = line.trim();
+    line.parse()
+        .map_err(|_| anyhow!("Expected an integer in the input stream, found `{}`", line))
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct RunningInterpreterState {
     ip: u32,
     stack: Stack,
+
+    /// The base of each call frame that has not returned yet: the index, in
+    /// the stack, of the first value pushed after its return address.
+    frames: Vec<usize>,
 }
 
 impl RunningInterpreterState {
@@ -48,7 +138,11 @@ impl RunningInterpreterState {
         let stack = Stack::new();
         let ip = 0;
 
-        RunningInterpreterState { ip, stack }
+        RunningInterpreterState {
+            ip,
+            stack,
+            frames: Vec::new(),
+        }
     }
 
     pub(crate) fn continue_to_next(mut self) -> RunningInterpreterState {
@@ -61,6 +155,25 @@ impl RunningInterpreterState {
         self
     }
 
+    /// Starts a call frame, right after the return address is pushed.
+    pub(crate) fn enter_frame(&mut self) {
+        self.frames.push(self.stack.0.len());
+    }
+
+    pub(crate) fn leave_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Returns the index, in the stack, of a slot of the current call frame.
+    /// Slots are counted from the base of the frame, which is the bottom of
+    /// the stack outside of any function.
+    pub(crate) fn frame_slot(&self, idx: i16) -> Result<usize> {
+        let base = self.frames.last().copied().unwrap_or(0);
+
+        base.checked_add_signed(idx as isize)
+            .ok_or_else(|| anyhow!("Out-of-bound stack access"))
+    }
+
     pub(crate) fn ip(&self) -> u32 {
         self.ip
     }
@@ -93,6 +206,31 @@ impl Stack {
             .context("Failed to pop an integer from the stack")
     }
 
+    pub(crate) fn push_float(&mut self, x: f64) {
+        let v = Value::Float(x);
+        self.push_value(v);
+    }
+
+    pub(crate) fn pop_float(&mut self) -> Result<f64> {
+        self.pop()
+            .and_then(Value::try_into_float)
+            .context("Failed to pop a float from the stack")
+    }
+
+    pub(crate) fn push_string(&mut self, s: &str) {
+        let v = Value::String(s.into());
+        self.push_value(v);
+    }
+
+    pub(crate) fn pop_many(&mut self, n: u16) -> Result<Vec<Value>> {
+        let n = n as usize;
+        ensure!(self.0.len() >= n, "Out-of-bound stack access");
+
+        let start = self.0.len() - n;
+
+        Ok(self.0.split_off(start))
+    }
+
     pub(crate) fn pop(&mut self) -> Result<Value> {
         self.0.pop().ok_or_else(|| anyhow!("Empty stack found"))
     }
@@ -124,6 +262,32 @@ impl Stack {
         Ok(())
     }
 
+    /// Pushes a copy of the value at an index, counted from the bottom of
+    /// the stack.
+    pub(crate) fn copy_value_at(&mut self, idx: usize) -> Result<()> {
+        let value = self
+            .0
+            .get(idx)
+            .ok_or_else(|| anyhow!("Out-of-bound stack access"))?
+            .clone();
+
+        self.0.push(value);
+
+        Ok(())
+    }
+
+    /// Replaces the value at an index, counted from the bottom of the stack.
+    pub(crate) fn replace_at(&mut self, idx: usize, val: Value) -> Result<()> {
+        let dest = self
+            .0
+            .get_mut(idx)
+            .ok_or_else(|| anyhow!("Out-of-bound stack access"))?;
+
+        *dest = val;
+
+        Ok(())
+    }
+
     pub(crate) fn push_instruction_pointer(&mut self, idx: u32) {
         let value = Value::InstructionPointer(idx);
         self.0.push(value);
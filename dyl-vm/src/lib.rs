@@ -1,6 +1,6 @@
 use anyhow::Result;
 
-use dyl_bytecode::Instruction;
+use dyl_bytecode::{constant_pool::ConstantPool, line_table::LineTable, Instruction};
 use interpreter::Interpreter;
 
 mod interpreter;
@@ -11,7 +11,28 @@ mod value;
 mod tests;
 
 pub fn run_program(bytecode: Vec<Instruction>) -> Result<()> {
-    let return_value = Interpreter::from_instructions(bytecode).run()?;
+    run_program_with_line_table(bytecode, LineTable::default())
+}
+
+/// Runs a program, whose runtime errors are located in the source code with
+/// the line table of the bytecode.
+pub fn run_program_with_line_table(
+    bytecode: Vec<Instruction>,
+    line_table: LineTable,
+) -> Result<()> {
+    run_program_with_constants(bytecode, line_table, ConstantPool::default())
+}
+
+/// Runs a program whose `push_const` instructions refer to a constant pool.
+pub fn run_program_with_constants(
+    bytecode: Vec<Instruction>,
+    line_table: LineTable,
+    constants: ConstantPool,
+) -> Result<()> {
+    let return_value = Interpreter::from_instructions(bytecode)
+        .with_line_table(line_table)
+        .with_constants(constants)
+        .run()?;
     println!("{}", return_value);
 
     Ok(())
@@ -1,15 +1,23 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, convert::TryFrom};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 
 use dyl_bytecode::{
     operations::{
-        AddI, Call, CondJmp, FStop, Goto, Mul, Neg, Pop, PopCopy, PushCopy, PushI, ResV, Ret,
+        AddF, AddI, AndI, ArithmeticOp, Call, CallClosure, CharAt, CheckFrame, CheckOverflow,
+        CondJmp, DivF, DivI, EqI, FStop, FToI, Field, GeI, Goto, GtI, IToF, InI, Index,
+        JmpIfNotZero, JmpIfZero, LeI, Len, LoadLocal, LtI, MakeClosure, ModI, Mul, MulF, Neg, NeqI,
+        NewArray, Nop, NotI, OrI, Out, Pack, Pop, PopBack, PopCopy, Pow, PushBack, PushConst,
+        PushCopy, PushF, PushI, PushS, ResV, Ret, ShlI, ShrI, StoreIndex, StoreLocal, SubF, SubI,
+        Substr, Swap, Trap, XorI,
     },
     Instruction,
 };
 
-use crate::{interpreter::RunningInterpreterState, value::Value};
+use crate::{
+    interpreter::RunningInterpreterState,
+    value::{self, ArrayRef, Closure, Value},
+};
 
 pub(crate) trait Runnable {
     fn run(&self, state: RunningInterpreterState) -> Result<RunStatus>;
@@ -37,6 +45,81 @@ impl Runnable for Instruction {
             Instruction::Neg(op) => op.run(state).context("Failed to run `neg` instruction"),
             Instruction::Mul(op) => op.run(state).context("Failed to run `mul` instruction"),
             Instruction::Pop(op) => op.run(state).context("Failed to run the `pop` instruction"),
+            Instruction::PushS(op) => op.run(state).context("Failed to run `push_s` instruction"),
+            Instruction::Pack(op) => op.run(state).context("Failed to run `pack` instruction"),
+            Instruction::Field(op) => op.run(state).context("Failed to run `field` instruction"),
+            Instruction::NewArray(op) => op
+                .run(state)
+                .context("Failed to run `new_array` instruction"),
+            Instruction::Index(op) => op.run(state).context("Failed to run `index` instruction"),
+            Instruction::StoreIndex(op) => op
+                .run(state)
+                .context("Failed to run `store_index` instruction"),
+            Instruction::MakeClosure(op) => op
+                .run(state)
+                .context("Failed to run `make_closure` instruction"),
+            Instruction::CallClosure(op) => op
+                .run(state)
+                .context("Failed to run `call_closure` instruction"),
+            Instruction::Pow(op) => op.run(state).context("Failed to run `pow` instruction"),
+            Instruction::Out(op) => op.run(state).context("Failed to run `out` instruction"),
+            Instruction::InI(op) => op.run(state).context("Failed to run `in_i` instruction"),
+            // The message of a trap is the error itself.
+            Instruction::Trap(op) => op.run(state),
+            Instruction::Len(op) => op.run(state).context("Failed to run `len` instruction"),
+            Instruction::Substr(op) => op.run(state).context("Failed to run `substr` instruction"),
+            Instruction::CharAt(op) => op.run(state).context("Failed to run `char_at` instruction"),
+            Instruction::PushBack(op) => op
+                .run(state)
+                .context("Failed to run `push_back` instruction"),
+            Instruction::PopBack(op) => op
+                .run(state)
+                .context("Failed to run `pop_back` instruction"),
+            Instruction::CheckFrame(op) => op
+                .run(state)
+                .context("Failed to run `check_frame` instruction"),
+            Instruction::CheckOverflow(op) => op
+                .run(state)
+                .context("Failed to run `check_overflow` instruction"),
+            Instruction::SubI(op) => op.run(state).context("Failed to run `sub_i` instruction"),
+            Instruction::DivI(op) => op.run(state).context("Failed to run `div_i` instruction"),
+            Instruction::ModI(op) => op.run(state).context("Failed to run `mod_i` instruction"),
+            Instruction::EqI(op) => op.run(state).context("Failed to run `eq_i` instruction"),
+            Instruction::NeqI(op) => op.run(state).context("Failed to run `neq_i` instruction"),
+            Instruction::LtI(op) => op.run(state).context("Failed to run `lt_i` instruction"),
+            Instruction::LeI(op) => op.run(state).context("Failed to run `le_i` instruction"),
+            Instruction::GtI(op) => op.run(state).context("Failed to run `gt_i` instruction"),
+            Instruction::GeI(op) => op.run(state).context("Failed to run `ge_i` instruction"),
+            Instruction::JmpIfZero(op) => op
+                .run(state)
+                .context("Failed to run `jmp_if_zero` instruction"),
+            Instruction::JmpIfNotZero(op) => op
+                .run(state)
+                .context("Failed to run `jmp_if_not_zero` instruction"),
+            Instruction::Swap(op) => op.run(state).context("Failed to run `swap` instruction"),
+            Instruction::Nop(op) => op.run(state).context("Failed to run `nop` instruction"),
+            Instruction::AndI(op) => op.run(state).context("Failed to run `and_i` instruction"),
+            Instruction::OrI(op) => op.run(state).context("Failed to run `or_i` instruction"),
+            Instruction::XorI(op) => op.run(state).context("Failed to run `xor_i` instruction"),
+            Instruction::ShlI(op) => op.run(state).context("Failed to run `shl_i` instruction"),
+            Instruction::ShrI(op) => op.run(state).context("Failed to run `shr_i` instruction"),
+            Instruction::NotI(op) => op.run(state).context("Failed to run `not_i` instruction"),
+            Instruction::PushF(op) => op.run(state).context("Failed to run `push_f` instruction"),
+            Instruction::AddF(op) => op.run(state).context("Failed to run `add_f` instruction"),
+            Instruction::SubF(op) => op.run(state).context("Failed to run `sub_f` instruction"),
+            Instruction::MulF(op) => op.run(state).context("Failed to run `mul_f` instruction"),
+            Instruction::DivF(op) => op.run(state).context("Failed to run `div_f` instruction"),
+            Instruction::IToF(op) => op.run(state).context("Failed to run `i_to_f` instruction"),
+            Instruction::FToI(op) => op.run(state).context("Failed to run `f_to_i` instruction"),
+            Instruction::PushConst(op) => op
+                .run(state)
+                .context("Failed to run `push_const` instruction"),
+            Instruction::LoadLocal(op) => op
+                .run(state)
+                .context("Failed to run `load_local` instruction"),
+            Instruction::StoreLocal(op) => op
+                .run(state)
+                .context("Failed to run `store_local` instruction"),
         }
     }
 }
@@ -50,6 +133,155 @@ impl Runnable for PushI {
     }
 }
 
+impl Runnable for PushS {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        state.stack_mut().push_string(&self.0);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Pack {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let values = state
+            .stack_mut()
+            .pop_many(self.0)
+            .context("Failed to get values to pack")?;
+
+        state.stack_mut().push_value(Value::Tuple(values.into()));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Field {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let tuple = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_tuple)
+            .context("Failed to get tuple")?;
+
+        let value = tuple
+            .get(self.0 as usize)
+            .cloned()
+            .ok_or_else(|| anyhow!("Out-of-bound field access"))?;
+
+        state.stack_mut().push_value(value);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for NewArray {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let values = state
+            .stack_mut()
+            .pop_many(self.0)
+            .context("Failed to get array elements")?;
+
+        state
+            .stack_mut()
+            .push_value(Value::Array(ArrayRef::alloc(values)));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Index {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let idx = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer index")?;
+        let array = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_array)
+            .context("Cannot index into a non-array value")?;
+
+        let value = array.get(idx)?;
+        state.stack_mut().push_value(value);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for StoreIndex {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let value = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get value to store")?;
+        let idx = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer index")?;
+        let array = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_array)
+            .context("Cannot index into a non-array value")?;
+
+        array.set(idx, value.clone())?;
+        state.stack_mut().push_value(value);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for MakeClosure {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let env = state
+            .stack_mut()
+            .pop_many(self.captures)
+            .context("Failed to get captured values")?;
+
+        let closure = Closure {
+            addr: self.addr,
+            env: env.into(),
+            arity: self.arity,
+        };
+        state.stack_mut().push_value(Value::Closure(closure));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for CallClosure {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let args = state
+            .stack_mut()
+            .pop_many(self.0)
+            .context("Failed to get closure arguments")?;
+        let closure = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_closure)
+            .context("Cannot call a non-closure value")?;
+
+        ensure!(
+            closure.arity == self.0,
+            "Wrong number of arguments for closure: expected {}, found {}",
+            closure.arity,
+            self.0,
+        );
+
+        closure
+            .env
+            .iter()
+            .cloned()
+            .chain(args)
+            .for_each(|value| state.stack_mut().push_value(value));
+
+        let next_addr = state.ip() + 1;
+        state.stack_mut().push_instruction_pointer(next_addr);
+        state.enter_frame();
+
+        Ok(state.continue_to(closure.addr).into())
+    }
+}
+
 impl Runnable for AddI {
     fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
         let lhs = state
@@ -61,7 +293,7 @@ impl Runnable for AddI {
             .pop_integer()
             .context("Failed to get integer right-hand-side value")?;
 
-        state.stack_mut().push_integer(lhs + rhs);
+        state.stack_mut().push_integer(lhs.wrapping_add(rhs));
 
         Ok(state.continue_to_next().into())
     }
@@ -93,6 +325,7 @@ impl Runnable for Call {
         let jump_addr = self.0;
         let next_addr = state.ip() + 1;
         state.stack_mut().push_instruction_pointer(next_addr);
+        state.enter_frame();
         Ok(state.continue_to(jump_addr).into())
     }
 }
@@ -108,6 +341,7 @@ impl Runnable for Ret {
             .stack_mut()
             .truncate(self.shrink_offset)
             .context("Failed to resize stack")?;
+        state.leave_frame();
 
         Ok(state.continue_to(initial_offset).into())
     }
@@ -143,6 +377,35 @@ impl Runnable for PopCopy {
     }
 }
 
+impl Runnable for LoadLocal {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let idx = state.frame_slot(self.0)?;
+        state
+            .stack_mut()
+            .copy_value_at(idx)
+            .context("Failed to load local variable")?;
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for StoreLocal {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let v = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get value to store")?;
+
+        let idx = state.frame_slot(self.0)?;
+        state
+            .stack_mut()
+            .replace_at(idx, v)
+            .context("Failed to store local variable")?;
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
 impl Runnable for Goto {
     fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
         let dest = self.0;
@@ -150,6 +413,38 @@ impl Runnable for Goto {
     }
 }
 
+impl Runnable for JmpIfZero {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let i = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get jump condition")?;
+
+        Ok(if i == 0 {
+            state.continue_to(self.0)
+        } else {
+            state.continue_to_next()
+        }
+        .into())
+    }
+}
+
+impl Runnable for JmpIfNotZero {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let i = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get jump condition")?;
+
+        Ok(if i != 0 {
+            state.continue_to(self.0)
+        } else {
+            state.continue_to_next()
+        }
+        .into())
+    }
+}
+
 impl Runnable for CondJmp {
     fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
         let i = state
@@ -172,7 +467,7 @@ impl Runnable for Neg {
             .stack_mut()
             .pop_integer()
             .context("Failed to get integer to negate")?;
-        state.stack_mut().push_integer(-i);
+        state.stack_mut().push_integer(i.wrapping_neg());
 
         Ok(state.continue_to_next().into())
     }
@@ -189,7 +484,389 @@ impl Runnable for Mul {
             .pop_integer()
             .context("Failed to get integer right-hand-side value")?;
 
-        state.stack_mut().push_integer(lhs * rhs);
+        state.stack_mut().push_integer(lhs.wrapping_mul(rhs));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Pow {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let exponent = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer exponent")?;
+        let base = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer base")?;
+
+        let exponent = u32::try_from(exponent)
+            .map_err(|_| anyhow!("Cannot raise to the negative power {}", exponent))?;
+
+        state.stack_mut().push_integer(base.wrapping_pow(exponent));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for SubI {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let rhs = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer right-hand-side value")?;
+        let lhs = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer left-hand-side value")?;
+
+        state.stack_mut().push_integer(lhs.wrapping_sub(rhs));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for DivI {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let (dividend, divisor) = pop_division_operands(&mut state)?;
+        state
+            .stack_mut()
+            .push_integer(dividend.wrapping_div(divisor));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for ModI {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let (dividend, divisor) = pop_division_operands(&mut state)?;
+        state
+            .stack_mut()
+            .push_integer(dividend.wrapping_rem(divisor));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+/// Pops the divisor, then the dividend, of a division. Fails if the divisor
+/// is zero.
+fn pop_division_operands(state: &mut RunningInterpreterState) -> Result<(i32, i32)> {
+    let divisor = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer divisor")?;
+    let dividend = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer dividend")?;
+
+    ensure!(divisor != 0, "Division by zero: `{} / 0`", dividend);
+
+    Ok((dividend, divisor))
+}
+
+impl Runnable for EqI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs == rhs)
+    }
+}
+
+impl Runnable for NeqI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs != rhs)
+    }
+}
+
+impl Runnable for LtI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs < rhs)
+    }
+}
+
+impl Runnable for LeI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs <= rhs)
+    }
+}
+
+impl Runnable for GtI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs > rhs)
+    }
+}
+
+impl Runnable for GeI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_comparison(state, |lhs, rhs| lhs >= rhs)
+    }
+}
+
+/// Pops the right-hand side, then the left-hand side, of a comparison, and
+/// pushes 1 if it holds, 0 otherwise.
+fn run_comparison(
+    mut state: RunningInterpreterState,
+    holds: impl Fn(i32, i32) -> bool,
+) -> Result<RunStatus> {
+    let rhs = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer right-hand-side value")?;
+    let lhs = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer left-hand-side value")?;
+
+    state.stack_mut().push_integer(holds(lhs, rhs) as i32);
+
+    Ok(state.continue_to_next().into())
+}
+
+impl Runnable for Swap {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let b = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get the top value")?;
+        let a = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get the value below the top one")?;
+
+        state.stack_mut().push_value(b);
+        state.stack_mut().push_value(a);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Nop {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for AndI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_bitwise(state, |lhs, rhs| lhs & rhs)
+    }
+}
+
+impl Runnable for OrI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_bitwise(state, |lhs, rhs| lhs | rhs)
+    }
+}
+
+impl Runnable for XorI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_bitwise(state, |lhs, rhs| lhs ^ rhs)
+    }
+}
+
+impl Runnable for ShlI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_bitwise(state, |lhs, rhs| lhs.wrapping_shl(rhs as u32))
+    }
+}
+
+impl Runnable for ShrI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_bitwise(state, |lhs, rhs| lhs.wrapping_shr(rhs as u32))
+    }
+}
+
+/// Pops the right-hand side, then the left-hand side, of a bitwise
+/// operation, and pushes its result.
+fn run_bitwise(
+    mut state: RunningInterpreterState,
+    op: impl Fn(i32, i32) -> i32,
+) -> Result<RunStatus> {
+    let rhs = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer right-hand-side value")?;
+    let lhs = state
+        .stack_mut()
+        .pop_integer()
+        .context("Failed to get integer left-hand-side value")?;
+
+    state.stack_mut().push_integer(op(lhs, rhs));
+
+    Ok(state.continue_to_next().into())
+}
+
+impl Runnable for NotI {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let i = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer to flip")?;
+        state.stack_mut().push_integer(!i);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for PushF {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        state.stack_mut().push_float(self.0);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for PushConst {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        Ok(RunStatus::PushConstant(state.continue_to_next(), self.0))
+    }
+}
+
+impl Runnable for AddF {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_float_arithmetic(state, |lhs, rhs| lhs + rhs)
+    }
+}
+
+impl Runnable for SubF {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_float_arithmetic(state, |lhs, rhs| lhs - rhs)
+    }
+}
+
+impl Runnable for MulF {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_float_arithmetic(state, |lhs, rhs| lhs * rhs)
+    }
+}
+
+impl Runnable for DivF {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        run_float_arithmetic(state, |lhs, rhs| lhs / rhs)
+    }
+}
+
+/// Pops the right-hand side, then the left-hand side, of a floating-point
+/// operation, and pushes its result.
+fn run_float_arithmetic(
+    mut state: RunningInterpreterState,
+    op: impl Fn(f64, f64) -> f64,
+) -> Result<RunStatus> {
+    let rhs = state
+        .stack_mut()
+        .pop_float()
+        .context("Failed to get float right-hand-side value")?;
+    let lhs = state
+        .stack_mut()
+        .pop_float()
+        .context("Failed to get float left-hand-side value")?;
+
+    state.stack_mut().push_float(op(lhs, rhs));
+
+    Ok(state.continue_to_next().into())
+}
+
+impl Runnable for IToF {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let i = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer to convert")?;
+        state.stack_mut().push_float(f64::from(i));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for FToI {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let x = state
+            .stack_mut()
+            .pop_float()
+            .context("Failed to get float to convert")?;
+        // Casting saturates at the bounds, and turns NaN into 0.
+        state.stack_mut().push_integer(x as i32);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Out {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let value = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get the value to write")?;
+
+        Ok(RunStatus::Output(state.continue_to_next(), value))
+    }
+}
+
+impl Runnable for InI {
+    fn run(&self, state: RunningInterpreterState) -> Result<RunStatus> {
+        Ok(RunStatus::InputInteger(state.continue_to_next()))
+    }
+}
+
+impl Runnable for Trap {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let message = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get trap message")?;
+
+        bail!("{}", message)
+    }
+}
+
+impl Runnable for CheckFrame {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let value = state.stack_mut().get_at_offset(self.0)?;
+
+        ensure!(
+            matches!(value, Value::InstructionPointer(_)),
+            "Stack frame mismatch: expected a return address at offset {}, found `{}`",
+            self.0,
+            value
+        );
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for CheckOverflow {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let CheckOverflow(op) = *self;
+
+        let rhs = state
+            .stack_mut()
+            .get_at_offset(0)?
+            .clone()
+            .try_into_integer()?;
+        let lhs = state
+            .stack_mut()
+            .get_at_offset(1)?
+            .clone()
+            .try_into_integer()?;
+
+        let result = match op {
+            ArithmeticOp::Add => lhs.checked_add(rhs),
+            ArithmeticOp::Sub => lhs.checked_sub(rhs),
+            ArithmeticOp::Mul => lhs.checked_mul(rhs),
+            // Negative exponents are reported by `pow` itself.
+            ArithmeticOp::Pow => match u32::try_from(rhs) {
+                Ok(exponent) => lhs.checked_pow(exponent),
+                Err(_) => Some(0),
+            },
+        };
+
+        ensure!(
+            result.is_some(),
+            "Arithmetic overflow: `{} {} {}`",
+            lhs,
+            op.symbol(),
+            rhs
+        );
 
         Ok(state.continue_to_next().into())
     }
@@ -203,9 +880,125 @@ impl Runnable for Pop {
     }
 }
 
+impl Runnable for Len {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let len = match state.stack_mut().pop().context("Failed to get value")? {
+            Value::String(string) => string.chars().count(),
+            Value::Array(array) => array.len(),
+            anything => bail!("Cannot take the length of `{}`", anything),
+        };
+
+        let len = i32::try_from(len).context("Length is too big")?;
+        state.stack_mut().push_integer(len);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for Substr {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let length = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get substring length")?;
+        let start = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get substring start")?;
+        let string = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_string)
+            .context("Failed to get string")?;
+
+        let len = string.chars().count();
+        let end = i64::from(start) + i64::from(length);
+        ensure!(
+            0 <= start && 0 <= length && end <= len as i64,
+            "Substring out of bounds: the length is {} but the substring spans {}..{}",
+            len,
+            start,
+            end,
+        );
+
+        let substring = string
+            .chars()
+            .skip(start as usize)
+            .take(length as usize)
+            .collect::<String>();
+        state.stack_mut().push_string(&substring);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for CharAt {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let idx = state
+            .stack_mut()
+            .pop_integer()
+            .context("Failed to get integer index")?;
+        let string = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_string)
+            .context("Failed to get string")?;
+
+        let idx = value::checked_index(idx, string.chars().count())?;
+        let c = string.chars().nth(idx).unwrap();
+        state.stack_mut().push_string(c.encode_utf8(&mut [0; 4]));
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for PushBack {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let value = state
+            .stack_mut()
+            .pop()
+            .context("Failed to get value to append")?;
+        let array = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_array)
+            .context("Cannot append to a non-array value")?;
+
+        array.push(value);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
+impl Runnable for PopBack {
+    fn run(&self, mut state: RunningInterpreterState) -> Result<RunStatus> {
+        let array = state
+            .stack_mut()
+            .pop()
+            .and_then(Value::try_into_array)
+            .context("Cannot remove from a non-array value")?;
+
+        let value = array.pop()?;
+        state.stack_mut().push_value(value);
+
+        Ok(state.continue_to_next().into())
+    }
+}
+
 pub(crate) enum RunStatus {
     Continue(RunningInterpreterState),
     Stop(Value),
+
+    /// Writes a value to the output stream, then continues.
+    Output(RunningInterpreterState, Value),
+
+    /// Reads an integer from the input stream and pushes it, then
+    /// continues.
+    InputInteger(RunningInterpreterState),
+
+    /// Pushes the constant of the constant pool at an index, then
+    /// continues.
+    PushConstant(RunningInterpreterState, u16),
 }
 
 impl From<RunningInterpreterState> for RunStatus {
@@ -1,7 +1,11 @@
-use dyl_bytecode::Instruction;
+use dyl_bytecode::{
+    constant_pool::{Constant, ConstantPool},
+    line_table::{LineTable, SourceLocation},
+    Instruction,
+};
 
 use crate::interpreter::Interpreter;
-use crate::value::Value;
+use crate::value::{ArrayRef, Closure, Value};
 
 macro_rules! generate_bytecode {
     (@internal($acc:ident, $val:expr) {}) => {};
@@ -21,6 +25,151 @@ macro_rules! generate_bytecode {
         generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
     };
 
+    (@internal($acc:ident, $val:expr) { sub_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::sub_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { div_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::div_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { mod_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::mod_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { eq_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::eq_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { neq_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::neq_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { lt_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::lt_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { le_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::le_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { gt_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::gt_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { ge_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::ge_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { jmp_if_zero $label:ident $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::jmp_if_zero($label));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { jmp_if_not_zero $label:ident $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::jmp_if_not_zero($label));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { swap $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::swap());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { nop $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::nop());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { and_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::and_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { or_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::or_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { xor_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::xor_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { shl_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::shl_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { shr_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::shr_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { not_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::not_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { push_f $f:literal $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::push_f($f));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { add_f $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::add_f());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { sub_f $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::sub_f());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { mul_f $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::mul_f());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { div_f $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::div_f());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { i_to_f $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::i_to_f());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { f_to_i $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::f_to_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { push_const $idx:literal $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::push_const($idx));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { load_local $idx:literal $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::load_local($idx));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { store_local $idx:literal $( $tail:tt )* }) => {
+        $acc.push(dyl_bytecode::Instruction::store_local($idx));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
     (@internal($acc:ident, $val:expr) { f_stop $( $tail:tt )* }) => {
         $acc.push(dyl_bytecode::Instruction::f_stop());
         generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
@@ -71,6 +220,103 @@ macro_rules! generate_bytecode {
         generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
     };
 
+    (@internal($acc:ident, $val:expr) { push_s $s:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::push_s($s));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { pack $len:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::pack($len));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { field $idx:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::field($idx));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { new_array $len:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::new_array($len));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { index $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::index());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { store_index $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::store_index());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { make_closure $label:ident $captures:literal $arity:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::make_closure($label, $captures, $arity));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { call_closure $arity:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::call_closure($arity));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { pow $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::pow());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { out $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::out());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { trap $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::trap());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { in_i $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::in_i());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { len $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::len());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { substr $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::substr());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { char_at $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::char_at());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { push_back $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::push_back());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { pop_back $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::pop_back());
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { check_frame $idx:literal $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::check_frame($idx));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
+    (@internal($acc:ident, $val:expr) { check_overflow $op:ident $( $tail:tt )* } ) => {
+        $acc.push(dyl_bytecode::Instruction::check_overflow(
+            dyl_bytecode::operations::ArithmeticOp::$op,
+        ));
+        generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
+    };
+
     (@internal($acc:ident, $val:expr) { pop $idx:literal $( $tail:tt )* } ) => {
         $acc.push(dyl_bytecode::Instruction::pop($idx));
         generate_bytecode! { @internal($acc, $val + 1) { $( $tail )* } }
@@ -257,6 +503,59 @@ test_bytecode_execution! {
     } = Ok(Value::Integer(42)),
 }
 
+test_bytecode_execution! {
+    recursive_function :: {
+            res_v 1
+            push_i 4
+            call SUM
+            f_stop
+
+        SUM:
+            push_cpy 1
+            cond_jmp BASE BASE REC
+
+        REC:
+            res_v 1
+            push_cpy 2
+            push_i -1
+            add_i
+            call SUM
+            push_cpy 2
+            add_i
+            pop_cpy 3
+            ret 2 0
+
+        BASE:
+            push_i 0
+            pop_cpy 3
+            ret 2 0
+    } = Ok(Value::Integer(10)),
+}
+
+test_bytecode_execution! {
+    frame_relative_locals :: {
+            res_v 1
+            push_i 3
+            call DOUBLE_PLUS_ONE
+            load_local 0
+            add_i
+            f_stop
+
+        DOUBLE_PLUS_ONE:
+            push_i 0
+            load_local -2
+            load_local -2
+            add_i
+            store_local 0
+            load_local 0
+            push_i 1
+            add_i
+            store_local -3
+            pop 1
+            ret 2 0
+    } = Ok(Value::Integer(14)),
+}
+
 test_bytecode_execution! {
     goto_simple :: {
             goto NEXT
@@ -336,6 +635,621 @@ test_bytecode_execution! {
     } = Ok(Value::Integer(42)),
 }
 
+test_bytecode_execution! {
+    pow_base_first :: {
+        push_i 2
+        push_i 10
+        pow
+        f_stop
+    } = Ok(Value::Integer(1024)),
+}
+
+test_bytecode_execution! {
+    pow_zero_exponent :: {
+        push_i 7
+        push_i 0
+        pow
+        f_stop
+    } = Ok(Value::Integer(1)),
+}
+
+#[test]
+fn pow_rejects_negative_exponents() {
+    let rslt = run_bytecode! {
+        push_i 2
+        push_i -1
+        pow
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Cannot raise to the negative power -1"
+    );
+}
+
+test_bytecode_execution! {
+    sub_i_subtracts_the_top_value :: {
+        push_i 50
+        push_i 8
+        sub_i
+        f_stop
+    } = Ok(Value::Integer(42)),
+}
+
+test_bytecode_execution! {
+    div_i_rounds_towards_zero :: {
+        push_i -7
+        push_i 2
+        div_i
+        f_stop
+    } = Ok(Value::Integer(-3)),
+}
+
+test_bytecode_execution! {
+    mod_i_has_the_sign_of_the_dividend :: {
+        push_i -7
+        push_i 2
+        mod_i
+        f_stop
+    } = Ok(Value::Integer(-1)),
+}
+
+test_bytecode_execution! {
+    integer_arithmetic_wraps_around :: {
+        push_i -2147483648
+        push_i -1
+        div_i
+        neg
+        push_i 2
+        mul
+        f_stop
+    } = Ok(Value::Integer(0)),
+}
+
+#[test]
+fn div_i_rejects_zero() {
+    let rslt = run_bytecode! {
+        push_i 1
+        push_i 0
+        div_i
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Division by zero: `1 / 0`"
+    );
+}
+
+#[test]
+fn mod_i_rejects_zero() {
+    let rslt = run_bytecode! {
+        push_i 1
+        push_i 0
+        mod_i
+        f_stop
+    };
+
+    assert!(rslt.is_err());
+}
+
+test_bytecode_execution! {
+    comparisons_push_booleans :: {
+        push_i 1
+        push_i 2
+        lt_i
+        push_i 2
+        push_i 2
+        ge_i
+        add_i
+        push_i -2147483648
+        push_i 2147483647
+        gt_i
+        add_i
+        push_i 3
+        push_i 4
+        eq_i
+        add_i
+        f_stop
+    } = Ok(Value::Integer(2)),
+}
+
+test_bytecode_execution! {
+    conditional_jumps_fall_through :: {
+            push_i 0
+            jmp_if_not_zero FAIL
+            push_i 0
+            jmp_if_zero ZERO
+        FAIL:
+            push_i 0
+            f_stop
+        ZERO:
+            push_i 1
+            jmp_if_zero FAIL
+            push_i 42
+            f_stop
+    } = Ok(Value::Integer(42)),
+}
+
+test_bytecode_execution! {
+    swap_exchanges_the_top_values :: {
+        push_i 2
+        push_i 50
+        swap
+        nop
+        sub_i
+        f_stop
+    } = Ok(Value::Integer(48)),
+}
+
+test_bytecode_execution! {
+    bitwise_operations :: {
+        push_i 12
+        push_i 10
+        and_i
+        push_i 3
+        or_i
+        push_i 5
+        xor_i
+        push_i 1
+        shl_i
+        f_stop
+    } = Ok(Value::Integer(28)),
+}
+
+test_bytecode_execution! {
+    shifts_use_the_amount_modulo_32 :: {
+        push_i -16
+        push_i 34
+        shr_i
+        push_i 33
+        shl_i
+        not_i
+        f_stop
+    } = Ok(Value::Integer(7)),
+}
+
+test_bytecode_execution! {
+    float_arithmetic :: {
+        push_f 1.5
+        push_i 3
+        i_to_f
+        mul_f
+        push_f 0.5
+        sub_f
+        push_f 2.0
+        div_f
+        push_f 0.25
+        add_f
+        f_stop
+    } = Ok(Value::Float(2.25)),
+}
+
+test_bytecode_execution! {
+    float_to_integer_rounds_towards_zero :: {
+        push_f -2.75
+        f_to_i
+        push_f 1.0
+        push_f 0.0
+        div_f
+        f_to_i
+        add_i
+        f_stop
+    } = Ok(Value::Integer(2147483645)),
+}
+
+#[test]
+fn trap_fails_with_its_message() {
+    let rslt = run_bytecode! {
+        push_s "Assertion failed"
+        trap
+        f_stop
+    };
+
+    assert_eq!(rslt.unwrap_err().to_string(), "Assertion failed");
+}
+
+#[test]
+fn errors_are_located_with_the_line_table() {
+    let instrs = generate_bytecode! {
+        push_i 1
+        push_s "Assertion failed"
+        trap
+    };
+    let location = |line| {
+        Some(SourceLocation {
+            file: Some("a.dyl".to_owned()),
+            line,
+            column: 5,
+        })
+    };
+    let line_table = LineTable::from_locations([location(1), location(2), location(3)]);
+
+    let err = Interpreter::from_instructions(instrs)
+        .with_line_table(line_table)
+        .run()
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "Runtime error at a.dyl:3:5");
+    assert_eq!(err.root_cause().to_string(), "Assertion failed");
+}
+
+#[test]
+fn out_writes_values() {
+    let instrs = generate_bytecode! {
+        push_i 4
+        out
+        push_s "2\n"
+        out
+        push_i 0
+        f_stop
+    };
+    let mut output = Vec::new();
+
+    let rslt = Interpreter::from_instructions(instrs).run_with_io(&mut &b""[..], &mut output);
+
+    assert_eq!(rslt.unwrap(), Value::Integer(0));
+    assert_eq!(output, b"42\n");
+}
+
+fn run_with_input(instrs: Vec<Instruction>, input: &str) -> anyhow::Result<Value> {
+    Interpreter::from_instructions(instrs).run_with_io(&mut input.as_bytes(), &mut Vec::new())
+}
+
+#[test]
+fn in_i_reads_one_integer_per_line() {
+    let instrs = generate_bytecode! {
+        in_i
+        in_i
+        mul
+        f_stop
+    };
+
+    let rslt = run_with_input(instrs, " 6\n-7 \n");
+
+    assert_eq!(rslt.unwrap(), Value::Integer(-42));
+}
+
+#[test]
+fn in_i_rejects_non_integers() {
+    let instrs = generate_bytecode! {
+        in_i
+        f_stop
+    };
+
+    let rslt = run_with_input(instrs, "forty-two\n");
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Expected an integer in the input stream, found `forty-two`"
+    );
+}
+
+#[test]
+fn in_i_fails_at_end_of_input() {
+    let instrs = generate_bytecode! {
+        in_i
+        f_stop
+    };
+
+    let rslt = run_with_input(instrs, "");
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Reached the end of the input stream"
+    );
+}
+
+test_bytecode_execution! {
+    push_s_simple :: {
+        push_s "hello"
+        f_stop
+    } = Ok(Value::String("hello".into())),
+}
+
+#[test]
+fn add_i_rejects_strings() {
+    let rslt = run_bytecode! {
+        push_i 1
+        push_s "1"
+        add_i
+        f_stop
+    };
+
+    assert!(rslt.is_err());
+}
+
+test_bytecode_execution! {
+    pack_keeps_order :: {
+        push_i 1
+        push_s "two"
+        pack 2
+        f_stop
+    } = Ok(Value::Tuple(vec![Value::Integer(1), Value::String("two".into())].into())),
+}
+
+test_bytecode_execution! {
+    field_access :: {
+        push_i 1
+        push_i 2
+        push_i 3
+        pack 3
+        field 1
+        f_stop
+    } = Ok(Value::Integer(2)),
+}
+
+#[test]
+fn field_out_of_bounds() {
+    let rslt = run_bytecode! {
+        push_i 1
+        pack 1
+        field 1
+        f_stop
+    };
+
+    assert!(rslt.is_err());
+}
+
+test_bytecode_execution! {
+    new_array_keeps_order :: {
+        push_i 1
+        push_i 2
+        push_i 3
+        new_array 3
+        f_stop
+    } = Ok(Value::Array(ArrayRef::alloc(vec![
+        Value::Integer(1),
+        Value::Integer(2),
+        Value::Integer(3),
+    ]))),
+}
+
+test_bytecode_execution! {
+    index_reads_element :: {
+        push_i 10
+        push_i 20
+        new_array 2
+        push_i 1
+        index
+        f_stop
+    } = Ok(Value::Integer(20)),
+}
+
+test_bytecode_execution! {
+    store_index_writes_shared_array :: {
+        push_i 10
+        push_i 20
+        new_array 2
+        push_cpy 0
+        push_i 0
+        push_i 30
+        store_index
+        pop 1
+        f_stop
+    } = Ok(Value::Array(ArrayRef::alloc(vec![
+        Value::Integer(30),
+        Value::Integer(20),
+    ]))),
+}
+
+#[test]
+fn index_out_of_bounds() {
+    let rslt = run_bytecode! {
+        push_i 10
+        new_array 1
+        push_i -1
+        index
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Index out of bounds: the length is 1 but the index is -1"
+    );
+}
+
+test_bytecode_execution! {
+    len_counts_characters :: {
+        push_s "héllo"
+        len
+        f_stop
+    } = Ok(Value::Integer(5)),
+}
+
+test_bytecode_execution! {
+    substr_extracts_characters :: {
+        push_s "héllo"
+        push_i 1
+        push_i 3
+        substr
+        f_stop
+    } = Ok(Value::String("éll".into())),
+}
+
+test_bytecode_execution! {
+    char_at_extracts_character :: {
+        push_s "héllo"
+        push_i 1
+        char_at
+        f_stop
+    } = Ok(Value::String("é".into())),
+}
+
+test_bytecode_execution! {
+    len_counts_elements :: {
+        push_i 10
+        push_i 20
+        new_array 2
+        len
+        f_stop
+    } = Ok(Value::Integer(2)),
+}
+
+test_bytecode_execution! {
+    push_back_appends_to_shared_array :: {
+        push_i 10
+        new_array 1
+        push_cpy 0
+        push_i 20
+        push_back
+        f_stop
+    } = Ok(Value::Array(ArrayRef::alloc(vec![
+        Value::Integer(10),
+        Value::Integer(20),
+    ]))),
+}
+
+test_bytecode_execution! {
+    pop_back_removes_last_element :: {
+        push_i 10
+        push_i 20
+        new_array 2
+        push_cpy 0
+        pop_back
+        pop 1
+        push_cpy 0
+        len
+        pop_cpy 1
+        f_stop
+    } = Ok(Value::Integer(1)),
+}
+
+#[test]
+fn pop_back_empty_array() {
+    let rslt = run_bytecode! {
+        new_array 0
+        pop_back
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Cannot remove an element from an empty array"
+    );
+}
+
+#[test]
+fn substr_out_of_bounds() {
+    let rslt = run_bytecode! {
+        push_s "abc"
+        push_i 2
+        push_i 2
+        substr
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Substring out of bounds: the length is 3 but the substring spans 2..4"
+    );
+}
+
+#[test]
+fn char_at_out_of_bounds() {
+    let rslt = run_bytecode! {
+        push_s "abc"
+        push_i 3
+        char_at
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Index out of bounds: the length is 3 but the index is 3"
+    );
+}
+
+#[test]
+fn index_non_array() {
+    let rslt = run_bytecode! {
+        push_i 10
+        push_i 0
+        index
+        f_stop
+    };
+
+    assert!(rslt.is_err());
+}
+
+test_bytecode_execution! {
+    make_closure_captures_values :: {
+            push_i 1
+            push_i 2
+            make_closure ADD 2 1
+            f_stop
+
+        ADD:
+            push_i 0
+            f_stop
+    } = Ok(Value::Closure(Closure {
+        addr: 4,
+        env: vec![Value::Integer(1), Value::Integer(2)].into(),
+        arity: 1,
+    })),
+}
+
+test_bytecode_execution! {
+    call_closure_passes_captures_first :: {
+            res_v 1
+            push_i 40
+            make_closure SUB 1 1
+            push_i 2
+            call_closure 1
+            f_stop
+
+        // Computes `captured - arg`.
+        SUB:
+            push_cpy 1
+            neg
+            push_cpy 3
+            add_i
+            pop_cpy 4
+            ret 3 0
+    } = Ok(Value::Integer(38)),
+}
+
+#[test]
+fn call_closure_checks_arity() {
+    let rslt = run_bytecode! {
+            res_v 1
+            make_closure F 0 2
+            push_i 1
+            call_closure 1
+            f_stop
+
+        F:
+            f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Wrong number of arguments for closure: expected 2, found 1"
+    );
+}
+
+#[test]
+fn call_closure_rejects_non_closures() {
+    let rslt = run_bytecode! {
+        res_v 1
+        push_i 0
+        call_closure 0
+        f_stop
+    };
+
+    assert!(rslt.is_err());
+}
+
+test_bytecode_execution! {
+    add_i_wraps_around :: {
+        push_i 2147483647
+        push_i 1
+        add_i
+        f_stop
+    } = Ok(Value::Integer(-2147483648)),
+}
+
 test_bytecode_execution! {
     typical_expr_execution :: {
         res_v 1
@@ -346,3 +1260,104 @@ test_bytecode_execution! {
         f_stop
     } = Ok(Value::Integer(101)),
 }
+
+test_bytecode_execution! {
+    check_frame_finds_return_address :: {
+            res_v 1
+            call F
+            f_stop
+
+        F:
+            push_i 1
+            check_frame 1
+            pop_cpy 2
+            ret 1 0
+    } = Ok(Value::Integer(1)),
+}
+
+#[test]
+fn check_frame_fails_elsewhere() {
+    let rslt = run_bytecode! {
+        push_i 1
+        push_i 2
+        check_frame 1
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Stack frame mismatch: expected a return address at offset 1, found `1`"
+    );
+}
+
+test_bytecode_execution! {
+    check_overflow_leaves_the_operands :: {
+        push_i 2147483646
+        push_i 1
+        check_overflow Add
+        add_i
+        f_stop
+    } = Ok(Value::Integer(i32::MAX)),
+}
+
+#[test]
+fn check_overflow_fails_on_overflow() {
+    let rslt = run_bytecode! {
+        push_i 65536
+        push_i 32768
+        check_overflow Mul
+        mul
+        f_stop
+    };
+
+    assert_eq!(
+        rslt.unwrap_err().root_cause().to_string(),
+        "Arithmetic overflow: `65536 * 32768`"
+    );
+}
+
+#[test]
+fn push_const_pushes_pool_entries() {
+    let instrs = generate_bytecode! {
+        push_const 1
+        push_const 0
+        push_const 2
+        pack 3
+        f_stop
+    };
+    let mut constants = ConstantPool::default();
+    constants.add(Constant::String("a".to_owned())).unwrap();
+    constants.add(Constant::Integer(-4)).unwrap();
+    constants.add(Constant::Float(0.5)).unwrap();
+
+    let value = Interpreter::from_instructions(instrs)
+        .with_constants(constants)
+        .run()
+        .unwrap();
+
+    assert_eq!(value.to_string(), "(-4, a, 0.5)");
+}
+
+#[test]
+fn push_const_fails_on_unknown_constants() {
+    let instrs = generate_bytecode! {
+        push_const 0
+        f_stop
+    };
+
+    let err = Interpreter::from_instructions(instrs).run().unwrap_err();
+
+    assert_eq!(err.to_string(), "Unknown constant: `0`");
+}
+
+#[test]
+fn load_local_fails_out_of_the_stack() {
+    for instrs in [
+        generate_bytecode! { push_i 1 load_local 1 f_stop },
+        generate_bytecode! { push_i 1 load_local -1 f_stop },
+    ] {
+        let err = Interpreter::from_instructions(instrs).run().unwrap_err();
+
+        assert_eq!(err.root_cause().to_string(), "Out-of-bound stack access");
+    }
+}
@@ -1,14 +1,20 @@
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
+    sync::{Arc, RwLock},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Context, Result};
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Value {
     Integer(i32),
+    Float(f64),
     InstructionPointer(u32),
+    String(Arc<str>),
+    Tuple(Arc<[Value]>),
+    Array(ArrayRef),
+    Closure(Closure),
 }
 
 impl Value {
@@ -22,6 +28,56 @@ impl Value {
         }
     }
 
+    pub(crate) fn try_into_float(self) -> Result<f64> {
+        match self {
+            Value::Float(val) => Ok(val),
+            anything => bail!(ValueConversionError {
+                expected_type: Type::Float,
+                found_value: anything,
+            }),
+        }
+    }
+
+    pub(crate) fn try_into_string(self) -> Result<Arc<str>> {
+        match self {
+            Value::String(s) => Ok(s),
+            anything => bail!(ValueConversionError {
+                expected_type: Type::String,
+                found_value: anything,
+            }),
+        }
+    }
+
+    pub(crate) fn try_into_tuple(self) -> Result<Arc<[Value]>> {
+        match self {
+            Value::Tuple(values) => Ok(values),
+            anything => bail!(ValueConversionError {
+                expected_type: Type::Tuple,
+                found_value: anything,
+            }),
+        }
+    }
+
+    pub(crate) fn try_into_array(self) -> Result<ArrayRef> {
+        match self {
+            Value::Array(array) => Ok(array),
+            anything => bail!(ValueConversionError {
+                expected_type: Type::Array,
+                found_value: anything,
+            }),
+        }
+    }
+
+    pub(crate) fn try_into_closure(self) -> Result<Closure> {
+        match self {
+            Value::Closure(closure) => Ok(closure),
+            anything => bail!(ValueConversionError {
+                expected_type: Type::Closure,
+                found_value: anything,
+            }),
+        }
+    }
+
     pub(crate) fn try_into_instruction_pointer(self) -> Result<u32> {
         match self {
             Value::InstructionPointer(ip) => Ok(ip),
@@ -35,7 +91,12 @@ impl Value {
     fn type_(&self) -> Type {
         match self {
             Value::Integer(_) => Type::Integer,
+            Value::Float(_) => Type::Float,
             Value::InstructionPointer(_) => Type::InstructionPointer,
+            Value::String(_) => Type::String,
+            Value::Tuple(_) => Type::Tuple,
+            Value::Array(_) => Type::Array,
+            Value::Closure(_) => Type::Closure,
         }
     }
 }
@@ -44,22 +105,147 @@ impl Display for Value {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
             Value::InstructionPointer(ip) => write!(f, "*{}*", ip),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", value)?;
+                }
+
+                if values.len() == 1 {
+                    write!(f, ",")?;
+                }
+
+                write!(f, ")")
+            }
+            Value::Array(array) => array.fmt(f),
+            Value::Closure(closure) => write!(f, "<closure at {}>", closure.addr),
         }
     }
 }
 
+/// A function along with the values it captured when it was created.
+///
+/// The captured values are copies: changing a variable after a closure
+/// captured it has no effect on the closure.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Closure {
+    pub(crate) addr: u32,
+    pub(crate) env: Arc<[Value]>,
+    pub(crate) arity: u16,
+}
+
+/// A reference to an array allocated on the heap.
+///
+/// Cloning an `ArrayRef` does not copy the underlying array: every clone
+/// refers to the same storage.
+#[derive(Clone, Debug)]
+pub(crate) struct ArrayRef(Arc<RwLock<Vec<Value>>>);
+
+impl ArrayRef {
+    pub(crate) fn alloc(values: Vec<Value>) -> ArrayRef {
+        ArrayRef(Arc::new(RwLock::new(values)))
+    }
+
+    pub(crate) fn values(&self) -> Vec<Value> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub(crate) fn get(&self, idx: i32) -> Result<Value> {
+        let values = self.0.read().unwrap();
+        let idx = checked_index(idx, values.len())?;
+
+        Ok(values[idx].clone())
+    }
+
+    pub(crate) fn set(&self, idx: i32, value: Value) -> Result<()> {
+        let mut values = self.0.write().unwrap();
+        let idx = checked_index(idx, values.len())?;
+
+        values[idx] = value;
+
+        Ok(())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.read().unwrap().len()
+    }
+
+    pub(crate) fn push(&self, value: Value) {
+        self.0.write().unwrap().push(value);
+    }
+
+    pub(crate) fn pop(&self) -> Result<Value> {
+        self.0
+            .write()
+            .unwrap()
+            .pop()
+            .context("Cannot remove an element from an empty array")
+    }
+}
+
+/// Checks that an index points to one of the `len` elements of a sequence.
+pub(crate) fn checked_index(idx: i32, len: usize) -> Result<usize> {
+    ensure!(
+        0 <= idx && (idx as usize) < len,
+        "Index out of bounds: the length is {} but the index is {}",
+        len,
+        idx,
+    );
+
+    Ok(idx as usize)
+}
+
+impl PartialEq for ArrayRef {
+    fn eq(&self, other: &ArrayRef) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.values() == other.values()
+    }
+}
+
+impl Display for ArrayRef {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (idx, value) in self.0.read().unwrap().iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{}", value)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Type {
     Integer,
+    Float,
     InstructionPointer,
+    String,
+    Tuple,
+    Array,
+    Closure,
 }
 
 impl Display for Type {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             Type::Integer => write!(f, "integer"),
+            Type::Float => write!(f, "float"),
             Type::InstructionPointer => write!(f, "instruction pointer"),
+            Type::String => write!(f, "string"),
+            Type::Tuple => write!(f, "tuple"),
+            Type::Array => write!(f, "array"),
+            Type::Closure => write!(f, "closure"),
         }
     }
 }